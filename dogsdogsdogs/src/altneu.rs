@@ -10,8 +10,17 @@
 //! coordinate equals the computed join. That may be the minimum
 //! element of the second lattice, if neither first element equals
 //! the join.
+//!
+//! Delta queries are assembled in a scope with these timestamps, which
+//! `delta_query` creates. Collections are brought into the scope with
+//! `enter_alt` and `enter_neu`, and within the scope may be delayed
+//! to `neu` times with `neu`.
 
+use timely::dataflow::Scope;
+use timely::dataflow::scopes::Child;
 
+use differential_dataflow::{Data, Collection};
+use differential_dataflow::difference::Monoid;
 
 /// A pair of timestamps, partially ordered by the product order.
 #[derive(Debug, Hash, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Abomonation, Serialize, Deserialize)]
@@ -98,3 +107,76 @@ impl<T: Lattice> Lattice for AltNeu<T> {
         AltNeu { time, neu }
     }
 }
+
+// Helpers for constructing delta queries.
+//
+// A delta query is assembled in a scope with `AltNeu` timestamps. Collections enter the scope at `alt`
+// times, and the arrangements a delta query reads from "after" its own changes are delayed to `neu` times.
+
+/// A scope whose timestamps order delta queries.
+pub type DeltaScope<'a, G> = Child<'a, G, AltNeu<<G as ::timely::dataflow::scopes::ScopeParent>::Timestamp>>;
+
+/// Assembles a delta query in a new scope with `AltNeu` timestamps, and returns its results to `scope`.
+///
+/// The collection `logic` returns is brought out of the nested scope; collections used in the query are
+/// brought in with `enter_alt` and `enter_neu`.
+pub fn delta_query<G, D, R, F>(scope: &G, name: &str, logic: F) -> Collection<G, D, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice,
+    D: Data,
+    R: Monoid,
+    for<'a> F: FnOnce(&mut DeltaScope<'a, G>)->Collection<DeltaScope<'a, G>, D, R>,
+{
+    let mut scope = scope.clone();
+    scope.scoped::<AltNeu<G::Timestamp>,_,_>(name, |inner| logic(inner).leave())
+}
+
+/// Methods for bringing collections into delta query scopes.
+pub trait EnterAltNeu<G: Scope, D: Data, R: Monoid> where G::Timestamp: Lattice {
+    /// Brings the collection into the scope with its updates at `alt` times.
+    ///
+    /// The delta query of a relation observes those relations whose indexes are at `alt` times including
+    /// changes simultaneous with those of the relation.
+    fn enter_alt<'a>(&self, child: &DeltaScope<'a, G>) -> Collection<DeltaScope<'a, G>, D, R>;
+    /// Brings the collection into the scope with its updates at `neu` times.
+    ///
+    /// The delta query of a relation observes those relations whose indexes are at `neu` times only as of
+    /// changes strictly before those of the relation.
+    fn enter_neu<'a>(&self, child: &DeltaScope<'a, G>) -> Collection<DeltaScope<'a, G>, D, R>;
+}
+
+impl<G: Scope, D: Data, R: Monoid> EnterAltNeu<G, D, R> for Collection<G, D, R> where G::Timestamp: Lattice {
+    fn enter_alt<'a>(&self, child: &DeltaScope<'a, G>) -> Collection<DeltaScope<'a, G>, D, R> {
+        self.enter(child)
+    }
+    fn enter_neu<'a>(&self, child: &DeltaScope<'a, G>) -> Collection<DeltaScope<'a, G>, D, R> {
+        self.enter(child).neu()
+    }
+}
+
+/// Methods for collections in delta query scopes.
+pub trait AltNeuCollection<G: Scope, T: Timestamp+Lattice, D: Data, R: Monoid> {
+    /// Delays the updates of the collection from `alt` to `neu` times.
+    fn neu(&self) -> Collection<G, D, R>;
+    /// Panics if the collection has an update at a `neu` time.
+    ///
+    /// Changes that drive delta queries must be at `alt` times, as those at `neu` times would not observe
+    /// the changes to relations before them.
+    fn assert_alt(&self) -> Collection<G, D, R>;
+}
+
+impl<G, T, D, R> AltNeuCollection<G, T, D, R> for Collection<G, D, R>
+where
+    G: Scope<Timestamp=AltNeu<T>>,
+    T: Timestamp+Lattice,
+    D: Data,
+    R: Monoid,
+{
+    fn neu(&self) -> Collection<G, D, R> {
+        self.delay(|time| AltNeu::neu(time.time.clone()))
+    }
+    fn assert_alt(&self) -> Collection<G, D, R> {
+        self.inspect(|x| assert!(!(x.1).neu, "delta query changes at a neu time: {:?}", x))
+    }
+}
@@ -0,0 +1,197 @@
+//! Extension of prefixes constrained by the absence of pairs from a relation.
+//!
+//! An `AntiExtender` validates proposed extensions `(prefix, value)` only if the pair of the prefix's key
+//! and the value is absent from a relation, as of the time of the extension. This responds correctly to
+//! changes in the extensions, but a delta query must also respond to changes in the negated relation: if
+//! a pair is added to the relation, results whose extensions it matches must be retracted, and if a pair
+//! is removed, such results must be introduced.
+//!
+//! The query `Q := P, not N` is `Q = P - (P, N')`, where `N'` is the presence of pairs in `N` with
+//! multiplicity one. Its changes are those of `P` validated by an anti-extender on `N`, and the negation of
+//! the changes of `N'` extended by `P` as of times strictly before them, as for any delta query. The
+//! `presence` method provides the changes of `N'` to drive the latter query.
+
+use std::rc::Rc;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use timely::PartialOrder;
+use timely::dataflow::Scope;
+use timely::dataflow::channels::pact::{Pipeline, Exchange};
+use timely::dataflow::operators::Operator;
+use timely::progress::Timestamp;
+
+use timely_sort::Unsigned;
+
+use differential_dataflow::{Data, Collection, AsCollection, Hashable};
+use differential_dataflow::difference::Monoid;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Threshold;
+use differential_dataflow::operators::arrange::ArrangeBySelf;
+use differential_dataflow::trace::{Cursor, TraceReader, BatchReader};
+
+use {TraceKeyHandle, lower_bound};
+
+/// An index of the presence of pairs in a relation.
+pub struct AntiIndex<K, V, T>
+where
+    K: Data,
+    V: Data,
+    T: Lattice+Data,
+{
+    /// A trace of type ((K, V), ()), with multiplicity one for each present pair.
+    presence_trace: TraceKeyHandle<(K, V), T, isize>,
+}
+
+impl<K, V, T> Clone for AntiIndex<K, V, T>
+where
+    K: Data+Hash,
+    V: Data+Hash,
+    T: Lattice+Data+Timestamp,
+{
+    fn clone(&self) -> Self {
+        AntiIndex {
+            presence_trace: self.presence_trace.clone(),
+        }
+    }
+}
+
+impl<K, V, T> AntiIndex<K, V, T>
+where
+    K: Data+Hash,
+    V: Data+Hash,
+    T: Lattice+Data+Timestamp,
+{
+    /// Indexes the pairs present in `collection`, regardless of their multiplicities.
+    pub fn index<G: Scope<Timestamp = T>, R: Monoid>(collection: &Collection<G, (K, V), R>) -> Self {
+        AntiIndex {
+            presence_trace: collection.distinct().arrange_by_self().trace,
+        }
+    }
+
+    /// The changes to the presence of pairs, each with multiplicity one while present.
+    pub fn presence<G: Scope<Timestamp = T>>(&mut self, scope: &G) -> Collection<G, (K, V), isize> {
+        self.presence_trace
+            .import(scope)
+            .as_collection(|pair, &()| pair.clone())
+    }
+
+    /// Validates extensions of prefixes whose key, determined by `logic`, is not paired with the extension.
+    pub fn anti_extend_using<P, F: Fn(&P)->K>(&self, logic: F) -> AntiExtender<K, V, T, P, F> {
+        AntiExtender {
+            phantom: std::marker::PhantomData,
+            index: self.clone(),
+            key_selector: Rc::new(logic),
+        }
+    }
+}
+
+/// Validates extensions absent from a relation.
+pub struct AntiExtender<K, V, T, P, F>
+where
+    K: Data,
+    V: Data,
+    T: Lattice+Data,
+    F: Fn(&P)->K,
+{
+    phantom: std::marker::PhantomData<P>,
+    index: AntiIndex<K, V, T>,
+    key_selector: Rc<F>,
+}
+
+impl<K, V, T, P, F> AntiExtender<K, V, T, P, F>
+where
+    K: Data+Hash,
+    V: Data+Hash,
+    T: Lattice+Data+Timestamp,
+    P: Data,
+    F: Fn(&P)->K+'static,
+{
+    /// Restricts extensions to those whose key and value are absent from the relation.
+    pub fn validate<G, R>(&mut self, extensions: &Collection<G, (P, V), R>) -> Collection<G, (P, V), R>
+    where
+        G: Scope<Timestamp = T>,
+        R: Monoid,
+    {
+        let presence = self.index.presence_trace.import(&extensions.scope());
+        let mut presence_trace = Some(presence.trace.clone());
+
+        let mut stash = HashMap::new();
+        let logic1 = self.key_selector.clone();
+        let logic2 = self.key_selector.clone();
+
+        let mut buffer1 = Vec::new();
+        let mut buffer2 = Vec::new();
+
+        let exchange = Exchange::new(move |update: &((P,V),G::Timestamp,R)|
+            (logic1(&(update.0).0).clone(), ((update.0).1).clone()).hashed().as_u64()
+        );
+
+        extensions.inner.binary_frontier(&presence.stream, exchange, Pipeline, "AntiValidate", move |_,_| move |input1, input2, output| {
+
+            // drain the first input, stashing requests.
+            input1.for_each(|capability, data| {
+                data.swap(&mut buffer1);
+                stash.entry(capability.retain())
+                     .or_insert(Vec::new())
+                     .extend(buffer1.drain(..))
+            });
+
+            // advance the `distinguish_since` frontier to allow all merges.
+            input2.for_each(|_, batches| {
+                batches.swap(&mut buffer2);
+                for batch in buffer2.drain(..) {
+                    if let Some(ref mut trace) = presence_trace {
+                        trace.distinguish_since(batch.upper());
+                    }
+                }
+            });
+
+            if let Some(ref mut trace) = presence_trace {
+
+                for (capability, prefixes) in stash.iter_mut() {
+
+                    // defer requests at incomplete times.
+                    // NOTE: not all updates may be at complete times, but if this test fails then none of them are.
+                    if !input2.frontier.less_equal(capability.time()) {
+
+                        let mut session = output.session(capability);
+
+                        // sort requests for in-order cursor traversal.
+                        prefixes.sort_by(|x,y| (logic2(&(x.0).0), &((x.0).1)).cmp(&(logic2(&(y.0).0), &((y.0).1))));
+
+                        let (mut cursor, storage) = trace.cursor();
+
+                        for &mut (ref prefix, ref time, ref mut diff) in prefixes.iter_mut() {
+                            if !input2.frontier.less_equal(time) {
+                                let key = (logic2(&prefix.0), (prefix.1).clone());
+                                let mut count = 0;
+                                if cursor.probe_key(&storage, &key) {
+                                    cursor.map_times(&storage, |t, d| if t.less_equal(time) { count += d; });
+                                }
+                                if count == 0 {
+                                    session.give((prefix.clone(), time.clone(), diff.clone()));
+                                }
+                                *diff = R::zero();
+                            }
+                        }
+
+                        prefixes.retain(|ptd| !ptd.2.is_zero());
+                    }
+                }
+            }
+
+            // drop fully processed capabilities.
+            stash.retain(|_,prefixes| !prefixes.is_empty());
+
+            // advance the consolidation frontier, retaining the times of stashed requests.
+            let frontier = lower_bound(input1.frontier().frontier(), stash.keys().map(|capability| capability.time()));
+            presence_trace.as_mut().map(|trace| trace.advance_by(frontier.elements()));
+
+            if input1.frontier().is_empty() && stash.is_empty() {
+                presence_trace = None;
+            }
+
+        }).as_collection()
+    }
+}
@@ -0,0 +1,240 @@
+//! A builder for worst-case optimal delta queries over binary relations.
+//!
+//! A query relates attributes, numbered from zero, by binary relations: each relation is a collection of
+//! pairs, and is associated with the two attributes its pairs bind. The query's results are the tuples
+//! of attribute values, indexed by attribute, for which each relation contains the pair of values of its
+//! attributes. The triangles query
+//!
+//!     triangles(a,b,c) := edge(a,b), edge(b,c), edge(a,c)
+//!
+//! has attributes `a`, `b`, and `c` numbered `0`, `1`, and `2`, and three uses of the `edge` relation,
+//! associated with attributes `(0, 1)`, `(1, 2)`, and `(0, 2)`.
+//!
+//! The `GenericJoin` builder constructs a delta query for each relation, driven by the changes to that
+//! relation, which binds the remaining attributes one at a time in the supplied order. Each attribute is
+//! bound by count-propose-validate extension, using each relation that relates the attribute to one bound
+//! before it. The delta queries are ordered with `AltNeu` timestamps, so that simultaneous changes to
+//! several relations are not derived more than once: the delta query of a relation sees the relations
+//! before it including changes simultaneous with its own, and the relations after it only as of changes
+//! strictly before its own.
+//!
+//! Each relation is maintained in four indexes, by each of its attributes and in each of the two orders,
+//! and there are no other arrangements: the memory required is proportional to the size of the input.
+//! The `delta_queries` function accepts inputs and constraints on them separately, so that an input
+//! used in several places in a query is indexed only once.
+
+use std::hash::Hash;
+use std::ops::Mul;
+
+use timely::dataflow::Scope;
+use timely::dataflow::operators::Concatenate;
+use timely::progress::Timestamp;
+
+use differential_dataflow::{Data, Collection, AsCollection};
+use differential_dataflow::difference::Monoid;
+use differential_dataflow::lattice::Lattice;
+
+use altneu::{delta_query, AltNeuCollection, EnterAltNeu};
+use {CollectionIndex, PrefixExtender, ProposeExtensionMethod};
+
+/// A builder for a worst-case optimal join of binary relations.
+pub struct GenericJoin<G: Scope, V: Data, R: Monoid+Mul<Output = R>> {
+    relations: Vec<(Collection<G, (V, V), R>, (usize, usize))>,
+}
+
+impl<G, V, R> GenericJoin<G, V, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data+Timestamp,
+    V: Data+Hash,
+    R: Monoid+Mul<Output = R>,
+{
+    /// Creates a builder with no relations.
+    pub fn new() -> Self {
+        GenericJoin { relations: Vec::new() }
+    }
+
+    /// Adds a relation whose pairs bind the two attributes of `attributes`.
+    pub fn relation(mut self, collection: &Collection<G, (V, V), R>, attributes: (usize, usize)) -> Self {
+        self.relations.push((collection.clone(), attributes));
+        self
+    }
+
+    /// Constructs the join, binding attributes in the order of `order`.
+    ///
+    /// The results are tuples indexed by attribute. Each attribute must occur in `order`, and each
+    /// attribute must be related to one before it in `order` by some relation; other than the two
+    /// attributes of the changed relation, attributes are bound in the order they occur.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    /// extern crate dogsdogsdogs;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use dogsdogsdogs::generic::GenericJoin;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let edges = scope.new_collection_from(vec![(0u32, 1u32), (1, 2), (0, 2), (2, 3)]).1;
+    ///         let expected = scope.new_collection_from(vec![vec![0u32, 1, 2]]).1;
+    ///
+    ///         // triangles(a,b,c) := edge(a,b), edge(b,c), edge(a,c)
+    ///         GenericJoin::new()
+    ///             .relation(&edges, (0, 1))
+    ///             .relation(&edges, (1, 2))
+    ///             .relation(&edges, (0, 2))
+    ///             .build(&[0, 1, 2])
+    ///             .assert_eq(&expected);
+    ///     });
+    /// }
+    /// ```
+    pub fn build(&self, order: &[usize]) -> Collection<G, Vec<V>, R> {
+        let inputs = self.relations.iter().map(|x| x.0.clone()).collect::<Vec<_>>();
+        let constraints = self.relations.iter().enumerate().map(|(index, x)| (index, x.1)).collect::<Vec<_>>();
+        delta_queries(&inputs[..], &constraints[..], order)
+    }
+}
+
+/// Constructs the delta queries of a join of binary relations, sharing indexes among uses of each input.
+///
+/// Each constraint `(input, (src, dst))` requires that `inputs[input]` contain the pair of values of the
+/// attributes `src` and `dst`. There is a delta query for each constraint, driven by the changes to its
+/// input, and the indexes of each input are shared by all constraints on it; an input used in several
+/// constraints, like the edges of a graph in a cyclic query, is indexed only four times. The results are
+/// tuples indexed by attribute, and attributes are bound in the order of `order`, as for `GenericJoin`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+/// extern crate dogsdogsdogs;
+///
+/// use differential_dataflow::input::Input;
+/// use dogsdogsdogs::generic::delta_queries;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1u32), (1, 2), (2, 3), (3, 0), (0, 2)]).1;
+///         let expected = scope.new_collection_from(vec![vec![0u32, 1, 2, 3]]).1;
+///
+///         // four-cycles(a,b,c,d) := edge(a,b), edge(b,c), edge(c,d), edge(d,a)
+///         let constraints = vec![(0, (0, 1)), (0, (1, 2)), (0, (2, 3)), (0, (3, 0))];
+///         delta_queries(&[edges], &constraints[..], &[0, 1, 2, 3])
+///             .filter(|x| x[0] == 0)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn delta_queries<G, V, R>(inputs: &[Collection<G, (V, V), R>], constraints: &[(usize, (usize, usize))], order: &[usize]) -> Collection<G, Vec<V>, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data+Timestamp,
+    V: Data+Hash,
+    R: Monoid+Mul<Output = R>,
+{
+    assert!(!constraints.is_empty(), "GenericJoin: no relations to join");
+
+    for (index, &(input, attributes)) in constraints.iter().enumerate() {
+        assert!(input < inputs.len(), "GenericJoin: constraint on absent input {}", input);
+        assert!(attributes.0 != attributes.1, "GenericJoin: relation must bind two distinct attributes");
+        for &(_, other) in constraints[.. index].iter() {
+            if other == attributes || other == (attributes.1, attributes.0) {
+                panic!("GenericJoin: more than one relation binds attributes {:?}", attributes);
+            }
+        }
+    }
+
+    let attributes = constraints.iter().map(|x| ::std::cmp::max((x.1).0, (x.1).1)).max().unwrap() + 1;
+    for attribute in 0 .. attributes {
+        assert!(order.contains(&attribute), "GenericJoin: attribute {} is not in the order", attribute);
+    }
+
+    delta_query(&inputs[0].scope(), "DeltaQueries", |inner| {
+
+        // Each input, and its indexes by each attribute as of `alt` and `neu` times.
+        let mut changes = Vec::new();
+        let mut alt = Vec::new();
+        let mut neu = Vec::new();
+        for input in inputs.iter() {
+            let forward = input.enter_alt(inner);
+            let reverse = forward.map(|(x,y)| (y,x));
+            alt.push((
+                CollectionIndex::index(&forward),
+                CollectionIndex::index(&reverse),
+            ));
+            neu.push((
+                CollectionIndex::index(&forward.neu()),
+                CollectionIndex::index(&reverse.neu()),
+            ));
+            changes.push(forward);
+        }
+
+        let mut results = Vec::new();
+        for (index, &(input, (first, second))) in constraints.iter().enumerate() {
+
+            // Attributes in the order they are bound, and the prefixes of their values.
+            let mut bound = vec![first, second];
+            let mut prefixes = changes[input].map(|(x,y)| vec![x, y]);
+
+            for &attribute in order.iter() {
+                if !bound.contains(&attribute) {
+
+                    // Each constraint relating the attribute to a bound attribute proposes or validates values.
+                    let mut extenders = Vec::new();
+                    for (other, &(input, (src, dst))) in constraints.iter().enumerate() {
+                        if other != index {
+                            let indices = if other < index { &alt[input] } else { &neu[input] };
+                            let choice =
+                            if dst == attribute && bound.contains(&src) { Some((&indices.0, src)) }
+                            else if src == attribute && bound.contains(&dst) { Some((&indices.1, dst)) }
+                            else { None };
+
+                            if let Some((relation, key)) = choice {
+                                let position = bound.iter().position(|x| x == &key).unwrap();
+                                extenders.push(relation.extend_using(move |prefix: &Vec<V>| prefix[position].clone()));
+                            }
+                        }
+                    }
+
+                    if extenders.is_empty() {
+                        panic!("GenericJoin: attribute {} is not related to an attribute before it", attribute);
+                    }
+
+                    let mut extenders =
+                    extenders
+                        .iter_mut()
+                        .map(|x| x as &mut PrefixExtender<_, R, Prefix=Vec<V>, Extension=V>)
+                        .collect::<Vec<_>>();
+
+                    prefixes =
+                    prefixes
+                        .extend(&mut extenders[..])
+                        .map(|(mut prefix, value)| { prefix.push(value); prefix });
+
+                    bound.push(attribute);
+                }
+            }
+
+            // Arrange values by attribute.
+            results.push(
+                prefixes
+                    .map(move |prefix| {
+                        let mut tuple = prefix.clone();
+                        for (position, &attribute) in bound.iter().enumerate() {
+                            tuple[attribute] = prefix[position].clone();
+                        }
+                        tuple
+                    })
+                    .inner
+            );
+        }
+
+        inner.concatenate(results).as_collection()
+    })
+}
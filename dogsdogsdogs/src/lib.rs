@@ -18,6 +18,7 @@ use timely::dataflow::Scope;
 use timely::dataflow::channels::pact::{Pipeline, Exchange};
 use timely::dataflow::operators::Operator;
 use timely::progress::Timestamp;
+use timely::progress::frontier::Antichain;
 use timely::dataflow::operators::Partition;
 use timely::dataflow::operators::Concatenate;
 
@@ -34,6 +35,10 @@ use differential_dataflow::trace::implementations::spine_fueled::Spine;
 use differential_dataflow::trace::implementations::ord::{OrdValBatch, OrdKeyBatch};
 
 pub mod altneu;
+pub mod anti;
+pub mod generic;
+pub mod registry;
+pub mod motifs;
 
 /// A type capable of extending a stream of prefixes.
 ///
@@ -102,11 +107,36 @@ impl<G: Scope, R: Monoid+Mul<Output = R>, P, E> ValidateExtensionMethod<G, R, P,
     }
 }
 
+/// The antichain of lower bounds of `frontier` and `times`.
+///
+/// Stashed requests retain their times after the input frontier has passed them, and traces must not be
+/// compacted beyond those times. With partially ordered times, for example in bitemporal dataflows, the
+/// lower bound may have several elements, and the trace is compacted only to times greater or equal to
+/// one of them.
+fn lower_bound<'a, T, I>(frontier: &[T], times: I) -> Antichain<T>
+where
+    T: PartialOrder+Clone+'a,
+    I: Iterator<Item=&'a T>,
+{
+    let mut lower = Antichain::new();
+    for time in frontier.iter() {
+        lower.insert(time.clone());
+    }
+    for time in times {
+        lower.insert(time.clone());
+    }
+    lower
+}
+
 // These are all defined here so that users can be assured a common layout.
-type TraceValSpine<K,V,T,R> = Spine<K, V, T, R, Rc<OrdValBatch<K,V,T,R>>>;
-type TraceValHandle<K,V,T,R> = TraceAgent<K, V, T, R, TraceValSpine<K,V,T,R>>;
-type TraceKeySpine<K,T,R> = Spine<K, (), T, R, Rc<OrdKeyBatch<K,T,R>>>;
-type TraceKeyHandle<K,T,R> = TraceAgent<K, (), T, R, TraceKeySpine<K,T,R>>;
+/// The spine of traces of keyed values used by indexes.
+pub type TraceValSpine<K,V,T,R> = Spine<K, V, T, R, Rc<OrdValBatch<K,V,T,R>>>;
+/// A handle to a trace of keyed values used by indexes.
+pub type TraceValHandle<K,V,T,R> = TraceAgent<K, V, T, R, TraceValSpine<K,V,T,R>>;
+/// The spine of traces of keys used by indexes.
+pub type TraceKeySpine<K,T,R> = Spine<K, (), T, R, Rc<OrdKeyBatch<K,T,R>>>;
+/// A handle to a trace of keys used by indexes.
+pub type TraceKeyHandle<K,T,R> = TraceAgent<K, (), T, R, TraceKeySpine<K,T,R>>;
 
 pub struct CollectionIndex<K, V, T, R>
 where
@@ -167,11 +197,110 @@ where
             validate_trace: validate,
         }
     }
+    /// Assembles an index from traces maintained elsewhere, for example by a long-lived dataflow serving indexes.
+    ///
+    /// The traces must have the layout `index` produces: `count_trace` has each key with multiplicity the number
+    /// of distinct values paired with it, `propose_trace` has the pairs of the relation by key, and `validate_trace`
+    /// has the pairs of the relation as keys. The traces need not belong to the dataflows that use the index, as
+    /// extenders import them into the scopes of the prefixes they extend.
+    pub fn from_traces(
+        count_trace: TraceKeyHandle<K, T, isize>,
+        propose_trace: TraceValHandle<K, V, T, R>,
+        validate_trace: TraceKeyHandle<(K, V), T, R>,
+    ) -> Self {
+        CollectionIndex {
+            count_trace,
+            propose_trace,
+            validate_trace,
+        }
+    }
+    /// The indexed relation, imported into `scope`.
+    ///
+    /// An index built by one dataflow can answer queries installed later by other dataflows on the same worker: the
+    /// relation drives a query in the new dataflow, and extenders import the traces of the indexes they use. The
+    /// relation's updates, and the updates the extenders observe, are compacted up to the index's `advance_frontier`,
+    /// and prefixes at times not greater or equal to it should be advanced before they are extended.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    /// extern crate dogsdogsdogs;
+    ///
+    /// use timely::Configuration;
+    /// use differential_dataflow::input::Input;
+    /// use dogsdogsdogs::{CollectionIndex, ProposeExtensionMethod};
+    ///
+    /// fn main() {
+    ///     ::timely::execute(Configuration::Thread, |worker| {
+    ///
+    ///         // a dataflow maintaining an index of edges.
+    ///         let mut index = worker.dataflow::<u32,_,_>(|scope| {
+    ///             let edges = scope.new_collection_from(vec![(0u32, 1u32), (1, 2), (2, 3)]).1;
+    ///             CollectionIndex::index(&edges)
+    ///         });
+    ///
+    ///         worker.step();
+    ///
+    ///         // a later dataflow querying paths of length two.
+    ///         worker.dataflow(|scope| {
+    ///             let expected = scope.new_collection_from(vec![((0u32, 1u32), 2u32), ((1, 2), 3)]).1;
+    ///             let mut extender = index.extend_using(|&(_, dst): &(u32, u32)| dst);
+    ///             index.relation(scope)
+    ///                  .extend(&mut [&mut extender])
+    ///                  .assert_eq(&expected);
+    ///         });
+    ///
+    ///     }).unwrap();
+    /// }
+    /// ```
+    pub fn relation<G: Scope<Timestamp = T>>(&mut self, scope: &G) -> Collection<G, (K, V), R> {
+        self.propose_trace
+            .import(scope)
+            .as_collection(|key, val| (key.clone(), val.clone()))
+    }
+    /// The frontier to which the index's traces may have been compacted.
+    ///
+    /// Updates at times not greater or equal to an element of this frontier may be presented at later times, and
+    /// queries installed against the index should extend prefixes only at times greater or equal to it.
+    pub fn advance_frontier(&mut self) -> Vec<T> {
+        self.propose_trace.advance_frontier().to_vec()
+    }
+    /// Allows the traces to compact updates at times not greater or equal to an element of `frontier`.
+    ///
+    /// Indexes held for use by dataflows not yet installed prevent compaction of their traces until advanced.
+    pub fn advance_by(&mut self, frontier: &[T]) {
+        self.count_trace.advance_by(frontier);
+        self.propose_trace.advance_by(frontier);
+        self.validate_trace.advance_by(frontier);
+    }
+    /// Allows the traces to merge batches whose boundaries are not greater or equal to an element of `frontier`.
+    pub fn distinguish_since(&mut self, frontier: &[T]) {
+        self.count_trace.distinguish_since(frontier);
+        self.propose_trace.distinguish_since(frontier);
+        self.validate_trace.distinguish_since(frontier);
+    }
     pub fn extend_using<P, F: Fn(&P)->K>(&self, logic: F) -> CollectionExtender<K, V, T, R, P, F> {
         CollectionExtender {
             phantom: std::marker::PhantomData,
             indices: self.clone(),
             key_selector: Rc::new(logic),
+            predicate: None,
+        }
+    }
+    /// Extends prefixes only with values satisfying `predicate`.
+    ///
+    /// The predicate is applied as values are read from the index, so that extensions which do not satisfy it
+    /// are neither proposed nor looked up for validation. Counts of extensions are not filtered, and so a
+    /// selective predicate does not make the extender more likely to be chosen to propose.
+    pub fn extend_using_filtered<P, F, PF>(&self, logic: F, predicate: PF) -> CollectionExtender<K, V, T, R, P, F>
+    where F: Fn(&P)->K, PF: Fn(&P, &V)->bool+'static {
+        CollectionExtender {
+            phantom: std::marker::PhantomData,
+            indices: self.clone(),
+            key_selector: Rc::new(logic),
+            predicate: Some(Rc::new(predicate)),
         }
     }
 }
@@ -187,6 +316,8 @@ where
     phantom: std::marker::PhantomData<P>,
     indices: CollectionIndex<K, V, T, R>,
     key_selector: Rc<F>,
+    /// An optional predicate extensions must satisfy.
+    predicate: Option<Rc<Fn(&P, &V)->bool>>,
 }
 
 impl<G, K, V, R, P, F> PrefixExtender<G, R> for CollectionExtender<K, V, G::Timestamp, R, P, F>
@@ -261,8 +392,7 @@ where
                         for &mut ((ref prefix, old_count, old_index), ref time, ref mut diff) in prefixes.iter_mut() {
                             if !input2.frontier.less_equal(time) {
                                 let key = logic2(prefix);
-                                cursor.seek_key(&storage, &key);
-                                if cursor.get_key(&storage) == Some(&key) {
+                                if cursor.probe_key(&storage, &key) {
                                     let mut count = 0;
                                     cursor.map_times(&storage, |t, d| if t.less_equal(time) { count += d; });
                                     // assert!(count >= 0);
@@ -288,8 +418,9 @@ where
             // drop fully processed capabilities.
             stash.retain(|_,prefixes| !prefixes.is_empty());
 
-            // advance the consolidation frontier (TODO: wierd lexicographic times!)
-            counts_trace.as_mut().map(|trace| trace.advance_by(&input1.frontier().frontier()));
+            // advance the consolidation frontier, retaining the times of stashed requests.
+            let frontier = lower_bound(input1.frontier().frontier(), stash.keys().map(|capability| capability.time()));
+            counts_trace.as_mut().map(|trace| trace.advance_by(frontier.elements()));
 
             if input1.frontier().is_empty() && stash.is_empty() {
                 counts_trace = None;
@@ -311,6 +442,7 @@ where
         let mut stash = HashMap::new();
         let logic1 = self.key_selector.clone();
         let logic2 = self.key_selector.clone();
+        let predicate = self.predicate.clone();
 
         let mut buffer1 = Vec::new();
         let mut buffer2 = Vec::new();
@@ -355,14 +487,15 @@ where
                         for &mut (ref prefix, ref time, ref mut diff) in prefixes.iter_mut() {
                             if !input2.frontier.less_equal(time) {
                                 let key = logic2(prefix);
-                                cursor.seek_key(&storage, &key);
-                                if cursor.get_key(&storage) == Some(&key) {
+                                if cursor.probe_key(&storage, &key) {
                                     while let Some(value) = cursor.get_val(&storage) {
-                                        let mut count = R::zero();
-                                        cursor.map_times(&storage, |t, d| if t.less_equal(time) { count += d; });
-                                        let prod = count * diff.clone();
-                                        if !prod.is_zero() {
-                                            session.give(((prefix.clone(), value.clone()), time.clone(), prod));
+                                        if predicate.as_ref().map(|predicate| predicate(prefix, value)).unwrap_or(true) {
+                                            let mut count = R::zero();
+                                            cursor.map_times(&storage, |t, d| if t.less_equal(time) { count += d; });
+                                            let prod = count * diff.clone();
+                                            if !prod.is_zero() {
+                                                session.give(((prefix.clone(), value.clone()), time.clone(), prod));
+                                            }
                                         }
                                         cursor.step_val(&storage);
                                     }
@@ -380,8 +513,9 @@ where
             // drop fully processed capabilities.
             stash.retain(|_,prefixes| !prefixes.is_empty());
 
-            // advance the consolidation frontier (TODO: wierd lexicographic times!)
-            propose_trace.as_mut().map(|trace| trace.advance_by(&input1.frontier().frontier()));
+            // advance the consolidation frontier, retaining the times of stashed requests.
+            let frontier = lower_bound(input1.frontier().frontier(), stash.keys().map(|capability| capability.time()));
+            propose_trace.as_mut().map(|trace| trace.advance_by(frontier.elements()));
 
             if input1.frontier().is_empty() && stash.is_empty() {
                 propose_trace = None;
@@ -404,6 +538,7 @@ where
         let mut stash = HashMap::new();
         let logic1 = self.key_selector.clone();
         let logic2 = self.key_selector.clone();
+        let predicate = self.predicate.clone();
 
         let mut buffer1 = Vec::new();
         let mut buffer2 = Vec::new();
@@ -450,8 +585,8 @@ where
                         for &mut (ref prefix, ref time, ref mut diff) in prefixes.iter_mut() {
                             if !input2.frontier.less_equal(time) {
                                 let key = (logic2(&prefix.0), (prefix.1).clone());
-                                cursor.seek_key(&storage, &key);
-                                if cursor.get_key(&storage) == Some(&key) {
+                                let accepted = predicate.as_ref().map(|predicate| predicate(&prefix.0, &prefix.1)).unwrap_or(true);
+                                if accepted && cursor.probe_key(&storage, &key) {
                                     let mut count = R::zero();
                                     cursor.map_times(&storage, |t, d| if t.less_equal(time) { count += d; });
                                     let prod = count * diff.clone();
@@ -471,8 +606,9 @@ where
             // drop fully processed capabilities.
             stash.retain(|_,prefixes| !prefixes.is_empty());
 
-            // advance the consolidation frontier (TODO: wierd lexicographic times!)
-            validate_trace.as_mut().map(|trace| trace.advance_by(&input1.frontier().frontier()));
+            // advance the consolidation frontier, retaining the times of stashed requests.
+            let frontier = lower_bound(input1.frontier().frontier(), stash.keys().map(|capability| capability.time()));
+            validate_trace.as_mut().map(|trace| trace.advance_by(frontier.elements()));
 
             if input1.frontier().is_empty() && stash.is_empty() {
                 validate_trace = None;
@@ -0,0 +1,116 @@
+//! Incrementally maintained triangles and cliques of a graph.
+//!
+//! The dataflows here are delta queries built with `delta_queries`, and maintain only four indexes of the
+//! edge collection regardless of the size of the motif. Edges are directed, and a motif is reported for
+//! each assignment of nodes to its positions such that each earlier position has an edge to each later
+//! position. To report each undirected triangle or clique exactly once, supply each undirected edge once,
+//! directed from its lesser to its greater node.
+
+use std::hash::Hash;
+use std::ops::Mul;
+
+use timely::dataflow::Scope;
+use timely::progress::Timestamp;
+
+use differential_dataflow::{Data, Collection};
+use differential_dataflow::difference::Monoid;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Count;
+
+use generic::delta_queries;
+
+/// The triangles `(a, b, c)` with edges `(a, b)`, `(b, c)`, and `(a, c)`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+/// extern crate dogsdogsdogs;
+///
+/// use differential_dataflow::input::Input;
+/// use dogsdogsdogs::motifs::triangles;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1u32), (1, 2), (0, 2), (2, 3), (1, 3)]).1;
+///         let expected = scope.new_collection_from(vec![(0u32, 1u32, 2u32), (1, 2, 3)]).1;
+///
+///         triangles(&edges).assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn triangles<G, V, R>(edges: &Collection<G, (V, V), R>) -> Collection<G, (V, V, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data+Timestamp,
+    V: Data+Hash,
+    R: Monoid+Mul<Output = R>,
+{
+    cliques(edges, 3).map(|clique| (clique[0].clone(), clique[1].clone(), clique[2].clone()))
+}
+
+/// The cliques of `k` nodes, as vectors of nodes each with an edge to each node after it.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+/// extern crate dogsdogsdogs;
+///
+/// use differential_dataflow::input::Input;
+/// use dogsdogsdogs::motifs::cliques;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1u32), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]).1;
+///         let expected = scope.new_collection_from(vec![vec![0u32, 1, 2, 3]]).1;
+///
+///         cliques(&edges, 4).assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn cliques<G, V, R>(edges: &Collection<G, (V, V), R>, k: usize) -> Collection<G, Vec<V>, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data+Timestamp,
+    V: Data+Hash,
+    R: Monoid+Mul<Output = R>,
+{
+    assert!(k >= 2, "cliques: cliques must have at least two nodes");
+
+    let mut constraints = Vec::new();
+    for later in 1 .. k {
+        for earlier in 0 .. later {
+            constraints.push((0, (earlier, later)));
+        }
+    }
+    let order = (0 .. k).collect::<Vec<_>>();
+
+    delta_queries(&[edges.clone()], &constraints[..], &order[..])
+}
+
+/// The number of triangles, as the record `((), count)` with multiplicity one, absent if there are none.
+pub fn count_triangles<G, V, R>(edges: &Collection<G, (V, V), R>) -> Collection<G, ((), R), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data+Timestamp+Ord,
+    V: Data+Hash,
+    R: Monoid+Mul<Output = R>,
+{
+    count_cliques(edges, 3)
+}
+
+/// The number of cliques of `k` nodes, as the record `((), count)` with multiplicity one, absent if there are none.
+pub fn count_cliques<G, V, R>(edges: &Collection<G, (V, V), R>, k: usize) -> Collection<G, ((), R), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data+Timestamp+Ord,
+    V: Data+Hash,
+    R: Monoid+Mul<Output = R>,
+{
+    cliques(edges, k).map(|_| ()).count()
+}
@@ -0,0 +1,116 @@
+//! A registry of relation indexes shared by delta query rules.
+//!
+//! Each delta query rule extends prefixes using indexes of the relations it joins, and rules installed
+//! separately would each index the relations again. An `IndexRegistry` holds the indexes of relations
+//! by name, for example `"edges-forward"` and `"edges-reverse"` for an edge relation keyed by source and
+//! by destination, and provides an existing index rather than constructing another. The traces of an
+//! index are shared: extenders import them into the dataflows that use them, including dataflows other
+//! than the one that constructed the index.
+//!
+//! The registry holds handles to the traces, which prevent them from compacting. The `advance_by` and
+//! `distinguish_since` methods allow compaction up to frontiers that future rules will not need to read
+//! before, and `remove` releases an index entirely.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Mul;
+
+use timely::dataflow::Scope;
+use timely::progress::Timestamp;
+
+use differential_dataflow::{Data, Collection};
+use differential_dataflow::difference::Monoid;
+use differential_dataflow::lattice::Lattice;
+
+use CollectionIndex;
+
+/// Indexes of relations of one type, by name.
+pub struct IndexRegistry<K, V, T, R>
+where
+    K: Data,
+    V: Data,
+    T: Lattice+Data,
+    R: Monoid+Mul<Output = R>,
+{
+    indices: HashMap<String, CollectionIndex<K, V, T, R>>,
+}
+
+impl<K, V, T, R> Default for IndexRegistry<K, V, T, R>
+where
+    K: Data,
+    V: Data,
+    T: Lattice+Data,
+    R: Monoid+Mul<Output = R>,
+{
+    /// An empty registry.
+    fn default() -> Self {
+        IndexRegistry { indices: HashMap::new() }
+    }
+}
+
+impl<K, V, T, R> IndexRegistry<K, V, T, R>
+where
+    K: Data+Hash,
+    V: Data+Hash,
+    T: Lattice+Data+Timestamp,
+    R: Monoid+Mul<Output = R>,
+{
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        IndexRegistry { indices: HashMap::new() }
+    }
+
+    /// The index registered as `name`, constructing it from the collection `relation` produces if absent.
+    ///
+    /// The `relation` closure is only called if no index is registered as `name`, so that a relation need
+    /// not be brought into a dataflow that will use an existing index.
+    pub fn index<G, F>(&mut self, name: &str, relation: F) -> CollectionIndex<K, V, T, R>
+    where
+        G: Scope<Timestamp = T>,
+        F: FnOnce()->Collection<G, (K, V), R>,
+    {
+        self.indices
+            .entry(name.to_string())
+            .or_insert_with(|| CollectionIndex::index(&relation()))
+            .clone()
+    }
+
+    /// The index registered as `name`, if one exists.
+    pub fn get(&self, name: &str) -> Option<CollectionIndex<K, V, T, R>> {
+        self.indices.get(name).cloned()
+    }
+
+    /// Registers `index` as `name`, returning any index previously registered as `name`.
+    pub fn insert(&mut self, name: &str, index: CollectionIndex<K, V, T, R>) -> Option<CollectionIndex<K, V, T, R>> {
+        self.indices.insert(name.to_string(), index)
+    }
+
+    /// Removes the index registered as `name`, releasing the registry's handles to its traces.
+    pub fn remove(&mut self, name: &str) -> Option<CollectionIndex<K, V, T, R>> {
+        self.indices.remove(name)
+    }
+
+    /// The number of registered indexes.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Indicates if no indexes are registered.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Allows the traces of all registered indexes to compact to `frontier`.
+    pub fn advance_by(&mut self, frontier: &[T]) {
+        for index in self.indices.values_mut() {
+            index.advance_by(frontier);
+        }
+    }
+
+    /// Allows the traces of all registered indexes to merge batches up to `frontier`.
+    pub fn distinguish_since(&mut self, frontier: &[T]) {
+        for index in self.indices.values_mut() {
+            index.distinguish_since(frontier);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+extern crate timely;
+extern crate differential_dataflow;
+extern crate dogsdogsdogs;
+
+use timely::PartialOrder;
+
+use differential_dataflow::input::{Input, InputSession};
+use differential_dataflow::operators::{Join, HalfJoin};
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use dogsdogsdogs::anti::AntiIndex;
+use dogsdogsdogs::generic::GenericJoin;
+
+// Changes the inputs in a round of updates, in which even rounds retract the edges of the round before.
+fn update(inputs: &mut [&mut InputSession<u64, (u32, u32), isize>], index: usize, peers: usize, round: u64) {
+    let edge = |input: usize, key: u64, round: u64| ((key % 5) as u32, ((key * (input as u64 + 1) + round) % 6) as u32);
+    for key in 0 .. 10u64 {
+        if (key + round) as usize % peers == index {
+            for (position, input) in inputs.iter_mut().enumerate() {
+                input.insert(edge(position, key, round));
+                if round > 0 && round % 2 == 0 {
+                    input.remove(edge(position, key, round - 1));
+                }
+            }
+        }
+    }
+    for input in inputs.iter_mut() {
+        input.advance_to(round + 1);
+        input.flush();
+    }
+}
+
+#[test]
+fn delta_join() {
+
+    timely::execute(timely::Configuration::Process(2), |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+
+        let (mut edges, probe) = worker.dataflow::<u64,_,_>(|scope| {
+
+            let (input, edges) = scope.new_collection::<(u32, u32), isize>();
+
+            // triangles(a,b,c) := edge(a,b), edge(b,c), edge(a,c)
+            let delta =
+            GenericJoin::new()
+                .relation(&edges, (0, 1))
+                .relation(&edges, (1, 2))
+                .relation(&edges, (0, 2))
+                .build(&[0, 1, 2]);
+
+            let joined =
+            edges
+                .map(|(a, b)| (b, a))
+                .join(&edges)
+                .map(|(b, (a, c))| ((a, c), b))
+                .join(&edges.map(|edge| (edge, ())))
+                .map(|((a, c), (b, ()))| vec![a, b, c]);
+
+            delta.assert_eq(&joined);
+
+            (input, joined.probe())
+        });
+
+        for round in 0 .. 10u64 {
+            update(&mut [&mut edges], index, peers, round);
+            while probe.less_than(edges.time()) {
+                worker.step();
+            }
+        }
+
+    }).unwrap().join().into_iter().for_each(|x| x.unwrap());
+}
+
+#[test]
+fn anti_join() {
+
+    timely::execute(timely::Configuration::Process(2), |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+
+        let (mut edges, mut blocked, probe) = worker.dataflow::<u64,_,_>(|scope| {
+
+            let (input1, edges) = scope.new_collection::<(u32, u32), isize>();
+            let (input2, blocked) = scope.new_collection::<(u32, u32), isize>();
+
+            // allowed(a,b) := edge(a,b), not blocked(a,b)
+            let mut anti = AntiIndex::index(&blocked);
+            let changes1 = anti.anti_extend_using(|&a: &u32| a).validate(&edges);
+            let changes2 =
+            anti.presence(&edges.scope())
+                .map(|pair| (pair, ()))
+                .half_join(&edges.map(|edge| (edge, ())).arrange_by_key(), |t2, t| t2.less_than(t), |&pair, &(), &()| pair)
+                .negate();
+
+            let antijoined =
+            edges
+                .map(|edge| (edge, ()))
+                .antijoin(&blocked)
+                .map(|(edge, ())| edge);
+
+            changes1.concat(&changes2).assert_eq(&antijoined);
+
+            (input1, input2, antijoined.probe())
+        });
+
+        for round in 0 .. 10u64 {
+            update(&mut [&mut edges, &mut blocked], index, peers, round);
+            while probe.less_than(edges.time()) {
+                worker.step();
+            }
+        }
+
+    }).unwrap().join().into_iter().for_each(|x| x.unwrap());
+}
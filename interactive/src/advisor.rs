@@ -0,0 +1,222 @@
+//! Recommendations for shared arrangements.
+//!
+//! The advisor observes the plans installed in the system, and tallies how
+//! often each sub-plan is used, as a keyed input to a join, as the input to a
+//! distinct, and as an aggregation. Sub-plans that are used repeatedly but are
+//! not yet maintained are good candidates for shared arrangements, as each further
+//! use could import the arrangement rather than re-computing and re-arranging
+//! the collection; maintained aggregations are pre-aggregations, whose uses import
+//! the aggregates rather than aggregating again.
+//!
+//! The manager reports to the advisor the logged costs of the operators arranging
+//! each sub-plan, which order recommendations with the same number of uses.
+//!
+//! By default the advisor only reports its recommendations. In the opt-in
+//! automatic mode, it installs the recommended arrangements directly in the
+//! trace manager, where subsequent queries will find and reuse them.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use timely::dataflow::{Scope, ProbeHandle};
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Time};
+
+/// A recommended arrangement.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Recommendation<Value> {
+    /// Arrange the output of the plan by the indicated columns.
+    Keyed(Plan<Value>, Vec<usize>),
+    /// Maintain the distinct output of the plan.
+    Distinct(Plan<Value>),
+    /// Maintain the output of the plan, an aggregation.
+    Aggregate(Plan<Value>),
+}
+
+/// Tallies uses of sub-plans and recommends arrangements.
+pub struct Advisor<Value: Data> {
+    /// Number of uses of each plan, arranged by each sequence of keys.
+    keyed: HashMap<(Plan<Value>, Vec<usize>), usize>,
+    /// Number of uses of the distinct of each plan.
+    distinct: HashMap<Plan<Value>, usize>,
+    /// Number of uses of each aggregation.
+    aggregated: HashMap<Plan<Value>, usize>,
+    /// Externally reported costs of maintaining each plan.
+    costs: HashMap<Plan<Value>, usize>,
+    /// The number of uses at which we recommend an arrangement.
+    pub threshold: usize,
+    /// Install recommendations as they are made.
+    pub automatic: bool,
+}
+
+impl<Value: Data+Hash> Advisor<Value> {
+
+    /// Creates a new advisor, which recommends arrangements used at least twice.
+    pub fn new() -> Self {
+        Advisor {
+            keyed: HashMap::new(),
+            distinct: HashMap::new(),
+            aggregated: HashMap::new(),
+            costs: HashMap::new(),
+            threshold: 2,
+            automatic: false,
+        }
+    }
+
+    /// Records the uses of arrangements by `plan` and its sub-plans.
+    pub fn observe(&mut self, plan: &Plan<Value>) {
+        match plan {
+            Plan::Project(project) => self.observe(&project.plan),
+            Plan::Map(map) => self.observe(&map.plan),
+            Plan::Distinct(distinct) => {
+                *self.distinct.entry((**distinct).clone()).or_insert(0) += 1;
+                self.observe(distinct);
+            },
+            Plan::Threshold(threshold) => self.observe(&threshold.plan),
+            Plan::Concat(concat) => {
+                for plan in concat.plans.iter() {
+                    self.observe(plan);
+                }
+            },
+            Plan::Union(plans) => {
+                for plan in plans.iter() {
+                    self.observe(plan);
+                }
+            },
+            Plan::Join(join) => {
+                let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+                *self.keyed.entry(((*join.plan1).clone(), keys1)).or_insert(0) += 1;
+                *self.keyed.entry(((*join.plan2).clone(), keys2)).or_insert(0) += 1;
+                self.observe(&join.plan1);
+                self.observe(&join.plan2);
+            },
+            Plan::Antijoin(antijoin) => {
+                self.observe(&antijoin.plan1);
+                self.observe(&antijoin.plan2);
+            },
+            Plan::Negate(negate) => self.observe(negate),
+            Plan::Filter(filter) => self.observe(&filter.plan),
+            Plan::Aggregate(aggregate) => {
+                *self.aggregated.entry(plan.clone()).or_insert(0) += 1;
+                self.observe(&aggregate.plan);
+            },
+            // Sub-plans of bodies and iterations may depend on their bindings, and cannot be arranged outside them.
+            Plan::Let(bind) => self.observe(&bind.value),
+            Plan::Iterate(_) => { },
+            Plan::Source(_) => { },
+            Plan::Inspect(_, plan) => self.observe(plan),
+        }
+    }
+
+    /// Records an observed cost for maintaining `plan`.
+    ///
+    /// Costs may come from any source; `Manager::observe_costs` reports the logged
+    /// sizes of the batches of the arrangements of the plan's output. Plans with
+    /// larger costs are preferred among recommendations with the same number of uses.
+    pub fn observe_cost(&mut self, plan: &Plan<Value>, cost: usize) {
+        *self.costs.entry(plan.clone()).or_insert(0) += cost;
+    }
+
+    /// Recommendations for arrangements not currently maintained by `traces`.
+    ///
+    /// Recommendations are ordered by decreasing estimated benefit, the number
+    /// of additional uses multiplied by the observed cost of the plan.
+    pub fn recommend(&self, traces: &TraceManager<Value>) -> Vec<Recommendation<Value>> {
+
+        let mut results = Vec::new();
+
+        for ((plan, keys), uses) in self.keyed.iter() {
+            if *uses >= self.threshold && traces.get_keyed(plan, &keys[..]).is_none() {
+                let benefit = (uses - 1) * (1 + self.costs.get(plan).cloned().unwrap_or(0));
+                results.push((benefit, Recommendation::Keyed(plan.clone(), keys.clone())));
+            }
+        }
+
+        for (plan, uses) in self.distinct.iter() {
+            let distinct = Plan::Distinct(Box::new(plan.clone()));
+            if *uses >= self.threshold && traces.get_unkeyed(&distinct).is_none() {
+                let benefit = (uses - 1) * (1 + self.costs.get(plan).cloned().unwrap_or(0));
+                results.push((benefit, Recommendation::Distinct(plan.clone())));
+            }
+        }
+
+        for (plan, uses) in self.aggregated.iter() {
+            if *uses >= self.threshold && traces.get_unkeyed(plan).is_none() {
+                let benefit = (uses - 1) * (1 + self.costs.get(plan).cloned().unwrap_or(0));
+                results.push((benefit, Recommendation::Aggregate(plan.clone())));
+            }
+        }
+
+        results.sort_by(|x,y| y.cmp(x));
+        results.into_iter().map(|(_benefit, recommendation)| recommendation).collect()
+    }
+
+    /// Installs each recommended arrangement in `traces`.
+    ///
+    /// The recommended collections are rendered in `scope`, and each of the new
+    /// arrangements is attached to `probe`. Returns the list of installed
+    /// recommendations.
+    ///
+    /// Workers observe different costs, reflecting their shares of the data, but must
+    /// install the same dataflows, so recommendations are installed in their own order
+    /// rather than by benefit.
+    pub fn install<S: RenderScope<Value>+Scope<Timestamp=Time>>(
+        &self,
+        scope: &mut S,
+        traces: &mut TraceManager<Value>,
+        probe: &mut ProbeHandle<Time>) -> Vec<Recommendation<Value>>
+    where
+        Value: Aggregable+Evaluable,
+    {
+        use timely::dataflow::operators::Probe;
+        use differential_dataflow::operators::Threshold;
+        use differential_dataflow::operators::arrange::{ArrangeByKey, ArrangeBySelf};
+
+        let mut recommendations = self.recommend(traces);
+        recommendations.sort();
+        for recommendation in recommendations.iter() {
+            match recommendation {
+                Recommendation::Keyed(plan, keys) => {
+                    let keys2 = keys.clone();
+                    let arrangement =
+                    plan.render(scope, traces, &Bindings::new())
+                        .map(move |tuple|
+                            (
+                                keys2.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
+                                tuple
+                                    .into_iter()
+                                    .enumerate()
+                                    .filter(|(index,_value)| !keys2.contains(index))
+                                    .map(|(_index,value)| value)
+                                    .collect::<Vec<_>>(),
+                            )
+                        )
+                        .arrange_by_key();
+                    arrangement.stream.probe_with(probe);
+                    traces.set_keyed(plan, &keys[..], &arrangement.trace);
+                },
+                Recommendation::Distinct(plan) => {
+                    let arrangement =
+                    plan.render(scope, traces, &Bindings::new())
+                        .distinct()
+                        .arrange_by_self();
+                    arrangement.stream.probe_with(probe);
+                    traces.set_unkeyed(&Plan::Distinct(Box::new(plan.clone())), &arrangement.trace);
+                },
+                Recommendation::Aggregate(plan) => {
+                    let arrangement =
+                    plan.render(scope, traces, &Bindings::new())
+                        .arrange_by_self();
+                    arrangement.stream.probe_with(probe);
+                    traces.set_unkeyed(plan, &arrangement.trace);
+                },
+            }
+        }
+
+        recommendations
+    }
+}
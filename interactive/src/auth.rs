@@ -0,0 +1,196 @@
+//! Authentication of clients, and authorization of their commands.
+//!
+//! A server may be configured with an `Authorizer`, mapping secret tokens to the
+//! principals that present them. A connection authenticates by sending the command
+//! `Authenticate` with its token, which the server handles on the connection rather
+//! than sequencing, and each later command of the connection is authorized for its
+//! principal before it is sequenced. Commands refused are answered by `Failed`.
+//!
+//! Principals are granted permissions within namespaces, each of which also covers
+//! the namespaces within it: `Define` to create and update inputs and schemas and to
+//! advance time, `Install` to install and uninstall dataflows, and `Read` to subscribe
+//! to and read collections. Commands of a namespace reading shared collections, as
+//! `namespace` describes, need only the permissions of the namespace, but those creating,
+//! updating, publishing, or uninstalling shared names require a principal with `admin`
+//! set, as do commands outside of any namespace, and those affecting the whole system
+//! even within one, such as `Budget`, `Vacuum`, and `LoadFile`, which reads the files of
+//! the server.
+//!
+//! Authorizers are read from JSON, for example
+//!
+//! ```ignore
+//! { "tokens": {
+//!     "s3cret": { "name": "alice", "admin": false, "grants": [
+//!         { "namespace": "alice", "permissions": ["Define", "Install", "Read"] },
+//!         { "namespace": "*", "permissions": ["Read"] } ] } } }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use Command;
+
+/// An action a principal may be permitted within a namespace.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Permission {
+    /// Creating and updating inputs and schemas, and advancing time.
+    Define,
+    /// Installing and uninstalling dataflows.
+    Install,
+    /// Subscribing to and reading collections.
+    Read,
+}
+
+/// Permissions within a namespace, and the namespaces within it.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Grant {
+    /// The namespace, or `*` for every namespace.
+    pub namespace: String,
+    /// The permitted actions.
+    pub permissions: Vec<Permission>,
+}
+
+/// An authenticated client, and its permissions.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Principal {
+    /// The name of the principal, for reports.
+    pub name: String,
+    /// Indicates if the principal may issue any command.
+    #[serde(default)]
+    pub admin: bool,
+    /// Permissions within namespaces.
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+}
+
+impl Principal {
+    /// Indicates if the principal is permitted `permission` within `namespace`.
+    pub fn permits(&self, namespace: &str, permission: Permission) -> bool {
+        self.admin || self.grants.iter().any(|grant| {
+            (grant.namespace == "*" || grant.namespace == namespace || ::namespace::within(&grant.namespace, namespace))
+            && grant.permissions.contains(&permission)
+        })
+    }
+}
+
+/// The principals presenting each token.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Authorizer {
+    /// Principals, by their secret tokens.
+    pub tokens: HashMap<String, Principal>,
+}
+
+impl Authorizer {
+
+    /// Reads an authorizer from the JSON file at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = ::std::fs::File::open(path).map_err(|error| format!("failed to open {}: {}", path, error))?;
+        ::serde_json::from_reader(::std::io::BufReader::new(file)).map_err(|error| format!("failed to read {}: {}", path, error))
+    }
+
+    /// The principal presenting `token`, if any.
+    pub fn authenticate(&self, token: &str) -> Option<&Principal> {
+        self.tokens.get(token)
+    }
+
+    /// Checks that `principal`, if authenticated, may issue `command`.
+    pub fn authorize<V: Debug>(&self, principal: Option<&Principal>, command: &Command<V>) -> Result<(), String> {
+        let principal = principal.ok_or_else(|| "Not authenticated".to_string())?;
+        let permitted = match required(None, command) {
+            Some((namespace, permission)) => principal.permits(&namespace, permission),
+            None => principal.admin,
+        };
+        if permitted { Ok(()) }
+        else { Err(format!("Not authorized: {} may not issue {}", principal.name, describe(command))) }
+    }
+}
+
+/// The namespace and permission `command` requires, or `None` if it requires administration.
+fn required<V>(namespace: Option<String>, command: &Command<V>) -> Option<(String, Permission)> {
+    let permission = match command {
+        Command::Namespaced(inner, command) => {
+            let qualified = match namespace {
+                Some(namespace) => ::namespace::qualify(&namespace, inner),
+                None => inner.clone(),
+            };
+            return required(Some(qualified), command);
+        },
+        Command::CreateInput(_, _) => Permission::Define,
+        Command::UpdateInput(_, _) => Permission::Define,
+        Command::CloseInput(_) => Permission::Define,
+        Command::CreateUpsertInput(_, _) => Permission::Define,
+        Command::UpsertInput(_, _) => Permission::Define,
+        Command::DeclareSchema(_, _) => Permission::Define,
+        Command::AdvanceTime(_) => Permission::Define,
+        Command::Query(_) => Permission::Install,
+        Command::Publish(_, _) => Permission::Install,
+        Command::Sql(_, _) => Permission::Install,
+        Command::Datalog(_) => Permission::Install,
+        Command::Uninstall(_) => Permission::Install,
+        // Within a namespace, shutting down uninstalls the namespace.
+        Command::Shutdown => Permission::Install,
+        Command::Subscribe(_, _) => Permission::Read,
+        Command::Unsubscribe(_) => Permission::Read,
+        Command::Resubscribe(_, _, _) => Permission::Read,
+        Command::QueryAsOf(_, _) => Permission::Read,
+        Command::GetByName(_) => Permission::Read,
+        Command::GetSchema(_) => Permission::Read,
+        Command::Explain(_) => Permission::Read,
+        Command::Estimate(_, _) => Permission::Read,
+        Command::Usage => Permission::Read,
+        Command::LoadFile(_, _, _) => return None,
+        Command::Advise(_) => return None,
+        Command::Vacuum(_) => return None,
+        Command::Budget(_) => return None,
+        Command::History(_) => return None,
+        Command::Describe => return None,
+        // Connections authenticate themselves, rather than sequencing this command.
+        Command::Authenticate(_) => return None,
+    };
+    // Changes to the shared collections affect every namespace.
+    if let Some(namespace) = namespace.as_ref() {
+        if permission != Permission::Read {
+            let shared = defined(command).iter().any(|name| {
+                let qualified = ::namespace::qualify(namespace, name);
+                ::namespace::SHARED.iter().any(|prefix| qualified.starts_with(prefix))
+            });
+            if shared {
+                return None;
+            }
+        }
+    }
+    namespace.map(|namespace| (namespace, permission))
+}
+
+/// The names `command` creates, updates, publishes, or uninstalls, before qualification.
+fn defined<V>(command: &Command<V>) -> Vec<String> {
+    match command {
+        Command::Query(query) => query.rules.iter().map(|rule| rule.name.clone()).collect(),
+        Command::Datalog(text) => {
+            // Programs that do not compile are refused when executed, and define nothing.
+            ::datalog::predicates(text).unwrap_or_default()
+        },
+        Command::CreateInput(name, _) => vec![name.clone()],
+        Command::UpdateInput(name, _) => vec![name.clone()],
+        Command::CloseInput(name) => vec![name.clone()],
+        Command::CreateUpsertInput(name, _) => vec![name.clone()],
+        Command::UpsertInput(name, _) => vec![name.clone()],
+        Command::DeclareSchema(name, _) => vec![name.clone()],
+        Command::Publish(name, _) => vec![name.clone()],
+        Command::Sql(name, _) => vec![name.clone()],
+        Command::Uninstall(name) => vec![name.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// The name of the variant of `command`, within its namespaces.
+fn describe<V: Debug>(command: &Command<V>) -> String {
+    match command {
+        Command::Namespaced(namespace, command) => format!("{} within {:?}", describe(command), namespace),
+        _ => {
+            // The name of the variant starts its debug text, before its arguments.
+            let text = format!("{:?}", command);
+            text.split('(').next().unwrap_or("").to_string()
+        },
+    }
+}
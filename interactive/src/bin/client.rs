@@ -1,25 +1,25 @@
 extern crate interactive;
 
 use std::time::Duration;
-use interactive::{Command, Query, Plan, Rule, Value};
+use interactive::{Client, Command, Query, Plan, Rule, Value};
 
 fn main() {
 
-    let mut socket = std::net::TcpStream::connect("127.0.0.1:8000".to_string()).expect("failed to connect");
+    let mut client = Client::<Value>::connect("127.0.0.1:8000").expect("failed to connect");
 
     // Create initially empty set of edges.
     let command = Command::<Value>::CreateInput("Edges".to_string(), Vec::new());
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     for node in 0 .. 1000 {
         let edge = vec![Value::Usize(node), Value::Usize(node+1)];
         let command = Command::<Value>::UpdateInput("Edges".to_string(), vec![(edge, Duration::from_secs(0), 1)]);
-        interactive::bincode_socket(&mut socket, &command);
+        client.send(&command).expect("failed to send command");
     }
 
     // Create initially empty set of edges.
     let command = Command::<Value>::CreateInput("Nodes".to_string(), Vec::new());
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command =
     Command::<Value>::Query(
@@ -34,16 +34,16 @@ fn main() {
                 }
             )
     );
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command = Command::<Value>::AdvanceTime(Duration::from_secs(1));
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command = Command::<Value>::UpdateInput("Nodes".to_string(), vec![(vec![Value::Usize(0)], Duration::from_secs(1), 1)]);
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command = Command::<Value>::AdvanceTime(Duration::from_secs(2));
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command =
     Command::<Value>::Query(
@@ -77,11 +77,11 @@ fn main() {
             ]
         }
     );
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command = Command::<Value>::AdvanceTime(Duration::from_secs(3));
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 
     let command = Command::<Value>::Shutdown;
-    interactive::bincode_socket(&mut socket, &command);
+    client.send(&command).expect("failed to send command");
 }
\ No newline at end of file
@@ -0,0 +1,392 @@
+extern crate rustyline;
+extern crate interactive;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rustyline::Editor;
+use rustyline::error::ReadlineError;
+
+use interactive::{Client, Command, Response, Plan, Value, Time, Diff};
+use interactive::schema::{Schema, Type};
+use interactive::sql::{Literal, FromLiteral};
+
+const HELP: &str = "\
+Commands, each on one line, continued onto the next by a trailing \\:
+
+    create NAME [(COLUMN: TYPE, ...)]   creates an input, with a schema if given
+    insert NAME (VALUE, ...), ...       adds tuples to an input at the current time
+    delete NAME (VALUE, ...), ...       removes tuples from an input at the current time
+    advance [SECONDS]                   completes the current time, moving to SECONDS or one second on
+    sql NAME QUERY                      installs a SQL query, publishing its result as NAME
+    datalog RULES                       installs Datalog rules, publishing each predicate they define
+    tail NAME                           prints the updates of NAME, as they complete
+    untail NAME                         stops printing the updates of NAME
+    wait                                prints updates until those of tailed names are complete
+    show NAME                           prints the contents of NAME at the last completed time
+    schema NAME                         prints the schema of NAME
+    uninstall NAME                      uninstalls NAME, and releases its traces
+    describe                            prints the state of the server
+    shutdown                            terminates the server, and exits
+    help                                prints this message
+    quit                                exits
+
+Values are numbers, 'strings', true, false, and null; types are bool, usize, string,
+address, duration, decimal, bytes, timestamp, and null. Contents read by show are those
+of the worker receiving the command.";
+
+/// A connection to the server, and the state of the session.
+struct Repl {
+    client: Client<Value>,
+    /// The time at which updates are introduced.
+    time: Time,
+    /// The time before the current time, if any, which has been completed.
+    completed: Option<Time>,
+    /// The frontier of each tailed name, by its subscription.
+    tails: HashMap<String, Vec<Time>>,
+}
+
+impl Repl {
+
+    /// Sends `command`, and reports its response, and any updates received before it.
+    ///
+    /// Returns `false` if the server failed to execute the command.
+    fn execute(&mut self, command: Command<Value>) -> Result<bool, String> {
+        self.client.send(&command)?;
+        self.report()
+    }
+
+    /// Reports the response to the earliest command whose response has not been read.
+    ///
+    /// Returns `false` if the server failed to execute the command.
+    fn report(&mut self) -> Result<bool, String> {
+        loop {
+            match self.client.receive()? {
+                Response::Executed(lines) => {
+                    for line in lines.iter() { println!("{}", line); }
+                    return Ok(true);
+                },
+                Response::Failed(reason) => {
+                    println!("error: {}", reason);
+                    return Ok(false);
+                },
+                Response::Busy(wait) => {
+                    println!("busy: the server refused the command; retry after {:?}", wait);
+                    return Ok(false);
+                },
+                Response::Contents(contents) => {
+                    let rows = contents.iter().map(|(values, diff)| {
+                        values.iter().map(render).chain(Some(diff.to_string())).collect()
+                    }).collect::<Vec<_>>();
+                    table(&rows, &["diff"]);
+                    return Ok(true);
+                },
+                response => self.observe(response),
+            }
+        }
+    }
+
+    /// Reports updates and progress of tailed names.
+    fn observe(&mut self, response: Response<Value>) {
+        match response {
+            Response::Updates(name, updates) => {
+                println!("{}:", name.trim_start_matches("tail/"));
+                let rows = updates.iter().map(|(values, time, diff)| {
+                    values.iter().map(render).chain(Some(format!("{:?}", time))).chain(Some(diff.to_string())).collect()
+                }).collect::<Vec<_>>();
+                table(&rows, &["time", "diff"]);
+            },
+            Response::Progress(name, frontier) => {
+                if let Some(tail) = self.tails.get_mut(&name) { *tail = frontier; }
+            },
+            other => println!("unexpected response: {:?}", other),
+        }
+    }
+
+    /// Reports updates until those of each tailed name are complete before the current time.
+    fn wait(&mut self) -> Result<(), String> {
+        let time = self.time;
+        while !self.tails.values().all(|frontier| frontier.iter().all(|t| *t >= time)) {
+            let response = self.client.receive()?;
+            self.observe(response);
+        }
+        Ok(())
+    }
+
+    /// Interprets a line of input, returning `false` if the session should end.
+    fn interpret(&mut self, line: &str) -> Result<bool, String> {
+        let line = line.trim();
+        let (word, rest) = match line.find(char::is_whitespace) {
+            Some(position) => (&line[..position], line[position..].trim()),
+            None => (line, ""),
+        };
+        match word {
+            "" => { },
+            "help" => println!("{}", HELP),
+            "quit" | "exit" => return Ok(false),
+            "create" => {
+                let (name, rest) = split(rest)?;
+                let schema = if rest.is_empty() { None } else { Some(parse_schema(rest)?) };
+                if self.execute(Command::CreateInput(name.to_string(), Vec::new()))? {
+                    if let Some(schema) = schema {
+                        self.execute(Command::DeclareSchema(name.to_string(), schema))?;
+                    }
+                }
+            },
+            "insert" | "delete" => {
+                let (name, rest) = split(rest)?;
+                let diff: Diff = if word == "insert" { 1 } else { -1 };
+                let time = self.time;
+                let updates = parse_tuples(rest)?.into_iter().map(|tuple| (tuple, time, diff)).collect();
+                self.execute(Command::UpdateInput(name.to_string(), updates))?;
+            },
+            "advance" => {
+                let time = if rest.is_empty() { self.time + Duration::from_secs(1) }
+                else { Duration::from_secs(rest.parse::<u64>().map_err(|_| format!("Expected seconds, found {}", rest))?) };
+                if time <= self.time {
+                    return Err(format!("Time {:?} is not after the current time {:?}", time, self.time));
+                }
+                if self.execute(Command::AdvanceTime(time))? {
+                    self.completed = Some(self.time);
+                    self.time = time;
+                    self.wait()?;
+                }
+            },
+            "sql" => {
+                let (name, query) = split(rest)?;
+                self.execute(Command::Sql(name.to_string(), query.to_string()))?;
+            },
+            "datalog" => { self.execute(Command::Datalog(rest.to_string()))?; },
+            "tail" => {
+                let (name, _) = split(rest)?;
+                let subscription = format!("tail/{}", name);
+                self.tails.insert(subscription.clone(), vec![Duration::from_secs(0)]);
+                // The client subscribes again if it reconnects, resuming from the progress it read.
+                self.client.subscribe(&subscription, Plan::source(name))?;
+                if self.report()? {
+                    self.wait()?;
+                }
+                else {
+                    self.tails.remove(&subscription);
+                }
+            },
+            "untail" => {
+                let (name, _) = split(rest)?;
+                let subscription = format!("tail/{}", name);
+                self.tails.remove(&subscription);
+                self.client.unsubscribe(&subscription)?;
+                self.report()?;
+            },
+            "wait" => self.wait()?,
+            "show" => {
+                let (name, _) = split(rest)?;
+                let time = self.completed.ok_or_else(|| "No time has completed; advance first".to_string())?;
+                self.execute(Command::QueryAsOf(Plan::source(name), time))?;
+            },
+            "schema" => {
+                let (name, _) = split(rest)?;
+                self.execute(Command::GetSchema(name.to_string()))?;
+            },
+            "uninstall" => {
+                let (name, _) = split(rest)?;
+                self.execute(Command::Uninstall(name.to_string()))?;
+            },
+            "describe" => { self.execute(Command::Describe)?; },
+            "shutdown" => {
+                self.client.send(&Command::Shutdown)?;
+                return Ok(false);
+            },
+            _ => return Err(format!("Unknown command {}; try help", word)),
+        }
+        Ok(true)
+    }
+}
+
+/// Splits a name from the start of `text`.
+fn split(text: &str) -> Result<(&str, &str), String> {
+    let end = text.find(|c: char| c.is_whitespace() || c == '(').unwrap_or(text.len());
+    if end == 0 { return Err("Expected a name".to_string()); }
+    Ok((&text[..end], text[end..].trim()))
+}
+
+/// Parses a parenthesized list of columns and their types.
+fn parse_schema(text: &str) -> Result<Schema, String> {
+    let inner = text.strip_prefix('(').and_then(|text| text.strip_suffix(')')).ok_or_else(|| format!("Expected a parenthesized schema, found {}", text))?;
+    let mut columns = Vec::new();
+    for column in inner.split(',') {
+        let mut parts = column.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let typed = match parts.next().map(|typed| typed.trim().to_lowercase()).as_ref().map(|typed| &typed[..]) {
+            Some("bool") => Type::Bool,
+            Some("usize") => Type::Usize,
+            Some("string") => Type::String,
+            Some("address") => Type::Address,
+            Some("duration") => Type::Duration,
+            Some("decimal") => Type::Decimal,
+            Some("bytes") => Type::Bytes,
+            Some("timestamp") => Type::Timestamp,
+            Some("null") => Type::Null,
+            _ => return Err(format!("Expected COLUMN: TYPE, found {}", column.trim())),
+        };
+        columns.push((name.to_string(), typed));
+    }
+    Ok(Schema::new(columns))
+}
+
+/// Parses a comma-separated list of parenthesized tuples of literals.
+fn parse_tuples(text: &str) -> Result<Vec<Vec<Value>>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut position = 0;
+    let skip = |position: &mut usize| while *position < chars.len() && chars[*position].is_whitespace() { *position += 1; };
+    let mut tuples = Vec::new();
+    loop {
+        skip(&mut position);
+        if chars.get(position) != Some(&'(') { return Err("Expected a parenthesized tuple".to_string()); }
+        position += 1;
+        let mut tuple = Vec::new();
+        loop {
+            skip(&mut position);
+            if tuple.is_empty() && chars.get(position) == Some(&')') { break; }
+            let literal = match chars.get(position) {
+                Some('\'') => {
+                    // Quotes within strings are written twice.
+                    let mut quoted = String::new();
+                    position += 1;
+                    loop {
+                        match chars.get(position) {
+                            Some('\'') if chars.get(position + 1) == Some(&'\'') => { quoted.push('\''); position += 2; },
+                            Some('\'') => { position += 1; break; },
+                            Some(&other) => { quoted.push(other); position += 1; },
+                            None => return Err(format!("Unterminated string: '{}", quoted)),
+                        }
+                    }
+                    Literal::String(quoted)
+                },
+                Some(_) => {
+                    let start = position;
+                    while position < chars.len() && !(chars[position] == ',' || chars[position] == ')' || chars[position].is_whitespace()) { position += 1; }
+                    let word = chars[start..position].iter().collect::<String>();
+                    match &word[..] {
+                        "true" => Literal::Bool(true),
+                        "false" => Literal::Bool(false),
+                        "null" => Literal::Null,
+                        _ => Literal::Number(word),
+                    }
+                },
+                None => return Err("Unterminated tuple".to_string()),
+            };
+            tuple.push(Value::from_literal(&literal)?);
+            skip(&mut position);
+            match chars.get(position) {
+                Some(',') => { position += 1; },
+                Some(')') => break,
+                _ => return Err("Expected , or ) in a tuple".to_string()),
+            }
+        }
+        position += 1;
+        tuples.push(tuple);
+        skip(&mut position);
+        match chars.get(position) {
+            Some(',') => { position += 1; },
+            None => return Ok(tuples),
+            _ => return Err("Expected , between tuples".to_string()),
+        }
+    }
+}
+
+/// The text of a value, as it would be written.
+fn render(value: &Value) -> String {
+    match value {
+        Value::Bool(value) => value.to_string(),
+        Value::Usize(value) => value.to_string(),
+        Value::String(value) => format!("'{}'", value.replace('\'', "''")),
+        Value::Address(address) => format!("{:?}", address),
+        Value::Duration(duration) => format!("{:?}", duration),
+        Value::Decimal(decimal) => decimal.to_string(),
+        Value::Bytes(bytes) => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        Value::Timestamp(micros) => format!("{}us", micros),
+        Value::Null => "null".to_string(),
+    }
+}
+
+/// Prints rows as a table, with columns numbered other than the last, which are named by `trailing`.
+fn table(rows: &[Vec<String>], trailing: &[&str]) {
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(trailing.len());
+    let mut header = (0 .. columns - trailing.len()).map(|index| format!("#{}", index)).collect::<Vec<_>>();
+    header.extend(trailing.iter().map(|name| name.to_string()));
+    let mut widths = header.iter().map(|name| name.len()).collect::<Vec<_>>();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = ::std::cmp::max(*width, cell.chars().count());
+        }
+    }
+    let print = |row: &[String]| {
+        let cells = row.iter().zip(widths.iter()).map(|(cell, width)| format!("{:width$}", cell, width = width)).collect::<Vec<_>>();
+        println!("{}", cells.join(" | ").trim_end());
+    };
+    print(&header);
+    println!("{}", widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("-+-"));
+    for row in rows.iter() {
+        print(row);
+    }
+    println!("({} rows)", rows.len());
+}
+
+fn main() {
+
+    // The address of the server, by argument or as the server's own default.
+    let address =
+    std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var("INTERACTIVE_ADDRESS").ok())
+        .unwrap_or_else(|| "127.0.0.1:8000".to_string());
+
+    let client = Client::<Value>::connect(&address).unwrap_or_else(|error| panic!("failed to connect to {}: {}", address, error));
+    let mut repl = Repl { client, time: Duration::from_secs(0), completed: None, tails: HashMap::new() };
+
+    let history = std::env::var("HOME").ok().map(|home| format!("{}/.dd_repl_history", home));
+    let mut editor = Editor::<()>::new();
+    if let Some(history) = history.as_ref() {
+        let _ = editor.load_history(history);
+    }
+
+    println!("Connected to {}; type help for commands.", address);
+
+    // The token with which to authenticate, if the server requires one.
+    if let Ok(token) = std::env::var("INTERACTIVE_TOKEN") {
+        repl.client.authenticate(&token).and_then(|()| repl.report()).expect("failed to authenticate");
+    }
+
+    let mut pending = String::new();
+    loop {
+        let prompt = if pending.is_empty() { "dd> " } else { "..> " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                editor.add_history_entry(line.as_str());
+                // A trailing backslash continues the command onto the next line.
+                if let Some(continued) = line.trim_end().strip_suffix('\\') {
+                    pending.push_str(continued);
+                    pending.push('\n');
+                    continue;
+                }
+                pending.push_str(&line);
+                let command = ::std::mem::replace(&mut pending, String::new());
+                match repl.interpret(&command) {
+                    Ok(true) => { },
+                    Ok(false) => break,
+                    Err(reason) => println!("error: {}", reason),
+                }
+            },
+            Err(ReadlineError::Interrupted) => { pending.clear(); },
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                println!("error: {}", error);
+                break;
+            },
+        }
+    }
+
+    if let Some(history) = history.as_ref() {
+        let _ = editor.save_history(history);
+    }
+}
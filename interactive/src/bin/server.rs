@@ -1,24 +1,144 @@
 extern crate timely;
 extern crate differential_dataflow;
 extern crate interactive;
+extern crate serde_json;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
 
 use timely::synchronization::Sequencer;
-use interactive::{Manager, Command, Value};
+use interactive::{Manager, Command, Response, Value, Journal, Authorizer};
+use interactive::metrics::Metrics;
+use interactive::redact::Columns;
 
 use timely::logging::TimelyEvent;
 use differential_dataflow::logging::DifferentialEvent;
 
+/// Commands, each tagged with the worker that sequenced it and the connection that sent it.
+///
+/// Commands replayed from the journal are sent by `REPLAYED`, which follows them with `None`.
+type Tagged = (usize, usize, Option<Command<Value>>);
+/// Commands received by this process, each with the connection that sent it.
+type Queue = Arc<Mutex<VecDeque<(usize, Command<Value>)>>>;
+/// Senders of responses to each connection of this process, by connection.
+type Responders = Arc<Mutex<HashMap<usize, Sender<Response<Value>>>>>;
+/// The latest metrics of each worker of this process, by worker.
+type Measured = Arc<Mutex<HashMap<usize, Metrics>>>;
+
+/// The connection that sends the commands replayed from the journal.
+const REPLAYED: usize = usize::max_value();
+
+/// The name under which workers maintain the subscription `name` of `connection`, so that
+/// connections may name their subscriptions alike without conflict.
+fn subscription_name(connection: usize, name: &str) -> String {
+    format!("{}@{}", name, connection)
+}
+
+/// The connection and the name it gave to the subscription that workers maintain as `name`.
+fn subscriber(name: &str) -> Option<(usize, String)> {
+    let at = name.rfind('@')?;
+    let connection = name[at + 1 ..].parse().ok()?;
+    Some((connection, name[.. at].to_string()))
+}
+
+/// Renames the subscriptions `command` creates or removes to those maintained for `connection`.
+fn owned(connection: usize, command: Command<Value>) -> Command<Value> {
+    match command {
+        Command::Subscribe(name, plan) => Command::Subscribe(subscription_name(connection, &name), plan),
+        Command::Unsubscribe(name) => Command::Unsubscribe(subscription_name(connection, &name)),
+        Command::Resubscribe(name, plan, frontier) => Command::Resubscribe(subscription_name(connection, &name), plan, frontier),
+        Command::Namespaced(namespace, command) => Command::Namespaced(namespace, Box::new(owned(connection, *command))),
+        command => command,
+    }
+}
+
+/// How often workers measure their metrics, when they are served.
+const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Limits on the commands of each connection, beyond which they are refused as `Busy`.
+#[derive(Copy, Clone, Debug)]
+struct Limits {
+    /// The number of commands of a connection that may wait to be sequenced.
+    queued: usize,
+    /// The number of commands per second a connection may send, after a burst of as many but at least one, if limited.
+    rate: Option<f64>,
+}
+
+/// The commands a connection may send before it must wait, replenished at a rate.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// The number of tokens a bucket replenished at `rate` may hold, which is at least one token.
+    fn capacity(rate: f64) -> f64 {
+        rate.max(1.0)
+    }
+    /// Takes a token, or returns the time until one is available.
+    fn take(&mut self, rate: f64) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated);
+        self.tokens = (self.tokens + rate * elapsed.as_secs_f64()).min(Bucket::capacity(rate));
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        }
+        else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
 fn main() {
 
     let mut args = std::env::args();
     args.next();
 
-    use std::sync::{Arc, Mutex};
-    use std::collections::VecDeque;
+    // The address on which to accept connections.
+    let address = std::env::var("INTERACTIVE_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8000".to_string());
+    // The journal from which to recover, and to which to append, if any. Only the first worker reads and
+    // appends to it, so only its process needs the file, but every process must be given the path so that
+    // its workers wait for the replay.
+    let journal_path = std::env::var("INTERACTIVE_JOURNAL").ok();
+    // The commands each connection may have waiting, and may send each second, if limited.
+    let limits = Limits {
+        queued: std::env::var("INTERACTIVE_QUEUE").ok().map(|queued| queued.parse().expect("failed to parse INTERACTIVE_QUEUE")).unwrap_or(1024),
+        rate: std::env::var("INTERACTIVE_RATE").ok().map(|rate| rate.parse().expect("failed to parse INTERACTIVE_RATE")),
+    };
+    // The principals of tokens, if connections must authenticate.
+    let authorizer = std::env::var("INTERACTIVE_AUTH").ok().map(|path| Arc::new(Authorizer::load(&path).expect("failed to load authorizer")));
+    // The seconds for which logged events are retained, if bounded.
+    let log_retention = std::env::var("INTERACTIVE_LOG_RETENTION").ok().map(|seconds| Duration::from_secs(seconds.parse().expect("failed to parse INTERACTIVE_LOG_RETENTION")));
+    // The columns of collections to redact from responses and logs, if any, as JSON from names to columns.
+    let redacted = std::env::var("INTERACTIVE_REDACT").ok().map(|path| {
+        let file = std::fs::File::open(&path).expect("failed to open INTERACTIVE_REDACT");
+        serde_json::from_reader::<_, HashMap<String, Vec<usize>>>(BufReader::new(file)).expect("failed to read INTERACTIVE_REDACT")
+    });
+    // The address on which to serve metrics over HTTP, if any.
+    let metrics_address = std::env::var("INTERACTIVE_METRICS").ok();
+    let serve_metrics = metrics_address.is_some();
 
-    let command_queue = Arc::new(Mutex::new(VecDeque::<Command<Value>>::new()));
+    let command_queue: Queue = Arc::new(Mutex::new(VecDeque::new()));
     let command_queue2 = command_queue.clone();
 
+    let responders: Responders = Arc::new(Mutex::new(HashMap::new()));
+    let responders2 = responders.clone();
+
+    // Set once a shutdown is sequenced, after which connections refuse further commands.
+    let closed = Arc::new(AtomicBool::new(false));
+    let closed2 = closed.clone();
+
+    let measured: Measured = Arc::new(Mutex::new(HashMap::new()));
+    let measured2 = measured.clone();
+    let command_queue3 = command_queue.clone();
+
     let guards =
     timely::execute_from_args(args, move |worker| {
 
@@ -26,50 +146,173 @@ fn main() {
         let mut manager = Manager::<Value>::new();
 
         let recv = command_queue.clone();
+        let responders = responders.clone();
+        let measured = measured.clone();
 
         use std::rc::Rc;
+        use std::cell::Cell;
         use timely::dataflow::operators::capture::event::link::EventLink;
         use timely::logging::BatchLogger;
 
+        manager.set_log_retention(log_retention);
+        if let Some(redacted) = redacted.as_ref() {
+            let redactor = Columns { columns: redacted.clone(), placeholder: Value::String("<redacted>".to_string().into()) };
+            manager.traces.set_redactor(Some(Rc::new(redactor)));
+        }
+
         let timely_events = Rc::new(EventLink::new());
         let differential_events = Rc::new(EventLink::new());
 
         manager.publish_timely_logging(worker, Some(timely_events.clone()));
         manager.publish_differential_logging(worker, Some(differential_events.clone()));
+        manager.publish_system(worker);
+        manager.publish_latency(worker);
+
+        // Logging views aggregated across workers, which every worker installs alike.
+        let cluster = interactive::cluster::query().expect("failed to plan cluster logging views");
+        if let Response::Failed(reason) = Command::Query(cluster).execute(&mut manager, worker, worker.index()) {
+            panic!("failed to install cluster logging views: {}", reason);
+        }
 
         let mut timely_logger = BatchLogger::new(timely_events.clone());
         worker
             .log_register()
             .insert::<TimelyEvent,_>("timely", move |time, data| timely_logger.publish_batch(time, data));
 
+        // Completed merges are counted as they are logged, for metrics.
+        let merged = Rc::new(Cell::new(0));
+        let merged2 = merged.clone();
         let mut differential_logger = BatchLogger::new(differential_events.clone());
         worker
             .log_register()
-            .insert::<DifferentialEvent,_>("differential/arrange", move |time, data| differential_logger.publish_batch(time, data));
+            .insert::<DifferentialEvent,_>("differential/arrange", move |time, data| {
+                let completed = data.iter().filter(|(_, _, event)| match event {
+                    DifferentialEvent::Merge(merge) => merge.complete.is_some(),
+                    _ => false,
+                });
+                merged2.set(merged2.get() + completed.count());
+                differential_logger.publish_batch(time, data)
+            });
 
-        let mut sequencer = Sequencer::new(worker, timer);
+        let mut sequencer = Sequencer::<Tagged>::new(worker, timer);
+
+        // The subscriptions this worker reports, by connection and the name the connection gave them.
+        let mut subscribers = HashSet::new();
+
+        // The first worker replays the journal through the sequencer, so that every worker executes its
+        // commands in the same order, and then appends to it. Workers sequence no commands of connections
+        // until the replayed commands have all been executed.
+        let mut journal = None;
+        let mut replaying = journal_path.is_some();
+        if let Some(path) = journal_path.as_ref() {
+            if worker.index() == 0 {
+                let (commands, length) = interactive::journal::replay::<Value, _>(path).expect("failed to read journal");
+                println!("{:?}\tReplaying {} commands", timer.elapsed(), commands.len());
+                for command in commands.into_iter() {
+                    sequencer.push((0, REPLAYED, Some(command)));
+                }
+                sequencer.push((0, REPLAYED, None));
+                journal = Some(Journal::resume(path, length).expect("failed to open journal"));
+            }
+        }
+
+        let mut measured_at = None;
 
         let mut done = false;
         while !done {
 
-            {   // Check out channel status.
+            if !replaying {   // Check out channel status.
                 let mut lock = recv.lock().expect("Mutex poisoned");
-                while let Some(command) = lock.pop_front() {
-                    sequencer.push(command);
+                // The worker that takes a command from the queue responds to it. Each worker takes
+                // at most one command of each connection at a time, so that none delays the others.
+                let mut taken = HashSet::new();
+                let mut remaining = VecDeque::new();
+                while let Some((connection, command)) = lock.pop_front() {
+                    if taken.insert(connection) { sequencer.push((worker.index(), connection, Some(command))); }
+                    else { remaining.push_back((connection, command)); }
                 }
+                *lock = remaining;
             }
 
             // Dequeue and act on commands.
             // One at a time, so that Shutdown works.
-            if let Some(command) = sequencer.next() {
+            if let Some((origin, connection, command)) = sequencer.next() {
+                let command = match command {
+                    Some(command) => command,
+                    None => {
+                        println!("{:?}\tReplayed journal", timer.elapsed());
+                        replaying = false;
+                        continue;
+                    },
+                };
                 println!("{:?}\tExecuting {:?}", timer.elapsed(), command);
                 if command == Command::Shutdown {
+                    closed.store(true, Ordering::SeqCst);
                     done = true;
                 }
-                command.execute(&mut manager, worker);
+                let subscription = command.subscription();
+                // Replayed commands are already in the journal, and have no connection to respond to.
+                let replayed = connection == REPLAYED;
+                let record = if journal.is_some() && !replayed && command.journaled() { Some(command.clone()) } else { None };
+                let response = command.execute(&mut manager, worker, origin);
+                match (journal.as_mut(), record, &response) {
+                    (_, _, Response::Failed(_)) => { },
+                    (Some(journal), Some(command), _) => journal.record(&command).expect("failed to append to journal"),
+                    _ => { },
+                }
+                if origin == worker.index() && !replayed {
+                    if let (Some((name, subscribe)), Response::Executed(_)) = (subscription, &response) {
+                        if let Some(owner) = subscriber(&name) {
+                            if subscribe { subscribers.insert(owner); }
+                            else { subscribers.remove(&owner); }
+                        }
+                    }
+                    // The connection may have closed, in which case the response is discarded.
+                    if let Some(sender) = responders.lock().expect("mutex poisoned").get(&connection) {
+                        let _ = sender.send(response);
+                    }
+                }
             }
 
             worker.step();
+            manager.poll_latency();
+
+            // Send the updates and progress of subscriptions to their subscribers, under the names they gave.
+            for response in manager.poll_subscriptions(worker.index()) {
+                let (owner, response) = match response {
+                    Response::Updates(name, updates) => match subscriber(&name) {
+                        Some((connection, name)) => ((connection, name.clone()), Response::Updates(name, updates)),
+                        None => continue,
+                    },
+                    Response::Progress(name, frontier) => match subscriber(&name) {
+                        Some((connection, name)) => ((connection, name.clone()), Response::Progress(name, frontier)),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                if subscribers.contains(&owner) {
+                    if let Some(sender) = responders.lock().expect("mutex poisoned").get(&owner.0) {
+                        let _ = sender.send(response);
+                    }
+                }
+            }
+
+            // Unsubscribe the subscriptions of connections that have closed.
+            if !subscribers.is_empty() {
+                let open = responders.lock().expect("mutex poisoned");
+                let ended = subscribers.iter().filter(|(connection, _)| !open.contains_key(connection)).cloned().collect::<Vec<_>>();
+                drop(open);
+                for (connection, name) in ended.into_iter() {
+                    sequencer.push((worker.index(), connection, Some(Command::Unsubscribe(subscription_name(connection, &name)))));
+                    subscribers.remove(&(connection, name));
+                }
+            }
+
+            if serve_metrics && measured_at.map_or(true, |at: Instant| at.elapsed() >= MEASURE_INTERVAL) {
+                let metrics = Metrics::measure(&mut manager, worker.index(), timer.elapsed(), merged.get());
+                measured.lock().expect("mutex poisoned").insert(worker.index(), metrics);
+                measured_at = Some(Instant::now());
+            }
         }
 
         println!("Shutting down");
@@ -88,7 +331,7 @@ fn main() {
 
     }).expect("Timely computation did not initialize cleanly");
 
-    println!("Now accepting commands");
+    println!("Now accepting commands on {}", address);
 
     // Detached thread for client connections.
     std::thread::Builder::new()
@@ -96,22 +339,166 @@ fn main() {
         .spawn(move || {
 
             use std::net::TcpListener;
-            let listener = TcpListener::bind("127.0.0.1:8000".to_string()).expect("failed to bind listener");
-            for mut stream in listener.incoming() {
-                let mut stream = stream.expect("listener error");
+            let listener = TcpListener::bind(&address[..]).expect("failed to bind listener");
+            for (connection, stream) in listener.incoming().enumerate() {
+                let stream = stream.expect("listener error");
                 let send = command_queue2.clone();
+                let responders = responders2.clone();
+                let closed = closed2.clone();
+                let authorizer = authorizer.clone();
                 std::thread::Builder::new()
                     .name("Client".to_string())
-                    .spawn(move || {
-                        while let Ok(command) = bincode::deserialize_from::<_,Command<Value>>(&mut stream) {
-                            send.lock()
-                                .expect("mutex poisoned")
-                                .push_back(command);
-                        }
-                    })
+                    .spawn(move || serve(connection, stream, send, responders, closed, authorizer, limits))
                     .expect("failed to create thread");
             }
 
         })
         .expect("Failed to spawn listen thread");
+
+    if let Some(metrics_address) = metrics_address {
+        println!("Serving metrics on http://{}/metrics", metrics_address);
+        std::thread::Builder::new()
+            .name("Metrics".to_string())
+            .spawn(move || {
+                use std::net::TcpListener;
+                let listener = TcpListener::bind(&metrics_address[..]).expect("failed to bind metrics listener");
+                for stream in listener.incoming() {
+                    match stream {
+                        // Requests are answered one at a time, as each is brief.
+                        Ok(stream) => {
+                            if let Err(error) = serve_metrics_request(stream, &measured2, &command_queue3) {
+                                println!("Metrics request failed: {}", error);
+                            }
+                        },
+                        Err(error) => println!("Metrics listener error: {}", error),
+                    }
+                }
+            })
+            .expect("Failed to spawn metrics thread");
+    }
+}
+
+/// Answers an HTTP request for `/metrics` with the latest metrics of each worker.
+///
+/// Requests for other paths are answered as not found.
+fn serve_metrics_request(stream: TcpStream, measured: &Measured, queue: &Queue) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Headers end at an empty line; their contents are not needed.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let mut workers = measured.lock().expect("mutex poisoned").values().cloned().collect::<Vec<_>>();
+            workers.sort_by_key(|metrics| metrics.worker);
+            let queued = queue.lock().expect("mutex poisoned").len();
+            ("200 OK", interactive::metrics::render(&workers, queued))
+        },
+        _ => ("404 Not Found", "Metrics are served at /metrics\n".to_string()),
+    };
+
+    write!(writer, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)?;
+    writer.flush()
+}
+
+/// Reads commands from a connection, and writes their responses back to it.
+///
+/// Clients may send commands either with `bincode` or as lines of JSON, in the encodings
+/// of `interactive::wire`, and receive responses in the same encoding. The first byte of
+/// a connection determines which: JSON messages start with `{`, which no bincode message
+/// of a supported version does. Once `closed` is set, commands are refused rather than queued.
+///
+/// With an `authorizer`, the connection must authenticate, and its commands are refused
+/// unless its principal is authorized to issue them. Commands beyond the `limits` of the
+/// connection are refused as `Busy`, with the time after which to send them again.
+///
+/// The names of subscriptions are those of the connection, and others may use the same names.
+/// The subscriptions of the connection are removed once it closes.
+fn serve(connection: usize, stream: TcpStream, send: Queue, responders: Responders, closed: Arc<AtomicBool>, authorizer: Option<Arc<Authorizer>>, limits: Limits) {
+    use interactive::wire;
+
+    let mut writer = stream.try_clone().expect("failed to clone stream");
+    let mut reader = BufReader::new(stream);
+    let json = reader.fill_buf().map(|bytes| bytes.first() == Some(&b'{')).unwrap_or(false);
+
+    let (sender, receiver) = channel::<Response<Value>>();
+    responders.lock().expect("mutex poisoned").insert(connection, sender);
+    let responder = std::thread::Builder::new()
+        .name("Responder".to_string())
+        .spawn(move || {
+            while let Ok(response) = receiver.recv() {
+                let result = if json { wire::write_json(&mut writer, &response) } else { wire::write_bincode(&mut writer, &response) };
+                if result.and_then(|()| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        })
+        .expect("failed to create thread");
+
+    let respond = |response| {
+        if let Some(sender) = responders.lock().expect("mutex poisoned").get(&connection) {
+            let _ = sender.send(response);
+        }
+    };
+
+    // The principal the connection has authenticated as, if any.
+    let mut principal = None;
+    let mut bucket = Bucket { tokens: limits.rate.map(Bucket::capacity).unwrap_or(0.0), updated: Instant::now() };
+
+    loop {
+        let command = if json { wire::read_json(&mut reader) } else { Some(wire::read_bincode(&mut reader)) };
+        match command {
+            Some(Ok(Command::Authenticate(token))) => {
+                let response = match authorizer.as_ref() {
+                    Some(authorizer) => {
+                        principal = authorizer.authenticate(&token).cloned();
+                        match principal.as_ref() {
+                            Some(found) => Response::Executed(vec![format!("Authenticated: {}", found.name)]),
+                            None => Response::Failed("Authentication failed".to_string()),
+                        }
+                    },
+                    None => Response::Executed(vec!["Authentication is not required".to_string()]),
+                };
+                respond(response);
+            },
+            Some(Ok(command)) => {
+                let refused = authorizer.as_ref().and_then(|authorizer| authorizer.authorize(principal.as_ref(), &command).err());
+                if let Some(reason) = refused {
+                    respond(Response::Failed(reason));
+                }
+                else if closed.load(Ordering::SeqCst) {
+                    respond(Response::Failed("Server is shutting down".to_string()));
+                }
+                else {
+                    let mut queue = send.lock().expect("mutex poisoned");
+                    // A full queue drains as workers take commands, which they do about once per step.
+                    let refused =
+                    if queue.iter().filter(|(from, _)| *from == connection).count() >= limits.queued { Some(Duration::from_millis(10)) }
+                    else { limits.rate.and_then(|rate| bucket.take(rate).err()) };
+                    match refused {
+                        Some(wait) => { drop(queue); respond(Response::Busy(wait)); },
+                        None => queue.push_back((connection, owned(connection, command))),
+                    }
+                }
+            },
+            Some(Err(error)) => {
+                // Undecodable commands end the connection, as the position of the next command is unknown.
+                println!("Connection {} closed: {}", connection, error);
+                break;
+            }
+            None => break,
+        }
+    }
+
+    // Dropping the sender ends the responder, once it has written outstanding responses.
+    responders.lock().expect("mutex poisoned").remove(&connection);
+    responder.join().expect("responder panicked");
 }
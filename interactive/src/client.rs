@@ -0,0 +1,418 @@
+//! A typed client for the interactive server.
+//!
+//! The server accepts a stream of commands on a TCP socket, each encoded with
+//! `bincode` in the versioned format of the `wire` module. The `Client` type
+//! wraps such a socket with methods for each command, and reconnects to the
+//! server if a command cannot be written or a response cannot be read. The
+//! `AsyncClient` type moves a client to a background thread, so that issuing
+//! commands does not block on the network; commands are written in the order they
+//! are issued, and responses are forwarded as they are read.
+//!
+//! The server responds to each command with a `Response`, reporting its results or
+//! its failure, which `Client::receive` reads. A server may refuse commands beyond its
+//! limits with `Busy`, after which they may be sent again. Responses that are not read wait in
+//! the connection. The output of a plan can be observed by subscribing to it, after
+//! which the server also sends responses reporting its updates and progress, until
+//! the subscription is cancelled. On reconnecting, the client subscribes again,
+//! resuming each subscription from the last progress it read.
+
+use std::io;
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use command::Response;
+use load::Format;
+use schema::Schema;
+
+use super::{Command, Query, Plan, Time, Diff};
+
+/// How long the thread of an `AsyncClient` waits for a response before sending enqueued commands.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A connection to an interactive server.
+pub struct Client<Value> {
+    address: String,
+    socket: Option<TcpStream>,
+    /// The number of times to attempt to reconnect before reporting an error.
+    pub retries: usize,
+    /// The time to wait between attempts to reconnect.
+    pub backoff: Duration,
+    /// The token with which the client authenticated, if any, presented again on reconnecting.
+    token: Option<String>,
+    /// The commands resuming the subscriptions of the client, by the names their updates are
+    /// reported under, each from the last progress read; these are sent again on reconnecting.
+    subscriptions: Vec<(String, Command<Value>)>,
+}
+
+impl<Value: Serialize> Client<Value> {
+
+    /// Connects to the server at `address`.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let socket = TcpStream::connect(address)?;
+        Ok(Client {
+            address: address.to_string(),
+            socket: Some(socket),
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            token: None,
+            subscriptions: Vec::new(),
+        })
+    }
+
+    /// Connects to the server again, authenticating again if the client had authenticated,
+    /// and resuming its subscriptions.
+    fn reconnect(&mut self) -> Result<(), String> {
+        let mut attempts = 0;
+        loop {
+            let connected = TcpStream::connect(&self.address[..]).and_then(|mut socket| {
+                if let Some(token) = self.token.as_ref() {
+                    ::wire::write_bincode(&mut socket, &Command::<Value>::Authenticate(token.clone()))?;
+                }
+                for (_name, command) in self.subscriptions.iter() {
+                    ::wire::write_bincode(&mut socket, command)?;
+                }
+                Ok(socket)
+            });
+            match connected {
+                Ok(socket) => {
+                    self.socket = Some(socket);
+                    return Ok(());
+                },
+                Err(error) => {
+                    if attempts >= self.retries {
+                        return Err(format!("failed to connect to {}: {}", self.address, error));
+                    }
+                    attempts += 1;
+                    ::std::thread::sleep(self.backoff);
+                }
+            }
+        }
+    }
+
+    /// Sends a command to the server.
+    ///
+    /// If the command cannot be written, the client reconnects and writes the command
+    /// again, after authenticating again if it had authenticated and resuming its subscriptions,
+    /// whose responses are read before that of the command. A command whose write failed part way
+    /// through may be lost, but will not be applied twice, as the server discards a connection once
+    /// it cannot decode it.
+    pub fn send(&mut self, command: &Command<Value>) -> Result<(), String> {
+        let mut attempts = 0;
+        loop {
+            if self.socket.is_none() {
+                self.reconnect()?;
+            }
+
+            let result = ::wire::write_bincode(self.socket.as_mut().unwrap(), command);
+            match result {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    self.socket = None;
+                    if attempts >= self.retries {
+                        return Err(format!("failed to send to {}: {}", self.address, error));
+                    }
+                    attempts += 1;
+                }
+            }
+        }
+    }
+
+    /// Reads the earliest response that has not been read.
+    ///
+    /// Responses to commands are read in the order the commands were sent, interleaved
+    /// with the updates and progress of subscriptions.
+    ///
+    /// If a response cannot be read, the client reconnects, as `send` does, and reads from
+    /// the new connection. Responses the server had not yet written to the old connection are
+    /// lost, other than those of subscriptions, which resume from the last progress read; updates
+    /// read beyond that progress are read again.
+    pub fn receive(&mut self) -> Result<Response<Value>, String> where Value: DeserializeOwned {
+        let mut attempts = 0;
+        loop {
+            if self.socket.is_none() {
+                self.reconnect()?;
+            }
+
+            let result = ::wire::read_bincode(self.socket.as_mut().unwrap());
+            match result {
+                Ok(response) => {
+                    if let Response::Progress(name, frontier) = &response {
+                        self.progressed(name, frontier);
+                    }
+                    return Ok(response);
+                },
+                Err(error) => {
+                    self.socket = None;
+                    if attempts >= self.retries {
+                        return Err(format!("failed to receive from {}: {}", self.address, error));
+                    }
+                    attempts += 1;
+                }
+            }
+        }
+    }
+
+    /// Reads the earliest response that has not been read, if one arrives within `timeout`,
+    /// which must not be zero.
+    pub fn receive_timeout(&mut self, timeout: Duration) -> Result<Option<Response<Value>>, String> where Value: DeserializeOwned {
+        if let Some(socket) = self.socket.as_ref() {
+            let mut byte = [0u8];
+            let _ = socket.set_read_timeout(Some(timeout));
+            let peeked = socket.peek(&mut byte);
+            let _ = socket.set_read_timeout(None);
+            match peeked {
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut => return Ok(None),
+                // A response has started to arrive, or the connection has failed and `receive` reconnects.
+                _ => { },
+            }
+        }
+        self.receive().map(Some)
+    }
+
+    /// Records that the subscription reported as `name` has progressed to `frontier`.
+    fn progressed(&mut self, name: &str, frontier: &[Time]) {
+        if let Some((_name, command)) = self.subscriptions.iter_mut().find(|(subscription, _)| subscription == name) {
+            if let Some(resumed) = resumed_from(command) {
+                *resumed = frontier.to_vec();
+            }
+        }
+    }
+
+    /// Sends `command`, a `Resubscribe` within any namespaces, and records it to send again on reconnecting.
+    fn resubscribe(&mut self, name: String, command: Command<Value>) -> Result<(), String> {
+        self.send(&command)?;
+        self.subscriptions.retain(|(subscription, _)| subscription != &name);
+        self.subscriptions.push((name, command));
+        Ok(())
+    }
+
+    /// Authenticates the connection with `token`, also presented on reconnecting.
+    pub fn authenticate(&mut self, token: &str) -> Result<(), String> {
+        self.token = Some(token.to_string());
+        self.send(&Command::Authenticate(token.to_string()))
+    }
+
+    /// Installs the query and publishes its rules.
+    pub fn query(&mut self, query: Query<Value>) -> Result<(), String> {
+        self.send(&Command::Query(query))
+    }
+
+    /// Advances all inputs and traces to `time`.
+    pub fn advance_time(&mut self, time: Time) -> Result<(), String> {
+        self.send(&Command::AdvanceTime(time))
+    }
+
+    /// Creates a new named input, with initial input.
+    pub fn create_input(&mut self, name: &str, initial: Vec<Vec<Value>>) -> Result<(), String> {
+        self.send(&Command::CreateInput(name.to_string(), initial))
+    }
+
+    /// Introduces updates to a named input.
+    pub fn update_input(&mut self, name: &str, updates: Vec<(Vec<Value>, Time, Diff)>) -> Result<(), String> {
+        self.send(&Command::UpdateInput(name.to_string(), updates))
+    }
+
+    /// Creates a new named input of upserts, whose keys have `arity` columns.
+    pub fn create_upsert_input(&mut self, name: &str, arity: usize) -> Result<(), String> {
+        self.send(&Command::CreateUpsertInput(name.to_string(), arity))
+    }
+
+    /// Introduces upserts to a named input, each of a key, its new value or `None`, and a time.
+    pub fn upsert_input(&mut self, name: &str, upserts: Vec<(Vec<Value>, Option<Vec<Value>>, Time)>) -> Result<(), String> {
+        self.send(&Command::UpsertInput(name.to_string(), upserts))
+    }
+
+    /// Introduces the tuples of the delimited file at `path`, which the server reads, to a named input.
+    pub fn load_file(&mut self, name: &str, path: &str, format: Format) -> Result<(), String> {
+        self.send(&Command::LoadFile(name.to_string(), path.to_string(), format))
+    }
+
+    /// Closes a named input.
+    pub fn close_input(&mut self, name: &str) -> Result<(), String> {
+        self.send(&Command::CloseInput(name.to_string()))
+    }
+
+    /// Installs `plan`, and receives its updates and progress as `name` until unsubscribed.
+    pub fn subscribe(&mut self, name: &str, plan: Plan<Value>) -> Result<(), String> {
+        self.resubscribe(name.to_string(), Command::Resubscribe(name.to_string(), plan, vec![Time::default()]))
+    }
+
+    /// Stops receiving the updates of the subscription `name`.
+    pub fn unsubscribe(&mut self, name: &str) -> Result<(), String> {
+        self.subscriptions.retain(|(subscription, _)| subscription != name);
+        self.send(&Command::Unsubscribe(name.to_string()))
+    }
+
+    /// Uninstalls the input or query publishing `name`, with the other names it publishes.
+    pub fn uninstall(&mut self, name: &str) -> Result<(), String> {
+        self.send(&Command::Uninstall(name.to_string()))
+    }
+
+    /// Publishes `plan` as `name`, installing it unless it is already maintained.
+    pub fn publish(&mut self, name: &str, plan: Plan<Value>) -> Result<(), String> {
+        self.send(&Command::Publish(name.to_string(), plan))
+    }
+
+    /// Requests the plan published as `name`.
+    pub fn get_by_name(&mut self, name: &str) -> Result<(), String> {
+        self.send(&Command::GetByName(name.to_string()))
+    }
+
+    /// Requests the numbers of traces, batches, and updates maintained by installations.
+    pub fn usage(&mut self) -> Result<(), String> {
+        self.send(&Command::Usage)
+    }
+
+    /// Issues `command` within `namespace`, qualifying the names it uses.
+    pub fn namespaced(&mut self, namespace: &str, command: Command<Value>) -> Result<(), String> {
+        match command {
+            Command::Subscribe(name, plan) => {
+                let reported = ::namespace::qualify(namespace, &name);
+                let command = Command::Resubscribe(name, plan, vec![Time::default()]);
+                self.resubscribe(reported, Command::Namespaced(namespace.to_string(), Box::new(command)))
+            },
+            Command::Unsubscribe(name) => {
+                let reported = ::namespace::qualify(namespace, &name);
+                self.subscriptions.retain(|(subscription, _)| subscription != &reported);
+                self.send(&Command::Namespaced(namespace.to_string(), Box::new(Command::Unsubscribe(name))))
+            },
+            command => self.send(&Command::Namespaced(namespace.to_string(), Box::new(command))),
+        }
+    }
+
+    /// Limits the bytes cached traces may occupy, or removes the limit.
+    pub fn budget(&mut self, budget: Option<usize>) -> Result<(), String> {
+        self.send(&Command::Budget(budget))
+    }
+
+    /// Requests a description of the inputs, traces, published names, and progress of the server.
+    pub fn describe(&mut self) -> Result<(), String> {
+        self.send(&Command::Describe)
+    }
+
+    /// Retains `history` before the current time in maintained traces, for queries as of past times.
+    pub fn history(&mut self, history: Time) -> Result<(), String> {
+        self.send(&Command::History(history))
+    }
+
+    /// Requests the contents of the collection of the maintained `plan` at `time`.
+    pub fn query_as_of(&mut self, plan: Plan<Value>, time: Time) -> Result<(), String> {
+        self.send(&Command::QueryAsOf(plan, time))
+    }
+
+    /// Declares the names and types of the columns of the collection `name`.
+    pub fn declare_schema(&mut self, name: &str, schema: Schema) -> Result<(), String> {
+        self.send(&Command::DeclareSchema(name.to_string(), schema))
+    }
+
+    /// Requests the schema of the collection `name`.
+    pub fn get_schema(&mut self, name: &str) -> Result<(), String> {
+        self.send(&Command::GetSchema(name.to_string()))
+    }
+
+    /// Installs the SQL query `text`, publishing its result as `name`.
+    pub fn sql(&mut self, name: &str, text: &str) -> Result<(), String> {
+        self.send(&Command::Sql(name.to_string(), text.to_string()))
+    }
+
+    /// Installs the rules of the Datalog program `text`, publishing each predicate they define.
+    pub fn datalog(&mut self, text: &str) -> Result<(), String> {
+        self.send(&Command::Datalog(text.to_string()))
+    }
+
+    /// Terminates the server.
+    pub fn shutdown(mut self) -> Result<(), String> {
+        self.send(&Command::Shutdown)
+    }
+}
+
+/// The frontier from which `command`, a `Resubscribe` within any namespaces, resumes.
+fn resumed_from<Value>(command: &mut Command<Value>) -> Option<&mut Vec<Time>> {
+    match command {
+        Command::Resubscribe(_, _, frontier) => Some(frontier),
+        Command::Namespaced(_, command) => resumed_from(command),
+        _ => None,
+    }
+}
+
+/// A client whose commands are written, and whose responses are read, by a background thread.
+pub struct AsyncClient<Value> {
+    sender: Option<Sender<Command<Value>>>,
+    responses: Receiver<Response<Value>>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl<Value: Serialize+DeserializeOwned+Send+'static> AsyncClient<Value> {
+
+    /// Moves `client` to a background thread.
+    pub fn new(mut client: Client<Value>) -> Self {
+        let (sender, receiver) = channel();
+        let (responder, responses) = channel();
+        let thread = ::std::thread::Builder::new()
+            .name("Client".to_string())
+            .spawn(move || {
+                loop {
+                    // Send the commands enqueued so far, and stop once none remain and none can be.
+                    loop {
+                        match receiver.try_recv() {
+                            Ok(command) => client.send(&command)?,
+                            Err(TryRecvError::Empty) => break,
+                            Err(TryRecvError::Disconnected) => return Ok(()),
+                        }
+                    }
+                    // Responses are discarded once they can no longer be received.
+                    if let Some(response) = client.receive_timeout(POLL_INTERVAL)? {
+                        let _ = responder.send(response);
+                    }
+                }
+            })
+            .expect("failed to create thread");
+
+        AsyncClient {
+            sender: Some(sender),
+            responses,
+            thread: Some(thread),
+        }
+    }
+
+    /// Waits for the earliest response that has not been received.
+    ///
+    /// An error indicates that the background thread has stopped; `finish` reports why.
+    pub fn receive(&self) -> Result<Response<Value>, String> {
+        self.responses
+            .recv()
+            .map_err(|_| "client thread has stopped".to_string())
+    }
+
+    /// Returns the earliest response that has not been received, if one has been read.
+    pub fn try_receive(&self) -> Option<Response<Value>> {
+        self.responses.try_recv().ok()
+    }
+
+    /// Enqueues a command to send to the server.
+    ///
+    /// An error indicates that the background thread has failed to send an earlier
+    /// command, and has stopped; `finish` reports the error.
+    pub fn send(&self, command: Command<Value>) -> Result<(), String> {
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(command)
+            .map_err(|_| "client thread has stopped".to_string())
+    }
+
+    /// Waits for all enqueued commands to be sent, reporting any error.
+    pub fn finish(mut self) -> Result<(), String> {
+        self.sender = None;
+        self.thread
+            .take()
+            .unwrap()
+            .join()
+            .map_err(|_| "client thread panicked".to_string())?
+    }
+}
@@ -0,0 +1,48 @@
+//! Views of logging collections aggregated across workers.
+//!
+//! Each worker publishes logging collections of only its own events. The views here group
+//! their tuples by operator or channel, which exchanges them among workers, and publish
+//! totals over all workers under `logs/cluster/`:
+//!
+//! * `logs/cluster/operates`: operator, name, and the number of workers hosting it.
+//! * `logs/cluster/schedule`: operator, and the number of times it was scheduled.
+//! * `logs/cluster/messages`: channel, and the numbers of messages and records sent.
+//! * `logs/cluster/arrange/batch`: operator, and the numbers of batches and of their updates.
+//! * `logs/cluster/arrange/merge`: operator, and the numbers of completed merges and of their updates.
+//! * `logs/cluster/arrange/shortfall`: operator, and the number of shortfalls and their total.
+//! * `logs/cluster/arrange/drop`: operator, and the numbers of dropped batches and of their updates.
+//! * `logs/cluster/arrange/share`: operator, and the number of handles to its traces.
+
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Aggregation, Predicate};
+use plan::filter::SecondArgument;
+use sql::{FromLiteral, Literal};
+use {Query, Rule};
+
+/// A query publishing the aggregated views of the logging collections.
+///
+/// The logging collections must be published, as `Manager::publish_timely_logging` and
+/// `Manager::publish_differential_logging` do, before the query is installed.
+pub fn query<V: Data+Hash+FromLiteral>() -> Result<Query<V>, String> {
+
+    // Selects the tuples whose column `index` is true: starts of scheduling, sends of messages,
+    // as each is also logged as it is received, and completions of merges.
+    let truth = V::from_literal(&Literal::Bool(true))?;
+    let holds = |index| Predicate::Equal(index, SecondArgument::Constant(truth.clone()));
+
+    let views = vec![
+        ("logs/cluster/operates", Plan::source("logs/timely/operates").project(vec![0, 2]).aggregate(vec![0, 1], vec![Aggregation::Count])),
+        ("logs/cluster/schedule", Plan::source("logs/timely/schedule").filter(holds(1)).aggregate(vec![0], vec![Aggregation::Count])),
+        ("logs/cluster/messages", Plan::source("logs/timely/messages").filter(holds(1)).aggregate(vec![0], vec![Aggregation::Count, Aggregation::Sum(5)])),
+        ("logs/cluster/arrange/batch", Plan::source("logs/differential/arrange/batch").aggregate(vec![0], vec![Aggregation::Count, Aggregation::Sum(1)])),
+        ("logs/cluster/arrange/merge", Plan::source("logs/differential/arrange/merge").filter(holds(5)).aggregate(vec![0], vec![Aggregation::Count, Aggregation::Sum(4)])),
+        ("logs/cluster/arrange/shortfall", Plan::source("logs/differential/arrange/shortfall").aggregate(vec![0], vec![Aggregation::Count, Aggregation::Sum(2)])),
+        ("logs/cluster/arrange/drop", Plan::source("logs/differential/arrange/drop").aggregate(vec![0], vec![Aggregation::Count, Aggregation::Sum(1)])),
+        ("logs/cluster/arrange/share", Plan::source("logs/differential/arrange/share").aggregate(vec![0], vec![Aggregation::Count])),
+    ];
+
+    Ok(views.into_iter().fold(Query::new(), |query, (name, plan)| query.add_rule(Rule { name: name.to_string(), plan })))
+}
@@ -7,11 +7,33 @@ use timely::worker::Worker;
 use differential_dataflow::{Data};
 
 use super::{Query, Rule, Plan, Time, Diff, Manager};
+use manager::UpsertSession;
+use load::{Format, FromField, load_file};
+use schema::{Schema, Typed, check};
+use sql::{FromLiteral, compile};
+use plan::{Aggregable, Evaluable, Bindings};
+
+/// The result of a command, returned to the client that issued it.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum Response<Value> {
+    /// The command was executed, and reported the lines.
+    Executed(Vec<String>),
+    /// The command could not be executed, for the reason given.
+    Failed(String),
+    /// Updates to a named subscription.
+    Updates(String, Vec<(Vec<Value>, Time, Diff)>),
+    /// The frontier of a named subscription, before which its updates are complete.
+    Progress(String, Vec<Time>),
+    /// The contents of a collection, as held by the worker that received the command.
+    Contents(Vec<(Vec<Value>, Diff)>),
+    /// The command was refused, as the server is busy, and may be sent again after the duration.
+    Busy(::std::time::Duration),
+}
 
 /// Commands accepted by the system.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Command<Value> {
-    /// Installs the query and publishes public rules.
+    /// Optimizes and installs the query, and publishes public rules.
     Query(Query<Value>),
     /// Advances all inputs and traces to `time`, and advances computation.
     AdvanceTime(Time),
@@ -21,39 +43,170 @@ pub enum Command<Value> {
     UpdateInput(String, Vec<(Vec<Value>, Time, Diff)>),
     /// Closes a specified input.
     CloseInput(String),
-    /// Terminates the system.
+    /// Reports recommended arrangements, and directs whether to install them automatically.
+    Advise(bool),
+    /// Reports estimated work for a plan, given numbers of updates to named sources.
+    Estimate(Plan<Value>, Vec<(String, usize)>),
+    /// Compacts maintained traces, either all or those of a named collection, and purges unreferenced interned strings.
+    Vacuum(Option<String>),
+    /// Completes the computation of submitted updates, and terminates the system.
     Shutdown,
+    /// Reports how a plan would be rendered, and the arrangements it would reuse and build.
+    Explain(Plan<Value>),
+    /// Installs a plan, and sends its updates and progress to the client until unsubscribed.
+    Subscribe(String, Plan<Value>),
+    /// Stops sending the updates of a named subscription, and uninstalls its dataflow.
+    Unsubscribe(String),
+    /// Uninstalls the input or query publishing a name, and releases its traces.
+    Uninstall(String),
+    /// Publishes a plan under a name, installing it unless it is already maintained.
+    Publish(String, Plan<Value>),
+    /// Reports the plan published under a name.
+    GetByName(String),
+    /// Reports the numbers of traces, batches, and updates maintained by installations.
+    Usage,
+    /// Executes a command within a namespace, qualifying the names it uses.
+    ///
+    /// Within a namespace, `Shutdown` uninstalls the namespace, and `Usage` reports
+    /// only its installations.
+    Namespaced(String, Box<Command<Value>>),
+    /// Sets the number of bytes cached traces may occupy, evicting the least recently used beyond it.
+    Budget(Option<usize>),
+    /// Reports the input sessions, maintained traces, published names, and progress of the system.
+    Describe,
+    /// Retains a duration of history before the current time in maintained traces.
+    History(Time),
+    /// Reports the contents of the collection of a maintained plan at a past time.
+    QueryAsOf(Plan<Value>, Time),
+    /// Creates a new named input of upserts, whose keys have the indicated number of columns.
+    CreateUpsertInput(String, usize),
+    /// Introduces upserts to a specified input, each of a key, its new value or `None`, and a time.
+    UpsertInput(String, Vec<(Vec<Value>, Option<Vec<Value>>, Time)>),
+    /// Introduces the tuples of the lines of a delimited file, by path, to a specified input, or none if any line is invalid.
+    LoadFile(String, String, Format),
+    /// Declares the names and types of the columns of a named collection, against which
+    /// tuples introduced to it and plans reading it are checked.
+    DeclareSchema(String, Schema),
+    /// Reports the schema of a named collection.
+    GetSchema(String),
+    /// Installs a SQL query reading collections with schemas, and publishes its result under a name.
+    Sql(String, String),
+    /// Installs the rules of a Datalog program, publishing each predicate they define.
+    Datalog(String),
+    /// Authenticates the connection with a token; see `auth`.
+    ///
+    /// Connections handle this command rather than sequencing it, and it has no effect if executed.
+    Authenticate(String),
+    /// Installs a plan, as `Subscribe` does, but sends only its updates at times beyond a frontier,
+    /// for a client resuming a subscription whose updates before the frontier it has received.
+    ///
+    /// Updates before the frontier are also sent if the traces the plan reads have advanced them beyond it.
+    Resubscribe(String, Plan<Value>, Vec<Time>),
 }
 
-impl<Value: Data+Hash> Command<Value> {
+impl<Value: Data+Hash+Aggregable+Evaluable+FromField+Typed+FromLiteral> Command<Value> {
 
-    /// Executes a command.
-    pub fn execute<A: Allocate>(self, manager: &mut Manager<Value>, worker: &mut Worker<A>) {
+    /// Indicates if the command changes the state of the system, and should be journaled.
+    ///
+    /// Commands that only report are not journaled, nor are subscriptions, whose clients do
+    /// not survive a restart, nor `Shutdown`, which would end a recovered system.
+    pub fn journaled(&self) -> bool {
+        match self {
+            Command::Query(_) => true,
+            Command::AdvanceTime(_) => true,
+            Command::CreateInput(_, _) => true,
+            Command::UpdateInput(_, _) => true,
+            Command::CloseInput(_) => true,
+            Command::CreateUpsertInput(_, _) => true,
+            Command::UpsertInput(_, _) => true,
+            Command::LoadFile(_, _, _) => true,
+            Command::Advise(_) => true,
+            Command::Uninstall(_) => true,
+            Command::Publish(_, _) => true,
+            Command::Budget(_) => true,
+            Command::History(_) => true,
+            Command::DeclareSchema(_, _) => true,
+            Command::Sql(_, _) => true,
+            Command::Datalog(_) => true,
+            Command::Namespaced(_, command) => **command == Command::Shutdown || command.journaled(),
+            _ => false,
+        }
+    }
+
+    /// The name of the subscription the command creates, with `true`, or removes, with `false`.
+    pub fn subscription(&self) -> Option<(String, bool)> {
+        match self {
+            Command::Subscribe(name, _) => Some((name.clone(), true)),
+            Command::Resubscribe(name, _, _) => Some((name.clone(), true)),
+            Command::Unsubscribe(name) => Some((name.clone(), false)),
+            Command::Namespaced(namespace, command) => (**command).clone().qualify(namespace).subscription(),
+            _ => None,
+        }
+    }
+
+    /// Executes a command, and reports its results.
+    ///
+    /// Reports describe the state of this worker, whose share of each collection may
+    /// differ from those of other workers. The command was received by worker `origin`,
+    /// which reports the updates of subscriptions it creates.
+    pub fn execute<A: Allocate>(self, manager: &mut Manager<Value>, worker: &mut Worker<A>, origin: usize) -> Response<Value> {
+
+        let mut report = Vec::new();
 
         match self {
 
             Command::Query(query) => {
 
+                let names = query.rules.iter().map(|rule| rule.name.clone()).collect();
+
+                // Checks each rule before installing any, with the schemas of the rules before it.
+                let mut schemas = manager.schemas.clone();
+                let mut rules = Vec::new();
+                for Rule { name, plan } in query.rules.into_iter() {
+                    match check(&plan, &schemas) {
+                        Ok((plan, schema)) => {
+                            if let Some(schema) = schema { schemas.insert(name.clone(), schema); }
+                            rules.push(Rule { name, plan });
+                        },
+                        Err(reason) => return Response::Failed(format!("Rule {:?}: {}", name, reason)),
+                    }
+                }
+                manager.schemas = schemas;
+
                 worker.dataflow(|scope| {
 
                     use timely::dataflow::operators::Probe;
                     use differential_dataflow::operators::arrange::ArrangeBySelf;
                     use plan::Render;
 
-                    for Rule { name, plan } in query.rules.into_iter() {
+                    for Rule { name, plan } in rules.into_iter() {
+
+                        let plan = manager.prepare(&plan);
+                        manager.advisor.observe(&plan);
+
                         let collection =
-                        plan.render(scope, &mut manager.traces)
+                        plan.render(scope, &mut manager.traces, &Bindings::new())
                             .arrange_by_self();
 
                         collection.stream.probe_with(&mut manager.probe);
                         let trace = collection.trace;
 
-                        // Can bind the trace to both the plan and the name.
+                        // Binds the trace to the plan, and publishes the plan under the name.
                         manager.traces.set_unkeyed(&plan, &trace);
-                        manager.traces.set_unkeyed(&Plan::Source(name), &trace);
+                        manager.traces.bind(&name, &plan);
+                    }
+
+                    if manager.advisor.automatic {
+                        manager.observe_costs();
+                        for recommendation in manager.advisor.install(scope, &mut manager.traces, &mut manager.probe) {
+                            report.push(format!("Installed: {:?}", recommendation));
+                        }
                     }
 
                 });
+
+                manager.installed(names);
+                evict(manager, &mut report);
             },
 
             Command::AdvanceTime(time) => {
@@ -61,11 +214,16 @@ impl<Value: Data+Hash> Command<Value> {
                 while manager.probe.less_than(&time) {
                     worker.step();
                 }
+                evict(manager, &mut report);
             },
 
             Command::CreateInput(name, updates) => {
 
                 use differential_dataflow::input::Input;
+
+                if let Err(reason) = conform(manager, &name, updates.iter()) {
+                    return Response::Failed(reason);
+                }
                 use differential_dataflow::operators::arrange::ArrangeBySelf;
 
                 let (input, trace) = worker.dataflow(|scope| {
@@ -79,26 +237,311 @@ impl<Value: Data+Hash> Command<Value> {
             },
 
             Command::UpdateInput(name, updates) => {
+                if let Err(reason) = conform(manager, &name, updates.iter().map(|(data, _time, _diff)| data)) {
+                    return Response::Failed(reason);
+                }
+                let epochs = updates.iter().map(|(_data, time, _diff)| *time).collect::<Vec<_>>();
                 if let Some(input) = manager.inputs.sessions.get_mut(&name) {
                     for (data, time, diff) in updates.into_iter() {
                         input.update_at(data, time, diff);
                     }
                 }
                 else {
-                    println!("Input not found: {:?}", name);
+                    return Response::Failed(format!("Input not found: {:?}", name));
                 }
+                manager.record_epochs(epochs);
             },
 
             Command::CloseInput(name) => {
-                manager.inputs.sessions.remove(&name);
+                manager.inputs.close(&name);
+            },
+
+            Command::LoadFile(name, path, format) => {
+                match manager.inputs.sessions.get_mut(&name) {
+                    Some(input) => {
+                        match load_file(worker, input, &path, &format, manager.schemas.get(&name)) {
+                            Ok(loaded) => report.push(format!("Loaded: {} tuples into {:?}", loaded, name)),
+                            Err(reason) => return Response::Failed(reason),
+                        }
+                    },
+                    None => return Response::Failed(format!("Input not found: {:?}", name)),
+                }
+            },
+
+            Command::CreateUpsertInput(name, arity) => {
+
+                use timely::dataflow::InputHandle;
+                use timely::dataflow::operators::Input;
+                use differential_dataflow::operators::arrange::ArrangeBySelf;
+                use differential_dataflow::operators::upsert::arrange_from_upsert;
+                use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+                let mut handle = InputHandle::new();
+                let (keyed, unkeyed) = worker.dataflow(|scope| {
+                    let arranged = arrange_from_upsert::<_,_,_,OrdValSpine<_,_,_,_>>(&scope.input_from(&mut handle), "UpsertInput");
+                    let unkeyed =
+                    arranged
+                        .as_collection(|key, value| key.iter().chain(value.iter()).cloned().collect::<Vec<_>>())
+                        .arrange_by_self()
+                        .trace;
+                    (arranged.trace, unkeyed)
+                });
+
+                manager.insert_upsert_input(name, UpsertSession::new(handle, arity), keyed, unkeyed);
+            },
+
+            Command::UpsertInput(name, upserts) => {
+                let upserted =
+                upserts
+                    .iter()
+                    .filter_map(|(key, value, _time)| value.as_ref().map(|value| key.iter().chain(value.iter()).cloned().collect::<Vec<_>>()))
+                    .collect::<Vec<_>>();
+                if let Err(reason) = conform(manager, &name, upserted.iter()) {
+                    return Response::Failed(reason);
+                }
+                let epochs = upserts.iter().map(|(_key, _value, time)| *time).collect::<Vec<_>>();
+                match manager.inputs.upserts.get_mut(&name) {
+                    Some(session) => {
+                        if let Err(reason) = session.upsert(upserts) {
+                            return Response::Failed(reason);
+                        }
+                    },
+                    None => return Response::Failed(format!("Upsert input not found: {:?}", name)),
+                }
+                manager.record_epochs(epochs);
+            },
+
+            Command::Advise(automatic) => {
+                manager.advisor.automatic = automatic;
+                manager.observe_costs();
+                for recommendation in manager.advisor.recommend(&manager.traces) {
+                    report.push(format!("Recommended: {:?}", recommendation));
+                }
+            },
+
+            Command::Estimate(plan, deltas) => {
+                use estimate::Estimator;
+                let mut estimator = Estimator::new(&manager.traces);
+                for (source, updates) in deltas.iter() {
+                    estimator = estimator.delta(source, *updates);
+                }
+                for estimate in estimator.estimate(&plan) {
+                    report.push(format!("Estimate: {:?}\t{:?}", (estimate.input, estimate.output), estimate.plan));
+                }
+            },
+
+            Command::Explain(plan) => {
+                report.extend(manager.explain(&plan).to_string().lines().map(|line| line.to_string()));
+            },
+
+            Command::Vacuum(name) => {
+                let frontier = manager.probe.with_frontier(|frontier| frontier.to_vec());
+                let (before, after) = manager.traces.vacuum(name.as_ref().map(|x| x.as_str()), &frontier[..]);
+                report.push(format!("Vacuum: batches {} -> {}, updates {} -> {}", before.0, after.0, before.1, after.1));
+                report.push(format!("Vacuum: purged {} interned strings", ::intern::purge()));
+            },
+
+            Command::Subscribe(name, plan) => {
+                return Command::Resubscribe(name, plan, vec![Time::default()]).execute(manager, worker, origin);
+            },
+
+            Command::Resubscribe(name, plan, frontier) => {
+
+                if manager.subscriptions.contains_key(&name) {
+                    return Response::Failed(format!("Subscription exists: {:?}", name));
+                }
+
+                let plan = match check(&plan, &manager.schemas) {
+                    Ok((plan, _schema)) => plan,
+                    Err(reason) => return Response::Failed(reason),
+                };
+
+                worker.dataflow(|scope| {
+
+                    use timely::dataflow::operators::{Exchange, Filter, Probe};
+                    use plan::Render;
+
+                    let plan = manager.prepare(&plan);
+                    manager.advisor.observe(&plan);
+
+                    let stream =
+                    plan.render(scope, &mut manager.traces, &Bindings::new())
+                        .inner
+                        .filter(move |(_, time, _)| frontier.iter().any(|bound| bound <= time))
+                        .exchange(move |_| origin as u64);
+
+                    stream.probe_with(&mut manager.probe);
+                    manager.subscribe(&name, origin, &stream);
+                });
+                evict(manager, &mut report);
+            },
+
+            Command::Unsubscribe(name) => {
+                match manager.unsubscribe(&name) {
+                    Some(released) => report.push(format!("Unsubscribed: {:?}, released traces {}, batches {}, updates {}", name, released.0, released.1, released.2)),
+                    None => return Response::Failed(format!("Subscription not found: {:?}", name)),
+                }
+            },
+
+            Command::Uninstall(name) => {
+                match manager.uninstall(&name) {
+                    Some(released) => report.push(format!("Uninstalled: {:?}, released traces {}, batches {}, updates {}", name, released.0, released.1, released.2)),
+                    None => return Response::Failed(format!("Installation not found: {:?}", name)),
+                }
+            },
+
+            Command::Publish(name, plan) => {
+                let (plan, schema) = match check(&plan, &manager.schemas) {
+                    Ok(checked) => checked,
+                    Err(reason) => return Response::Failed(reason),
+                };
+                let prepared = manager.prepare(&plan);
+                if manager.traces.get_unkeyed(&prepared).is_some() {
+                    manager.traces.bind(&name, &prepared);
+                    manager.installed(vec![name.clone()]);
+                    if let Some(schema) = schema { manager.schemas.insert(name.clone(), schema); }
+                    report.push(format!("Published: {:?} = {:?}", name, prepared));
+                }
+                else {
+                    return Command::Query(Query::new().add_rule(Rule { name, plan })).execute(manager, worker, origin);
+                }
+            },
+
+            Command::GetByName(name) => {
+                match manager.traces.get_by_name(&name) {
+                    Some(plan) => report.push(format!("Published: {:?} = {:?}", name, plan)),
+                    None => return Response::Failed(format!("Name not published: {:?}", name)),
+                }
+            },
+
+            Command::Usage => {
+                let usage = manager.usage(None);
+                report.push(format!("Usage: traces {}, batches {}, updates {}", usage.0, usage.1, usage.2));
+            },
+
+            Command::Namespaced(namespace, command) => {
+                match *command {
+                    Command::Shutdown => {
+                        let released = manager.shutdown_namespace(&namespace);
+                        report.push(format!("Shut down: {:?}, released traces {}, batches {}, updates {}", namespace, released.0, released.1, released.2));
+                    },
+                    Command::Usage => {
+                        let usage = manager.usage(Some(&namespace));
+                        report.push(format!("Usage: {:?}, traces {}, batches {}, updates {}", namespace, usage.0, usage.1, usage.2));
+                    },
+                    Command::Sql(name, text) => {
+                        return install_sql(manager, worker, origin, ::namespace::qualify(&namespace, &name), &text, Some(&namespace));
+                    },
+                    Command::Datalog(text) => {
+                        return match ::datalog::compile(&text) {
+                            Ok(query) => Command::Query(query).qualify(&namespace).execute(manager, worker, origin),
+                            Err(reason) => Response::Failed(format!("Datalog: {}", reason)),
+                        };
+                    },
+                    command => {
+                        return command.qualify(&namespace).execute(manager, worker, origin);
+                    },
+                }
+            },
+
+            Command::History(history) => {
+                manager.traces.set_history(history);
+            },
+
+            Command::QueryAsOf(plan, time) => {
+                return match manager.query_as_of(&plan, &time) {
+                    Ok(contents) => Response::Contents(contents),
+                    Err(reason) => Response::Failed(reason),
+                };
+            },
+
+            Command::Sql(name, text) => {
+                return install_sql(manager, worker, origin, name, &text, None);
+            },
+
+            Command::Datalog(text) => {
+                return match ::datalog::compile(&text) {
+                    Ok(query) => Command::Query(query).execute(manager, worker, origin),
+                    Err(reason) => Response::Failed(format!("Datalog: {}", reason)),
+                };
+            },
+
+            Command::Authenticate(_) => {
+                return Response::Failed("Authentication is handled by connections".to_string());
+            },
+
+            Command::DeclareSchema(name, schema) => {
+                report.push(format!("Declared: {:?} = {}", name, schema));
+                manager.schemas.insert(name, schema);
+            },
+
+            Command::GetSchema(name) => {
+                match manager.schemas.get(&name) {
+                    Some(schema) => report.push(format!("Schema: {:?} = {}", name, schema)),
+                    None => return Response::Failed(format!("Schema not found: {:?}", name)),
+                }
+            },
+
+            Command::Describe => {
+                report.extend(manager.describe().to_string().lines().map(|line| line.to_string()));
+            },
+
+            Command::Budget(budget) => {
+                manager.traces.set_budget(budget);
+                evict(manager, &mut report);
             },
 
             Command::Shutdown => {
-                println!("Shutdown received");
-                manager.shutdown();
+                report.push("Shutdown received".to_string());
+                manager.shutdown(worker);
+            }
+        }
+
+        Response::Executed(report)
+    }
+
+}
+
+/// Compiles the SQL query `text`, reading tables within `namespace` if any, and installs it as `name`.
+///
+/// The schema published for `name` uses the names the query gives its columns.
+fn install_sql<A, Value>(manager: &mut Manager<Value>, worker: &mut Worker<A>, origin: usize, name: String, text: &str, namespace: Option<&str>) -> Response<Value>
+where
+    A: Allocate,
+    Value: Data+Hash+Aggregable+Evaluable+FromField+Typed+FromLiteral,
+{
+    let (plan, columns) = match compile(text, &manager.schemas, namespace) {
+        Ok(compiled) => compiled,
+        Err(reason) => return Response::Failed(format!("SQL: {}", reason)),
+    };
+    let response = Command::Query(Query::new().add_rule(Rule { name: name.clone(), plan })).execute(manager, worker, origin);
+    if let Response::Executed(_) = response {
+        if let Some(schema) = manager.schemas.get_mut(&name) {
+            for (column, named) in schema.columns.iter_mut().zip(columns.into_iter()) {
+                column.0 = named;
             }
         }
+    }
+    response
+}
 
+/// Checks `tuples` against the schema of the collection `name`, if one is declared.
+fn conform<'a, Value, I>(manager: &Manager<Value>, name: &str, tuples: I) -> Result<(), String>
+where
+    Value: Data+Hash+Typed+'a,
+    I: Iterator<Item=&'a Vec<Value>>,
+{
+    if let Some(schema) = manager.schemas.get(name) {
+        for tuple in tuples {
+            schema.check(tuple).map_err(|reason| format!("Input {:?}: {}", name, reason))?;
+        }
     }
+    Ok(())
+}
 
-}
\ No newline at end of file
+/// Evicts cached traces beyond the budget of `manager`, and reports them.
+fn evict<Value: Data+Hash>(manager: &mut Manager<Value>, report: &mut Vec<String>) {
+    for evicted in manager.traces.enforce_budget() {
+        report.push(format!("Evicted: {:?}", evicted));
+    }
+}
@@ -0,0 +1,307 @@
+//! Lowering parsed rules to plans.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Expression, Predicate, Evaluable};
+use plan::filter::SecondArgument;
+use Query;
+
+use sql::FromLiteral;
+use super::parse::{Rule, Atom, Term, Comparison, Subgoal};
+
+/// The plan of the tuples satisfying some subgoals, and the variable of each of its columns.
+struct Bound<V> {
+    plan: Plan<V>,
+    variables: Vec<String>,
+}
+
+impl<V> Bound<V> {
+    /// The column of `variable`, if it is bound.
+    fn index(&self, variable: &str) -> Option<usize> {
+        self.variables.iter().position(|bound| bound == variable)
+    }
+}
+
+/// The relations a program defines, and its rules.
+pub struct Program<'a> {
+    rules: &'a [Rule],
+    /// Defined predicates, in order of their first definition.
+    defined: Vec<String>,
+    /// For each defined predicate, the defined predicates its rules read, with `true` for those read negatively.
+    depends: HashMap<String, Vec<(String, bool)>>,
+}
+
+impl<'a> Program<'a> {
+
+    /// Collects the predicates `rules` define, and checks that each predicate has one arity.
+    pub fn new(rules: &'a [Rule]) -> Result<Self, String> {
+
+        let mut arities = HashMap::new();
+        for rule in rules.iter() {
+            let atoms = Some(&rule.head).into_iter().chain(rule.body.iter().filter_map(|subgoal| match subgoal {
+                Subgoal::Atom(atom) | Subgoal::Negated(atom) => Some(atom),
+                Subgoal::Compare(_, _, _) => None,
+            }));
+            for atom in atoms {
+                let arity = *arities.entry(atom.name.clone()).or_insert(atom.terms.len());
+                if arity != atom.terms.len() {
+                    return Err(format!("Predicate {} has arities {} and {}", atom.name, arity, atom.terms.len()));
+                }
+            }
+        }
+
+        let mut defined = Vec::new();
+        for rule in rules.iter() {
+            if !defined.contains(&rule.head.name) { defined.push(rule.head.name.clone()); }
+        }
+
+        let mut depends = HashMap::new();
+        for rule in rules.iter() {
+            let reads = depends.entry(rule.head.name.clone()).or_insert_with(Vec::new);
+            for subgoal in rule.body.iter() {
+                let (atom, negated) = match subgoal {
+                    Subgoal::Atom(atom) => (atom, false),
+                    Subgoal::Negated(atom) => (atom, true),
+                    Subgoal::Compare(_, _, _) => continue,
+                };
+                if defined.contains(&atom.name) {
+                    reads.push((atom.name.clone(), negated));
+                }
+            }
+        }
+
+        Ok(Program { rules, defined, depends })
+    }
+
+    /// The defined predicates `name` reads, directly or indirectly.
+    fn reachable(&self, name: &str) -> HashSet<String> {
+        let mut reached = HashSet::new();
+        let mut todo = vec![name.to_string()];
+        while let Some(next) = todo.pop() {
+            for (read, _negated) in self.depends[&next].iter() {
+                if reached.insert(read.clone()) {
+                    todo.push(read.clone());
+                }
+            }
+        }
+        reached
+    }
+
+    /// Groups the defined predicates into strata, each of mutually recursive predicates,
+    /// after the strata of the predicates they read.
+    ///
+    /// Fails if some predicate reads negatively a predicate that reads it.
+    pub fn strata(&self) -> Result<Vec<Vec<String>>, String> {
+
+        let reachable = self.defined.iter().map(|name| (name.clone(), self.reachable(name))).collect::<HashMap<_,_>>();
+
+        for name in self.defined.iter() {
+            for (read, negated) in self.depends[name].iter() {
+                if *negated && reachable[read].contains(name) {
+                    return Err(format!("Program is not stratified: {} negates {}, which depends on it", name, read));
+                }
+            }
+        }
+
+        // The component of each predicate holds those it reads that read it.
+        let component = |name: &String| {
+            self.defined
+                .iter()
+                .filter(|other| *other == name || (reachable[name].contains(*other) && reachable[*other].contains(name)))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        // Repeatedly emits the first component whose other dependencies have been emitted.
+        let mut strata: Vec<Vec<String>> = Vec::new();
+        let mut emitted = HashSet::new();
+        while emitted.len() < self.defined.len() {
+            let next =
+            self.defined
+                .iter()
+                .filter(|name| !emitted.contains(*name))
+                .map(|name| component(name))
+                .find(|members| members.iter().all(|member| {
+                    reachable[member].iter().all(|read| emitted.contains(read) || members.contains(read))
+                }))
+                .expect("Components of predicates are acyclic");
+            emitted.extend(next.iter().cloned());
+            strata.push(next);
+        }
+
+        Ok(strata)
+    }
+
+    /// A query defining each predicate of `strata` under its name, in order.
+    ///
+    /// The predicates of a recursive stratum are each the result of an iteration binding all of them.
+    pub fn query<V: Data+Hash+Evaluable+FromLiteral>(&self, strata: &[Vec<String>]) -> Result<Query<V>, String> {
+        let mut query = Query::new();
+        for stratum in strata.iter() {
+            let recursive = stratum.len() > 1 || self.depends[&stratum[0]].iter().any(|(read, _)| read == &stratum[0]);
+            if recursive {
+                let bindings =
+                stratum
+                    .iter()
+                    .map(|name| Ok((name.clone(), self.define(name)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                for name in stratum.iter() {
+                    let plan = Plan::source(name).iterate(bindings.clone());
+                    query = query.add_rule(::Rule { name: name.clone(), plan });
+                }
+            }
+            else {
+                let plan = self.define(&stratum[0])?.distinct();
+                query = query.add_rule(::Rule { name: stratum[0].clone(), plan });
+            }
+        }
+        Ok(query)
+    }
+
+    /// The concatenation of the plans of the rules defining `name`.
+    fn define<V: Data+Hash+Evaluable+FromLiteral>(&self, name: &str) -> Result<Plan<V>, String> {
+        let mut plans =
+        self.rules
+            .iter()
+            .filter(|rule| rule.head.name == name)
+            .map(|rule| lower_rule(rule).map_err(|reason| format!("Rule for {}: {}", name, reason)))
+            .collect::<Result<Vec<_>, String>>()?;
+        if plans.len() == 1 { Ok(plans.remove(0)) }
+        else { Ok(Plan::concat(plans)) }
+    }
+}
+
+/// The plan of the tuples of the head of `rule` its body derives.
+///
+/// Atoms are joined in order, on the variables they share with those before them.
+/// Comparisons and negated atoms then filter the results, and must only use variables
+/// the atoms bind.
+fn lower_rule<V: Data+Hash+Evaluable+FromLiteral>(rule: &Rule) -> Result<Plan<V>, String> {
+
+    let mut bound: Option<Bound<V>> = None;
+    for subgoal in rule.body.iter() {
+        if let Subgoal::Atom(atom) = subgoal {
+            let next = lower_atom(atom)?;
+            bound = Some(match bound {
+                None => next,
+                Some(bound) => join(bound, next),
+            });
+        }
+    }
+    let mut bound = bound.ok_or_else(|| "Rules must have an atom in their body; introduce facts as inputs".to_string())?;
+
+    for subgoal in rule.body.iter() {
+        match subgoal {
+            Subgoal::Atom(_) => { },
+            Subgoal::Negated(atom) => {
+                let negated = lower_atom(atom)?;
+                let keys =
+                negated
+                    .variables
+                    .iter()
+                    .enumerate()
+                    .map(|(index, variable)| match bound.index(variable) {
+                        Some(column) => Ok((column, index)),
+                        None => Err(format!("Variable {} of negated {} is not bound by an atom", variable, atom.name)),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                bound.plan = bound.plan.antijoin(negated.plan, keys);
+            },
+            Subgoal::Compare(comparison, term1, term2) => {
+                let predicate = compare(&bound, *comparison, term1, term2)?;
+                bound.plan = bound.plan.filter(predicate);
+            },
+        }
+    }
+
+    let mut indices = Vec::new();
+    let mut expressions = Vec::new();
+    for term in rule.head.terms.iter() {
+        match term {
+            Term::Variable(variable) => {
+                let index = bound.index(variable).ok_or_else(|| format!("Variable {} of the head is not bound by an atom", variable))?;
+                indices.push(index);
+                expressions.push(Expression::Column(index));
+            },
+            Term::Literal(literal) => expressions.push(Expression::Constant(V::from_literal(literal)?)),
+            Term::Wildcard => return Err("The head of a rule cannot use _".to_string()),
+        }
+    }
+
+    if indices.len() == expressions.len() {
+        if indices == (0..bound.variables.len()).collect::<Vec<_>>() { Ok(bound.plan) }
+        else { Ok(bound.plan.project(indices)) }
+    }
+    else {
+        Ok(bound.plan.map(expressions))
+    }
+}
+
+/// The plan of the tuples of `atom`, with a column for each of its distinct variables.
+///
+/// Constants and repeated variables filter the tuples of the predicate.
+fn lower_atom<V: Data+Hash+Evaluable+FromLiteral>(atom: &Atom) -> Result<Bound<V>, String> {
+    let mut plan = Plan::source(&atom.name);
+    let mut variables: Vec<String> = Vec::new();
+    let mut indices = Vec::new();
+    for (index, term) in atom.terms.iter().enumerate() {
+        match term {
+            Term::Variable(variable) => {
+                match variables.iter().position(|bound| bound == variable) {
+                    Some(position) => { plan = plan.filter(Predicate::Equal(index, SecondArgument::Position(indices[position]))); },
+                    None => { variables.push(variable.clone()); indices.push(index); },
+                }
+            },
+            Term::Literal(literal) => { plan = plan.filter(Predicate::Equal(index, SecondArgument::Constant(V::from_literal(literal)?))); },
+            Term::Wildcard => { },
+        }
+    }
+    if indices != (0..atom.terms.len()).collect::<Vec<_>>() {
+        plan = plan.project(indices);
+    }
+    Ok(Bound { plan, variables })
+}
+
+/// The join of `bound` and `next` on their shared variables.
+fn join<V: Data+Hash>(bound: Bound<V>, next: Bound<V>) -> Bound<V> {
+    let keys =
+    bound
+        .variables
+        .iter()
+        .enumerate()
+        .filter_map(|(index, variable)| next.index(variable).map(|other| (index, other)))
+        .collect::<Vec<_>>();
+    // Joins produce the keys, then the other columns of each input.
+    let mut variables = keys.iter().map(|(index, _)| bound.variables[*index].clone()).collect::<Vec<_>>();
+    variables.extend(bound.variables.iter().enumerate().filter(|(index, _)| !keys.iter().any(|key| key.0 == *index)).map(|(_, variable)| variable.clone()));
+    variables.extend(next.variables.iter().enumerate().filter(|(index, _)| !keys.iter().any(|key| key.1 == *index)).map(|(_, variable)| variable.clone()));
+    Bound { plan: bound.plan.join(next.plan, keys), variables }
+}
+
+/// The predicate of a comparison between terms, at least one of which is a bound variable.
+fn compare<V: FromLiteral>(bound: &Bound<V>, comparison: Comparison, term1: &Term, term2: &Term) -> Result<Predicate<V>, String> {
+    let argument = |term: &Term| match term {
+        Term::Variable(variable) => match bound.index(variable) {
+            Some(index) => Ok(SecondArgument::Position(index)),
+            None => Err(format!("Variable {} of a comparison is not bound by an atom", variable)),
+        },
+        Term::Literal(literal) => Ok(SecondArgument::Constant(V::from_literal(literal)?)),
+        Term::Wildcard => Err("Comparisons cannot use _".to_string()),
+    };
+    let (comparison, index, other) = match (argument(term1)?, argument(term2)?) {
+        (SecondArgument::Position(index), other) => (comparison, index, other),
+        (other, SecondArgument::Position(index)) => (comparison.reverse(), index, other),
+        _ => return Err("Comparisons must use a variable".to_string()),
+    };
+    Ok(match comparison {
+        Comparison::Equal => Predicate::Equal(index, other),
+        Comparison::NotEqual => Predicate::NotEqual(index, other),
+        Comparison::LessThan => Predicate::LessThan(index, other),
+        Comparison::LessEqual => Predicate::LessEqual(index, other),
+        Comparison::GreaterThan => Predicate::GreaterThan(index, other),
+        Comparison::GreaterEqual => Predicate::GreaterEqual(index, other),
+    })
+}
@@ -0,0 +1,58 @@
+//! A Datalog frontend, compiling programs of rules to queries.
+//!
+//! A program is a list of rules, each deriving an atom from the atoms, negated
+//! atoms, and comparisons of its body. Variables start with capitals, `_` is a
+//! variable distinct from all others, and constants are numbers, quoted strings,
+//! `true`, `false`, and `null`. For example, with collections `edges` of pairs and
+//! `roots` of nodes,
+//!
+//! ```ignore
+//! % Nodes reachable from a root, and those that are not.
+//! reach(X) :- roots(X).
+//! reach(Y) :- reach(X), edges(X, Y).
+//! unreached(X) :- edges(X, _), !reach(X).
+//! ```
+//!
+//! Each predicate a program defines becomes a rule of the query, published under its
+//! name and maintained as its inputs change; other predicates are read as collections.
+//! Predicates are sets, whose tuples are distinct, and columns are by position.
+//!
+//! Negation must be stratified: no predicate may negate one that depends on it, so
+//! that each predicate is defined after those it negates have been computed. Mutually
+//! recursive predicates are computed together by `Iterate` plans, each publishing one
+//! of them. Rules must be safe, in that each variable of the head, of a negated atom,
+//! and of a comparison must appear in an atom of the body.
+
+mod parse;
+mod lower;
+
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::Evaluable;
+use sql::FromLiteral;
+use Query;
+
+/// Compiles the program `text` to a query defining each of its predicates.
+pub fn compile<V>(text: &str) -> Result<Query<V>, String>
+where
+    V: Data+Hash+Evaluable+FromLiteral,
+{
+    let rules = parse::Parser::new(text)?.program()?;
+    let program = lower::Program::new(&rules)?;
+    let strata = program.strata()?;
+    program.query(&strata)
+}
+
+/// The predicates the program `text` defines, each once, in the order they are first defined.
+pub fn predicates(text: &str) -> Result<Vec<String>, String> {
+    let rules = parse::Parser::new(text)?.program()?;
+    let mut names = Vec::new();
+    for rule in rules.iter() {
+        if !names.contains(&rule.head.name) {
+            names.push(rule.head.name.clone());
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,289 @@
+//! Parsing Datalog text into rules.
+
+use sql::Literal;
+
+/// A rule, deriving its head from the subgoals of its body.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rule {
+    /// The atom the rule derives.
+    pub head: Atom,
+    /// The subgoals the values of the head must satisfy.
+    pub body: Vec<Subgoal>,
+}
+
+/// A predicate applied to terms.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Atom {
+    /// The name of the predicate.
+    pub name: String,
+    /// The terms, one for each column of the predicate.
+    pub terms: Vec<Term>,
+}
+
+/// A term of an atom or comparison.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Term {
+    /// A named variable.
+    Variable(String),
+    /// The anonymous variable, `_`, distinct from all others.
+    Wildcard,
+    /// A constant.
+    Literal(Literal),
+}
+
+/// A comparison between terms.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    /// Equal.
+    Equal,
+    /// Not equal.
+    NotEqual,
+    /// Strictly less than.
+    LessThan,
+    /// Less than or equal.
+    LessEqual,
+    /// Strictly greater than.
+    GreaterThan,
+    /// Greater than or equal.
+    GreaterEqual,
+}
+
+impl Comparison {
+    /// The comparison holding when that of `self` holds with its terms exchanged.
+    pub fn reverse(self) -> Self {
+        match self {
+            Comparison::LessThan => Comparison::GreaterThan,
+            Comparison::LessEqual => Comparison::GreaterEqual,
+            Comparison::GreaterThan => Comparison::LessThan,
+            Comparison::GreaterEqual => Comparison::LessEqual,
+            symmetric => symmetric,
+        }
+    }
+}
+
+/// A subgoal of the body of a rule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Subgoal {
+    /// An atom that must hold.
+    Atom(Atom),
+    /// An atom that must not hold.
+    Negated(Atom),
+    /// A comparison that must hold.
+    Compare(Comparison, Term, Term),
+}
+
+/// A token of Datalog text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    /// A name of a predicate or variable, or a keyword.
+    Word(String),
+    /// A number, as written.
+    Number(String),
+    /// A quoted string, without its quotes.
+    Text(String),
+    /// Punctuation or an operator.
+    Symbol(&'static str),
+}
+
+/// Symbols, each listed before those that are its prefixes.
+const SYMBOLS: &[&str] = &[":-", "<>", "!=", "<=", ">=", "(", ")", ",", ".", "!", "=", "<", ">", "-"];
+
+/// Splits `text` into tokens, skipping whitespace and `%` comments.
+///
+/// Names may contain `/`, so that rules may read qualified and shared collections.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < chars.len() {
+        let c = chars[position];
+        if c.is_whitespace() {
+            position += 1;
+        }
+        else if c == '%' {
+            while position < chars.len() && chars[position] != '\n' { position += 1; }
+        }
+        else if c.is_alphabetic() || c == '_' {
+            let start = position;
+            while position < chars.len() && (chars[position].is_alphanumeric() || chars[position] == '_' || chars[position] == '/') { position += 1; }
+            tokens.push(Token::Word(chars[start..position].iter().collect()));
+        }
+        else if c.is_ascii_digit() {
+            let start = position;
+            while position < chars.len() && chars[position].is_ascii_digit() { position += 1; }
+            if chars.get(position) == Some(&'.') && chars.get(position + 1).map_or(false, |c| c.is_ascii_digit()) {
+                position += 1;
+                while position < chars.len() && chars[position].is_ascii_digit() { position += 1; }
+            }
+            tokens.push(Token::Number(chars[start..position].iter().collect()));
+        }
+        else if c == '\'' || c == '"' {
+            // Quotes within quoted text are written twice.
+            let mut quoted = String::new();
+            position += 1;
+            loop {
+                match chars.get(position) {
+                    Some(&q) if q == c && chars.get(position + 1) == Some(&c) => { quoted.push(c); position += 2; },
+                    Some(&q) if q == c => { position += 1; break; },
+                    Some(&other) => { quoted.push(other); position += 1; },
+                    None => return Err(format!("Unterminated quotation: {}{}", c, quoted)),
+                }
+            }
+            tokens.push(Token::Text(quoted));
+        }
+        else {
+            let rest = chars[position..].iter().take(2).collect::<String>();
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                Some(symbol) => {
+                    tokens.push(Token::Symbol(*symbol));
+                    position += symbol.len();
+                },
+                None => return Err(format!("Unexpected character: {:?}", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A parser of the tokens of a program.
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+
+    /// Creates a parser of the tokens of `text`.
+    pub fn new(text: &str) -> Result<Self, String> {
+        Ok(Parser { tokens: tokenize(text)?, position: 0 })
+    }
+
+    /// Parses rules, each ending with a period, which must be all of the tokens.
+    pub fn program(&mut self) -> Result<Vec<Rule>, String> {
+        let mut rules = Vec::new();
+        while self.peek().is_some() {
+            rules.push(self.rule()?);
+        }
+        Ok(rules)
+    }
+
+    fn rule(&mut self) -> Result<Rule, String> {
+        let head = self.atom()?;
+        let mut body = Vec::new();
+        if self.symbol(":-") {
+            loop {
+                body.push(self.subgoal()?);
+                if !self.symbol(",") { break; }
+            }
+        }
+        self.expect_symbol(".")?;
+        Ok(Rule { head, body })
+    }
+
+    fn subgoal(&mut self) -> Result<Subgoal, String> {
+        if self.symbol("!") || self.keyword("not") {
+            return Ok(Subgoal::Negated(self.atom()?));
+        }
+        // An atom is a name followed by a parenthesis; anything else starts a comparison.
+        let atom = match (self.peek(), self.tokens.get(self.position + 1)) {
+            (Some(Token::Word(_)), Some(Token::Symbol("("))) => true,
+            _ => false,
+        };
+        if atom {
+            return Ok(Subgoal::Atom(self.atom()?));
+        }
+        let term1 = self.term()?;
+        let comparison = match self.next() {
+            Some(Token::Symbol("=")) => Comparison::Equal,
+            Some(Token::Symbol("!=")) | Some(Token::Symbol("<>")) => Comparison::NotEqual,
+            Some(Token::Symbol("<")) => Comparison::LessThan,
+            Some(Token::Symbol("<=")) => Comparison::LessEqual,
+            Some(Token::Symbol(">")) => Comparison::GreaterThan,
+            Some(Token::Symbol(">=")) => Comparison::GreaterEqual,
+            found => return Err(format!("Expected a comparison, found {:?}", found)),
+        };
+        let term2 = self.term()?;
+        Ok(Subgoal::Compare(comparison, term1, term2))
+    }
+
+    fn atom(&mut self) -> Result<Atom, String> {
+        let name = match self.next() {
+            Some(Token::Word(word)) => {
+                if starts_variable(&word) { return Err(format!("Expected a predicate, found the variable {}", word)); }
+                word
+            },
+            found => return Err(format!("Expected a predicate, found {:?}", found)),
+        };
+        self.expect_symbol("(")?;
+        let mut terms = Vec::new();
+        if !self.symbol(")") {
+            loop {
+                terms.push(self.term()?);
+                if !self.symbol(",") { break; }
+            }
+            self.expect_symbol(")")?;
+        }
+        Ok(Atom { name, terms })
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        match self.next() {
+            Some(Token::Word(word)) => {
+                if word == "_" { Ok(Term::Wildcard) }
+                else if starts_variable(&word) { Ok(Term::Variable(word)) }
+                else if word == "true" { Ok(Term::Literal(Literal::Bool(true))) }
+                else if word == "false" { Ok(Term::Literal(Literal::Bool(false))) }
+                else if word == "null" { Ok(Term::Literal(Literal::Null)) }
+                else { Err(format!("Expected a term, found {}; variables start with capitals, and strings are quoted", word)) }
+            },
+            Some(Token::Number(number)) => Ok(Term::Literal(Literal::Number(number))),
+            Some(Token::Text(text)) => Ok(Term::Literal(Literal::String(text))),
+            Some(Token::Symbol("-")) => match self.next() {
+                Some(Token::Number(number)) => Ok(Term::Literal(Literal::Number(format!("-{}", number)))),
+                _ => Err("Negation applies only to numbers".to_string()),
+            },
+            Some(token) => Err(format!("Expected a term, found {:?}", token)),
+            None => Err("Expected a term, found the end of the program".to_string()),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Reads `keyword` if it is next.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Word(word)) => word == keyword,
+            _ => false,
+        };
+        if found { self.position += 1; }
+        found
+    }
+
+    /// Reads `symbol` if it is next.
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Symbol(found)) => *found == symbol,
+            _ => false,
+        };
+        if found { self.position += 1; }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) { Ok(()) }
+        else { Err(format!("Expected {}, found {:?}", symbol, self.peek())) }
+    }
+}
+
+/// Indicates if `word` names a variable, by starting with a capital or an underscore.
+fn starts_variable(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_uppercase() || c == '_')
+}
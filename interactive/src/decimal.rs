@@ -0,0 +1,124 @@
+//! Fixed-point decimal numbers.
+//!
+//! Quantities such as prices must be represented exactly, which integers do only by
+//! fixing the unit for the whole system, and floating point numbers do not at all.
+//! A `Decimal` holds an integer number of units together with the number of digits
+//! after the point, so that each value carries its own precision.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The largest number of digits after the point.
+pub const MAX_SCALE: u32 = 18;
+
+/// A fixed-point decimal number, of `units` multiples of ten to the power of minus `scale`.
+///
+/// Decimals are constructed without trailing zeros after the point, so that equal numbers
+/// have equal representations, and are ordered by the numbers they represent.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Decimal {
+    units: i64,
+    scale: u32,
+}
+
+impl Decimal {
+    /// The number `units` times ten to the power of minus `scale`, if `scale` is at most `MAX_SCALE`.
+    pub fn new(mut units: i64, mut scale: u32) -> Option<Self> {
+        if scale > MAX_SCALE { return None; }
+        while scale > 0 && units % 10 == 0 {
+            units /= 10;
+            scale -= 1;
+        }
+        Some(Decimal { units, scale })
+    }
+    /// The integer `units`.
+    pub fn from_integer(units: i64) -> Self {
+        Decimal { units, scale: 0 }
+    }
+    /// The number of units.
+    pub fn units(&self) -> i64 { self.units }
+    /// The number of digits after the point.
+    pub fn scale(&self) -> u32 { self.scale }
+
+    /// The sum of two decimals, unless it overflows.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let (units1, units2, scale) = self.aligned(other)?;
+        Decimal::new(units1.checked_add(units2)?, scale)
+    }
+    /// The difference of two decimals, unless it overflows.
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        let (units1, units2, scale) = self.aligned(other)?;
+        Decimal::new(units1.checked_sub(units2)?, scale)
+    }
+    /// The product of two decimals, truncated to `MAX_SCALE` digits after the point, unless it overflows.
+    pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+        let units = (self.units as i128) * (other.units as i128);
+        let scale = self.scale + other.scale;
+        let (units, scale) =
+        if scale > MAX_SCALE { (units / 10i128.pow(scale - MAX_SCALE), MAX_SCALE) }
+        else { (units, scale) };
+        Decimal::new(i64::try_from(units).ok()?, scale)
+    }
+
+    /// The units of both decimals at the larger of their scales, unless they overflow.
+    fn aligned(&self, other: &Self) -> Option<(i64, i64, u32)> {
+        let scale = ::std::cmp::max(self.scale, other.scale);
+        let units1 = self.units.checked_mul(10i64.pow(scale - self.scale))?;
+        let units2 = other.units.checked_mul(10i64.pow(scale - other.scale))?;
+        Some((units1, units2, scale))
+    }
+    /// The units of the decimal at `MAX_SCALE`, which cannot overflow.
+    fn widened(&self) -> i128 {
+        (self.units as i128) * 10i128.pow(MAX_SCALE - self.scale)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Representations of the same number, not constructed by `new`, are ordered by scale.
+        self.widened().cmp(&other.widened()).then(self.scale.cmp(&other.scale))
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ::std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let units = (self.units as i128).abs();
+        let divisor = 10i128.pow(self.scale);
+        if self.units < 0 { write!(f, "-")?; }
+        write!(f, "{}", units / divisor)?;
+        if self.scale > 0 {
+            write!(f, ".{:0width$}", units % divisor, width = self.scale as usize)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        let (integer, fraction) = match digits.find('.') {
+            Some(point) => (&digits[..point], &digits[point+1..]),
+            None => (digits, ""),
+        };
+        let valid = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if integer.is_empty() || !valid(integer) || !valid(fraction) {
+            return Err(format!("invalid decimal: {:?}", text));
+        }
+        let units =
+        format!("{}{}{}", if negative { "-" } else { "" }, integer, fraction)
+            .parse::<i64>()
+            .map_err(|_| format!("decimal out of range: {:?}", text))?;
+        Decimal::new(units, fraction.len() as u32).ok_or_else(|| format!("decimal has more than {} digits after the point: {:?}", MAX_SCALE, text))
+    }
+}
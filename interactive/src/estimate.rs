@@ -0,0 +1,186 @@
+//! Estimates of the work required to update a plan.
+//!
+//! Given a plan and a description of hypothetical changes to its sources, the
+//! estimator predicts the number of updates each stage of the plan will process.
+//! The estimates use statistics drawn from arrangements already maintained by the
+//! trace manager, most importantly the average number of records per key of the
+//! arrangements a join would use. Collections without maintained arrangements are
+//! assumed to have one record per key, so estimates for plans over fresh data will
+//! be optimistic.
+//!
+//! The estimates are upper bounds in the sense that they do not account for filters
+//! or cancellation; they are meant to compare candidate plans, not to predict exact
+//! counts.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+use differential_dataflow::trace::TraceReader;
+
+use plan::Plan;
+use TraceManager;
+
+/// Summary statistics of a maintained arrangement.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Statistics {
+    /// Number of distinct keys.
+    pub keys: usize,
+    /// Number of distinct (key, value) records.
+    pub records: usize,
+}
+
+impl Statistics {
+    /// The average number of records per key, rounded up.
+    pub fn fanout(&self) -> usize {
+        if self.keys == 0 { 1 }
+        else { (self.records + self.keys - 1) / self.keys }
+    }
+}
+
+/// The estimated number of updates processed by one stage of a plan.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Estimate<Value> {
+    /// The plan stage.
+    pub plan: Plan<Value>,
+    /// Estimated number of updates received by the stage.
+    pub input: usize,
+    /// Estimated number of updates produced by the stage.
+    pub output: usize,
+}
+
+/// Estimates the updates processed by each stage of a plan.
+pub struct Estimator<'a, Value: Data+'a> {
+    traces: &'a TraceManager<Value>,
+    deltas: HashMap<String, usize>,
+    estimates: Vec<Estimate<Value>>,
+}
+
+impl<'a, Value: Data+Hash> Estimator<'a, Value> {
+
+    /// Creates a new estimator drawing statistics from `traces`.
+    pub fn new(traces: &'a TraceManager<Value>) -> Self {
+        Estimator {
+            traces,
+            deltas: HashMap::new(),
+            estimates: Vec::new(),
+        }
+    }
+
+    /// Describes a hypothetical number of updates to the named source.
+    pub fn delta(mut self, source: &str, updates: usize) -> Self {
+        *self.deltas.entry(source.to_string()).or_insert(0) += updates;
+        self
+    }
+
+    /// Estimates the work of each stage of `plan`, in post-order.
+    ///
+    /// The final estimate describes the plan itself.
+    pub fn estimate(mut self, plan: &Plan<Value>) -> Vec<Estimate<Value>> {
+        self.visit(plan);
+        self.estimates
+    }
+
+    /// Estimates the output of `plan`, recording estimates for it and its sub-plans.
+    fn visit(&mut self, plan: &Plan<Value>) -> usize {
+        let (input, output) = match plan {
+            Plan::Project(project) => { let d = self.visit(&project.plan); (d, d) },
+            Plan::Map(map) => { let d = self.visit(&map.plan); (d, d) },
+            Plan::Distinct(distinct) => {
+                // Each input update may produce one retraction and one addition.
+                let d = self.visit(distinct);
+                (d, 2 * d)
+            },
+            Plan::Threshold(threshold) => {
+                let d = self.visit(&threshold.plan);
+                (d, 2 * d)
+            },
+            Plan::Concat(concat) => {
+                let d = concat.plans.iter().map(|plan| self.visit(plan)).sum();
+                (d, d)
+            },
+            Plan::Union(plans) => {
+                let d = plans.iter().map(|plan| self.visit(plan)).sum();
+                (d, 2 * d)
+            },
+            Plan::Join(join) => {
+                let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+                let d1 = self.visit(&join.plan1);
+                let d2 = self.visit(&join.plan2);
+                let fanout1 = self.keyed_statistics(&join.plan1, &keys1[..]).map(|s| s.fanout()).unwrap_or(1);
+                let fanout2 = self.keyed_statistics(&join.plan2, &keys2[..]).map(|s| s.fanout()).unwrap_or(1);
+                (d1 + d2, d1 * fanout2 + d2 * fanout1)
+            },
+            Plan::Antijoin(antijoin) => {
+                // Each change to the right input may exclude or restore each matching left tuple.
+                let keys1 = antijoin.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                let d1 = self.visit(&antijoin.plan1);
+                let d2 = self.visit(&antijoin.plan2);
+                let fanout1 = self.keyed_statistics(&antijoin.plan1, &keys1[..]).map(|s| s.fanout()).unwrap_or(1);
+                (d1 + d2, d1 + d2 * fanout1)
+            },
+            Plan::Negate(negate) => { let d = self.visit(negate); (d, d) },
+            Plan::Filter(filter) => { let d = self.visit(&filter.plan); (d, d) },
+            Plan::Aggregate(aggregate) => {
+                // Each input update may retract and replace the aggregates of its group.
+                let d = self.visit(&aggregate.plan);
+                (d, 2 * d)
+            },
+            Plan::Let(bind) => {
+                // References to the name within the body receive the updates of the bound plan.
+                let d = self.visit(&bind.value);
+                let shadowed = self.deltas.insert(bind.name.clone(), d);
+                let b = self.visit(&bind.body);
+                match shadowed {
+                    Some(shadowed) => { self.deltas.insert(bind.name.clone(), shadowed); },
+                    None => { self.deltas.remove(&bind.name); },
+                }
+                (d, b)
+            },
+            Plan::Iterate(iterate) => {
+                // Without a bound on the number of rounds, each binding is visited once.
+                let d = iterate.bindings.iter().map(|(_name, plan)| self.visit(plan)).sum::<usize>() + self.visit(&iterate.body);
+                (d, d)
+            },
+            Plan::Source(name) => {
+                let d = self.deltas.get(name).cloned().unwrap_or(0);
+                (d, d)
+            },
+            Plan::Inspect(_, plan) => { let d = self.visit(plan); (d, d) },
+        };
+
+        self.estimates.push(Estimate { plan: plan.clone(), input, output });
+        output
+    }
+
+    /// Statistics for the arrangement of `plan` by `keys`, if one is maintained.
+    fn keyed_statistics(&self, plan: &Plan<Value>, keys: &[usize]) -> Option<Statistics> {
+        self.traces
+            .get_keyed(plan, keys)
+            .map(|mut trace| statistics(&mut trace))
+    }
+}
+
+/// Computes statistics of the accumulated contents of a trace.
+///
+/// Records whose differences accumulate to zero are not counted.
+pub fn statistics<K, V, T, R, Tr>(trace: &mut Tr) -> Statistics
+where
+    Tr: TraceReader<K, V, T, R>,
+    K: Clone+PartialEq,
+    V: Clone,
+    T: ::differential_dataflow::lattice::Lattice,
+    R: ::differential_dataflow::difference::Monoid,
+{
+    let mut result = Statistics::default();
+    let mut last = None;
+    for (key, _val, _count) in trace.snapshot(&[]).expect("snapshot of all times is always available") {
+        result.records += 1;
+        if last.as_ref() != Some(&key) {
+            result.keys += 1;
+            last = Some(key);
+        }
+    }
+    result
+}
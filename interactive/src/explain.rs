@@ -0,0 +1,233 @@
+//! Descriptions of how plans would be rendered.
+//!
+//! Rendering a plan builds the operators its stages require, importing the
+//! collections and arrangements the trace manager maintains and building the
+//! others. The memory a query uses is mostly that of the arrangements it builds,
+//! which are not apparent from the plan. The explainer follows the decisions of
+//! rendering without building a dataflow, and reports the tree of operators,
+//! the maintained arrangements that would be reused, and the arrangements that
+//! would be built.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Aggregation};
+use TraceManager;
+
+/// An arrangement used by a rendered plan.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Arrangement<Value> {
+    /// The collection of a plan, arranged by its tuples.
+    Unkeyed(Plan<Value>),
+    /// The collection of a plan, arranged by the values at the indicated indices.
+    Keyed(Plan<Value>, Vec<usize>),
+    /// The input of an operator, arranged by the operator itself.
+    Operator(String, Plan<Value>),
+}
+
+/// A description of the rendering of a plan.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Explanation<Value> {
+    /// The plan described.
+    pub plan: Plan<Value>,
+    /// The operators rendered, one per line, indented beneath the operators that use them.
+    pub operators: Vec<String>,
+    /// Maintained arrangements that would be imported.
+    pub reused: Vec<Arrangement<Value>>,
+    /// Arrangements that would be built.
+    pub built: Vec<Arrangement<Value>>,
+}
+
+impl<Value: fmt::Debug> fmt::Display for Explanation<Value> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for operator in self.operators.iter() {
+            writeln!(f, "{}", operator)?;
+        }
+        for arrangement in self.reused.iter() {
+            writeln!(f, "Reused: {:?}", arrangement)?;
+        }
+        for arrangement in self.built.iter() {
+            writeln!(f, "Built: {:?}", arrangement)?;
+        }
+        Ok(())
+    }
+}
+
+/// Explains the rendering of plans.
+pub struct Explainer<'a, Value: Data+'a> {
+    traces: &'a TraceManager<Value>,
+    /// Names bound while rendering.
+    bound: HashSet<String>,
+    /// The number of iterative scopes enclosing the stage described.
+    depth: usize,
+    operators: Vec<String>,
+    reused: Vec<Arrangement<Value>>,
+    built: Vec<Arrangement<Value>>,
+}
+
+impl<'a, Value: Data+Hash> Explainer<'a, Value> {
+
+    /// Creates a new explainer, consulting the arrangements of `traces`.
+    pub fn new(traces: &'a TraceManager<Value>) -> Self {
+        Explainer {
+            traces,
+            bound: HashSet::new(),
+            depth: 0,
+            operators: Vec::new(),
+            reused: Vec::new(),
+            built: Vec::new(),
+        }
+    }
+
+    /// Explains the rendering of `plan` in a new dataflow.
+    pub fn explain(mut self, plan: &Plan<Value>) -> Explanation<Value> {
+        self.visit(plan, 0);
+        Explanation {
+            plan: plan.clone(),
+            operators: self.operators,
+            reused: self.reused,
+            built: self.built,
+        }
+    }
+
+    fn line(&mut self, indent: usize, text: String) {
+        self.operators.push(format!("{}{}", "  ".repeat(indent), text));
+    }
+
+    /// Indicates if `plan` reads any names bound while rendering.
+    fn reads(&self, plan: &Plan<Value>) -> bool {
+        match plan {
+            Plan::Source(name) => self.bound.contains(name),
+            _ => plan.children().into_iter().any(|child| self.reads(child)),
+        }
+    }
+
+    /// Describes the rendering of `plan`, as `Render::render` would perform it.
+    fn visit(&mut self, plan: &Plan<Value>, indent: usize) {
+
+        if !self.reads(plan) && self.traces.get_unkeyed(plan).is_some() {
+            let text = match plan {
+                Plan::Source(name) => format!("Import {}", name),
+                _ => format!("Import {:?}", plan),
+            };
+            self.line(indent, text);
+            self.reused.push(Arrangement::Unkeyed(plan.clone()));
+            return;
+        }
+
+        match plan {
+            Plan::Project(project) => {
+                self.line(indent, format!("Project {:?}", project.indices));
+                self.visit(&project.plan, indent + 1);
+            },
+            Plan::Map(map) => {
+                self.line(indent, format!("Map {:?}", map.expressions));
+                self.visit(&map.plan, indent + 1);
+            },
+            Plan::Distinct(distinct) => {
+                self.line(indent, "Distinct".to_string());
+                self.built.push(Arrangement::Operator("distinct".to_string(), (**distinct).clone()));
+                self.visit(distinct, indent + 1);
+            },
+            Plan::Threshold(threshold) => {
+                self.line(indent, format!("Threshold {}", threshold.minimum));
+                self.built.push(Arrangement::Operator("threshold".to_string(), (*threshold.plan).clone()));
+                self.visit(&threshold.plan, indent + 1);
+            },
+            Plan::Concat(concat) => {
+                self.line(indent, "Concat".to_string());
+                for plan in concat.plans.iter() {
+                    self.visit(plan, indent + 1);
+                }
+            },
+            Plan::Union(plans) => {
+                self.line(indent, "Union".to_string());
+                self.built.push(Arrangement::Operator("distinct".to_string(), Plan::concat(plans.clone())));
+                for plan in plans.iter() {
+                    self.visit(plan, indent + 1);
+                }
+            },
+            Plan::Join(join) => {
+                self.line(indent, format!("Join {:?}", join.keys));
+                let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+                self.arrange(&join.plan1, keys1, indent + 1);
+                self.arrange(&join.plan2, keys2, indent + 1);
+            },
+            Plan::Antijoin(antijoin) => {
+                self.line(indent, format!("Antijoin {:?}", antijoin.keys));
+                self.built.push(Arrangement::Operator("semijoin".to_string(), (*antijoin.plan1).clone()));
+                self.built.push(Arrangement::Operator("distinct".to_string(), (*antijoin.plan2).clone()));
+                self.visit(&antijoin.plan1, indent + 1);
+                self.visit(&antijoin.plan2, indent + 1);
+            },
+            Plan::Negate(negate) => {
+                self.line(indent, "Negate".to_string());
+                self.visit(negate, indent + 1);
+            },
+            Plan::Filter(filter) => {
+                self.line(indent, format!("Filter {:?}", filter.predicate));
+                self.visit(&filter.plan, indent + 1);
+            },
+            Plan::Aggregate(aggregate) => {
+                self.line(indent, format!("Aggregate {:?} {:?}", aggregate.keys, aggregate.aggregates));
+                let operator = if aggregate.aggregates == vec![Aggregation::Count] { "count" } else { "reduce" };
+                self.built.push(Arrangement::Operator(operator.to_string(), (*aggregate.plan).clone()));
+                self.visit(&aggregate.plan, indent + 1);
+            },
+            Plan::Let(bind) => {
+                self.line(indent, format!("Let {}", bind.name));
+                self.built.push(Arrangement::Operator(format!("let {}", bind.name), (*bind.value).clone()));
+                self.visit(&bind.value, indent + 1);
+                let shadowed = !self.bound.insert(bind.name.clone());
+                self.visit(&bind.body, indent + 1);
+                if !shadowed { self.bound.remove(&bind.name); }
+            },
+            Plan::Iterate(iterate) => {
+                self.line(indent, "Iterate".to_string());
+                self.depth += 1;
+                let mut inserted = Vec::new();
+                for (name, _plan) in iterate.bindings.iter() {
+                    if self.bound.insert(name.clone()) {
+                        inserted.push(name.clone());
+                    }
+                }
+                for (name, plan) in iterate.bindings.iter() {
+                    self.line(indent + 1, format!("Variable {}", name));
+                    self.built.push(Arrangement::Operator("distinct".to_string(), plan.clone()));
+                    self.visit(plan, indent + 2);
+                }
+                self.visit(&iterate.body, indent + 1);
+                for name in inserted.iter() {
+                    self.bound.remove(name);
+                }
+                self.depth -= 1;
+            },
+            Plan::Source(name) => {
+                if self.bound.contains(name) { self.line(indent, format!("Binding {}", name)); }
+                else { self.line(indent, format!("Missing source {}", name)); }
+            },
+            Plan::Inspect(text, plan) => {
+                self.line(indent, format!("Inspect {:?}", text));
+                self.visit(plan, indent + 1);
+            },
+        }
+    }
+
+    /// Describes the arrangement of `plan` by `keys` for a join, as `RenderScope::arrange_keyed` would perform it.
+    fn arrange(&mut self, plan: &Plan<Value>, keys: Vec<usize>, indent: usize) {
+        // Only dataflows, outside iterative scopes, maintain arrangements.
+        if self.depth == 0 && !self.reads(plan) && self.traces.get_keyed(plan, &keys[..]).is_some() {
+            self.line(indent, format!("Import arrangement by {:?}", keys));
+            self.reused.push(Arrangement::Keyed(plan.clone(), keys));
+        }
+        else {
+            self.line(indent, format!("Arrange by {:?}", keys));
+            self.built.push(Arrangement::Keyed(plan.clone(), keys));
+            self.visit(plan, indent + 1);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Interning of strings, so that repeated strings are stored once.
+//!
+//! String-heavy collections repeat the same few strings across many tuples, and
+//! each tuple held by an arrangement would otherwise hold its own copy. Each worker
+//! maintains a dictionary of the strings it has seen, and an `Interned` string is
+//! a shared reference to the dictionary's copy. Strings are interned when they are
+//! constructed or decoded, so that strings exchanged between workers, which are
+//! serialized as their text, are interned in the dictionary of the receiving worker.
+//!
+//! Interned strings compare as their text, so that they order as strings do, but
+//! equal strings of the same dictionary are recognized by their reference without
+//! comparing their text.
+//!
+//! Unlike a dictionary of integer ids, the dictionaries are not exchanged alongside
+//! batches, and strings are not compared by id. Ids would only be meaningful within
+//! the dictionary that assigned them, and exchanging them would require either a
+//! dictionary agreed on by all workers, or translating the ids of each received
+//! batch; instead, strings are sent as their text, which each worker interns again,
+//! and strings from different dictionaries are compared by their text.
+//!
+//! The dictionary retains its strings until they are purged. `purge` removes those
+//! no longer referenced, and `compact` does so whenever the dictionary has doubled
+//! in size since it last purged, which the manager calls as it compacts its traces.
+
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+thread_local! {
+    /// The dictionary of the worker on this thread.
+    static DICTIONARY: RefCell<HashSet<Arc<str>>> = RefCell::new(HashSet::new());
+    /// The number of strings in the dictionary after it was last purged.
+    static PURGED: Cell<usize> = Cell::new(0);
+}
+
+/// The number of strings below which `compact` does not purge the dictionary.
+const COMPACT_MINIMUM: usize = 1 << 10;
+
+/// A string, interned in the dictionary of the worker that constructed it.
+#[derive(Clone)]
+pub struct Interned(Arc<str>);
+
+impl Interned {
+    /// Interns `text`, returning a reference to the dictionary's copy.
+    pub fn new(text: &str) -> Self {
+        DICTIONARY.with(|dictionary| {
+            let mut dictionary = dictionary.borrow_mut();
+            if let Some(interned) = dictionary.get(text) {
+                return Interned(interned.clone());
+            }
+            let interned: Arc<str> = Arc::from(text);
+            dictionary.insert(interned.clone());
+            Interned(interned)
+        })
+    }
+    /// The text of the string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Removes the strings of this worker's dictionary that are no longer referenced, and returns their number.
+pub fn purge() -> usize {
+    DICTIONARY.with(|dictionary| {
+        let mut dictionary = dictionary.borrow_mut();
+        let before = dictionary.len();
+        dictionary.retain(|interned| Arc::strong_count(interned) > 1);
+        PURGED.with(|purged| purged.set(dictionary.len()));
+        before - dictionary.len()
+    })
+}
+
+/// Purges this worker's dictionary if it has doubled in size since it was last purged, and returns the number of strings removed.
+///
+/// The work of purging, which visits each string, is then proportional to the number of strings interned since.
+pub fn compact() -> usize {
+    let length = DICTIONARY.with(|dictionary| dictionary.borrow().len());
+    let purged = PURGED.with(|purged| purged.get());
+    if length >= COMPACT_MINIMUM && length >= 2 * purged { purge() } else { 0 }
+}
+
+/// The numbers of strings in this worker's dictionary, and of bytes of their text.
+pub fn statistics() -> (usize, usize) {
+    DICTIONARY.with(|dictionary| {
+        let dictionary = dictionary.borrow();
+        (dictionary.len(), dictionary.iter().map(|interned| interned.len()).sum())
+    })
+}
+
+impl Deref for Interned {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a str> for Interned {
+    fn from(text: &'a str) -> Self {
+        Interned::new(text)
+    }
+}
+
+impl From<String> for Interned {
+    fn from(text: String) -> Self {
+        Interned::new(&text)
+    }
+}
+
+impl PartialEq for Interned {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Interned { }
+
+impl Ord for Interned {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if Arc::ptr_eq(&self.0, &other.0) { Ordering::Equal }
+        else { self.0.cmp(&other.0) }
+    }
+}
+
+impl PartialOrd for Interned {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Interned {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl ::std::fmt::Debug for Interned {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::std::fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl ::std::fmt::Display for Interned {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::std::fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+// Interned strings are encoded as strings, so that the encoding of values is unchanged.
+impl Serialize for Interned {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Ok(Interned::new(&text))
+    }
+}
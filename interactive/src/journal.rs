@@ -0,0 +1,82 @@
+//! A journal of commands, from which the state of a system can be recovered.
+//!
+//! The state of the system is determined by the commands that changed it: the
+//! creation and updates of inputs, the advancement of time, and the installation
+//! of queries. A journal appends these commands to a file as they are executed,
+//! each in the `bincode` encoding of `wire`, and `replay` reads them back so that
+//! a restarted system can execute them again, rebuilding its inputs and traces.
+//! A crash while appending can leave an incomplete command at the end of the
+//! journal, which `replay` ignores and `Journal::resume` removes; any other command
+//! that cannot be decoded is an error, rather than the silent loss of the commands
+//! after it.
+//! Commands that only report, and subscriptions, whose clients do not survive a
+//! restart, are not journaled.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Cursor, Read, Write, Result, Error, ErrorKind};
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use Command;
+
+/// An append-only journal of commands.
+pub struct Journal {
+    writer: BufWriter<File>,
+}
+
+impl Journal {
+
+    /// Opens the journal at `path` for appending, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal { writer: BufWriter::new(file) })
+    }
+
+    /// Opens the journal at `path` for appending after its first `length` bytes, discarding the rest.
+    ///
+    /// The length is that returned by `replay`, so that commands are appended after the last complete
+    /// command rather than after an incomplete one.
+    pub fn resume<P: AsRef<Path>>(path: P, length: u64) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.set_len(length)?;
+        Ok(Journal { writer: BufWriter::new(file) })
+    }
+
+    /// Appends `command` to the journal, returning once it has reached the disk.
+    pub fn record<Value: Serialize>(&mut self, command: &Command<Value>) -> Result<()> {
+        ::wire::write_bincode(&mut self.writer, command)?;
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+}
+
+/// Reads the commands of the journal at `path`, in the order they were recorded, and the number of bytes they occupy.
+///
+/// A journal that does not exist holds no commands. An incomplete command at the end of the
+/// journal, left by a crash while recording, is ignored and excluded from the length. A command
+/// that cannot be decoded before the end of the journal is an error naming its byte offset.
+pub fn replay<Value: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<(Vec<Command<Value>>, u64)> {
+    let mut bytes = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => { file.read_to_end(&mut bytes)?; },
+        Err(ref error) if error.kind() == ErrorKind::NotFound => { },
+        Err(error) => return Err(error),
+    }
+
+    let mut commands = Vec::new();
+    let mut reader = Cursor::new(&bytes[..]);
+    while (reader.position() as usize) < bytes.len() {
+        let start = reader.position();
+        match ::wire::read_bincode(&mut reader) {
+            Ok(command) => commands.push(command),
+            // A command that runs past the end of the journal was not completely recorded.
+            Err(_) if reader.position() as usize >= bytes.len() => return Ok((commands, start)),
+            Err(error) => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("journal: corrupt command at byte {}: {}", start, error)));
+            }
+        }
+    }
+    Ok((commands, bytes.len() as u64))
+}
@@ -12,6 +12,7 @@ extern crate differential_dataflow;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 
 pub mod plan;
 pub use plan::Plan;
@@ -20,7 +21,54 @@ pub mod manager;
 pub use manager::{Manager, TraceManager, InputManager};
 
 pub mod command;
-pub use command::Command;
+pub use command::{Command, Response};
+
+pub mod advisor;
+pub use advisor::Advisor;
+
+pub mod estimate;
+pub use estimate::Estimator;
+
+pub mod optimize;
+pub use optimize::Optimizer;
+
+pub mod share;
+
+pub mod namespace;
+
+pub mod explain;
+pub use explain::Explainer;
+
+pub mod client;
+pub use client::{Client, AsyncClient};
+
+pub mod wire;
+
+pub mod journal;
+pub use journal::Journal;
+
+pub mod auth;
+pub use auth::Authorizer;
+
+pub mod metrics;
+
+pub mod cluster;
+
+pub mod redact;
+
+pub mod load;
+
+pub mod decimal;
+pub use decimal::Decimal;
+
+pub mod intern;
+pub use intern::Interned;
+
+pub mod schema;
+pub use schema::Schema;
+
+pub mod sql;
+pub mod datalog;
 
 /// System-wide notion of time.
 pub type Time = ::std::time::Duration;
@@ -62,12 +110,185 @@ pub enum Value {
     Bool(bool),
     /// integer
     Usize(usize),
-    /// string
-    String(String),
+    /// string, interned by the worker holding it
+    String(Interned),
     /// operator address
     Address(Vec<usize>),
     /// duration
     Duration(::std::time::Duration),
+    /// fixed-point decimal
+    Decimal(Decimal),
+    /// byte array
+    Bytes(Vec<u8>),
+    /// timestamp, in microseconds since the Unix epoch
+    Timestamp(i64),
+    /// unknown value
+    Null,
+}
+
+/// The number of microseconds in a duration, if it fits in a timestamp.
+fn micros(duration: &::std::time::Duration) -> Option<i64> {
+    use std::convert::TryFrom;
+    i64::try_from(duration.as_micros()).ok()
+}
+
+impl plan::Aggregable for Value {
+    fn from_count(count: Diff) -> Self {
+        Value::Usize(count as usize)
+    }
+    fn sum<'a, I: Iterator<Item=(&'a Self, Diff)>>(values: I) -> Self {
+        use std::convert::TryFrom;
+        let mut total: Option<Value> = None;
+        let mut nulls = false;
+        for (value, count) in values {
+            if let Value::Null = value { nulls = true; continue; }
+            let usizes = usize::try_from(count).ok();
+            let u32s = u32::try_from(count).ok();
+            let decimals = i64::try_from(count).ok().map(Decimal::from_integer);
+            let sum = match (total, value) {
+                (None, Value::Usize(x)) => usizes.and_then(|count| x.checked_mul(count)).map(Value::Usize),
+                (None, Value::Duration(x)) => u32s.and_then(|count| x.checked_mul(count)).map(Value::Duration),
+                (None, Value::Decimal(x)) => decimals.and_then(|count| x.checked_mul(&count)).map(Value::Decimal),
+                (Some(Value::Usize(t)), Value::Usize(x)) => usizes.and_then(|count| x.checked_mul(count)).and_then(|x| t.checked_add(x)).map(Value::Usize),
+                (Some(Value::Duration(t)), Value::Duration(x)) => u32s.and_then(|count| x.checked_mul(count)).and_then(|x| t.checked_add(x)).map(Value::Duration),
+                (Some(Value::Decimal(t)), Value::Decimal(x)) => decimals.and_then(|count| x.checked_mul(&count)).and_then(|x| t.checked_add(&x)).map(Value::Decimal),
+                // Values that cannot be summed, including those of other types than the sum so far, make the sum null.
+                _ => None,
+            };
+            match sum {
+                Some(sum) => total = Some(sum),
+                // Sums that overflow are also null.
+                None => return Value::Null,
+            }
+        }
+        total.unwrap_or(if nulls { Value::Null } else { Value::Usize(0) })
+    }
+}
+
+impl plan::Evaluable for Value {
+    fn from_bool(value: bool) -> Self {
+        Value::Bool(value)
+    }
+    fn as_bool(&self) -> Option<bool> {
+        if let Value::Bool(value) = self { Some(*value) } else { None }
+    }
+    fn binary(op: plan::BinaryOp, value1: &Self, value2: &Self) -> Option<Self> {
+        use plan::BinaryOp;
+        match (op, value1, value2) {
+            (BinaryOp::Add, Value::Usize(x), Value::Usize(y)) => x.checked_add(*y).map(Value::Usize),
+            (BinaryOp::Sub, Value::Usize(x), Value::Usize(y)) => x.checked_sub(*y).map(Value::Usize),
+            (BinaryOp::Mul, Value::Usize(x), Value::Usize(y)) => x.checked_mul(*y).map(Value::Usize),
+            (BinaryOp::Div, Value::Usize(x), Value::Usize(y)) => x.checked_div(*y).map(Value::Usize),
+            (BinaryOp::Rem, Value::Usize(x), Value::Usize(y)) => x.checked_rem(*y).map(Value::Usize),
+            (BinaryOp::Add, Value::Duration(x), Value::Duration(y)) => x.checked_add(*y).map(Value::Duration),
+            (BinaryOp::Sub, Value::Duration(x), Value::Duration(y)) => x.checked_sub(*y).map(Value::Duration),
+            (BinaryOp::Add, Value::Decimal(x), Value::Decimal(y)) => x.checked_add(y).map(Value::Decimal),
+            (BinaryOp::Sub, Value::Decimal(x), Value::Decimal(y)) => x.checked_sub(y).map(Value::Decimal),
+            (BinaryOp::Mul, Value::Decimal(x), Value::Decimal(y)) => x.checked_mul(y).map(Value::Decimal),
+            (BinaryOp::Add, Value::Timestamp(x), Value::Duration(y)) => x.checked_add(micros(y)?).map(Value::Timestamp),
+            (BinaryOp::Sub, Value::Timestamp(x), Value::Duration(y)) => x.checked_sub(micros(y)?).map(Value::Timestamp),
+            (BinaryOp::Sub, Value::Timestamp(x), Value::Timestamp(y)) => {
+                let elapsed = x.checked_sub(*y)?;
+                if elapsed < 0 { None } else { Some(Value::Duration(::std::time::Duration::from_micros(elapsed as u64))) }
+            },
+            (BinaryOp::Concat, Value::String(x), Value::String(y)) => Some(Value::String(format!("{}{}", x, y).into())),
+            (BinaryOp::Concat, Value::Bytes(x), Value::Bytes(y)) => Some(Value::Bytes(x.iter().chain(y.iter()).cloned().collect())),
+            (BinaryOp::Contains, Value::String(x), Value::String(y)) => Some(Value::Bool(x.contains(y.as_str()))),
+            (BinaryOp::StartsWith, Value::String(x), Value::String(y)) => Some(Value::Bool(x.starts_with(y.as_str()))),
+            _ => None,
+        }
+    }
+    fn unary(op: plan::UnaryOp, value: &Self) -> Option<Self> {
+        use plan::UnaryOp;
+        match (op, value) {
+            (UnaryOp::Length, Value::String(x)) => Some(Value::Usize(x.chars().count())),
+            (UnaryOp::Length, Value::Bytes(x)) => Some(Value::Usize(x.len())),
+            (UnaryOp::Upper, Value::String(x)) => Some(Value::String(x.to_uppercase().into())),
+            (UnaryOp::Lower, Value::String(x)) => Some(Value::String(x.to_lowercase().into())),
+            _ => None,
+        }
+    }
+    fn null() -> Option<Self> {
+        Some(Value::Null)
+    }
+    fn is_null(&self) -> bool {
+        *self == Value::Null
+    }
+}
+
+impl schema::Typed for Value {
+    fn type_of(&self) -> schema::Type {
+        use schema::Type;
+        match self {
+            Value::Bool(_) => Type::Bool,
+            Value::Usize(_) => Type::Usize,
+            Value::String(_) => Type::String,
+            Value::Address(_) => Type::Address,
+            Value::Duration(_) => Type::Duration,
+            Value::Decimal(_) => Type::Decimal,
+            Value::Bytes(_) => Type::Bytes,
+            Value::Timestamp(_) => Type::Timestamp,
+            Value::Null => Type::Null,
+        }
+    }
+    fn binary_type(op: plan::BinaryOp, type1: schema::Type, type2: schema::Type) -> Option<schema::Type> {
+        use plan::BinaryOp;
+        use schema::Type;
+        match (op, type1, type2) {
+            (BinaryOp::Add, Type::Usize, Type::Usize) => Some(Type::Usize),
+            (BinaryOp::Sub, Type::Usize, Type::Usize) => Some(Type::Usize),
+            (BinaryOp::Mul, Type::Usize, Type::Usize) => Some(Type::Usize),
+            (BinaryOp::Div, Type::Usize, Type::Usize) => Some(Type::Usize),
+            (BinaryOp::Rem, Type::Usize, Type::Usize) => Some(Type::Usize),
+            (BinaryOp::Add, Type::Duration, Type::Duration) => Some(Type::Duration),
+            (BinaryOp::Sub, Type::Duration, Type::Duration) => Some(Type::Duration),
+            (BinaryOp::Add, Type::Decimal, Type::Decimal) => Some(Type::Decimal),
+            (BinaryOp::Sub, Type::Decimal, Type::Decimal) => Some(Type::Decimal),
+            (BinaryOp::Mul, Type::Decimal, Type::Decimal) => Some(Type::Decimal),
+            (BinaryOp::Add, Type::Timestamp, Type::Duration) => Some(Type::Timestamp),
+            (BinaryOp::Sub, Type::Timestamp, Type::Duration) => Some(Type::Timestamp),
+            (BinaryOp::Sub, Type::Timestamp, Type::Timestamp) => Some(Type::Duration),
+            (BinaryOp::Concat, Type::String, Type::String) => Some(Type::String),
+            (BinaryOp::Concat, Type::Bytes, Type::Bytes) => Some(Type::Bytes),
+            (BinaryOp::Contains, Type::String, Type::String) => Some(Type::Bool),
+            (BinaryOp::StartsWith, Type::String, Type::String) => Some(Type::Bool),
+            _ => None,
+        }
+    }
+    fn unary_type(op: plan::UnaryOp, type1: schema::Type) -> Option<schema::Type> {
+        use plan::UnaryOp;
+        use schema::Type;
+        match (op, type1) {
+            (UnaryOp::Length, Type::String) => Some(Type::Usize),
+            (UnaryOp::Length, Type::Bytes) => Some(Type::Usize),
+            (UnaryOp::Upper, Type::String) => Some(Type::String),
+            (UnaryOp::Lower, Type::String) => Some(Type::String),
+            _ => None,
+        }
+    }
+}
+
+impl load::FromField for Value {
+    fn from_field(field: &str) -> Result<Self, String> {
+        match field {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Ok(field.parse::<usize>().map(Value::Usize)
+                    .or_else(|_| field.parse::<Decimal>().map(Value::Decimal))
+                    .unwrap_or_else(|_| Value::String(field.into()))),
+        }
+    }
+}
+
+impl sql::FromLiteral for Value {
+    fn from_literal(literal: &sql::Literal) -> Result<Self, String> {
+        match literal {
+            sql::Literal::Number(number) => number.parse::<usize>().map(Value::Usize).or_else(|_| number.parse::<Decimal>().map(Value::Decimal)),
+            sql::Literal::String(text) => Ok(Value::String(text.as_str().into())),
+            sql::Literal::Bool(value) => Ok(Value::Bool(*value)),
+            sql::Literal::Null => Ok(Value::Null),
+        }
+    }
 }
 
 use manager::AsVector;
@@ -77,7 +298,7 @@ impl AsVector<Value> for TimelyEvent {
     fn as_vector(self) -> Vec<Value> {
         match self {
             TimelyEvent::Operates(x) => {
-                vec![Value::Usize(x.id), Value::Address(x.addr), Value::String(x.name)]
+                vec![Value::Usize(x.id), Value::Address(x.addr), Value::String(x.name.into())]
             },
             TimelyEvent::Channels(x) => {
                 vec![Value::Usize(x.id), Value::Address(x.scope_addr), Value::Usize(x.source.0), Value::Usize(x.source.1), Value::Usize(x.target.0), Value::Usize(x.target.1)]
@@ -88,11 +309,56 @@ impl AsVector<Value> for TimelyEvent {
             TimelyEvent::Messages(x) => {
                 vec![Value::Usize(x.channel), Value::Bool(x.is_send), Value::Usize(x.source), Value::Usize(x.target), Value::Usize(x.seq_no), Value::Usize(x.length)]
             },
+            // Parking, with the longest it may last if bounded, or unparking, which has no duration.
+            TimelyEvent::Park(x) => {
+                match x {
+                    ::timely::logging::ParkEvent::Park(duration) => vec![Value::Bool(true), duration.map(Value::Duration).unwrap_or(Value::Null)],
+                    ::timely::logging::ParkEvent::Unpark => vec![Value::Bool(false), Value::Null],
+                }
+            },
+            TimelyEvent::Text(x) => {
+                vec![Value::String(x.into())]
+            },
+            // Progress messages, with the numbers of their message and internal updates.
+            TimelyEvent::Progress(x) => {
+                vec![Value::Usize(x.channel), Value::Bool(x.is_send), Value::Usize(x.source), Value::Usize(x.seq_no), Value::Address(x.addr), Value::Usize(x.messages.len()), Value::Usize(x.internal.len())]
+            },
+            TimelyEvent::Shutdown(x) => {
+                vec![Value::Usize(x.id)]
+            },
             _ => { vec![] },
         }
     }
 }
 
+use manager::TraceDescription;
+
+impl AsVector<Value> for TraceDescription<Value> {
+    fn as_vector(self) -> Vec<Value> {
+        vec![
+            Value::String(format!("{:?}", self.plan).into()),
+            Value::Bool(self.keys.is_some()),
+            Value::Address(self.keys.unwrap_or_default()),
+            Value::Usize(self.statistics.batches),
+            Value::Usize(self.statistics.updates),
+            Value::Usize(self.statistics.keys),
+            Value::Usize(self.statistics.bytes),
+        ]
+    }
+}
+
+use manager::LatencyEvent;
+
+impl AsVector<Value> for LatencyEvent {
+    fn as_vector(self) -> Vec<Value> {
+        vec![
+            Value::Usize(self.worker),
+            Value::Duration(self.epoch),
+            Value::Duration(self.latency),
+        ]
+    }
+}
+
 use differential_dataflow::logging::DifferentialEvent;
 
 impl AsVector<Value> for DifferentialEvent {
@@ -114,12 +380,36 @@ impl AsVector<Value> for DifferentialEvent {
                     Value::Bool(x.complete.is_some()),
                 ]
             },
-            _ => { vec![] },
+            DifferentialEvent::MergeShortfall(x) => {
+                vec![
+                    Value::Usize(x.operator),
+                    Value::Usize(x.scale),
+                    Value::Usize(x.shortfall),
+                ]
+            },
+            DifferentialEvent::Drop(x) => {
+                vec![
+                    Value::Usize(x.operator),
+                    Value::Usize(x.length),
+                ]
+            },
+            // The change in the number of handles is the difference of the update.
+            DifferentialEvent::TraceShare(x) => {
+                vec![
+                    Value::Usize(x.operator),
+                ]
+            },
+            DifferentialEvent::Layout(x) => {
+                vec![
+                    Value::Usize(x.operator),
+                    Value::Usize(x.update_bytes),
+                ]
+            },
         }
     }
 }
 
-/// Serializes a command into a socket.
+/// Serializes a command into a socket, in the versioned encoding of `wire`.
 pub fn bincode_socket(socket: &mut std::net::TcpStream, command: &Command<Value>) {
-    bincode::serialize_into(socket, command).expect("bincode: serialization failed");
+    wire::write_bincode(socket, command).expect("bincode: serialization failed");
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+//! Loading delimited files into inputs.
+//!
+//! Introducing many tuples with `UpdateInput` commands sends each through the
+//! sequencer to every worker. Loading a file instead has each worker read the
+//! file itself and introduce its share of the lines, so that only the command
+//! is sequenced. Lines are read and introduced in chunks, between which the
+//! worker flushes the input and steps its dataflows, so that the tuples read are
+//! not all held in memory at once.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use timely::communication::Allocate;
+use timely::worker::Worker;
+use differential_dataflow::Data;
+use differential_dataflow::input::InputSession;
+
+use schema::{Schema, Typed};
+use {Time, Diff};
+
+/// The number of lines a worker reads between flushing its input and stepping.
+pub const CHUNK: usize = 100_000;
+
+/// The layout of a delimited file.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Format {
+    /// The character separating fields; fields are not quoted, and cannot contain it.
+    pub delimiter: char,
+    /// Indicates if the first line names the columns, and is not loaded.
+    pub header: bool,
+}
+
+impl Format {
+    /// Comma-separated fields, with a header line.
+    pub fn csv() -> Self { Format { delimiter: ',', header: true } }
+    /// Tab-separated fields, without a header line.
+    pub fn tsv() -> Self { Format { delimiter: '\t', header: false } }
+}
+
+/// A value that can be parsed from a field of a delimited file.
+pub trait FromField: Sized {
+    /// Parses `field`, or reports why it cannot be parsed.
+    fn from_field(field: &str) -> Result<Self, String>;
+}
+
+/// Introduces the tuples of the lines of `path` this worker is responsible for into `input`.
+///
+/// Worker `index` of `peers` loads the lines whose position, counting from zero after any
+/// header, is `index` modulo `peers`; every worker must be able to read the file. Tuples are
+/// checked against `schema`, if any. Returns the number of tuples this worker introduced.
+///
+/// Each worker first parses and checks every line of the file, and introduces no tuples if any
+/// line is invalid, so that the load either applies in full on all workers or not at all. An
+/// error can then only occur after tuples are introduced if the file changes during the load.
+pub fn load_file<A, V>(worker: &mut Worker<A>, input: &mut InputSession<Time, Vec<V>, Diff>, path: &str, format: &Format, schema: Option<&Schema>) -> Result<usize, String>
+where
+    A: Allocate,
+    V: Data+FromField+Typed,
+{
+    parse_lines::<V, _, _>(path, format, schema, |_| true, |_| ())?;
+
+    let index = worker.index();
+    let peers = worker.peers();
+
+    let mut loaded = 0;
+    parse_lines(path, format, schema, |position| position % peers == index, |tuple| {
+        input.insert(tuple);
+        loaded += 1;
+        if loaded % CHUNK == 0 {
+            input.flush();
+            worker.step();
+        }
+    })
+    .map_err(|error| format!("{}, after introducing {} tuples", error, loaded))?;
+
+    input.flush();
+    Ok(loaded)
+}
+
+// Parses and checks the lines of `path` whose positions `select` accepts, passing their tuples to `logic`.
+fn parse_lines<V, S, L>(path: &str, format: &Format, schema: Option<&Schema>, mut select: S, mut logic: L) -> Result<(), String>
+where
+    V: FromField+Typed,
+    S: FnMut(usize) -> bool,
+    L: FnMut(Vec<V>),
+{
+    let file = File::open(path).map_err(|error| format!("failed to open {}: {}", path, error))?;
+    let skipped = if format.header { 1 } else { 0 };
+    let lines = BufReader::new(file).lines().skip(skipped);
+
+    for (position, line) in lines.enumerate() {
+        let line = line.map_err(|error| format!("failed to read {}: {}", path, error))?;
+        if select(position) {
+            let tuple =
+            line.split(format.delimiter)
+                .map(V::from_field)
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|tuple| schema.map_or(Ok(()), |schema| schema.check(&tuple)).map(|()| tuple))
+                .map_err(|error| format!("{}, line {}: {}", path, skipped + position + 1, error))?;
+            logic(tuple);
+        }
+    }
+    Ok(())
+}
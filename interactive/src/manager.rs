@@ -1,24 +1,33 @@
 //! Management of inputs and traces.
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::hash::Hash;
+use std::rc::{Rc, Weak};
 use std::time::Duration;
 
-use timely::dataflow::ProbeHandle;
+use timely::dataflow::{InputHandle, ProbeHandle, Scope};
+use timely::dataflow::operators::CapabilitySet;
 use timely::communication::Allocate;
 use timely::worker::Worker;
 use timely::logging::TimelyEvent;
 
-use timely::dataflow::operators::capture::event::EventIterator;
+use timely::dataflow::operators::capture::event::{Event, EventIterator, EventPusher};
 
-use differential_dataflow::Data;
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::trace::TraceStatistics;
 use differential_dataflow::trace::implementations::ord::{OrdKeySpine, OrdValSpine};
-use differential_dataflow::operators::arrange::TraceAgent;
+use differential_dataflow::operators::arrange::{TraceAgent, ShutdownButton};
 use differential_dataflow::input::InputSession;
 
 use differential_dataflow::logging::DifferentialEvent;
 
-use super::{Time, Diff, Plan};
+use super::{Time, Diff, Plan, Response};
+use advisor::Advisor;
+use schema::Schema;
+use namespace::within;
+use explain::{Explainer, Explanation};
+use redact::Redaction;
 
 /// A trace handle for key-only data.
 pub type TraceKeyHandle<K, T, R> = TraceAgent<K, (), T, R, OrdKeySpine<K, T, R>>;
@@ -35,6 +44,182 @@ pub trait AsVector<T> {
     fn as_vector(self) -> Vec<T>;
 }
 
+/// A maintained trace, identified by its plan and, for keyed arrangements, its keys.
+pub type Maintained<Value> = (Plan<Value>, Option<Vec<usize>>);
+
+/// The name of the system collection describing maintained traces.
+pub const SYSTEM_TRACES: &str = "system/traces";
+
+/// A description of a maintained trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceDescription<Value> {
+    /// The plan of the maintained collection.
+    pub plan: Plan<Value>,
+    /// The columns by which the collection is arranged, or `None` if by its tuples.
+    pub keys: Option<Vec<usize>>,
+    /// The contents of the trace.
+    pub statistics: TraceStatistics<Time>,
+}
+
+/// A description of the inputs, traces, and progress of a manager.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Description<Value> {
+    /// The names of input sessions, in order.
+    pub inputs: Vec<String>,
+    /// The maintained traces, in order of plan and keys.
+    pub traces: Vec<TraceDescription<Value>>,
+    /// The published names and their plans, in order of name.
+    pub names: Vec<(String, Plan<Value>)>,
+    /// The frontier of the probe of all computations.
+    pub frontier: Vec<Time>,
+    /// The numbers of strings interned by this worker, and of bytes of their text.
+    pub interned: (usize, usize),
+}
+
+impl<Value: ::std::fmt::Debug> ::std::fmt::Display for Description<Value> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        for input in self.inputs.iter() {
+            writeln!(f, "Input: {:?}", input)?;
+        }
+        for trace in self.traces.iter() {
+            let statistics = &trace.statistics;
+            writeln!(f, "Trace: {:?} by {:?}: batches {}, updates {}, keys {}, bytes {}",
+                trace.plan, trace.keys, statistics.batches, statistics.updates, statistics.keys, statistics.bytes)?;
+        }
+        for (name, plan) in self.names.iter() {
+            writeln!(f, "Name: {:?} = {:?}", name, plan)?;
+        }
+        writeln!(f, "Interned: strings {}, bytes {}", self.interned.0, self.interned.1)?;
+        writeln!(f, "Frontier: {:?}", self.frontier)
+    }
+}
+
+/// The collection of the latencies of epochs.
+pub const LOGS_LATENCY: &str = "logs/latency";
+
+/// The delay from the first command introducing updates at an epoch until the probe passed it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LatencyEvent {
+    /// The worker whose probe passed the epoch.
+    pub worker: usize,
+    /// The time of the updates.
+    pub epoch: Time,
+    /// The wall-clock delay from the command to the probe passing the epoch.
+    pub latency: Duration,
+}
+
+/// The collection of the latencies of epochs.
+struct Latency<Value> {
+    /// The index of the worker.
+    worker: usize,
+    /// Converts a measured latency to a tuple.
+    convert: Box<Fn(LatencyEvent)->Vec<Value>>,
+    /// Epochs the probe has not passed, and the instant of the first command introducing updates at each.
+    pending: BTreeMap<Time, ::std::time::Instant>,
+}
+
+/// The system collection describing maintained traces.
+struct System<Value> {
+    /// Converts the description of a trace to a tuple.
+    convert: Box<Fn(&TraceDescription<Value>)->Vec<Value>>,
+    /// The tuples currently in the collection.
+    current: Vec<Vec<Value>>,
+}
+
+/// The imports and maintained traces of installed dataflows, which can be uninstalled.
+pub struct Installation<Value> {
+    /// The names the dataflows publish.
+    pub names: Vec<String>,
+    /// Shut down the imports of maintained traces.
+    buttons: Vec<ShutdownButton<CapabilitySet<Time>>>,
+    /// The maintained traces imported, which are pinned while the dataflows are installed.
+    imports: Vec<Maintained<Value>>,
+    /// Plans whose unkeyed traces the dataflows maintain.
+    unkeyed: Vec<Plan<Value>>,
+    /// Plans and keys whose keyed traces the dataflows maintain.
+    keyed: Vec<(Plan<Value>, Vec<usize>)>,
+}
+
+impl<Value> Installation<Value> {
+    /// Creates a new, empty installation.
+    fn new() -> Self {
+        Installation {
+            names: Vec::new(),
+            buttons: Vec::new(),
+            imports: Vec::new(),
+            unkeyed: Vec::new(),
+            keyed: Vec::new(),
+        }
+    }
+}
+
+/// Events captured from a subscribed collection.
+type Events<Value> = Rc<RefCell<VecDeque<Event<Time, (Vec<Value>, Time, Diff)>>>>;
+
+/// Captures events into a subscription, until the subscription is dropped.
+struct Capture<Value>(Weak<RefCell<VecDeque<Event<Time, (Vec<Value>, Time, Diff)>>>>);
+
+impl<Value> EventPusher<Time, (Vec<Value>, Time, Diff)> for Capture<Value> {
+    fn push(&mut self, event: Event<Time, (Vec<Value>, Time, Diff)>) {
+        if let Some(events) = self.0.upgrade() {
+            events.borrow_mut().push_back(event);
+        }
+    }
+}
+
+/// The updates of a collection, and the progress of its computation, reported to a subscriber.
+pub struct Subscription<Value> {
+    /// The worker that receives the updates, and reports them.
+    pub origin: usize,
+    events: Events<Value>,
+    /// Accumulated changes to the number of capabilities at each time.
+    counts: BTreeMap<Time, i64>,
+    /// The dataflow producing the subscribed collection.
+    installation: Installation<Value>,
+}
+
+impl<Value: Data> Subscription<Value> {
+
+    /// Drains captured events into a response reporting updates and one reporting the frontier.
+    ///
+    /// Either response is omitted if there are no updates, or the frontier has not changed.
+    fn poll(&mut self, name: &str, redactor: Option<&Redaction<Value>>) -> Vec<Response<Value>> {
+        let mut updates = Vec::new();
+        let mut changed = false;
+        for event in self.events.borrow_mut().drain(..) {
+            match event {
+                Event::Messages(_time, data) => updates.extend(data),
+                Event::Progress(changes) => {
+                    for (time, delta) in changes.into_iter() {
+                        let remove = {
+                            let count = self.counts.entry(time.clone()).or_insert(0);
+                            *count += delta;
+                            *count == 0
+                        };
+                        if remove { self.counts.remove(&time); }
+                        changed = true;
+                    }
+                },
+            }
+        }
+
+        for (tuple, _time, _diff) in updates.iter_mut() {
+            ::redact::apply(redactor, name, tuple);
+        }
+
+        let mut responses = Vec::new();
+        if !updates.is_empty() {
+            responses.push(Response::Updates(name.to_string(), updates));
+        }
+        if changed {
+            // Times are totally ordered, and the frontier is the least time with a capability.
+            let frontier = self.counts.keys().next().cloned().into_iter().collect();
+            responses.push(Response::Progress(name.to_string(), frontier));
+        }
+        responses
+    }
+}
+
 /// Manages inputs and traces.
 pub struct Manager<Value: Data> {
     /// Manages input sessions.
@@ -43,6 +228,23 @@ pub struct Manager<Value: Data> {
     pub traces: TraceManager<Value>,
     /// Probes all computations.
     pub probe: ProbeHandle<Time>,
+    /// Recommends arrangements based on installed queries.
+    pub advisor: Advisor<Value>,
+    /// Subscribed collections, by name.
+    pub subscriptions: HashMap<String, Subscription<Value>>,
+    /// Installed inputs and queries, which can be uninstalled by any name they publish.
+    pub installations: Vec<Installation<Value>>,
+    /// The system collection describing maintained traces, if published.
+    system: Option<System<Value>>,
+    /// Schemas of named collections, declared for inputs and inferred for installed plans.
+    pub schemas: HashMap<String, Schema>,
+    /// The duration for which logged events are retained, if bounded.
+    log_retention: Option<Time>,
+    /// The collection of the latencies of epochs, if published.
+    latency: Option<Latency<Value>>,
+    /// The numbers of updates in the batches logged by each operator, by global identifier,
+    /// not yet reported to the advisor.
+    logged_costs: Rc<RefCell<HashMap<usize, usize>>>,
 }
 
 impl<Value: Data+Hash> Manager<Value> {
@@ -53,12 +255,60 @@ impl<Value: Data+Hash> Manager<Value> {
             inputs: InputManager::new(),
             traces: TraceManager::new(),
             probe: ProbeHandle::new(),
+            advisor: Advisor::new(),
+            subscriptions: HashMap::new(),
+            installations: Vec::new(),
+            system: None,
+            schemas: HashMap::new(),
+            log_retention: None,
+            latency: None,
+            logged_costs: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Reports the costs logged since the last report to the advisor.
+    ///
+    /// The cost of a plan is the number of updates in the batches of the arrangements of its
+    /// output, as logged by this worker once differential logging is published.
+    pub fn observe_costs(&mut self) {
+        for (operator, cost) in self.logged_costs.borrow_mut().drain() {
+            if let Some(plan) = self.traces.arranged_plan(operator) {
+                self.advisor.observe_cost(plan, cost);
+            }
         }
     }
 
-    /// Clear the managed inputs and traces.
-    pub fn shutdown(&mut self) {
+    /// Retains logged events for `retention` after their times, or indefinitely if `None`.
+    ///
+    /// Applies to the logging published afterwards. Events are retracted once retention has
+    /// passed, and their updates are dropped as maintained traces compact past the retractions,
+    /// as they do when inputs advance.
+    pub fn set_log_retention(&mut self, retention: Option<Time>) {
+        self.log_retention = retention;
+    }
+
+    /// Completes the computation of submitted updates, and then clears the managed inputs and traces.
+    ///
+    /// Inputs are flushed and advanced past the latest of their times, and the worker steps until
+    /// the probe reaches that time, so that no submitted update is discarded unprocessed. Then the
+    /// imports of installed dataflows are shut down, and the inputs and traces are dropped.
+    pub fn shutdown<A: Allocate>(&mut self, worker: &mut Worker<A>) {
+        if let Some(last) = self.inputs.time() {
+            let next = last + Duration::new(0, 1);
+            self.inputs.advance_time(&next);
+            while self.probe.less_than(&next) {
+                worker.step();
+            }
+        }
+
+        for installation in self.installations.drain(..) {
+            self.traces.uninstall(installation);
+        }
+        for (_name, subscription) in self.subscriptions.drain() {
+            self.traces.uninstall(subscription.installation);
+        }
         self.inputs.sessions.clear();
+        self.inputs.upserts.clear();
         self.traces.inputs.clear();
         self.traces.arrangements.clear();
     }
@@ -71,16 +321,321 @@ impl<Value: Data+Hash> Manager<Value> {
         trace: KeysOnlyHandle<Value>)
     {
         self.inputs.sessions.insert(name.clone(), input);
-        self.traces.set_unkeyed(&Plan::Source(name), &trace);
+        self.traces.set_unkeyed(&Plan::Source(name.clone()), &trace);
+        self.installed(vec![name]);
+    }
+
+    /// Inserts a new upsert input session by name.
+    ///
+    /// The collection of the input is maintained both by its tuples, and by its keys.
+    pub fn insert_upsert_input(
+        &mut self,
+        name: String,
+        session: UpsertSession<Value>,
+        keyed: KeysValsHandle<Value>,
+        unkeyed: KeysOnlyHandle<Value>)
+    {
+        let keys = (0 .. session.arity).collect::<Vec<_>>();
+        self.inputs.insert_upsert_input(name.clone(), session);
+        self.traces.set_keyed(&Plan::Source(name.clone()), &keys[..], &keyed);
+        self.traces.set_unkeyed(&Plan::Source(name.clone()), &unkeyed);
+        self.installed(vec![name]);
+    }
+
+    /// Records the dataflows installed since the last installation, as publishing `names`.
+    pub fn installed(&mut self, names: Vec<String>) {
+        let mut installation = self.traces.take_installation();
+        installation.names = names;
+        self.installations.push(installation);
+    }
+
+    /// Uninstalls the installation publishing `name`, and the other names it publishes.
+    ///
+    /// The imports of its dataflows are shut down, so that they complete, and its input
+    /// sessions and maintained traces are dropped. Returns the numbers of traces, batches,
+    /// and updates released, or `None` if no installation publishes `name`. Dataflows that
+    /// imported the released traces continue to hold them until they are uninstalled.
+    pub fn uninstall(&mut self, name: &str) -> Option<(usize, usize, usize)> {
+        let position = self.installations.iter().position(|installation| installation.names.iter().any(|x| x == name))?;
+        let installation = self.installations.remove(position);
+        for name in installation.names.iter() {
+            self.inputs.close(name);
+            self.schemas.remove(name);
+        }
+        Some(self.traces.uninstall(installation))
     }
 
     /// Advances inputs and traces to `time`.
+    ///
+    /// The system collection is first updated to describe the traces before `time`, and interned
+    /// strings are purged once the dictionary has grown enough since it was last purged.
     pub fn advance_time(&mut self, time: &Time) {
+        self.refresh_system();
         self.inputs.advance_time(time);
         self.traces.advance_time(time);
+        ::intern::compact();
+    }
+
+    /// Describes the input sessions, maintained traces, published names, and progress of the manager.
+    ///
+    /// Traces are described by `TraceReader::statistics`, which visits each of their keys.
+    pub fn describe(&mut self) -> Description<Value> {
+        Description {
+            inputs: self.inputs.names(),
+            traces: self.traces.describe(),
+            names: self.traces.names(),
+            frontier: self.probe.with_frontier(|frontier| frontier.to_vec()),
+            interned: ::intern::statistics(),
+        }
+    }
+
+    /// Publishes the system collection `system/traces`, describing maintained traces.
+    ///
+    /// The collection holds a tuple for each maintained trace, produced by `AsVector`, and
+    /// is updated each time the manager advances its inputs.
+    pub fn publish_system<A>(&mut self, worker: &mut Worker<A>)
+    where
+        A: Allocate,
+        TraceDescription<Value>: AsVector<Value>,
+    {
+        use differential_dataflow::input::Input;
+        use differential_dataflow::operators::arrange::ArrangeBySelf;
+
+        let (input, trace) = worker.dataflow(|scope| {
+            let (input, collection) = scope.new_collection();
+            let trace = collection.arrange_by_self().trace;
+            (input, trace)
+        });
+
+        self.insert_input(SYSTEM_TRACES.to_string(), input, trace);
+        self.system = Some(System {
+            convert: Box::new(|description: &TraceDescription<Value>| description.clone().as_vector()),
+            current: Vec::new(),
+        });
+    }
+
+    /// Updates the system collection, if it is published and open, to describe the current traces.
+    fn refresh_system(&mut self) {
+        if let Some(system) = self.system.as_mut() {
+            if let Some(session) = self.inputs.sessions.get_mut(SYSTEM_TRACES) {
+                let mut tuples = self.traces.describe().iter().map(|trace| (system.convert)(trace)).collect::<Vec<_>>();
+                tuples.sort();
+                if tuples != system.current {
+                    for tuple in system.current.drain(..) {
+                        session.remove(tuple);
+                    }
+                    for tuple in tuples.iter() {
+                        session.insert(tuple.clone());
+                    }
+                    system.current = tuples;
+                }
+            }
+        }
+    }
+
+    /// The contents of the collection of `plan` at `time`, as held by this worker.
+    ///
+    /// The plan must be maintained, and is read from its trace rather than installed. Reading
+    /// fails if the system has not completed `time`, or if the trace's advance frontier is not at
+    /// or before `time`, so that it may have compacted `time` together with later times, which
+    /// `TraceManager::set_history` can prevent.
+    pub fn query_as_of(&mut self, plan: &Plan<Value>, time: &Time) -> Result<Vec<(Vec<Value>, Diff)>, String> {
+        use differential_dataflow::trace::TraceReader;
+        let plan = self.prepare(plan);
+        let mut trace = self.traces.get_unkeyed(&plan).ok_or_else(|| format!("Plan not maintained: {:?}", plan))?;
+        if self.probe.less_equal(time) {
+            return Err(format!("Time not complete: {:?}", time));
+        }
+        // Compaction preserves the collection at `time` only if the trace has not advanced beyond it.
+        if !trace.advance_frontier().iter().any(|t| t <= time) {
+            return Err(format!("Time has been compacted: {:?}", time));
+        }
+        // The collection at `time` accumulates the updates at times strictly before the next time.
+        let upper = [*time + Duration::new(0, 1)];
+        let contents = trace.snapshot(&upper).ok_or_else(|| format!("Time has been compacted: {:?}", time))?;
+        let redactor = self.traces.redactor();
+        let context = match &plan { Plan::Source(name) => name.clone(), plan => format!("{:?}", plan) };
+        Ok(contents.map(|(mut tuple, (), diff)| { ::redact::apply(redactor.as_ref(), &context, &mut tuple); (tuple, diff) }).collect())
+    }
+
+    /// Reports the numbers of traces, batches, and updates maintained by installations and
+    /// subscriptions, either all or those publishing names within `namespace`.
+    pub fn usage(&mut self, namespace: Option<&str>) -> (usize, usize, usize) {
+        let selected = |names: &[String]| namespace.map(|namespace| names.iter().any(|name| within(namespace, name))).unwrap_or(true);
+        let mut usage = (0, 0, 0);
+        for installation in self.installations.iter() {
+            if selected(&installation.names[..]) {
+                let (traces, batches, updates) = self.traces.measure(installation);
+                usage = (usage.0 + traces, usage.1 + batches, usage.2 + updates);
+            }
+        }
+        for (name, subscription) in self.subscriptions.iter() {
+            if selected(&[name.clone()]) {
+                let (traces, batches, updates) = self.traces.measure(&subscription.installation);
+                usage = (usage.0 + traces, usage.1 + batches, usage.2 + updates);
+            }
+        }
+        usage
+    }
+
+    /// Uninstalls the installations and subscriptions publishing names within `namespace`.
+    ///
+    /// Returns the numbers of traces, batches, and updates released.
+    pub fn shutdown_namespace(&mut self, namespace: &str) -> (usize, usize, usize) {
+        let installed =
+        self.installations
+            .iter()
+            .filter_map(|installation| installation.names.iter().find(|name| within(namespace, name)).cloned())
+            .collect::<Vec<_>>();
+        let subscribed =
+        self.subscriptions
+            .keys()
+            .filter(|name| within(namespace, name))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut released = (0, 0, 0);
+        for (traces, batches, updates) in installed.iter().filter_map(|name| self.uninstall(name)).collect::<Vec<_>>() {
+            released = (released.0 + traces, released.1 + batches, released.2 + updates);
+        }
+        for (traces, batches, updates) in subscribed.iter().filter_map(|name| self.unsubscribe(name)).collect::<Vec<_>>() {
+            released = (released.0 + traces, released.1 + batches, released.2 + updates);
+        }
+        released
+    }
+
+    /// Creates a subscription to `name`, reporting to worker `origin`, and the sink capturing `stream` into it.
+    ///
+    /// The stream should be exchanged to `origin`, which reports its updates and progress; the
+    /// captured events at other workers only describe progress, and are discarded. The dataflows
+    /// installed since the last installation are those of the subscription, which `unsubscribe`
+    /// uninstalls.
+    pub fn subscribe<S>(&mut self, name: &str, origin: usize, stream: &::timely::dataflow::Stream<S, (Vec<Value>, Time, Diff)>)
+    where
+        S: ::timely::dataflow::Scope<Timestamp=Time>,
+    {
+        use timely::dataflow::operators::capture::Capture as CaptureStream;
+
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        stream.capture_into(Capture(Rc::downgrade(&events)));
+        let installation = self.traces.take_installation();
+        self.subscriptions.insert(name.to_string(), Subscription { origin, events, counts: BTreeMap::new(), installation });
+    }
+
+    /// Removes the subscription `name`, and uninstalls its dataflow.
+    ///
+    /// Returns the numbers of traces, batches, and updates released, or `None` if there is
+    /// no subscription `name`.
+    pub fn unsubscribe(&mut self, name: &str) -> Option<(usize, usize, usize)> {
+        let subscription = self.subscriptions.remove(name)?;
+        Some(self.traces.uninstall(subscription.installation))
+    }
+
+    /// Publishes the collection `logs/latency`, of the latencies of epochs.
+    ///
+    /// The collection holds a tuple for each epoch at which commands introduced updates,
+    /// produced by `AsVector` from the wall-clock delay from the first such command until
+    /// the probe of this worker passed the epoch. Epochs are recorded by `record_epochs`,
+    /// and their latencies measured by `poll_latency`.
+    pub fn publish_latency<A>(&mut self, worker: &mut Worker<A>)
+    where
+        A: Allocate,
+        LatencyEvent: AsVector<Value>,
+    {
+        use differential_dataflow::input::Input;
+        use differential_dataflow::operators::arrange::ArrangeBySelf;
+
+        let (input, trace) = worker.dataflow(|scope| {
+            let (input, collection) = scope.new_collection();
+            let trace = collection.arrange_by_self().trace;
+            (input, trace)
+        });
+
+        self.insert_input(LOGS_LATENCY.to_string(), input, trace);
+        self.latency = Some(Latency {
+            worker: worker.index(),
+            convert: Box::new(|event: LatencyEvent| event.as_vector()),
+            pending: BTreeMap::new(),
+        });
+    }
+
+    /// Records that a command introduced updates at `epochs`, if latencies are published.
+    ///
+    /// Epochs already recorded keep the instant of their first command.
+    pub fn record_epochs<I: IntoIterator<Item=Time>>(&mut self, epochs: I) {
+        if let Some(latency) = self.latency.as_mut() {
+            let now = ::std::time::Instant::now();
+            for epoch in epochs {
+                latency.pending.entry(epoch).or_insert(now);
+            }
+        }
+    }
+
+    /// Publishes the latencies of recorded epochs the probe has passed.
+    pub fn poll_latency(&mut self) {
+        let probe = &self.probe;
+        if let Some(latency) = self.latency.as_mut() {
+            if let Some(session) = self.inputs.sessions.get_mut(LOGS_LATENCY) {
+                while let Some(epoch) = latency.pending.keys().next().cloned() {
+                    if probe.less_equal(&epoch) { break; }
+                    let instant = latency.pending.remove(&epoch).expect("epoch is pending");
+                    let event = LatencyEvent { worker: latency.worker, epoch, latency: instant.elapsed() };
+                    let mut tuple = (latency.convert)(event);
+                    ::redact::apply(self.traces.redactor.as_ref(), LOGS_LATENCY, &mut tuple);
+                    session.insert(tuple);
+                }
+            }
+        }
+    }
+
+    /// Reports the updates and progress of subscriptions reported by worker `index`.
+    ///
+    /// Events captured for subscriptions reported by other workers are discarded.
+    pub fn poll_subscriptions(&mut self, index: usize) -> Vec<Response<Value>> {
+        let mut responses = Vec::new();
+        let redactor = self.traces.redactor();
+        for (name, subscription) in self.subscriptions.iter_mut() {
+            if subscription.origin == index {
+                responses.extend(subscription.poll(name, redactor.as_ref()));
+            }
+            else {
+                subscription.events.borrow_mut().clear();
+            }
+        }
+        responses
+    }
+
+    /// Rewrites `plan` into the plan that installing it renders.
+    ///
+    /// The plan is canonicalized, optimized, and its repeated sub-plans are shared.
+    pub fn prepare(&self, plan: &Plan<Value>) -> Plan<Value> {
+        use optimize::Optimizer;
+        use share::{canonicalize, eliminate};
+
+        let plan = canonicalize(plan);
+        let plan = canonicalize(&Optimizer::new(&self.traces).optimize(&plan));
+        eliminate(&plan, &self.traces)
+    }
+
+    /// Describes how installing `plan` would render it, without installing it.
+    ///
+    /// The explanation lists the operators that would be rendered, the maintained
+    /// arrangements they would import, and the arrangements they would build.
+    pub fn explain(&self, plan: &Plan<Value>) -> Explanation<Value> {
+        Explainer::new(&self.traces).explain(&self.prepare(plan))
     }
 
     /// Timely logging capture and arrangement.
+    ///
+    /// Each kind of event is published as a collection under `logs/timely/`, of operators,
+    /// channels, scheduling, messages, parking of the worker, text, progress messages, and
+    /// operator shutdowns. The first column of operators, scheduling, and shutdowns is the
+    /// operator identifier, and that of channels, messages, and progress messages is the
+    /// channel identifier; these collections are also arranged by it, so that joins with
+    /// operators and channels use the arrangements rather than building their own.
+    ///
+    /// Events other than those of operators, channels, and shutdowns, which describe the
+    /// dataflows rather than their activity, are retained as `set_log_retention` configures.
     pub fn publish_timely_logging<A, I>(&mut self, worker: &mut Worker<A>, events: I)
     where
         A: Allocate,
@@ -88,7 +643,9 @@ impl<Value: Data+Hash> Manager<Value> {
         I : IntoIterator,
         <I as IntoIterator>::Item: EventIterator<Duration, (Duration, usize, TimelyEvent)>+'static
     {
-        let (operates, channels, schedule, messages) =
+        let retention = self.log_retention;
+        let redactor = self.traces.redactor();
+        let arranged =
         worker.dataflow(move |scope| {
 
             use timely::dataflow::operators::capture::Replay;
@@ -105,6 +662,10 @@ impl<Value: Data+Hash> Manager<Value> {
             let (mut channels_out, channels) = demux.new_output();
             let (mut schedule_out, schedule) = demux.new_output();
             let (mut messages_out, messages) = demux.new_output();
+            let (mut park_out, park) = demux.new_output();
+            let (mut text_out, text) = demux.new_output();
+            let (mut progress_out, progress) = demux.new_output();
+            let (mut shutdown_out, shutdown) = demux.new_output();
 
             let mut demux_buffer = Vec::new();
 
@@ -116,6 +677,10 @@ impl<Value: Data+Hash> Manager<Value> {
                     let mut channels = channels_out.activate();
                     let mut schedule = schedule_out.activate();
                     let mut messages = messages_out.activate();
+                    let mut park = park_out.activate();
+                    let mut text = text_out.activate();
+                    let mut progress = progress_out.activate();
+                    let mut shutdown = shutdown_out.activate();
 
                     input.for_each(|time, data| {
                         data.swap(&mut demux_buffer);
@@ -123,6 +688,10 @@ impl<Value: Data+Hash> Manager<Value> {
                         let mut channels_session = channels.session(&time);
                         let mut schedule_session = schedule.session(&time);
                         let mut messages_session = messages.session(&time);
+                        let mut park_session = park.session(&time);
+                        let mut text_session = text.session(&time);
+                        let mut progress_session = progress.session(&time);
+                        let mut shutdown_session = shutdown.session(&time);
 
                         for (time, _worker, datum) in demux_buffer.drain(..) {
                             match datum {
@@ -138,6 +707,18 @@ impl<Value: Data+Hash> Manager<Value> {
                                 TimelyEvent::Messages(_) => {
                                     messages_session.give((datum.as_vector(), time, 1));
                                 },
+                                TimelyEvent::Park(_) => {
+                                    park_session.give((datum.as_vector(), time, 1));
+                                },
+                                TimelyEvent::Text(_) => {
+                                    text_session.give((datum.as_vector(), time, 1));
+                                },
+                                TimelyEvent::Progress(_) => {
+                                    progress_session.give((datum.as_vector(), time, 1));
+                                },
+                                TimelyEvent::Shutdown(_) => {
+                                    shutdown_session.give((datum.as_vector(), time, 1));
+                                },
                                 _ => { },
                             }
                         }
@@ -146,22 +727,58 @@ impl<Value: Data+Hash> Manager<Value> {
             });
 
             use differential_dataflow::collection::AsCollection;
-            use differential_dataflow::operators::arrange::ArrangeBySelf;
-            let operates = operates.as_collection().arrange_by_self().trace;
-            let channels = channels.as_collection().arrange_by_self().trace;
-            let schedule = schedule.as_collection().arrange_by_self().trace;
-            let messages = messages.as_collection().arrange_by_self().trace;
-
-            (operates, channels, schedule, messages)
+            use differential_dataflow::operators::arrange::{ArrangeByKey, ArrangeBySelf};
+
+            // Events whose first column is an operator or channel identifier are also arranged by it,
+            // and those of the activity of dataflows are retained for the configured duration.
+            vec![
+                ("logs/timely/operates", operates, true, None),
+                ("logs/timely/channels", channels, true, None),
+                ("logs/timely/schedule", schedule, true, retention),
+                ("logs/timely/messages", messages, true, retention),
+                ("logs/timely/park", park, false, retention),
+                ("logs/timely/text", text, false, retention),
+                ("logs/timely/progress", progress, true, retention),
+                ("logs/timely/shutdown", shutdown, true, None),
+            ]
+            .into_iter()
+            .map(|(name, stream, identified, retention)| {
+                let collection = retain(redact(stream.as_collection(), name, redactor.clone()), retention);
+                let keyed = if identified {
+                    Some(collection
+                        .map(|tuple| (tuple[..1].to_vec(), tuple[1..].to_vec()))
+                        .arrange_by_key()
+                        .trace)
+                }
+                else { None };
+                (name, collection.arrange_by_self().trace, keyed)
+            })
+            .collect::<Vec<_>>()
         });
 
-        self.traces.set_unkeyed(&Plan::Source("logs/timely/operates".to_string()), &operates);
-        self.traces.set_unkeyed(&Plan::Source("logs/timely/channels".to_string()), &channels);
-        self.traces.set_unkeyed(&Plan::Source("logs/timely/schedule".to_string()), &schedule);
-        self.traces.set_unkeyed(&Plan::Source("logs/timely/messages".to_string()), &messages);
+        let mut names = Vec::new();
+        for (name, unkeyed, keyed) in arranged.into_iter() {
+            let plan = Plan::Source(name.to_string());
+            self.traces.set_unkeyed(&plan, &unkeyed);
+            if let Some(keyed) = keyed {
+                self.traces.set_keyed(&plan, &[0], &keyed);
+            }
+            names.push(name.to_string());
+        }
+        self.installed(names);
     }
 
-    /// Timely logging capture and arrangement.
+    /// Differential logging capture and arrangement.
+    ///
+    /// Each kind of event is published as a collection under `logs/differential/arrange/`,
+    /// of batches, merges, merge shortfalls, dropped batches, trace sharing, and the bytes of
+    /// each update of a trace, whose first column is the operator maintaining the trace. Each
+    /// collection is also arranged by its operator. Trace sharing events have no other columns, and change the multiplicity of
+    /// their operator by their difference, which so accumulates to its number of handles.
+    ///
+    /// Merges and merge shortfalls are retained as `set_log_retention` configures. Batches,
+    /// dropped batches, trace sharing, and layouts are retained indefinitely, as they accumulate
+    /// to the live contents of traces.
     pub fn publish_differential_logging<A, I>(&mut self, worker: &mut Worker<A>, events: I)
     where
         A: Allocate,
@@ -169,7 +786,10 @@ impl<Value: Data+Hash> Manager<Value> {
         I : IntoIterator,
         <I as IntoIterator>::Item: EventIterator<Duration, (Duration, usize, DifferentialEvent)>+'static
     {
-        let (merge,batch) =
+        let retention = self.log_retention;
+        let redactor = self.traces.redactor();
+        let logged_costs = self.logged_costs.clone();
+        let arranged =
         worker.dataflow(move |scope| {
 
             use timely::dataflow::operators::capture::Replay;
@@ -184,6 +804,10 @@ impl<Value: Data+Hash> Manager<Value> {
 
             let (mut batch_out, batch) = demux.new_output();
             let (mut merge_out, merge) = demux.new_output();
+            let (mut shortfall_out, shortfall) = demux.new_output();
+            let (mut dropped_out, dropped) = demux.new_output();
+            let (mut share_out, share) = demux.new_output();
+            let (mut layout_out, layout) = demux.new_output();
 
             let mut demux_buffer = Vec::new();
 
@@ -193,37 +817,209 @@ impl<Value: Data+Hash> Manager<Value> {
 
                     let mut batch = batch_out.activate();
                     let mut merge = merge_out.activate();
+                    let mut shortfall = shortfall_out.activate();
+                    let mut dropped = dropped_out.activate();
+                    let mut share = share_out.activate();
+                    let mut layout = layout_out.activate();
 
                     input.for_each(|time, data| {
                         data.swap(&mut demux_buffer);
                         let mut batch_session = batch.session(&time);
                         let mut merge_session = merge.session(&time);
+                        let mut shortfall_session = shortfall.session(&time);
+                        let mut dropped_session = dropped.session(&time);
+                        let mut share_session = share.session(&time);
+                        let mut layout_session = layout.session(&time);
 
                         for (time, _worker, datum) in demux_buffer.drain(..) {
                             match datum {
-                                DifferentialEvent::Batch(_) => {
+                                DifferentialEvent::Batch(ref event) => {
+                                    *logged_costs.borrow_mut().entry(event.operator).or_insert(0) += event.length;
                                     batch_session.give((datum.as_vector(), time, 1));
                                 },
                                 DifferentialEvent::Merge(_) => {
                                     merge_session.give((datum.as_vector(), time, 1));
                                 },
-                                _ => { },
+                                DifferentialEvent::MergeShortfall(_) => {
+                                    shortfall_session.give((datum.as_vector(), time, 1));
+                                },
+                                DifferentialEvent::Drop(_) => {
+                                    dropped_session.give((datum.as_vector(), time, 1));
+                                },
+                                DifferentialEvent::TraceShare(ref event) => {
+                                    let diff = event.diff;
+                                    share_session.give((datum.as_vector(), time, diff));
+                                },
+                                DifferentialEvent::Layout(_) => {
+                                    layout_session.give((datum.as_vector(), time, 1));
+                                },
                             }
                         }
                     });
                 }
             });
 
+            use differential_dataflow::collection::AsCollection;
+            use differential_dataflow::operators::arrange::{ArrangeByKey, ArrangeBySelf};
+
+            vec![
+                ("logs/differential/arrange/batch", batch, None),
+                ("logs/differential/arrange/merge", merge, retention),
+                ("logs/differential/arrange/shortfall", shortfall, retention),
+                ("logs/differential/arrange/drop", dropped, None),
+                ("logs/differential/arrange/share", share, None),
+                ("logs/differential/arrange/layout", layout, None),
+            ]
+            .into_iter()
+            .map(|(name, stream, retention)| {
+                let collection = retain(redact(stream.as_collection(), name, redactor.clone()), retention);
+                let keyed =
+                collection
+                    .map(|tuple| (tuple[..1].to_vec(), tuple[1..].to_vec()))
+                    .arrange_by_key()
+                    .trace;
+                (name, collection.arrange_by_self().trace, keyed)
+            })
+            .collect::<Vec<_>>()
+        });
+
+        let mut names = Vec::new();
+        for (name, unkeyed, keyed) in arranged.into_iter() {
+            let plan = Plan::Source(name.to_string());
+            self.traces.set_unkeyed(&plan, &unkeyed);
+            self.traces.set_keyed(&plan, &[0], &keyed);
+            names.push(name.to_string());
+        }
+        self.installed(names);
+    }
+
+    /// Registers a logger of application events, and publishes them as a collection.
+    ///
+    /// The logger is registered with the worker under the name of the collection,
+    /// `logs/user/<name>`, from which the application obtains it with
+    /// `worker.log_register().get::<E>(..)`. Each logged event is a tuple of the
+    /// collection, produced by `AsVector`. The logging dataflow shuts down once the
+    /// logger is deregistered, by inserting one that discards its events.
+    ///
+    /// As it builds a dataflow, each worker must register the same loggers in the same order.
+    /// Events are retained as `set_log_retention` configures.
+    pub fn publish_user_logging<A, E>(&mut self, worker: &mut Worker<A>, name: &str) -> String
+    where
+        A: Allocate,
+        E: AsVector<Value>+Clone+'static,
+    {
+        use timely::dataflow::operators::capture::event::link::EventLink;
+        use timely::logging::BatchLogger;
+
+        let qualified = format!("logs/user/{}", name);
+
+        let events = Rc::new(EventLink::<Duration, (Duration, usize, E)>::new());
+        let mut logger = BatchLogger::new(events.clone());
+        worker
+            .log_register()
+            .insert::<E,_>(&qualified, move |time, data| logger.publish_batch(time, data));
+
+        let retention = self.log_retention;
+        let redactor = self.traces.redactor();
+        let context = qualified.clone();
+        let trace =
+        worker.dataflow(move |scope| {
+
+            use timely::dataflow::operators::Map;
+            use timely::dataflow::operators::capture::Replay;
             use differential_dataflow::collection::AsCollection;
             use differential_dataflow::operators::arrange::ArrangeBySelf;
-            let batch = batch.as_collection().arrange_by_self().trace;
-            let merge = merge.as_collection().arrange_by_self().trace;
 
-            (merge,batch)
+            let events =
+            Some(events)
+                .replay_into(scope)
+                .map(|(time, _worker, datum)| (datum.as_vector(), time, 1))
+                .as_collection();
+
+            retain(redact(events, &context, redactor), retention)
+                .arrange_by_self()
+                .trace
         });
 
-        self.traces.set_unkeyed(&Plan::Source("logs/differential/arrange/batch".to_string()), &batch);
-        self.traces.set_unkeyed(&Plan::Source("logs/differential/arrange/merge".to_string()), &merge);
+        self.traces.set_unkeyed(&Plan::Source(qualified.clone()), &trace);
+        self.installed(vec![qualified.clone()]);
+        qualified
+    }
+}
+
+/// Rewrites the tuples of the logging collection `name` with `redactor`, if there is one.
+fn redact<G, Value>(collection: Collection<G, Vec<Value>, Diff>, name: &str, redactor: Option<Redaction<Value>>) -> Collection<G, Vec<Value>, Diff>
+where
+    G: Scope<Timestamp=Time>,
+    Value: Data,
+{
+    match redactor {
+        Some(redactor) => {
+            let name = name.to_string();
+            collection.map(move |mut tuple| { ::redact::apply(Some(&redactor), &name, &mut tuple); tuple })
+        },
+        None => collection,
+    }
+}
+
+/// Retracts each update of `collection` once `retention` has passed after its time, if supplied.
+fn retain<G, Value>(collection: Collection<G, Vec<Value>, Diff>, retention: Option<Time>) -> Collection<G, Vec<Value>, Diff>
+where
+    G: Scope<Timestamp=Time>,
+    Value: Data,
+{
+    use timely::dataflow::operators::FlatMap;
+    use differential_dataflow::collection::AsCollection;
+
+    match retention {
+        Some(retention) => {
+            collection
+                .inner
+                .flat_map(move |(tuple, time, diff)| {
+                    let expired = (tuple.clone(), time + retention, -diff);
+                    vec![(tuple, time, diff), expired]
+                })
+                .as_collection()
+        },
+        None => collection,
+    }
+}
+
+/// An input of upserts, each setting or removing the value of a key from some time on.
+///
+/// The collection of the input holds the tuple of each key followed by its value.
+pub struct UpsertSession<Value: Data> {
+    handle: InputHandle<Time, (Vec<Value>, Option<Vec<Value>>, Time)>,
+    /// The number of columns of each key.
+    pub arity: usize,
+}
+
+impl<Value: Data> UpsertSession<Value> {
+
+    /// Creates a session introducing upserts of keys with `arity` columns through `handle`.
+    pub fn new(handle: InputHandle<Time, (Vec<Value>, Option<Vec<Value>>, Time)>, arity: usize) -> Self {
+        UpsertSession { handle, arity }
+    }
+
+    /// The time from which upserts may be introduced.
+    pub fn time(&self) -> &Time { self.handle.time() }
+
+    /// Introduces upserts, each of a key, its new value or `None` to remove it, and a time.
+    ///
+    /// All upserts are rejected if any has a key of the wrong arity or a time before `time()`.
+    pub fn upsert(&mut self, upserts: Vec<(Vec<Value>, Option<Vec<Value>>, Time)>) -> Result<(), String> {
+        for (key, _value, time) in upserts.iter() {
+            if key.len() != self.arity {
+                return Err(format!("Key has {} columns rather than {}: {:?}", key.len(), self.arity, key));
+            }
+            if time < self.handle.time() {
+                return Err(format!("Time {:?} is before the input time {:?}", time, self.handle.time()));
+            }
+        }
+        for upsert in upserts.into_iter() {
+            self.handle.send(upsert);
+        }
+        Ok(())
     }
 }
 
@@ -231,12 +1027,40 @@ impl<Value: Data+Hash> Manager<Value> {
 pub struct InputManager<Value: Data> {
     /// Input sessions by name.
     pub sessions: HashMap<String, InputSession<Time, Vec<Value>, Diff>>,
+    /// Upsert input sessions by name.
+    pub upserts: HashMap<String, UpsertSession<Value>>,
 }
 
 impl<Value: Data> InputManager<Value> {
 
     /// Creates a new empty input manager.
-    pub fn new() -> Self { Self { sessions: HashMap::new() } }
+    pub fn new() -> Self { Self { sessions: HashMap::new(), upserts: HashMap::new() } }
+
+    /// Inserts a new upsert input session by name.
+    pub fn insert_upsert_input(&mut self, name: String, session: UpsertSession<Value>) {
+        self.upserts.insert(name, session);
+    }
+
+    /// Closes the input session of `name`, of either kind, indicating if there was one.
+    pub fn close(&mut self, name: &str) -> bool {
+        let session = self.sessions.remove(name).is_some();
+        let upsert = self.upserts.remove(name).is_some();
+        session || upsert
+    }
+
+    /// The names of all input sessions, in order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.sessions.keys().chain(self.upserts.keys()).cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// The latest time of any input session, if there are any.
+    pub fn time(&self) -> Option<Time> {
+        let sessions = self.sessions.values().map(|session| session.time().clone());
+        let upserts = self.upserts.values().map(|upsert| upsert.time().clone());
+        sessions.chain(upserts).max()
+    }
 
     /// Advances the times of all managed inputs.
     pub fn advance_time(&mut self, time: &Time) {
@@ -244,6 +1068,9 @@ impl<Value: Data> InputManager<Value> {
             session.advance_to(time.clone());
             session.flush();
         }
+        for upsert in self.upserts.values_mut() {
+            upsert.handle.advance_to(time.clone());
+        }
     }
 
 }
@@ -264,18 +1091,285 @@ pub struct TraceManager<Value: Data> {
     /// Arrangements of collections by key.
     arrangements: HashMap<Plan<Value>, HashMap<Vec<usize>, KeysValsHandle<Value>>>,
 
+    /// Plans published under user-supplied names.
+    ///
+    /// A source of a published name denotes its plan, and finds the arrangements of the plan.
+    names: HashMap<String, Plan<Value>>,
+
+    /// Imports and traces installed since the last call to `take_installation`.
+    installation: Installation<Value>,
+
+    /// The number of bytes cached traces may occupy, beyond which they are evicted.
+    budget: Option<usize>,
+    /// Counts uses of maintained traces, to order them by recency.
+    clock: usize,
+    /// The clock at the most recent use of each maintained trace.
+    recency: HashMap<Maintained<Value>, usize>,
+    /// The number of installed imports of each maintained trace, which may not be evicted.
+    pins: HashMap<Maintained<Value>, usize>,
+    /// The duration of history traces retain, so that they can be read as of past times.
+    history: Time,
+    /// Rewrites tuples as they are emitted, if set.
+    redactor: Option<Redaction<Value>>,
+    /// The plans whose outputs have been arranged, by the global identifiers of the operators
+    /// arranging them, to which the costs logged for those operators are attributed.
+    arranged: HashMap<usize, Plan<Value>>,
+}
+
+/// The numbers of batches and of updates in `trace`.
+fn measure<Tr: ::differential_dataflow::trace::TraceReader<K,V,Time,Diff>, K, V>(trace: &mut Tr) -> (usize, usize) {
+    use differential_dataflow::trace::BatchReader;
+    let mut batches = 0;
+    let mut updates = 0;
+    trace.map_batches(|batch| { batches += 1; updates += batch.len(); });
+    (batches, updates)
 }
 
 impl<Value: Data+Hash> TraceManager<Value> {
 
     /// Creates a new empty trace manager.
-    pub fn new() -> Self { Self { inputs: HashMap::new(), arrangements: HashMap::new() } }
+    pub fn new() -> Self {
+        Self {
+            inputs: HashMap::new(),
+            arrangements: HashMap::new(),
+            names: HashMap::new(),
+            installation: Installation::new(),
+            budget: None,
+            clock: 0,
+            recency: HashMap::new(),
+            pins: HashMap::new(),
+            history: Default::default(),
+            redactor: None,
+            arranged: HashMap::new(),
+        }
+    }
+
+    /// Sets the redactor of tuples emitted from managed collections, or removes it.
+    ///
+    /// Applies to logging published, and dataflows installed, afterwards, and to all later
+    /// responses.
+    pub fn set_redactor(&mut self, redactor: Option<Redaction<Value>>) {
+        self.redactor = redactor;
+    }
+
+    /// The redactor of tuples emitted from managed collections, if any.
+    pub fn redactor(&self) -> Option<Redaction<Value>> {
+        self.redactor.clone()
+    }
+
+    /// Retains `history` before the current time in maintained traces, when they are advanced.
+    pub fn set_history(&mut self, history: Time) {
+        self.history = history;
+    }
+
+    /// The time to which maintained traces may compact, when the system has reached `time`.
+    fn compaction(&self, time: &Time) -> Time {
+        time.checked_sub(self.history).unwrap_or_default()
+    }
+
+    /// Sets the number of bytes cached traces may occupy, or removes the limit.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Records a use of a maintained trace.
+    fn touch(&mut self, maintained: Maintained<Value>) {
+        self.clock += 1;
+        self.recency.insert(maintained, self.clock);
+    }
+
+    /// Indicates if the trace may be evicted: it is neither published, nor imported by installed dataflows.
+    fn evictable(&self, maintained: &Maintained<Value>) -> bool {
+        let published = match maintained {
+            (Plan::Source(_), None) => true,
+            (plan, None) => self.names.values().any(|bound| bound == plan),
+            (_plan, Some(_keys)) => false,
+        };
+        !published && self.pins.get(maintained).cloned().unwrap_or(0) == 0
+    }
+
+    /// Evicts cached traces, least recently used first, until they fit within the budget.
+    ///
+    /// Published traces and traces imported by installed dataflows are not evicted, though they
+    /// count towards the budget. Traces are sized by `TraceReader::statistics`, which visits each
+    /// of their keys. Returns the traces evicted.
+    pub fn enforce_budget(&mut self) -> Vec<Maintained<Value>> {
+
+        use differential_dataflow::trace::TraceReader;
+
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return Vec::new(),
+        };
+
+        let mut total = 0;
+        let mut sizes = Vec::new();
+        for (plan, trace) in self.inputs.iter_mut() {
+            let bytes = trace.statistics().bytes;
+            total += bytes;
+            sizes.push(((plan.clone(), None), bytes));
+        }
+        for (plan, map) in self.arrangements.iter_mut() {
+            for (keys, trace) in map.iter_mut() {
+                let bytes = trace.statistics().bytes;
+                total += bytes;
+                sizes.push(((plan.clone(), Some(keys.clone())), bytes));
+            }
+        }
+
+        let mut candidates =
+        sizes
+            .into_iter()
+            .filter(|(maintained, _bytes)| self.evictable(maintained))
+            .map(|(maintained, bytes)| (self.recency.get(&maintained).cloned().unwrap_or(0), maintained, bytes))
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        let mut evicted = Vec::new();
+        for (_recency, maintained, bytes) in candidates.into_iter() {
+            if total <= budget { break; }
+            self.recency.remove(&maintained);
+            match &maintained {
+                (plan, None) => { self.inputs.remove(plan); },
+                (plan, Some(keys)) => {
+                    let empty = self.arrangements.get_mut(plan).map(|map| { map.remove(keys); map.is_empty() }).unwrap_or(false);
+                    if empty { self.arrangements.remove(plan); }
+                },
+            }
+            total -= bytes;
+            evicted.push(maintained);
+        }
+        evicted
+    }
+
+    /// Publishes `plan` under `name`, so that sources of `name` find the arrangements of `plan`.
+    pub fn bind(&mut self, name: &str, plan: &Plan<Value>) {
+        let plan = self.resolve(plan).clone();
+        self.names.insert(name.to_string(), plan);
+    }
+
+    /// Describes each maintained trace, in order of plan and keys.
+    pub fn describe(&mut self) -> Vec<TraceDescription<Value>> {
+        use differential_dataflow::trace::TraceReader;
+        let mut traces = Vec::new();
+        for (plan, trace) in self.inputs.iter_mut() {
+            traces.push(TraceDescription { plan: plan.clone(), keys: None, statistics: trace.statistics() });
+        }
+        for (plan, map) in self.arrangements.iter_mut() {
+            for (keys, trace) in map.iter_mut() {
+                traces.push(TraceDescription { plan: plan.clone(), keys: Some(keys.clone()), statistics: trace.statistics() });
+            }
+        }
+        traces.sort_by(|x, y| (&x.plan, &x.keys).cmp(&(&y.plan, &y.keys)));
+        traces
+    }
+
+    /// The published names and their plans, in order of name.
+    pub fn names(&self) -> Vec<(String, Plan<Value>)> {
+        let mut names = self.names.iter().map(|(name, plan)| (name.clone(), plan.clone())).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// The plan published under `name`, if any.
+    pub fn get_by_name(&self, name: &str) -> Option<&Plan<Value>> {
+        self.names.get(name)
+    }
+
+    /// The plan a source of a published name denotes, or otherwise `plan` itself.
+    pub fn resolve<'a>(&'a self, plan: &'a Plan<Value>) -> &'a Plan<Value> {
+        if let Plan::Source(name) = plan {
+            if let Some(bound) = self.names.get(name) {
+                return bound;
+            }
+        }
+        plan
+    }
+
+    /// Records that the operator with global identifier `operator` arranges the output of `plan`.
+    pub fn arranged(&mut self, operator: usize, plan: &Plan<Value>) {
+        self.arranged.insert(operator, plan.clone());
+    }
+
+    /// The plan whose output the operator with global identifier `operator` arranges, if recorded.
+    pub fn arranged_plan(&self, operator: usize) -> Option<&Plan<Value>> {
+        self.arranged.get(&operator)
+    }
+
+    /// Records the import of the trace of `plan`, arranged by `keys` if supplied, shut down by `button`.
+    pub fn imported(&mut self, plan: &Plan<Value>, keys: Option<&[usize]>, button: ShutdownButton<CapabilitySet<Time>>) {
+        let maintained = (self.resolve(plan).clone(), keys.map(|keys| keys.to_vec()));
+        *self.pins.entry(maintained.clone()).or_insert(0) += 1;
+        self.touch(maintained.clone());
+        self.installation.imports.push(maintained);
+        self.installation.buttons.push(button);
+    }
+
+    /// Returns the imports and traces installed since the last call, without names.
+    pub fn take_installation(&mut self) -> Installation<Value> {
+        ::std::mem::replace(&mut self.installation, Installation::new())
+    }
+
+    /// Reports the numbers of traces, batches, and updates of the traces `installation` installed.
+    pub fn measure(&mut self, installation: &Installation<Value>) -> (usize, usize, usize) {
+        let mut usage = (0, 0, 0);
+        for plan in installation.unkeyed.iter() {
+            if let Some(trace) = self.inputs.get_mut(plan) {
+                let (batches, updates) = measure(trace);
+                usage = (usage.0 + 1, usage.1 + batches, usage.2 + updates);
+            }
+        }
+        for (plan, keys) in installation.keyed.iter() {
+            if let Some(trace) = self.arrangements.get_mut(plan).and_then(|map| map.get_mut(keys)) {
+                let (batches, updates) = measure(trace);
+                usage = (usage.0 + 1, usage.1 + batches, usage.2 + updates);
+            }
+        }
+        usage
+    }
+
+    /// Shuts down the imports of `installation`, and drops the traces it installed.
+    ///
+    /// Returns the numbers of traces, batches, and updates released.
+    pub fn uninstall(&mut self, installation: Installation<Value>) -> (usize, usize, usize) {
+        for mut button in installation.buttons.into_iter() {
+            button.press();
+        }
+        for maintained in installation.imports.iter() {
+            let unpinned = self.pins.get_mut(maintained).map(|count| { *count -= 1; *count == 0 }).unwrap_or(false);
+            if unpinned { self.pins.remove(maintained); }
+        }
+        for name in installation.names.iter() {
+            self.names.remove(name);
+        }
+        let mut released = (0, 0, 0);
+        for plan in installation.unkeyed.iter() {
+            self.recency.remove(&(plan.clone(), None));
+            if let Some(mut trace) = self.inputs.remove(plan) {
+                let (batches, updates) = measure(&mut trace);
+                released = (released.0 + 1, released.1 + batches, released.2 + updates);
+            }
+        }
+        for (plan, keys) in installation.keyed.iter() {
+            self.recency.remove(&(plan.clone(), Some(keys.clone())));
+            let mut removed = None;
+            if let Some(map) = self.arrangements.get_mut(plan) {
+                removed = map.remove(keys);
+                if map.is_empty() { self.arrangements.remove(plan); }
+            }
+            if let Some(mut trace) = removed {
+                let (batches, updates) = measure(&mut trace);
+                released = (released.0 + 1, released.1 + batches, released.2 + updates);
+            }
+        }
+        released
+    }
 
     /// Advances the frontier of each maintained trace.
     pub fn advance_time(&mut self, time: &Time) {
         use differential_dataflow::trace::TraceReader;
 
-        let frontier = &[time.clone()];
+        let frontier = &[self.compaction(time)];
         for trace in self.inputs.values_mut() {
             trace.advance_by(frontier);
         }
@@ -286,10 +1380,61 @@ impl<Value: Data+Hash> TraceManager<Value> {
         }
     }
 
+    /// Compacts the maintained traces, or only those of `name` if supplied.
+    ///
+    /// Each trace is advanced to `frontier`, which should be the greatest frontier that the
+    /// system has completed, less the retained history, and is then asked to complete outstanding
+    /// merges. Returns the numbers of batches and of updates before and after compaction.
+    pub fn vacuum(&mut self, name: Option<&str>, frontier: &[Time]) -> ((usize, usize), (usize, usize)) {
+
+        use differential_dataflow::trace::TraceReader;
+
+        let frontier = frontier.iter().map(|time| self.compaction(time)).collect::<Vec<_>>();
+        let frontier = &frontier[..];
+
+        // Advances the trace only if `frontier` is not behind its current frontier.
+        fn advance<Tr: TraceReader<K,V,Time,Diff>, K, V>(trace: &mut Tr, frontier: &[Time]) {
+            use timely::order::PartialOrder;
+            let current = trace.advance_frontier().to_vec();
+            if frontier.iter().all(|t1| current.iter().any(|t2| t2.less_equal(t1))) {
+                trace.advance_by(frontier);
+            }
+        }
+
+        let mut before = (0, 0);
+        let mut after = (0, 0);
+
+        let source = name.map(|name| self.resolve(&Plan::Source(name.to_string())).clone());
+        for (plan, trace) in self.inputs.iter_mut() {
+            if source.as_ref().map(|source| source == plan).unwrap_or(true) {
+                let (b, u) = measure(trace);
+                before = (before.0 + b, before.1 + u);
+                advance(trace, frontier);
+                trace.compact();
+                let (b, u) = measure(trace);
+                after = (after.0 + b, after.1 + u);
+            }
+        }
+        for (plan, map) in self.arrangements.iter_mut() {
+            if source.as_ref().map(|source| source == plan).unwrap_or(true) {
+                for trace in map.values_mut() {
+                    let (b, u) = measure(trace);
+                    before = (before.0 + b, before.1 + u);
+                    advance(trace, frontier);
+                    trace.compact();
+                    let (b, u) = measure(trace);
+                    after = (after.0 + b, after.1 + u);
+                }
+            }
+        }
+
+        (before, after)
+    }
+
     /// Recover an arrangement by plan and keys, if it is cached.
     pub fn get_unkeyed(&self, plan: &Plan<Value>) -> Option<KeysOnlyHandle<Value>> {
         self.inputs
-            .get(plan)
+            .get(self.resolve(plan))
             .map(|x| x.clone())
     }
 
@@ -299,28 +1444,34 @@ impl<Value: Data+Hash> TraceManager<Value> {
         println!("Setting unkeyed: {:?}", plan);
 
         use differential_dataflow::trace::TraceReader;
+        let plan = self.resolve(plan).clone();
         let mut handle = handle.clone();
         handle.distinguish_since(&[]);
         self.inputs
             .insert(plan.clone(), handle);
+        self.touch((plan.clone(), None));
+        self.installation.unkeyed.push(plan);
     }
 
     /// Recover an arrangement by plan and keys, if it is cached.
     pub fn get_keyed(&self, plan: &Plan<Value>, keys: &[usize]) -> Option<KeysValsHandle<Value>> {
         self.arrangements
-            .get(plan)
+            .get(self.resolve(plan))
             .and_then(|map| map.get(keys).map(|x| x.clone()))
     }
 
     /// Installs a keyed arrangement for a specified plan and sequence of keys.
     pub fn set_keyed(&mut self, plan: &Plan<Value>, keys: &[usize], handle: &KeysValsHandle<Value>) {
         use differential_dataflow::trace::TraceReader;
+        let plan = self.resolve(plan).clone();
         let mut handle = handle.clone();
         handle.distinguish_since(&[]);
         self.arrangements
             .entry(plan.clone())
             .or_insert(HashMap::new())
             .insert(keys.to_vec(), handle);
+        self.touch((plan.clone(), Some(keys.to_vec())));
+        self.installation.keyed.push((plan, keys.to_vec()));
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+//! Health metrics of workers, in the Prometheus text format.
+//!
+//! Each worker periodically measures a `Metrics`, from its maintained traces, the progress
+//! of its inputs and probe, and the merges its differential logging reports, so that a
+//! server can report them without a client subscribing to a logging dataflow. Lags are
+//! measured against the time elapsed since the worker started, which is the time commands
+//! advance inputs to when they follow the clock.
+
+use std::fmt::{Debug, Write};
+use std::hash::Hash;
+use std::time::Duration;
+
+use differential_dataflow::Data;
+
+use {Manager, Time};
+
+/// The sizes of a maintained trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Arrangement {
+    /// The plan of the trace, as debug text.
+    pub plan: String,
+    /// The columns by which the trace is arranged, or `None` if by its tuples.
+    pub keys: Option<Vec<usize>>,
+    /// The number of batches the trace holds.
+    pub batches: usize,
+    /// The number of updates in the batches.
+    pub updates: usize,
+    /// An estimate of the bytes the updates occupy.
+    pub bytes: usize,
+}
+
+/// The health of a worker, at some moment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Metrics {
+    /// The index of the worker.
+    pub worker: usize,
+    /// The sizes of each maintained trace.
+    pub arrangements: Vec<Arrangement>,
+    /// The number of merges of batches the worker has completed.
+    pub merged: usize,
+    /// How far the latest input time trails the elapsed time, if there are inputs.
+    pub input_lag: Option<Duration>,
+    /// How far the frontier of the probe trails the latest input time, if there are inputs.
+    pub probe_lag: Option<Duration>,
+}
+
+impl Metrics {
+    /// Measures the health of the worker `worker` of `manager`, `elapsed` after it started.
+    ///
+    /// Traces are measured by `TraceReader::statistics`, which visits each of their keys.
+    pub fn measure<Value: Data+Hash>(manager: &mut Manager<Value>, worker: usize, elapsed: Duration, merged: usize) -> Self {
+        let arrangements =
+        manager
+            .traces
+            .describe()
+            .into_iter()
+            .map(|trace| Arrangement {
+                plan: format!("{:?}", trace.plan),
+                keys: trace.keys,
+                batches: trace.statistics.batches,
+                updates: trace.statistics.updates,
+                bytes: trace.statistics.bytes,
+            })
+            .collect();
+        let time = manager.inputs.time();
+        let frontier: Option<Time> = manager.probe.with_frontier(|frontier| frontier.first().cloned());
+        let probe_lag = time.map(|time| match frontier {
+            Some(frontier) => time.checked_sub(frontier).unwrap_or_else(|| Duration::from_secs(0)),
+            // The probe is complete, and trails nothing.
+            None => Duration::from_secs(0),
+        });
+        Metrics {
+            worker,
+            arrangements,
+            merged,
+            input_lag: time.map(|time| elapsed.checked_sub(time).unwrap_or_else(|| Duration::from_secs(0))),
+            probe_lag,
+        }
+    }
+}
+
+/// Renders the metrics of workers, and the depth of the command queue, in the Prometheus text format.
+pub fn render(workers: &[Metrics], queued: usize) -> String {
+
+    let mut text = String::new();
+
+    describe(&mut text, "interactive_command_queue_depth", "gauge", "Commands received and not yet sequenced.");
+    writeln!(text, "interactive_command_queue_depth {}", queued).expect("writing to a string");
+
+    describe(&mut text, "interactive_batches_merged_total", "counter", "Merges of batches completed.");
+    for metrics in workers.iter() {
+        writeln!(text, "interactive_batches_merged_total{{worker=\"{}\"}} {}", metrics.worker, metrics.merged).expect("writing to a string");
+    }
+
+    describe(&mut text, "interactive_input_lag_seconds", "gauge", "How far the latest input time trails the elapsed time.");
+    for metrics in workers.iter() {
+        if let Some(lag) = metrics.input_lag {
+            writeln!(text, "interactive_input_lag_seconds{{worker=\"{}\"}} {}", metrics.worker, lag.as_secs_f64()).expect("writing to a string");
+        }
+    }
+
+    describe(&mut text, "interactive_probe_lag_seconds", "gauge", "How far the frontier of the probe trails the latest input time.");
+    for metrics in workers.iter() {
+        if let Some(lag) = metrics.probe_lag {
+            writeln!(text, "interactive_probe_lag_seconds{{worker=\"{}\"}} {}", metrics.worker, lag.as_secs_f64()).expect("writing to a string");
+        }
+    }
+
+    let sizes: &[(&str, &str, fn(&Arrangement) -> usize)] = &[
+        ("interactive_arrangement_batches", "Batches held by a maintained trace.", |arrangement| arrangement.batches),
+        ("interactive_arrangement_updates", "Updates held by a maintained trace.", |arrangement| arrangement.updates),
+        ("interactive_arrangement_bytes", "Estimated bytes of the updates of a maintained trace.", |arrangement| arrangement.bytes),
+    ];
+    for (name, help, size) in sizes.iter() {
+        describe(&mut text, name, "gauge", help);
+        for metrics in workers.iter() {
+            for arrangement in metrics.arrangements.iter() {
+                writeln!(text, "{}{{worker=\"{}\",plan=\"{}\",keys=\"{}\"}} {}",
+                    name, metrics.worker, escape(&arrangement.plan), escape(&keys(&arrangement.keys)), size(arrangement)).expect("writing to a string");
+            }
+        }
+    }
+
+    text
+}
+
+/// Writes the help and type lines of the metric `name`.
+fn describe(text: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(text, "# HELP {} {}", name, help).expect("writing to a string");
+    writeln!(text, "# TYPE {} {}", name, kind).expect("writing to a string");
+}
+
+/// The keys of an arrangement, as a label.
+fn keys<T: Debug>(keys: &Option<T>) -> String {
+    match keys {
+        Some(keys) => format!("{:?}", keys),
+        None => "self".to_string(),
+    }
+}
+
+/// Escapes `value` for a label, in which backslashes, quotes, and newlines are escaped.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
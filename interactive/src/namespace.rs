@@ -0,0 +1,116 @@
+//! Namespaces for applications sharing a system.
+//!
+//! Applications issue commands within a namespace by wrapping them in
+//! `Command::Namespaced`. The names of inputs, rules, subscriptions, and the
+//! sources of plans are then qualified by the namespace, as `namespace/name`,
+//! so that applications using the same names do not collide. The logging and
+//! system collections, whose names start with `logs/` and `system/`, describe
+//! the whole system and are shared by all namespaces, so their names are not
+//! qualified.
+//!
+//! Within a namespace, `Shutdown` uninstalls the inputs, queries, and
+//! subscriptions of the namespace rather than terminating the system, and
+//! `Usage` reports the traces they maintain.
+
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Let, Iterate};
+use {Command, Query, Rule};
+
+/// The prefixes of the names of logging and system collections, which are shared by all namespaces.
+pub const SHARED: &[&str] = &["logs/", "system/"];
+
+/// Qualifies `name` by `namespace`, unless it names a shared collection.
+pub fn qualify(namespace: &str, name: &str) -> String {
+    if SHARED.iter().any(|prefix| name.starts_with(prefix)) { name.to_string() }
+    else { format!("{}/{}", namespace, name) }
+}
+
+/// Indicates if `name` has been qualified by `namespace`, or a namespace within it.
+pub fn within(namespace: &str, name: &str) -> bool {
+    name.len() > namespace.len() && name.starts_with(namespace) && name[namespace.len()..].starts_with('/')
+}
+
+/// Qualifies the sources of `plan` by `namespace`, other than those bound within `plan`.
+pub fn qualify_plan<V: Data+Hash>(namespace: &str, plan: &Plan<V>) -> Plan<V> {
+    qualify_bound(namespace, plan, &mut Vec::new())
+}
+
+/// Qualifies the sources of `plan` by `namespace`, other than those named in `bound`.
+fn qualify_bound<V: Data+Hash>(namespace: &str, plan: &Plan<V>, bound: &mut Vec<String>) -> Plan<V> {
+    match plan {
+        Plan::Source(name) => {
+            if bound.contains(name) { plan.clone() }
+            else { Plan::Source(qualify(namespace, name)) }
+        },
+        Plan::Let(bind) => {
+            let value = qualify_bound(namespace, &bind.value, bound);
+            bound.push(bind.name.clone());
+            let body = qualify_bound(namespace, &bind.body, bound);
+            bound.pop();
+            Plan::Let(Let { name: bind.name.clone(), value: Box::new(value), body: Box::new(body) })
+        },
+        Plan::Iterate(iterate) => {
+            let depth = bound.len();
+            bound.extend(iterate.bindings.iter().map(|(name, _plan)| name.clone()));
+            let bindings =
+            iterate
+                .bindings
+                .iter()
+                .map(|(name, plan)| (name.clone(), qualify_bound(namespace, plan, bound)))
+                .collect();
+            let body = qualify_bound(namespace, &iterate.body, bound);
+            bound.truncate(depth);
+            Plan::Iterate(Iterate { bindings, body: Box::new(body) })
+        },
+        _ => plan.map_children(|child| qualify_bound(namespace, child, bound)),
+    }
+}
+
+impl<Value: Data+Hash> Command<Value> {
+
+    /// Qualifies the names of the command by `namespace`.
+    ///
+    /// Commands that name nothing, including `Shutdown` and `Usage`, are unchanged;
+    /// `Command::execute` interprets them within the namespace. The names of `Sql`
+    /// queries and `Datalog` programs are qualified as they are compiled, and are also unchanged.
+    pub fn qualify(self, namespace: &str) -> Self {
+        let name = |name: String| qualify(namespace, &name);
+        let plan = |plan: Plan<Value>| qualify_plan(namespace, &plan);
+        match self {
+            Command::Query(query) => {
+                let mut qualified = Query::new();
+                for rule in query.rules.into_iter() {
+                    qualified = qualified.add_rule(Rule { name: name(rule.name), plan: plan(rule.plan) });
+                }
+                Command::Query(qualified)
+            },
+            Command::CreateInput(input, updates) => Command::CreateInput(name(input), updates),
+            Command::UpdateInput(input, updates) => Command::UpdateInput(name(input), updates),
+            Command::CloseInput(input) => Command::CloseInput(name(input)),
+            Command::CreateUpsertInput(input, arity) => Command::CreateUpsertInput(name(input), arity),
+            Command::UpsertInput(input, upserts) => Command::UpsertInput(name(input), upserts),
+            Command::LoadFile(input, path, format) => Command::LoadFile(name(input), path, format),
+            Command::Estimate(estimated, deltas) => {
+                let deltas = deltas.into_iter().map(|(source, updates)| (name(source), updates)).collect();
+                Command::Estimate(plan(estimated), deltas)
+            },
+            // Vacuuming all traces is not limited to those of the namespace.
+            Command::Vacuum(vacuumed) => Command::Vacuum(vacuumed.map(name)),
+            Command::Explain(explained) => Command::Explain(plan(explained)),
+            Command::Subscribe(subscription, subscribed) => Command::Subscribe(name(subscription), plan(subscribed)),
+            Command::Unsubscribe(subscription) => Command::Unsubscribe(name(subscription)),
+            Command::Resubscribe(subscription, subscribed, frontier) => Command::Resubscribe(name(subscription), plan(subscribed), frontier),
+            Command::Uninstall(installed) => Command::Uninstall(name(installed)),
+            Command::Publish(published, definition) => Command::Publish(name(published), plan(definition)),
+            Command::GetByName(published) => Command::GetByName(name(published)),
+            Command::QueryAsOf(queried, time) => Command::QueryAsOf(plan(queried), time),
+            Command::DeclareSchema(declared, schema) => Command::DeclareSchema(name(declared), schema),
+            Command::GetSchema(declared) => Command::GetSchema(name(declared)),
+            Command::Namespaced(inner, command) => Command::Namespaced(name(inner), command),
+            command => command,
+        }
+    }
+}
@@ -0,0 +1,425 @@
+//! Rewriting plans into equivalent plans that are cheaper to maintain.
+//!
+//! Plans are rendered as written, and a plan written naively may arrange far more
+//! data than it needs to. The optimizer applies three rewrites, bottom-up:
+//!
+//! * Filters are pushed below joins, projections, concatenations, and other stages
+//!   that commute with them, so that fewer tuples reach the arrangements of joins.
+//! * Projections of joins prune the values of each input that are neither keys nor
+//!   projected, so that the arrangements of joins hold only the values they need.
+//! * Joins of joins are reordered, when the keys allow it, to first join the input
+//!   with fewer records per key, using statistics of the arrangements maintained by
+//!   the trace manager.
+//!
+//! The rewrites that change the positions of values need the arities of the plans
+//! involved. These are known for most plans, and for sources with at least one
+//! maintained tuple; where an arity is unknown, the rewrite is not applied. Plans
+//! that are already maintained are left unchanged, so that they may be imported.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+use differential_dataflow::trace::{Cursor, TraceReader};
+
+use plan::{Plan, Project, Map, Threshold, Concat, Join, Antijoin, Filter, Predicate, Aggregate, Let, Iterate};
+use estimate::statistics;
+use TraceManager;
+
+/// The input from which a join draws a value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Side {
+    /// The value at an index of the left input.
+    Left(usize),
+    /// The value at an index of the right input.
+    Right(usize),
+}
+
+/// The sources of the values produced by a join on `keys` of inputs of arities `arity1` and `arity2`.
+///
+/// Joins produce the key values, then the other values of the left input, then the other values
+/// of the right input. Key values are attributed to the left input.
+fn join_layout(keys: &[(usize, usize)], arity1: usize, arity2: usize) -> Vec<Side> {
+    let mut layout = keys.iter().map(|key| Side::Left(key.0)).collect::<Vec<_>>();
+    layout.extend((0 .. arity1).filter(|index| !keys.iter().any(|key| key.0 == *index)).map(Side::Left));
+    layout.extend((0 .. arity2).filter(|index| !keys.iter().any(|key| key.1 == *index)).map(Side::Right));
+    layout
+}
+
+/// The predicate reading values at `map[index]`, if `map` supplies each index it reads.
+fn remap<Value: Clone>(predicate: &Predicate<Value>, map: &[Option<usize>]) -> Option<Predicate<Value>> {
+    let mut columns = Vec::new();
+    predicate.columns(&mut columns);
+    if columns.iter().all(|column| map.get(*column).map(|x| x.is_some()).unwrap_or(false)) {
+        Some(predicate.remap(&|column| map[column].unwrap()))
+    }
+    else {
+        None
+    }
+}
+
+/// Rewrites plans using statistics of maintained arrangements.
+pub struct Optimizer<'a, Value: Data+'a> {
+    traces: &'a TraceManager<Value>,
+    /// Arities of the collections bound to names, where known.
+    arities: HashMap<String, Option<usize>>,
+}
+
+impl<'a, Value: Data+Hash> Optimizer<'a, Value> {
+
+    /// Creates a new optimizer drawing statistics from `traces`.
+    pub fn new(traces: &'a TraceManager<Value>) -> Self {
+        Optimizer {
+            traces,
+            arities: HashMap::new(),
+        }
+    }
+
+    /// Produces a plan equivalent to `plan`, and expected to be no more expensive.
+    pub fn optimize(mut self, plan: &Plan<Value>) -> Plan<Value> {
+        self.rewrite(plan)
+    }
+
+    /// Rewrites the sub-plans of `plan`, and then `plan` itself.
+    fn rewrite(&mut self, plan: &Plan<Value>) -> Plan<Value> {
+
+        if self.arities.is_empty() && self.traces.get_unkeyed(plan).is_some() {
+            return plan.clone();
+        }
+
+        match plan {
+            Plan::Project(project) => {
+                let input = self.rewrite(&project.plan);
+                self.project(project.indices.clone(), input)
+            },
+            Plan::Map(map) => Plan::Map(Map { expressions: map.expressions.clone(), plan: Box::new(self.rewrite(&map.plan)) }),
+            Plan::Distinct(distinct) => Plan::Distinct(Box::new(self.rewrite(distinct))),
+            Plan::Threshold(threshold) => Plan::Threshold(Threshold { minimum: threshold.minimum, plan: Box::new(self.rewrite(&threshold.plan)) }),
+            Plan::Concat(concat) => Plan::Concat(Concat { plans: concat.plans.iter().map(|plan| self.rewrite(plan)).collect() }),
+            Plan::Union(plans) => Plan::Union(plans.iter().map(|plan| self.rewrite(plan)).collect()),
+            Plan::Join(join) => {
+                let input1 = self.rewrite(&join.plan1);
+                let input2 = self.rewrite(&join.plan2);
+                self.join(join.keys.clone(), input1, input2)
+            },
+            Plan::Antijoin(antijoin) => Plan::Antijoin(Antijoin {
+                keys: antijoin.keys.clone(),
+                plan1: Box::new(self.rewrite(&antijoin.plan1)),
+                plan2: Box::new(self.rewrite(&antijoin.plan2)),
+            }),
+            Plan::Negate(negate) => Plan::Negate(Box::new(self.rewrite(negate))),
+            Plan::Filter(filter) => {
+                let input = self.rewrite(&filter.plan);
+                self.filter(filter.predicate.clone(), input)
+            },
+            Plan::Aggregate(aggregate) => Plan::Aggregate(Aggregate {
+                keys: aggregate.keys.clone(),
+                aggregates: aggregate.aggregates.clone(),
+                plan: Box::new(self.rewrite(&aggregate.plan)),
+            }),
+            Plan::Let(bind) => {
+                let value = self.rewrite(&bind.value);
+                let arity = self.arity(&value);
+                let shadowed = self.arities.insert(bind.name.clone(), arity);
+                let body = self.rewrite(&bind.body);
+                self.restore(&bind.name, shadowed);
+                Plan::Let(Let { name: bind.name.clone(), value: Box::new(value), body: Box::new(body) })
+            },
+            Plan::Iterate(iterate) => {
+                // The arities of recursive bindings are not determined before their plans are.
+                let shadowed =
+                iterate.bindings
+                    .iter()
+                    .map(|(name, _plan)| (name.clone(), self.arities.insert(name.clone(), None)))
+                    .collect::<Vec<_>>();
+                let bindings = iterate.bindings.iter().map(|(name, plan)| (name.clone(), self.rewrite(plan))).collect();
+                let body = self.rewrite(&iterate.body);
+                for (name, shadowed) in shadowed.into_iter().rev() {
+                    self.restore(&name, shadowed);
+                }
+                Plan::Iterate(Iterate { bindings, body: Box::new(body) })
+            },
+            Plan::Source(_) => plan.clone(),
+            Plan::Inspect(text, plan) => Plan::Inspect(text.clone(), Box::new(self.rewrite(plan))),
+        }
+    }
+
+    /// Restores the arity bound to `name` before it was shadowed.
+    fn restore(&mut self, name: &str, shadowed: Option<Option<usize>>) {
+        match shadowed {
+            Some(arity) => { self.arities.insert(name.to_string(), arity); },
+            None => { self.arities.remove(name); },
+        }
+    }
+
+    /// Filters `plan` by `predicate`, as far below the stages of `plan` as possible.
+    fn filter(&mut self, predicate: Predicate<Value>, plan: Plan<Value>) -> Plan<Value> {
+
+        // Each conjunct of a conjunction may be pushed to a different place.
+        let predicate = match predicate {
+            Predicate::All(predicates) => {
+                return predicates.into_iter().fold(plan, |plan, predicate| self.filter(predicate, plan));
+            },
+            predicate => predicate,
+        };
+
+        match plan {
+            Plan::Project(project) => {
+                let map = project.indices.iter().map(|index| Some(*index)).collect::<Vec<_>>();
+                match remap(&predicate, &map[..]) {
+                    Some(remapped) => Plan::Project(Project { indices: project.indices, plan: Box::new(self.filter(remapped, *project.plan)) }),
+                    None => Plan::Filter(Filter { predicate, plan: Box::new(Plan::Project(project)) }),
+                }
+            },
+            Plan::Distinct(distinct) => Plan::Distinct(Box::new(self.filter(predicate, *distinct))),
+            Plan::Threshold(threshold) => Plan::Threshold(Threshold { minimum: threshold.minimum, plan: Box::new(self.filter(predicate, *threshold.plan)) }),
+            Plan::Concat(concat) => Plan::Concat(Concat { plans: concat.plans.into_iter().map(|plan| self.filter(predicate.clone(), plan)).collect() }),
+            Plan::Union(plans) => Plan::Union(plans.into_iter().map(|plan| self.filter(predicate.clone(), plan)).collect()),
+            Plan::Negate(negate) => Plan::Negate(Box::new(self.filter(predicate, *negate))),
+            Plan::Filter(filter) => Plan::Filter(Filter { predicate: filter.predicate, plan: Box::new(self.filter(predicate, *filter.plan)) }),
+            Plan::Antijoin(antijoin) => Plan::Antijoin(Antijoin {
+                keys: antijoin.keys,
+                plan1: Box::new(self.filter(predicate, *antijoin.plan1)),
+                plan2: antijoin.plan2,
+            }),
+            Plan::Aggregate(aggregate) => {
+                // Predicates of the key values select whole groups, and may be applied to their records.
+                let map = aggregate.keys.iter().map(|index| Some(*index)).collect::<Vec<_>>();
+                match remap(&predicate, &map[..]) {
+                    Some(remapped) => Plan::Aggregate(Aggregate {
+                        keys: aggregate.keys,
+                        aggregates: aggregate.aggregates,
+                        plan: Box::new(self.filter(remapped, *aggregate.plan)),
+                    }),
+                    None => Plan::Filter(Filter { predicate, plan: Box::new(Plan::Aggregate(aggregate)) }),
+                }
+            },
+            Plan::Join(join) => {
+                let arities = (self.arity(&join.plan1), self.arity(&join.plan2));
+                if let (Some(arity1), Some(arity2)) = arities {
+                    // Key values may be read from either input.
+                    let layout = join_layout(&join.keys[..], arity1, arity2);
+                    let map1 = layout.iter().map(|side| match side { Side::Left(index) => Some(*index), _ => None }).collect::<Vec<_>>();
+                    let map2 = layout.iter().enumerate().map(|(position, side)| match side {
+                        Side::Right(index) => Some(*index),
+                        Side::Left(_) => join.keys.get(position).map(|key| key.1),
+                    }).collect::<Vec<_>>();
+                    match (remap(&predicate, &map1[..]), remap(&predicate, &map2[..])) {
+                        (Some(predicate1), Some(predicate2)) => Plan::Join(Join {
+                            keys: join.keys,
+                            plan1: Box::new(self.filter(predicate1, *join.plan1)),
+                            plan2: Box::new(self.filter(predicate2, *join.plan2)),
+                        }),
+                        (Some(predicate1), None) => Plan::Join(Join {
+                            keys: join.keys,
+                            plan1: Box::new(self.filter(predicate1, *join.plan1)),
+                            plan2: join.plan2,
+                        }),
+                        (None, Some(predicate2)) => Plan::Join(Join {
+                            keys: join.keys,
+                            plan1: join.plan1,
+                            plan2: Box::new(self.filter(predicate2, *join.plan2)),
+                        }),
+                        (None, None) => Plan::Filter(Filter { predicate, plan: Box::new(Plan::Join(join)) }),
+                    }
+                }
+                else {
+                    Plan::Filter(Filter { predicate, plan: Box::new(Plan::Join(join)) })
+                }
+            },
+            plan => Plan::Filter(Filter { predicate, plan: Box::new(plan) }),
+        }
+    }
+
+    /// Projects `plan` onto the values at `indices`, pruning unused values below the stages of `plan`.
+    fn project(&mut self, indices: Vec<usize>, plan: Plan<Value>) -> Plan<Value> {
+        match plan {
+            Plan::Project(project) => {
+                if indices.iter().all(|index| *index < project.indices.len()) {
+                    let indices = indices.iter().map(|index| project.indices[*index]).collect();
+                    self.project(indices, *project.plan)
+                }
+                else {
+                    Plan::Project(Project { indices, plan: Box::new(Plan::Project(project)) })
+                }
+            },
+            Plan::Concat(concat) => Plan::Concat(Concat { plans: concat.plans.into_iter().map(|plan| self.project(indices.clone(), plan)).collect() }),
+            Plan::Negate(negate) => Plan::Negate(Box::new(self.project(indices, *negate))),
+            Plan::Join(join) => {
+                let arities = (self.arity(&join.plan1), self.arity(&join.plan2));
+                match arities {
+                    (Some(arity1), Some(arity2)) => self.prune_join(indices, join, arity1, arity2),
+                    _ => Plan::Project(Project { indices, plan: Box::new(Plan::Join(join)) }),
+                }
+            },
+            plan => Plan::Project(Project { indices, plan: Box::new(plan) }),
+        }
+    }
+
+    /// Projects the inputs of `join` onto their keys and the values at `indices` draws from them.
+    fn prune_join(&mut self, indices: Vec<usize>, join: Join<Value>, arity1: usize, arity2: usize) -> Plan<Value> {
+
+        let layout = join_layout(&join.keys[..], arity1, arity2);
+        if indices.iter().any(|index| *index >= layout.len()) {
+            return Plan::Project(Project { indices, plan: Box::new(Plan::Join(join)) });
+        }
+
+        let mut used1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let mut used2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+        for index in indices.iter() {
+            match layout[*index] {
+                Side::Left(index) => used1.push(index),
+                Side::Right(index) => used2.push(index),
+            }
+        }
+        used1.sort();
+        used1.dedup();
+        used2.sort();
+        used2.dedup();
+
+        if used1.len() == arity1 && used2.len() == arity2 {
+            return Plan::Project(Project { indices, plan: Box::new(Plan::Join(join)) });
+        }
+
+        let position = |used: &[usize], index: usize| used.iter().position(|x| *x == index).expect("pruned inputs retain their keys");
+        let keys =
+        join.keys
+            .iter()
+            .map(|key| (position(&used1[..], key.0), position(&used2[..], key.1)))
+            .collect::<Vec<_>>();
+
+        // The values of the pruned join, in terms of the values of the original inputs.
+        let pruned =
+        join_layout(&keys[..], used1.len(), used2.len())
+            .into_iter()
+            .map(|side| match side {
+                Side::Left(index) => Side::Left(used1[index]),
+                Side::Right(index) => Side::Right(used2[index]),
+            })
+            .collect::<Vec<_>>();
+
+        let indices =
+        indices
+            .iter()
+            .map(|index| pruned.iter().position(|side| *side == layout[*index]).expect("pruned join retains projected values"))
+            .collect();
+
+        let plan1 = if used1.len() < arity1 { self.project(used1, *join.plan1) } else { *join.plan1 };
+        let plan2 = if used2.len() < arity2 { self.project(used2, *join.plan2) } else { *join.plan2 };
+
+        Plan::Project(Project {
+            indices,
+            plan: Box::new(Plan::Join(Join { keys, plan1: Box::new(plan1), plan2: Box::new(plan2) })),
+        })
+    }
+
+    /// Joins `plan1` and `plan2` on `keys`, reordering a join of joins if it is expected to be cheaper.
+    fn join(&mut self, keys: Vec<(usize, usize)>, plan1: Plan<Value>, plan2: Plan<Value>) -> Plan<Value> {
+        let reordered =
+        if let Plan::Join(inner) = &plan1 { self.reorder(&keys[..], inner, &plan2) }
+        else { None };
+
+        reordered.unwrap_or_else(|| Plan::Join(Join { keys, plan1: Box::new(plan1), plan2: Box::new(plan2) }))
+    }
+
+    /// Rewrites the join of `inner` with `plan` on `keys` to join `plan` with the left input of `inner` first.
+    ///
+    /// This is possible when `keys` read only values of the left input of `inner`, and is done when
+    /// the arrangement of `plan` has fewer records per key than that of the right input of `inner`,
+    /// as the intermediate join then produces fewer results.
+    fn reorder(&mut self, keys: &[(usize, usize)], inner: &Join<Value>, plan: &Plan<Value>) -> Option<Plan<Value>> {
+
+        let arity_a = self.arity(&inner.plan1)?;
+        let arity_b = self.arity(&inner.plan2)?;
+        let arity_c = self.arity(plan)?;
+
+        let layout_ab = join_layout(&inner.keys[..], arity_a, arity_b);
+        let keys_ac =
+        keys.iter()
+            .map(|key| match layout_ab.get(key.0) { Some(Side::Left(index)) => Some((*index, key.1)), _ => None })
+            .collect::<Option<Vec<_>>>()?;
+
+        let keys_b = inner.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+        let keys_c = keys_ac.iter().map(|key| key.1).collect::<Vec<_>>();
+        if self.fanout(plan, &keys_c[..]) >= self.fanout(&inner.plan2, &keys_b[..]) {
+            return None;
+        }
+
+        let layout_ac = join_layout(&keys_ac[..], arity_a, arity_c);
+        let keys_acb =
+        inner.keys
+            .iter()
+            .map(|key| layout_ac.iter().position(|side| *side == Side::Left(key.0)).map(|index| (index, key.1)))
+            .collect::<Option<Vec<_>>>()?;
+
+        // The values of both joins, as pairs of input (0, 1, or 2 for A, B, or C) and index.
+        let original =
+        join_layout(keys, layout_ab.len(), arity_c)
+            .into_iter()
+            .map(|side| match side {
+                Side::Left(index) => match layout_ab[index] { Side::Left(a) => (0, a), Side::Right(b) => (1, b) },
+                Side::Right(c) => (2, c),
+            })
+            .collect::<Vec<_>>();
+        let reordered =
+        join_layout(&keys_acb[..], layout_ac.len(), arity_b)
+            .into_iter()
+            .map(|side| match side {
+                Side::Left(index) => match layout_ac[index] { Side::Left(a) => (0, a), Side::Right(c) => (2, c) },
+                Side::Right(b) => (1, b),
+            })
+            .collect::<Vec<_>>();
+
+        let indices =
+        original
+            .iter()
+            .map(|value| reordered.iter().position(|x| x == value))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Plan::Project(Project {
+            indices,
+            plan: Box::new(Plan::Join(Join {
+                keys: keys_acb,
+                plan1: Box::new(Plan::Join(Join { keys: keys_ac, plan1: inner.plan1.clone(), plan2: Box::new(plan.clone()) })),
+                plan2: inner.plan2.clone(),
+            })),
+        }))
+    }
+
+    /// The number of values in each tuple of `plan`, if known.
+    fn arity(&self, plan: &Plan<Value>) -> Option<usize> {
+        match plan {
+            Plan::Project(project) => Some(project.indices.len()),
+            Plan::Map(map) => Some(map.expressions.len()),
+            Plan::Distinct(plan) | Plan::Negate(plan) | Plan::Inspect(_, plan) => self.arity(plan),
+            Plan::Threshold(threshold) => self.arity(&threshold.plan),
+            Plan::Concat(concat) => concat.plans.iter().find_map(|plan| self.arity(plan)),
+            Plan::Union(plans) => plans.iter().find_map(|plan| self.arity(plan)),
+            Plan::Join(join) => Some(join_layout(&join.keys[..], self.arity(&join.plan1)?, self.arity(&join.plan2)?).len()),
+            Plan::Antijoin(antijoin) => self.arity(&antijoin.plan1),
+            Plan::Filter(filter) => self.arity(&filter.plan),
+            Plan::Aggregate(aggregate) => Some(aggregate.keys.len() + aggregate.aggregates.len()),
+            Plan::Let(_) | Plan::Iterate(_) => None,
+            Plan::Source(name) => {
+                if let Some(arity) = self.arities.get(name) { *arity }
+                else {
+                    // Sources are maintained, and any tuple of one has its arity.
+                    self.traces.get_unkeyed(plan).and_then(|mut trace| {
+                        let (cursor, storage) = trace.cursor();
+                        cursor.get_key(&storage).map(|key| key.len())
+                    })
+                }
+            },
+        }
+    }
+
+    /// The average number of records per key of the arrangement of `plan` by `keys`.
+    ///
+    /// As for estimates, plans without maintained arrangements are assumed to have one record per key.
+    fn fanout(&self, plan: &Plan<Value>, keys: &[usize]) -> usize {
+        self.traces
+            .get_keyed(plan, keys)
+            .map(|mut trace| statistics(&mut trace).fanout())
+            .unwrap_or(1)
+    }
+}
@@ -0,0 +1,110 @@
+//! Aggregation expression plan.
+
+use std::hash::Hash;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Evaluable};
+use {TraceManager, Diff};
+
+/// Values that can be produced by aggregations.
+pub trait Aggregable: Sized {
+    /// The value representing a count of records.
+    fn from_count(count: Diff) -> Self;
+    /// The sum of values, each with a multiplicity.
+    ///
+    /// Values that cannot be summed should produce a value, such as a null, rather than panic.
+    fn sum<'a, I: Iterator<Item=(&'a Self, Diff)>>(values: I) -> Self where Self: 'a;
+}
+
+/// An aggregate of the records in each group.
+///
+/// Aggregates of the values at an index ignore null values, and are null if all are null.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Aggregation {
+    /// The number of records.
+    Count,
+    /// The sum of the values at an index.
+    Sum(usize),
+    /// The least of the values at an index.
+    Min(usize),
+    /// The greatest of the values at an index.
+    Max(usize),
+}
+
+/// A plan stage grouping records by the values at the indicated indices, and
+/// producing the key values followed by the aggregates of each group.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Aggregate<V> {
+    /// Indices of the values by which records are grouped.
+    pub keys: Vec<usize>,
+    /// Aggregates to produce for each group, in order.
+    pub aggregates: Vec<Aggregation>,
+    /// Plan for the data source.
+    pub plan: Box<Plan<V>>,
+}
+
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Aggregate<V> {
+
+    type Value = V;
+
+    fn render<S: RenderScope<Self::Value>>(
+        &self,
+        scope: &mut S,
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
+    {
+        use differential_dataflow::operators::{Count, Reduce};
+        use differential_dataflow::operators::arrange::{ArrangeByKey, ArrangeBySelf};
+
+        let keys = self.keys.clone();
+        let input = self.plan.render(scope, arrangements, bindings);
+        // The costs logged for the arranged input are those of the aggregation.
+        let plan = Plan::Aggregate(self.clone());
+
+        // A lone count needs only the key values, and can use `count` directly.
+        if self.aggregates == vec![Aggregation::Count] {
+            let arranged =
+            input
+                .map(move |tuple| keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>())
+                .arrange_by_self();
+            arrangements.arranged(arranged.trace.operator(), &plan);
+            arranged
+                .count()
+                .map(|(mut keys, count)| { keys.push(V::from_count(count)); keys })
+        }
+        else {
+            let aggregates = self.aggregates.clone();
+            let arranged =
+            input
+                .map(move |tuple| (keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(), tuple))
+                .arrange_by_key();
+            arrangements.arranged(arranged.trace.operator(), &plan);
+            arranged
+                .reduce(move |_keys, input, output| {
+                    // Input tuples are sorted, but not by any one index, so each aggregate scans them.
+                    let values =
+                    aggregates
+                        .iter()
+                        .map(|aggregate| match aggregate {
+                            Aggregation::Count => V::from_count(input.iter().map(|(_, count)| count).sum()),
+                            Aggregation::Sum(index) => V::sum(input.iter().map(|(tuple, count)| (&tuple[*index], *count))),
+                            Aggregation::Min(index) => extreme(input.iter().map(|(tuple, _)| &tuple[*index]).filter(|value| !value.is_null()).min(), &input[0].0[*index]),
+                            Aggregation::Max(index) => extreme(input.iter().map(|(tuple, _)| &tuple[*index]).filter(|value| !value.is_null()).max(), &input[0].0[*index]),
+                        })
+                        .collect::<Vec<_>>();
+                    output.push((values, 1));
+                })
+                .map(|(mut keys, values)| { keys.extend(values); keys })
+        }
+    }
+}
+
+/// The least or greatest non-null value of a group, or null if all are null.
+///
+/// Without a null value, a group whose values are all null reports `first`, one of its values.
+fn extreme<V: Evaluable+Clone>(value: Option<&V>, first: &V) -> V {
+    value.cloned().or_else(V::null).unwrap_or_else(|| first.clone())
+}
@@ -0,0 +1,59 @@
+//! Antijoin expression plan.
+
+use std::hash::Hash;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
+
+/// A plan stage retaining the tuples of the left input whose values at the
+/// specified indices do not match those of any tuple of the right input.
+///
+/// The right input is treated as a set: the multiplicities of its tuples do not
+/// affect the result, and the tuples of the left input that remain do so with
+/// their multiplicities unchanged.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Antijoin<Value> {
+    /// Pairs of indices whose values must be equal for a left tuple to be excluded.
+    pub keys: Vec<(usize, usize)>,
+    /// Plan for the left input.
+    pub plan1: Box<Plan<Value>>,
+    /// Plan for the right input.
+    pub plan2: Box<Plan<Value>>,
+}
+
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Antijoin<V> {
+
+    type Value = V;
+
+    fn render<S: RenderScope<Self::Value>>(
+        &self,
+        scope: &mut S,
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
+    {
+        use differential_dataflow::operators::{Join, Threshold};
+
+        let keys1 = self.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let keys2 = self.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+
+        let left =
+        self.plan1
+            .render(scope, arrangements, bindings)
+            .map(move |tuple| (keys1.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(), tuple));
+
+        // Null keys match no keys, so tuples with null keys are retained, and null keys retain nothing.
+        let right =
+        self.plan2
+            .render(scope, arrangements, bindings)
+            .map(move |tuple| keys2.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>())
+            .filter(|keys| !keys.iter().any(|key| key.is_null()))
+            .distinct();
+
+        left.concat(&left.semijoin(&right).negate())
+            .map(|(_keys, tuple)| tuple)
+    }
+}
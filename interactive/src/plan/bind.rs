@@ -0,0 +1,49 @@
+//! Let-binding expression plan.
+
+use std::hash::Hash;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
+
+/// A plan stage binding the result of a plan to a name, and producing the
+/// result of a body plan that may refer to the name as a source.
+///
+/// The bound plan is rendered and arranged once, and each reference to the name
+/// within the body uses that rendering rather than rendering the plan again.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Let<V> {
+    /// Name by which the body refers to the bound plan.
+    pub name: String,
+    /// Plan for the bound collection.
+    pub value: Box<Plan<V>>,
+    /// Plan for the result.
+    pub body: Box<Plan<V>>,
+}
+
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Let<V> {
+
+    type Value = V;
+
+    fn render<S: RenderScope<Self::Value>>(
+        &self,
+        scope: &mut S,
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
+    {
+        use differential_dataflow::operators::arrange::ArrangeBySelf;
+
+        let value =
+        self.value
+            .render(scope, arrangements, bindings)
+            .arrange_by_self()
+            .as_collection(|k,()| k.to_vec());
+
+        let mut inner = bindings.clone();
+        inner.insert(self.name.clone(), value);
+        self.body.render(scope, arrangements, &inner)
+    }
+}
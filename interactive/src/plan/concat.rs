@@ -2,11 +2,10 @@
 
 use std::hash::Hash;
 
-use timely::dataflow::Scope;
-
 use differential_dataflow::{Collection, Data};
-use plan::{Plan, Render};
-use {TraceManager, Time, Diff};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
 
 /// Merges the source collections.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -15,14 +14,17 @@ pub struct Concat<V> {
     pub plans: Vec<Plan<V>>,
 }
 
-impl<V: Data+Hash> Render for Concat<V> {
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Concat<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S: RenderScope<Self::Value>>(
         &self,
         scope: &mut S,
-        arrangements: &mut TraceManager<Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
     {
         use timely::dataflow::operators::Concatenate;
         use differential_dataflow::AsCollection;
@@ -30,7 +32,7 @@ impl<V: Data+Hash> Render for Concat<V> {
         let collections =
         self.plans
             .iter()
-            .map(|plan| plan.render(scope, arrangements).inner)
+            .map(|plan| plan.render(scope, arrangements, bindings).inner)
             .collect::<Vec<_>>();
 
         scope
@@ -0,0 +1,230 @@
+//! Scalar expressions, and the plan evaluating them.
+
+use std::hash::Hash;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable};
+use {TraceManager, Diff};
+
+/// Operators combining two values.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum BinaryOp {
+    /// Addition.
+    Add,
+    /// Subtraction.
+    Sub,
+    /// Multiplication.
+    Mul,
+    /// Division.
+    Div,
+    /// Remainder.
+    Rem,
+    /// Equal.
+    Equal,
+    /// Not equal.
+    NotEqual,
+    /// Strictly less than.
+    LessThan,
+    /// Less than or equal.
+    LessEqual,
+    /// Strictly greater than.
+    GreaterThan,
+    /// Greater than or equal.
+    GreaterEqual,
+    /// Both booleans are true.
+    And,
+    /// Either boolean is true.
+    Or,
+    /// String concatenation.
+    Concat,
+    /// The first string contains the second.
+    Contains,
+    /// The first string starts with the second.
+    StartsWith,
+}
+
+/// Operators applied to one value.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum UnaryOp {
+    /// Boolean negation.
+    Not,
+    /// The length of a string.
+    Length,
+    /// A string in upper case.
+    Upper,
+    /// A string in lower case.
+    Lower,
+}
+
+/// Values that expressions can be evaluated on.
+///
+/// Comparisons use the order of values, and boolean logic uses `from_bool` and
+/// `as_bool`; the other operators are implemented by the value type, which returns
+/// `None` for operators that do not apply to its arguments.
+///
+/// A value type may have a null value, representing an unknown value. Operators
+/// applied to null produce null, other than boolean logic, which is three-valued:
+/// false and null is false, true or null is true, and otherwise null is unknown.
+pub trait Evaluable: Ord+Clone+Sized {
+    /// The value representing a boolean.
+    fn from_bool(value: bool) -> Self;
+    /// The boolean the value represents, if any.
+    fn as_bool(&self) -> Option<bool>;
+    /// Applies an arithmetic or string operator to two values.
+    fn binary(op: BinaryOp, value1: &Self, value2: &Self) -> Option<Self>;
+    /// Applies a string or arithmetic operator to one value.
+    fn unary(op: UnaryOp, value: &Self) -> Option<Self>;
+    /// The null value, if the value type has one.
+    fn null() -> Option<Self> { None }
+    /// Indicates if the value is null.
+    fn is_null(&self) -> bool { false }
+}
+
+/// The truth of a value in three-valued logic, which is `None` if the value is null.
+///
+/// The result is `None` if the value is neither a boolean nor null.
+pub fn truth<V: Evaluable>(value: &V) -> Option<Option<bool>> {
+    if value.is_null() { Some(None) } else { value.as_bool().map(Some) }
+}
+
+/// An expression computing a value from the values of a tuple.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Expression<V> {
+    /// The value at an index of the tuple.
+    Column(usize),
+    /// A constant value.
+    Constant(V),
+    /// An operator applied to the values of two expressions.
+    Binary(BinaryOp, Box<Expression<V>>, Box<Expression<V>>),
+    /// An operator applied to the value of an expression.
+    Unary(UnaryOp, Box<Expression<V>>),
+    /// The value of a column, by name, which installation resolves to its index.
+    ///
+    /// Expressions naming columns do not evaluate; see `schema`.
+    Named(String),
+}
+
+impl<V: Evaluable> Expression<V> {
+    /// Combines two expressions with a binary operator.
+    pub fn binary(op: BinaryOp, expr1: Self, expr2: Self) -> Self {
+        Expression::Binary(op, Box::new(expr1), Box::new(expr2))
+    }
+    /// Applies a unary operator to an expression.
+    pub fn unary(op: UnaryOp, expr: Self) -> Self {
+        Expression::Unary(op, Box::new(expr))
+    }
+    /// Evaluates the expression on a tuple.
+    ///
+    /// The result is `None` if an operator does not apply to its arguments, for
+    /// example adding a string to a number, or if it fails, as for division by zero.
+    /// Operators applied to null arguments produce null, rather than failing.
+    pub fn evaluate(&self, tuple: &[V]) -> Option<V> {
+        match self {
+            Expression::Column(index) => tuple.get(*index).cloned(),
+            Expression::Constant(value) => Some(value.clone()),
+            Expression::Binary(op, expr1, expr2) => {
+                let value1 = expr1.evaluate(tuple)?;
+                match op {
+                    BinaryOp::And | BinaryOp::Or => {
+                        // The value that decides the result, short-circuiting so that the other argument need not evaluate.
+                        let decisive = *op == BinaryOp::Or;
+                        let truth1 = truth(&value1)?;
+                        if truth1 == Some(decisive) { return Some(V::from_bool(decisive)); }
+                        let truth2 = truth(&expr2.evaluate(tuple)?)?;
+                        match (truth1, truth2) {
+                            (_, Some(truth2)) if truth2 == decisive => Some(V::from_bool(decisive)),
+                            (Some(_), Some(_)) => Some(V::from_bool(!decisive)),
+                            _ => V::null(),
+                        }
+                    },
+                    _ => {
+                        let value2 = expr2.evaluate(tuple)?;
+                        if value1.is_null() || value2.is_null() { return V::null(); }
+                        match op {
+                            BinaryOp::Equal => Some(V::from_bool(value1 == value2)),
+                            BinaryOp::NotEqual => Some(V::from_bool(value1 != value2)),
+                            BinaryOp::LessThan => Some(V::from_bool(value1 < value2)),
+                            BinaryOp::LessEqual => Some(V::from_bool(value1 <= value2)),
+                            BinaryOp::GreaterThan => Some(V::from_bool(value1 > value2)),
+                            BinaryOp::GreaterEqual => Some(V::from_bool(value1 >= value2)),
+                            _ => V::binary(*op, &value1, &value2),
+                        }
+                    },
+                }
+            },
+            Expression::Unary(op, expr) => {
+                let value = expr.evaluate(tuple)?;
+                match op {
+                    UnaryOp::Not => truth(&value)?.map_or_else(V::null, |truth| Some(V::from_bool(!truth))),
+                    _ if value.is_null() => V::null(),
+                    _ => V::unary(*op, &value),
+                }
+            },
+            Expression::Named(_) => None,
+        }
+    }
+    /// Indicates if the expression evaluates to true, rather than false, null, or failing.
+    pub fn holds(&self, tuple: &[V]) -> bool {
+        self.evaluate(tuple).and_then(|value| value.as_bool()) == Some(true)
+    }
+}
+
+impl<V: Clone> Expression<V> {
+    /// Appends the indices of the values the expression reads to `columns`.
+    pub fn columns(&self, columns: &mut Vec<usize>) {
+        match self {
+            Expression::Column(index) => columns.push(*index),
+            Expression::Constant(_) => { },
+            Expression::Binary(_, expr1, expr2) => { expr1.columns(columns); expr2.columns(columns); },
+            Expression::Unary(_, expr) => expr.columns(columns),
+            Expression::Named(_) => { },
+        }
+    }
+    /// The expression reading each value at `map(index)` rather than `index`.
+    pub fn remap<F: Fn(usize)->usize>(&self, map: &F) -> Self {
+        match self {
+            Expression::Column(index) => Expression::Column(map(*index)),
+            Expression::Constant(value) => Expression::Constant(value.clone()),
+            Expression::Binary(op, expr1, expr2) => Expression::Binary(*op, Box::new(expr1.remap(map)), Box::new(expr2.remap(map))),
+            Expression::Unary(op, expr) => Expression::Unary(*op, Box::new(expr.remap(map))),
+            Expression::Named(name) => Expression::Named(name.clone()),
+        }
+    }
+}
+
+/// A plan stage producing, for each source tuple, the tuple of values of the
+/// expressions.
+///
+/// Source tuples for which an expression cannot be evaluated produce no tuple.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Map<V> {
+    /// Expressions to evaluate, in order.
+    pub expressions: Vec<Expression<V>>,
+    /// Plan for the data source.
+    pub plan: Box<Plan<V>>,
+}
+
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Map<V> {
+
+    type Value = V;
+
+    fn render<S: RenderScope<Self::Value>>(
+        &self,
+        scope: &mut S,
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
+    {
+        let expressions = self.expressions.clone();
+        self.plan
+            .render(scope, arrangements, bindings)
+            .flat_map(move |tuple|
+                expressions
+                    .iter()
+                    .map(|expression| expression.evaluate(&tuple[..]))
+                    .collect::<Option<Vec<_>>>()
+            )
+    }
+}
@@ -2,11 +2,10 @@
 
 use std::hash::Hash;
 
-use timely::dataflow::Scope;
-
 use differential_dataflow::{Collection, Data};
-use plan::{Plan, Render};
-use {TraceManager, Time, Diff};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable, Expression};
+use {TraceManager, Diff};
 
 /// What to compare against.
 ///
@@ -29,6 +28,16 @@ impl<Value> SecondArgument<Value> {
     }
 }
 
+impl<Value: Clone> SecondArgument<Value> {
+    /// The argument reading the value at `map(index)` rather than `index`.
+    pub fn remap<F: Fn(usize)->usize>(&self, map: &F) -> Self {
+        match self {
+            SecondArgument::Constant(value) => SecondArgument::Constant(value.clone()),
+            SecondArgument::Position(index) => SecondArgument::Position(map(*index)),
+        }
+    }
+}
+
 /// Possible predicates to apply.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Predicate<Value> {
@@ -50,21 +59,92 @@ pub enum Predicate<Value> {
     All(Vec<Predicate<Value>>),
     /// The complement of a predicate.
     Not(Box<Predicate<Value>>),
+    /// An expression evaluating to true.
+    Holds(Expression<Value>),
 }
 
-impl<Value: Ord> Predicate<Value> {
-    /// Indicates if the predicate is satisfied.
+impl<Value: Evaluable> Predicate<Value> {
+    /// Indicates if the predicate is satisfied, rather than false or unknown.
     pub fn satisfied(&self, values: &[Value]) -> bool {
+        self.truth(values) == Some(true)
+    }
+    /// The truth of the predicate in three-valued logic, which is `None` if it is unknown.
+    ///
+    /// Comparisons with null are unknown, as are expressions evaluating to null. The
+    /// complement of an unknown predicate is unknown, so that tuples with nulls satisfy
+    /// neither a comparison nor its complement.
+    pub fn truth(&self, values: &[Value]) -> Option<bool> {
+        match self {
+            Predicate::LessThan(index, other) => compare(&values[*index], other.value(values), |x, y| x.lt(y)),
+            Predicate::LessEqual(index, other) => compare(&values[*index], other.value(values), |x, y| x.le(y)),
+            Predicate::GreaterThan(index, other) => compare(&values[*index], other.value(values), |x, y| x.gt(y)),
+            Predicate::GreaterEqual(index, other) => compare(&values[*index], other.value(values), |x, y| x.ge(y)),
+            Predicate::Equal(index, other) => compare(&values[*index], other.value(values), |x, y| x.eq(y)),
+            Predicate::NotEqual(index, other) => compare(&values[*index], other.value(values), |x, y| x.ne(y)),
+            Predicate::Any(predicates) => {
+                let truths = predicates.iter().map(|p| p.truth(values)).collect::<Vec<_>>();
+                if truths.contains(&Some(true)) { Some(true) }
+                else if truths.contains(&None) { None }
+                else { Some(false) }
+            },
+            Predicate::All(predicates) => {
+                let truths = predicates.iter().map(|p| p.truth(values)).collect::<Vec<_>>();
+                if truths.contains(&Some(false)) { Some(false) }
+                else if truths.contains(&None) { None }
+                else { Some(true) }
+            },
+            Predicate::Not(predicate) => predicate.truth(values).map(|truth| !truth),
+            Predicate::Holds(expression) => match expression.evaluate(values) {
+                Some(ref value) if value.is_null() => None,
+                value => Some(value.and_then(|value| value.as_bool()) == Some(true)),
+            },
+        }
+    }
+}
+
+/// Compares two values, unless either is null.
+fn compare<Value: Evaluable, F: Fn(&Value, &Value)->bool>(value1: &Value, value2: &Value, logic: F) -> Option<bool> {
+    if value1.is_null() || value2.is_null() { None } else { Some(logic(value1, value2)) }
+}
+
+impl<Value: Clone> Predicate<Value> {
+    /// Appends the indices of the values the predicate reads to `columns`.
+    pub fn columns(&self, columns: &mut Vec<usize>) {
+        match self {
+            Predicate::LessThan(index, other) |
+            Predicate::LessEqual(index, other) |
+            Predicate::GreaterThan(index, other) |
+            Predicate::GreaterEqual(index, other) |
+            Predicate::Equal(index, other) |
+            Predicate::NotEqual(index, other) => {
+                columns.push(*index);
+                if let SecondArgument::Position(index) = other {
+                    columns.push(*index);
+                }
+            },
+            Predicate::Any(predicates) |
+            Predicate::All(predicates) => {
+                for predicate in predicates.iter() {
+                    predicate.columns(columns);
+                }
+            },
+            Predicate::Not(predicate) => predicate.columns(columns),
+            Predicate::Holds(expression) => expression.columns(columns),
+        }
+    }
+    /// The predicate reading each value at `map(index)` rather than `index`.
+    pub fn remap<F: Fn(usize)->usize>(&self, map: &F) -> Self {
         match self {
-            Predicate::LessThan(index, other) => values[*index].lt(other.value(values)),
-            Predicate::LessEqual(index, other) => values[*index].le(other.value(values)),
-            Predicate::GreaterThan(index, other) => values[*index].gt(other.value(values)),
-            Predicate::GreaterEqual(index, other) => values[*index].ge(other.value(values)),
-            Predicate::Equal(index, other) => values[*index].eq(other.value(values)),
-            Predicate::NotEqual(index, other) => values[*index].ne(other.value(values)),
-            Predicate::Any(predicates) => predicates.iter().any(|p| p.satisfied(values)),
-            Predicate::All(predicates) => predicates.iter().all(|p| p.satisfied(values)),
-            Predicate::Not(predicate) => !predicate.satisfied(values),
+            Predicate::LessThan(index, other) => Predicate::LessThan(map(*index), other.remap(map)),
+            Predicate::LessEqual(index, other) => Predicate::LessEqual(map(*index), other.remap(map)),
+            Predicate::GreaterThan(index, other) => Predicate::GreaterThan(map(*index), other.remap(map)),
+            Predicate::GreaterEqual(index, other) => Predicate::GreaterEqual(map(*index), other.remap(map)),
+            Predicate::Equal(index, other) => Predicate::Equal(map(*index), other.remap(map)),
+            Predicate::NotEqual(index, other) => Predicate::NotEqual(map(*index), other.remap(map)),
+            Predicate::Any(predicates) => Predicate::Any(predicates.iter().map(|p| p.remap(map)).collect()),
+            Predicate::All(predicates) => Predicate::All(predicates.iter().map(|p| p.remap(map)).collect()),
+            Predicate::Not(predicate) => Predicate::Not(Box::new(predicate.remap(map))),
+            Predicate::Holds(expression) => Predicate::Holds(expression.remap(map)),
         }
     }
 }
@@ -80,18 +160,21 @@ pub struct Filter<V> {
     pub plan: Box<Plan<V>>,
 }
 
-impl<V: Data+Hash> Render for Filter<V> {
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Filter<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S: RenderScope<Self::Value>>(
         &self,
         scope: &mut S,
-        arrangements: &mut TraceManager<Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
     {
         let predicate = self.predicate.clone();
         self.plan
-            .render(scope, arrangements)
+            .render(scope, arrangements, bindings)
             .filter(move |tuple| predicate.satisfied(tuple))
     }
 }
\ No newline at end of file
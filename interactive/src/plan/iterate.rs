@@ -0,0 +1,77 @@
+//! Iterative expression plan.
+
+use std::hash::Hash;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
+
+/// A plan stage defining named collections recursively, and producing the
+/// result of a body plan that may use them.
+///
+/// Each binding is a name and a plan, which may refer to the names of any of
+/// the bindings as sources. The bindings start empty and are repeatedly set to
+/// the distinct results of their plans, until none change. The body is then
+/// evaluated with sources named by bindings referring to their final values.
+///
+/// For example, with a source `edges` of pairs, the nodes reachable from node
+/// zero are
+///
+/// ```ignore
+/// Plan::source("reach").iterate(vec![("reach".to_string(), Plan::concat(vec![
+///     Plan::source("edges").filter(Predicate::Equal(0, SecondArgument::Constant(Value::Usize(0)))).project(vec![1]),
+///     Plan::source("reach").join(Plan::source("edges"), vec![(0, 0)]).project(vec![1]),
+/// ]))])
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Iterate<V> {
+    /// Names and plans of the recursively defined collections.
+    pub bindings: Vec<(String, Plan<V>)>,
+    /// Plan for the result.
+    pub body: Box<Plan<V>>,
+}
+
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Iterate<V> {
+
+    type Value = V;
+
+    fn render<S: RenderScope<Self::Value>>(
+        &self,
+        scope: &mut S,
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
+    {
+        use timely::order::Product;
+        use differential_dataflow::operators::Threshold;
+        use differential_dataflow::operators::iterate::Variable;
+
+        scope.iterative::<u64,_,_>(|nested| {
+
+            // Bindings of enclosing scopes remain visible, unless shadowed by these bindings.
+            let mut inner =
+            bindings
+                .iter()
+                .map(|(name, collection)| (name.clone(), collection.enter(nested)))
+                .collect::<Bindings<_, V>>();
+
+            let mut variables = Vec::new();
+            for (name, _plan) in self.bindings.iter() {
+                let variable = Variable::new(nested, Product::new(Default::default(), 1));
+                inner.insert(name.clone(), (*variable).clone());
+                variables.push(variable);
+            }
+
+            for ((_name, plan), variable) in self.bindings.iter().zip(variables.into_iter()) {
+                let result = plan.render(nested, arrangements, &inner).distinct();
+                variable.set(&result);
+            }
+
+            self.body
+                .render(nested, arrangements, &inner)
+                .leave()
+        })
+    }
+}
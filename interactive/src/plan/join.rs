@@ -2,13 +2,12 @@
 
 use std::hash::Hash;
 
-use timely::dataflow::Scope;
-
 use differential_dataflow::operators::JoinCore;
 
 use differential_dataflow::{Collection, Data};
-use plan::{Plan, Render};
-use {TraceManager, Time, Diff};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
 
 /// A plan stage joining two source relations on the specified
 /// symbols. Throws if any of the join symbols isn't bound by both
@@ -23,80 +22,28 @@ pub struct Join<Value> {
     pub plan2: Box<Plan<Value>>,
 }
 
-impl<V: Data+Hash> Render for Join<V> {
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Join<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S: RenderScope<Self::Value>>(
         &self,
         scope: &mut S,
-        arrangements: &mut TraceManager<Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
     {
-        use differential_dataflow::operators::arrange::ArrangeByKey;
-
         // acquire arrangements for each input.
         let keys1 = self.keys.iter().map(|key| key.0).collect::<Vec<_>>();
-        let mut trace1 =
-        if let Some(arrangement) = arrangements.get_keyed(&self.plan1, &keys1[..]) {
-            arrangement
-        }
-        else {
-            let keys = keys1.clone();
-            let arrangement =
-            self.plan1
-                .render(scope, arrangements)
-                .map(move |tuple|
-                    (
-                        // TODO: Re-use `tuple` for values.
-                        keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
-                        tuple
-                            .into_iter()
-                            .enumerate()
-                            .filter(|(index,_value)| !keys.contains(index))
-                            .map(|(_index,value)| value)
-                            .collect::<Vec<_>>(),
-                    )
-                )
-                .arrange_by_key();
-
-            arrangements.set_keyed(&self.plan1, &keys1[..], &arrangement.trace);
-            arrangement.trace
-        };
-
-        // extract relevant fields for each index.
         let keys2 = self.keys.iter().map(|key| key.1).collect::<Vec<_>>();
-        let mut trace2 =
-        if let Some(arrangement) = arrangements.get_keyed(&self.plan2, &keys2[..]) {
-            arrangement
-        }
-        else {
-            let keys = keys2.clone();
-            let arrangement =
-            self.plan2
-                .render(scope, arrangements)
-                .map(move |tuple|
-                    (
-                        // TODO: Re-use `tuple` for values.
-                        keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
-                        tuple
-                            .into_iter()
-                            .enumerate()
-                            .filter(|(index,_value)| !keys.contains(index))
-                            .map(|(_index,value)| value)
-                            .collect::<Vec<_>>(),
-                    )
-                )
-                .arrange_by_key();
-
-            arrangements.set_keyed(&self.plan2, &keys2[..], &arrangement.trace);
-            arrangement.trace
-        };
-
-        let arrange1 = trace1.import(scope);
-        let arrange2 = trace2.import(scope);
+        let arrange1 = scope.arrange_keyed(&self.plan1, &keys1[..], arrangements, bindings);
+        let arrange2 = scope.arrange_keyed(&self.plan2, &keys2[..], arrangements, bindings);
 
+        // Null keys are unknown, and match no keys, not even null keys.
         arrange1
             .join_core(&arrange2, |keys, vals1, vals2| {
+                if keys.iter().any(|key| key.is_null()) { return None; }
                 Some(
                     keys.iter().cloned()
                         .chain(vals1.iter().cloned())
@@ -1,55 +1,224 @@
 //! Types and traits for implementing query plans.
+//!
+//! Plans are rendered in dataflows, whose timestamps are `Time`, and in the
+//! iterative scopes that `Iterate` plans establish within them. Both kinds of
+//! scope implement `RenderScope`, which imports the collections maintained by
+//! the trace manager into the scope. Only dataflows install new arrangements in
+//! the trace manager, as those of iterative scopes are not valid outside them.
 
+use std::collections::HashMap;
 use std::hash::Hash;
 
-use timely::dataflow::Scope;
+use timely::dataflow::{Scope, ScopeParent};
+use timely::dataflow::scopes::Child;
+use timely::order::Product;
+use timely::progress::Timestamp;
+use timely::progress::timestamp::Refines;
+
 use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::arrange::Arranged;
 
+use manager::TraceValHandle;
 use {TraceManager, Time, Diff};
 
 // pub mod count;
+pub mod aggregate;
+pub mod antijoin;
+pub mod bind;
 pub mod concat;
+pub mod expression;
 pub mod filter;
+pub mod iterate;
 pub mod join;
 pub mod project;
+pub mod threshold;
 
 // pub use self::count::Count;
+pub use self::aggregate::{Aggregate, Aggregation, Aggregable};
+pub use self::antijoin::Antijoin;
+pub use self::bind::Let;
 pub use self::concat::Concat;
+pub use self::expression::{Expression, BinaryOp, UnaryOp, Evaluable, Map};
 pub use self::filter::{Filter, Predicate};
+pub use self::iterate::Iterate;
 pub use self::join::Join;
 pub use self::project::Project;
+pub use self::threshold::Threshold;
+
+/// Collections bound to names while rendering, which shadow sources of the same name.
+pub type Bindings<S, V> = HashMap<String, Collection<S, Vec<V>, Diff>>;
+
+/// An arrangement of tuples by the values at some indices, in a scope `S`.
+pub type KeyedArrangement<S, V> = Arranged<S, Vec<V>, Vec<V>, Diff, TraceValHandle<Vec<V>, Vec<V>, <S as ScopeParent>::Timestamp, Diff>>;
+
+/// A scope into which plans can be rendered.
+pub trait RenderScope<V: Data+Hash+Aggregable+Evaluable> : Scope {
+
+    /// Imports the collection of `plan` into the scope, if `arrangements` maintains it.
+    ///
+    /// The import is recorded by `arrangements`, so that it can be shut down if the dataflow is
+    /// uninstalled, and so that the trace is not evicted while the dataflow is installed.
+    fn import_unkeyed(&mut self, plan: &Plan<V>, arrangements: &mut TraceManager<V>) -> Option<Collection<Self, Vec<V>, Diff>>;
+
+    /// Arranges the output of `plan` by the values at `keys`.
+    ///
+    /// A maintained arrangement is used if there is one, and otherwise the plan is
+    /// rendered and arranged, and the arrangement maintained if the scope allows it.
+    fn arrange_keyed(
+        &mut self,
+        plan: &Plan<V>,
+        keys: &[usize],
+        arrangements: &mut TraceManager<V>,
+        bindings: &Bindings<Self, V>) -> KeyedArrangement<Self, V>
+    where
+        Self::Timestamp: Lattice+Ord;
+}
+
+impl<'a, G, V> RenderScope<V> for Child<'a, G, Time>
+where
+    G: ScopeParent,
+    Time: Refines<G::Timestamp>,
+    V: Data+Hash+Aggregable+Evaluable,
+{
+    fn import_unkeyed(&mut self, plan: &Plan<V>, arrangements: &mut TraceManager<V>) -> Option<Collection<Self, Vec<V>, Diff>> {
+        let mut trace = arrangements.get_unkeyed(plan)?;
+        let (arranged, button) = trace.import_core(self, "ImportUnkeyed");
+        arrangements.imported(plan, None, button);
+        Some(arranged.as_collection(|k,()| k.to_vec()))
+    }
+
+    fn arrange_keyed(
+        &mut self,
+        plan: &Plan<V>,
+        keys: &[usize],
+        arrangements: &mut TraceManager<V>,
+        bindings: &Bindings<Self, V>) -> KeyedArrangement<Self, V>
+    {
+        // Plans that read bindings depend on them, and are not maintained.
+        let maintain = !plan.reads(bindings);
+        if maintain {
+            if let Some(mut trace) = arrangements.get_keyed(plan, keys) {
+                let (arranged, button) = trace.import_core(self, "ImportKeyed");
+                arrangements.imported(plan, Some(keys), button);
+                return arranged;
+            }
+        }
+        let arrangement = arrange_by_keys(&plan.render(self, arrangements, bindings), keys);
+        arrangements.arranged(arrangement.trace.operator(), plan);
+        if maintain {
+            arrangements.set_keyed(plan, keys, &arrangement.trace);
+        }
+        arrangement
+    }
+}
+
+impl<'a, G, T, V> RenderScope<V> for Child<'a, G, Product<G::Timestamp, T>>
+where
+    G: RenderScope<V>,
+    G::Timestamp: Lattice+Ord,
+    T: Timestamp+Lattice+Ord,
+    V: Data+Hash+Aggregable+Evaluable,
+{
+    fn import_unkeyed(&mut self, plan: &Plan<V>, arrangements: &mut TraceManager<V>) -> Option<Collection<Self, Vec<V>, Diff>> {
+        let mut parent = self.parent.clone();
+        parent
+            .import_unkeyed(plan, arrangements)
+            .map(|collection| collection.enter(self))
+    }
+
+    fn arrange_keyed(
+        &mut self,
+        plan: &Plan<V>,
+        keys: &[usize],
+        arrangements: &mut TraceManager<V>,
+        bindings: &Bindings<Self, V>) -> KeyedArrangement<Self, V>
+    {
+        let arrangement = arrange_by_keys(&plan.render(self, arrangements, bindings), keys);
+        arrangements.arranged(arrangement.trace.operator(), plan);
+        arrangement
+    }
+}
+
+/// Arranges tuples by the values at `keys`, with the remaining values in order.
+fn arrange_by_keys<S, V>(collection: &Collection<S, Vec<V>, Diff>, keys: &[usize]) -> KeyedArrangement<S, V>
+where
+    S: Scope,
+    S::Timestamp: Lattice+Ord,
+    V: Data+Hash,
+{
+    use differential_dataflow::operators::arrange::ArrangeByKey;
+
+    let keys = keys.to_vec();
+    collection
+        .map(move |tuple|
+            (
+                // TODO: Re-use `tuple` for values.
+                keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
+                tuple
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index,_value)| !keys.contains(index))
+                    .map(|(_index,value)| value)
+                    .collect::<Vec<_>>(),
+            )
+        )
+        .arrange_by_key()
+}
 
 /// A type that can be rendered as a collection.
 pub trait Render : Sized {
 
     /// Value type produced.
-    type Value: Data;
+    type Value: Data+Hash+Aggregable+Evaluable;
 
     /// Renders the instance as a collection in the supplied scope.
     ///
     /// This method has access to arranged data, and may rely on and update the set
     /// of arrangements based on the needs and offerings of the rendering process.
-    fn render<S: Scope<Timestamp = Time>>(
+    /// Sources are first sought among `bindings`, and then among the arrangements.
+    fn render<S: RenderScope<Self::Value>>(
         &self,
         scope: &mut S,
-        arrangements: &mut TraceManager<Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>;
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord;
 }
 
 /// Possible query plan types.
+///
+/// Collections are multisets, and most plans preserve multiplicities: `Concat`
+/// is the union of multisets, adding multiplicities. The `Union`, `Distinct`,
+/// and `Threshold` plans instead produce sets, in which each tuple occurs once.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Plan<Value> {
     /// Projection / Permutation
     Project(Project<Value>),
-    /// Distinct
+    /// Tuples of values computed by expressions
+    Map(Map<Value>),
+    /// Distinct tuples, as a set
     Distinct(Box<Plan<Value>>),
-    /// Concat
+    /// Tuples whose multiplicities reach a minimum, as a set
+    Threshold(Threshold<Value>),
+    /// Concat, adding multiplicities
     Concat(Concat<Value>),
+    /// Union, as a set of the tuples of any plan
+    Union(Vec<Plan<Value>>),
     /// Equijoin
     Join(Join<Value>),
+    /// Antijoin, retaining tuples without matches
+    Antijoin(Antijoin<Value>),
     /// Negation
     Negate(Box<Plan<Value>>),
     /// Filters bindings by one of the built-in predicates
     Filter(Filter<Value>),
+    /// Groups records by key and aggregates each group
+    Aggregate(Aggregate<Value>),
+    /// Binds a plan to a name, for use by another plan
+    Let(Let<Value>),
+    /// Iterates named, mutually recursive bindings to a fixed point
+    Iterate(Iterate<Value>),
     /// Sources data from another relation.
     Source(String),
     /// Prints resulting updates.
@@ -64,6 +233,13 @@ impl<V: Data+Hash> Plan<V> {
             plan: Box::new(self),
         })
     }
+    /// Replaces each tuple by the values of `expressions` evaluated on it.
+    pub fn map(self, expressions: Vec<Expression<V>>) -> Self {
+        Plan::Map(Map {
+            expressions,
+            plan: Box::new(self),
+        })
+    }
     /// Reduces a collection to distinct tuples.
     pub fn distinct(self) -> Self {
         Plan::Distinct(Box::new(self))
@@ -72,6 +248,14 @@ impl<V: Data+Hash> Plan<V> {
     pub fn concat(plans: Vec<Self>) -> Self {
         Plan::Concat(Concat { plans } )
     }
+    /// Retains one copy of each tuple occurring at least `minimum` times.
+    pub fn threshold(self, minimum: Diff) -> Self {
+        Plan::Threshold(Threshold { minimum, plan: Box::new(self) } )
+    }
+    /// The distinct tuples of multiple collections.
+    pub fn union(plans: Vec<Self>) -> Self {
+        Plan::Union(plans)
+    }
     /// Equi-joins two collections using the specified pairs of keys.
     pub fn join(self, other: Plan<V>, keys: Vec<(usize, usize)>) -> Self {
         Plan::Join(Join {
@@ -80,6 +264,14 @@ impl<V: Data+Hash> Plan<V> {
             plan2: Box::new(other),
         })
     }
+    /// Retains tuples without matches in `other` under the specified pairs of keys.
+    pub fn antijoin(self, other: Plan<V>, keys: Vec<(usize, usize)>) -> Self {
+        Plan::Antijoin(Antijoin {
+            keys,
+            plan1: Box::new(self),
+            plan2: Box::new(other),
+        })
+    }
     /// Negates a collection (negating multiplicities).
     pub fn negate(self) -> Self {
         Plan::Negate(Box::new(self))
@@ -88,6 +280,20 @@ impl<V: Data+Hash> Plan<V> {
     pub fn filter(self, predicate: Predicate<V>) -> Self {
         Plan::Filter(Filter { predicate, plan: Box::new(self) } )
     }
+    /// Groups tuples by the values at `keys`, and produces the keys followed by each aggregate.
+    pub fn aggregate(self, keys: Vec<usize>, aggregates: Vec<Aggregation>) -> Self {
+        Plan::Aggregate(Aggregate { keys, aggregates, plan: Box::new(self) } )
+    }
+    /// Binds `value` to `name`, and produces `self` which may use it as a source.
+    pub fn bind(self, name: &str, value: Plan<V>) -> Self {
+        Plan::Let(Let { name: name.to_string(), value: Box::new(value), body: Box::new(self) } )
+    }
+    /// Iterates `bindings` to a fixed point, and produces `self` evaluated with them.
+    ///
+    /// Within the iteration, sources named by bindings refer to their current values.
+    pub fn iterate(self, bindings: Vec<(String, Plan<V>)>) -> Self {
+        Plan::Iterate(Iterate { bindings, body: Box::new(self) } )
+    }
     /// Loads a source of data by name.
     pub fn source(name: &str) -> Self {
         Plan::Source(name.to_string())
@@ -96,42 +302,134 @@ impl<V: Data+Hash> Plan<V> {
     pub fn inspect(self, text: &str) -> Self {
         Plan::Inspect(text.to_string(), Box::new(self))
     }
+    /// The plans whose outputs this plan uses, in order.
+    pub fn children(&self) -> Vec<&Plan<V>> {
+        match self {
+            Plan::Project(project) => vec![&project.plan],
+            Plan::Map(map) => vec![&map.plan],
+            Plan::Distinct(plan) | Plan::Negate(plan) | Plan::Inspect(_, plan) => vec![plan],
+            Plan::Threshold(threshold) => vec![&threshold.plan],
+            Plan::Concat(concat) => concat.plans.iter().collect(),
+            Plan::Union(plans) => plans.iter().collect(),
+            Plan::Join(join) => vec![&join.plan1, &join.plan2],
+            Plan::Antijoin(antijoin) => vec![&antijoin.plan1, &antijoin.plan2],
+            Plan::Filter(filter) => vec![&filter.plan],
+            Plan::Aggregate(aggregate) => vec![&aggregate.plan],
+            Plan::Let(bind) => vec![&bind.value, &bind.body],
+            Plan::Iterate(iterate) => iterate.bindings.iter().map(|(_name, plan)| plan).chain(Some(&*iterate.body)).collect(),
+            Plan::Source(_) => Vec::new(),
+        }
+    }
+    /// The plan with each of its children replaced by `logic` applied to it.
+    pub fn map_children<F: FnMut(&Plan<V>)->Plan<V>>(&self, mut logic: F) -> Self {
+        match self {
+            Plan::Project(project) => Plan::Project(Project { indices: project.indices.clone(), plan: Box::new(logic(&project.plan)) }),
+            Plan::Map(map) => Plan::Map(Map { expressions: map.expressions.clone(), plan: Box::new(logic(&map.plan)) }),
+            Plan::Distinct(plan) => Plan::Distinct(Box::new(logic(plan))),
+            Plan::Threshold(threshold) => Plan::Threshold(Threshold { minimum: threshold.minimum, plan: Box::new(logic(&threshold.plan)) }),
+            Plan::Concat(concat) => Plan::Concat(Concat { plans: concat.plans.iter().map(logic).collect() }),
+            Plan::Union(plans) => Plan::Union(plans.iter().map(logic).collect()),
+            Plan::Join(join) => Plan::Join(Join { keys: join.keys.clone(), plan1: Box::new(logic(&join.plan1)), plan2: Box::new(logic(&join.plan2)) }),
+            Plan::Antijoin(antijoin) => Plan::Antijoin(Antijoin { keys: antijoin.keys.clone(), plan1: Box::new(logic(&antijoin.plan1)), plan2: Box::new(logic(&antijoin.plan2)) }),
+            Plan::Negate(plan) => Plan::Negate(Box::new(logic(plan))),
+            Plan::Filter(filter) => Plan::Filter(Filter { predicate: filter.predicate.clone(), plan: Box::new(logic(&filter.plan)) }),
+            Plan::Aggregate(aggregate) => Plan::Aggregate(Aggregate { keys: aggregate.keys.clone(), aggregates: aggregate.aggregates.clone(), plan: Box::new(logic(&aggregate.plan)) }),
+            Plan::Let(bind) => Plan::Let(Let { name: bind.name.clone(), value: Box::new(logic(&bind.value)), body: Box::new(logic(&bind.body)) }),
+            Plan::Iterate(iterate) => Plan::Iterate(Iterate {
+                bindings: iterate.bindings.iter().map(|(name, plan)| (name.clone(), logic(plan))).collect(),
+                body: Box::new(logic(&iterate.body)),
+            }),
+            Plan::Source(name) => Plan::Source(name.clone()),
+            Plan::Inspect(text, plan) => Plan::Inspect(text.clone(), Box::new(logic(plan))),
+        }
+    }
+    /// Indicates if the plan reads any of the collections bound in `bindings`.
+    ///
+    /// Plans that do not may be maintained, and imported rather than rendered.
+    pub fn reads<S: Scope>(&self, bindings: &Bindings<S, V>) -> bool {
+        match self {
+            Plan::Source(name) => bindings.contains_key(name),
+            _ => !bindings.is_empty() && self.children().into_iter().any(|child| child.reads(bindings)),
+        }
+    }
 }
 
-impl<V: Data+Hash> Render for Plan<V> {
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Plan<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S: RenderScope<Self::Value>>(
         &self,
         scope: &mut S,
-        arrangements: &mut TraceManager<Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
     {
+        // Maintained plans are imported rather than rendered, unless they read bindings.
+        if !self.reads(bindings) {
+            if let Some(collection) = scope.import_unkeyed(self, arrangements) {
+                return collection;
+            }
+        }
+
         match self {
-            Plan::Project(projection) => projection.render(scope, arrangements),
+            Plan::Project(projection) => projection.render(scope, arrangements, bindings),
+            Plan::Map(map) => map.render(scope, arrangements, bindings),
             Plan::Distinct(distinct) => {
-                // TODO: Check for existing arrangement.
                 use differential_dataflow::operators::Threshold;
-                distinct.render(scope, arrangements).distinct()
+                use differential_dataflow::operators::arrange::ArrangeBySelf;
+                let arranged = distinct.render(scope, arrangements, bindings).arrange_by_self();
+                arrangements.arranged(arranged.trace.operator(), distinct);
+                arranged.distinct()
             },
+            Plan::Threshold(threshold) => threshold.render(scope, arrangements, bindings),
             // Plan::Count(count) => count.render(scope, arrangements),
-            Plan::Concat(concat) => concat.render(scope, arrangements),
-            Plan::Join(join) => join.render(scope, arrangements),
+            Plan::Concat(concat) => concat.render(scope, arrangements, bindings),
+            Plan::Union(plans) => {
+                use timely::dataflow::operators::Concatenate;
+                use differential_dataflow::AsCollection;
+                use differential_dataflow::operators::Threshold;
+
+                let collections =
+                plans
+                    .iter()
+                    .map(|plan| plan.render(scope, arrangements, bindings).inner)
+                    .collect::<Vec<_>>();
+
+                scope
+                    .concatenate(collections)
+                    .as_collection()
+                    .distinct()
+            },
+            Plan::Join(join) => join.render(scope, arrangements, bindings),
+            Plan::Antijoin(antijoin) => antijoin.render(scope, arrangements, bindings),
             Plan::Negate(negate) => {
-                negate.render(scope, arrangements).negate()
+                negate.render(scope, arrangements, bindings).negate()
             },
-            Plan::Filter(filter) => filter.render(scope, arrangements),
+            Plan::Filter(filter) => filter.render(scope, arrangements, bindings),
+            Plan::Aggregate(aggregate) => aggregate.render(scope, arrangements, bindings),
+            Plan::Let(bind) => bind.render(scope, arrangements, bindings),
+            Plan::Iterate(iterate) => iterate.render(scope, arrangements, bindings),
             Plan::Source(source) => {
-                arrangements
-                    .get_unkeyed(self)
-                    .expect(&format!("Failed to find source collection: {:?}", source))
-                    .import(scope)
-                    .as_collection(|k,()| k.to_vec())
+                if let Some(collection) = bindings.get(source) {
+                    collection.clone()
+                }
+                else {
+                    scope
+                        .import_unkeyed(self, arrangements)
+                        .expect(&format!("Failed to find source collection: {:?}", source))
+                }
             },
             Plan::Inspect(text, plan) => {
                 let text = text.clone();
-                plan.render(scope, arrangements)
-                    .inspect(move |x| println!("{}\t{:?}", text, x))
+                let redactor = arrangements.redactor();
+                plan.render(scope, arrangements, bindings)
+                    .inspect(move |(tuple, time, diff)| {
+                        let mut tuple = tuple.clone();
+                        ::redact::apply(redactor.as_ref(), &text, &mut tuple);
+                        println!("{}\t{:?}", text, (tuple, time, diff))
+                    })
             },
         }
     }
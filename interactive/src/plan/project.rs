@@ -2,17 +2,17 @@
 
 use std::hash::Hash;
 
-use timely::dataflow::Scope;
-
 use differential_dataflow::{Collection, Data};
-use plan::{Plan, Render};
-use {TraceManager, Time, Diff};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
 
 /// A plan which retains values at specified locations.
 ///
 /// The plan does not ascribe meaning to specific locations (e.g. bindings)
 /// to variable names, and simply selects out the indicated sequence of values,
-/// panicking if some input record is insufficiently long.
+/// panicking if some input record is insufficiently long. To compute new values
+/// from those of each record, use `Map`.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Project<V> {
     /// Sequence (and order) of indices to be retained.
@@ -21,20 +21,23 @@ pub struct Project<V> {
     pub plan: Box<Plan<V>>,
 }
 
-impl<V: Data+Hash> Render for Project<V> {
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Project<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S: RenderScope<Self::Value>>(
         &self,
         scope: &mut S,
-        arrangements: &mut TraceManager<Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
     {
         let indices = self.indices.clone();
 
         // TODO: re-use `tuple` allocation.
         self.plan
-            .render(scope, arrangements)
+            .render(scope, arrangements, bindings)
             .map(move |tuple| indices.iter().map(|index| tuple[*index].clone()).collect())
     }
 }
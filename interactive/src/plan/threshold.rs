@@ -0,0 +1,42 @@
+//! Threshold expression plan.
+
+use std::hash::Hash;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::lattice::Lattice;
+use plan::{Plan, Render, RenderScope, Bindings, Aggregable, Evaluable};
+use {TraceManager, Diff};
+
+/// A plan stage retaining one copy of each tuple whose multiplicity is at
+/// least `minimum`, and no copies of the others.
+///
+/// The result is a set. A minimum of one produces the distinct tuples, and
+/// larger minimums the tuples that occur at least that many times.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Threshold<V> {
+    /// The least multiplicity of retained tuples.
+    pub minimum: Diff,
+    /// Plan for the data source.
+    pub plan: Box<Plan<V>>,
+}
+
+impl<V: Data+Hash+Aggregable+Evaluable> Render for Threshold<V> {
+
+    type Value = V;
+
+    fn render<S: RenderScope<Self::Value>>(
+        &self,
+        scope: &mut S,
+        arrangements: &mut TraceManager<Self::Value>,
+        bindings: &Bindings<S, Self::Value>) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S::Timestamp: Lattice+Ord,
+    {
+        use differential_dataflow::operators::Threshold;
+
+        let minimum = self.minimum;
+        self.plan
+            .render(scope, arrangements, bindings)
+            .threshold(move |_tuple, count| if *count >= minimum { 1 } else { 0 })
+    }
+}
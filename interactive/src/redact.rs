@@ -0,0 +1,70 @@
+//! Redaction of sensitive values as they leave the system.
+//!
+//! A `Redactor` set with `TraceManager::set_redactor` rewrites tuples as they are emitted:
+//! into the logging collections the manager publishes, by `inspect` plans, and as updates
+//! of subscriptions and contents of reads in the responses of the server. Collections and
+//! the dataflows computing them retain the original values, so that redaction changes what
+//! is reported and not what is computed.
+//!
+//! Redactors see the name of the collection a tuple belongs to, where it has one. Tuples of
+//! plans read without a name, and of `inspect` plans, are described by the debug text of the
+//! plan and by the text of the inspection, respectively, so a redactor that protects named
+//! collections should redact these conservatively, as they may derive from the collections.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Rewrites tuples before they are emitted.
+pub trait Redactor<V> {
+    /// Rewrites the values of `tuple`, emitted in `context`, in place.
+    fn redact(&self, context: &str, tuple: &mut Vec<V>);
+}
+
+impl<V, F: Fn(&str, &mut Vec<V>)> Redactor<V> for F {
+    fn redact(&self, context: &str, tuple: &mut Vec<V>) {
+        (self)(context, tuple)
+    }
+}
+
+/// A shared redactor.
+pub type Redaction<V> = Rc<Redactor<V>>;
+
+/// Replaces the values of columns of named collections with a placeholder.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Columns<V> {
+    /// The redacted columns of each collection, by name.
+    pub columns: HashMap<String, Vec<usize>>,
+    /// The value that replaces those redacted.
+    pub placeholder: V,
+}
+
+impl<V> Columns<V> {
+    /// Creates a redactor replacing values with `placeholder`, of no columns.
+    pub fn new(placeholder: V) -> Self {
+        Columns { columns: HashMap::new(), placeholder }
+    }
+    /// Also redacts `columns` of the collection `name`.
+    pub fn hide(mut self, name: &str, columns: Vec<usize>) -> Self {
+        self.columns.entry(name.to_string()).or_insert_with(Vec::new).extend(columns);
+        self
+    }
+}
+
+impl<V: Clone> Redactor<V> for Columns<V> {
+    fn redact(&self, context: &str, tuple: &mut Vec<V>) {
+        if let Some(columns) = self.columns.get(context) {
+            for column in columns.iter() {
+                if let Some(value) = tuple.get_mut(*column) {
+                    *value = self.placeholder.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites `tuple`, emitted in `context`, with `redactor` if there is one.
+pub fn apply<V>(redactor: Option<&Redaction<V>>, context: &str, tuple: &mut Vec<V>) {
+    if let Some(redactor) = redactor {
+        redactor.redact(context, tuple);
+    }
+}
@@ -0,0 +1,403 @@
+//! Schemas naming and typing the columns of collections.
+//!
+//! Plans otherwise address the values of tuples by position, and an operator
+//! applied to a value of the wrong type, or a position beyond the end of a tuple,
+//! only produces no output once the plan is running. A schema declared for an
+//! input names and types its columns, and tuples introduced to the input are
+//! checked against it. When a plan is installed, `check` infers the schema of each
+//! of its stages from those of its sources, reports the first stage that misuses
+//! a column, and resolves the columns that expressions name to their positions.
+//! Sources without schemas, and the stages reading them, are not checked, other
+//! than concatenations and unions, which have the schemas of their inputs with
+//! schemas, so that the schemas of recursive definitions follow from their base
+//! cases. Null values belong to every type, and null constants have the type `Null`,
+//! which is compatible with every other.
+//!
+//! Schemas describe tuples, and do not change how they are stored: tuples remain
+//! vectors of values, each of which records its own type.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Let, Iterate, Map, Filter, Predicate, Expression, BinaryOp, UnaryOp, Aggregation, Aggregable};
+use plan::filter::SecondArgument;
+
+/// The types of values.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Type {
+    /// Booleans.
+    Bool,
+    /// Integers.
+    Usize,
+    /// Strings.
+    String,
+    /// Operator addresses.
+    Address,
+    /// Durations.
+    Duration,
+    /// Fixed-point decimals.
+    Decimal,
+    /// Byte arrays.
+    Bytes,
+    /// Timestamps.
+    Timestamp,
+    /// The type of null values, compatible with every type.
+    Null,
+}
+
+impl Type {
+    /// The type of values of both types, if they are compatible.
+    pub fn unify(self, other: Type) -> Option<Type> {
+        match (self, other) {
+            (Type::Null, other) => Some(other),
+            (this, Type::Null) => Some(this),
+            (this, other) => if this == other { Some(this) } else { None },
+        }
+    }
+}
+
+/// Values whose types can be checked.
+///
+/// Comparisons apply to values of the same type, and boolean logic to booleans; the
+/// other operators are typed by the value type, consistently with `Evaluable`.
+pub trait Typed {
+    /// The type of the value.
+    fn type_of(&self) -> Type;
+    /// The type of an arithmetic or string operator applied to values of two types, if it applies.
+    fn binary_type(op: BinaryOp, type1: Type, type2: Type) -> Option<Type>;
+    /// The type of a string or arithmetic operator applied to a value of a type, if it applies.
+    fn unary_type(op: UnaryOp, type1: Type) -> Option<Type>;
+}
+
+/// The names and types of the columns of a collection.
+///
+/// Columns computed by expressions other than columns have empty names, and cannot be named.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Schema {
+    /// The name and type of each column, in order.
+    pub columns: Vec<(String, Type)>,
+}
+
+impl Schema {
+    /// Creates a schema with the named and typed columns.
+    pub fn new(columns: Vec<(String, Type)>) -> Self {
+        Schema { columns }
+    }
+    /// The number of columns.
+    pub fn arity(&self) -> usize {
+        self.columns.len()
+    }
+    /// The name and type of the column at `index`.
+    pub fn column(&self, index: usize) -> Result<&(String, Type), String> {
+        self.columns.get(index).ok_or_else(|| format!("Column {} of {} columns", index, self.arity()))
+    }
+    /// The index of the column named `name`, which must name exactly one column.
+    pub fn index(&self, name: &str) -> Result<usize, String> {
+        let mut indices = self.columns.iter().enumerate().filter(|(_, (column, _))| !column.is_empty() && column == name);
+        match (indices.next(), indices.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (Some(_), Some(_)) => Err(format!("Column {:?} is ambiguous in {}", name, self)),
+            (None, _) => Err(format!("Column {:?} not found in {}", name, self)),
+        }
+    }
+    /// Checks that `tuple` has a value of the type of each column, or null.
+    pub fn check<V: Typed+::std::fmt::Debug>(&self, tuple: &[V]) -> Result<(), String> {
+        if tuple.len() != self.arity() {
+            return Err(format!("Tuple {:?} has {} values, rather than the {} of {}", tuple, tuple.len(), self.arity(), self));
+        }
+        for (value, (name, typed)) in tuple.iter().zip(self.columns.iter()) {
+            if value.type_of().unify(*typed).is_none() {
+                return Err(format!("Tuple {:?} has {:?} for column {:?} of type {:?}", tuple, value, name, typed));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ::std::fmt::Display for Schema {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "(")?;
+        for (index, (name, typed)) in self.columns.iter().enumerate() {
+            if index > 0 { write!(f, ", ")?; }
+            write!(f, "{}: {:?}", name, typed)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Checks `plan` against the schemas of the named collections it reads.
+///
+/// Returns the plan with named columns resolved to their positions, and its schema if
+/// the schemas of the collections it reads are known, or the first error found.
+pub fn check<V>(plan: &Plan<V>, schemas: &HashMap<String, Schema>) -> Result<(Plan<V>, Option<Schema>), String>
+where
+    V: Data+Hash+Aggregable+Typed,
+{
+    Checker { schemas, bound: Vec::new() }.infer(plan)
+}
+
+/// Infers the schemas of plans, given those of named collections and of names bound within plans.
+struct Checker<'a> {
+    schemas: &'a HashMap<String, Schema>,
+    bound: Vec<(String, Option<Schema>)>,
+}
+
+impl<'a> Checker<'a> {
+
+    fn infer<V: Data+Hash+Aggregable+Typed>(&mut self, plan: &Plan<V>) -> Result<(Plan<V>, Option<Schema>), String> {
+        match plan {
+            Plan::Source(name) => {
+                let schema = match self.bound.iter().rev().find(|(bound, _)| bound == name) {
+                    Some((_, schema)) => schema.clone(),
+                    None => self.schemas.get(name).cloned(),
+                };
+                Ok((plan.clone(), schema))
+            },
+            Plan::Let(bind) => {
+                let (value, schema) = self.infer(&bind.value)?;
+                self.bound.push((bind.name.clone(), schema));
+                let body = self.infer(&bind.body);
+                self.bound.pop();
+                let (body, schema) = body?;
+                Ok((Plan::Let(Let { name: bind.name.clone(), value: Box::new(value), body: Box::new(body) }), schema))
+            },
+            Plan::Iterate(iterate) => {
+                // Infers the schemas of the bindings from their plans without the schemas of
+                // the bindings, as from the parts of concatenations that do not read them, and
+                // then checks the plans with those schemas.
+                let depth = self.bound.len();
+                self.bound.extend(iterate.bindings.iter().map(|(name, _plan)| (name.clone(), None)));
+                let schemas =
+                iterate
+                    .bindings
+                    .iter()
+                    .map(|(_name, plan)| self.infer(plan).ok().and_then(|(_plan, schema)| schema))
+                    .collect::<Vec<_>>();
+                for (bound, schema) in self.bound[depth..].iter_mut().zip(schemas) {
+                    bound.1 = schema;
+                }
+                let bindings =
+                iterate
+                    .bindings
+                    .iter()
+                    .map(|(name, plan)| self.infer(plan).map(|(plan, _schema)| (name.clone(), plan)))
+                    .collect::<Result<Vec<_>, _>>();
+                let checked = bindings.and_then(|bindings| self.infer(&iterate.body).map(|(body, schema)| (bindings, body, schema)));
+                self.bound.truncate(depth);
+                let (bindings, body, schema) = checked?;
+                Ok((Plan::Iterate(Iterate { bindings, body: Box::new(body) }), schema))
+            },
+            _ => {
+                let mut checked = Vec::new();
+                for child in plan.children() {
+                    checked.push(self.infer(child)?);
+                }
+                let known = checked.iter().filter_map(|(_plan, schema)| schema.clone()).collect::<Vec<_>>();
+                let inputs =
+                match plan {
+                    // Combined collections have the schema of those whose schemas are known.
+                    Plan::Concat(_) | Plan::Union(_) if !known.is_empty() => Some(known),
+                    _ if known.len() == checked.len() => Some(known),
+                    _ => None,
+                };
+                let mut children = checked.into_iter().map(|(plan, _schema)| plan);
+                let plan = plan.map_children(|_child| children.next().expect("one checked plan per child"));
+                match inputs {
+                    Some(inputs) => stage(plan, inputs),
+                    None => unchecked(plan),
+                }
+            },
+        }
+    }
+}
+
+/// Checks a stage whose inputs have the schemas `inputs`, and infers its schema.
+fn stage<V: Data+Hash+Aggregable+Typed>(plan: Plan<V>, mut inputs: Vec<Schema>) -> Result<(Plan<V>, Option<Schema>), String> {
+    match plan {
+        Plan::Project(project) => {
+            let columns = project.indices.iter().map(|index| inputs[0].column(*index).map(|column| column.clone())).collect::<Result<Vec<_>, _>>()?;
+            Ok((Plan::Project(project), Some(Schema::new(columns))))
+        },
+        Plan::Map(map) => {
+            let mut expressions = Vec::new();
+            let mut columns = Vec::new();
+            for expression in map.expressions.iter() {
+                let (expression, typed) = resolve(expression, &inputs[0])?;
+                let name = match &expression {
+                    Expression::Column(index) => inputs[0].columns[*index].0.clone(),
+                    _ => String::new(),
+                };
+                expressions.push(expression);
+                columns.push((name, typed));
+            }
+            Ok((Plan::Map(Map { expressions, plan: map.plan }), Some(Schema::new(columns))))
+        },
+        Plan::Concat(_) | Plan::Union(_) => {
+            for schema in inputs[1..].iter() {
+                if !same_types(&inputs[0], schema) {
+                    return Err(format!("Combining collections of schemas {} and {}", inputs[0], schema));
+                }
+            }
+            let schema = inputs.into_iter().next();
+            Ok((plan, schema))
+        },
+        Plan::Join(join) => {
+            let (left, right) = (&inputs[0], &inputs[1]);
+            let mut columns = Vec::new();
+            for (key1, key2) in join.keys.iter() {
+                let (column1, column2) = (left.column(*key1)?, right.column(*key2)?);
+                if column1.1.unify(column2.1).is_none() {
+                    return Err(format!("Joining column {:?} of type {:?} with {:?} of type {:?}", column1.0, column1.1, column2.0, column2.1));
+                }
+                columns.push(column1.clone());
+            }
+            columns.extend(left.columns.iter().enumerate().filter(|(index, _)| !join.keys.iter().any(|key| key.0 == *index)).map(|(_, column)| column.clone()));
+            columns.extend(right.columns.iter().enumerate().filter(|(index, _)| !join.keys.iter().any(|key| key.1 == *index)).map(|(_, column)| column.clone()));
+            Ok((Plan::Join(join), Some(Schema::new(columns))))
+        },
+        Plan::Antijoin(antijoin) => {
+            for (key1, key2) in antijoin.keys.iter() {
+                let (column1, column2) = (inputs[0].column(*key1)?, inputs[1].column(*key2)?);
+                if column1.1.unify(column2.1).is_none() {
+                    return Err(format!("Antijoining column {:?} of type {:?} with {:?} of type {:?}", column1.0, column1.1, column2.0, column2.1));
+                }
+            }
+            Ok((Plan::Antijoin(antijoin), Some(inputs.remove(0))))
+        },
+        Plan::Filter(filter) => {
+            let predicate = resolve_predicate(&filter.predicate, &inputs[0])?;
+            Ok((Plan::Filter(Filter { predicate, plan: filter.plan }), Some(inputs.remove(0))))
+        },
+        Plan::Aggregate(aggregate) => {
+            let input = &inputs[0];
+            let mut columns = aggregate.keys.iter().map(|index| input.column(*index).map(|column| column.clone())).collect::<Result<Vec<_>, _>>()?;
+            for aggregation in aggregate.aggregates.iter() {
+                let typed = match aggregation {
+                    Aggregation::Count => V::from_count(0).type_of(),
+                    Aggregation::Sum(index) => {
+                        let (name, typed) = input.column(*index)?;
+                        if V::binary_type(BinaryOp::Add, *typed, *typed) != Some(*typed) {
+                            return Err(format!("Summing column {:?} of type {:?}", name, typed));
+                        }
+                        *typed
+                    },
+                    Aggregation::Min(index) | Aggregation::Max(index) => input.column(*index)?.1,
+                };
+                columns.push((String::new(), typed));
+            }
+            Ok((Plan::Aggregate(aggregate), Some(Schema::new(columns))))
+        },
+        Plan::Distinct(_) | Plan::Threshold(_) | Plan::Negate(_) | Plan::Inspect(_, _) => {
+            let schema = inputs.into_iter().next();
+            Ok((plan, schema))
+        },
+        Plan::Let(_) | Plan::Iterate(_) | Plan::Source(_) => unreachable!("Stages binding or reading names are inferred by the checker"),
+    }
+}
+
+/// Accepts a stage reading collections without schemas, unless it names columns, which cannot be resolved.
+fn unchecked<V: Data+Hash>(plan: Plan<V>) -> Result<(Plan<V>, Option<Schema>), String> {
+    let named = match &plan {
+        Plan::Map(map) => map.expressions.iter().any(names),
+        Plan::Filter(filter) => predicate_names(&filter.predicate),
+        _ => false,
+    };
+    if named { Err(format!("Columns named in a stage reading collections without schemas: {:?}", plan)) }
+    else { Ok((plan, None)) }
+}
+
+/// Indicates if two schemas have columns of the same types, whatever their names.
+fn same_types(schema1: &Schema, schema2: &Schema) -> bool {
+    schema1.arity() == schema2.arity() && schema1.columns.iter().zip(schema2.columns.iter()).all(|(column1, column2)| column1.1.unify(column2.1).is_some())
+}
+
+/// Resolves the columns `expression` names in `schema`, and infers its type.
+fn resolve<V: Data+Typed>(expression: &Expression<V>, schema: &Schema) -> Result<(Expression<V>, Type), String> {
+    match expression {
+        Expression::Column(index) => Ok((expression.clone(), schema.column(*index)?.1)),
+        Expression::Named(name) => {
+            let index = schema.index(name)?;
+            Ok((Expression::Column(index), schema.columns[index].1))
+        },
+        Expression::Constant(value) => Ok((expression.clone(), value.type_of())),
+        Expression::Binary(op, expr1, expr2) => {
+            let (expr1, type1) = resolve(expr1, schema)?;
+            let (expr2, type2) = resolve(expr2, schema)?;
+            let typed = match op {
+                BinaryOp::Equal | BinaryOp::NotEqual |
+                BinaryOp::LessThan | BinaryOp::LessEqual |
+                BinaryOp::GreaterThan | BinaryOp::GreaterEqual => type1.unify(type2).map(|_| Type::Bool),
+                BinaryOp::And | BinaryOp::Or => type1.unify(Type::Bool).and(type2.unify(Type::Bool)),
+                // Null arguments produce null, whose type is compatible with any result.
+                _ if type1 == Type::Null || type2 == Type::Null => Some(Type::Null),
+                _ => V::binary_type(*op, type1, type2),
+            };
+            let typed = typed.ok_or_else(|| format!("{:?} applied to {:?} and {:?}", op, type1, type2))?;
+            Ok((Expression::Binary(*op, Box::new(expr1), Box::new(expr2)), typed))
+        },
+        Expression::Unary(op, expr) => {
+            let (expr, type1) = resolve(expr, schema)?;
+            let typed = match op {
+                UnaryOp::Not => type1.unify(Type::Bool),
+                _ if type1 == Type::Null => Some(Type::Null),
+                _ => V::unary_type(*op, type1),
+            };
+            let typed = typed.ok_or_else(|| format!("{:?} applied to {:?}", op, type1))?;
+            Ok((Expression::Unary(*op, Box::new(expr)), typed))
+        },
+    }
+}
+
+/// Resolves the columns `predicate` names in `schema`, and checks the types it compares.
+fn resolve_predicate<V: Data+Typed>(predicate: &Predicate<V>, schema: &Schema) -> Result<Predicate<V>, String> {
+    match predicate {
+        Predicate::LessThan(index, other) |
+        Predicate::LessEqual(index, other) |
+        Predicate::GreaterThan(index, other) |
+        Predicate::GreaterEqual(index, other) |
+        Predicate::Equal(index, other) |
+        Predicate::NotEqual(index, other) => {
+            let (name, typed) = schema.column(*index)?;
+            let other_type = match other {
+                SecondArgument::Constant(value) => value.type_of(),
+                SecondArgument::Position(position) => schema.column(*position)?.1,
+            };
+            if typed.unify(other_type).is_none() {
+                return Err(format!("Comparing column {:?} of type {:?} with {:?}", name, typed, other_type));
+            }
+            Ok(predicate.clone())
+        },
+        Predicate::Any(predicates) => Ok(Predicate::Any(predicates.iter().map(|p| resolve_predicate(p, schema)).collect::<Result<_, _>>()?)),
+        Predicate::All(predicates) => Ok(Predicate::All(predicates.iter().map(|p| resolve_predicate(p, schema)).collect::<Result<_, _>>()?)),
+        Predicate::Not(negated) => Ok(Predicate::Not(Box::new(resolve_predicate(negated, schema)?))),
+        Predicate::Holds(expression) => {
+            let (expression, typed) = resolve(expression, schema)?;
+            if typed.unify(Type::Bool).is_none() {
+                return Err(format!("Filtering by an expression of type {:?}", typed));
+            }
+            Ok(Predicate::Holds(expression))
+        },
+    }
+}
+
+/// Indicates if `expression` names any columns.
+fn names<V>(expression: &Expression<V>) -> bool {
+    match expression {
+        Expression::Named(_) => true,
+        Expression::Column(_) | Expression::Constant(_) => false,
+        Expression::Binary(_, expr1, expr2) => names(expr1) || names(expr2),
+        Expression::Unary(_, expr) => names(expr),
+    }
+}
+
+/// Indicates if `predicate` names any columns.
+fn predicate_names<V>(predicate: &Predicate<V>) -> bool {
+    match predicate {
+        Predicate::Any(predicates) | Predicate::All(predicates) => predicates.iter().any(predicate_names),
+        Predicate::Not(negated) => predicate_names(negated),
+        Predicate::Holds(expression) => names(expression),
+        _ => false,
+    }
+}
@@ -0,0 +1,177 @@
+//! Sharing the work of equivalent plans.
+//!
+//! The trace manager maintains collections by plan, and rendering imports a
+//! maintained collection wherever its plan occurs. Plans are compared by their
+//! structure, so different ways of writing the same collection would not find
+//! each other. Before a plan is rendered, `canonicalize` rewrites it into a
+//! canonical form, in which for example the inputs of concatenations are sorted
+//! and consecutive filters are combined, so that equivalent plans are more often
+//! equal. Then `eliminate` binds sub-plans that occur more than once to names,
+//! so that each is rendered once and shared by its uses.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Project, Concat, Filter, Predicate, Let};
+use TraceManager;
+
+/// Rewrites `plan` into a canonical form, which is equivalent to it.
+///
+/// Concatenations are flattened and their inputs sorted; unions are expressed as the
+/// distinct tuples of concatenations, and thresholds of one as distinct tuples; repeated
+/// distincts, negations, and projections are collapsed; and consecutive filters are
+/// combined into one filter of a sorted conjunction.
+pub fn canonicalize<V: Data+Hash>(plan: &Plan<V>) -> Plan<V> {
+
+    let plan = plan.map_children(canonicalize);
+
+    match plan {
+        Plan::Project(project) => {
+            match *project.plan {
+                Plan::Project(inner) if project.indices.iter().all(|index| *index < inner.indices.len()) => {
+                    let indices = project.indices.iter().map(|index| inner.indices[*index]).collect();
+                    Plan::Project(Project { indices, plan: inner.plan })
+                },
+                plan => Plan::Project(Project { indices: project.indices, plan: Box::new(plan) }),
+            }
+        },
+        Plan::Distinct(distinct) => {
+            match *distinct {
+                Plan::Distinct(inner) => Plan::Distinct(inner),
+                plan => Plan::Distinct(Box::new(plan)),
+            }
+        },
+        Plan::Threshold(threshold) => {
+            if threshold.minimum == 1 { canonicalize(&Plan::Distinct(threshold.plan)) }
+            else { Plan::Threshold(threshold) }
+        },
+        Plan::Concat(concat) => {
+            let mut plans = Vec::new();
+            for plan in concat.plans.into_iter() {
+                match plan {
+                    Plan::Concat(inner) => plans.extend(inner.plans),
+                    plan => plans.push(plan),
+                }
+            }
+            plans.sort();
+            if plans.len() == 1 { plans.pop().unwrap() }
+            else { Plan::Concat(Concat { plans }) }
+        },
+        Plan::Union(plans) => canonicalize(&Plan::Distinct(Box::new(Plan::Concat(Concat { plans })))),
+        Plan::Negate(negate) => {
+            match *negate {
+                Plan::Negate(inner) => *inner,
+                plan => Plan::Negate(Box::new(plan)),
+            }
+        },
+        Plan::Filter(filter) => {
+            let mut predicates = Vec::new();
+            conjuncts(filter.predicate, &mut predicates);
+            let mut plan = *filter.plan;
+            while let Plan::Filter(inner) = plan {
+                conjuncts(inner.predicate, &mut predicates);
+                plan = *inner.plan;
+            }
+            predicates.sort();
+            predicates.dedup();
+            let predicate = if predicates.len() == 1 { predicates.pop().unwrap() } else { Predicate::All(predicates) };
+            Plan::Filter(Filter { predicate, plan: Box::new(plan) })
+        },
+        plan => plan,
+    }
+}
+
+/// Appends the conjuncts of `predicate` to `predicates`.
+fn conjuncts<V>(predicate: Predicate<V>, predicates: &mut Vec<Predicate<V>>) {
+    match predicate {
+        Predicate::All(list) => {
+            for predicate in list.into_iter() {
+                conjuncts(predicate, predicates);
+            }
+        },
+        predicate => predicates.push(predicate),
+    }
+}
+
+/// Binds each sub-plan occurring more than once in `plan` to a name, used in its place.
+///
+/// Sub-plans are bound only if they are not sources, are not already maintained by `traces`,
+/// and do not read names bound within `plan`. Larger sub-plans are bound before the sub-plans
+/// they contain, and bindings are named `$0`, `$1`, and so on, in the order they are made.
+pub fn eliminate<V: Data+Hash>(plan: &Plan<V>, traces: &TraceManager<V>) -> Plan<V> {
+
+    let mut counts = HashMap::new();
+    count(plan, &mut counts);
+
+    let mut candidates =
+    counts
+        .into_iter()
+        // Sources are exactly the plans without children, and are already shared.
+        .filter(|(candidate, count)| *count > 1 && !candidate.children().is_empty() && traces.get_unkeyed(candidate).is_none())
+        .map(|(candidate, _count)| (size(&candidate), candidate))
+        .collect::<Vec<_>>();
+
+    // Larger plans first, and otherwise in order, so that names are assigned deterministically.
+    candidates.sort_by(|x, y| y.0.cmp(&x.0).then_with(|| x.1.cmp(&y.1)));
+
+    let mut plan = plan.clone();
+    let mut index = 0;
+    for (_size, candidate) in candidates.into_iter() {
+        let mut bound = HashSet::new();
+        names(&plan, &mut bound);
+        if occurrences(&plan, &candidate) > 1 && !reads_any(&candidate, &bound) {
+            let name = format!("${}", index);
+            index += 1;
+            let body = replace(&plan, &candidate, &Plan::Source(name.clone()));
+            plan = Plan::Let(Let { name, value: Box::new(candidate), body: Box::new(body) });
+        }
+    }
+    plan
+}
+
+/// Counts the occurrences of each sub-plan of `plan`, including `plan` itself.
+fn count<V: Data+Hash>(plan: &Plan<V>, counts: &mut HashMap<Plan<V>, usize>) {
+    *counts.entry(plan.clone()).or_insert(0) += 1;
+    for child in plan.children() {
+        count(child, counts);
+    }
+}
+
+/// The number of stages of `plan`.
+fn size<V: Data+Hash>(plan: &Plan<V>) -> usize {
+    1 + plan.children().into_iter().map(size).sum::<usize>()
+}
+
+/// The number of occurrences of `candidate` in `plan`.
+fn occurrences<V: Data+Hash>(plan: &Plan<V>, candidate: &Plan<V>) -> usize {
+    if plan == candidate { 1 }
+    else { plan.children().into_iter().map(|child| occurrences(child, candidate)).sum() }
+}
+
+/// Collects the names bound by `Let` and `Iterate` stages of `plan`.
+fn names<V: Data+Hash>(plan: &Plan<V>, bound: &mut HashSet<String>) {
+    match plan {
+        Plan::Let(bind) => { bound.insert(bind.name.clone()); },
+        Plan::Iterate(iterate) => { bound.extend(iterate.bindings.iter().map(|(name, _plan)| name.clone())); },
+        _ => { },
+    }
+    for child in plan.children() {
+        names(child, bound);
+    }
+}
+
+/// Indicates if `plan` reads a source with any of the names in `bound`.
+fn reads_any<V: Data+Hash>(plan: &Plan<V>, bound: &HashSet<String>) -> bool {
+    match plan {
+        Plan::Source(name) => bound.contains(name),
+        _ => plan.children().into_iter().any(|child| reads_any(child, bound)),
+    }
+}
+
+/// Replaces each occurrence of `from` in `plan` by `to`.
+fn replace<V: Data+Hash>(plan: &Plan<V>, from: &Plan<V>, to: &Plan<V>) -> Plan<V> {
+    if plan == from { to.clone() }
+    else { plan.map_children(|child| replace(child, from, to)) }
+}
@@ -0,0 +1,74 @@
+//! Splitting SQL text into tokens.
+
+/// A token of SQL text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token {
+    /// A keyword or unquoted identifier.
+    Word(String),
+    /// An identifier in double quotes.
+    Quoted(String),
+    /// A number, as written.
+    Number(String),
+    /// A string in single quotes, without its quotes.
+    Text(String),
+    /// Punctuation or an operator.
+    Symbol(&'static str),
+}
+
+/// Symbols, each listed before those that are its prefixes.
+const SYMBOLS: &[&str] = &["<>", "!=", "<=", ">=", "||", "(", ")", ",", ".", "*", "+", "-", "/", "%", "=", "<", ">", ";"];
+
+/// Splits `text` into tokens, skipping whitespace and `--` comments.
+pub fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < chars.len() {
+        let c = chars[position];
+        if c.is_whitespace() {
+            position += 1;
+        }
+        else if c == '-' && chars.get(position + 1) == Some(&'-') {
+            while position < chars.len() && chars[position] != '\n' { position += 1; }
+        }
+        else if c.is_alphabetic() || c == '_' {
+            let start = position;
+            while position < chars.len() && (chars[position].is_alphanumeric() || chars[position] == '_') { position += 1; }
+            tokens.push(Token::Word(chars[start..position].iter().collect()));
+        }
+        else if c.is_ascii_digit() {
+            let start = position;
+            while position < chars.len() && chars[position].is_ascii_digit() { position += 1; }
+            if chars.get(position) == Some(&'.') && chars.get(position + 1).map_or(false, |c| c.is_ascii_digit()) {
+                position += 1;
+                while position < chars.len() && chars[position].is_ascii_digit() { position += 1; }
+            }
+            tokens.push(Token::Number(chars[start..position].iter().collect()));
+        }
+        else if c == '\'' || c == '"' {
+            // Quotes within quoted text are written twice.
+            let mut quoted = String::new();
+            position += 1;
+            loop {
+                match chars.get(position) {
+                    Some(&q) if q == c && chars.get(position + 1) == Some(&c) => { quoted.push(c); position += 2; },
+                    Some(&q) if q == c => { position += 1; break; },
+                    Some(&other) => { quoted.push(other); position += 1; },
+                    None => return Err(format!("Unterminated quotation: {}{}", c, quoted)),
+                }
+            }
+            tokens.push(if c == '\'' { Token::Text(quoted) } else { Token::Quoted(quoted) });
+        }
+        else {
+            let rest = chars[position..].iter().take(2).collect::<String>();
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                Some(symbol) => {
+                    tokens.push(Token::Symbol(*symbol));
+                    position += symbol.len();
+                },
+                None => return Err(format!("Unexpected character: {:?}", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
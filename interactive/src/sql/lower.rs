@@ -0,0 +1,394 @@
+//! Lowering parsed queries to plans.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Expression, Predicate, Aggregation, BinaryOp, Evaluable};
+use schema::Schema;
+use namespace::qualify;
+
+use super::FromLiteral;
+use super::parse::{Query, SetExpr, Select, Item, Relation, Expr, AggregateKind};
+
+/// A column visible to expressions, by its qualifier and name, and its index in tuples.
+///
+/// The columns of a join condition are equal in its results, which hold only one of
+/// them; the column of each relation remains visible, at the index of that value.
+#[derive(Clone, Debug)]
+struct Column {
+    qualifier: Option<String>,
+    name: String,
+    index: usize,
+}
+
+/// The columns visible to the expressions of a selection, and the arity of its tuples.
+#[derive(Clone, Debug)]
+struct Scope {
+    columns: Vec<Column>,
+    arity: usize,
+}
+
+impl Scope {
+    /// The columns of `names`, qualified by `qualifier`.
+    fn new(qualifier: &str, names: &[String]) -> Self {
+        let columns =
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| Column { qualifier: Some(qualifier.to_string()), name: name.clone(), index })
+            .collect();
+        Scope { columns, arity: names.len() }
+    }
+    /// The index of the value of the column, which must be visible and unambiguous.
+    fn resolve(&self, qualifier: &Option<String>, name: &str) -> Result<usize, String> {
+        let mut indices =
+        self.columns
+            .iter()
+            .filter(|column| column.name == name && (qualifier.is_none() || column.qualifier == *qualifier))
+            .map(|column| column.index)
+            .collect::<Vec<_>>();
+        indices.sort();
+        indices.dedup();
+        let written = match qualifier {
+            Some(qualifier) => format!("{}.{}", qualifier, name),
+            None => name.to_string(),
+        };
+        match indices.len() {
+            1 => Ok(indices[0]),
+            0 => Err(format!("Column not found: {}", written)),
+            _ => Err(format!("Column is ambiguous: {}", written)),
+        }
+    }
+}
+
+/// Lowers queries to plans, resolving the names of tables and columns.
+pub struct Lowerer<'a> {
+    schemas: &'a HashMap<String, Schema>,
+    namespace: Option<&'a str>,
+    /// Common table expressions in scope, and the names of their columns.
+    bound: Vec<(String, Vec<String>)>,
+}
+
+impl<'a> Lowerer<'a> {
+
+    /// Creates a lowerer reading tables with `schemas`, named within `namespace` if any.
+    pub fn new(schemas: &'a HashMap<String, Schema>, namespace: Option<&'a str>) -> Self {
+        Lowerer { schemas, namespace, bound: Vec::new() }
+    }
+
+    /// The plan of a query, and the names of its columns.
+    pub fn query<V: Data+Hash+Evaluable+FromLiteral>(&mut self, query: &Query) -> Result<(Plan<V>, Vec<String>), String> {
+        let depth = self.bound.len();
+        let lowered = self.ctes(query);
+        self.bound.truncate(depth);
+        lowered
+    }
+
+    /// The plan of a query, whose common table expressions are left bound.
+    fn ctes<V: Data+Hash+Evaluable+FromLiteral>(&mut self, query: &Query) -> Result<(Plan<V>, Vec<String>), String> {
+        let mut values = Vec::new();
+        for cte in query.ctes.iter() {
+            if query.recursive && cte.query.references(&cte.name) {
+                // The columns of a recursive definition are those of the selection it starts with.
+                let base = cte.query.leftmost();
+                if base.references(&cte.name) {
+                    return Err(format!("Recursive {} must start with a selection not reading it", cte.name));
+                }
+                let names = if cte.columns.is_empty() { self.select::<V>(base)?.1 } else { cte.columns.clone() };
+                self.bound.push((cte.name.clone(), names.clone()));
+                let (definition, defined) = self.set_expr::<V>(&cte.query)?;
+                if defined.len() != names.len() {
+                    return Err(format!("{} has {} columns, rather than {}", cte.name, defined.len(), names.len()));
+                }
+                values.push((cte.name.clone(), Plan::source(&cte.name).iterate(vec![(cte.name.clone(), definition)])));
+            }
+            else {
+                let (value, mut names) = self.set_expr::<V>(&cte.query)?;
+                if !cte.columns.is_empty() {
+                    if cte.columns.len() != names.len() {
+                        return Err(format!("{} has {} columns, rather than {}", cte.name, names.len(), cte.columns.len()));
+                    }
+                    names = cte.columns.clone();
+                }
+                self.bound.push((cte.name.clone(), names));
+                values.push((cte.name.clone(), value));
+            }
+        }
+        let (mut plan, names) = self.set_expr::<V>(&query.body)?;
+        for (name, value) in values.into_iter().rev() {
+            plan = plan.bind(&name, value);
+        }
+        Ok((plan, names))
+    }
+
+    fn set_expr<V: Data+Hash+Evaluable+FromLiteral>(&mut self, query: &SetExpr) -> Result<(Plan<V>, Vec<String>), String> {
+        match query {
+            SetExpr::Select(select) => self.select(select),
+            SetExpr::Union(query1, query2, all) => {
+                let (plan1, names) = self.set_expr::<V>(query1)?;
+                let (plan2, names2) = self.set_expr::<V>(query2)?;
+                if names.len() != names2.len() {
+                    return Err(format!("UNION of {} and {} columns", names.len(), names2.len()));
+                }
+                let plan = if *all { Plan::concat(vec![plan1, plan2]) } else { Plan::union(vec![plan1, plan2]) };
+                Ok((plan, names))
+            },
+        }
+    }
+
+    fn select<V: Data+Hash+Evaluable+FromLiteral>(&mut self, select: &Select) -> Result<(Plan<V>, Vec<String>), String> {
+
+        let mut relations = select.from.iter();
+        let (mut plan, mut scope) = match relations.next() {
+            Some((relation, _on)) => self.relation::<V>(relation)?,
+            None => return Err("SELECT without FROM is not supported".to_string()),
+        };
+
+        // Equalities between each relation and those before it become keys of joins; those
+        // of `ON` conditions, or of the `WHERE` condition for relations without them.
+        let mut conditions = select.selection.as_ref().map(|selection| selection.conjuncts()).unwrap_or_default();
+        for (relation, on) in relations {
+            let (other, other_scope) = self.relation::<V>(relation)?;
+            let mut joined = on.as_ref().map(|on| on.conjuncts()).unwrap_or_default();
+            let mut keys = Vec::new();
+            {
+                let candidates = if on.is_some() { &mut joined } else { &mut conditions };
+                candidates.retain(|condition| match key(condition, &scope, &other_scope) {
+                    Some(pair) => { keys.push(pair); false },
+                    None => true,
+                });
+            }
+            scope = join_scope(&scope, &other_scope, &keys);
+            plan = self.filter(plan.join(other, keys), &joined, &scope)?;
+        }
+        plan = self.filter(plan, &conditions, &scope)?;
+
+        let grouped =
+        !select.group_by.is_empty() ||
+        select.having.is_some() ||
+        select.items.iter().any(|item| match item { Item::Expr(expr, _alias) => expr.has_aggregate(), Item::Wildcard => false });
+
+        let (plan, expressions, names) =
+        if grouped { self.grouped(plan, &scope, select)? }
+        else {
+            let mut expressions = Vec::new();
+            let mut names = Vec::new();
+            for item in select.items.iter() {
+                match item {
+                    Item::Wildcard => {
+                        for column in scope.columns.iter() {
+                            expressions.push(Expression::Column(column.index));
+                            names.push(column.name.clone());
+                        }
+                    },
+                    Item::Expr(expr, alias) => {
+                        expressions.push(self.expression(expr, &scope)?);
+                        names.push(name(expr, alias));
+                    },
+                }
+            }
+            (plan, expressions, names)
+        };
+
+        let plan = plan.map(expressions);
+        Ok((if select.distinct { plan.distinct() } else { plan }, names))
+    }
+
+    /// Groups and aggregates the results of `plan`, and returns the expressions and names of the
+    /// selected columns of the groups.
+    fn grouped<V: Data+Hash+Evaluable+FromLiteral>(&self, plan: Plan<V>, scope: &Scope, select: &Select) -> Result<(Plan<V>, Vec<Expression<V>>, Vec<String>), String> {
+
+        let groups = select.group_by.iter().map(|expr| self.expression(expr, scope)).collect::<Result<Vec<_>, _>>()?;
+        let mut aggregates = Vec::new();
+        for item in select.items.iter() {
+            match item {
+                Item::Expr(expr, _alias) => self.aggregates(expr, scope, &mut aggregates)?,
+                Item::Wildcard => return Err("SELECT * of groups is not supported".to_string()),
+            }
+        }
+        if let Some(having) = select.having.as_ref() {
+            self.aggregates(having, scope, &mut aggregates)?;
+        }
+
+        // Computes the grouped values and the aggregated arguments, and then aggregates them.
+        let mut computed = groups.clone();
+        let mut aggregations = Vec::new();
+        for (kind, argument) in aggregates.iter() {
+            let index = computed.len();
+            if let Some(argument) = argument { computed.push(argument.clone()); }
+            aggregations.push(match kind {
+                AggregateKind::Count => Aggregation::Count,
+                AggregateKind::Sum => Aggregation::Sum(index),
+                AggregateKind::Min => Aggregation::Min(index),
+                AggregateKind::Max => Aggregation::Max(index),
+            });
+        }
+        let mut plan = plan.map(computed).aggregate((0..groups.len()).collect(), aggregations);
+
+        if let Some(having) = select.having.as_ref() {
+            plan = plan.filter(Predicate::Holds(self.of_group(having, scope, &groups, &aggregates)?));
+        }
+
+        let mut expressions = Vec::new();
+        let mut names = Vec::new();
+        for item in select.items.iter() {
+            if let Item::Expr(expr, alias) = item {
+                expressions.push(self.of_group(expr, scope, &groups, &aggregates)?);
+                names.push(name(expr, alias));
+            }
+        }
+        Ok((plan, expressions, names))
+    }
+
+    /// Appends the aggregates of `expr` that are not in `aggregates`, with their lowered arguments.
+    fn aggregates<V: Data+Evaluable+FromLiteral>(&self, expr: &Expr, scope: &Scope, aggregates: &mut Vec<(AggregateKind, Option<Expression<V>>)>) -> Result<(), String> {
+        match expr {
+            Expr::Aggregate(kind, argument) => {
+                let argument = match argument {
+                    Some(argument) => Some(self.expression(argument, scope)?),
+                    None => None,
+                };
+                let aggregate = (*kind, argument);
+                if !aggregates.contains(&aggregate) { aggregates.push(aggregate); }
+                Ok(())
+            },
+            Expr::Column(_, _) | Expr::Literal(_) => Ok(()),
+            Expr::Binary(_, expr1, expr2) => { self.aggregates(expr1, scope, aggregates)?; self.aggregates(expr2, scope, aggregates) },
+            Expr::Unary(_, expr) => self.aggregates(expr, scope, aggregates),
+        }
+    }
+
+    /// Lowers `expr` to an expression of the grouped values followed by the aggregates of a group.
+    fn of_group<V: Data+Evaluable+FromLiteral>(&self, expr: &Expr, scope: &Scope, groups: &[Expression<V>], aggregates: &[(AggregateKind, Option<Expression<V>>)]) -> Result<Expression<V>, String> {
+        if !expr.has_aggregate() {
+            if let Ok(lowered) = self.expression(expr, scope) {
+                if let Some(index) = groups.iter().position(|group| *group == lowered) {
+                    return Ok(Expression::Column(index));
+                }
+            }
+        }
+        match expr {
+            Expr::Aggregate(kind, argument) => {
+                let argument = match argument {
+                    Some(argument) => Some(self.expression(argument, scope)?),
+                    None => None,
+                };
+                let aggregate = (*kind, argument);
+                let index = aggregates.iter().position(|other| *other == aggregate).expect("aggregates are collected before lowering");
+                Ok(Expression::Column(groups.len() + index))
+            },
+            Expr::Literal(literal) => Ok(Expression::Constant(V::from_literal(literal)?)),
+            Expr::Binary(op, expr1, expr2) => Ok(Expression::binary(*op, self.of_group(expr1, scope, groups, aggregates)?, self.of_group(expr2, scope, groups, aggregates)?)),
+            Expr::Unary(op, expr) => Ok(Expression::unary(*op, self.of_group(expr, scope, groups, aggregates)?)),
+            Expr::Column(qualifier, column) => {
+                scope.resolve(qualifier, column)?;
+                Err(format!("Column {} is neither grouped nor aggregated", column))
+            },
+        }
+    }
+
+    /// The plan of a relation, and the columns it makes visible.
+    fn relation<V: Data+Hash+Evaluable+FromLiteral>(&mut self, relation: &Relation) -> Result<(Plan<V>, Scope), String> {
+        match relation {
+            Relation::Table(name, alias) => {
+                let qualifier = alias.as_ref().unwrap_or(name);
+                if let Some((_, names)) = self.bound.iter().rev().find(|(bound, _)| bound == name) {
+                    return Ok((Plan::source(name), Scope::new(qualifier, names)));
+                }
+                let qualified = match self.namespace {
+                    Some(namespace) => qualify(namespace, name),
+                    None => name.clone(),
+                };
+                match self.schemas.get(&qualified) {
+                    Some(schema) => {
+                        let names = schema.columns.iter().map(|(name, _type)| name.clone()).collect::<Vec<_>>();
+                        Ok((Plan::source(&qualified), Scope::new(qualifier, &names)))
+                    },
+                    None => Err(format!("Table has no schema: {}", name)),
+                }
+            },
+            Relation::Subquery(query, alias) => {
+                let (plan, names) = self.set_expr::<V>(query)?;
+                Ok((plan, Scope::new(alias, &names)))
+            },
+        }
+    }
+
+    /// Filters `plan` by the conjunction of `conditions`, if there are any.
+    fn filter<V: Data+Hash+Evaluable+FromLiteral>(&self, plan: Plan<V>, conditions: &[&Expr], scope: &Scope) -> Result<Plan<V>, String> {
+        let mut conjunction = None;
+        for condition in conditions.iter() {
+            let lowered = self.expression(condition, scope)?;
+            conjunction = Some(match conjunction {
+                Some(conjunction) => Expression::binary(BinaryOp::And, conjunction, lowered),
+                None => lowered,
+            });
+        }
+        Ok(match conjunction {
+            Some(conjunction) => plan.filter(Predicate::Holds(conjunction)),
+            None => plan,
+        })
+    }
+
+    /// Lowers an expression without aggregates.
+    fn expression<V: Data+Evaluable+FromLiteral>(&self, expr: &Expr, scope: &Scope) -> Result<Expression<V>, String> {
+        match expr {
+            Expr::Column(qualifier, name) => Ok(Expression::Column(scope.resolve(qualifier, name)?)),
+            Expr::Literal(literal) => Ok(Expression::Constant(V::from_literal(literal)?)),
+            Expr::Binary(op, expr1, expr2) => Ok(Expression::binary(*op, self.expression(expr1, scope)?, self.expression(expr2, scope)?)),
+            Expr::Unary(op, expr) => Ok(Expression::unary(*op, self.expression(expr, scope)?)),
+            Expr::Aggregate(_, _) => Err("Aggregates are allowed only in the columns and HAVING condition of a selection".to_string()),
+        }
+    }
+}
+
+/// The name of a selected column: its alias, the name of the column it selects, or the empty string.
+fn name(expr: &Expr, alias: &Option<String>) -> String {
+    match (alias, expr) {
+        (Some(alias), _) => alias.clone(),
+        (None, Expr::Column(_, name)) => name.clone(),
+        (None, _) => String::new(),
+    }
+}
+
+/// The indices of the columns a condition equates, if it equates a column of each of two scopes.
+fn key(condition: &Expr, scope1: &Scope, scope2: &Scope) -> Option<(usize, usize)> {
+    if let Expr::Binary(BinaryOp::Equal, expr1, expr2) = condition {
+        if let (Expr::Column(qualifier1, name1), Expr::Column(qualifier2, name2)) = (&**expr1, &**expr2) {
+            let only = |scope: &Scope, other: &Scope, qualifier: &Option<String>, name: &str| {
+                scope.resolve(qualifier, name).ok().filter(|_| other.resolve(qualifier, name).is_err())
+            };
+            if let (Some(index1), Some(index2)) = (only(scope1, scope2, qualifier1, name1), only(scope2, scope1, qualifier2, name2)) {
+                return Some((index1, index2));
+            }
+            if let (Some(index1), Some(index2)) = (only(scope1, scope2, qualifier2, name2), only(scope2, scope1, qualifier1, name1)) {
+                return Some((index1, index2));
+            }
+        }
+    }
+    None
+}
+
+/// The columns visible in the results of joining two scopes by `keys`.
+///
+/// Results hold the values of the keys, then the other values of the first scope, then the
+/// other values of the second scope.
+fn join_scope(scope1: &Scope, scope2: &Scope, keys: &[(usize, usize)]) -> Scope {
+    let rest1 = (0..scope1.arity).filter(|index| !keys.iter().any(|key| key.0 == *index)).collect::<Vec<_>>();
+    let rest2 = (0..scope2.arity).filter(|index| !keys.iter().any(|key| key.1 == *index)).collect::<Vec<_>>();
+    let index1 = |index: usize| match keys.iter().position(|key| key.0 == index) {
+        Some(position) => position,
+        None => keys.len() + rest1.iter().position(|rest| *rest == index).expect("unkeyed columns are retained"),
+    };
+    let index2 = |index: usize| match keys.iter().position(|key| key.1 == index) {
+        Some(position) => position,
+        None => keys.len() + rest1.len() + rest2.iter().position(|rest| *rest == index).expect("unkeyed columns are retained"),
+    };
+    let columns =
+    scope1.columns.iter().map(|column| Column { index: index1(column.index), ..column.clone() })
+        .chain(scope2.columns.iter().map(|column| Column { index: index2(column.index), ..column.clone() }))
+        .collect();
+    Scope { columns, arity: keys.len() + rest1.len() + rest2.len() }
+}
@@ -0,0 +1,68 @@
+//! A SQL frontend, compiling queries to plans.
+//!
+//! Queries are written in a subset of SQL, of selections from tables joined by
+//! `JOIN ... ON` or listed in `FROM`, filtered by `WHERE`, and grouped by
+//! `GROUP BY` with `COUNT(*)`, `SUM`, `MIN`, and `MAX` aggregates, filtered by
+//! `HAVING`. Selections combine by `UNION` and `UNION ALL`, and may be named by
+//! common table expressions in `WITH`, which `WITH RECURSIVE` allows to read
+//! themselves. For example, with tables `edges(src, dst)` and `roots(node)`,
+//!
+//! ```ignore
+//! WITH RECURSIVE reach(node) AS (
+//!     SELECT node FROM roots
+//!     UNION
+//!     SELECT edges.dst FROM reach JOIN edges ON reach.node = edges.src
+//! )
+//! SELECT node, COUNT(*) FROM reach JOIN edges ON node = src GROUP BY node
+//! ```
+//!
+//! Tables are resolved by the schema registry, whose column names the query uses.
+//! Keywords are written in any case, while names of tables and columns must match
+//! their declarations; quoting with double quotes allows names that are keywords.
+//! Equalities between the columns of the relations a join combines become the keys by
+//! which it is arranged, and other conditions filter its results. Collections are
+//! unordered, so `ORDER BY` and `LIMIT` are not supported, and recursive definitions
+//! produce sets, as `Iterate` does, whether they use `UNION` or `UNION ALL`.
+
+mod lex;
+mod parse;
+mod lower;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use differential_dataflow::Data;
+
+use plan::{Plan, Evaluable};
+use schema::Schema;
+
+/// A literal value, as written in a query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Literal {
+    /// A number, as written, with a leading `-` if it is negative.
+    Number(String),
+    /// A string, without its quotes.
+    String(String),
+    /// A boolean.
+    Bool(bool),
+    /// The null value.
+    Null,
+}
+
+/// Values that literals of queries can denote.
+pub trait FromLiteral: Sized {
+    /// The value of `literal`, or the reason it has none.
+    fn from_literal(literal: &Literal) -> Result<Self, String>;
+}
+
+/// Compiles the query `text` to a plan, and returns it with the names of its columns.
+///
+/// The names of tables are qualified by `namespace`, if any, other than those of common
+/// table expressions, and resolved by `schemas`.
+pub fn compile<V>(text: &str, schemas: &HashMap<String, Schema>, namespace: Option<&str>) -> Result<(Plan<V>, Vec<String>), String>
+where
+    V: Data+Hash+Evaluable+FromLiteral,
+{
+    let query = parse::Parser::new(lex::tokenize(text)?).statement()?;
+    lower::Lowerer::new(schemas, namespace).query(&query)
+}
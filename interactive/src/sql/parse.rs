@@ -0,0 +1,501 @@
+//! Parsing SQL tokens into queries.
+
+use plan::{BinaryOp, UnaryOp};
+
+use super::Literal;
+use super::lex::Token;
+
+/// A query, of common table expressions and the set expression using them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Query {
+    /// Indicates if common table expressions may refer to themselves.
+    pub recursive: bool,
+    /// Common table expressions, each of which may use those before it.
+    pub ctes: Vec<Cte>,
+    /// The result of the query.
+    pub body: SetExpr,
+}
+
+/// A common table expression, naming the result of a set expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cte {
+    /// The name of the result.
+    pub name: String,
+    /// Names for the columns of the result, if any are given.
+    pub columns: Vec<String>,
+    /// The definition of the result.
+    pub query: SetExpr,
+}
+
+/// Selections, and unions of them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SetExpr {
+    /// A selection.
+    Select(Box<Select>),
+    /// The union of two set expressions, retaining duplicates if `true`.
+    Union(Box<SetExpr>, Box<SetExpr>, bool),
+}
+
+/// A selection from the product of some relations.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Select {
+    /// Indicates if duplicate results are removed.
+    pub distinct: bool,
+    /// The selected columns.
+    pub items: Vec<Item>,
+    /// The relations, each with the condition joining it to those before it, if any.
+    pub from: Vec<(Relation, Option<Expr>)>,
+    /// The condition results must satisfy, if any.
+    pub selection: Option<Expr>,
+    /// The expressions by which results are grouped.
+    pub group_by: Vec<Expr>,
+    /// The condition groups must satisfy, if any.
+    pub having: Option<Expr>,
+}
+
+/// A selected column.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Item {
+    /// All columns of the relations.
+    Wildcard,
+    /// An expression, and its alias if any.
+    Expr(Expr, Option<String>),
+}
+
+/// A relation read by a selection.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Relation {
+    /// A named table or common table expression, and its alias if any.
+    Table(String, Option<String>),
+    /// A set expression, and its alias.
+    Subquery(Box<SetExpr>, String),
+}
+
+/// Aggregates of groups.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AggregateKind {
+    /// The number of rows.
+    Count,
+    /// The sum of an expression.
+    Sum,
+    /// The least value of an expression.
+    Min,
+    /// The greatest value of an expression.
+    Max,
+}
+
+/// A scalar expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    /// A column, by its optional qualifier and name.
+    Column(Option<String>, String),
+    /// A literal value.
+    Literal(Literal),
+    /// An operator applied to two expressions.
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// An operator applied to one expression.
+    Unary(UnaryOp, Box<Expr>),
+    /// An aggregate of an expression, which is absent for `COUNT(*)`.
+    Aggregate(AggregateKind, Option<Box<Expr>>),
+}
+
+impl Expr {
+    /// Indicates if the expression contains an aggregate.
+    pub fn has_aggregate(&self) -> bool {
+        match self {
+            Expr::Aggregate(_, _) => true,
+            Expr::Column(_, _) | Expr::Literal(_) => false,
+            Expr::Binary(_, expr1, expr2) => expr1.has_aggregate() || expr2.has_aggregate(),
+            Expr::Unary(_, expr) => expr.has_aggregate(),
+        }
+    }
+    /// The expressions whose conjunction is the expression.
+    pub fn conjuncts(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Binary(BinaryOp::And, expr1, expr2) => {
+                let mut conjuncts = expr1.conjuncts();
+                conjuncts.extend(expr2.conjuncts());
+                conjuncts
+            },
+            _ => vec![self],
+        }
+    }
+}
+
+impl SetExpr {
+    /// Indicates if the set expression reads the relation `name`.
+    pub fn references(&self, name: &str) -> bool {
+        match self {
+            SetExpr::Select(select) => select.references(name),
+            SetExpr::Union(query1, query2, _all) => query1.references(name) || query2.references(name),
+        }
+    }
+    /// The first selection of the set expression.
+    pub fn leftmost(&self) -> &Select {
+        match self {
+            SetExpr::Select(select) => select,
+            SetExpr::Union(query1, _query2, _all) => query1.leftmost(),
+        }
+    }
+}
+
+impl Select {
+    /// Indicates if the selection reads the relation `name`.
+    pub fn references(&self, name: &str) -> bool {
+        self.from.iter().any(|(relation, _on)| match relation {
+            Relation::Table(table, _alias) => table == name,
+            Relation::Subquery(query, _alias) => query.references(name),
+        })
+    }
+}
+
+/// Words that cannot be used as unquoted identifiers.
+const RESERVED: &[&str] = &[
+    "ALL", "AND", "AS", "BY", "CROSS", "DISTINCT", "FALSE", "FROM", "GROUP", "HAVING", "INNER", "JOIN", "LIMIT",
+    "NOT", "NULL", "ON", "OR", "ORDER", "RECURSIVE", "SELECT", "TRUE", "UNION", "WHERE", "WITH",
+];
+
+/// A parser of a sequence of tokens.
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+
+    /// Creates a parser of `tokens`.
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser { tokens, position: 0 }
+    }
+
+    /// Parses a query, optionally followed by a semicolon, which must be all of the tokens.
+    pub fn statement(&mut self) -> Result<Query, String> {
+        let query = self.query()?;
+        if self.keyword("ORDER") || self.keyword("LIMIT") {
+            return Err("ORDER BY and LIMIT are not supported, as collections are unordered".to_string());
+        }
+        self.symbol(";");
+        match self.peek() {
+            None => Ok(query),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        let mut recursive = false;
+        let mut ctes = Vec::new();
+        if self.keyword("WITH") {
+            recursive = self.keyword("RECURSIVE");
+            loop {
+                let name = self.identifier()?;
+                let mut columns = Vec::new();
+                if self.symbol("(") {
+                    columns = self.identifiers()?;
+                    self.expect_symbol(")")?;
+                }
+                self.expect_keyword("AS")?;
+                self.expect_symbol("(")?;
+                let query = self.set_expr()?;
+                self.expect_symbol(")")?;
+                ctes.push(Cte { name, columns, query });
+                if !self.symbol(",") { break; }
+            }
+        }
+        let body = self.set_expr()?;
+        Ok(Query { recursive, ctes, body })
+    }
+
+    fn set_expr(&mut self) -> Result<SetExpr, String> {
+        let mut query = self.set_term()?;
+        while self.keyword("UNION") {
+            let all = self.keyword("ALL");
+            let other = self.set_term()?;
+            query = SetExpr::Union(Box::new(query), Box::new(other), all);
+        }
+        Ok(query)
+    }
+
+    fn set_term(&mut self) -> Result<SetExpr, String> {
+        if self.symbol("(") {
+            let query = self.set_expr()?;
+            self.expect_symbol(")")?;
+            Ok(query)
+        }
+        else {
+            Ok(SetExpr::Select(Box::new(self.select()?)))
+        }
+    }
+
+    fn select(&mut self) -> Result<Select, String> {
+        self.expect_keyword("SELECT")?;
+        let distinct = self.keyword("DISTINCT");
+        if !distinct { self.keyword("ALL"); }
+
+        let mut items = Vec::new();
+        loop {
+            if self.symbol("*") {
+                items.push(Item::Wildcard);
+            }
+            else {
+                let expr = self.expr()?;
+                let alias = self.alias()?;
+                items.push(Item::Expr(expr, alias));
+            }
+            if !self.symbol(",") { break; }
+        }
+
+        let mut from = Vec::new();
+        if self.keyword("FROM") {
+            from.push((self.relation()?, None));
+            loop {
+                if self.symbol(",") {
+                    from.push((self.relation()?, None));
+                }
+                else if self.keyword("CROSS") {
+                    self.expect_keyword("JOIN")?;
+                    from.push((self.relation()?, None));
+                }
+                else if self.keyword("INNER") {
+                    self.expect_keyword("JOIN")?;
+                    from.push(self.join()?);
+                }
+                else if self.keyword("JOIN") {
+                    from.push(self.join()?);
+                }
+                else { break; }
+            }
+        }
+
+        let selection = if self.keyword("WHERE") { Some(self.expr()?) } else { None };
+        let mut group_by = Vec::new();
+        if self.keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            loop {
+                group_by.push(self.expr()?);
+                if !self.symbol(",") { break; }
+            }
+        }
+        let having = if self.keyword("HAVING") { Some(self.expr()?) } else { None };
+
+        Ok(Select { distinct, items, from, selection, group_by, having })
+    }
+
+    /// A joined relation and its condition, whose `JOIN` has been read.
+    fn join(&mut self) -> Result<(Relation, Option<Expr>), String> {
+        let relation = self.relation()?;
+        self.expect_keyword("ON")?;
+        Ok((relation, Some(self.expr()?)))
+    }
+
+    fn relation(&mut self) -> Result<Relation, String> {
+        if self.symbol("(") {
+            let query = self.set_expr()?;
+            self.expect_symbol(")")?;
+            match self.alias()? {
+                Some(alias) => Ok(Relation::Subquery(Box::new(query), alias)),
+                None => Err("Subqueries in FROM must have aliases".to_string()),
+            }
+        }
+        else {
+            let name = self.identifier()?;
+            Ok(Relation::Table(name, self.alias()?))
+        }
+    }
+
+    /// An alias, introduced by `AS` or written directly.
+    fn alias(&mut self) -> Result<Option<String>, String> {
+        if self.keyword("AS") { return self.identifier().map(Some); }
+        match self.peek() {
+            Some(Token::Word(word)) if !reserved(word) => self.identifier().map(Some),
+            Some(Token::Quoted(_)) => self.identifier().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.conjunction()?;
+        while self.keyword("OR") {
+            expr = Expr::Binary(BinaryOp::Or, Box::new(expr), Box::new(self.conjunction()?));
+        }
+        Ok(expr)
+    }
+
+    fn conjunction(&mut self) -> Result<Expr, String> {
+        let mut expr = self.negation()?;
+        while self.keyword("AND") {
+            expr = Expr::Binary(BinaryOp::And, Box::new(expr), Box::new(self.negation()?));
+        }
+        Ok(expr)
+    }
+
+    fn negation(&mut self) -> Result<Expr, String> {
+        if self.keyword("NOT") { Ok(Expr::Unary(UnaryOp::Not, Box::new(self.negation()?))) }
+        else { self.comparison() }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let expr = self.additive()?;
+        let op =
+        if self.symbol("=") { BinaryOp::Equal }
+        else if self.symbol("<>") || self.symbol("!=") { BinaryOp::NotEqual }
+        else if self.symbol("<=") { BinaryOp::LessEqual }
+        else if self.symbol(">=") { BinaryOp::GreaterEqual }
+        else if self.symbol("<") { BinaryOp::LessThan }
+        else if self.symbol(">") { BinaryOp::GreaterThan }
+        else { return Ok(expr); };
+        Ok(Expr::Binary(op, Box::new(expr), Box::new(self.additive()?)))
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut expr = self.multiplicative()?;
+        loop {
+            let op =
+            if self.symbol("+") { BinaryOp::Add }
+            else if self.symbol("-") { BinaryOp::Sub }
+            else if self.symbol("||") { BinaryOp::Concat }
+            else { return Ok(expr); };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        loop {
+            let op =
+            if self.symbol("*") { BinaryOp::Mul }
+            else if self.symbol("/") { BinaryOp::Div }
+            else if self.symbol("%") { BinaryOp::Rem }
+            else { return Ok(expr); };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.primary()?));
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        if self.symbol("(") {
+            let expr = self.expr()?;
+            self.expect_symbol(")")?;
+            return Ok(expr);
+        }
+        if self.symbol("-") {
+            return match self.next() {
+                Some(Token::Number(number)) => Ok(Expr::Literal(Literal::Number(format!("-{}", number)))),
+                _ => Err("Negation applies only to numbers".to_string()),
+            };
+        }
+        if self.keyword("TRUE") { return Ok(Expr::Literal(Literal::Bool(true))); }
+        if self.keyword("FALSE") { return Ok(Expr::Literal(Literal::Bool(false))); }
+        if self.keyword("NULL") { return Ok(Expr::Literal(Literal::Null)); }
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => { self.position += 1; Ok(Expr::Literal(Literal::Number(number))) },
+            Some(Token::Text(text)) => { self.position += 1; Ok(Expr::Literal(Literal::String(text))) },
+            Some(Token::Word(_)) | Some(Token::Quoted(_)) => {
+                let name = self.identifier()?;
+                if self.symbol("(") {
+                    self.function(&name)
+                }
+                else if self.symbol(".") {
+                    let column = self.identifier()?;
+                    Ok(Expr::Column(Some(name), column))
+                }
+                else {
+                    Ok(Expr::Column(None, name))
+                }
+            },
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of query".to_string()),
+        }
+    }
+
+    /// A call of the function `name`, whose opening parenthesis has been read.
+    fn function(&mut self, name: &str) -> Result<Expr, String> {
+        let kind = match &name.to_uppercase()[..] {
+            "COUNT" => Some(AggregateKind::Count),
+            "SUM" => Some(AggregateKind::Sum),
+            "MIN" => Some(AggregateKind::Min),
+            "MAX" => Some(AggregateKind::Max),
+            _ => None,
+        };
+        let expr = match kind {
+            Some(AggregateKind::Count) => {
+                if !self.symbol("*") {
+                    return Err("COUNT applies only to *, as nulls are otherwise not counted".to_string());
+                }
+                Expr::Aggregate(AggregateKind::Count, None)
+            },
+            Some(kind) => Expr::Aggregate(kind, Some(Box::new(self.expr()?))),
+            None => {
+                let op = match &name.to_uppercase()[..] {
+                    "LENGTH" => UnaryOp::Length,
+                    "UPPER" => UnaryOp::Upper,
+                    "LOWER" => UnaryOp::Lower,
+                    _ => return Err(format!("Unknown function: {}", name)),
+                };
+                Expr::Unary(op, Box::new(self.expr()?))
+            },
+        };
+        self.expect_symbol(")")?;
+        Ok(expr)
+    }
+
+    fn identifiers(&mut self) -> Result<Vec<String>, String> {
+        let mut identifiers = vec![self.identifier()?];
+        while self.symbol(",") {
+            identifiers.push(self.identifier()?);
+        }
+        Ok(identifiers)
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) => if reserved(&word) { Err(format!("Expected an identifier, found {}", word)) } else { Ok(word) },
+            Some(Token::Quoted(name)) => Ok(name),
+            Some(token) => Err(format!("Expected an identifier, found {:?}", token)),
+            None => Err("Expected an identifier, found the end of the query".to_string()),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Reads `keyword`, written in any case, if it is next.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Word(word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        };
+        if found { self.position += 1; }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.keyword(keyword) { Ok(()) }
+        else { Err(format!("Expected {}, found {:?}", keyword, self.peek())) }
+    }
+
+    /// Reads `symbol` if it is next.
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Symbol(found)) => *found == symbol,
+            _ => false,
+        };
+        if found { self.position += 1; }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) { Ok(()) }
+        else { Err(format!("Expected {}, found {:?}", symbol, self.peek())) }
+    }
+}
+
+/// Indicates if `word` is reserved, in any case.
+fn reserved(word: &str) -> bool {
+    RESERVED.iter().any(|reserved| word.eq_ignore_ascii_case(reserved))
+}
@@ -0,0 +1,99 @@
+//! A versioned encoding of commands and responses, for clients and servers.
+//!
+//! Clients send commands and servers return responses, as messages each holding
+//! the version of the encoding and a command or a response. A message can be
+//! encoded either with `bincode`, which is compact and suits clients written in
+//! Rust, or as JSON, which clients in other languages can produce with standard
+//! libraries. Servers reject messages of other versions rather than misinterpret
+//! them, as the encodings of plans and commands change as the system does. New
+//! commands are added after the existing ones, whose encodings then remain valid
+//! in the same version.
+//!
+//! In JSON, each message is one line, and enumerations are objects naming the variant.
+//! For example, a query publishing the first values of the tuples of `edges` is
+//!
+//! ```text
+//! {"version":1,"message":{"Query":{"rules":[{"name":"nodes","plan":{"Project":{"indices":[0],"plan":{"Source":"edges"}}}}]}}}
+//! ```
+//!
+//! and a `Value::Usize(3)` is `{"Usize":3}`. Times are durations, `{"secs":1,"nanos":0}`.
+
+use std::io::{BufRead, Read, Write, Result, Error, ErrorKind};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// The version of the encoding written by this module.
+pub const VERSION: u32 = 1;
+
+/// A message to write, borrowing its contents.
+#[derive(Serialize)]
+struct Outgoing<'a, M: 'a> {
+    version: u32,
+    message: &'a M,
+}
+
+/// A message to read, whose contents are decoded only if the version is supported.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// A message read, with its contents.
+#[derive(Deserialize)]
+struct Incoming<M> {
+    message: M,
+}
+
+fn invalid<E: ::std::fmt::Display>(error: E) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("wire encoding: {}", error))
+}
+
+fn check(version: u32) -> Result<()> {
+    if version == VERSION { Ok(()) }
+    else { Err(invalid(format!("unsupported version {} (expected {})", version, VERSION))) }
+}
+
+/// Writes `message`, a command or a response, to `writer` with `bincode`.
+pub fn write_bincode<M: Serialize, W: Write>(writer: W, message: &M) -> Result<()> {
+    ::bincode::serialize_into(writer, &Outgoing { version: VERSION, message }).map_err(invalid)
+}
+
+/// Reads a message written by `write_bincode` from `reader`.
+pub fn read_bincode<M: DeserializeOwned, R: Read>(mut reader: R) -> Result<M> {
+    // The version precedes the message, and is checked before the message is decoded.
+    let version: u32 = ::bincode::deserialize_from(&mut reader).map_err(invalid)?;
+    check(version)?;
+    ::bincode::deserialize_from(&mut reader).map_err(invalid)
+}
+
+/// Encodes `message` as a line of JSON, without the terminating newline.
+pub fn to_json<M: Serialize>(message: &M) -> Result<String> {
+    ::serde_json::to_string(&Outgoing { version: VERSION, message }).map_err(invalid)
+}
+
+/// Decodes a message from JSON produced by `to_json`.
+pub fn from_json<M: DeserializeOwned>(text: &str) -> Result<M> {
+    let version: Version = ::serde_json::from_str(text).map_err(invalid)?;
+    check(version.version)?;
+    let incoming: Incoming<M> = ::serde_json::from_str(text).map_err(invalid)?;
+    Ok(incoming.message)
+}
+
+/// Writes `message` to `writer` as a line of JSON.
+pub fn write_json<M: Serialize, W: Write>(mut writer: W, message: &M) -> Result<()> {
+    let text = to_json(message)?;
+    writeln!(writer, "{}", text)
+}
+
+/// Reads a line of JSON written by `write_json` from `reader`.
+///
+/// Returns `None` once `reader` is exhausted.
+pub fn read_json<M: DeserializeOwned, R: BufRead>(mut reader: R) -> Option<Result<M>> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => None,
+        Ok(_) => Some(from_json(line.trim_end())),
+        Err(error) => Some(Err(error)),
+    }
+}
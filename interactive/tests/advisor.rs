@@ -0,0 +1,49 @@
+extern crate interactive;
+
+use interactive::{Plan, TraceManager, Value};
+use interactive::advisor::{Advisor, Recommendation};
+
+#[test]
+fn recommend_by_cost() {
+
+    let traces = TraceManager::<Value>::new();
+    let mut advisor = Advisor::<Value>::new();
+
+    // Each source is arranged by its first column for each of two joins.
+    let join = Plan::source("edges").join(Plan::source("nodes"), vec![(0, 0)]);
+    advisor.observe(&join);
+    advisor.observe(&join);
+
+    let keyed = |name: &str| Recommendation::Keyed(Plan::source(name), vec![0]);
+
+    // With equal uses and no observed costs, neither source is preferred by benefit.
+    let recommendations = advisor.recommend(&traces);
+    assert_eq!(recommendations.len(), 2);
+    assert_eq!(recommendations[0], keyed("nodes"));
+
+    // Observing the cost of arranging `edges` makes it the more beneficial arrangement.
+    advisor.observe_cost(&Plan::source("edges"), 100);
+    let recommendations = advisor.recommend(&traces);
+    assert_eq!(recommendations, vec![keyed("edges"), keyed("nodes")]);
+}
+
+#[test]
+fn recommend_aggregation() {
+
+    use interactive::plan::{Aggregate, Aggregation};
+
+    let traces = TraceManager::<Value>::new();
+    let mut advisor = Advisor::<Value>::new();
+
+    let aggregate = Plan::Aggregate(Aggregate {
+        keys: vec![0],
+        aggregates: vec![Aggregation::Count],
+        plan: Box::new(Plan::source("edges")),
+    });
+
+    advisor.observe(&aggregate);
+    assert!(advisor.recommend(&traces).is_empty());
+
+    advisor.observe(&aggregate);
+    assert_eq!(advisor.recommend(&traces), vec![Recommendation::Aggregate(aggregate)]);
+}
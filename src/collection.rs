@@ -230,9 +230,48 @@ impl<G: Scope, D: Data, R: Monoid> Collection<G, D, R> where G::Timestamp: Data
           <R2 as Mul<R>>::Output: Data+Monoid,
           I: IntoIterator<Item=(D2,R2)>,
           L: Fn(D)->I+'static,
+    {
+        self.explode_core(move |x, d| logic(x).into_iter().map(move |(x,d2)| (x, d2 * d.clone())))
+    }
+
+    /// Replaces each record and its difference with others, with a new difference type.
+    ///
+    /// Unlike `explode`, which multiplies the produced differences by the input difference,
+    /// this method presents the input difference to `logic`, which is then responsible for
+    /// producing the output differences. The output difference type need only be a `Monoid`,
+    /// and need not support multiplication nor negation. This allows the construction of, for
+    /// example, collections with min-plus weights from collections with integer counts, where
+    /// `logic` is responsible for rejecting updates (e.g. retractions) that have no meaning in
+    /// the output difference type.
+    ///
+    /// #Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let nums = scope.new_collection_from(0 .. 10).1;
+    ///         let x1 = nums.flat_map(|x| 0 .. x);
+    ///         let x2 = nums.map(|x| (x, 9 - x))
+    ///                      .explode_core(|(x,y), diff: isize| Some((x, (y as isize) * diff)));
+    ///
+    ///         x1.assert_eq(&x2);
+    ///     });
+    /// }
+    /// ```
+    pub fn explode_core<D2, R2, I, L>(&self, logic: L) -> Collection<G, D2, R2>
+    where D2: Data,
+          R2: Monoid,
+          I: IntoIterator<Item=(D2,R2)>,
+          L: Fn(D, R)->I+'static,
     {
         self.inner
-            .flat_map(move |(x, t, d)| logic(x).into_iter().map(move |(x,d2)| (x, t.clone(), d2 * d.clone())))
+            .flat_map(move |(x, t, d)| logic(x, d).into_iter().map(move |(x,d2)| (x, t.clone(), d2)))
             .as_collection()
     }
 
@@ -317,6 +356,107 @@ impl<G: Scope, D: Data, R: Monoid> Collection<G, D, R> where G::Timestamp: Data
             .as_collection()
     }
 
+    /// Applies `logic` to the collection in a nested scope, and returns the result to this scope.
+    ///
+    /// The nested scope has timestamp `T`, and `logic` receives the collection already brought into
+    /// the nested scope. The collection `logic` returns is brought out of the nested scope.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let data = scope.new_collection_from(1 .. 10).1;
+    ///
+    ///         let result = data.scoped::<usize,_,_,_>("Nested", |inner| inner.map(|x| x + 1));
+    ///
+    ///         data.map(|x| x + 1).assert_eq(&result);
+    ///     });
+    /// }
+    /// ```
+    pub fn scoped<T, D2, R2, F>(&self, name: &str, logic: F) -> Collection<G, D2, R2>
+    where
+        T: Timestamp+Refines<G::Timestamp>,
+        D2: Data,
+        R2: Monoid,
+        for<'a> F: FnOnce(&Collection<Child<'a, G, T>, D, R>)->Collection<Child<'a, G, T>, D2, R2>,
+    {
+        self.inner.scope().scoped::<T,_,_>(name, |child| logic(&self.enter(child)).leave())
+    }
+
+    /// Applies `logic` to the collection in a region, and returns the result to this scope.
+    ///
+    /// A region is a nested scope with the same timestamp as this scope. Regions group operators
+    /// together, for example to present them as one operator in logging, without changing the
+    /// computation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let data = scope.new_collection_from(1 .. 10).1;
+    ///
+    ///         let result = data.within_region("Increment", |inner| inner.map(|x| x + 1));
+    ///
+    ///         data.map(|x| x + 1).assert_eq(&result);
+    ///     });
+    /// }
+    /// ```
+    pub fn within_region<D2, R2, F>(&self, name: &str, logic: F) -> Collection<G, D2, R2>
+    where
+        D2: Data,
+        R2: Monoid,
+        for<'a> F: FnOnce(&Collection<Child<'a, G, G::Timestamp>, D, R>)->Collection<Child<'a, G, G::Timestamp>, D2, R2>,
+    {
+        self.scoped::<G::Timestamp,_,_,_>(name, logic)
+    }
+
+    /// Applies `logic` to this and another collection in a region, and returns the result to this scope.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let odds = scope.new_collection_from(vec![1, 3, 5]).1;
+    ///         let evens = scope.new_collection_from(vec![2, 4]).1;
+    ///
+    ///         let result = odds.within_region_with("Union", &evens, |odds, evens| odds.concat(evens));
+    ///
+    ///         odds.concat(&evens).assert_eq(&result);
+    ///     });
+    /// }
+    /// ```
+    pub fn within_region_with<D2, R2, D3, R3, F>(&self, name: &str, other: &Collection<G, D2, R2>, logic: F) -> Collection<G, D3, R3>
+    where
+        D2: Data,
+        R2: Monoid,
+        D3: Data,
+        R3: Monoid,
+        for<'a> F: FnOnce(&Collection<Child<'a, G, G::Timestamp>, D, R>, &Collection<Child<'a, G, G::Timestamp>, D2, R2>)->Collection<Child<'a, G, G::Timestamp>, D3, R3>,
+    {
+        self.inner.scope().scoped::<G::Timestamp,_,_>(name, |child| logic(&self.enter(child), &other.enter(child)).leave())
+    }
+
     /// Delays each difference by a supplied function.
     ///
     /// It is assumed that `func` only advances timestamps; this is not verified, and things may go horribly
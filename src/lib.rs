@@ -87,6 +87,8 @@ pub trait Data : timely::ExchangeData + Ord + Debug { }
 impl<T: timely::ExchangeData + Ord + Debug> Data for T { }
 
 extern crate fnv;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 extern crate timely;
 extern crate timely_sort;
 
@@ -96,6 +98,10 @@ extern crate abomonation;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
+#[cfg(feature = "bincode")]
+extern crate bincode;
+#[cfg(feature = "arrow")]
+extern crate arrow;
 
 pub mod hashable;
 pub mod operators;
@@ -106,3 +112,4 @@ pub mod input;
 pub mod difference;
 pub mod collection;
 pub mod logging;
+pub mod sink;
@@ -1,4 +1,18 @@
 //! Loggers and logging events for differential dataflow.
+//!
+//! The number of updates an arrangement holds follows from its events alone: the lengths
+//! of its batches, plus for each completed merge the length of its result less those of
+//! its inputs, less the lengths of the batches it drops. Its number of readers follows
+//! from the sum of the differences of its trace sharing events. Its bytes are estimated as
+//! its number of updates times the size of each update, reported by its layout event, and
+//! `arrangement_sizes` maintains both for each arrangement.
+
+use std::time::Duration;
+
+use timely::dataflow::{Scope, Stream};
+use timely::logging::TimelyEvent;
+
+use Collection;
 
 /// Logger for differential dataflow events.
 pub type Logger = ::timely::logging::Logger<DifferentialEvent>;
@@ -12,6 +26,12 @@ pub enum DifferentialEvent {
     Merge(MergeEvent),
     /// A merge failed to complete in time.
     MergeShortfall(MergeShortfall),
+    /// Batches dropped by a trace.
+    Drop(DropEvent),
+    /// A change in the number of handles to a trace.
+    TraceShare(TraceShare),
+    /// The size of the updates of a trace.
+    Layout(LayoutEvent),
 }
 
 /// Either the start or end of a merge event.
@@ -54,3 +74,105 @@ pub struct MergeShortfall {
 }
 
 impl From<MergeShortfall> for DifferentialEvent { fn from(e: MergeShortfall) -> Self { DifferentialEvent::MergeShortfall(e) } }
+
+/// A batch dropped by a trace, other than as the input of a merge.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct DropEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// The number of updates in the batch.
+    pub length: usize,
+}
+
+impl From<DropEvent> for DifferentialEvent { fn from(e: DropEvent) -> Self { DifferentialEvent::Drop(e) } }
+
+/// A change in the number of handles to the trace of an operator.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct TraceShare {
+    /// Operator identifier.
+    pub operator: usize,
+    /// The change in the number of handles.
+    pub diff: isize,
+}
+
+impl From<TraceShare> for DifferentialEvent { fn from(e: TraceShare) -> Self { DifferentialEvent::TraceShare(e) } }
+
+/// The size of each update of the trace of an operator, reported as the trace is created.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct LayoutEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// The bytes of a `(key, val, time, diff)` tuple.
+    pub update_bytes: usize,
+}
+
+impl From<LayoutEvent> for DifferentialEvent { fn from(e: LayoutEvent) -> Self { DifferentialEvent::Layout(e) } }
+
+/// Logs the layout of the trace of `operator`, whose updates have the types `K`, `V`, `T`, and `R`.
+pub(crate) fn log_layout<K, V, T, R>(logger: &Option<Logger>, operator: usize) {
+    if let Some(logger) = logger.as_ref() {
+        logger.log(LayoutEvent { operator, update_bytes: ::std::mem::size_of::<(K, V, T, R)>() });
+    }
+}
+
+/// Maintains the number of updates, and an estimate of their bytes, of each arrangement.
+///
+/// The arrangements are those whose operators `operates` describes, and whose events `events`
+/// reports, for example as replayed from the `timely` and `differential/arrange` loggers. The
+/// result holds `(operator, name, records, bytes)` for each arrangement with updates, where
+/// `operator` is the identifier of the operator and `name` the name it was created with, and
+/// sums the arrangements of the operator over all workers reporting events.
+pub fn arrangement_sizes<G>(
+    operates: &Stream<G, (Duration, usize, TimelyEvent)>,
+    events: &Stream<G, (Duration, usize, DifferentialEvent)>,
+) -> Collection<G, (usize, String, usize, usize), isize>
+where
+    G: Scope<Timestamp=Duration>,
+{
+    use timely::dataflow::operators::{FlatMap, Map};
+    use collection::AsCollection;
+    use difference::DiffPair;
+    use operators::{Consolidate, Count, Join, Threshold};
+
+    let names =
+    operates
+        .flat_map(|(time, _worker, event)| match event {
+            TimelyEvent::Operates(operates) => Some(((operates.id, operates.name), time, 1)),
+            _ => None,
+        })
+        .as_collection()
+        .distinct();
+
+    let layouts =
+    events
+        .flat_map(|(time, _worker, event)| match event {
+            DifferentialEvent::Layout(layout) => Some(((layout.operator, layout.update_bytes), time, 1)),
+            _ => None,
+        })
+        .as_collection()
+        .distinct();
+
+    // The updates of each arrangement change with its batches, completed merges, and dropped batches.
+    let records =
+    events
+        .flat_map(|(time, _worker, event)| match event {
+            DifferentialEvent::Batch(batch) => Some((batch.operator, time, batch.length as isize)),
+            DifferentialEvent::Merge(merge) => merge.complete.map(|length|
+                (merge.operator, time, length as isize - merge.length1 as isize - merge.length2 as isize)
+            ),
+            DifferentialEvent::Drop(dropped) => Some((dropped.operator, time, -(dropped.length as isize))),
+            _ => None,
+        })
+        .as_collection()
+        .consolidate();
+
+    records
+        .map(|operator| (operator, ()))
+        .join_map(&layouts, |operator, &(), update_bytes| (*operator, *update_bytes))
+        .join_map(&names, |operator, update_bytes, name| ((*operator, name.clone()), *update_bytes))
+        .explode(|(arrangement, update_bytes)| Some((arrangement, DiffPair::new(1, update_bytes as isize))))
+        .count()
+        .inner
+        .map(|(((operator, name), sizes), time, diff)| ((operator, name, sizes.element1 as usize, sizes.element2 as usize), time, diff))
+        .as_collection()
+}
@@ -17,10 +17,11 @@
 //! see ill-defined data at times for which the trace is not complete. (All current implementations
 //! commit only completed data to the trace).
 
+use std::any::Any;
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 use std::default::Default;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 
 use timely::dataflow::operators::{Enter, Map};
 use timely::order::{PartialOrder, TotalOrder};
@@ -125,6 +126,10 @@ where T: Lattice+Ord+Clone+'static, Tr: TraceReader<K,V,T,R> {
     queues: Weak<RefCell<(Vec<T>,Vec<TraceAgentQueueWriter<K,V,T,R,Tr>>)>>,
     advance: Vec<T>,
     through: Vec<T>,
+    /// The global identifier of the operator maintaining the trace.
+    operator: usize,
+    /// A logger for the creation and release of handles to the trace, if any.
+    logging: Option<::logging::Logger>,
 }
 
 impl<K, V, T, R, Tr> TraceReader<K, V, T, R> for TraceAgent<K, V, T, R, Tr>
@@ -151,6 +156,9 @@ where T: Lattice+Ord+Clone+'static, Tr: TraceReader<K,V,T,R> {
         self.trace.borrow_mut().trace.cursor_through(frontier)
     }
     fn map_batches<F: FnMut(&Self::Batch)>(&mut self, f: F) { self.trace.borrow_mut().trace.map_batches(f) }
+    fn compact(&mut self) { self.trace.borrow_mut().trace.compact() }
+    fn warm(&mut self, keys: Option<(&K, &K)>) { self.trace.borrow_mut().trace.warm(keys) }
+    fn cool(&mut self) { self.trace.borrow_mut().trace.cool() }
 }
 
 impl<K, V, T, R, Tr> TraceAgent<K, V, T, R, Tr>
@@ -158,6 +166,14 @@ where T: Timestamp+Lattice, Tr: TraceReader<K,V,T,R> {
 
     /// Creates a new agent from a trace reader.
     pub fn new(trace: Tr) -> (Self, TraceWriter<K,V,T,R,Tr>) where Tr: Trace<K,V,T,R>, Tr::Batch: Batch<K,V,T,R> {
+        Self::new_with_logging(trace, 0, None)
+    }
+
+    /// Creates a new agent from a trace reader, logging trace sharing events.
+    ///
+    /// The agent, and each of its clones, logs its creation and release as trace sharing events
+    /// of `operator`, which maintains the trace.
+    pub fn new_with_logging(trace: Tr, operator: usize, logging: Option<::logging::Logger>) -> (Self, TraceWriter<K,V,T,R,Tr>) where Tr: Trace<K,V,T,R>, Tr::Batch: Batch<K,V,T,R> {
 
         let trace = Rc::new(RefCell::new(TraceBox::new(trace)));
         let queues = Rc::new(RefCell::new((vec![Default::default()], Vec::new())));
@@ -168,8 +184,14 @@ where T: Timestamp+Lattice, Tr: TraceReader<K,V,T,R> {
             queues: Rc::downgrade(&queues),
             advance: trace.borrow().advance_frontiers.frontier().to_vec(),
             through: trace.borrow().through_frontiers.frontier().to_vec(),
+            operator,
+            logging,
         };
 
+        if let Some(logger) = reader.logging.as_ref() {
+            logger.log(::logging::TraceShare { operator, diff: 1 });
+        }
+
         let writer = TraceWriter {
             phantom: ::std::marker::PhantomData,
             trace: Rc::downgrade(&trace),
@@ -179,6 +201,11 @@ where T: Timestamp+Lattice, Tr: TraceReader<K,V,T,R> {
         (reader, writer)
     }
 
+    /// The global identifier of the operator maintaining the trace, as reported in its logged events.
+    pub fn operator(&self) -> usize {
+        self.operator
+    }
+
     /// Attaches a new shared queue to the trace.
     ///
     /// The queue will be immediately populated with existing historical batches from the trace, and until the reference
@@ -421,12 +448,18 @@ where T: Lattice+Ord+Clone+'static, Tr: TraceReader<K,V,T,R> {
         self.trace.borrow_mut().adjust_advance_frontier(&[], &self.advance[..]);
         self.trace.borrow_mut().adjust_through_frontier(&[], &self.through[..]);
 
+        if let Some(logger) = self.logging.as_ref() {
+            logger.log(::logging::TraceShare { operator: self.operator, diff: 1 });
+        }
+
         TraceAgent {
             phantom: ::std::marker::PhantomData,
             trace: self.trace.clone(),
             queues: self.queues.clone(),
             advance: self.advance.clone(),
             through: self.through.clone(),
+            operator: self.operator,
+            logging: self.logging.clone(),
         }
     }
 }
@@ -438,6 +471,10 @@ where T: Lattice+Ord+Clone+'static, Tr: TraceReader<K,V,T,R> {
         // decrement borrow counts to remove all holds
         self.trace.borrow_mut().adjust_advance_frontier(&self.advance[..], &[]);
         self.trace.borrow_mut().adjust_through_frontier(&self.through[..], &[]);
+
+        if let Some(logger) = self.logging.as_ref() {
+            logger.log(::logging::TraceShare { operator: self.operator, diff: -1 });
+        }
     }
 }
 
@@ -807,6 +844,30 @@ where
     where
         T: Trace<K, V, G::Timestamp, R>+'static,
         T::Batch: Batch<K, V, G::Timestamp, R>;
+
+    /// Arranges a stream of `(Key, Val)` updates by `Key`, as `arrange` does, without sorting those already sorted.
+    ///
+    /// Updates that arrive sorted by key, value, and time, as when re-arranging the contents of an arrangement
+    /// by the same key, are passed to the batcher with `Batcher::push_sorted`, which need not sort them again.
+    /// Each received message is checked, and those that are not sorted are sorted as `arrange` would.
+    fn arrange_sorted<T>(&self) -> Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>>
+    where
+        T: Trace<K, V, G::Timestamp, R>+'static,
+        T::Batch: Batch<K, V, G::Timestamp, R>,
+    {
+        self.arrange_sorted_named("ArrangeSorted")
+    }
+
+    /// Arranges a stream of `(Key, Val)` updates by `Key`, as `arrange_named` does, without sorting those already sorted.
+    ///
+    /// By default this arranges updates as `arrange_named`, sorting all of them.
+    fn arrange_sorted_named<T>(&self, name: &str) -> Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>>
+    where
+        T: Trace<K, V, G::Timestamp, R>+'static,
+        T::Batch: Batch<K, V, G::Timestamp, R>,
+    {
+        self.arrange_named(name)
+    }
 }
 
 impl<G: Scope, K: Data+Hashable, V: Data, R: Monoid> Arrange<G, K, V, R> for Collection<G, (K, V), R>
@@ -818,111 +879,138 @@ where
         T: Trace<K, V, G::Timestamp, R>+'static,
         T::Batch: Batch<K, V, G::Timestamp, R>,
     {
+        arrange_core(self, name, false)
+    }
 
-        let mut reader = None;
+    fn arrange_sorted_named<T>(&self, name: &str) -> Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>>
+    where
+        T: Trace<K, V, G::Timestamp, R>+'static,
+        T::Batch: Batch<K, V, G::Timestamp, R>,
+    {
+        arrange_core(self, name, true)
+    }
+}
 
-        // fabricate a data-parallel operator using the `unary_notify` pattern.
-        let stream = {
+/// Arranges `collection` by key, passing messages of sorted updates to the batcher as sorted if `sorted` is set.
+fn arrange_core<G, K, V, R, T>(collection: &Collection<G, (K, V), R>, name: &str, sorted: bool) -> Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: Data+Hashable,
+    V: Data,
+    R: Monoid,
+    T: Trace<K, V, G::Timestamp, R>+'static,
+    T::Batch: Batch<K, V, G::Timestamp, R>,
+{
+    let mut reader = None;
 
-            let reader = &mut reader;
-            let exchange = Exchange::new(move |update: &((K,V),G::Timestamp,R)| (update.0).0.hashed().as_u64());
+    // fabricate a data-parallel operator using the `unary_notify` pattern.
+    let stream = {
 
-            self.inner.unary_frontier(exchange, name, move |_capability, _info| {
+        let reader = &mut reader;
+        let exchange = Exchange::new(move |update: &((K,V),G::Timestamp,R)| (update.0).0.hashed().as_u64());
 
-                // Attempt to acquire a logger for arrange events.
-                let logger = {
-                    let scope = self.scope();
-                    let register = scope.log_register();
-                    register.get::<::logging::DifferentialEvent>("differential/arrange")
-                };
+        collection.inner.unary_frontier(exchange, name, move |_capability, _info| {
 
-                // Where we will deposit received updates, and from which we extract batches.
-                let mut batcher = <T::Batch as Batch<K,V,G::Timestamp,R>>::Batcher::new();
+            // Attempt to acquire a logger for arrange events.
+            let logger = {
+                let scope = collection.scope();
+                let register = scope.log_register();
+                register.get::<::logging::DifferentialEvent>("differential/arrange")
+            };
 
-                // Capabilities for the lower envelope of updates in `batcher`.
-                let mut capabilities = Antichain::<Capability<G::Timestamp>>::new();
+            // Where we will deposit received updates, and from which we extract batches.
+            let mut batcher = <T::Batch as Batch<K,V,G::Timestamp,R>>::Batcher::new();
 
-                let mut buffer = Vec::new();
+            // Capabilities for the lower envelope of updates in `batcher`.
+            let mut capabilities = Antichain::<Capability<G::Timestamp>>::new();
 
-                let empty_trace = T::new(_info, logger);
-                let (reader_local, mut writer) = TraceAgent::new(empty_trace);
-                *reader = Some(reader_local);
+            let mut buffer = Vec::new();
 
-                move |input, output| {
+            let operator = _info.global_id;
+            let empty_trace = T::new(_info, logger.clone());
+            let (reader_local, mut writer) = TraceAgent::new_with_logging(empty_trace, operator, logger);
+            *reader = Some(reader_local);
 
-                // As we receive data, we need to (i) stash the data and (ii) keep *enough* capabilities.
-                // We don't have to keep all capabilities, but we need to be able to form output messages
-                // when we realize that time intervals are complete.
+            move |input, output| {
 
-                input.for_each(|cap, data| {
-                    capabilities.insert(cap.retain());
-                    data.swap(&mut buffer);
+            // As we receive data, we need to (i) stash the data and (ii) keep *enough* capabilities.
+            // We don't have to keep all capabilities, but we need to be able to form output messages
+            // when we realize that time intervals are complete.
+
+            input.for_each(|cap, data| {
+                capabilities.insert(cap.retain());
+                data.swap(&mut buffer);
+                if sorted && buffer.windows(2).all(|x| (&x[0].0, &x[0].1) <= (&x[1].0, &x[1].1)) {
+                    batcher.push_sorted(&mut buffer);
+                }
+                else {
                     batcher.push_batch(&mut buffer);
-                });
+                }
+            });
 
-                // The frontier may have advanced by multiple elements, which is an issue because
-                // timely dataflow currently only allows one capability per message. This means we
-                // must pretend to process the frontier advances one element at a time, batching
-                // and sending smaller bites than we might have otherwise done.
+            // The frontier may have advanced by multiple elements, which is an issue because
+            // timely dataflow currently only allows one capability per message. This means we
+            // must pretend to process the frontier advances one element at a time, batching
+            // and sending smaller bites than we might have otherwise done.
 
-                // If there is at least one capability no longer in advance of the input frontier ...
-                if capabilities.elements().iter().any(|c| !input.frontier().less_equal(c.time())) {
+            // If there is at least one capability no longer in advance of the input frontier ...
+            if capabilities.elements().iter().any(|c| !input.frontier().less_equal(c.time())) {
 
-                    let mut upper = Antichain::new();   // re-used allocation for sealing batches.
+                let mut upper = Antichain::new();   // re-used allocation for sealing batches.
 
-                    // For each capability not in advance of the input frontier ...
-                    for (index, capability) in capabilities.elements().iter().enumerate() {
+                // For each capability not in advance of the input frontier ...
+                for (index, capability) in capabilities.elements().iter().enumerate() {
 
-                        if !input.frontier().less_equal(capability.time()) {
+                    if !input.frontier().less_equal(capability.time()) {
 
-                            // Assemble the upper bound on times we can commit with this capabilities.
-                            // We must respect the input frontier, and *subsequent* capabilities, as
-                            // we are pretending to retire the capability changes one by one.
-                            upper.clear();
-                            for time in input.frontier().frontier().iter() {
-                                upper.insert(time.clone());
-                            }
-                            for other_capability in &capabilities.elements()[(index + 1) .. ] {
-                                upper.insert(other_capability.time().clone());
-                            }
+                        // Assemble the upper bound on times we can commit with this capabilities.
+                        // We must respect the input frontier, and *subsequent* capabilities, as
+                        // we are pretending to retire the capability changes one by one.
+                        upper.clear();
+                        for time in input.frontier().frontier().iter() {
+                            upper.insert(time.clone());
+                        }
+                        for other_capability in &capabilities.elements()[(index + 1) .. ] {
+                            upper.insert(other_capability.time().clone());
+                        }
 
-                            // Extract updates not in advance of `upper`.
-                            let batch = batcher.seal(upper.elements());
+                        // Extract updates not in advance of `upper`.
+                        let batch = batcher.seal(upper.elements());
 
-                            writer.seal(upper.elements(), Some((capability.time().clone(), batch.clone())));
+                        writer.seal(upper.elements(), Some((capability.time().clone(), batch.clone())));
 
-                            // send the batch to downstream consumers, empty or not.
-                            output.session(&capabilities.elements()[index]).give(batch);
-                        }
+                        // send the batch to downstream consumers, empty or not.
+                        output.session(&capabilities.elements()[index]).give(batch);
                     }
+                }
 
-                    // Having extracted and sent batches between each capability and the input frontier,
-                    // we should downgrade all capabilities to match the batcher's lower update frontier.
-                    // This may involve discarding capabilities, which is fine as any new updates arrive
-                    // in messages with new capabilities.
+                // Having extracted and sent batches between each capability and the input frontier,
+                // we should downgrade all capabilities to match the batcher's lower update frontier.
+                // This may involve discarding capabilities, which is fine as any new updates arrive
+                // in messages with new capabilities.
 
-                    let mut new_capabilities = Antichain::new();
-                    for time in batcher.frontier() {
-                        if let Some(capability) = capabilities.elements().iter().find(|c| c.time().less_equal(time)) {
-                            new_capabilities.insert(capability.delayed(time));
-                        }
-                        else {
-                            panic!("failed to find capability");
-                        }
+                let mut new_capabilities = Antichain::new();
+                for time in batcher.frontier() {
+                    if let Some(capability) = capabilities.elements().iter().find(|c| c.time().less_equal(time)) {
+                        new_capabilities.insert(capability.delayed(time));
+                    }
+                    else {
+                        panic!("failed to find capability");
                     }
-
-                    capabilities = new_capabilities;
                 }
 
-                // Announce progress updates.
-                // TODO: This is very noisy; consider tracking the previous frontier, and issuing an update
-                //       if and when it changes.
-                writer.seal(&input.frontier().frontier(), None);
-            }})
-        };
+                capabilities = new_capabilities;
+            }
 
-        Arranged { stream: stream, trace: reader.unwrap() }
-    }
+            // Announce progress updates.
+            // TODO: This is very noisy; consider tracking the previous frontier, and issuing an update
+            //       if and when it changes.
+            writer.seal(&input.frontier().frontier(), None);
+        }})
+    };
+
+    Arranged { stream: stream, trace: reader.unwrap() }
 }
 
 impl<G: Scope, K: Data+Hashable, R: Monoid> Arrange<G, K, (), R> for Collection<G, K, R>
@@ -937,6 +1025,15 @@ where
         self.map(|k| (k, ()))
             .arrange_named(name)
     }
+
+    fn arrange_sorted_named<T>(&self, name: &str) -> Arranged<G, K, (), R, TraceAgent<K, (), G::Timestamp, R, T>>
+    where
+        T: Trace<K, (), G::Timestamp, R>+'static,
+        T::Batch: Batch<K, (), G::Timestamp, R>
+    {
+        self.map(|k| (k, ()))
+            .arrange_sorted_named(name)
+    }
 }
 
 // impl<G, K, V, R, T> Arrange<G, K, V, R, T> for Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>>
@@ -998,3 +1095,167 @@ where G::Timestamp: Lattice+Ord {
     }
 }
 
+
+// A published trace, whose handle the registry can advance without knowing its type.
+trait RegisteredTrace<T> {
+    fn as_any(&self) -> &Any;
+    fn advance_by(&mut self, frontier: &[T]);
+    fn distinguish_since(&mut self, frontier: &[T]);
+}
+
+impl<K, V, T, R, Tr> RegisteredTrace<T> for TraceAgent<K, V, T, R, Tr>
+where
+    K: 'static,
+    V: 'static,
+    T: Lattice+Ord+Clone+'static,
+    R: 'static,
+    Tr: TraceReader<K, V, T, R>+'static,
+{
+    fn as_any(&self) -> &Any { self }
+    fn advance_by(&mut self, frontier: &[T]) { TraceReader::advance_by(self, frontier) }
+    fn distinguish_since(&mut self, frontier: &[T]) { TraceReader::distinguish_since(self, frontier) }
+}
+
+/// A collection of traces, by name, which may be imported into other dataflows on the same worker.
+///
+/// Publishing a trace registers a handle to it, which like any `TraceAgent` holds back the compaction of
+/// the trace until it is advanced. The registry advances the handles of all of its traces together with
+/// `advance_by` and `distinguish_since`, and `unpublish` drops the handle of a trace, so that the trace is
+/// released once no dataflow reads it.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use timely::Configuration;
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::operators::arrange::{ArrangeBySelf, TraceRegistry};
+/// use differential_dataflow::operators::reduce::Count;
+/// use differential_dataflow::trace::implementations::ord::OrdKeySpine;
+///
+/// fn main() {
+///     ::timely::execute(Configuration::Thread, |worker| {
+///
+///         let mut registry = TraceRegistry::new();
+///
+///         // publish an arrangement from a first dataflow.
+///         worker.dataflow::<u32,_,_>(|scope| {
+///             let trace = scope.new_collection_from(0 .. 10u32).1.arrange_by_self().trace;
+///             registry.publish("numbers", &trace);
+///         });
+///
+///         // import the arrangement by name into a second dataflow.
+///         worker.dataflow(|scope| {
+///             registry
+///                 .import::<_, u32, (), isize, OrdKeySpine<u32, u32, isize>>("numbers", scope)
+///                 .expect("trace not published")
+///                 .as_collection(|key, _| *key)
+///                 .count();
+///         });
+///
+///         registry.unpublish("numbers");
+///
+///     }).unwrap();
+/// }
+/// ```
+pub struct TraceRegistry<T> {
+    traces: HashMap<String, Box<RegisteredTrace<T>>>,
+}
+
+impl<T> Default for TraceRegistry<T> {
+    /// An empty registry.
+    fn default() -> Self {
+        TraceRegistry { traces: HashMap::new() }
+    }
+}
+
+impl<T: Lattice+Ord+Clone+'static> TraceRegistry<T> {
+
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        TraceRegistry { traces: HashMap::new() }
+    }
+
+    /// Publishes a handle to `trace` as `name`, returning `true` if it replaces a trace of that name.
+    pub fn publish<K, V, R, Tr>(&mut self, name: &str, trace: &TraceAgent<K, V, T, R, Tr>) -> bool
+    where
+        K: 'static,
+        V: 'static,
+        R: 'static,
+        Tr: TraceReader<K, V, T, R>+'static,
+    {
+        self.traces.insert(name.to_owned(), Box::new(trace.clone())).is_some()
+    }
+
+    /// A handle to the trace published as `name`, if there is one and it has the requested type.
+    pub fn get<K, V, R, Tr>(&self, name: &str) -> Option<TraceAgent<K, V, T, R, Tr>>
+    where
+        K: 'static,
+        V: 'static,
+        R: 'static,
+        Tr: TraceReader<K, V, T, R>+'static,
+    {
+        self.traces
+            .get(name)
+            .and_then(|trace| trace.as_any().downcast_ref::<TraceAgent<K, V, T, R, Tr>>())
+            .cloned()
+    }
+
+    /// Imports the trace published as `name` into `scope`, if there is one and it has the requested type.
+    pub fn import<G, K, V, R, Tr>(&self, name: &str, scope: &G) -> Option<Arranged<G, K, V, R, TraceAgent<K, V, T, R, Tr>>>
+    where
+        G: Scope<Timestamp=T>,
+        T: Timestamp,
+        K: 'static,
+        V: 'static,
+        R: 'static,
+        Tr: TraceReader<K, V, T, R>+'static,
+    {
+        self.get::<K, V, R, Tr>(name).map(|mut trace| trace.import_named(scope, name))
+    }
+
+    /// Removes the trace published as `name`, returning `true` if there was one.
+    ///
+    /// Dataflows that imported the trace continue to read it, and the trace is released once they complete.
+    pub fn unpublish(&mut self, name: &str) -> bool {
+        self.traces.remove(name).is_some()
+    }
+
+    /// Indicates if a trace is published as `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.traces.contains_key(name)
+    }
+
+    /// The names of published traces, in order.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.traces.keys().map(|name| &name[..]).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// The number of published traces.
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    /// Indicates if no traces are published.
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    /// Allows each published trace to compact updates at times not in advance of `frontier`.
+    pub fn advance_by(&mut self, frontier: &[T]) {
+        for trace in self.traces.values_mut() {
+            trace.advance_by(frontier);
+        }
+    }
+
+    /// Allows each published trace to merge batches at times not in advance of `frontier`.
+    pub fn distinguish_since(&mut self, frontier: &[T]) {
+        for trace in self.traces.values_mut() {
+            trace.distinguish_since(frontier);
+        }
+    }
+}
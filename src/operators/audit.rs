@@ -0,0 +1,146 @@
+//! Reports how many updates to a collection introduce records, and how many re-derive them.
+//!
+//! The `audit` operator is intended for the bodies of recursive computations. For each time at which
+//! a collection changes, it reports the number of records that become present, having accumulated to
+//! zero at all earlier times, and the number of records whose accumulated difference changes but which
+//! were already present. In a loop, the first are new facts derived in a round, and the second are
+//! facts derived again, which semi-naive evaluation should avoid. A body whose rounds report many
+//! re-derivations relative to new facts is a candidate for restructuring, for example by concluding
+//! with `distinct` so that multiplicities do not circulate.
+
+use timely::order::PartialOrder;
+use timely::dataflow::*;
+use timely::dataflow::operators::Operator;
+use timely::dataflow::channels::pact::Pipeline;
+
+use lattice::Lattice;
+use ::{Data, Collection};
+use ::difference::Monoid;
+use hashable::Hashable;
+use operators::arrange::ArrangeBySelf;
+use trace::{BatchReader, Cursor, TraceReader, consolidate};
+
+/// Extension trait for the `audit` differential dataflow method.
+pub trait Audit<G: Scope, D: Data, R: Monoid> where G::Timestamp: Lattice+Ord {
+    /// Reports, for each time at which the collection changes, the numbers of records introduced and
+    /// re-derived at that time, as `(time, introduced, rederived)`.
+    ///
+    /// Each worker reports counts for the records it is responsible for, and may report a time more
+    /// than once; the counts for a time are the sums of those reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use timely::dataflow::operators::Inspect;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::{Audit, Iterate, Join, Threshold};
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let edges = scope.new_collection_from(vec![(0u32, 1u32), (1, 2), (2, 0)]).1;
+    ///
+    ///         // reachable pairs, auditing derivations before `distinct`.
+    ///         edges.iterate(|paths| {
+    ///                  let edges = edges.enter(&paths.scope());
+    ///                  let derived = paths.map(|(x, y)| (y, x))
+    ///                                     .join_map(&edges, |_, &x, &z| (x, z))
+    ///                                     .concat(&edges);
+    ///                  derived.audit()
+    ///                         .inspect(|x| println!("round {:?}: {} new, {} re-derived", x.0, x.1, x.2));
+    ///                  derived.distinct()
+    ///              });
+    ///     });
+    /// }
+    /// ```
+    fn audit(&self) -> Stream<G, (G::Timestamp, usize, usize)>;
+}
+
+impl<G: Scope, D: Data+Hashable, R: Monoid> Audit<G, D, R> for Collection<G, D, R>
+where G::Timestamp: Lattice+Ord {
+
+    fn audit(&self) -> Stream<G, (G::Timestamp, usize, usize)> {
+
+        let arranged = self.arrange_by_self();
+        let mut trace = arranged.trace.clone();
+        let mut buffer = Vec::new();
+
+        let mut prior = Vec::new();
+        let mut edits = Vec::new();
+        let mut counts = Vec::new();
+
+        arranged.stream.unary(Pipeline, "Audit", move |_,_| move |input, output| {
+
+            input.for_each(|capability, batches| {
+                batches.swap(&mut buffer);
+                let capability = capability.retain();
+                for batch in buffer.drain(..) {
+
+                    let mut batch_cursor = batch.cursor();
+                    let (mut trace_cursor, trace_storage) = trace.cursor_through(batch.lower()).unwrap();
+
+                    while batch_cursor.key_valid(&batch) {
+
+                        let key = batch_cursor.key(&batch);
+
+                        // Updates to the record prior to the batch.
+                        prior.clear();
+                        trace_cursor.seek_key(&trace_storage, key);
+                        if trace_cursor.key_valid(&trace_storage) && trace_cursor.key(&trace_storage) == key {
+                            trace_cursor.map_times(&trace_storage, |time, diff| prior.push((time.clone(), diff.clone())));
+                        }
+
+                        // Updates to the record in the batch, one per time.
+                        edits.clear();
+                        batch_cursor.map_times(&batch, |time, diff| edits.push((time.clone(), diff.clone())));
+                        consolidate(&mut edits, 0);
+
+                        // Compare the accumulations strictly before and through each time.
+                        for &(ref time, ref diff) in edits.iter() {
+                            let mut before = R::zero();
+                            for &(ref time2, ref diff2) in prior.iter() {
+                                if time2.less_equal(time) { before += diff2; }
+                            }
+                            for &(ref time2, ref diff2) in edits.iter() {
+                                if time2.less_equal(time) && time2 != time { before += diff2; }
+                            }
+                            let mut after = before.clone();
+                            after += diff;
+
+                            if !after.is_zero() {
+                                if before.is_zero() { counts.push((time.clone(), 1, 0)); }
+                                else { counts.push((time.clone(), 0, 1)); }
+                            }
+                        }
+
+                        batch_cursor.step_key(&batch);
+                    }
+
+                    // tidy up the shared input trace.
+                    trace.advance_by(batch.upper());
+                    trace.distinguish_since(batch.upper());
+                }
+
+                // Report the counts for each time in the batches.
+                counts.sort_by(|x: &(G::Timestamp, usize, usize), y: &(G::Timestamp, usize, usize)| x.0.cmp(&y.0));
+                let mut position = 0;
+                while position < counts.len() {
+                    let time = counts[position].0.clone();
+                    let mut introduced = 0;
+                    let mut rederived = 0;
+                    while position < counts.len() && counts[position].0 == time {
+                        introduced += counts[position].1;
+                        rederived += counts[position].2;
+                        position += 1;
+                    }
+                    output.session(&capability.delayed(&time)).give((time, introduced, rederived));
+                }
+                counts.clear();
+            });
+        })
+    }
+}
@@ -86,6 +86,40 @@ pub trait ConsolidateStream<D: Data+Hashable> {
     /// }
     /// ```
     fn consolidate_stream(&self) -> Self;
+
+    /// Aggregates the weights of equal records, buffering across batches.
+    ///
+    /// Like `consolidate_stream`, this method does not exchange data nor build an arrangement.
+    /// Updates are buffered by timestamp and consolidated together with updates from subsequent
+    /// batches at the same timestamp, until either the input frontier passes the timestamp or
+    /// the buffer for the timestamp holds more than `limit` updates even after consolidation.
+    /// This may produce far fewer records than `consolidate_stream` when updates for the same
+    /// `(data, time)` arrive in multiple batches, at the cost of delaying the records until the
+    /// frontier advances.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::consolidate::ConsolidateStream;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(1 .. 10u32).1;
+    ///
+    ///         // cancellation occurs, as all updates are buffered together.
+    ///         x.negate()
+    ///          .concat(&x)
+    ///          .consolidate_stream_bounded(1_000)
+    ///          .assert_empty();
+    ///     });
+    /// }
+    /// ```
+    fn consolidate_stream_bounded(&self, limit: usize) -> Self;
 }
 
 impl<G: Scope, D, R> ConsolidateStream<D> for Collection<G, D, R>
@@ -107,18 +141,75 @@ where
                 move |input, output| {
                     input.for_each(|time, data| {
                         data.swap(&mut vector);
-                        vector.sort_unstable_by(|x,y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
-                        for index in 1 .. vector.len() {
-                            if vector[index].0 == vector[index - 1].0 && vector[index].1 == vector[index - 1].1 {
-                                let prev = ::std::mem::replace(&mut vector[index - 1].2, R::zero());
-                                vector[index].2 += &prev;
-                            }
-                        }
-                        vector.retain(|x| !x.2.is_zero());
+                        consolidate_updates(&mut vector);
                         output.session(&time).give_vec(&mut vector);
                     })
                 }
             })
             .as_collection()
     }
+
+    fn consolidate_stream_bounded(&self, limit: usize) -> Self {
+
+        use timely::dataflow::channels::pact::Pipeline;
+        use timely::dataflow::operators::{Operator, Capability};
+        use collection::AsCollection;
+
+        self.inner
+            .unary_frontier(Pipeline, "ConsolidateStreamBounded", move |_cap, _info| {
+
+                // Buffered updates, by the capability for their timestamp.
+                let mut stash: Vec<(Capability<G::Timestamp>, Vec<(D, G::Timestamp, R)>)> = Vec::new();
+                let mut vector = Vec::new();
+
+                move |input, output| {
+
+                    input.for_each(|time, data| {
+                        data.swap(&mut vector);
+                        let position = stash.iter().position(|x| x.0.time() == time.time());
+                        let index = position.unwrap_or_else(|| {
+                            stash.push((time.retain(), Vec::new()));
+                            stash.len() - 1
+                        });
+                        stash[index].1.extend(vector.drain(..));
+                        if stash[index].1.len() > limit {
+                            consolidate_updates(&mut stash[index].1);
+                            // If consolidation did not bring us below half the limit, flush.
+                            if 2 * stash[index].1.len() > limit {
+                                let (capability, mut updates) = stash.remove(index);
+                                output.session(&capability).give_vec(&mut updates);
+                            }
+                        }
+                    });
+
+                    // Flush buffers whose timestamps are complete.
+                    let mut index = 0;
+                    while index < stash.len() {
+                        if !input.frontier().less_equal(stash[index].0.time()) {
+                            let (capability, mut updates) = stash.remove(index);
+                            consolidate_updates(&mut updates);
+                            if !updates.is_empty() {
+                                output.session(&capability).give_vec(&mut updates);
+                            }
+                        }
+                        else {
+                            index += 1;
+                        }
+                    }
+                }
+            })
+            .as_collection()
+    }
+}
+
+/// Sorts and consolidates `(data, time, diff)` updates, removing any that accumulate to zero.
+fn consolidate_updates<D: Ord, T: Ord, R: Monoid>(vector: &mut Vec<(D, T, R)>) {
+    vector.sort_unstable_by(|x,y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
+    for index in 1 .. vector.len() {
+        if vector[index].0 == vector[index - 1].0 && vector[index].1 == vector[index - 1].1 {
+            let prev = ::std::mem::replace(&mut vector[index - 1].2, R::zero());
+            vector[index].2 += &prev;
+        }
+    }
+    vector.retain(|x| !x.2.is_zero());
 }
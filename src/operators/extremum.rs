@@ -0,0 +1,175 @@
+//! Maintains the least or greatest value associated with each key.
+//!
+//! The `min_total` and `max_total` operators act on data that can be viewed as pairs `(key, val)`,
+//! and report for each key the least or greatest value with a non-zero accumulated difference. They
+//! may be implemented with `reduce`, but when times are totally ordered the operators can avoid the
+//! partial-order bookkeeping `reduce` performs, and instead maintain each key's values as times are
+//! completed one after the other.
+
+use timely::order::TotalOrder;
+use timely::dataflow::*;
+use timely::dataflow::operators::Operator;
+use timely::dataflow::channels::pact::Pipeline;
+
+use lattice::Lattice;
+use ::{Data, Collection};
+use ::difference::Monoid;
+use hashable::Hashable;
+use collection::AsCollection;
+use operators::arrange::{Arranged, ArrangeByKey};
+use trace::{BatchReader, Cursor, TraceReader, consolidate};
+
+/// Extension trait for the `min_total` and `max_total` differential dataflow methods.
+pub trait ExtremumTotal<G: Scope, K: Data, V: Data, R: Monoid> where G::Timestamp: TotalOrder+Lattice+Ord {
+    /// Reports the least value associated with each key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::ExtremumTotal;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the least element of each group of three.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .min_total();
+    ///     });
+    /// }
+    /// ```
+    fn min_total(&self) -> Collection<G, (K, V), isize> {
+        self.extremum_total(false)
+    }
+
+    /// Reports the greatest value associated with each key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::ExtremumTotal;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the greatest element of each group of three.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .max_total();
+    ///     });
+    /// }
+    /// ```
+    fn max_total(&self) -> Collection<G, (K, V), isize> {
+        self.extremum_total(true)
+    }
+
+    /// Reports the greatest value associated with each key if `max` is set, and the least otherwise.
+    fn extremum_total(&self, max: bool) -> Collection<G, (K, V), isize>;
+}
+
+impl<G: Scope, K: Data+Hashable, V: Data, R: Monoid> ExtremumTotal<G, K, V, R> for Collection<G, (K, V), R>
+where G::Timestamp: TotalOrder+Lattice+Ord {
+    fn extremum_total(&self, max: bool) -> Collection<G, (K, V), isize> {
+        self.arrange_by_key()
+            .extremum_total(max)
+    }
+}
+
+impl<G: Scope, K: Data, V: Data, R: Monoid, T1> ExtremumTotal<G, K, V, R> for Arranged<G, K, V, R, T1>
+where
+    G::Timestamp: TotalOrder+Lattice+Ord,
+    T1: TraceReader<K, V, G::Timestamp, R>+Clone+'static,
+    T1::Batch: BatchReader<K, V, G::Timestamp, R> {
+
+    fn extremum_total(&self, max: bool) -> Collection<G, (K, V), isize> {
+
+        let mut trace = self.trace.clone();
+        let mut buffer = Vec::new();
+
+        let mut accum = Vec::new();
+        let mut edits = Vec::new();
+
+        let name = if max { "MaxTotal" } else { "MinTotal" };
+        self.stream.unary(Pipeline, name, move |_,_| move |input, output| {
+
+            input.for_each(|capability, batches| {
+                batches.swap(&mut buffer);
+                let mut session = output.session(&capability);
+                for batch in buffer.drain(..) {
+
+                    let mut batch_cursor = batch.cursor();
+                    let (mut trace_cursor, trace_storage) = trace.cursor_through(batch.lower()).unwrap();
+
+                    while batch_cursor.key_valid(&batch) {
+
+                        let key = batch_cursor.key(&batch);
+
+                        // Accumulate the values of the key prior to the batch.
+                        accum.clear();
+                        trace_cursor.seek_key(&trace_storage, key);
+                        if trace_cursor.key_valid(&trace_storage) && trace_cursor.key(&trace_storage) == key {
+                            while trace_cursor.val_valid(&trace_storage) {
+                                let mut count = R::zero();
+                                trace_cursor.map_times(&trace_storage, |_, diff| count += diff);
+                                if !count.is_zero() {
+                                    accum.push((trace_cursor.val(&trace_storage).clone(), count));
+                                }
+                                trace_cursor.step_val(&trace_storage);
+                            }
+                        }
+                        let mut prior = extremum(&accum[..], max);
+
+                        // Collect the changes to the key in the batch, ordered by time.
+                        edits.clear();
+                        while batch_cursor.val_valid(&batch) {
+                            let val = batch_cursor.val(&batch);
+                            batch_cursor.map_times(&batch, |time, diff| edits.push((time.clone(), val.clone(), diff.clone())));
+                            batch_cursor.step_val(&batch);
+                        }
+                        edits.sort_by(|x,y| x.0.cmp(&y.0));
+
+                        // Apply changes one time at a time, reporting any change in the extremum.
+                        let mut position = 0;
+                        while position < edits.len() {
+                            let time = edits[position].0.clone();
+                            while position < edits.len() && edits[position].0 == time {
+                                accum.push((edits[position].1.clone(), edits[position].2.clone()));
+                                position += 1;
+                            }
+                            consolidate(&mut accum, 0);
+                            let next = extremum(&accum[..], max);
+                            if next != prior {
+                                if let Some(prior) = prior {
+                                    session.give(((key.clone(), prior), time.clone(), -1));
+                                }
+                                if let Some(next) = next.clone() {
+                                    session.give(((key.clone(), next), time.clone(), 1));
+                                }
+                                prior = next;
+                            }
+                        }
+
+                        batch_cursor.step_key(&batch);
+                    }
+
+                    // tidy up the shared input trace.
+                    trace.advance_by(batch.upper());
+                    trace.distinguish_since(batch.upper());
+                }
+            });
+        })
+        .as_collection()
+    }
+}
+
+/// The greatest value in the consolidated `accum` if `max` is set, and the least otherwise.
+fn extremum<V: Clone, R>(accum: &[(V, R)], max: bool) -> Option<V> {
+    if max { accum.last().map(|x| x.0.clone()) }
+    else { accum.first().map(|x| x.0.clone()) }
+}
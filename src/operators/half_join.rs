@@ -0,0 +1,220 @@
+//! Joins changes to a collection with the accumulated contents of an arrangement.
+//!
+//! The `half_join` operator is the building block of delta queries. Where `join` responds to changes in
+//! either of its inputs, `half_join` responds only to changes in its collection input: each update
+//! `((key, val1), time, diff1)` is joined with those updates `(key, val2, time2, diff2)` of the arrangement
+//! for which `comparison(time2, time)` holds, producing `(logic(key, val1, val2), time, diff1 * diff2)`
+//! where `diff2` is accumulated over those updates. Changes to the arrangement produce no output on their
+//! own, and are only observed by later changes to the collection.
+//!
+//! An update to the collection at `time` is joined once the arrangement is complete through `time`, and
+//! the comparison must only accept times less or equal to `time`. The comparison must also be preserved
+//! when times in the arrangement are advanced to the frontier of the collection input and the times of
+//! changes not yet joined, as the operator allows the arrangement to compact to that frontier; `less_equal`
+//! has this property for partially ordered times, and so does any comparison of totally ordered times.
+//!
+//! With totally ordered times, the changes to the join of collections `A` and `B` are the half join of
+//! the changes to `A` with `B` at time less or equal to theirs, and the half join of the changes to `B`
+//! with `A` at times strictly less than theirs. Delta queries generalize this to joins of many relations,
+//! using a half join for each relation and each arrangement it is joined with, and maintaining only one
+//! arrangement for each relation.
+//!
+//! The `half_join_fueled` variant limits the number of results the operator produces in each invocation,
+//! rescheduling itself to continue the work, so that large joins share the worker with other operators.
+
+use std::ops::Mul;
+
+use timely::dataflow::*;
+use timely::dataflow::operators::{Capability, Operator};
+use timely::dataflow::channels::pact::{Pipeline, Exchange};
+use timely::scheduling::Activator;
+use timely::progress::frontier::Antichain;
+
+use timely_sort::Unsigned;
+
+use lattice::Lattice;
+use ::{Data, Collection, AsCollection, Hashable};
+use ::difference::Monoid;
+use operators::arrange::Arranged;
+use trace::{BatchReader, Cursor, TraceReader};
+
+/// Extension trait for the `half_join` differential dataflow methods.
+pub trait HalfJoin<G: Scope, K: Data, V: Data, R: Monoid> where G::Timestamp: Lattice+Ord {
+    /// Joins each change to the collection with the contents of `arrangement` at times accepted by `comparison`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use timely::PartialOrder;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::{HalfJoin, Join};
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let names = scope.new_collection_from(vec![(0, "zero"), (1, "one")]).1;
+    ///         let pairs = scope.new_collection_from(vec![(0, 'a'), (1, 'b'), (2, 'c')]).1;
+    ///
+    ///         // the changes to the join, as produced by half joins.
+    ///         let delta1 = names.half_join(&pairs.arrange_by_key(), |t2, t| t2.less_equal(t), |k, n, c| (*k, *n, *c));
+    ///         let delta2 = pairs.half_join(&names.arrange_by_key(), |t2, t| t2.less_than(t), |k, c, n| (*k, *n, *c));
+    ///
+    ///         delta1.concat(&delta2)
+    ///               .assert_eq(&names.join(&pairs).map(|(k, (n, c))| (k, n, c)));
+    ///     });
+    /// }
+    /// ```
+    fn half_join<V2, R2, Tr, CF, D, L>(&self, arrangement: &Arranged<G, K, V2, R2, Tr>, comparison: CF, logic: L) -> Collection<G, D, <R as Mul<R2>>::Output>
+    where
+        V2: Data,
+        R2: Monoid,
+        Tr: TraceReader<K, V2, G::Timestamp, R2>+Clone+'static,
+        Tr::Batch: BatchReader<K, V2, G::Timestamp, R2>+'static,
+        R: Mul<R2>,
+        <R as Mul<R2>>::Output: Monoid,
+        CF: Fn(&G::Timestamp, &G::Timestamp)->bool+'static,
+        D: Data,
+        L: Fn(&K, &V, &V2)->D+'static,
+    {
+        self.half_join_fueled(arrangement, comparison, logic, usize::max_value())
+    }
+
+    /// Joins each change to the collection with the contents of `arrangement` at times accepted by `comparison`,
+    /// producing approximately at most `fuel` results each time the operator is scheduled.
+    fn half_join_fueled<V2, R2, Tr, CF, D, L>(&self, arrangement: &Arranged<G, K, V2, R2, Tr>, comparison: CF, logic: L, fuel: usize) -> Collection<G, D, <R as Mul<R2>>::Output>
+    where
+        V2: Data,
+        R2: Monoid,
+        Tr: TraceReader<K, V2, G::Timestamp, R2>+Clone+'static,
+        Tr::Batch: BatchReader<K, V2, G::Timestamp, R2>+'static,
+        R: Mul<R2>,
+        <R as Mul<R2>>::Output: Monoid,
+        CF: Fn(&G::Timestamp, &G::Timestamp)->bool+'static,
+        D: Data,
+        L: Fn(&K, &V, &V2)->D+'static;
+}
+
+impl<G, K, V, R> HalfJoin<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: Data+Hashable,
+    V: Data,
+    R: Monoid,
+{
+    fn half_join_fueled<V2, R2, Tr, CF, D, L>(&self, arrangement: &Arranged<G, K, V2, R2, Tr>, comparison: CF, logic: L, fuel: usize) -> Collection<G, D, <R as Mul<R2>>::Output>
+    where
+        V2: Data,
+        R2: Monoid,
+        Tr: TraceReader<K, V2, G::Timestamp, R2>+Clone+'static,
+        Tr::Batch: BatchReader<K, V2, G::Timestamp, R2>+'static,
+        R: Mul<R2>,
+        <R as Mul<R2>>::Output: Monoid,
+        CF: Fn(&G::Timestamp, &G::Timestamp)->bool+'static,
+        D: Data,
+        L: Fn(&K, &V, &V2)->D+'static,
+    {
+        let mut trace = Some(arrangement.trace.clone());
+        let exchange = Exchange::new(|update: &((K, V), G::Timestamp, R)| (update.0).0.hashed().as_u64());
+
+        // Changes to the collection not yet joined, by capability.
+        let mut stash: Vec<(Capability<G::Timestamp>, Vec<((K, V), G::Timestamp, R)>)> = Vec::new();
+        let mut buffer = Vec::new();
+
+        self.inner.binary_frontier(&arrangement.stream, exchange, Pipeline, "HalfJoin", move |_, info| {
+
+            let activations = self.scope().activations().clone();
+            let activator = Activator::new(&info.address[..], activations);
+
+            move |input1, input2, output| {
+
+                // drain the first input, stashing changes.
+                input1.for_each(|capability, data| {
+                    let mut updates = Vec::new();
+                    data.swap(&mut updates);
+                    stash.push((capability.retain(), updates));
+                });
+
+                // advance the `distinguish_since` frontier to allow all merges.
+                input2.for_each(|_, batches| {
+                    batches.swap(&mut buffer);
+                    for batch in buffer.drain(..) {
+                        if let Some(ref mut trace) = trace {
+                            trace.distinguish_since(batch.upper());
+                        }
+                    }
+                });
+
+                let mut remaining = fuel;
+
+                if let Some(ref mut trace) = trace {
+
+                    for &mut (ref capability, ref mut updates) in stash.iter_mut() {
+
+                        // defer changes at times the arrangement may still change.
+                        // NOTE: not all changes may be at complete times, but if this test fails then none of them are.
+                        if remaining > 0 && !input2.frontier().less_equal(capability.time()) {
+
+                            let mut session = output.session(capability);
+
+                            // sort changes for in-order cursor traversal.
+                            updates.sort_by(|x,y| (x.0).0.cmp(&(y.0).0));
+
+                            let (mut cursor, storage) = trace.cursor();
+
+                            for &mut ((ref key, ref val1), ref time, ref mut diff1) in updates.iter_mut() {
+                                if remaining > 0 && !input2.frontier().less_equal(time) {
+                                    if cursor.probe_key(&storage, key) {
+                                        while let Some(val2) = cursor.get_val(&storage) {
+                                            let mut diff2 = R2::zero();
+                                            cursor.map_times(&storage, |t, d| if comparison(t, time) { diff2 += d; });
+                                            if !diff2.is_zero() {
+                                                session.give((logic(key, val1, val2), time.clone(), diff1.clone() * diff2));
+                                                remaining = remaining.saturating_sub(1);
+                                            }
+                                            cursor.step_val(&storage);
+                                        }
+                                        cursor.rewind_vals(&storage);
+                                    }
+                                    *diff1 = R::zero();
+                                }
+                            }
+
+                            updates.retain(|x| !x.2.is_zero());
+                        }
+                    }
+                }
+
+                // drop fully processed capabilities.
+                stash.retain(|x| !x.1.is_empty());
+
+                // continue with the remaining work if fuel ran out.
+                if remaining == 0 && !stash.is_empty() {
+                    activator.activate();
+                }
+
+                // advance the consolidation frontier, retaining the times of stashed changes.
+                if let Some(ref mut trace) = trace {
+                    let mut frontier = Antichain::new();
+                    for time in input1.frontier().frontier().iter() {
+                        frontier.insert(time.clone());
+                    }
+                    for &(ref capability, _) in stash.iter() {
+                        frontier.insert(capability.time().clone());
+                    }
+                    trace.advance_by(frontier.elements());
+                }
+
+                if input1.frontier().is_empty() && stash.is_empty() {
+                    trace = None;
+                }
+            }
+        })
+        .as_collection()
+    }
+}
@@ -29,19 +29,28 @@
 //! and it can be used in most situations where a collection can be used. The act of setting a
 //! `Variable` consumes it and returns the corresponding `Collection`, preventing you from setting
 //! it multiple times.
+//!
+//! The `iterate_nested` method establishes a loop within each round of another loop, for computations
+//! that repeatedly adjust the input to a fixed point.
+//!
+//! The `MutualRecursion` type groups several variables, and checks that each of them is set before
+//! the group is dropped.
 
 use std::fmt::Debug;
 use std::ops::Deref;
+use std::rc::Rc;
+use std::cell::RefCell;
 
 use timely::progress::{Timestamp, PathSummary};
 use timely::order::Product;
 
 use timely::dataflow::*;
 use timely::dataflow::scopes::child::Iterative;
-use timely::dataflow::operators::{Feedback, ConnectLoop, Map};
+use timely::dataflow::operators::{Feedback, ConnectLoop, Map, Inspect, Operator, Capability};
 use timely::dataflow::operators::feedback::Handle;
+use timely::dataflow::channels::pact::{Pipeline, Exchange};
 
-use ::{Data, Collection};
+use ::{Data, Collection, Hashable};
 use ::difference::{Monoid, Abelian};
 use lattice::Lattice;
 
@@ -80,6 +89,118 @@ pub trait Iterate<G: Scope, D: Data, R: Monoid> {
         where
             G::Timestamp: Lattice,
             for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R>;
+
+    /// Iteratively apply `logic` to the source collection until convergence, panicking if the number
+    /// of updates in the loop fails to decrease for `rounds` consecutive rounds.
+    ///
+    /// This is a diagnostic for iterations that do not terminate, which otherwise occupy the worker
+    /// without any indication of the problem. Some correct iterations do not shrink for many rounds,
+    /// for example those propagating labels along long paths, and `rounds` should be chosen so that
+    /// such iterations are not reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Iterate;
+    /// use differential_dataflow::operators::Consolidate;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .iterate_monitored(100, |values| {
+    ///                  values.map(|x| if x % 2 == 0 { x/2 } else { x })
+    ///                        .consolidate()
+    ///              });
+    ///     });
+    /// }
+    /// ```
+    fn iterate_monitored<F>(&self, rounds: usize, logic: F) -> Collection<G, D, R>
+        where
+            G::Timestamp: Lattice+Ord,
+            for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R>;
+
+    /// Iteratively apply `logic` to the source collection until convergence, in a loop whose rounds
+    /// are counted by timestamps of type `T` and advanced by `step` in each round.
+    ///
+    /// The `iterate` method counts rounds with `u64` timestamps advanced by one. A smaller timestamp
+    /// type such as `u32` reduces the size of each update, which is significant in deeply nested loops
+    /// where each level contributes a coordinate, and other summaries allow rounds to be counted
+    /// differently, for example by several steps at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Iterate;
+    /// use differential_dataflow::operators::Consolidate;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .iterate_with::<u32,_>(1, |values| {
+    ///                  values.map(|x| if x % 2 == 0 { x/2 } else { x })
+    ///                        .consolidate()
+    ///              });
+    ///     });
+    /// }
+    /// ```
+    fn iterate_with<T, F>(&self, step: T::Summary, logic: F) -> Collection<G, D, R>
+        where
+            G::Timestamp: Lattice,
+            T: Timestamp+Lattice,
+            for<'a> F: FnOnce(&Collection<Iterative<'a, G, T>, D, R>)->Collection<Iterative<'a, G, T>, D, R>;
+
+    /// Iteratively apply `logic` to the source collection, until convergence or until the updates of a
+    /// round are small enough that `stop` holds.
+    ///
+    /// The updates fed back around the loop in each round are weighed by `weight`, and `stop` is applied
+    /// to the time of the round and the total weight of its updates. If `stop` holds the updates are not
+    /// fed back, and the loop concludes with the collection as of the previous round. This allows
+    /// computations like PageRank to conclude once they are within a tolerance of their fixed point,
+    /// rather than iterating until they reach it exactly. The weights are of updates as the loop body
+    /// produces them, and those of updates that would cancel are not discounted.
+    ///
+    /// The decision is made for each round independently, and is the same on all workers. When the input
+    /// changes, the loop at later times may stop at a different round than it had, and so the results
+    /// need not be the same as those of a loop applied to the changed input from scratch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Iterate;
+    /// use differential_dataflow::operators::Consolidate;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         // stop once fewer than four numbers change in a round.
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .iterate_until(|_x, diff| (*diff as f64).abs(), |_time, total| total < 8.0, |values| {
+    ///                  values.map(|x| if x % 2 == 0 { x/2 } else { x })
+    ///                        .consolidate()
+    ///              });
+    ///     });
+    /// }
+    /// ```
+    fn iterate_until<W, P, F>(&self, weight: W, stop: P, logic: F) -> Collection<G, D, R>
+        where
+            G::Timestamp: Lattice,
+            W: Fn(&D, &R)->f64+'static,
+            P: Fn(&Product<G::Timestamp, u64>, f64)->bool+'static,
+            for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R>;
 }
 
 impl<G: Scope, D: Ord+Data+Debug, R: Abelian> Iterate<G, D, R> for Collection<G, D, R> {
@@ -100,6 +221,45 @@ impl<G: Scope, D: Ord+Data+Debug, R: Abelian> Iterate<G, D, R> for Collection<G,
             result.leave()
         })
     }
+
+    fn iterate_monitored<F>(&self, rounds: usize, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice+Ord,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R> {
+
+        self.inner.scope().scoped("Iterate", |subgraph| {
+            let variable = Variable::new_from(self.enter(subgraph), Product::new(Default::default(), 1));
+            let result = logic(&variable);
+            variable.set_monitored(&result, rounds).1.inspect(move |x| diverged(rounds, x));
+            result.leave()
+        })
+    }
+
+    fn iterate_with<T, F>(&self, step: T::Summary, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              T: Timestamp+Lattice,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, T>, D, R>)->Collection<Iterative<'a, G, T>, D, R> {
+
+        self.inner.scope().scoped::<Product<G::Timestamp, T>,_,_>("Iterate", |subgraph| {
+            let variable = Variable::new_from(self.enter(subgraph), Product::new(Default::default(), step));
+            let result = logic(&variable);
+            variable.set(&result);
+            result.leave()
+        })
+    }
+
+    fn iterate_until<W, P, F>(&self, weight: W, stop: P, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              W: Fn(&D, &R)->f64+'static,
+              P: Fn(&Product<G::Timestamp, u64>, f64)->bool+'static,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R> {
+
+        self.inner.scope().scoped("Iterate", |subgraph| {
+            let variable = Variable::new_from(self.enter(subgraph), Product::new(Default::default(), 1));
+            let result = logic(&variable);
+            variable.set_until(&result, weight, stop);
+            result.leave()
+        })
+    }
 }
 
 impl<G: Scope, D: Ord+Data+Debug, R: Monoid> Iterate<G, D, R> for G {
@@ -124,6 +284,191 @@ impl<G: Scope, D: Ord+Data+Debug, R: Monoid> Iterate<G, D, R> for G {
             }
         )
     }
+
+    fn iterate_monitored<F>(&self, rounds: usize, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice+Ord,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R> {
+
+        let mut clone = self.clone();
+        clone
+            .scoped("Iterate", |subgraph| {
+                let variable = MonoidVariable::new(subgraph, Product::new(Default::default(), 1));
+                let result = logic(&variable);
+                variable.set_monitored(&result, rounds).1.inspect(move |x| diverged(rounds, x));
+                result.leave()
+            }
+        )
+    }
+
+    fn iterate_with<T, F>(&self, step: T::Summary, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              T: Timestamp+Lattice,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, T>, D, R>)->Collection<Iterative<'a, G, T>, D, R> {
+
+        let mut clone = self.clone();
+        clone
+            .scoped::<Product<G::Timestamp, T>,_,_>("Iterate", |subgraph| {
+                let variable = MonoidVariable::new(subgraph, Product::new(Default::default(), step));
+                let result = logic(&variable);
+                variable.set(&result);
+                result.leave()
+            }
+        )
+    }
+
+    fn iterate_until<W, P, F>(&self, weight: W, stop: P, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              W: Fn(&D, &R)->f64+'static,
+              P: Fn(&Product<G::Timestamp, u64>, f64)->bool+'static,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R> {
+
+        let mut clone = self.clone();
+        clone
+            .scoped("Iterate", |subgraph| {
+                let variable = MonoidVariable::new(subgraph, Product::new(Default::default(), 1));
+                let result = logic(&variable);
+                variable.set_until(&result, weight, stop);
+                result.leave()
+            }
+        )
+    }
+}
+
+/// An extension trait for the `iterate_monotonic` method.
+pub trait IterateMonotonic<G: Scope, D: Data, R: Monoid> {
+    /// Iteratively applies `logic` to the source collection and the records it derives, until no new
+    /// records are derived.
+    ///
+    /// The loop body must be monotone: as its input grows, its output may only grow. Under this
+    /// assumption, the records derived in each round are simply added to the source collection, rather
+    /// than replacing the previous round's records, which avoids circulating retractions and negating
+    /// the source collection in each round. The output is the accumulation of the source collection and
+    /// all derived records, consolidated only on exit from the loop; records derived several times, or
+    /// derived and also present in the source, appear with the sum of their multiplicities, and `logic`
+    /// should end with `distinct` if set semantics are needed.
+    ///
+    /// The source collection and collections `logic` enters may retract records. The output at each
+    /// time is then that of the loop applied to the inputs as of that time, and records no longer
+    /// derivable are retracted. Within the loop such changes appear as negative updates, as do the
+    /// corrections made when a later time derives a record in an earlier round than before, and so
+    /// the updates `logic` produces need not be positive even though it is monotone; for this reason
+    /// monotonicity is not checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::{IterateMonotonic, Join, Threshold};
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let edges = scope.new_collection_from(vec![(0u32, 1u32), (1, 2), (2, 0)]).1;
+    ///         let roots = scope.new_collection_from(vec![0u32]).1;
+    ///
+    ///         // nodes reachable from the roots, including the roots.
+    ///         roots.iterate_monotonic(|reach| {
+    ///                  let edges = edges.enter(&reach.scope());
+    ///                  reach.map(|x| (x, ()))
+    ///                       .join_map(&edges, |_, &(), &y| y)
+    ///                       .distinct()
+    ///              })
+    ///              .distinct();
+    ///     });
+    /// }
+    /// ```
+    fn iterate_monotonic<F>(&self, logic: F) -> Collection<G, D, R>
+        where
+            G::Timestamp: Lattice+Ord,
+            for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R>;
+}
+
+impl<G: Scope, D: Data+Hashable, R: Monoid> IterateMonotonic<G, D, R> for Collection<G, D, R> {
+    fn iterate_monotonic<F>(&self, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice+Ord,
+              for<'a> F: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R> {
+
+        use operators::Consolidate;
+
+        self.inner.scope().scoped("IterateMonotonic", |subgraph| {
+            // The current records are the source and everything derived in previous rounds.
+            let variable = MonoidVariable::new(subgraph, Product::new(Default::default(), 1));
+            let current = variable.concat(&self.enter(subgraph));
+            let result = logic(&current);
+            variable.set(&result);
+            current.leave()
+        })
+        .consolidate()
+    }
+}
+
+/// An extension trait for the `iterate_nested` method.
+pub trait IterateNested<G: Scope, D: Data, R: Monoid> {
+    /// Iteratively applies `outer` to a collection and the fixed point `inner` derives from it, with
+    /// the fixed point computed in a loop nested within each round of the outer loop.
+    ///
+    /// In each round of the outer loop, `inner` is iterated to its fixed point starting from the outer
+    /// loop's current collection, and receives that collection brought into the inner loop as its first
+    /// argument. The `outer` closure then receives the outer loop's current collection and the inner
+    /// fixed point, and produces the outer loop's collection for the next round. This establishes the
+    /// nested scopes and their timestamps, and brings collections into and out of them; for example, an
+    /// outer loop may sweep over alternatives, each round adjusting a parameter that the inner fixed
+    /// point depends on.
+    ///
+    /// As with `iterate`, the results are not automatically consolidated, and both closures should
+    /// conclude with consolidation to ensure that logically empty collections do not circulate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::IterateNested;
+    /// use differential_dataflow::operators::Consolidate;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         // divide out all factors of two, and then one factor of three, until neither remains.
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .iterate_nested(
+    ///                  |_outer, values| values.map(|x| if x % 2 == 0 { x/2 } else { x }).consolidate(),
+    ///                  |_values, odd| odd.map(|x| if x % 3 == 0 { x/3 } else { x }).consolidate(),
+    ///              );
+    ///     });
+    /// }
+    /// ```
+    fn iterate_nested<F1, F2>(&self, inner: F1, outer: F2) -> Collection<G, D, R>
+        where
+            G::Timestamp: Lattice,
+            for<'a, 'b> F1: FnOnce(&Collection<Iterative<'b, Iterative<'a, G, u64>, u64>, D, R>, &Collection<Iterative<'b, Iterative<'a, G, u64>, u64>, D, R>)->Collection<Iterative<'b, Iterative<'a, G, u64>, u64>, D, R>,
+            for<'a> F2: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>, &Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R>;
+}
+
+impl<G: Scope, D: Ord+Data+Debug, R: Abelian> IterateNested<G, D, R> for Collection<G, D, R> {
+    fn iterate_nested<F1, F2>(&self, inner: F1, outer: F2) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              for<'a, 'b> F1: FnOnce(&Collection<Iterative<'b, Iterative<'a, G, u64>, u64>, D, R>, &Collection<Iterative<'b, Iterative<'a, G, u64>, u64>, D, R>)->Collection<Iterative<'b, Iterative<'a, G, u64>, u64>, D, R>,
+              for<'a> F2: FnOnce(&Collection<Iterative<'a, G, u64>, D, R>, &Collection<Iterative<'a, G, u64>, D, R>)->Collection<Iterative<'a, G, u64>, D, R> {
+
+        self.iterate(|current| {
+            let fixed = current.iterate(|values| {
+                let current = current.enter(&values.scope());
+                inner(&current, values)
+            });
+            outer(current, &fixed)
+        })
+    }
+}
+
+/// Panics with a report of a round whose updates have failed to decrease.
+fn diverged<T: Debug>(rounds: usize, report: &(T, usize)) {
+    panic!("Iterate: updates have not decreased for {} rounds; {} updates at {:?}", rounds, report.1, report.0);
 }
 
 /// A recursively defined collection.
@@ -198,6 +543,102 @@ impl<G: Scope, D: Data, R: Abelian> Variable<G, D, R> where G::Timestamp: Lattic
 
         self.collection
     }
+
+    /// Adds a new source of data to the `Variable`, delaying each update by an amount determined by its data.
+    ///
+    /// Each update fed back around the loop advances by the variable's step, and then by `priority` applied
+    /// to its data. Updates with greater delays are processed in later rounds, which allows algorithms that
+    /// correct labels, like shortest paths, to process the most promising updates first, rather than all
+    /// updates in synchronous rounds. The result is the same as with `set`, but it may be reached with less
+    /// work, as updates that would be corrected by earlier ones are never produced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use timely::order::Product;
+    /// use timely::dataflow::Scope;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::iterate::Variable;
+    /// use differential_dataflow::operators::Consolidate;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let numbers = scope.new_collection_from(1 .. 10u32).1;
+    ///
+    ///         scope.iterative::<u64,_,_>(|nested| {
+    ///             let summary = Product::new(Default::default(), 1);
+    ///             let variable = Variable::new_from(numbers.enter(nested), summary);
+    ///             let result = variable.map(|x| if x % 2 == 0 { x/2 } else { x })
+    ///                                  .consolidate();
+    ///             // process smaller numbers first.
+    ///             variable.set_prioritized(&result, |x| Product::new(Default::default(), *x as u64))
+    ///                     .leave()
+    ///         });
+    ///     })
+    /// }
+    /// ```
+    pub fn set_prioritized<F>(self, result: &Collection<G, D, R>, priority: F) -> Collection<G, D, R>
+    where F: Fn(&D)-><G::Timestamp as Timestamp>::Summary+'static {
+        let step = self.step;
+        self.source
+            .negate()
+            .concat(result)
+            .inner
+            .flat_map(move |(x,t,d)| {
+                let delay = priority(&x);
+                step.results_in(&t)
+                    .and_then(|t| delay.results_in(&t))
+                    .map(|t| (x,t,d))
+            })
+            .connect_loop(self.feedback);
+
+        self.collection
+    }
+
+    /// Adds a new source of data to the `Variable`, and reports rounds whose updates fail to decrease.
+    ///
+    /// The returned stream contains the time and number of updates of each round at which the number of
+    /// updates fed back around the loop has not decreased for `rounds` consecutive rounds.
+    pub fn set_monitored(self, result: &Collection<G, D, R>, rounds: usize) -> (Collection<G, D, R>, Stream<G, (G::Timestamp, usize)>)
+    where G::Timestamp: Ord {
+        let step = self.step.clone();
+        let updates =
+        self.source
+            .negate()
+            .concat(result)
+            .inner
+            .flat_map(move |(x,t,d)| step.results_in(&t).map(|t| (x,t,d)));
+
+        let diagnostics = monitor(&updates, self.step, rounds);
+        updates.connect_loop(self.feedback);
+
+        (self.collection, diagnostics)
+    }
+
+    /// Adds a new source of data to the `Variable`, unless the updates of a round are small enough that `stop` holds.
+    ///
+    /// The updates fed back around the loop at each time are weighed by `weight`, and `stop` is applied to
+    /// the time and the total weight. If `stop` holds the updates at that time are discarded rather than fed
+    /// back, which concludes the iteration for that time.
+    pub fn set_until<W, P>(self, result: &Collection<G, D, R>, weight: W, stop: P) -> Collection<G, D, R>
+    where W: Fn(&D, &R)->f64+'static, P: Fn(&G::Timestamp, f64)->bool+'static {
+        let step = self.step;
+        let updates =
+        self.source
+            .negate()
+            .concat(result)
+            .inner
+            .flat_map(move |(x,t,d)| step.results_in(&t).map(|t| (x,t,d)));
+
+        gate(&updates, weight, stop).connect_loop(self.feedback);
+
+        self.collection
+    }
 }
 
 impl<G: Scope, D: Data, R: Abelian> Deref for Variable<G, D, R> where G::Timestamp: Lattice {
@@ -269,6 +710,224 @@ impl<G: Scope, D: Data, R: Monoid> MonoidVariable<G, D, R> where G::Timestamp: L
 
         self.collection
     }
+
+    /// Adds a new source of data to the `Variable`, and reports rounds whose updates fail to decrease.
+    ///
+    /// The returned stream contains the time and number of updates of each round at which the number of
+    /// updates fed back around the loop has not decreased for `rounds` consecutive rounds.
+    pub fn set_monitored(self, result: &Collection<G, D, R>, rounds: usize) -> (Collection<G, D, R>, Stream<G, (G::Timestamp, usize)>)
+    where G::Timestamp: Ord {
+        let step = self.step.clone();
+        let updates =
+        result
+            .inner
+            .flat_map(move |(x,t,d)| step.results_in(&t).map(|t| (x,t,d)));
+
+        let diagnostics = monitor(&updates, self.step, rounds);
+        updates.connect_loop(self.feedback);
+
+        (self.collection, diagnostics)
+    }
+
+    /// Adds a new source of data to the `Variable`, unless the updates of a round are small enough that `stop` holds.
+    ///
+    /// The updates fed back around the loop at each time are weighed by `weight`, and `stop` is applied to
+    /// the time and the total weight. If `stop` holds the updates at that time are discarded rather than fed
+    /// back, which concludes the iteration for that time.
+    pub fn set_until<W, P>(self, result: &Collection<G, D, R>, weight: W, stop: P) -> Collection<G, D, R>
+    where W: Fn(&D, &R)->f64+'static, P: Fn(&G::Timestamp, f64)->bool+'static {
+        let step = self.step;
+        let updates =
+        result
+            .inner
+            .flat_map(move |(x,t,d)| step.results_in(&t).map(|t| (x,t,d)));
+
+        gate(&updates, weight, stop).connect_loop(self.feedback);
+
+        self.collection
+    }
+}
+
+/// Passes along updates at each time unless `stop` holds for the time and the total weight of its updates.
+///
+/// Each worker sums the weights of its updates at a time once the time is complete, and reports the sum
+/// to all workers. Each worker then adds the sums in the order of the workers reporting them, so that all
+/// workers make the same decision for each time.
+fn gate<G: Scope, D: Data, R: Monoid, W, P>(updates: &Stream<G, (D, G::Timestamp, R)>, weight: W, stop: P) -> Stream<G, (D, G::Timestamp, R)>
+where
+    W: Fn(&D, &R)->f64+'static,
+    P: Fn(&G::Timestamp, f64)->bool+'static,
+{
+    let index = updates.scope().index();
+    let peers = updates.scope().peers();
+
+    let totals = updates.unary_frontier(Pipeline, "SumWeights", move |_,_| {
+
+        // Summed weights at times not yet complete.
+        let mut pending: Vec<(Capability<G::Timestamp>, f64)> = Vec::new();
+        let mut buffer = Vec::new();
+
+        move |input, output| {
+
+            input.for_each(|capability, data| {
+                data.swap(&mut buffer);
+                let capability = capability.retain();
+                for &(ref x, ref time, ref diff) in buffer.iter() {
+                    let amount = weight(x, diff);
+                    match pending.iter_mut().find(|entry| entry.0.time() == time) {
+                        Some(entry) => { entry.1 += amount; },
+                        None => { pending.push((capability.delayed(time), amount)); },
+                    }
+                }
+                buffer.clear();
+            });
+
+            // Report the sums of complete times to all workers.
+            let mut position = 0;
+            while position < pending.len() {
+                if !input.frontier().less_equal(pending[position].0.time()) {
+                    let (capability, total) = pending.swap_remove(position);
+                    let mut session = output.session(&capability);
+                    for peer in 0 .. peers {
+                        session.give((peer, index, capability.time().clone(), total));
+                    }
+                }
+                else {
+                    position += 1;
+                }
+            }
+        }
+    });
+
+    let exchange = Exchange::new(|x: &(usize, usize, G::Timestamp, f64)| x.0 as u64);
+    updates.binary_frontier(&totals, Pipeline, exchange, "StopWhen", move |_,_| {
+
+        // Updates at times not yet decided, and the sums reported by each worker for those times.
+        let mut stash: Vec<(Capability<G::Timestamp>, Vec<(D, G::Timestamp, R)>)> = Vec::new();
+        let mut sums: Vec<(G::Timestamp, Vec<(usize, f64)>)> = Vec::new();
+        let mut buffer1 = Vec::new();
+        let mut buffer2 = Vec::new();
+
+        move |input1, input2, output| {
+
+            input1.for_each(|capability, data| {
+                data.swap(&mut buffer1);
+                let capability = capability.retain();
+                for (x, time, diff) in buffer1.drain(..) {
+                    let position = match stash.iter().position(|entry| entry.0.time() == &time) {
+                        Some(position) => position,
+                        None => {
+                            stash.push((capability.delayed(&time), Vec::new()));
+                            stash.len() - 1
+                        },
+                    };
+                    stash[position].1.push((x, time, diff));
+                }
+            });
+
+            input2.for_each(|_capability, data| {
+                data.swap(&mut buffer2);
+                for (_, worker, time, total) in buffer2.drain(..) {
+                    match sums.iter_mut().find(|entry| entry.0 == time) {
+                        Some(entry) => { entry.1.push((worker, total)); },
+                        None => { sums.push((time, vec![(worker, total)])); },
+                    }
+                }
+            });
+
+            // Decide times for which all sums have been reported.
+            let mut position = 0;
+            while position < stash.len() {
+                let time = stash[position].0.time().clone();
+                if !input1.frontier().less_equal(&time) && !input2.frontier().less_equal(&time) {
+                    let (capability, updates) = stash.swap_remove(position);
+                    let mut total = 0.0;
+                    if let Some(entry) = sums.iter_mut().find(|entry| entry.0 == time) {
+                        entry.1.sort_by_key(|x| x.0);
+                        for &(_, amount) in entry.1.iter() { total += amount; }
+                    }
+                    if !stop(&time, total) {
+                        output.session(&capability).give_iterator(updates.into_iter());
+                    }
+                }
+                else {
+                    position += 1;
+                }
+            }
+
+            // Discard sums for times that have been decided.
+            sums.retain(|entry| input1.frontier().less_equal(&entry.0) || input2.frontier().less_equal(&entry.0));
+        }
+    })
+}
+
+/// Reports rounds whose number of updates has not decreased for `rounds` consecutive rounds.
+///
+/// A round is identified with its time, and its successor is the time that results from `step`.
+fn monitor<G: Scope, D: Data, R: Monoid>(
+    updates: &Stream<G, (D, G::Timestamp, R)>,
+    step: <G::Timestamp as Timestamp>::Summary,
+    rounds: usize,
+) -> Stream<G, (G::Timestamp, usize)>
+where G::Timestamp: Ord {
+
+    updates.unary_frontier(Pipeline, "MonitorRounds", move |_,_| {
+
+        // Numbers of updates at times not yet complete.
+        let mut pending: Vec<(Capability<G::Timestamp>, usize)> = Vec::new();
+        // For successors of complete times, the number of updates and the length of the non-decreasing run.
+        let mut expected: Vec<(G::Timestamp, usize, usize)> = Vec::new();
+        let mut buffer = Vec::new();
+        let mut ready = Vec::new();
+
+        move |input, output| {
+
+            input.for_each(|capability, data| {
+                data.swap(&mut buffer);
+                let capability = capability.retain();
+                for &(_, ref time, _) in buffer.iter() {
+                    match pending.iter_mut().find(|x| x.0.time() == time) {
+                        Some(entry) => { entry.1 += 1; },
+                        None => { pending.push((capability.delayed(time), 1)); },
+                    }
+                }
+                buffer.clear();
+            });
+
+            // Complete times no longer in advance of the input frontier, in order.
+            let mut index = 0;
+            while index < pending.len() {
+                if !input.frontier().less_equal(pending[index].0.time()) {
+                    ready.push(pending.swap_remove(index));
+                }
+                else {
+                    index += 1;
+                }
+            }
+            ready.sort_by(|x: &(Capability<G::Timestamp>, usize), y: &(Capability<G::Timestamp>, usize)| x.0.time().cmp(y.0.time()));
+
+            for (capability, count) in ready.drain(..) {
+                let time = capability.time().clone();
+                let run = match expected.iter().position(|x| x.0 == time) {
+                    Some(position) => {
+                        let (_, previous, run) = expected.swap_remove(position);
+                        if count >= previous { run + 1 } else { 0 }
+                    },
+                    None => 0,
+                };
+                if run >= rounds {
+                    output.session(&capability).give((time.clone(), count));
+                }
+                if let Some(next) = step.results_in(&time) {
+                    expected.push((next, count, run));
+                }
+            }
+
+            // Successors that can no longer receive updates had none, and end their runs.
+            let frontier = input.frontier();
+            expected.retain(|x| frontier.less_equal(&x.0));
+        }
+    })
 }
 
 impl<G: Scope, D: Data, R: Monoid> Deref for MonoidVariable<G, D, R> where G::Timestamp: Lattice {
@@ -276,4 +935,143 @@ impl<G: Scope, D: Data, R: Monoid> Deref for MonoidVariable<G, D, R> where G::Ti
     fn deref(&self) -> &Self::Target {
         &self.collection
     }
-}
\ No newline at end of file
+}
+
+/// A group of mutually recursive collections.
+///
+/// Each variable created through a `MutualRecursion` is recorded by name, and the group checks that
+/// every variable has been set once the group is dropped or `complete` is called. A variable that is
+/// never set silently contributes no feedback, which leads to incorrect results rather than an error;
+/// the group turns this mistake into a panic naming the variables that were not set.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use timely::order::Product;
+/// use timely::dataflow::Scope;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::operators::iterate::MutualRecursion;
+/// use differential_dataflow::operators::Threshold;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let zero = scope.new_collection_from(vec![0u32]).1;
+///
+///         scope.iterative::<u64,_,_>(|nested| {
+///
+///             let zero = zero.enter(nested);
+///
+///             let recursion = MutualRecursion::new(nested, Product::new(Default::default(), 1));
+///             let evens = recursion.variable_from("evens", zero.clone());
+///             let odds = recursion.variable::<u32, isize>("odds");
+///
+///             let next_odds = evens.map(|x| x + 1).filter(|x| x < &10).distinct();
+///             let next_evens = odds.map(|x| x + 1).filter(|x| x < &10).concat(&zero).distinct();
+///
+///             odds.set(&next_odds);
+///             let evens = evens.set(&next_evens);
+///             recursion.complete();
+///
+///             evens.leave()
+///         });
+///     })
+/// }
+/// ```
+pub struct MutualRecursion<G: Scope> {
+    scope: G,
+    step: <G::Timestamp as Timestamp>::Summary,
+    variables: Rc<RefCell<Vec<(String, bool)>>>,
+}
+
+impl<G: Scope> MutualRecursion<G> where G::Timestamp: Lattice {
+
+    /// Creates a new group of variables, each advancing by `step` around the loop.
+    pub fn new(scope: &G, step: <G::Timestamp as Timestamp>::Summary) -> Self {
+        MutualRecursion {
+            scope: scope.clone(),
+            step,
+            variables: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Creates a new initially empty variable in the group.
+    pub fn variable<D: Data, R: Abelian>(&self, name: &str) -> RecursiveVariable<G, D, R> {
+        let mut scope = self.scope.clone();
+        self.register(name, Variable::new(&mut scope, self.step.clone()))
+    }
+
+    /// Creates a new variable in the group from a supplied `source` collection.
+    pub fn variable_from<D: Data, R: Abelian>(&self, name: &str, source: Collection<G, D, R>) -> RecursiveVariable<G, D, R> {
+        self.register(name, Variable::new_from(source, self.step.clone()))
+    }
+
+    fn register<D: Data, R: Abelian>(&self, name: &str, variable: Variable<G, D, R>) -> RecursiveVariable<G, D, R> {
+        let mut variables = self.variables.borrow_mut();
+        if variables.iter().any(|x| x.0 == name) {
+            panic!("MutualRecursion: variable {:?} declared twice", name);
+        }
+        variables.push((name.to_string(), false));
+        RecursiveVariable {
+            variable,
+            index: variables.len() - 1,
+            variables: self.variables.clone(),
+        }
+    }
+}
+
+impl<G: Scope> MutualRecursion<G> {
+
+    /// Checks that all variables in the group have been set, panicking otherwise.
+    pub fn complete(self) {
+        // The check is performed when `self` is dropped.
+    }
+
+    /// The names of variables in the group that have not been set.
+    pub fn unset(&self) -> Vec<String> {
+        self.variables
+            .borrow()
+            .iter()
+            .filter(|x| !x.1)
+            .map(|x| x.0.clone())
+            .collect()
+    }
+}
+
+impl<G: Scope> Drop for MutualRecursion<G> {
+    fn drop(&mut self) {
+        if !::std::thread::panicking() {
+            let unset = self.unset();
+            if !unset.is_empty() {
+                panic!("MutualRecursion: variables not set: {:?}", unset);
+            }
+        }
+    }
+}
+
+/// A variable in a group of mutually recursive collections.
+pub struct RecursiveVariable<G: Scope, D: Data, R: Abelian>
+where G::Timestamp: Lattice {
+    variable: Variable<G, D, R>,
+    index: usize,
+    variables: Rc<RefCell<Vec<(String, bool)>>>,
+}
+
+impl<G: Scope, D: Data, R: Abelian> RecursiveVariable<G, D, R> where G::Timestamp: Lattice {
+    /// Sets the variable to `result`, recording it as set in its group.
+    pub fn set(self, result: &Collection<G, D, R>) -> Collection<G, D, R> {
+        self.variables.borrow_mut()[self.index].1 = true;
+        self.variable.set(result)
+    }
+}
+
+impl<G: Scope, D: Data, R: Abelian> Deref for RecursiveVariable<G, D, R> where G::Timestamp: Lattice {
+    type Target = Collection<G, D, R>;
+    fn deref(&self) -> &Self::Target {
+        &self.variable
+    }
+}
@@ -18,7 +18,8 @@ use hashable::Hashable;
 use ::{Data, Collection, AsCollection};
 use ::difference::{Monoid, Abelian};
 use lattice::Lattice;
-use operators::arrange::{Arranged, ArrangeByKey, ArrangeBySelf};
+use operators::arrange::{Arrange, Arranged, ArrangeByKey, ArrangeBySelf};
+use trace::implementations::spill::OrdValSpill;
 use trace::{BatchReader, Cursor, consolidate};
 use operators::ValueHistory;
 
@@ -203,6 +204,152 @@ impl<G, K, V, R, T> Join<G, K, V, R> for Arranged<G,K,V,R,T>
     }
 }
 
+/// A key extraction function selected at runtime.
+pub type KeyFunction<D, K> = Box<Fn(&D)->K>;
+/// A join result function selected at runtime.
+pub type ResultFunction<K, D1, D2, D> = Box<Fn(&K, &D1, &D2)->D>;
+
+/// Join implementations whose key extraction and result logic are selected at runtime.
+///
+/// The methods in `Join` and `JoinCore` are generic over their closures, which means that each use
+/// of a join must be known at compile time. Systems that assemble dataflows from plans produced at
+/// runtime can instead use boxed functions, at the cost of dynamic dispatch for each record.
+pub trait JoinDynamic<G: Scope, D: Data, R: Monoid> where G::Timestamp: Lattice+Ord {
+    /// Matches pairs of records from two collections whose keys, as produced by `key1` and `key2`, are equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::join::JoinDynamic;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![vec![0, 1], vec![1, 3]]).1;
+    ///         let y = scope.new_collection_from(vec![vec![5, 0], vec![6, 1]]).1;
+    ///         let z = scope.new_collection_from(vec![vec![0, 1, 5], vec![1, 3, 6]]).1;
+    ///
+    ///         // keys chosen at runtime, perhaps from a query plan.
+    ///         let (column1, column2) = (0, 1);
+    ///
+    ///         x.join_dynamic(
+    ///             &y,
+    ///             Box::new(move |r: &Vec<i32>| r[column1]),
+    ///             Box::new(move |r: &Vec<i32>| r[column2]),
+    ///             Box::new(move |_k, r1: &Vec<i32>, r2: &Vec<i32>| vec![r1[0], r1[1], r2[0]]),
+    ///          )
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn join_dynamic<D2, K, O, R2>(
+        &self,
+        other: &Collection<G, D2, R2>,
+        key1: KeyFunction<D, K>,
+        key2: KeyFunction<D2, K>,
+        result: ResultFunction<K, D, D2, O>,
+    ) -> Collection<G, O, <R as Mul<R2>>::Output>
+    where
+        D2: Data,
+        K: Data+Hashable,
+        O: Data,
+        R2: Monoid,
+        R: Mul<R2>,
+        <R as Mul<R2>>::Output: Monoid;
+}
+
+impl<G, D, R> JoinDynamic<G, D, R> for Collection<G, D, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    D: Data,
+    R: Monoid,
+{
+    fn join_dynamic<D2, K, O, R2>(
+        &self,
+        other: &Collection<G, D2, R2>,
+        key1: KeyFunction<D, K>,
+        key2: KeyFunction<D2, K>,
+        result: ResultFunction<K, D, D2, O>,
+    ) -> Collection<G, O, <R as Mul<R2>>::Output>
+    where
+        D2: Data,
+        K: Data+Hashable,
+        O: Data,
+        R2: Monoid,
+        R: Mul<R2>,
+        <R as Mul<R2>>::Output: Monoid,
+    {
+        let arranged1 = self.map(move |x| (key1(&x), x)).arrange_by_key();
+        let arranged2 = other.map(move |x| (key2(&x), x)).arrange_by_key();
+        arranged1.join_core(&arranged2, move |k,v1,v2| Some(result(k,v1,v2)))
+    }
+}
+
+/// Join implementations whose arrangements may spill to disk.
+///
+/// These methods arrange their inputs in `OrdValSpill` traces, which write their oldest batches to disk
+/// once they exceed a memory budget. The budget and the directory spilled batches are written to are
+/// taken from the environment, as described in the `spill` module. Joins whose inputs must be retained
+/// in full, for example many-to-many joins of large collections, can use these methods to complete with
+/// bounded memory, at the expense of reading batches from disk as updates arrive.
+pub trait JoinSpilled<G: Scope, K: Data, V: Data, R: Monoid> where G::Timestamp: Lattice+Ord {
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, using arrangements that may spill to disk.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::join::JoinSpilled;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (1, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, (1, 'a')), (1, (3, 'b'))]).1;
+    ///
+    ///         x.join_spilled(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn join_spilled<V2: Data, R2: Monoid>(&self, other: &Collection<G, (K,V2), R2>) -> Collection<G, (K,(V,V2)), <R as Mul<R2>>::Output>
+    where R: Mul<R2>, <R as Mul<R2>>::Output: Monoid
+    {
+        self.join_spilled_map(other, |k,v,v2| (k.clone(),(v.clone(),v2.clone())))
+    }
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key` and then applies a function, using
+    /// arrangements that may spill to disk.
+    fn join_spilled_map<V2: Data, R2: Monoid, D: Data, L>(&self, other: &Collection<G, (K,V2), R2>, logic: L) -> Collection<G, D, <R as Mul<R2>>::Output>
+    where R: Mul<R2>, <R as Mul<R2>>::Output: Monoid, L: Fn(&K, &V, &V2)->D+'static;
+}
+
+impl<G, K, V, R> JoinSpilled<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: Data+Hashable,
+    V: Data,
+    R: Monoid,
+{
+    fn join_spilled_map<V2: Data, R2: Monoid, D: Data, L>(&self, other: &Collection<G, (K,V2), R2>, logic: L) -> Collection<G, D, <R as Mul<R2>>::Output>
+    where R: Mul<R2>, <R as Mul<R2>>::Output: Monoid, L: Fn(&K, &V, &V2)->D+'static
+    {
+        let arranged1 = self.arrange::<OrdValSpill<K, V, G::Timestamp, R>>();
+        let arranged2 = other.arrange::<OrdValSpill<K, V2, G::Timestamp, R2>>();
+        arranged1.join_core(&arranged2, move |k,v1,v2| Some(logic(k,v1,v2)))
+    }
+}
+
 /// Matches the elements of two arranged traces.
 ///
 /// This method is used by the various `join` implementations, but it can also be used
@@ -257,6 +404,84 @@ pub trait JoinCore<G: Scope, K: 'static, V: 'static, R: Monoid> where G::Timesta
         I::Item: Data,
         L: Fn(&K,&V,&V2)->I+'static,
         ;
+
+    /// Joins two arranged collections with the same key type, diverting errors to a second collection.
+    ///
+    /// This method is like `join_core`, except that the `result` function may fail. Rather than panicking,
+    /// the function can return an error, which is reported in the second returned collection with the
+    /// multiplicity the results would have had. Errors are data like any other, and they will be retracted
+    /// if the records that produced them are retracted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::operators::join::JoinCore;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0u32, 1), (1, 3)]).1
+    ///                      .arrange_by_key();
+    ///         let y = scope.new_collection_from(vec![(0, 0), (1, 1)]).1
+    ///                      .arrange_by_key();
+    ///
+    ///         let z = scope.new_collection_from(vec![3]).1;
+    ///         let e = scope.new_collection_from(vec![format!("division by zero")]).1;
+    ///
+    ///         let (results, errors) =
+    ///         x.join_core_fallible(&y, |_key, &a, &b| {
+    ///             if b == 0 { Err(format!("division by zero")) }
+    ///             else { Ok(Some(a / b)) }
+    ///         });
+    ///
+    ///         results.assert_eq(&z);
+    ///         errors.assert_eq(&e);
+    ///     });
+    /// }
+    /// ```
+    fn join_core_fallible<V2,T2,R2,I,E,L> (&self, stream2: &Arranged<G,K,V2,R2,T2>, result: L) -> (Collection<G,I::Item,<R as Mul<R2>>::Output>, Collection<G,E,<R as Mul<R2>>::Output>)
+    where
+        V2: Ord+Clone+Debug+'static,
+        T2: TraceReader<K, V2, G::Timestamp, R2>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R2>+'static,
+        R2: Monoid,
+        R: Mul<R2>,
+        <R as Mul<R2>>::Output: Monoid,
+        I: IntoIterator,
+        I::Item: Data,
+        E: Data,
+        L: Fn(&K,&V,&V2)->Result<I,E>+'static,
+    {
+        let results = self.join_core(stream2, move |k,v1,v2| {
+            match result(k,v1,v2) {
+                Ok(iter) => Fallible::Ok(iter.into_iter()),
+                Err(error) => Fallible::Err(Some(error)),
+            }
+        });
+
+        (results.flat_map(|x| x.ok()), results.flat_map(|x| x.err()))
+    }
+}
+
+/// An iterator over either successful results or a single error.
+enum Fallible<I, E> {
+    Ok(I),
+    Err(Option<E>),
+}
+
+impl<I: Iterator, E> Iterator for Fallible<I, E> {
+    type Item = Result<I::Item, E>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Fallible::Ok(iter) => iter.next().map(Ok),
+            Fallible::Err(error) => error.take().map(Err),
+        }
+    }
 }
 
 
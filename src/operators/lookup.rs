@@ -0,0 +1,185 @@
+//! Enriches records with values looked up from an external service.
+//!
+//! The `lookup_join` operator extracts a key from each record, requests the value associated with
+//! the key from a `LookupClient`, and produces the result of applying a function to the record and
+//! the value. Requests are asynchronous: records wait, holding capabilities for their times, until
+//! the client reports a response for their key, and the operator reschedules itself while requests
+//! are outstanding.
+//!
+//! The value reported for a key is retained as long as some record with the key has a non-zero
+//! accumulated difference, and is used for all such records. This ensures that the retraction of a
+//! record retracts exactly the enrichment it produced, even if the external service would now report
+//! a different value. Once no records with the key remain the value is discarded, and later records
+//! with the key result in a new request.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use timely::dataflow::*;
+use timely::dataflow::operators::{Capability, Operator};
+use timely::dataflow::channels::pact::Exchange;
+use timely::scheduling::Activator;
+
+use timely_sort::Unsigned;
+
+use ::{Data, Collection, AsCollection, Hashable};
+use ::difference::Monoid;
+use trace::consolidate;
+
+/// A client of an external service associating values with keys.
+pub trait LookupClient<K, V> {
+    /// Requests the value associated with `key`.
+    ///
+    /// The request should not block; its response is reported by a later call to `poll`.
+    fn request(&mut self, key: &K);
+    /// Reports responses to earlier requests, as each key and its value if one exists.
+    fn poll(&mut self, responses: &mut Vec<(K, Option<V>)>);
+}
+
+/// Extension trait for the `lookup_join` differential dataflow method.
+pub trait LookupJoin<G: Scope, D: Data, R: Monoid> {
+    /// Enriches each record with the value associated with its key by `client`.
+    ///
+    /// Records whose key has no associated value produce no output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use std::collections::HashMap;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::lookup::{LookupClient, LookupJoin};
+    ///
+    /// /// A client that responds immediately from a map.
+    /// struct MapClient {
+    ///     map: HashMap<u32, String>,
+    ///     requests: Vec<u32>,
+    /// }
+    ///
+    /// impl LookupClient<u32, String> for MapClient {
+    ///     fn request(&mut self, key: &u32) {
+    ///         self.requests.push(*key);
+    ///     }
+    ///     fn poll(&mut self, responses: &mut Vec<(u32, Option<String>)>) {
+    ///         for key in self.requests.drain(..) {
+    ///             responses.push((key, self.map.get(&key).cloned()));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let mut map = HashMap::new();
+    ///         map.insert(0, "zero".to_string());
+    ///         map.insert(1, "one".to_string());
+    ///         let client = MapClient { map, requests: Vec::new() };
+    ///
+    ///         let names = scope.new_collection_from(vec![(0, 'a'), (1, 'b'), (2, 'c')]).1;
+    ///         let expected = scope.new_collection_from(vec![('a', "zero".to_string()), ('b', "one".to_string())]).1;
+    ///
+    ///         names.lookup_join(client, |x| x.0, |x, name| (x.1, name.clone()))
+    ///              .assert_eq(&expected);
+    ///     });
+    /// }
+    /// ```
+    fn lookup_join<K, V, D2, C, KF, L>(&self, client: C, key: KF, logic: L) -> Collection<G, D2, R>
+    where
+        K: Data+Hash,
+        V: Clone+'static,
+        D2: Data,
+        C: LookupClient<K, V>+'static,
+        KF: Fn(&D)->K+'static,
+        L: Fn(&D, &V)->D2+'static;
+}
+
+impl<G: Scope, D: Data+Hashable, R: Monoid> LookupJoin<G, D, R> for Collection<G, D, R> {
+
+    fn lookup_join<K, V, D2, C, KF, L>(&self, mut client: C, key: KF, logic: L) -> Collection<G, D2, R>
+    where
+        K: Data+Hash,
+        V: Clone+'static,
+        D2: Data,
+        C: LookupClient<K, V>+'static,
+        KF: Fn(&D)->K+'static,
+        L: Fn(&D, &V)->D2+'static,
+    {
+        // Each record must be enriched by the same worker at all times, so that its retraction
+        // uses the same value as its introduction.
+        let exchange = Exchange::new(|update: &(D,G::Timestamp,R)| update.0.hashed().as_u64());
+
+        // Values for keys, and the accumulated records with each key.
+        let mut values: HashMap<K, (Option<V>, Vec<(D, R)>)> = HashMap::new();
+        // Records awaiting a response, by key.
+        let mut waiting: HashMap<K, Vec<(Capability<G::Timestamp>, D, G::Timestamp, R)>> = HashMap::new();
+
+        let mut buffer = Vec::new();
+        let mut responses = Vec::new();
+
+        self.inner.unary(exchange, "LookupJoin", move |_cap, info| {
+
+            let activations = self.scope().activations().clone();
+            let activator = Activator::new(&info.address[..], activations);
+
+            move |input, output| {
+
+                input.for_each(|capability, data| {
+                    data.swap(&mut buffer);
+                    let capability = capability.retain();
+                    let mut session = output.session(&capability);
+                    for (data, time, diff) in buffer.drain(..) {
+                        let key = key(&data);
+                        let evict = match values.get_mut(&key) {
+                            Some(&mut (ref value, ref mut records)) => {
+                                if let Some(ref value) = *value {
+                                    session.give((logic(&data, value), time, diff.clone()));
+                                }
+                                records.push((data, diff));
+                                consolidate(records, 0);
+                                records.is_empty()
+                            },
+                            None => {
+                                let list = waiting.entry(key.clone()).or_insert_with(Vec::new);
+                                if list.is_empty() {
+                                    client.request(&key);
+                                }
+                                list.push((capability.clone(), data, time, diff));
+                                false
+                            },
+                        };
+                        if evict {
+                            values.remove(&key);
+                        }
+                    }
+                });
+
+                // Produce output for records whose keys have responses.
+                client.poll(&mut responses);
+                for (key, value) in responses.drain(..) {
+                    if let Some(list) = waiting.remove(&key) {
+                        let mut records = Vec::with_capacity(list.len());
+                        for (capability, data, time, diff) in list.into_iter() {
+                            if let Some(ref value) = value {
+                                output.session(&capability).give((logic(&data, value), time, diff.clone()));
+                            }
+                            records.push((data, diff));
+                        }
+                        consolidate(&mut records, 0);
+                        if !records.is_empty() {
+                            values.insert(key, (value, records));
+                        }
+                    }
+                }
+
+                // Poll again while requests are outstanding.
+                if !waiting.is_empty() {
+                    activator.activate();
+                }
+            }
+        })
+        .as_collection()
+    }
+}
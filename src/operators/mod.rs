@@ -6,18 +6,26 @@
 
 pub use self::reduce::{Reduce, Threshold, Count, consolidate_from};
 pub use self::consolidate::Consolidate;
-pub use self::iterate::Iterate;
-pub use self::join::{Join, JoinCore};
+pub use self::iterate::{Iterate, IterateMonotonic, IterateNested};
+pub use self::join::{Join, JoinCore, JoinDynamic, JoinSpilled};
 pub use self::count::CountTotal;
 pub use self::threshold::ThresholdTotal;
+pub use self::extremum::ExtremumTotal;
+pub use self::audit::Audit;
+pub use self::half_join::HalfJoin;
 
 pub mod arrange;
 pub mod reduce;
 pub mod consolidate;
 pub mod iterate;
 pub mod join;
+pub mod half_join;
 pub mod count;
 pub mod threshold;
+pub mod extremum;
+pub mod upsert;
+pub mod lookup;
+pub mod audit;
 
 use ::difference::Monoid;
 use lattice::Lattice;
@@ -0,0 +1,346 @@
+//! Conversion between streams of upserts and collections.
+//!
+//! Many external sources of data, for example change data capture from key-value stores, describe
+//! their changes as *upserts*: a key and the new value for the key, if any, at some time. These are
+//! not differential updates, as they do not indicate which prior value is replaced. The operator in
+//! this module maintains the current value of each key, and converts each upsert into the retraction
+//! of the prior value and the addition of the new value, producing a correctly retracting arrangement.
+//!
+//! The `ToUpsert` trait provides the inverse conversion, from a keyed collection to a stream of upserts.
+//!
+//! The operators require totally ordered timestamps, so that for each key and time there is a well-defined
+//! prior value. Multiple upserts for the same key at the same time are resolved in favor of the last one
+//! received by the worker, and because the stream is exchanged by key, by the last one sent by any single
+//! upstream worker.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate timely;
+//! extern crate differential_dataflow;
+//!
+//! use timely::dataflow::operators::ToStream;
+//! use differential_dataflow::operators::upsert::arrange_from_upsert;
+//! use differential_dataflow::trace::implementations::ord::OrdValSpine;
+//!
+//! fn main() {
+//!     ::timely::example(|scope| {
+//!
+//!         let upserts = vec![
+//!             (0, Some(10), 0),
+//!             (0, Some(11), 1),
+//!             (1, Some(12), 1),
+//!             (0, None, 2),
+//!         ];
+//!
+//!         arrange_from_upsert::<_,_,_,OrdValSpine<_,_,_,_>>(&upserts.to_stream(scope), "Upsert")
+//!             .as_collection(|k,v| (k.clone(), v.clone()))
+//!             .inspect(|x| println!("{:?}", x));
+//!     });
+//! }
+//! ```
+
+use timely::order::{PartialOrder, TotalOrder};
+use timely::dataflow::{Scope, Stream};
+use timely::dataflow::operators::generic::Operator;
+use timely::dataflow::channels::pact::{Pipeline, Exchange};
+use timely::dataflow::operators::Capability;
+
+use timely_sort::Unsigned;
+
+use ::{Data, Collection, Hashable};
+use ::difference::Monoid;
+use lattice::Lattice;
+use trace::{Trace, TraceReader, Batch, BatchReader, Batcher, Cursor, consolidate};
+use operators::arrange::{Arranged, ArrangeByKey, TraceAgent};
+
+/// Arranges a stream of `(key, value, time)` upserts by key.
+///
+/// Each upsert indicates that at `time` the value associated with `key` becomes `value`, or that
+/// the key is removed if `value` is `None`. The resulting arrangement contains for each key at most
+/// one value at each time, with differences of `1`.
+pub fn arrange_from_upsert<G, K, V, Tr>(
+    stream: &Stream<G, (K, Option<V>, G::Timestamp)>,
+    name: &str,
+) -> Arranged<G, K, V, isize, TraceAgent<K, V, G::Timestamp, isize, Tr>>
+where
+    G: Scope,
+    G::Timestamp: Lattice+TotalOrder+Ord,
+    K: Data+Hashable,
+    V: Data,
+    Tr: Trace<K, V, G::Timestamp, isize>+'static,
+    Tr::Batch: Batch<K, V, G::Timestamp, isize>,
+{
+    let mut reader = None;
+
+    let stream = {
+
+        let reader = &mut reader;
+        let exchange = Exchange::new(move |update: &(K,Option<V>,G::Timestamp)| (update.0).hashed().as_u64());
+
+        stream.unary_frontier(exchange, name, move |_capability, info| {
+
+            // Attempt to acquire a logger for arrange events.
+            let logger = {
+                let scope = stream.scope();
+                let register = scope.log_register();
+                register.get::<::logging::DifferentialEvent>("differential/arrange")
+            };
+
+            // Upserts not yet applied, and a capability for the least of their times.
+            let mut stash: Vec<(K, Option<V>, G::Timestamp)> = Vec::new();
+            let mut capability: Option<Capability<G::Timestamp>> = None;
+
+            let mut batcher = <Tr::Batch as Batch<K,V,G::Timestamp,isize>>::Batcher::new();
+            let mut buffer = Vec::new();
+            let mut updates = Vec::new();
+
+            let operator = info.global_id;
+            let empty_trace = Tr::new(info, logger.clone());
+            let (reader_local, mut writer) = TraceAgent::new_with_logging(empty_trace, operator, logger);
+            // A handle to the trace we maintain, used to find prior values of keys.
+            let mut current = reader_local.clone();
+            *reader = Some(reader_local);
+
+            move |input, output| {
+
+                input.for_each(|cap, data| {
+                    if capability.as_ref().map(|c| cap.time().less_than(c.time())).unwrap_or(true) {
+                        capability = Some(cap.retain());
+                    }
+                    data.swap(&mut buffer);
+                    stash.extend(buffer.drain(..));
+                });
+
+                let frontier = input.frontier().frontier().to_vec();
+                let complete = capability.as_ref().map(|c| !frontier.iter().any(|t| t.less_equal(c.time()))).unwrap_or(false);
+                if complete {
+
+                    // Extract upserts at times not beyond the frontier, in order of key and then time.
+                    // The sort is stable, so upserts for the same key and time remain in arrival order.
+                    let (mut ready, pending): (Vec<_>, Vec<_>) =
+                    stash
+                        .drain(..)
+                        .partition(|x| !frontier.iter().any(|t| t.less_equal(&x.2)));
+                    stash = pending;
+                    ready.sort_by(|x,y| (&x.0, &x.2).cmp(&(&y.0, &y.2)));
+
+                    let (mut cursor, storage) = current.cursor();
+
+                    let mut position = 0;
+                    while position < ready.len() {
+
+                        // Determine the current value of the key.
+                        let key = ready[position].0.clone();
+                        let mut prior: Option<V> = None;
+                        cursor.seek_key(&storage, &key);
+                        if cursor.get_key(&storage) == Some(&key) {
+                            while let Some(val) = cursor.get_val(&storage) {
+                                let mut count = 0;
+                                cursor.map_times(&storage, |_time, diff| count += diff);
+                                if count > 0 {
+                                    prior = Some(val.clone());
+                                }
+                                cursor.step_val(&storage);
+                            }
+                        }
+
+                        // Apply each upsert for the key, retracting the prior value.
+                        while position < ready.len() && ready[position].0 == key {
+                            let value = ready[position].1.take();
+                            let time = ready[position].2.clone();
+                            // Only the last upsert at each time takes effect.
+                            let last = position + 1 == ready.len() || ready[position + 1].0 != key || ready[position + 1].2 != time;
+                            if last && value != prior {
+                                if let Some(prior) = prior.take() {
+                                    updates.push(((key.clone(), prior), time.clone(), -1));
+                                }
+                                if let Some(value) = value.as_ref() {
+                                    updates.push(((key.clone(), value.clone()), time.clone(), 1));
+                                }
+                                prior = value;
+                            }
+                            position += 1;
+                        }
+                    }
+
+                    batcher.push_batch(&mut updates);
+                    let batch = batcher.seal(&frontier[..]);
+
+                    if let Some(capability) = capability.as_ref() {
+                        writer.seal(&frontier[..], Some((capability.time().clone(), batch.clone())));
+                        output.session(capability).give(batch);
+                    }
+
+                    // Retain a capability for the least time of the remaining upserts.
+                    let least = stash.iter().map(|x| x.2.clone()).min();
+                    capability = match (capability.take(), least) {
+                        (Some(capability), Some(time)) => Some(capability.delayed(&time)),
+                        _ => None,
+                    };
+                }
+                else {
+                    writer.seal(&frontier[..], None);
+                }
+
+                // Allow the trace to compact, as we only need current values of keys.
+                current.advance_by(&frontier[..]);
+                current.distinguish_since(&frontier[..]);
+            }
+        })
+    };
+
+    Arranged { stream: stream, trace: reader.unwrap() }
+}
+
+/// Extension trait for converting keyed collections into streams of upserts.
+pub trait ToUpsert<G: Scope, K: Data, V: Data, R: Monoid> where G::Timestamp: TotalOrder+Lattice+Ord {
+    /// Converts a collection of `(key, val)` records into a stream of upserts.
+    ///
+    /// At each time at which the values associated with a key change, the operator produces an upsert
+    /// whose value is the value of the key with the greatest accumulated multiplicity, breaking ties in
+    /// favor of the least value, or `None` if the key has no values. This is the inverse of the
+    /// `arrange_from_upsert` operator, and is meant for writing collections to key-value stores.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use timely::dataflow::operators::Inspect;
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::upsert::ToUpsert;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .to_upsert()
+    ///              .inspect(|x| println!("{:?}", x));
+    ///     });
+    /// }
+    /// ```
+    fn to_upsert(&self) -> Stream<G, (K, Option<V>, G::Timestamp)> {
+        use timely::dataflow::operators::Map;
+        self.to_upsert_core(false)
+            .flat_map(|(key, value, time)| value.ok().map(|value| (key, value, time)))
+    }
+
+    /// Converts a collection of `(key, val)` records into a stream of upserts, reporting conflicts.
+    ///
+    /// Rather than choose among multiple values for a key, this method reports the key and its values in
+    /// the second stream at each time at which the key has multiple values. No upserts are produced for
+    /// the key until it again has at most one value.
+    fn to_upsert_checked(&self) -> (Stream<G, (K, Option<V>, G::Timestamp)>, Stream<G, (K, Vec<(V, R)>, G::Timestamp)>) {
+        use timely::dataflow::operators::Map;
+        let results = self.to_upsert_core(true);
+        let upserts = results.flat_map(|(key, value, time)| value.ok().map(|value| (key, value, time)));
+        let errors = results.flat_map(|(key, value, time)| value.err().map(|values| (key, values, time)));
+        (upserts, errors)
+    }
+
+    /// Converts a collection of `(key, val)` records into a stream of upserts or conflicts.
+    ///
+    /// If `checked` is set, keys with multiple values produce the list of values and their multiplicities
+    /// as errors. Otherwise, keys with multiple values produce the value with greatest multiplicity.
+    fn to_upsert_core(&self, checked: bool) -> Stream<G, (K, Result<Option<V>, Vec<(V, R)>>, G::Timestamp)>;
+}
+
+impl<G: Scope, K: Data+Hashable, V: Data, R: Monoid> ToUpsert<G, K, V, R> for Collection<G, (K, V), R>
+where G::Timestamp: TotalOrder+Lattice+Ord {
+    fn to_upsert_core(&self, checked: bool) -> Stream<G, (K, Result<Option<V>, Vec<(V, R)>>, G::Timestamp)> {
+        self.arrange_by_key()
+            .to_upsert_core(checked)
+    }
+}
+
+impl<G: Scope, K: Data, V: Data, R: Monoid, T1> ToUpsert<G, K, V, R> for Arranged<G, K, V, R, T1>
+where
+    G::Timestamp: TotalOrder+Lattice+Ord,
+    T1: TraceReader<K, V, G::Timestamp, R>+Clone+'static,
+    T1::Batch: BatchReader<K, V, G::Timestamp, R> {
+
+    fn to_upsert_core(&self, checked: bool) -> Stream<G, (K, Result<Option<V>, Vec<(V, R)>>, G::Timestamp)> {
+
+        let mut trace = self.trace.clone();
+        let mut buffer = Vec::new();
+
+        let mut accum = Vec::new();
+        let mut edits = Vec::new();
+
+        self.stream.unary(Pipeline, "ToUpsert", move |_,_| move |input, output| {
+
+            input.for_each(|capability, batches| {
+                batches.swap(&mut buffer);
+                let mut session = output.session(&capability);
+                for batch in buffer.drain(..) {
+
+                    let mut batch_cursor = batch.cursor();
+                    let (mut trace_cursor, trace_storage) = trace.cursor_through(batch.lower()).unwrap();
+
+                    while batch_cursor.key_valid(&batch) {
+
+                        let key = batch_cursor.key(&batch);
+
+                        // Accumulate the values of the key prior to the batch.
+                        accum.clear();
+                        trace_cursor.seek_key(&trace_storage, key);
+                        if trace_cursor.key_valid(&trace_storage) && trace_cursor.key(&trace_storage) == key {
+                            while trace_cursor.val_valid(&trace_storage) {
+                                let mut count = R::zero();
+                                trace_cursor.map_times(&trace_storage, |_, diff| count += diff);
+                                if !count.is_zero() {
+                                    accum.push((trace_cursor.val(&trace_storage).clone(), count));
+                                }
+                                trace_cursor.step_val(&trace_storage);
+                            }
+                        }
+                        let mut prior = select(&accum[..], checked);
+
+                        // Collect the changes to the key in the batch, ordered by time.
+                        edits.clear();
+                        while batch_cursor.val_valid(&batch) {
+                            let val = batch_cursor.val(&batch);
+                            batch_cursor.map_times(&batch, |time, diff| edits.push((time.clone(), val.clone(), diff.clone())));
+                            batch_cursor.step_val(&batch);
+                        }
+                        edits.sort_by(|x,y| x.0.cmp(&y.0));
+
+                        // Apply changes one time at a time, reporting any change in the selected value.
+                        let mut position = 0;
+                        while position < edits.len() {
+                            let time = edits[position].0.clone();
+                            while position < edits.len() && edits[position].0 == time {
+                                accum.push((edits[position].1.clone(), edits[position].2.clone()));
+                                position += 1;
+                            }
+                            consolidate(&mut accum, 0);
+                            let next = select(&accum[..], checked);
+                            if next != prior {
+                                session.give((key.clone(), next.clone(), time));
+                                prior = next;
+                            }
+                        }
+
+                        batch_cursor.step_key(&batch);
+                    }
+
+                    // tidy up the shared input trace.
+                    trace.advance_by(batch.upper());
+                    trace.distinguish_since(batch.upper());
+                }
+            });
+        })
+    }
+}
+
+/// Selects the value with greatest multiplicity, or reports multiple values if `checked` is set.
+fn select<V: Ord+Clone, R: Ord+Clone>(accum: &[(V, R)], checked: bool) -> Result<Option<V>, Vec<(V, R)>> {
+    if checked && accum.len() > 1 {
+        Err(accum.to_vec())
+    }
+    else {
+        Ok(accum.iter().max_by(|x,y| x.1.cmp(&y.1).then(y.0.cmp(&x.0))).map(|x| x.0.clone()))
+    }
+}
@@ -0,0 +1,298 @@
+//! Coordinated output of collections to external systems.
+//!
+//! A collection written to several external systems can leave those systems in inconsistent
+//! states if one of them fails partway through a time: some systems reflect the updates at the
+//! time and others do not. The types in this module coordinate several sinks using a two-phase
+//! protocol. Each sink first *prepares* the updates through some frontier, making them durable
+//! but not yet visible. Once every sink has prepared through a frontier, the coordinator asks
+//! each sink to *commit* through that frontier. If any sink fails to prepare, the coordinator
+//! asks all sinks to *abort* their prepared updates and commits nothing further. If a sink fails
+//! to commit, the coordinator asks the sinks that have already committed through the frontier to
+//! *roll back* to the previously committed frontier, asks the others to abort, and likewise
+//! commits nothing further.
+//!
+//! The coordinator is local to a worker, and coordinates the sinks of that worker. Sinks must
+//! each report the frontier through which they have committed for recovery across workers to
+//! be consistent.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate timely;
+//! extern crate differential_dataflow;
+//!
+//! use std::rc::Rc;
+//! use std::cell::RefCell;
+//!
+//! use differential_dataflow::input::Input;
+//! use differential_dataflow::sink::{Coordinator, CoordinatedSink, Participant, Sink};
+//!
+//! /// Buffers prepared updates, and publishes them on commit.
+//! struct VecSink {
+//!     prepared: Vec<(u32, usize, isize)>,
+//!     visible: Vec<(u32, usize, isize)>,
+//! }
+//!
+//! impl Participant<usize> for VecSink {
+//!     fn commit(&mut self, _upper: &[usize]) -> Result<(), String> {
+//!         self.visible.extend(self.prepared.drain(..));
+//!         Ok(())
+//!     }
+//!     fn abort(&mut self, _upper: &[usize]) {
+//!         self.prepared.clear();
+//!     }
+//! }
+//!
+//! impl Sink<u32, usize, isize> for VecSink {
+//!     fn prepare(&mut self, _upper: &[usize], updates: &mut Vec<(u32, usize, isize)>) -> Result<(), String> {
+//!         self.prepared.extend(updates.drain(..));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! fn main() {
+//!     ::timely::execute(::timely::Configuration::Thread, |worker| {
+//!
+//!         let coordinator = Coordinator::new();
+//!         let sink1 = Rc::new(RefCell::new(VecSink { prepared: Vec::new(), visible: Vec::new() }));
+//!         let sink2 = Rc::new(RefCell::new(VecSink { prepared: Vec::new(), visible: Vec::new() }));
+//!
+//!         worker.dataflow::<usize,_,_>(|scope| {
+//!             let data = scope.new_collection_from(1 .. 10u32).1;
+//!             data.sink_coordinated(&coordinator, sink1.clone());
+//!             data.map(|x| x + 1).sink_coordinated(&coordinator, sink2.clone());
+//!         });
+//!
+//!         while worker.step() { }
+//!
+//!         // Both sinks have committed all of their updates.
+//!         assert!(coordinator.committed().elements().is_empty());
+//!         assert_eq!(sink1.borrow().visible.len(), 9);
+//!         assert_eq!(sink2.borrow().visible.len(), 9);
+//!         assert!(sink1.borrow().prepared.is_empty());
+//!
+//!     }).unwrap();
+//! }
+//! ```
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::order::PartialOrder;
+use timely::progress::frontier::Antichain;
+use timely::dataflow::Scope;
+
+use ::{Collection, Data};
+use ::difference::Monoid;
+
+/// A participant in a two-phase commit.
+pub trait Participant<T> {
+    /// Makes visible all updates prepared at times not greater or equal to an element of `upper`.
+    fn commit(&mut self, upper: &[T]) -> Result<(), String>;
+    /// Discards all prepared but uncommitted updates.
+    ///
+    /// The argument `upper` is the frontier through which updates have been committed.
+    fn abort(&mut self, upper: &[T]);
+    /// Reverts the most recent commit, so that only updates committed through `upper` are visible,
+    /// and discards all prepared but uncommitted updates.
+    ///
+    /// This is called when another participant fails to commit the frontier this participant last
+    /// committed. Participants that cannot revert commits may keep the default implementation, which
+    /// only aborts, but the external systems may then be left inconsistent.
+    fn rollback(&mut self, upper: &[T]) {
+        self.abort(upper);
+    }
+}
+
+/// An external system that receives updates in two phases.
+pub trait Sink<D, T, R> : Participant<T> {
+    /// Durably stages `updates`, which contains all updates at times not greater or equal to an
+    /// element of `upper` and not previously prepared.
+    ///
+    /// The updates should not be visible in the external system until they are committed.
+    fn prepare(&mut self, upper: &[T], updates: &mut Vec<(D, T, R)>) -> Result<(), String>;
+}
+
+struct CoordinatorState<T: PartialOrder> {
+    /// Participants, and the frontiers through which each has prepared updates.
+    participants: Vec<(Rc<RefCell<Participant<T>>>, Antichain<T>)>,
+    /// The frontier through which updates have been committed by all participants.
+    committed: Antichain<T>,
+    /// Set if some participant failed, after which nothing further is committed.
+    failed: Option<String>,
+}
+
+/// Coordinates two-phase commits across several sinks.
+///
+/// Cloning a coordinator produces another handle to the same coordinator.
+pub struct Coordinator<T: PartialOrder> {
+    state: Rc<RefCell<CoordinatorState<T>>>,
+}
+
+impl<T: PartialOrder> Clone for Coordinator<T> {
+    fn clone(&self) -> Self {
+        Coordinator { state: self.state.clone() }
+    }
+}
+
+impl<T: PartialOrder+Clone+Default> Coordinator<T> {
+
+    /// Creates a new coordinator without participants.
+    pub fn new() -> Self {
+        Coordinator {
+            state: Rc::new(RefCell::new(CoordinatorState {
+                participants: Vec::new(),
+                committed: Antichain::from_elem(Default::default()),
+                failed: None,
+            }))
+        }
+    }
+
+    /// The frontier through which all participants have committed updates.
+    pub fn committed(&self) -> Antichain<T> {
+        self.state.borrow().committed.clone()
+    }
+
+    /// The error reported by a failed participant, if any.
+    pub fn failed(&self) -> Option<String> {
+        self.state.borrow().failed.clone()
+    }
+
+    /// Registers a participant, returning its identifier.
+    ///
+    /// No updates are committed until registered participants have prepared them, so all
+    /// participants should be registered before any updates are prepared.
+    pub fn register(&self, participant: Rc<RefCell<Participant<T>>>) -> usize {
+        let mut state = self.state.borrow_mut();
+        state.participants.push((participant, Antichain::from_elem(Default::default())));
+        state.participants.len() - 1
+    }
+
+    /// Records that participant `index` has prepared all updates not beyond `upper`.
+    ///
+    /// If all participants have now prepared through a frontier beyond the committed frontier,
+    /// the participants are asked to commit through the new frontier. If a participant fails to
+    /// commit, those which have committed are rolled back and the rest aborted, and the committed
+    /// frontier is unchanged.
+    pub fn prepared(&self, index: usize, upper: &[T]) {
+
+        let mut state = self.state.borrow_mut();
+        if state.failed.is_some() {
+            return;
+        }
+
+        state.participants[index].1 = Antichain::new();
+        for time in upper.iter() {
+            state.participants[index].1.insert(time.clone());
+        }
+
+        // Times are prepared by all participants if not beyond any participant's frontier.
+        let mut frontier = Antichain::new();
+        for &(_, ref prepared) in state.participants.iter() {
+            for time in prepared.elements().iter() {
+                frontier.insert(time.clone());
+            }
+        }
+
+        if frontier.elements() != state.committed.elements() {
+            for index in 0 .. state.participants.len() {
+                let result = state.participants[index].0.borrow_mut().commit(frontier.elements());
+                if let Err(error) = result {
+                    let committed = state.committed.clone();
+                    for (index2, &(ref participant, _)) in state.participants.iter().enumerate() {
+                        if index2 < index {
+                            participant.borrow_mut().rollback(committed.elements());
+                        }
+                        else {
+                            participant.borrow_mut().abort(committed.elements());
+                        }
+                    }
+                    state.failed = Some(format!("participant {}: {}", index, error));
+                    return;
+                }
+            }
+            state.committed = frontier;
+        }
+    }
+
+    /// Records that participant `index` failed to prepare updates, and aborts all participants.
+    pub fn failure(&self, index: usize, error: String) {
+        let mut state = self.state.borrow_mut();
+        if state.failed.is_none() {
+            let committed = state.committed.clone();
+            for &(ref participant, _) in state.participants.iter() {
+                participant.borrow_mut().abort(committed.elements());
+            }
+            state.failed = Some(format!("participant {}: {}", index, error));
+        }
+    }
+}
+
+/// An extension method for writing collections to coordinated sinks.
+pub trait CoordinatedSink<G: Scope, D: Data, R: Monoid> {
+    /// Writes the collection to `sink`, committing updates only once all sinks registered
+    /// with `coordinator` have prepared them.
+    ///
+    /// Updates are prepared once the input frontier passes their times, in batches containing
+    /// all updates not beyond the new frontier.
+    fn sink_coordinated<S>(&self, coordinator: &Coordinator<G::Timestamp>, sink: Rc<RefCell<S>>)
+    where S: Sink<D, G::Timestamp, R>+'static;
+}
+
+impl<G: Scope, D: Data, R: Monoid> CoordinatedSink<G, D, R> for Collection<G, D, R>
+where G::Timestamp: Default {
+
+    fn sink_coordinated<S>(&self, coordinator: &Coordinator<G::Timestamp>, sink: Rc<RefCell<S>>)
+    where S: Sink<D, G::Timestamp, R>+'static {
+
+        use timely::dataflow::channels::pact::Pipeline;
+        use timely::dataflow::operators::Operator;
+
+        let index = coordinator.register(sink.clone());
+        let coordinator = coordinator.clone();
+
+        let mut buffer = Vec::new();
+        let mut stash: Vec<(D, G::Timestamp, R)> = Vec::new();
+        let mut ready = Vec::new();
+        let mut upper = Antichain::from_elem(Default::default());
+
+        self.inner
+            .sink(Pipeline, "SinkCoordinated", move |input| {
+
+                input.for_each(|_time, data| {
+                    data.swap(&mut buffer);
+                    stash.extend(buffer.drain(..));
+                });
+
+                // Prepare updates once the frontier advances.
+                let frontier = input.frontier().frontier().to_vec();
+                if upper.elements() != &frontier[..] {
+
+                    let mut index2 = 0;
+                    while index2 < stash.len() {
+                        let complete = !frontier.iter().any(|t| t.less_equal(&stash[index2].1));
+                        if complete {
+                            ready.push(stash.swap_remove(index2));
+                        }
+                        else {
+                            index2 += 1;
+                        }
+                    }
+
+                    if coordinator.failed().is_none() {
+                        let result = sink.borrow_mut().prepare(&frontier[..], &mut ready);
+                        match result {
+                            Ok(()) => coordinator.prepared(index, &frontier[..]),
+                            Err(error) => coordinator.failure(index, error),
+                        }
+                    }
+                    ready.clear();
+
+                    upper = Antichain::new();
+                    for time in frontier.into_iter() {
+                        upper.insert(time);
+                    }
+                }
+            });
+    }
+}
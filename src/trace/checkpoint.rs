@@ -0,0 +1,133 @@
+//! Writing the contents of traces to files, and rebuilding traces from them.
+//!
+//! A checkpoint records the contents of a trace with times advanced to the trace's advance frontier,
+//! and consolidated, together with that frontier and the upper frontier of the trace. It is what the
+//! trace is obliged to report from its advance frontier onward, and is often much smaller than the
+//! history of updates that produced it. A trace restored from a checkpoint holds its contents as a
+//! single batch, from the minimal time up to the recorded upper frontier, after which new batches may
+//! be inserted as usual; for example, a restored trace wrapped in a `TraceAgent` can be imported into
+//! dataflows, and its `TraceWriter` used to continue the trace from inputs replayed from that frontier.
+//!
+//! Checkpoints are encoded with `abomonation`, and should only be read by the same build of the same
+//! program that wrote them.
+
+use std::fs::File;
+use std::io::{Read, Write, BufWriter, Result, Error, ErrorKind};
+use std::path::Path;
+
+use abomonation::Abomonation;
+use timely::dataflow::operators::generic::OperatorInfo;
+
+use ::difference::Monoid;
+use lattice::Lattice;
+use super::{Batch, BatchReader, Builder, Cursor, Trace, TraceReader, consolidate};
+
+/// The consolidated contents of a trace.
+#[derive(Clone, Debug, Abomonation)]
+pub struct Checkpoint<K, V, T, R> {
+	/// The frontier to which times have been advanced.
+	pub since: Vec<T>,
+	/// The frontier through which the trace was complete.
+	pub upper: Vec<T>,
+	/// The consolidated updates, in order.
+	pub updates: Vec<((K, V), T, R)>,
+}
+
+/// Collects the contents of `trace`, with times advanced to its advance frontier and consolidated.
+pub fn capture<K, V, T, R, Tr>(trace: &mut Tr) -> Checkpoint<K, V, T, R>
+where
+	K: Ord+Clone,
+	V: Ord+Clone,
+	T: Lattice+Ord+Clone,
+	R: Monoid,
+	Tr: TraceReader<K, V, T, R>,
+{
+	let since = trace.advance_frontier().to_vec();
+
+	let mut upper = None;
+	trace.map_batches(|batch| upper = Some(batch.upper().to_vec()));
+	let upper = upper.unwrap_or_else(|| vec![T::minimum()]);
+
+	let mut updates = Vec::new();
+	let (mut cursor, storage) = trace.cursor();
+	while cursor.key_valid(&storage) {
+		while cursor.val_valid(&storage) {
+			let key = cursor.key(&storage);
+			let val = cursor.val(&storage);
+			cursor.map_times(&storage, |time, diff| {
+				let mut time = time.clone();
+				time.advance_by(&since[..]);
+				updates.push((((key.clone(), val.clone()), time), diff.clone()));
+			});
+			cursor.step_val(&storage);
+		}
+		cursor.step_key(&storage);
+	}
+	consolidate(&mut updates, 0);
+
+	Checkpoint {
+		since,
+		upper,
+		updates: updates.into_iter().map(|((record, time), diff)| (record, time, diff)).collect(),
+	}
+}
+
+/// Builds a trace containing the updates of `checkpoint`.
+pub fn rebuild<K, V, T, R, Tr>(checkpoint: Checkpoint<K, V, T, R>, info: OperatorInfo, logging: Option<::logging::Logger>) -> Tr
+where
+	T: Lattice+Ord+Clone,
+	Tr: Trace<K, V, T, R>,
+	Tr::Batch: Batch<K, V, T, R>,
+{
+	let mut builder = <<Tr::Batch as Batch<K, V, T, R>>::Builder as Builder<K, V, T, R, Tr::Batch>>::with_capacity(checkpoint.updates.len());
+	for ((key, val), time, diff) in checkpoint.updates.into_iter() {
+		builder.push((key, val, time, diff));
+	}
+	let batch = builder.done(&[T::minimum()], &checkpoint.upper[..], &checkpoint.since[..]);
+
+	let mut trace = Tr::new(info, logging);
+	trace.insert(batch);
+	trace.advance_by(&checkpoint.since[..]);
+	trace
+}
+
+/// Writes the consolidated contents of `trace` to the file at `path`.
+pub fn write<K, V, T, R, Tr, P>(trace: &mut Tr, path: P) -> Result<()>
+where
+	K: Abomonation+Ord+Clone,
+	V: Abomonation+Ord+Clone,
+	T: Abomonation+Lattice+Ord+Clone,
+	R: Abomonation+Monoid,
+	Tr: TraceReader<K, V, T, R>,
+	P: AsRef<Path>,
+{
+	let checkpoint = capture(trace);
+	let mut writer = BufWriter::new(File::create(path)?);
+	unsafe { ::abomonation::encode(&checkpoint, &mut writer)?; }
+	writer.flush()
+}
+
+/// Reads the checkpoint in the file at `path`, and builds a trace containing its updates.
+pub fn read<K, V, T, R, Tr, P>(path: P, info: OperatorInfo, logging: Option<::logging::Logger>) -> Result<Tr>
+where
+	K: Abomonation+Clone,
+	V: Abomonation+Clone,
+	T: Abomonation+Lattice+Ord+Clone,
+	R: Abomonation+Clone,
+	Tr: Trace<K, V, T, R>,
+	Tr::Batch: Batch<K, V, T, R>,
+	P: AsRef<Path>,
+{
+	let mut bytes = Vec::new();
+	File::open(path)?.read_to_end(&mut bytes)?;
+	let checkpoint = match unsafe { ::abomonation::decode::<Checkpoint<K, V, T, R>>(&mut bytes[..]) } {
+		Some((checkpoint, remaining)) => {
+			if !remaining.is_empty() {
+				return Err(Error::new(ErrorKind::InvalidData, "trailing bytes after trace checkpoint"));
+			}
+			checkpoint.clone()
+		},
+		None => return Err(Error::new(ErrorKind::InvalidData, "malformed trace checkpoint")),
+	};
+	Ok(rebuild(checkpoint, info, logging))
+}
@@ -136,6 +136,21 @@ where
         }
         self.minimize_keys(storage);
     }
+    #[inline(always)]
+    fn probe_key(&mut self, storage: &Self::Storage, key: &K) -> bool {
+        // Cursors that cannot contain the key are left in place, and are not candidates for the minimum.
+        self.min_key.clear();
+        for index in 0 .. self.cursors.len() {
+            if self.cursors[index].may_contain(&storage[index], key) {
+                self.cursors[index].seek_key(&storage[index], key);
+                if self.cursors[index].get_key(&storage[index]) == Some(key) {
+                    self.min_key.push(index);
+                }
+            }
+        }
+        self.minimize_vals(storage);
+        !self.min_key.is_empty()
+    }
 
     // value methods
     #[inline(always)]
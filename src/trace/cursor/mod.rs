@@ -58,6 +58,80 @@ pub trait Cursor<K, V, T, R> {
 	fn rewind_keys(&mut self, storage: &Self::Storage);
 	/// Rewinds the cursor to the first value for current key.
 	fn rewind_vals(&mut self, storage: &Self::Storage);
+
+	/// Indicates if `storage` may contain `key`.
+	///
+	/// A result of `false` guarantees that the key is absent, and allows a probe for the key to skip
+	/// the storage. Cursors without summaries of their keys, like Bloom filters, always return `true`.
+	#[inline(always)]
+	fn may_contain(&self, _storage: &Self::Storage, _key: &K) -> bool { true }
+
+	/// Advances the cursor to `key`, and indicates if the key is present.
+	///
+	/// If the key is present the cursor is positioned at it, as for `seek_key`. Otherwise the cursor may
+	/// skip storage that cannot contain the key, and its position is only suitable for further probes, or
+	/// seeks of keys at least `key`; it should not be stepped until it is next sought.
+	fn probe_key(&mut self, storage: &Self::Storage, key: &K) -> bool where K: PartialEq {
+		self.seek_key(storage, key);
+		self.get_key(storage) == Some(key)
+	}
+
+	/// Enumerates the values and updates of keys greater or equal to `lower` and less than `upper`.
+	///
+	/// The cursor is rewound and then seeks `lower`, so that only the keys in the range are visited.
+	fn seek_key_range<'a>(&'a mut self, storage: &'a Self::Storage, lower: &K, upper: K) -> CursorScan<'a, K, V, T, R, Self>
+	where Self: Sized, K: Ord+'a {
+		self.seek_key_prefix(storage, lower, move |key| key < &upper)
+	}
+
+	/// Enumerates the values and updates of keys from `lower` onward, for as long as `within` holds.
+	///
+	/// The predicate should hold for a contiguous range of keys starting at `lower`, as it does for keys
+	/// sharing a prefix when `lower` is the least key with the prefix. The cursor is rewound and then seeks
+	/// `lower`, and the enumeration ends at the first key for which `within` does not hold.
+	fn seek_key_prefix<'a, F>(&'a mut self, storage: &'a Self::Storage, lower: &K, within: F) -> CursorScan<'a, K, V, T, R, Self>
+	where Self: Sized, K: 'a, F: Fn(&K)->bool+'a {
+		self.rewind_keys(storage);
+		self.seek_key(storage, lower);
+		CursorScan {
+			cursor: self,
+			storage,
+			within: Box::new(within),
+			phantom: ::std::marker::PhantomData,
+		}
+	}
+}
+
+/// An iterator over the values and updates of a range of keys.
+///
+/// Each item is a key, one of its values, and the updates of the pair. The iterator is produced by the
+/// `seek_key_range` and `seek_key_prefix` methods of `Cursor`.
+pub struct CursorScan<'a, K: 'a, V, T, R, C: Cursor<K, V, T, R>+'a> where C::Storage: 'a {
+	cursor: &'a mut C,
+	storage: &'a C::Storage,
+	within: Box<Fn(&K)->bool+'a>,
+	phantom: ::std::marker::PhantomData<(V, T, R)>,
+}
+
+impl<'a, K: 'a, V: 'a, T: Clone, R: Clone, C: Cursor<K, V, T, R>+'a> Iterator for CursorScan<'a, K, V, T, R, C> where C::Storage: 'a {
+	type Item = (&'a K, &'a V, Vec<(T, R)>);
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.cursor.key_valid(self.storage) {
+			let key = self.cursor.key(self.storage);
+			if !(self.within)(key) {
+				return None;
+			}
+			if self.cursor.val_valid(self.storage) {
+				let val = self.cursor.val(self.storage);
+				let mut updates = Vec::new();
+				self.cursor.map_times(self.storage, |time, diff| updates.push((time.clone(), diff.clone())));
+				self.cursor.step_val(self.storage);
+				return Some((key, val, updates));
+			}
+			self.cursor.step_key(self.storage);
+		}
+		None
+	}
 }
 
 /// Debugging and testing utilities for Cursor.
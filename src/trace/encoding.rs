@@ -0,0 +1,119 @@
+//! A portable encoding of batches, using `serde` and `bincode`.
+//!
+//! Batches encoded with `abomonation` are their in-memory layout, and can only be decoded by the same
+//! build of the same program. The encoding here instead writes a header, identifying the encoding and
+//! its version, followed by the batch's description and its updates in order, each serialized with
+//! `bincode`. The result does not depend on the layout of the batch or of its types, and can be read by
+//! other builds and other batch implementations, for example to exchange batches between processes of
+//! a heterogeneous cluster, or to persist them across upgrades.
+//!
+//! The encoding is available with the `bincode` feature.
+
+use std::io::{Read, Write, Result, Error, ErrorKind};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::{Batch, BatchReader, Builder, Cursor};
+
+/// Bytes identifying the encoding, at the start of each encoded batch.
+pub const MAGIC: [u8; 4] = *b"DDBt";
+
+/// The version of the encoding written by `encode`.
+pub const VERSION: u32 = 1;
+
+/// The header preceding each encoded batch.
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+	magic: [u8; 4],
+	version: u32,
+	updates: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Frontiers<T> {
+	lower: Vec<T>,
+	upper: Vec<T>,
+	since: Vec<T>,
+}
+
+fn invalid<E: ::std::fmt::Display>(error: E) -> Error {
+	Error::new(ErrorKind::InvalidData, format!("batch encoding: {}", error))
+}
+
+/// Writes `batch` to `writer`.
+pub fn encode<K, V, T, R, B, W>(batch: &B, mut writer: W) -> Result<()>
+where
+	K: Serialize,
+	V: Serialize,
+	T: Serialize+Clone,
+	R: Serialize,
+	B: BatchReader<K, V, T, R>,
+	W: Write,
+{
+	let header = Header {
+		magic: MAGIC,
+		version: VERSION,
+		updates: batch.len() as u64,
+	};
+	::bincode::serialize_into(&mut writer, &header).map_err(invalid)?;
+
+	let description = batch.description();
+	let frontiers = Frontiers {
+		lower: description.lower().to_vec(),
+		upper: description.upper().to_vec(),
+		since: description.since().to_vec(),
+	};
+	::bincode::serialize_into(&mut writer, &frontiers).map_err(invalid)?;
+
+	let mut result = Ok(());
+	let mut cursor = batch.cursor();
+	while cursor.key_valid(batch) {
+		while cursor.val_valid(batch) {
+			let key = cursor.key(batch);
+			let val = cursor.val(batch);
+			cursor.map_times(batch, |time, diff| {
+				if result.is_ok() {
+					result = ::bincode::serialize_into(&mut writer, &(key, val, time, diff)).map_err(invalid);
+				}
+			});
+			cursor.step_val(batch);
+		}
+		cursor.step_key(batch);
+	}
+	result
+}
+
+/// Reads a batch written by `encode` from `reader`.
+///
+/// The updates are read in the order they were written, and must be in the order of the batch type;
+/// this is the case when the types of keys and values order themselves as in the writing program.
+pub fn decode<K, V, T, R, B, Rd>(mut reader: Rd) -> Result<B>
+where
+	K: DeserializeOwned,
+	V: DeserializeOwned,
+	T: DeserializeOwned,
+	R: DeserializeOwned,
+	B: Batch<K, V, T, R>,
+	Rd: Read,
+{
+	let header: Header = ::bincode::deserialize_from(&mut reader).map_err(invalid)?;
+	if header.magic != MAGIC {
+		return Err(invalid("not an encoded batch"));
+	}
+	if header.version != VERSION {
+		return Err(invalid(format!("unsupported version {} (expected {})", header.version, VERSION)));
+	}
+
+	let frontiers: Frontiers<T> = ::bincode::deserialize_from(&mut reader).map_err(invalid)?;
+
+	// the header is not trusted to size allocations beyond a modest bound.
+	let capacity = ::std::cmp::min(header.updates, 1 << 20) as usize;
+	let mut builder = <B::Builder as Builder<K, V, T, R, B>>::with_capacity(capacity);
+	for _ in 0 .. header.updates {
+		let update: (K, V, T, R) = ::bincode::deserialize_from(&mut reader).map_err(invalid)?;
+		builder.push(update);
+	}
+
+	Ok(builder.done(&frontiers.lower[..], &frontiers.upper[..], &frontiers.since[..]))
+}
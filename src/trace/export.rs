@@ -0,0 +1,175 @@
+//! Exporting the contents of traces as Apache Arrow record batches.
+//!
+//! The `export` function reads the contents of a trace with times advanced to a frontier, as a trace
+//! reports them from its advance frontier onward, consolidates them, and produces Arrow record batches
+//! with columns `key`, `val`, `time`, and `diff`. The record batches can be handed to anything that reads
+//! Arrow, for example written as Arrow IPC files and read by `pyarrow` or `pandas`.
+//!
+//! Each coordinate type must implement `Column`, which is implemented for integer, floating point,
+//! boolean, and string types, and for `()`, the value of traces of keys alone, as a boolean column
+//! whose values are all `true`. The export is available with the `arrow` feature.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayBuilder, ArrayRef, BooleanBuilder, PrimitiveBuilder, StringBuilder};
+use arrow::datatypes::{self, DataType, Field, Schema};
+use arrow::error::Result;
+use arrow::record_batch::RecordBatch;
+
+use ::difference::Monoid;
+use lattice::Lattice;
+use super::{Cursor, TraceReader, consolidate};
+
+/// Types that can be written to an Arrow column.
+pub trait Column {
+	/// The builder of columns of the type.
+	type Builder: ArrayBuilder;
+	/// The Arrow data type of the column.
+	fn data_type() -> DataType;
+	/// Allocates a builder for a column.
+	fn builder(capacity: usize) -> Self::Builder;
+	/// Appends the value to the column.
+	fn append(&self, builder: &mut Self::Builder) -> Result<()>;
+}
+
+macro_rules! primitive_column {
+	($type:ty, $arrow:ty, $data_type:expr) => {
+		impl Column for $type {
+			type Builder = PrimitiveBuilder<$arrow>;
+			fn data_type() -> DataType { $data_type }
+			fn builder(capacity: usize) -> Self::Builder { PrimitiveBuilder::<$arrow>::new(capacity) }
+			fn append(&self, builder: &mut Self::Builder) -> Result<()> { builder.append_value(*self as _) }
+		}
+	}
+}
+
+primitive_column!(i8, datatypes::Int8Type, DataType::Int8);
+primitive_column!(i16, datatypes::Int16Type, DataType::Int16);
+primitive_column!(i32, datatypes::Int32Type, DataType::Int32);
+primitive_column!(i64, datatypes::Int64Type, DataType::Int64);
+primitive_column!(isize, datatypes::Int64Type, DataType::Int64);
+primitive_column!(u8, datatypes::UInt8Type, DataType::UInt8);
+primitive_column!(u16, datatypes::UInt16Type, DataType::UInt16);
+primitive_column!(u32, datatypes::UInt32Type, DataType::UInt32);
+primitive_column!(u64, datatypes::UInt64Type, DataType::UInt64);
+primitive_column!(usize, datatypes::UInt64Type, DataType::UInt64);
+primitive_column!(f32, datatypes::Float32Type, DataType::Float32);
+primitive_column!(f64, datatypes::Float64Type, DataType::Float64);
+
+impl Column for bool {
+	type Builder = BooleanBuilder;
+	fn data_type() -> DataType { DataType::Boolean }
+	fn builder(capacity: usize) -> Self::Builder { BooleanBuilder::new(capacity) }
+	fn append(&self, builder: &mut Self::Builder) -> Result<()> { builder.append_value(*self) }
+}
+
+/// The unit value, written as `true`, so that traces of keys alone have a `val` column of fixed content.
+impl Column for () {
+	type Builder = BooleanBuilder;
+	fn data_type() -> DataType { DataType::Boolean }
+	fn builder(capacity: usize) -> Self::Builder { BooleanBuilder::new(capacity) }
+	fn append(&self, builder: &mut Self::Builder) -> Result<()> { builder.append_value(true) }
+}
+
+impl Column for String {
+	type Builder = StringBuilder;
+	fn data_type() -> DataType { DataType::Utf8 }
+	fn builder(capacity: usize) -> Self::Builder { StringBuilder::new(capacity) }
+	fn append(&self, builder: &mut Self::Builder) -> Result<()> { builder.append_value(self) }
+}
+
+/// The schema of exported record batches.
+pub fn schema<K: Column, V: Column, T: Column, R: Column>() -> Schema {
+	Schema::new(vec![
+		Field::new("key", K::data_type(), false),
+		Field::new("val", V::data_type(), false),
+		Field::new("time", T::data_type(), false),
+		Field::new("diff", R::data_type(), false),
+	])
+}
+
+/// Exports the contents of `trace` with times advanced by `frontier`, in record batches of at most `rows` rows.
+///
+/// The frontier should be in advance of the trace's advance frontier, as times are only accurate from there
+/// onward. Updates are consolidated after their times are advanced, and appear in order of key and value.
+pub fn export<K, V, T, R, Tr>(trace: &mut Tr, frontier: &[T], rows: usize) -> Result<Vec<RecordBatch>>
+where
+	K: Column,
+	V: Column,
+	T: Column+Lattice+Ord+Clone,
+	R: Column+Monoid,
+	Tr: TraceReader<K, V, T, R>,
+{
+	assert!(rows > 0, "record batches must have at least one row");
+
+	let schema = Arc::new(schema::<K, V, T, R>());
+
+	let mut batches = Vec::new();
+	let mut columns = Columns::<K, V, T, R>::new(rows);
+	let mut updates = Vec::new();
+
+	let (mut cursor, storage) = trace.cursor();
+	while let Some(key) = cursor.get_key(&storage) {
+		while let Some(val) = cursor.get_val(&storage) {
+			cursor.map_times(&storage, |time, diff| {
+				let mut time = time.clone();
+				time.advance_by(frontier);
+				updates.push((time, diff.clone()));
+			});
+			consolidate(&mut updates, 0);
+			for (time, diff) in updates.drain(..) {
+				columns.push(key, val, &time, &diff)?;
+				if columns.len == rows {
+					let full = ::std::mem::replace(&mut columns, Columns::new(rows));
+					batches.push(full.finish(schema.clone())?);
+				}
+			}
+			cursor.step_val(&storage);
+		}
+		cursor.step_key(&storage);
+	}
+
+	if columns.len > 0 {
+		batches.push(columns.finish(schema)?);
+	}
+
+	Ok(batches)
+}
+
+// Builders for the columns of a record batch.
+struct Columns<K: Column, V: Column, T: Column, R: Column> {
+	keys: K::Builder,
+	vals: V::Builder,
+	times: T::Builder,
+	diffs: R::Builder,
+	len: usize,
+}
+
+impl<K: Column, V: Column, T: Column, R: Column> Columns<K, V, T, R> {
+	fn new(capacity: usize) -> Self {
+		Columns {
+			keys: K::builder(capacity),
+			vals: V::builder(capacity),
+			times: T::builder(capacity),
+			diffs: R::builder(capacity),
+			len: 0,
+		}
+	}
+	fn push(&mut self, key: &K, val: &V, time: &T, diff: &R) -> Result<()> {
+		key.append(&mut self.keys)?;
+		val.append(&mut self.vals)?;
+		time.append(&mut self.times)?;
+		diff.append(&mut self.diffs)?;
+		self.len += 1;
+		Ok(())
+	}
+	fn finish(mut self, schema: Arc<Schema>) -> Result<RecordBatch> {
+		let columns: Vec<ArrayRef> = vec![
+			ArrayBuilder::finish(&mut self.keys),
+			ArrayBuilder::finish(&mut self.vals),
+			ArrayBuilder::finish(&mut self.times),
+			ArrayBuilder::finish(&mut self.diffs),
+		];
+		RecordBatch::try_new(schema, columns)
+	}
+}
@@ -0,0 +1,367 @@
+//! Trace and batch implementations for collections without retractions.
+//!
+//! Collections derived from logs, whose records are only ever added, have updates whose differences
+//! are all positive and most often one. The `AppendBatch` stores such updates in the columnar layout
+//! of `ColValBatch`, but with each time paired with an unsigned `u32` count rather than an `isize`
+//! difference. Equal times of a value are consolidated into one pair, including as batches are merged
+//! and their times advanced. This halves the memory of updates with `u32` times, and avoids the
+//! signed bookkeeping of retractions.
+//!
+//! Updates with negative differences cannot be represented, and are reported by a debug assertion
+//! as the batch is built; in release builds they are discarded.
+
+use std::rc::Rc;
+
+use lattice::Lattice;
+
+use trace::{Batch, BatchReader, Builder, Merger, Cursor};
+use trace::description::Description;
+use trace::layers::ordered_leaf::advance;
+
+use super::spine_fueled::Spine;
+use super::merge_batcher::MergeBatcher;
+
+/// A trace implementation for collections without retractions.
+pub type AppendSpine<K, V, T> = Spine<K, V, T, isize, Rc<AppendBatch<K, V, T>>>;
+
+/// A trace implementation for collections of keys without retractions.
+pub type AppendKeySpine<K, T> = Spine<K, (), T, isize, Rc<AppendBatch<K, (), T>>>;
+
+/// An immutable collection of positive updates, from a contiguous interval of logical times.
+#[derive(Debug, Abomonation)]
+pub struct AppendBatch<K, V, T: Lattice> {
+	/// The distinct keys, in order.
+	pub keys: Vec<K>,
+	/// The values of key `i` are those in `key_offs[i] .. key_offs[i+1]`.
+	pub key_offs: Vec<usize>,
+	/// The distinct values of each key, in order.
+	pub vals: Vec<V>,
+	/// The times of value `i` are those in `val_offs[i] .. val_offs[i+1]`.
+	pub val_offs: Vec<usize>,
+	/// The distinct times of each value, in order, with their counts.
+	pub times: Vec<(T, u32)>,
+	/// Description of the update times this batch represents.
+	pub desc: Description<T>,
+}
+
+impl<K, V, T> BatchReader<K, V, T, isize> for AppendBatch<K, V, T>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+'static {
+	type Cursor = AppendCursor;
+	fn cursor(&self) -> Self::Cursor { AppendCursor { key: 0, val: self.key_offs[0] } }
+	fn len(&self) -> usize { self.times.len() }
+	fn description(&self) -> &Description<T> { &self.desc }
+}
+
+impl<K, V, T> Batch<K, V, T, isize> for AppendBatch<K, V, T>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static {
+	type Batcher = MergeBatcher<K, V, T, isize, Self>;
+	type Builder = AppendBuilder<K, V, T>;
+	type Merger = AppendMerger<K, V, T>;
+
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		AppendMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating an append-only batch.
+#[derive(Debug)]
+pub struct AppendCursor {
+	key: usize,
+	val: usize,
+}
+
+impl<K, V, T> Cursor<K, V, T, isize> for AppendCursor
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone {
+
+	type Storage = AppendBatch<K, V, T>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &storage.keys[self.key] }
+	fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { &storage.vals[self.val] }
+	fn map_times<L: FnMut(&T, &isize)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let lower = storage.val_offs[self.val];
+		let upper = storage.val_offs[self.val + 1];
+		for &(ref time, count) in storage.times[lower .. upper].iter() {
+			logic(time, &(count as isize));
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.key < storage.keys.len() }
+	fn val_valid(&self, storage: &Self::Storage) -> bool {
+		self.key < storage.keys.len() && self.val < storage.key_offs[self.key + 1]
+	}
+	fn step_key(&mut self, storage: &Self::Storage){
+		if self.key < storage.keys.len() { self.key += 1; }
+		self.val = storage.key_offs[self.key];
+	}
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) {
+		self.key += advance(&storage.keys[self.key ..], |x| x.lt(key));
+		self.val = storage.key_offs[self.key];
+	}
+	fn step_val(&mut self, storage: &Self::Storage) {
+		if self.val_valid(storage) { self.val += 1; }
+	}
+	fn seek_val(&mut self, storage: &Self::Storage, val: &V) {
+		if self.key_valid(storage) {
+			let upper = storage.key_offs[self.key + 1];
+			self.val += advance(&storage.vals[self.val .. upper], |x| x.lt(val));
+		}
+	}
+	fn rewind_keys(&mut self, storage: &Self::Storage) {
+		self.key = 0;
+		self.val = storage.key_offs[0];
+	}
+	fn rewind_vals(&mut self, storage: &Self::Storage) {
+		self.val = storage.key_offs[self.key];
+	}
+}
+
+/// A builder for creating append-only batches from ordered update tuples.
+pub struct AppendBuilder<K, V, T> {
+	keys: Vec<K>,
+	key_offs: Vec<usize>,
+	vals: Vec<V>,
+	val_offs: Vec<usize>,
+	times: Vec<(T, u32)>,
+}
+
+impl<K, V, T> AppendBuilder<K, V, T>
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone {
+
+	// Appends the times of value `val` of `batch`, returning their number.
+	fn copy_times(&mut self, batch: &AppendBatch<K, V, T>, val: usize) -> usize {
+		let lower = batch.val_offs[val];
+		let upper = batch.val_offs[val + 1];
+		self.times.extend_from_slice(&batch.times[lower .. upper]);
+		upper - lower
+	}
+
+	// Adds `count` updates at `time` to the most recently pushed value, consolidating with its last time
+	// if equal, and splitting counts that do not fit in a `u32`.
+	fn push_time(&mut self, time: T, mut count: usize) {
+		let start = self.val_offs.last().cloned().unwrap_or(0);
+		if self.times.len() > start {
+			let last = self.times.last_mut().unwrap();
+			if last.0 == time {
+				let added = ::std::cmp::min(count, (u32::max_value() - last.1) as usize);
+				last.1 += added as u32;
+				count -= added;
+			}
+		}
+		while count > 0 {
+			let added = ::std::cmp::min(count, u32::max_value() as usize);
+			self.times.push((time.clone(), added as u32));
+			count -= added;
+		}
+	}
+
+	// Completes the most recently pushed value, whose times start at `start`, advancing its times by
+	// `frontier` if supplied, and restoring their order and consolidating equal times if they may be
+	// out of order.
+	fn seal_val(&mut self, start: usize, frontier: Option<&[T]>, unordered: bool) {
+		if let Some(frontier) = frontier {
+			for &mut (ref mut time, _) in self.times[start ..].iter_mut() {
+				time.advance_by(frontier);
+			}
+		}
+		if frontier.is_some() || unordered {
+			let mut times = self.times.split_off(start);
+			times.sort_by(|x, y| x.0.cmp(&y.0));
+			for (time, count) in times.into_iter() {
+				self.push_time(time, count as usize);
+			}
+		}
+	}
+
+	// Appends key `key` of `batch`, returning the number of updates read.
+	fn copy_key(&mut self, batch: &AppendBatch<K, V, T>, key: usize, frontier: Option<&[T]>) -> usize {
+		let mut effort = 0;
+		self.keys.push(batch.keys[key].clone());
+		self.key_offs.push(self.vals.len());
+		for val in batch.key_offs[key] .. batch.key_offs[key + 1] {
+			let times = self.times.len();
+			self.vals.push(batch.vals[val].clone());
+			self.val_offs.push(times);
+			effort += self.copy_times(batch, val);
+			self.seal_val(times, frontier, false);
+		}
+		effort
+	}
+
+	// Appends the merge of key `key1` of `batch1` and the equal key `key2` of `batch2`, returning the number of updates read.
+	fn merge_key(&mut self, (batch1, key1): (&AppendBatch<K, V, T>, usize), (batch2, key2): (&AppendBatch<K, V, T>, usize), frontier: Option<&[T]>) -> usize {
+
+		let mut effort = 0;
+		self.keys.push(batch1.keys[key1].clone());
+		self.key_offs.push(self.vals.len());
+
+		let (mut lower1, upper1) = (batch1.key_offs[key1], batch1.key_offs[key1 + 1]);
+		let (mut lower2, upper2) = (batch2.key_offs[key2], batch2.key_offs[key2 + 1]);
+
+		while lower1 < upper1 || lower2 < upper2 {
+			let order =
+			if lower1 == upper1 { ::std::cmp::Ordering::Greater }
+			else if lower2 == upper2 { ::std::cmp::Ordering::Less }
+			else { batch1.vals[lower1].cmp(&batch2.vals[lower2]) };
+
+			let times = self.times.len();
+			match order {
+				::std::cmp::Ordering::Less => {
+					self.vals.push(batch1.vals[lower1].clone());
+					self.val_offs.push(times);
+					effort += self.copy_times(batch1, lower1);
+					self.seal_val(times, frontier, false);
+					lower1 += 1;
+				},
+				::std::cmp::Ordering::Equal => {
+					self.vals.push(batch1.vals[lower1].clone());
+					self.val_offs.push(times);
+					effort += self.copy_times(batch1, lower1);
+					effort += self.copy_times(batch2, lower2);
+					self.seal_val(times, frontier, true);
+					lower1 += 1;
+					lower2 += 1;
+				},
+				::std::cmp::Ordering::Greater => {
+					self.vals.push(batch2.vals[lower2].clone());
+					self.val_offs.push(times);
+					effort += self.copy_times(batch2, lower2);
+					self.seal_val(times, frontier, false);
+					lower2 += 1;
+				},
+			}
+		}
+
+		effort
+	}
+
+	// Completes the columns, producing a batch with `description`.
+	fn finish(mut self, description: Description<T>) -> AppendBatch<K, V, T> {
+		self.key_offs.push(self.vals.len());
+		self.val_offs.push(self.times.len());
+		AppendBatch {
+			keys: self.keys,
+			key_offs: self.key_offs,
+			vals: self.vals,
+			val_offs: self.val_offs,
+			times: self.times,
+			desc: description,
+		}
+	}
+}
+
+impl<K, V, T> Builder<K, V, T, isize, AppendBatch<K, V, T>> for AppendBuilder<K, V, T>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static {
+
+	fn new() -> Self {
+		<Self as Builder<K, V, T, isize, AppendBatch<K, V, T>>>::with_capacity(0)
+	}
+	fn with_capacity(cap: usize) -> Self {
+		AppendBuilder {
+			keys: Vec::new(),
+			key_offs: Vec::new(),
+			vals: Vec::new(),
+			val_offs: Vec::new(),
+			times: Vec::with_capacity(cap),
+		}
+	}
+
+	#[inline(always)]
+	fn push(&mut self, (key, val, time, diff): (K, V, T, isize)) {
+		debug_assert!(diff >= 0, "negative difference in an append-only batch");
+		if diff > 0 {
+			if self.keys.last() != Some(&key) {
+				self.keys.push(key);
+				self.key_offs.push(self.vals.len());
+				self.vals.push(val);
+				self.val_offs.push(self.times.len());
+			}
+			else if self.vals.last() != Some(&val) {
+				self.vals.push(val);
+				self.val_offs.push(self.times.len());
+			}
+			self.push_time(time, diff as usize);
+		}
+	}
+
+	#[inline(never)]
+	fn done(self, lower: &[T], upper: &[T], since: &[T]) -> AppendBatch<K, V, T> {
+		self.finish(Description::new(lower, upper, since))
+	}
+}
+
+/// State for an in-progress merge.
+pub struct AppendMerger<K, V, T: Lattice> {
+	// first batch, and position therein.
+	lower1: usize,
+	upper1: usize,
+	// second batch, and position therein.
+	lower2: usize,
+	upper2: usize,
+	// result that we are currently assembling.
+	result: AppendBuilder<K, V, T>,
+	description: Description<T>,
+}
+
+impl<K, V, T> Merger<K, V, T, isize, AppendBatch<K, V, T>> for AppendMerger<K, V, T>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static {
+	fn new(batch1: &AppendBatch<K, V, T>, batch2: &AppendBatch<K, V, T>) -> Self {
+
+		assert!(batch1.upper() == batch2.lower());
+
+		let since = if batch1.description().since().iter().all(|t1| batch2.description().since().iter().any(|t2| t2.less_equal(t1))) {
+			batch2.description().since()
+		}
+		else {
+			batch1.description().since()
+		};
+
+		let description = Description::new(batch1.lower(), batch2.upper(), since);
+
+		AppendMerger {
+			lower1: 0,
+			upper1: batch1.keys.len(),
+			lower2: 0,
+			upper2: batch2.keys.len(),
+			result: <AppendBuilder<K, V, T> as Builder<K, V, T, isize, AppendBatch<K, V, T>>>::with_capacity(batch1.len() + batch2.len()),
+			description: description,
+		}
+	}
+	fn done(self) -> AppendBatch<K, V, T> {
+
+		assert!(self.lower1 == self.upper1);
+		assert!(self.lower2 == self.upper2);
+
+		self.result.finish(self.description)
+	}
+	fn work(&mut self, source1: &AppendBatch<K,V,T>, source2: &AppendBatch<K,V,T>, frontier: &Option<Vec<T>>, fuel: &mut usize) {
+
+		let frontier = frontier.as_ref().map(|frontier| &frontier[..]);
+		let mut effort = 0;
+
+		// merge keys in order, until both mergees are exhausted or fuel runs out.
+		while (self.lower1 < self.upper1 || self.lower2 < self.upper2) && effort < *fuel {
+			let order =
+			if self.lower1 == self.upper1 { ::std::cmp::Ordering::Greater }
+			else if self.lower2 == self.upper2 { ::std::cmp::Ordering::Less }
+			else { source1.keys[self.lower1].cmp(&source2.keys[self.lower2]) };
+
+			match order {
+				::std::cmp::Ordering::Less => {
+					effort += self.result.copy_key(source1, self.lower1, frontier);
+					self.lower1 += 1;
+				},
+				::std::cmp::Ordering::Equal => {
+					effort += self.result.merge_key((source1, self.lower1), (source2, self.lower2), frontier);
+					self.lower1 += 1;
+					self.lower2 += 1;
+				},
+				::std::cmp::Ordering::Greater => {
+					effort += self.result.copy_key(source2, self.lower2, frontier);
+					self.lower2 += 1;
+				},
+			}
+		}
+
+		if effort >= *fuel { *fuel = 0; }
+		else 			   { *fuel -= effort; }
+	}
+}
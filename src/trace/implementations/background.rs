@@ -0,0 +1,292 @@
+//! Batches whose large merges run on background threads.
+//!
+//! A spine merges batches progressively, performing work proportional to each inserted batch, but a
+//! merge of large batches is still a large amount of work, and it delays the worker that performs it.
+//! The `Background` batch wraps a shareable batch, and its merger hands merges of at least a threshold
+//! number of updates to a pool of threads adjacent to the worker, reporting the merge as pending until
+//! the merged batch is available, without consuming fuel that other merges could use. The spine then
+//! swaps in the result as it would for any completed merge, and if the spine needs the result before
+//! it is available, for example to merge it further, the worker waits.
+//!
+//! Offloading is controlled by the `DIFFERENTIAL_MERGE_BACKGROUND` environment variable, which sets the
+//! threshold and is otherwise disabled, or by `set_background_threshold` for the current thread. Each
+//! worker thread has its own pool, of `DIFFERENTIAL_MERGE_THREADS` threads, defaulting to one.
+
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
+
+use trace::{Batch, BatchReader, Batcher, Builder, Merger, Cursor};
+use trace::description::Description;
+
+use super::spine_fueled::Spine;
+use super::ord::{OrdValBatch, OrdKeyBatch};
+
+/// A trace implementation using a spine of ordered lists merged in the background.
+pub type BackgroundValSpine<K, V, T, R> = Spine<K, V, T, R, Background<OrdValBatch<K, V, T, R>>>;
+
+/// A trace implementation for empty values using a spine of ordered lists merged in the background.
+pub type BackgroundKeySpine<K, T, R> = Spine<K, (), T, R, Background<OrdKeyBatch<K, T, R>>>;
+
+type Job = Box<FnMut()+Send>;
+
+// A pool of threads performing merges.
+struct MergePool {
+	sender: Sender<Job>,
+}
+
+impl MergePool {
+	fn new(threads: usize) -> Self {
+		let (sender, receiver) = channel::<Job>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		for index in 0 .. threads {
+			let receiver = receiver.clone();
+			::std::thread::Builder::new()
+				.name(format!("differential-merge-{}", index))
+				.spawn(move || {
+					// threads exit once the pool, and with it the sender, is dropped.
+					loop {
+						let job = receiver.lock().expect("merge pool poisoned").recv();
+						match job {
+							Ok(mut job) => job(),
+							Err(_) => break,
+						}
+					}
+				})
+				.expect("failed to start merge thread");
+		}
+		MergePool { sender }
+	}
+	fn spawn<F: FnOnce()+Send+'static>(&self, job: F) {
+		let mut job = Some(job);
+		self.sender
+			.send(Box::new(move || if let Some(job) = job.take() { job() }))
+			.expect("merge pool unavailable");
+	}
+}
+
+fn read(name: &str) -> Option<usize> {
+	::std::env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+thread_local! {
+	static POOL: MergePool = MergePool::new(::std::cmp::max(1, read("DIFFERENTIAL_MERGE_THREADS").unwrap_or(1)));
+	static THRESHOLD: Cell<Option<usize>> = Cell::new(read("DIFFERENTIAL_MERGE_BACKGROUND"));
+}
+
+/// Sets the least number of updates in merges begun on this thread that run in the background.
+///
+/// A threshold of `None` performs all merges on this thread.
+pub fn set_background_threshold(threshold: Option<usize>) {
+	THRESHOLD.with(|x| x.set(threshold));
+}
+
+/// The least number of updates in merges begun on this thread that run in the background.
+pub fn background_threshold() -> Option<usize> {
+	THRESHOLD.with(|x| x.get())
+}
+
+/// A shared batch whose merges may run in the background.
+#[derive(Debug)]
+pub struct Background<B> {
+	/// The shared batch.
+	pub batch: Arc<B>,
+}
+
+impl<B> Clone for Background<B> {
+	fn clone(&self) -> Self { Background { batch: self.batch.clone() } }
+}
+
+impl<B> Background<B> {
+	/// Wraps `batch` for sharing.
+	pub fn new(batch: B) -> Self { Background { batch: Arc::new(batch) } }
+}
+
+impl<K, V, T, R, B: BatchReader<K, V, T, R>> BatchReader<K, V, T, R> for Background<B> {
+	type Cursor = BackgroundCursor<K, V, T, R, B>;
+	fn cursor(&self) -> Self::Cursor {
+		BackgroundCursor {
+			phantom: ::std::marker::PhantomData,
+			cursor: self.batch.cursor(),
+		}
+	}
+	fn len(&self) -> usize { self.batch.len() }
+	fn description(&self) -> &Description<T> { self.batch.description() }
+}
+
+impl<K, V, T, R, B> Batch<K, V, T, R> for Background<B>
+where
+	K: 'static,
+	V: 'static,
+	T: Clone+Send+'static,
+	R: 'static,
+	B: Batch<K, V, T, R>+Send+Sync+'static,
+{
+	type Batcher = BackgroundBatcher<K, V, T, R, B>;
+	type Builder = BackgroundBuilder<K, V, T, R, B>;
+	type Merger = BackgroundMerger<K, V, T, R, B>;
+}
+
+/// A cursor for navigating a shared batch.
+pub struct BackgroundCursor<K, V, T, R, B: BatchReader<K, V, T, R>> {
+	phantom: ::std::marker::PhantomData<(K, V, T, R)>,
+	cursor: B::Cursor,
+}
+
+impl<K, V, T, R, B: BatchReader<K, V, T, R>> Cursor<K, V, T, R> for BackgroundCursor<K, V, T, R, B> {
+
+	type Storage = Background<B>;
+
+	#[inline(always)] fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.key_valid(&storage.batch) }
+	#[inline(always)] fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.val_valid(&storage.batch) }
+
+	#[inline(always)] fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { self.cursor.key(&storage.batch) }
+	#[inline(always)] fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { self.cursor.val(&storage.batch) }
+
+	#[inline(always)]
+	fn map_times<L: FnMut(&T, &R)>(&mut self, storage: &Self::Storage, logic: L) {
+		self.cursor.map_times(&storage.batch, logic)
+	}
+
+	#[inline(always)] fn step_key(&mut self, storage: &Self::Storage) { self.cursor.step_key(&storage.batch) }
+	#[inline(always)] fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek_key(&storage.batch, key) }
+
+	#[inline(always)] fn step_val(&mut self, storage: &Self::Storage) { self.cursor.step_val(&storage.batch) }
+	#[inline(always)] fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.seek_val(&storage.batch, val) }
+
+	#[inline(always)] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(&storage.batch) }
+	#[inline(always)] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(&storage.batch) }
+
+	#[inline(always)] fn may_contain(&self, storage: &Self::Storage, key: &K) -> bool { self.cursor.may_contain(&storage.batch, key) }
+}
+
+/// A batcher for shared batches.
+pub struct BackgroundBatcher<K, V, T, R, B: Batch<K, V, T, R>> { batcher: B::Batcher }
+
+impl<K, V, T, R, B> Batcher<K, V, T, R, Background<B>> for BackgroundBatcher<K, V, T, R, B>
+where
+	K: 'static,
+	V: 'static,
+	T: Clone+Send+'static,
+	R: 'static,
+	B: Batch<K, V, T, R>+Send+Sync+'static,
+{
+	fn new() -> Self { BackgroundBatcher { batcher: <B::Batcher as Batcher<K, V, T, R, B>>::new() } }
+	fn push_batch(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_batch(batch) }
+	fn push_sorted(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_sorted(batch) }
+	fn seal(&mut self, upper: &[T]) -> Background<B> { Background::new(self.batcher.seal(upper)) }
+	fn frontier(&mut self) -> &[T] { self.batcher.frontier() }
+}
+
+/// A builder for shared batches.
+pub struct BackgroundBuilder<K, V, T, R, B: Batch<K, V, T, R>> { builder: B::Builder }
+
+impl<K, V, T, R, B> Builder<K, V, T, R, Background<B>> for BackgroundBuilder<K, V, T, R, B>
+where
+	K: 'static,
+	V: 'static,
+	T: Clone+Send+'static,
+	R: 'static,
+	B: Batch<K, V, T, R>+Send+Sync+'static,
+{
+	fn new() -> Self { BackgroundBuilder { builder: <B::Builder as Builder<K, V, T, R, B>>::new() } }
+	fn with_capacity(cap: usize) -> Self { BackgroundBuilder { builder: <B::Builder as Builder<K, V, T, R, B>>::with_capacity(cap) } }
+	fn push(&mut self, element: (K, V, T, R)) { self.builder.push(element) }
+	fn done(self, lower: &[T], upper: &[T], since: &[T]) -> Background<B> { Background::new(self.builder.done(lower, upper, since)) }
+}
+
+// The progress of a merge.
+enum MergeProgress<K, V, T, R, B: Batch<K, V, T, R>> {
+	// The merge is performed on this thread.
+	Inline(B::Merger),
+	// The merge will run in the background, once it learns its compaction frontier.
+	Unstarted,
+	// The merge is running in the background.
+	Running(Receiver<B>),
+	// The merge has completed in the background.
+	Complete(B),
+}
+
+/// A merge in progress of shared batches, on this thread or in the background.
+pub struct BackgroundMerger<K, V, T, R, B: Batch<K, V, T, R>> {
+	progress: Option<MergeProgress<K, V, T, R, B>>,
+}
+
+impl<K, V, T, R, B> Merger<K, V, T, R, Background<B>> for BackgroundMerger<K, V, T, R, B>
+where
+	K: 'static,
+	V: 'static,
+	T: Clone+Send+'static,
+	R: 'static,
+	B: Batch<K, V, T, R>+Send+Sync+'static,
+{
+	fn new(source1: &Background<B>, source2: &Background<B>) -> Self {
+		let offload = background_threshold().map(|threshold| source1.len() + source2.len() >= threshold).unwrap_or(false);
+		let progress =
+		if offload { MergeProgress::Unstarted }
+		else { MergeProgress::Inline(source1.batch.begin_merge(&source2.batch)) };
+		BackgroundMerger { progress: Some(progress) }
+	}
+	fn work(&mut self, source1: &Background<B>, source2: &Background<B>, frontier: &Option<Vec<T>>, fuel: &mut usize) {
+		let progress = match self.progress.take().expect("merge already completed") {
+			MergeProgress::Inline(mut merger) => {
+				merger.work(&source1.batch, &source2.batch, frontier, fuel);
+				MergeProgress::Inline(merger)
+			},
+			MergeProgress::Unstarted => {
+				let (sender, receiver) = channel();
+				let batch1 = source1.batch.clone();
+				let batch2 = source2.batch.clone();
+				let frontier = frontier.clone();
+				POOL.with(|pool| pool.spawn(move || {
+					let mut merger = batch1.begin_merge(&batch2);
+					let mut fuel = usize::max_value();
+					merger.work(&batch1, &batch2, &frontier, &mut fuel);
+					// the worker may have dropped the merge, and the result with it.
+					let _ = sender.send(merger.done());
+				}));
+				Self::poll(receiver, *fuel)
+			},
+			MergeProgress::Running(receiver) => Self::poll(receiver, *fuel),
+			MergeProgress::Complete(batch) => MergeProgress::Complete(batch),
+		};
+		self.progress = Some(progress);
+	}
+	fn pending(&self) -> bool {
+		match self.progress {
+			Some(MergeProgress::Running(_)) => true,
+			_ => false,
+		}
+	}
+	fn done(self) -> Background<B> {
+		match self.progress.expect("merge already completed") {
+			MergeProgress::Inline(merger) => Background::new(merger.done()),
+			MergeProgress::Unstarted => panic!("merge completed without work"),
+			MergeProgress::Running(receiver) => Background::new(receiver.recv().expect("background merge failed")),
+			MergeProgress::Complete(batch) => Background::new(batch),
+		}
+	}
+}
+
+impl<K, V, T, R, B: Batch<K, V, T, R>> BackgroundMerger<K, V, T, R, B> {
+	// Collects the result of a background merge, if available, and otherwise leaves the merge running
+	// and the fuel unspent.
+	//
+	// Unbounded fuel indicates that the merge must complete now, and the result is awaited.
+	fn poll(receiver: Receiver<B>, fuel: usize) -> MergeProgress<K, V, T, R, B> {
+		let result =
+		if fuel == usize::max_value() { Some(receiver.recv().expect("background merge failed")) }
+		else {
+			match receiver.try_recv() {
+				Ok(batch) => Some(batch),
+				Err(TryRecvError::Empty) => None,
+				Err(TryRecvError::Disconnected) => panic!("background merge failed"),
+			}
+		};
+
+		match result {
+			Some(batch) => MergeProgress::Complete(batch),
+			None => MergeProgress::Running(receiver),
+		}
+	}
+}
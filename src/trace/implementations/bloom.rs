@@ -0,0 +1,222 @@
+//! Batches with Bloom filters of their keys.
+//!
+//! Operators that look up keys in a trace, like `half_join`, seek each key in each batch of the trace, at
+//! a cost logarithmic in the size of the batch. When lookups are selective most batches do not contain the
+//! key, and the search establishes only its absence. The `BloomBatch` wraps a batch with a Bloom filter of
+//! its keys, built as the batch is built or merged, and its cursor reports keys the filter excludes through
+//! `Cursor::may_contain`. Probes with `Cursor::probe_key` then skip the batches that cannot contain the key.
+//!
+//! The filter uses ten bits for each distinct key, and has rate of false positives of around one percent.
+
+use std::rc::Rc;
+
+use timely_sort::Unsigned;
+
+use hashable::Hashable;
+
+use trace::{Batch, BatchReader, Batcher, Builder, Merger, Cursor};
+use trace::description::Description;
+
+use super::spine_fueled::Spine;
+use super::ord::{OrdValBatch, OrdKeyBatch};
+
+/// A trace implementation using a spine of ordered lists with Bloom filters.
+pub type BloomValSpine<K, V, T, R> = Spine<K, V, T, R, Rc<BloomBatch<OrdValBatch<K, V, T, R>>>>;
+
+/// A trace implementation for empty values using a spine of ordered lists with Bloom filters.
+pub type BloomKeySpine<K, T, R> = Spine<K, (), T, R, Rc<BloomBatch<OrdKeyBatch<K, T, R>>>>;
+
+/// The number of bits of filter for each distinct key.
+const BITS_PER_KEY: usize = 10;
+/// The number of bits set for each key.
+const HASHES: u64 = 7;
+
+/// A Bloom filter of hashed keys.
+#[derive(Debug, Abomonation)]
+pub struct BloomFilter {
+	bits: Vec<u64>,
+}
+
+impl BloomFilter {
+	/// Creates a filter containing the supplied key hashes.
+	pub fn new(hashes: &[u64]) -> Self {
+		let words = ::std::cmp::max(1, (hashes.len() * BITS_PER_KEY + 63) / 64).next_power_of_two();
+		let mut filter = BloomFilter { bits: vec![0; words] };
+		for &hash in hashes.iter() {
+			let mask = (filter.bits.len() * 64 - 1) as u64;
+			let (mut bit, step) = Self::probes(hash);
+			for _ in 0 .. HASHES {
+				let index = bit & mask;
+				filter.bits[(index / 64) as usize] |= 1 << (index % 64);
+				bit = bit.wrapping_add(step);
+			}
+		}
+		filter
+	}
+
+	/// Indicates if the filter may contain the key hash, returning `false` only if it is absent.
+	#[inline]
+	pub fn may_contain(&self, hash: u64) -> bool {
+		let mask = (self.bits.len() * 64 - 1) as u64;
+		let (mut bit, step) = Self::probes(hash);
+		for _ in 0 .. HASHES {
+			let index = bit & mask;
+			if self.bits[(index / 64) as usize] & (1 << (index % 64)) == 0 {
+				return false;
+			}
+			bit = bit.wrapping_add(step);
+		}
+		true
+	}
+
+	// The first bit and the step between bits for `hash`, mixed so that keys hashing to themselves are spread out.
+	#[inline(always)]
+	fn probes(hash: u64) -> (u64, u64) {
+		let mut mixed = hash.wrapping_add(0x9e3779b97f4a7c15);
+		mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d049bb133111eb);
+		mixed = mixed ^ (mixed >> 31);
+		(mixed, (mixed >> 32) | 1)
+	}
+}
+
+#[inline(always)]
+fn hash_key<K: Hashable>(key: &K) -> u64 { key.hashed().as_u64() }
+
+/// A batch with a Bloom filter of its keys.
+#[derive(Debug, Abomonation)]
+pub struct BloomBatch<B> {
+	/// The batch of updates.
+	pub batch: B,
+	/// A filter of the keys of `batch`.
+	pub filter: BloomFilter,
+}
+
+impl<B> BloomBatch<B> {
+	/// Builds a filter of the keys of `batch`.
+	pub fn new<K: Hashable, V, T, R>(batch: B) -> Self where B: BatchReader<K, V, T, R> {
+		let mut hashes = Vec::new();
+		let mut cursor = batch.cursor();
+		while let Some(key) = cursor.get_key(&batch) {
+			hashes.push(hash_key(key));
+			cursor.step_key(&batch);
+		}
+		BloomBatch {
+			filter: BloomFilter::new(&hashes[..]),
+			batch: batch,
+		}
+	}
+}
+
+impl<K: Hashable, V, T, R, B: BatchReader<K, V, T, R>> BatchReader<K, V, T, R> for BloomBatch<B> {
+	type Cursor = BloomCursor<K, V, T, R, B>;
+	fn cursor(&self) -> Self::Cursor {
+		BloomCursor {
+			phantom: ::std::marker::PhantomData,
+			cursor: self.batch.cursor(),
+		}
+	}
+	fn len(&self) -> usize { self.batch.len() }
+	fn description(&self) -> &Description<T> { self.batch.description() }
+}
+
+impl<K: Hashable, V, T, R, B: Batch<K, V, T, R>> Batch<K, V, T, R> for BloomBatch<B> {
+	type Batcher = BloomBatcher<K, V, T, R, B>;
+	type Builder = BloomBuilder<K, V, T, R, B>;
+	type Merger = BloomMerger<K, V, T, R, B>;
+}
+
+/// A cursor for navigating a batch with a Bloom filter.
+pub struct BloomCursor<K, V, T, R, B: BatchReader<K, V, T, R>> {
+	phantom: ::std::marker::PhantomData<(K, V, T, R)>,
+	cursor: B::Cursor,
+}
+
+impl<K: Hashable, V, T, R, B: BatchReader<K, V, T, R>> Cursor<K, V, T, R> for BloomCursor<K, V, T, R, B> {
+
+	type Storage = BloomBatch<B>;
+
+	#[inline(always)] fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.key_valid(&storage.batch) }
+	#[inline(always)] fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.val_valid(&storage.batch) }
+
+	#[inline(always)] fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { self.cursor.key(&storage.batch) }
+	#[inline(always)] fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { self.cursor.val(&storage.batch) }
+
+	#[inline(always)]
+	fn map_times<L: FnMut(&T, &R)>(&mut self, storage: &Self::Storage, logic: L) {
+		self.cursor.map_times(&storage.batch, logic)
+	}
+
+	#[inline(always)] fn step_key(&mut self, storage: &Self::Storage) { self.cursor.step_key(&storage.batch) }
+	#[inline(always)] fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek_key(&storage.batch, key) }
+
+	#[inline(always)] fn step_val(&mut self, storage: &Self::Storage) { self.cursor.step_val(&storage.batch) }
+	#[inline(always)] fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.seek_val(&storage.batch, val) }
+
+	#[inline(always)] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(&storage.batch) }
+	#[inline(always)] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(&storage.batch) }
+
+	#[inline(always)]
+	fn may_contain(&self, storage: &Self::Storage, key: &K) -> bool {
+		storage.filter.may_contain(hash_key(key))
+	}
+}
+
+/// A batcher for batches with Bloom filters.
+pub struct BloomBatcher<K, V, T, R, B: Batch<K, V, T, R>> { batcher: B::Batcher }
+
+impl<K: Hashable, V, T, R, B: Batch<K, V, T, R>> Batcher<K, V, T, R, BloomBatch<B>> for BloomBatcher<K, V, T, R, B> {
+	fn new() -> Self { BloomBatcher { batcher: <B::Batcher as Batcher<K, V, T, R, B>>::new() } }
+	fn push_batch(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_batch(batch) }
+	fn push_sorted(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_sorted(batch) }
+	fn seal(&mut self, upper: &[T]) -> BloomBatch<B> { BloomBatch::new::<K, V, T, R>(self.batcher.seal(upper)) }
+	fn frontier(&mut self) -> &[T] { self.batcher.frontier() }
+}
+
+/// A builder for batches with Bloom filters.
+pub struct BloomBuilder<K, V, T, R, B: Batch<K, V, T, R>> {
+	builder: B::Builder,
+	hashes: Vec<u64>,
+}
+
+impl<K: Hashable, V, T, R, B: Batch<K, V, T, R>> Builder<K, V, T, R, BloomBatch<B>> for BloomBuilder<K, V, T, R, B> {
+	fn new() -> Self {
+		BloomBuilder { builder: <B::Builder as Builder<K, V, T, R, B>>::new(), hashes: Vec::new() }
+	}
+	fn with_capacity(cap: usize) -> Self {
+		BloomBuilder { builder: <B::Builder as Builder<K, V, T, R, B>>::with_capacity(cap), hashes: Vec::new() }
+	}
+	#[inline(always)]
+	fn push(&mut self, element: (K, V, T, R)) {
+		// updates arrive ordered by key, and so repeated keys are consecutive.
+		let hash = hash_key(&element.0);
+		if self.hashes.last() != Some(&hash) {
+			self.hashes.push(hash);
+		}
+		self.builder.push(element);
+	}
+	fn done(self, lower: &[T], upper: &[T], since: &[T]) -> BloomBatch<B> {
+		BloomBatch {
+			filter: BloomFilter::new(&self.hashes[..]),
+			batch: self.builder.done(lower, upper, since),
+		}
+	}
+}
+
+/// A merge in progress of batches with Bloom filters.
+pub struct BloomMerger<K, V, T, R, B: Batch<K, V, T, R>> { merger: B::Merger }
+
+impl<K: Hashable, V, T, R, B: Batch<K, V, T, R>> Merger<K, V, T, R, BloomBatch<B>> for BloomMerger<K, V, T, R, B> {
+	fn new(source1: &BloomBatch<B>, source2: &BloomBatch<B>) -> Self {
+		BloomMerger { merger: source1.batch.begin_merge(&source2.batch) }
+	}
+	fn work(&mut self, source1: &BloomBatch<B>, source2: &BloomBatch<B>, frontier: &Option<Vec<T>>, fuel: &mut usize) {
+		self.merger.work(&source1.batch, &source2.batch, frontier, fuel)
+	}
+	fn done(self) -> BloomBatch<B> {
+		BloomBatch::new::<K, V, T, R>(self.merger.done())
+	}
+	fn pending(&self) -> bool {
+		self.merger.pending()
+	}
+}
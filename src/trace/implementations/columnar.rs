@@ -0,0 +1,368 @@
+//! Trace and batch implementations storing each coordinate of updates in its own column.
+//!
+//! The `ColValBatch` holds the distinct keys, the distinct values of each key, and the times and
+//! differences of each value in four separate vectors, with offsets relating each key to its range
+//! of values and each value to its range of updates. This is the layout of `OrdValBatch`, except
+//! that times and differences are held apart rather than as pairs, so that scans of times do not
+//! step over differences and the columns need no padding to align pairs of wide types.
+//!
+//! The columns are also exposed directly, so that code reading a batch in bulk, for example to
+//! consolidate or export it, can do so a column at a time rather than through a cursor.
+
+use std::rc::Rc;
+
+use ::difference::Monoid;
+use lattice::Lattice;
+
+use trace::{Batch, BatchReader, Builder, Merger, Cursor, consolidate};
+use trace::description::Description;
+use trace::layers::ordered_leaf::advance;
+
+use super::spine_fueled::Spine;
+use super::merge_batcher::MergeBatcher;
+
+/// A trace implementation using a spine of columnar batches.
+pub type ColValSpine<K, V, T, R> = Spine<K, V, T, R, Rc<ColValBatch<K, V, T, R>>>;
+
+/// A trace implementation for empty values using a spine of columnar batches.
+pub type ColKeySpine<K, T, R> = Spine<K, (), T, R, Rc<ColValBatch<K, (), T, R>>>;
+
+/// An immutable collection of update tuples, from a contiguous interval of logical times.
+#[derive(Debug, Abomonation)]
+pub struct ColValBatch<K, V, T: Lattice, R> {
+	/// The distinct keys, in order.
+	pub keys: Vec<K>,
+	/// The values of key `i` are those in `key_offs[i] .. key_offs[i+1]`.
+	pub key_offs: Vec<usize>,
+	/// The distinct values of each key, in order.
+	pub vals: Vec<V>,
+	/// The updates of value `i` are those in `val_offs[i] .. val_offs[i+1]`.
+	pub val_offs: Vec<usize>,
+	/// The times of updates, in order for each value.
+	pub times: Vec<T>,
+	/// The differences of updates, corresponding to `times`.
+	pub diffs: Vec<R>,
+	/// Description of the update times this batch represents.
+	pub desc: Description<T>,
+}
+
+impl<K, V, T, R> BatchReader<K, V, T, R> for ColValBatch<K, V, T, R>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+'static, R: Monoid {
+	type Cursor = ColValCursor;
+	fn cursor(&self) -> Self::Cursor { ColValCursor { key: 0, val: self.key_offs[0] } }
+	fn len(&self) -> usize { self.times.len() }
+	fn description(&self) -> &Description<T> { &self.desc }
+}
+
+impl<K, V, T, R> Batch<K, V, T, R> for ColValBatch<K, V, T, R>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Monoid {
+	type Batcher = MergeBatcher<K, V, T, R, Self>;
+	type Builder = ColValBuilder<K, V, T, R>;
+	type Merger = ColValMerger<K, V, T, R>;
+
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		ColValMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating a columnar batch.
+#[derive(Debug)]
+pub struct ColValCursor {
+	key: usize,
+	val: usize,
+}
+
+impl<K, V, T, R> Cursor<K, V, T, R> for ColValCursor
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Monoid {
+
+	type Storage = ColValBatch<K, V, T, R>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &storage.keys[self.key] }
+	fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { &storage.vals[self.val] }
+	fn map_times<L: FnMut(&T, &R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let lower = storage.val_offs[self.val];
+		let upper = storage.val_offs[self.val + 1];
+		for index in lower .. upper {
+			logic(&storage.times[index], &storage.diffs[index]);
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.key < storage.keys.len() }
+	fn val_valid(&self, storage: &Self::Storage) -> bool {
+		self.key < storage.keys.len() && self.val < storage.key_offs[self.key + 1]
+	}
+	fn step_key(&mut self, storage: &Self::Storage){
+		if self.key < storage.keys.len() { self.key += 1; }
+		self.val = storage.key_offs[self.key];
+	}
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) {
+		self.key += advance(&storage.keys[self.key ..], |x| x.lt(key));
+		self.val = storage.key_offs[self.key];
+	}
+	fn step_val(&mut self, storage: &Self::Storage) {
+		if self.val_valid(storage) { self.val += 1; }
+	}
+	fn seek_val(&mut self, storage: &Self::Storage, val: &V) {
+		if self.key_valid(storage) {
+			let upper = storage.key_offs[self.key + 1];
+			self.val += advance(&storage.vals[self.val .. upper], |x| x.lt(val));
+		}
+	}
+	fn rewind_keys(&mut self, storage: &Self::Storage) {
+		self.key = 0;
+		self.val = storage.key_offs[0];
+	}
+	fn rewind_vals(&mut self, storage: &Self::Storage) {
+		self.val = storage.key_offs[self.key];
+	}
+}
+
+/// A builder for creating columnar batches from ordered update tuples.
+pub struct ColValBuilder<K, V, T, R> {
+	keys: Vec<K>,
+	key_offs: Vec<usize>,
+	vals: Vec<V>,
+	val_offs: Vec<usize>,
+	times: Vec<T>,
+	diffs: Vec<R>,
+}
+
+impl<K, V, T, R> ColValBuilder<K, V, T, R>
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Monoid {
+
+	// Appends the updates of value `val` of `batch`, returning their number.
+	fn copy_updates(&mut self, batch: &ColValBatch<K, V, T, R>, val: usize) -> usize {
+		let lower = batch.val_offs[val];
+		let upper = batch.val_offs[val + 1];
+		self.times.extend_from_slice(&batch.times[lower .. upper]);
+		self.diffs.extend_from_slice(&batch.diffs[lower .. upper]);
+		upper - lower
+	}
+
+	// Completes the most recently pushed value, whose updates start at `start`, consolidating its
+	// updates if they may be out of order or have been advanced, and removing it if none remain.
+	fn seal_val(&mut self, start: usize, frontier: Option<&[T]>, unordered: bool) {
+		if frontier.is_some() || unordered {
+			let mut updates: Vec<(T, R)> = self.times.drain(start ..).zip(self.diffs.drain(start ..)).collect();
+			if let Some(frontier) = frontier {
+				for update in updates.iter_mut() {
+					update.0.advance_by(frontier);
+				}
+			}
+			consolidate(&mut updates, 0);
+			for (time, diff) in updates.into_iter() {
+				self.times.push(time);
+				self.diffs.push(diff);
+			}
+		}
+		if self.times.len() == start {
+			self.vals.pop();
+			self.val_offs.pop();
+		}
+	}
+
+	// Completes the most recently pushed key, whose values start at `start`, removing it if no values remain.
+	fn seal_key(&mut self, start: usize) {
+		if self.vals.len() == start {
+			self.keys.pop();
+			self.key_offs.pop();
+		}
+	}
+
+	// Appends key `key` of `batch`, returning the number of updates read.
+	fn copy_key(&mut self, batch: &ColValBatch<K, V, T, R>, key: usize, frontier: Option<&[T]>) -> usize {
+		let mut effort = 0;
+		let start = self.vals.len();
+		self.keys.push(batch.keys[key].clone());
+		self.key_offs.push(start);
+		for val in batch.key_offs[key] .. batch.key_offs[key + 1] {
+			let times = self.times.len();
+			self.vals.push(batch.vals[val].clone());
+			self.val_offs.push(times);
+			effort += self.copy_updates(batch, val);
+			self.seal_val(times, frontier, false);
+		}
+		self.seal_key(start);
+		effort
+	}
+
+	// Appends the merge of key `key1` of `batch1` and the equal key `key2` of `batch2`, returning the number of updates read.
+	fn merge_key(&mut self, (batch1, key1): (&ColValBatch<K, V, T, R>, usize), (batch2, key2): (&ColValBatch<K, V, T, R>, usize), frontier: Option<&[T]>) -> usize {
+
+		let mut effort = 0;
+		let start = self.vals.len();
+		self.keys.push(batch1.keys[key1].clone());
+		self.key_offs.push(start);
+
+		let (mut lower1, upper1) = (batch1.key_offs[key1], batch1.key_offs[key1 + 1]);
+		let (mut lower2, upper2) = (batch2.key_offs[key2], batch2.key_offs[key2 + 1]);
+
+		while lower1 < upper1 || lower2 < upper2 {
+			let order =
+			if lower1 == upper1 { ::std::cmp::Ordering::Greater }
+			else if lower2 == upper2 { ::std::cmp::Ordering::Less }
+			else { batch1.vals[lower1].cmp(&batch2.vals[lower2]) };
+
+			let times = self.times.len();
+			match order {
+				::std::cmp::Ordering::Less => {
+					self.vals.push(batch1.vals[lower1].clone());
+					self.val_offs.push(times);
+					effort += self.copy_updates(batch1, lower1);
+					self.seal_val(times, frontier, false);
+					lower1 += 1;
+				},
+				::std::cmp::Ordering::Equal => {
+					self.vals.push(batch1.vals[lower1].clone());
+					self.val_offs.push(times);
+					effort += self.copy_updates(batch1, lower1);
+					effort += self.copy_updates(batch2, lower2);
+					self.seal_val(times, frontier, true);
+					lower1 += 1;
+					lower2 += 1;
+				},
+				::std::cmp::Ordering::Greater => {
+					self.vals.push(batch2.vals[lower2].clone());
+					self.val_offs.push(times);
+					effort += self.copy_updates(batch2, lower2);
+					self.seal_val(times, frontier, false);
+					lower2 += 1;
+				},
+			}
+		}
+
+		self.seal_key(start);
+		effort
+	}
+
+	// Completes the columns, producing a batch with `description`.
+	fn finish(mut self, description: Description<T>) -> ColValBatch<K, V, T, R> {
+		self.key_offs.push(self.vals.len());
+		self.val_offs.push(self.times.len());
+		ColValBatch {
+			keys: self.keys,
+			key_offs: self.key_offs,
+			vals: self.vals,
+			val_offs: self.val_offs,
+			times: self.times,
+			diffs: self.diffs,
+			desc: description,
+		}
+	}
+}
+
+impl<K, V, T, R> Builder<K, V, T, R, ColValBatch<K, V, T, R>> for ColValBuilder<K, V, T, R>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Monoid {
+
+	fn new() -> Self {
+		<Self as Builder<K, V, T, R, ColValBatch<K, V, T, R>>>::with_capacity(0)
+	}
+	fn with_capacity(cap: usize) -> Self {
+		ColValBuilder {
+			keys: Vec::new(),
+			key_offs: Vec::new(),
+			vals: Vec::new(),
+			val_offs: Vec::new(),
+			times: Vec::with_capacity(cap),
+			diffs: Vec::with_capacity(cap),
+		}
+	}
+
+	#[inline(always)]
+	fn push(&mut self, (key, val, time, diff): (K, V, T, R)) {
+		if self.keys.last() != Some(&key) {
+			self.keys.push(key);
+			self.key_offs.push(self.vals.len());
+			self.vals.push(val);
+			self.val_offs.push(self.times.len());
+		}
+		else if self.vals.last() != Some(&val) {
+			self.vals.push(val);
+			self.val_offs.push(self.times.len());
+		}
+		self.times.push(time);
+		self.diffs.push(diff);
+	}
+
+	#[inline(never)]
+	fn done(self, lower: &[T], upper: &[T], since: &[T]) -> ColValBatch<K, V, T, R> {
+		self.finish(Description::new(lower, upper, since))
+	}
+}
+
+/// State for an in-progress merge.
+pub struct ColValMerger<K, V, T: Lattice, R> {
+	// first batch, and position therein.
+	lower1: usize,
+	upper1: usize,
+	// second batch, and position therein.
+	lower2: usize,
+	upper2: usize,
+	// result that we are currently assembling.
+	result: ColValBuilder<K, V, T, R>,
+	description: Description<T>,
+}
+
+impl<K, V, T, R> Merger<K, V, T, R, ColValBatch<K, V, T, R>> for ColValMerger<K, V, T, R>
+where K: Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Monoid {
+	fn new(batch1: &ColValBatch<K, V, T, R>, batch2: &ColValBatch<K, V, T, R>) -> Self {
+
+		assert!(batch1.upper() == batch2.lower());
+
+		let since = if batch1.description().since().iter().all(|t1| batch2.description().since().iter().any(|t2| t2.less_equal(t1))) {
+			batch2.description().since()
+		}
+		else {
+			batch1.description().since()
+		};
+
+		let description = Description::new(batch1.lower(), batch2.upper(), since);
+
+		ColValMerger {
+			lower1: 0,
+			upper1: batch1.keys.len(),
+			lower2: 0,
+			upper2: batch2.keys.len(),
+			result: <ColValBuilder<K, V, T, R> as Builder<K, V, T, R, ColValBatch<K, V, T, R>>>::with_capacity(batch1.len() + batch2.len()),
+			description: description,
+		}
+	}
+	fn done(self) -> ColValBatch<K, V, T, R> {
+
+		assert!(self.lower1 == self.upper1);
+		assert!(self.lower2 == self.upper2);
+
+		self.result.finish(self.description)
+	}
+	fn work(&mut self, source1: &ColValBatch<K,V,T,R>, source2: &ColValBatch<K,V,T,R>, frontier: &Option<Vec<T>>, fuel: &mut usize) {
+
+		let frontier = frontier.as_ref().map(|frontier| &frontier[..]);
+		let mut effort = 0;
+
+		// merge keys in order, until both mergees are exhausted or fuel runs out.
+		while (self.lower1 < self.upper1 || self.lower2 < self.upper2) && effort < *fuel {
+			let order =
+			if self.lower1 == self.upper1 { ::std::cmp::Ordering::Greater }
+			else if self.lower2 == self.upper2 { ::std::cmp::Ordering::Less }
+			else { source1.keys[self.lower1].cmp(&source2.keys[self.lower2]) };
+
+			match order {
+				::std::cmp::Ordering::Less => {
+					effort += self.result.copy_key(source1, self.lower1, frontier);
+					self.lower1 += 1;
+				},
+				::std::cmp::Ordering::Equal => {
+					effort += self.result.merge_key((source1, self.lower1), (source2, self.lower2), frontier);
+					self.lower1 += 1;
+					self.lower2 += 1;
+				},
+				::std::cmp::Ordering::Greater => {
+					effort += self.result.copy_key(source2, self.lower2, frontier);
+					self.lower2 += 1;
+				},
+			}
+		}
+
+		if effort >= *fuel { *fuel = 0; }
+		else 			   { *fuel -= effort; }
+	}
+}
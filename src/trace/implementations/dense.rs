@@ -0,0 +1,206 @@
+//! Trace and batch implementations for small dense integer keys.
+//!
+//! Graph computations commonly key their collections by node identifiers drawn from `0 .. nodes`.
+//! The `DenseBatch` stores updates as a `ColValBatch`, and adds an index from each integer from the
+//! batch's least key up to its largest key to the position of the first key at least as large. Seeking
+//! a key is then a lookup rather than a search, which matters for joins that seek many keys in each batch.
+//!
+//! The index has one entry for each integer between the least and largest keys, however many keys are
+//! present. Batches whose keys are too sparse for this, with a range of more than `SPARSITY` integers for
+//! each key, are not indexed, and their keys are found by binary search as in other batches.
+
+use std::rc::Rc;
+
+use ::difference::Monoid;
+use lattice::Lattice;
+
+use trace::{Batch, BatchReader, Builder, Merger, Cursor};
+use trace::description::Description;
+use trace::layers::ordered_leaf::advance;
+
+use super::spine_fueled::Spine;
+use super::merge_batcher::MergeBatcher;
+use super::columnar::{ColValBatch, ColValBuilder, ColValMerger};
+
+/// A trace implementation for dense integer keys.
+pub type DenseValSpine<K, V, T, R> = Spine<K, V, T, R, Rc<DenseBatch<K, V, T, R>>>;
+
+/// A trace implementation for dense integer keys and empty values.
+pub type DenseKeySpine<K, T, R> = Spine<K, (), T, R, Rc<DenseBatch<K, (), T, R>>>;
+
+/// The number of integers per key in the range of a batch's keys beyond which the batch is not indexed.
+pub const SPARSITY: usize = 4;
+
+/// Keys that correspond to positions in a vector.
+///
+/// The order of keys must be the order of their indices.
+pub trait DenseKey {
+	/// The position corresponding to the key.
+	fn index(&self) -> usize;
+}
+
+impl DenseKey for u8 { #[inline(always)] fn index(&self) -> usize { *self as usize } }
+impl DenseKey for u16 { #[inline(always)] fn index(&self) -> usize { *self as usize } }
+impl DenseKey for u32 { #[inline(always)] fn index(&self) -> usize { *self as usize } }
+impl DenseKey for u64 { #[inline(always)] fn index(&self) -> usize { *self as usize } }
+impl DenseKey for usize { #[inline(always)] fn index(&self) -> usize { *self } }
+
+/// An immutable collection of update tuples with dense integer keys.
+#[derive(Debug, Abomonation)]
+pub struct DenseBatch<K, V, T: Lattice, R> {
+	/// The updates, in columns.
+	pub batch: ColValBatch<K, V, T, R>,
+	/// The integer of the least key, from which `index` is offset.
+	pub offset: usize,
+	/// For each integer `offset + i` up to one more than the largest key, the position in `batch.keys` of the first
+	/// key at least `offset + i`, or nothing if the keys are too sparse to index.
+	pub index: Vec<usize>,
+}
+
+impl<K: DenseKey, V, T: Lattice, R> DenseBatch<K, V, T, R> {
+	/// Indexes the keys of `batch`.
+	pub fn new(batch: ColValBatch<K, V, T, R>) -> Self {
+		let offset = batch.keys.first().map(|key| key.index()).unwrap_or(0);
+		let range = batch.keys.last().map(|key| key.index() + 1 - offset).unwrap_or(0);
+		let mut index = Vec::new();
+		if range <= SPARSITY * batch.keys.len() {
+			index.reserve(range + 1);
+			for (position, key) in batch.keys.iter().enumerate() {
+				while offset + index.len() <= key.index() {
+					index.push(position);
+				}
+			}
+			index.push(batch.keys.len());
+		}
+		DenseBatch {
+			batch: batch,
+			offset: offset,
+			index: index,
+		}
+	}
+
+	// The position of the first key at least `key`.
+	#[inline(always)]
+	fn position(&self, key: &K) -> usize {
+		let index = key.index();
+		if self.index.is_empty() {
+			advance(&self.batch.keys[..], |x| x.index() < index)
+		}
+		else if index < self.offset { 0 }
+		else if index - self.offset < self.index.len() { self.index[index - self.offset] }
+		else { self.batch.keys.len() }
+	}
+}
+
+impl<K, V, T, R> BatchReader<K, V, T, R> for DenseBatch<K, V, T, R>
+where K: DenseKey+Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+'static, R: Monoid {
+	type Cursor = DenseCursor;
+	fn cursor(&self) -> Self::Cursor { DenseCursor { key: 0, val: self.batch.key_offs[0] } }
+	fn len(&self) -> usize { self.batch.len() }
+	fn description(&self) -> &Description<T> { self.batch.description() }
+}
+
+impl<K, V, T, R> Batch<K, V, T, R> for DenseBatch<K, V, T, R>
+where K: DenseKey+Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Monoid {
+	type Batcher = MergeBatcher<K, V, T, R, Self>;
+	type Builder = DenseBuilder<K, V, T, R>;
+	type Merger = DenseMerger<K, V, T, R>;
+
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		DenseMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating a dense batch.
+#[derive(Debug)]
+pub struct DenseCursor {
+	key: usize,
+	val: usize,
+}
+
+impl<K, V, T, R> Cursor<K, V, T, R> for DenseCursor
+where K: DenseKey+Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Monoid {
+
+	type Storage = DenseBatch<K, V, T, R>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &storage.batch.keys[self.key] }
+	fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { &storage.batch.vals[self.val] }
+	fn map_times<L: FnMut(&T, &R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let lower = storage.batch.val_offs[self.val];
+		let upper = storage.batch.val_offs[self.val + 1];
+		for index in lower .. upper {
+			logic(&storage.batch.times[index], &storage.batch.diffs[index]);
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.key < storage.batch.keys.len() }
+	fn val_valid(&self, storage: &Self::Storage) -> bool {
+		self.key < storage.batch.keys.len() && self.val < storage.batch.key_offs[self.key + 1]
+	}
+	fn step_key(&mut self, storage: &Self::Storage){
+		if self.key < storage.batch.keys.len() { self.key += 1; }
+		self.val = storage.batch.key_offs[self.key];
+	}
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) {
+		let position = storage.position(key);
+		if self.key < position { self.key = position; }
+		self.val = storage.batch.key_offs[self.key];
+	}
+	fn step_val(&mut self, storage: &Self::Storage) {
+		if self.val_valid(storage) { self.val += 1; }
+	}
+	fn seek_val(&mut self, storage: &Self::Storage, val: &V) {
+		if self.key_valid(storage) {
+			let upper = storage.batch.key_offs[self.key + 1];
+			self.val += advance(&storage.batch.vals[self.val .. upper], |x| x.lt(val));
+		}
+	}
+	fn rewind_keys(&mut self, storage: &Self::Storage) {
+		self.key = 0;
+		self.val = storage.batch.key_offs[0];
+	}
+	fn rewind_vals(&mut self, storage: &Self::Storage) {
+		self.val = storage.batch.key_offs[self.key];
+	}
+}
+
+/// A builder for creating dense batches from ordered update tuples.
+pub struct DenseBuilder<K, V, T, R> {
+	builder: ColValBuilder<K, V, T, R>,
+}
+
+impl<K, V, T, R> Builder<K, V, T, R, DenseBatch<K, V, T, R>> for DenseBuilder<K, V, T, R>
+where K: DenseKey+Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Monoid {
+
+	fn new() -> Self {
+		DenseBuilder { builder: <ColValBuilder<K, V, T, R> as Builder<K, V, T, R, ColValBatch<K, V, T, R>>>::new() }
+	}
+	fn with_capacity(cap: usize) -> Self {
+		DenseBuilder { builder: <ColValBuilder<K, V, T, R> as Builder<K, V, T, R, ColValBatch<K, V, T, R>>>::with_capacity(cap) }
+	}
+	#[inline(always)]
+	fn push(&mut self, element: (K, V, T, R)) {
+		self.builder.push(element);
+	}
+	#[inline(never)]
+	fn done(self, lower: &[T], upper: &[T], since: &[T]) -> DenseBatch<K, V, T, R> {
+		DenseBatch::new(self.builder.done(lower, upper, since))
+	}
+}
+
+/// State for an in-progress merge.
+pub struct DenseMerger<K, V, T: Lattice, R> {
+	merger: ColValMerger<K, V, T, R>,
+}
+
+impl<K, V, T, R> Merger<K, V, T, R, DenseBatch<K, V, T, R>> for DenseMerger<K, V, T, R>
+where K: DenseKey+Ord+Clone+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Monoid {
+	fn new(batch1: &DenseBatch<K, V, T, R>, batch2: &DenseBatch<K, V, T, R>) -> Self {
+		DenseMerger { merger: ColValMerger::new(&batch1.batch, &batch2.batch) }
+	}
+	fn done(self) -> DenseBatch<K, V, T, R> {
+		DenseBatch::new(self.merger.done())
+	}
+	fn work(&mut self, source1: &DenseBatch<K,V,T,R>, source2: &DenseBatch<K,V,T,R>, frontier: &Option<Vec<T>>, fuel: &mut usize) {
+		self.merger.work(&source1.batch, &source2.batch, frontier, fuel);
+	}
+}
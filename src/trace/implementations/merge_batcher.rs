@@ -37,6 +37,11 @@ where
         self.sorter.push(batch);
     }
 
+    #[inline(never)]
+    fn push_sorted(&mut self, batch: &mut Vec<((K,V),T,R)>) {
+        self.sorter.push_sorted(batch);
+    }
+
     // Sealing a batch means finding those updates with times not greater or equal to any time
     // in `upper`. All updates must have time greater or equal to the previously used `upper`,
     // which we call `lower`, by assumption that after sealing a batcher we receive no more
@@ -204,8 +209,26 @@ impl<D: Ord, T: Ord, R: Monoid> MergeSorter<D, T, R> {
             ::std::mem::replace(batch, Vec::new())
         };
 
+        batch.sort_unstable_by(|x,y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
+        self.push_run(batch);
+    }
+
+    /// Pushes a batch already sorted by data and time, which is consolidated but not sorted.
+    #[inline]
+    pub fn push_sorted(&mut self, batch: &mut Vec<(D, T, R)>) {
+        let batch = if self.stash.len() > 2 {
+            ::std::mem::replace(batch, self.stash.pop().unwrap())
+        }
+        else {
+            ::std::mem::replace(batch, Vec::new())
+        };
+        debug_assert!(batch.windows(2).all(|x| (&x[0].0, &x[0].1) <= (&x[1].0, &x[1].1)), "push_sorted: batch is not sorted");
+        self.push_run(batch);
+    }
+
+    // Consolidates a sorted batch and enqueues it for merging.
+    fn push_run(&mut self, mut batch: Vec<(D, T, R)>) {
         if batch.len() > 0 {
-            batch.sort_unstable_by(|x,y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
             for index in 1 .. batch.len() {
                 if batch[index].0 == batch[index - 1].0 && batch[index].1 == batch[index - 1].1 {
                     let prev = ::std::mem::replace(&mut batch[index - 1].2, R::zero());
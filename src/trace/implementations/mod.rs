@@ -45,4 +45,11 @@ mod merge_batcher;
 pub use self::merge_batcher::MergeBatcher as Batcher;
 
 pub mod ord;
+pub mod columnar;
+pub mod append;
+pub mod dense;
+pub mod bloom;
+pub mod background;
+pub mod spill;
+pub mod store;
 // pub mod hash;
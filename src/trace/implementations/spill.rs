@@ -0,0 +1,925 @@
+//! A collection of update batches, some of which may be held on disk.
+//!
+//! The `SpillSpine` collects and merges batches much like the `Spine`, but it tracks the number of
+//! bytes used by the batches it holds. When this number exceeds a configured budget, the oldest
+//! batches in memory are written out, by default to files, and only a description of each is
+//! retained. Cursors over the trace read a spilled batch back in only once they visit keys in its
+//! range, which is recorded when the batch is spilled, and release it when the cursor is dropped.
+//! Seeks and probes for keys outside the range of a batch do not read it.
+//!
+//! This trades latency for survivability: cursor access to a trace with spilled batches performs
+//! file reads, but a trace whose contents do not fit in memory will not exhaust it. The trace is
+//! most appropriate for arrangements that are large but rarely read in their entirety, such as the
+//! inputs to a many-to-many join.
+//!
+//! Spilled batches can be read back in ahead of time with `warm`, which retains them in memory until
+//! `cool` is called, so that latency-sensitive reads do not wait on the disk.
+//!
+//! Spilled batches read back in by cursors may also be retained as pages, up to a second budget of
+//! bytes, so that repeated reads of the same batches do not each read them from disk. When the pages
+//! exceed this budget the least recently read are discarded. The page budget defaults to 256 megabytes,
+//! and may be set explicitly with `SpillSpine::set_page_budget`; a budget of zero retains no pages.
+//!
+//! Batches may also be spilled by age rather than size, with `SpillSpine::set_spill_age`, which keeps
+//! only the newest batches in memory. With a `CompressedStore` this compresses historical batches,
+//! which cursors decompress as they read them.
+//!
+//! The budget and the directory holding spilled batches default to one gigabyte and the system's
+//! temporary directory, and may be set explicitly with `SpillSpine::with_config`. `SpillConfig::from_env`
+//! reads them from the environment variables `DIFFERENTIAL_SPILL_BUDGET` (in bytes) and
+//! `DIFFERENTIAL_SPILL_DIR`, for programs that choose to be configured by them. Batches may be spilled to other storage than files
+//! with `SpillSpine::with_store`, using any implementation of the `BatchStore` trait.
+//!
+//! Failures to write or read spilled batches do not panic. A batch that cannot be written remains in
+//! memory, a merge whose inputs cannot be read is deferred, and a cursor presents a batch it cannot
+//! read as empty. The first such error is retained, and should be collected with `SpillSpine::error`.
+
+use std::rc::Rc;
+use std::cell::{RefCell, UnsafeCell};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::fmt::Debug;
+
+use abomonation::{Abomonation, measure};
+use abomonation::abomonated::Abomonated;
+
+use ::difference::Monoid;
+use lattice::Lattice;
+use trace::{Batch, BatchReader, Builder, Merger, Trace, TraceReader};
+use trace::cursor::{Cursor, CursorList};
+
+use super::ord::{OrdValBatch, OrdKeyBatch};
+use super::store::{BatchStore, FileStore};
+
+use ::timely::dataflow::operators::generic::OperatorInfo;
+
+/// A trace implementation using a spine of ordered lists, which may spill to disk.
+pub type OrdValSpill<K, V, T, R> = SpillSpine<K, V, T, R, Rc<Abomonated<OrdValBatch<K, V, T, R>, Vec<u8>>>>;
+
+/// A trace implementation for empty values using a spine of ordered lists, which may spill to disk.
+pub type OrdKeySpill<K, T, R> = SpillSpine<K, (), T, R, Rc<Abomonated<OrdKeyBatch<K, T, R>, Vec<u8>>>>;
+
+/// A batch that can be written to and read back from a sequence of bytes.
+pub trait Spill: Sized {
+    /// Writes the batch to `writer`.
+    fn spill<W: Write>(&self, writer: &mut W) -> ::std::io::Result<()>;
+    /// Reconstructs a batch from bytes written by `spill`.
+    fn restore(bytes: Vec<u8>) -> Option<Self>;
+    /// The number of bytes the batch occupies.
+    fn size(&self) -> usize;
+}
+
+impl<B: Abomonation> Spill for Rc<Abomonated<B, Vec<u8>>> {
+    fn spill<W: Write>(&self, writer: &mut W) -> ::std::io::Result<()> {
+        unsafe { ::abomonation::encode(&***self, writer) }
+    }
+    fn restore(bytes: Vec<u8>) -> Option<Self> {
+        unsafe { Abomonated::<B, _>::new(bytes) }.map(Rc::new)
+    }
+    fn size(&self) -> usize {
+        measure(&***self)
+    }
+}
+
+/// Configuration for when and where a `SpillSpine` writes batches to disk.
+#[derive(Clone, Debug)]
+pub struct SpillConfig {
+    /// The directory in which to write spilled batches.
+    pub directory: PathBuf,
+    /// The number of bytes of batches to hold in memory before spilling.
+    pub budget: usize,
+}
+
+impl Default for SpillConfig {
+    /// A budget of one gigabyte, in the system's temporary directory.
+    fn default() -> Self {
+        SpillConfig { directory: ::std::env::temp_dir(), budget: 1 << 30 }
+    }
+}
+
+impl SpillConfig {
+    /// Reads the configuration from the environment, using the default for each variable that is not set.
+    pub fn from_env() -> Self {
+        let default = SpillConfig::default();
+        let directory =
+        ::std::env::var("DIFFERENTIAL_SPILL_DIR")
+            .map(PathBuf::from)
+            .unwrap_or(default.directory);
+        let budget =
+        ::std::env::var("DIFFERENTIAL_SPILL_BUDGET")
+            .ok()
+            .and_then(|budget| budget.parse().ok())
+            .unwrap_or(default.budget);
+        SpillConfig { directory, budget }
+    }
+}
+
+/// The multiple of each inserted batch's length applied as work to the merge in progress.
+const EFFORT: usize = 4;
+
+/// A batch, either held in memory or written to a file.
+enum Stored<K, B> {
+    /// A batch in memory, and the number of bytes it occupies.
+    Resident(B, usize),
+    /// A batch written to the store.
+    Spilled(Spilled<K, B>),
+}
+
+/// The in-memory description of a batch written to the store.
+struct Spilled<K, B> {
+    /// The key of the batch in the store.
+    key: Rc<StoreKey<B>>,
+    /// The number of updates in the batch.
+    len: usize,
+    /// The least and greatest keys in the batch, if it has any updates.
+    keys: Option<(K, K)>,
+    /// A copy of the batch read back in by `warm`.
+    warm: Option<B>,
+}
+
+/// A merge of two adjacent batches in progress, which advances as batches are inserted.
+struct Merging<K, V, T, R, B: Batch<K, V, T, R>> {
+    /// The position in `batches` of the older of the two batches.
+    index: usize,
+    /// The two batches, read back in if spilled.
+    batch1: B,
+    batch2: B,
+    /// The frontier to which the merge advances times.
+    frontier: Option<Vec<T>>,
+    merger: <B as Batch<K, V, T, R>>::Merger,
+}
+
+/// The store of spilled batches, and the pages of them retained in memory once read by cursors.
+struct Pager<B> {
+    store: Box<BatchStore>,
+    /// Batches read by cursors, with the bytes each occupies and the number of its most recent read.
+    pages: HashMap<u64, (B, usize, usize)>,
+    /// The number of bytes of pages to retain.
+    budget: usize,
+    /// The number of reads of spilled batches, used to discard the least recently read pages.
+    reads: usize,
+    /// The first error met writing or reading batches, not yet collected.
+    error: Option<io::Error>,
+}
+
+impl<B: Spill+Clone> Pager<B> {
+    // Produces the batch stored under `key`, from its page if present, and otherwise from the store,
+    // retaining what is read as a page if it fits in the budget.
+    fn page(&mut self, key: u64) -> Option<B> {
+        self.reads += 1;
+        if let Some(page) = self.pages.get_mut(&key) {
+            page.2 = self.reads;
+            return Some(page.0.clone());
+        }
+        let batch = self.read(key)?;
+        let size = batch.size();
+        if size <= self.budget {
+            self.pages.insert(key, (batch.clone(), size, self.reads));
+            self.evict();
+        }
+        Some(batch)
+    }
+
+    // Produces the batch stored under `key`, from its page if present, and otherwise from the store,
+    // without retaining it.
+    fn read(&mut self, key: u64) -> Option<B> {
+        if let Some(page) = self.pages.get(&key) {
+            return Some(page.0.clone());
+        }
+        let read =
+        self.store
+            .get(key)
+            .and_then(|bytes| B::restore(bytes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "failed to decode spilled batch")));
+        match read {
+            Ok(batch) => Some(batch),
+            Err(error) => { self.fail(error); None },
+        }
+    }
+
+    // Records `error`, unless an earlier error has not yet been collected.
+    fn fail(&mut self, error: io::Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    // The number of bytes of pages retained.
+    fn paged(&self) -> usize {
+        self.pages.values().map(|page| page.1).sum()
+    }
+
+    // Discards the least recently read pages until the pages fit in the budget.
+    fn evict(&mut self) {
+        let mut paged = self.paged();
+        while paged > self.budget {
+            let oldest = self.pages.iter().min_by_key(|&(_, page)| page.2).map(|(&key, _)| key);
+            match oldest.and_then(|key| self.pages.remove(&key)) {
+                Some(page) => { paged -= page.1; },
+                None => break,
+            }
+        }
+    }
+}
+
+/// The key of a batch in the store, which removes the batch once neither the trace nor any cursor refers to it.
+struct StoreKey<B> {
+    key: u64,
+    pager: Rc<RefCell<Pager<B>>>,
+}
+
+impl<B: Spill+Clone> StoreKey<B> {
+    // Produces the batch, retaining it as a page if it fits in the page budget.
+    fn page(&self) -> Option<B> {
+        self.pager.borrow_mut().page(self.key)
+    }
+    // Produces the batch, without retaining it.
+    fn read(&self) -> Option<B> {
+        self.pager.borrow_mut().read(self.key)
+    }
+}
+
+impl<B> Drop for StoreKey<B> {
+    fn drop(&mut self) {
+        let mut pager = self.pager.borrow_mut();
+        pager.pages.remove(&self.key);
+        let _ = pager.store.remove(self.key);
+    }
+}
+
+/// A value set at most once, through a shared reference.
+struct Lazy<T> {
+    value: UnsafeCell<Option<T>>,
+}
+
+impl<T> Lazy<T> {
+    fn new(value: Option<T>) -> Self {
+        Lazy { value: UnsafeCell::new(value) }
+    }
+    fn get(&self) -> Option<&T> {
+        unsafe { (*self.value.get()).as_ref() }
+    }
+    // Sets the value if it is not yet set. A value once set is never changed or dropped before `self`,
+    // and so references produced by `get` remain valid.
+    fn set(&self, value: T) {
+        if self.get().is_none() {
+            unsafe { *self.value.get() = Some(value); }
+        }
+    }
+}
+
+/// A batch of a `SpillSpine` as seen by its cursors, which is read from the store only when first needed.
+pub struct SpillStorage<K, B> {
+    /// The batch, once read or if held in memory, or `None` if it could not be read.
+    batch: Lazy<Option<B>>,
+    /// The key and the range of keys of a spilled batch.
+    spilled: Option<(Rc<StoreKey<B>>, Option<(K, K)>)>,
+}
+
+impl<K, B: Spill+Clone> SpillStorage<K, B> {
+    // Storage for a batch held in memory.
+    fn resident(batch: B) -> Self {
+        SpillStorage { batch: Lazy::new(Some(Some(batch))), spilled: None }
+    }
+    // Storage for a spilled batch, which is not read until needed.
+    fn spilled(key: Rc<StoreKey<B>>, keys: Option<(K, K)>) -> Self {
+        SpillStorage { batch: Lazy::new(None), spilled: Some((key, keys)) }
+    }
+    // The batch, read from the store if it has not been, or `None` if it could not be read.
+    fn batch(&self) -> Option<&B> {
+        if self.batch.get().is_none() {
+            let batch = self.spilled.as_ref().and_then(|&(ref key, _)| key.page());
+            self.batch.set(batch);
+        }
+        self.batch.get().and_then(|batch| batch.as_ref())
+    }
+    // The batch, which has been read.
+    fn read(&self) -> &B {
+        match self.batch.get() {
+            Some(&Some(ref batch)) => batch,
+            _ => panic!("spilled batch has not been read"),
+        }
+    }
+    // The least and greatest keys of a spilled batch, unless it has no updates or could not be read.
+    fn keys(&self) -> Option<&(K, K)> {
+        match self.batch.get() {
+            Some(&None) => None,
+            _ => self.spilled.as_ref().and_then(|&(_, ref keys)| keys.as_ref()),
+        }
+    }
+}
+
+/// A cursor over a batch of a `SpillSpine`, which reads a spilled batch only once it visits keys the batch
+/// may contain.
+pub struct SpillCursor<K, V, T, R, B: BatchReader<K, V, T, R>> {
+    position: Position<B::Cursor>,
+    phantom: ::std::marker::PhantomData<(K, V, T, R)>,
+}
+
+/// The position of a `SpillCursor`.
+enum Position<C> {
+    /// At the first key of a batch that has not been read.
+    First,
+    /// Past the last key of a batch that has not been read.
+    Past,
+    /// In a batch that has been read, or is held in memory.
+    Read(C),
+}
+
+impl<K, V, T, R, B: BatchReader<K, V, T, R>+Spill+Clone> SpillCursor<K, V, T, R, B> {
+    fn new(storage: &SpillStorage<K, B>) -> Self {
+        let position = match storage.batch.get() {
+            Some(&Some(ref batch)) => Position::Read(batch.cursor()),
+            _ => Position::First,
+        };
+        SpillCursor { position, phantom: ::std::marker::PhantomData }
+    }
+    // Reads the batch, if the cursor is at the first key of a batch that has not been read.
+    fn start(&mut self, storage: &SpillStorage<K, B>) {
+        if let Position::First = self.position {
+            self.position = match storage.batch() {
+                Some(batch) => Position::Read(batch.cursor()),
+                None => Position::Past,
+            };
+        }
+    }
+}
+
+impl<K, V, T, R, B> Cursor<K, V, T, R> for SpillCursor<K, V, T, R, B>
+where
+    K: Ord,
+    B: BatchReader<K, V, T, R>+Spill+Clone,
+{
+    type Storage = SpillStorage<K, B>;
+
+    fn key_valid(&self, storage: &Self::Storage) -> bool {
+        match self.position {
+            Position::First => storage.keys().is_some(),
+            Position::Past => false,
+            Position::Read(ref cursor) => cursor.key_valid(storage.read()),
+        }
+    }
+    fn val_valid(&self, storage: &Self::Storage) -> bool {
+        match self.position {
+            Position::First => storage.batch().map(|batch| batch.cursor().val_valid(batch)).unwrap_or(false),
+            Position::Past => false,
+            Position::Read(ref cursor) => cursor.val_valid(storage.read()),
+        }
+    }
+
+    fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K {
+        match self.position {
+            Position::First => &storage.keys().expect("key of exhausted cursor").0,
+            Position::Past => panic!("key of exhausted cursor"),
+            Position::Read(ref cursor) => cursor.key(storage.read()),
+        }
+    }
+    fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V {
+        match self.position {
+            Position::First => {
+                let batch = storage.batch().expect("val of exhausted cursor");
+                batch.cursor().val(batch)
+            },
+            Position::Past => panic!("val of exhausted cursor"),
+            Position::Read(ref cursor) => cursor.val(storage.read()),
+        }
+    }
+
+    fn map_times<L: FnMut(&T, &R)>(&mut self, storage: &Self::Storage, logic: L) {
+        self.start(storage);
+        if let Position::Read(ref mut cursor) = self.position {
+            cursor.map_times(storage.read(), logic);
+        }
+    }
+
+    fn step_key(&mut self, storage: &Self::Storage) {
+        self.start(storage);
+        if let Position::Read(ref mut cursor) = self.position {
+            cursor.step_key(storage.read());
+        }
+    }
+    fn seek_key(&mut self, storage: &Self::Storage, key: &K) {
+        // Seeks within or past the range of keys of an unread batch need not read it.
+        if let Position::First = self.position {
+            match storage.keys() {
+                Some(&(ref least, ref greatest)) => {
+                    if key > greatest { self.position = Position::Past; }
+                    else if key > least { self.start(storage); }
+                },
+                None => { self.position = Position::Past; },
+            }
+        }
+        if let Position::Read(ref mut cursor) = self.position {
+            cursor.seek_key(storage.read(), key);
+        }
+    }
+
+    fn step_val(&mut self, storage: &Self::Storage) {
+        self.start(storage);
+        if let Position::Read(ref mut cursor) = self.position {
+            cursor.step_val(storage.read());
+        }
+    }
+    fn seek_val(&mut self, storage: &Self::Storage, val: &V) {
+        self.start(storage);
+        if let Position::Read(ref mut cursor) = self.position {
+            cursor.seek_val(storage.read(), val);
+        }
+    }
+
+    fn rewind_keys(&mut self, storage: &Self::Storage) {
+        match self.position {
+            Position::Read(ref mut cursor) => cursor.rewind_keys(storage.read()),
+            _ => self.position = Position::First,
+        }
+    }
+    fn rewind_vals(&mut self, storage: &Self::Storage) {
+        if let Position::Read(ref mut cursor) = self.position {
+            cursor.rewind_vals(storage.read());
+        }
+    }
+
+    fn may_contain(&self, storage: &Self::Storage, key: &K) -> bool {
+        match self.position {
+            Position::Read(ref cursor) if storage.spilled.is_none() => cursor.may_contain(storage.read(), key),
+            _ => storage.keys().map(|&(ref least, ref greatest)| least <= key && key <= greatest).unwrap_or(false),
+        }
+    }
+}
+
+/// An append-only collection of update tuples, some of which may be held on disk.
+///
+/// A merge begins when the two newest batches have similar sizes, and advances by work proportional
+/// to the length of each batch inserted, as in the `Spine`; a merge in progress is completed at once
+/// only when its result must be merged again, or by `compact`. The oldest batches held in memory are
+/// written to disk whenever the batches in memory exceed the configured budget. Merges involving
+/// spilled batches read them back in, and hold them in memory until complete, and the results are
+/// subject to the same budget.
+pub struct SpillSpine<K, V, T: Lattice+Ord, R: Monoid, B: Batch<K, V, T, R>> {
+    operator: OperatorInfo,
+    logger: Option<::logging::Logger>,
+    phantom: ::std::marker::PhantomData<(K, V, R)>,
+    advance_frontier: Vec<T>,            // Times after which the trace must accumulate correctly.
+    through_frontier: Vec<T>,            // Times after which the trace must be able to subset its inputs.
+    batches: Vec<Stored<K, B>>,          // Batches from oldest to newest.
+    merging: Option<Merging<K, V, T, R, B>>, // A merge of two adjacent batches in progress.
+    pending: Vec<B>,                     // Batches at times in advance of `frontier`.
+    upper: Vec<T>,
+    budget: usize,                       // Number of bytes of batches to hold in memory.
+    pager: Rc<RefCell<Pager<B>>>,        // Storage for spilled batches, and pages of those read.
+    spill_age: Option<usize>,            // Number of newest batches exempt from spilling regardless of budget.
+}
+
+impl<K, V, T, R, B> TraceReader<K, V, T, R> for SpillSpine<K, V, T, R, B>
+where
+    K: Ord+Clone,
+    V: Ord+Clone,
+    T: Lattice+Ord+Clone+Debug,
+    R: Monoid,
+    B: Batch<K, V, T, R>+Spill+Clone+'static,
+{
+    type Batch = B;
+    type Cursor = CursorList<K, V, T, R, SpillCursor<K, V, T, R, B>>;
+
+    fn cursor_through(&mut self, upper: &[T]) -> Option<(Self::Cursor, <Self::Cursor as Cursor<K, V, T, R>>::Storage)> {
+
+        // we shouldn't grab a cursor into a closed trace, right?
+        assert!(self.advance_frontier.len() > 0);
+
+        // Check that `upper` is greater or equal to `self.through_frontier`.
+        if upper.iter().all(|t1| self.through_frontier.iter().any(|t2| t2.less_equal(t1))) {
+
+            let mut cursors = Vec::new();
+            let mut storage = Vec::new();
+
+            // Spilled batches are read by their cursors only if needed.
+            for stored in self.batches.iter() {
+                let batch = match *stored {
+                    Stored::Resident(ref batch, _) => SpillStorage::resident(batch.clone()),
+                    Stored::Spilled(ref spilled) => match spilled.warm {
+                        Some(ref batch) => SpillStorage::resident(batch.clone()),
+                        None => SpillStorage::spilled(spilled.key.clone(), spilled.keys.clone()),
+                    },
+                };
+                cursors.push(SpillCursor::new(&batch));
+                storage.push(batch);
+            }
+
+            for batch in &self.pending {
+                let include_lower = upper.iter().all(|t1| batch.lower().iter().any(|t2| t2.less_equal(t1)));
+                let include_upper = upper.iter().all(|t1| batch.upper().iter().any(|t2| t2.less_equal(t1)));
+
+                if include_lower != include_upper && upper != batch.lower() {
+                    panic!("`cursor_through`: `upper` straddles batch");
+                }
+
+                // include pending batches
+                if include_upper {
+                    let batch = SpillStorage::resident(batch.clone());
+                    cursors.push(SpillCursor::new(&batch));
+                    storage.push(batch);
+                }
+            }
+            Some((CursorList::new(cursors, &storage), storage))
+        }
+        else {
+            None
+        }
+    }
+    fn advance_by(&mut self, frontier: &[T]) {
+        self.advance_frontier = frontier.to_vec();
+        if self.advance_frontier.len() == 0 {
+            self.log_dropped();
+            self.pending.clear();
+            self.merging = None;
+            self.batches.clear();
+        }
+    }
+    fn advance_frontier(&mut self) -> &[T] { &self.advance_frontier[..] }
+    fn distinguish_since(&mut self, frontier: &[T]) {
+        self.through_frontier = frontier.to_vec();
+        self.consider_merges();
+    }
+    fn distinguish_frontier(&mut self) -> &[T] { &self.through_frontier[..] }
+
+    fn map_batches<F: FnMut(&Self::Batch)>(&mut self, mut f: F) {
+        for stored in self.batches.iter() {
+            match *stored {
+                Stored::Resident(ref batch, _) => f(batch),
+                Stored::Spilled(ref spilled) => {
+                    // Batches that cannot be read are skipped, and the error retained.
+                    if let Some(batch) = spilled.warm.clone().or_else(|| spilled.key.page()) {
+                        f(&batch);
+                    }
+                },
+            }
+        }
+        for batch in self.pending.iter() {
+            f(batch);
+        }
+    }
+
+    // Merges all batches into one batch, which is spilled if it exceeds the budget.
+    fn compact(&mut self) {
+        self.complete_merge();
+        while self.batches.len() > 1 && self.merge_last() { }
+        self.enforce_budget();
+    }
+
+    // Reads back in spilled batches which may contain keys in the range.
+    fn warm(&mut self, keys: Option<(&K, &K)>) {
+        for stored in self.batches.iter_mut() {
+            if let Stored::Spilled(ref mut spilled) = *stored {
+                let overlaps = match (keys, spilled.keys.as_ref()) {
+                    (Some((lower, upper)), Some(&(ref least, ref greatest))) => lower <= greatest && least <= upper,
+                    (None, Some(_)) => true,
+                    (_, None) => false,
+                };
+                if overlaps && spilled.warm.is_none() {
+                    spilled.warm = spilled.key.read();
+                }
+            }
+        }
+    }
+
+    fn cool(&mut self) {
+        for stored in self.batches.iter_mut() {
+            if let Stored::Spilled(ref mut spilled) = *stored {
+                spilled.warm = None;
+            }
+        }
+    }
+}
+
+impl<K, V, T, R, B> Trace<K, V, T, R> for SpillSpine<K, V, T, R, B>
+where
+    K: Ord+Clone,
+    V: Ord+Clone,
+    T: Lattice+Ord+Clone+Debug,
+    R: Monoid,
+    B: Batch<K, V, T, R>+Spill+Clone+'static,
+{
+    fn new(info: OperatorInfo, logging: Option<::logging::Logger>) -> Self {
+        Self::with_config(SpillConfig::default(), info, logging)
+    }
+
+    fn insert(&mut self, batch: Self::Batch) {
+
+        self.logger.as_ref().map(|l| l.log(::logging::BatchEvent {
+            operator: self.operator.global_id,
+            length: batch.len()
+        }));
+
+        if batch.lower() != batch.upper() {
+            assert_eq!(batch.lower(), &self.upper[..]);
+            self.upper = batch.upper().to_vec();
+            self.pending.push(batch);
+            self.consider_merges();
+        }
+        else {
+            // degenerate batches had best be empty.
+            assert!(batch.len() == 0);
+        }
+    }
+
+    fn close(&mut self) {
+        if self.upper != Vec::new() {
+            let builder = B::Builder::new();
+            let batch = builder.done(&self.upper[..], &[], &self.upper[..]);
+            self.insert(batch);
+        }
+    }
+}
+
+impl<K, V, T, R, B> SpillSpine<K, V, T, R, B>
+where
+    K: Ord+Clone,
+    V: Ord+Clone,
+    T: Lattice+Ord+Clone+Debug,
+    R: Monoid,
+    B: Batch<K, V, T, R>+Spill+Clone+'static,
+{
+    /// Allocates a `SpillSpine` which writes batches to disk as described by `config`.
+    pub fn with_config(config: SpillConfig, operator: OperatorInfo, logger: Option<::logging::Logger>) -> Self {
+        Self::with_store(config.budget, Box::new(FileStore::new(config.directory)), operator, logger)
+    }
+
+    /// Allocates a `SpillSpine` which writes batches to `store` once those in memory exceed `budget` bytes.
+    ///
+    /// The spine retains only a description of each stored batch, and the store should not be shared with
+    /// other spines unless it assigns distinct keys to each.
+    pub fn with_store(budget: usize, store: Box<BatchStore>, operator: OperatorInfo, logger: Option<::logging::Logger>) -> Self {
+        ::logging::log_layout::<K, V, T, R>(&logger, operator.global_id);
+        SpillSpine {
+            operator,
+            logger,
+            phantom: ::std::marker::PhantomData,
+            advance_frontier: vec![<T as Lattice>::minimum()],
+            through_frontier: vec![<T as Lattice>::minimum()],
+            batches: Vec::new(),
+            merging: None,
+            pending: Vec::new(),
+            upper: vec![<T as Lattice>::minimum()],
+            budget,
+            pager: Rc::new(RefCell::new(Pager {
+                store,
+                pages: HashMap::new(),
+                budget: 1 << 28,
+                reads: 0,
+                error: None,
+            })),
+            spill_age: None,
+        }
+    }
+
+    /// Spills all but the newest `age` batches, whether or not the batches in memory exceed the budget.
+    ///
+    /// Old batches are merged into larger batches, and are read rarely, when their keys are needed by
+    /// cursors. With a store that compresses batches, this holds historical batches compressed while
+    /// recent batches remain in memory, and cursors decompress the historical batches as they need them.
+    /// Passing `None` spills batches only to keep within the budget.
+    pub fn set_spill_age(&mut self, age: Option<usize>) {
+        self.spill_age = age;
+        self.enforce_budget();
+    }
+
+    /// Sets the number of bytes of spilled batches to retain in memory once read by cursors.
+    ///
+    /// Pages beyond the new budget are discarded, least recently read first.
+    pub fn set_page_budget(&mut self, budget: usize) {
+        let mut pager = self.pager.borrow_mut();
+        pager.budget = budget;
+        pager.evict();
+    }
+
+    /// The number of bytes of spilled batches retained in memory as pages.
+    pub fn paged(&self) -> usize {
+        self.pager.borrow().paged()
+    }
+
+    /// Takes the first error met writing or reading spilled batches since the last call, if any.
+    ///
+    /// Neither cursors nor the `Trace` methods can report errors, and the trace instead continues without
+    /// the batches concerned: a batch that cannot be written remains in memory, a merge whose inputs cannot
+    /// be read is deferred, and a cursor presents a batch it cannot read as empty. The contents presented
+    /// by a cursor are incomplete if an error is reported after its use.
+    pub fn error(&mut self) -> Option<io::Error> {
+        self.pager.borrow_mut().error.take()
+    }
+
+    /// The number of bytes of batches held in memory.
+    pub fn resident(&self) -> usize {
+        self.batches.iter().map(|stored| match *stored {
+            Stored::Resident(_, size) => size,
+            Stored::Spilled(..) => 0,
+        }).sum()
+    }
+
+    /// The number of batches written to disk.
+    pub fn spilled(&self) -> usize {
+        self.batches.iter().filter(|stored| match **stored { Stored::Spilled(..) => true, _ => false }).count()
+    }
+
+    // Migrate data from `self.pending` into `self.batches`, merge similarly sized batches, and spill.
+    fn consider_merges(&mut self) {
+
+        while self.pending.len() > 0 &&
+              self.through_frontier.iter().all(|t1| self.pending[0].upper().iter().any(|t2| t2.less_equal(t1)))
+        {
+            let batch = self.pending.remove(0);
+            let mut fuel = EFFORT.saturating_mul(::std::cmp::max(batch.len(), 1));
+            let size = batch.size();
+            self.batches.push(Stored::Resident(batch, size));
+
+            // Merge the newest batches while the older of the two is not much larger, completing the merge
+            // in progress first if it involves them, and stop once the fuel of the new batch is spent.
+            loop {
+                if self.mergeable() {
+                    self.complete_merge();
+                    if !self.begin_merge() { break; }
+                }
+                if !self.work_merge(&mut fuel) { break; }
+            }
+        }
+
+        self.enforce_budget();
+    }
+
+    // Indicates if the two newest batches should be merged, as the older is not much larger.
+    fn mergeable(&self) -> bool {
+        let len = self.batches.len();
+        len > 1 && Self::length(&self.batches[len-2]) <= 2 * Self::length(&self.batches[len-1])
+    }
+
+    // The number of updates in a stored batch.
+    fn length(stored: &Stored<K, B>) -> usize {
+        match *stored {
+            Stored::Resident(ref batch, _) => batch.len(),
+            Stored::Spilled(ref spilled) => spilled.len,
+        }
+    }
+
+    // Merges the two newest batches to completion, reading them back in if spilled, and indicates if
+    // the merge was performed. Batches that cannot be read are left unmerged, and the error retained.
+    fn merge_last(&mut self) -> bool {
+        self.complete_merge();
+        let began = self.begin_merge();
+        self.complete_merge();
+        began
+    }
+
+    // Begins a merge of the two newest batches, reading them back in if spilled, and indicates if the
+    // merge began. There must be no merge in progress. Batches that cannot be read are left unmerged,
+    // and the error retained.
+    fn begin_merge(&mut self) -> bool {
+
+        let index = self.batches.len() - 2;
+        let (batch1, batch2) = match (load(&self.batches[index]), load(&self.batches[index+1])) {
+            (Some(batch1), Some(batch2)) => (batch1, batch2),
+            _ => return false,
+        };
+
+        self.logger.as_ref().map(|l|
+            l.log(::logging::MergeEvent {
+                operator: self.operator.global_id,
+                scale: index,
+                length1: batch1.len(),
+                length2: batch2.len(),
+                complete: None,
+            })
+        );
+
+        let merger = <B as Batch<K, V, T, R>>::begin_merge(&batch1, &batch2);
+        let frontier = Some(self.advance_frontier.clone());
+        self.merging = Some(Merging { index, batch1, batch2, frontier, merger });
+        true
+    }
+
+    // Performs up to `fuel` work on the merge in progress, if any, and indicates if it completed.
+    fn work_merge(&mut self, fuel: &mut usize) -> bool {
+
+        let complete = match self.merging {
+            Some(Merging { ref batch1, ref batch2, ref frontier, ref mut merger, .. }) => {
+                merger.work(batch1, batch2, frontier, fuel);
+                *fuel > 0 && !merger.pending()
+            },
+            None => false,
+        };
+
+        if complete {
+            if let Some(merging) = self.merging.take() {
+                let merged = merging.merger.done();
+
+                self.logger.as_ref().map(|l|
+                    l.log(::logging::MergeEvent {
+                        operator: self.operator.global_id,
+                        scale: merging.index,
+                        length1: merging.batch1.len(),
+                        length2: merging.batch2.len(),
+                        complete: Some(merged.len()),
+                    })
+                );
+
+                // Spilled inputs are removed from the store once no cursor refers to them.
+                let size = merged.size();
+                self.batches.remove(merging.index + 1);
+                self.batches[merging.index] = Stored::Resident(merged, size);
+            }
+        }
+        complete
+    }
+
+    // Completes the merge in progress, if any.
+    fn complete_merge(&mut self) {
+        let mut fuel = usize::max_value();
+        self.work_merge(&mut fuel);
+    }
+
+    // Writes the oldest resident batches to the store until the resident batches fit in the budget,
+    // and all batches older than the spill age are written. Writing stops at the first error, which
+    // is retained.
+    fn enforce_budget(&mut self) {
+        let mut resident = self.resident();
+        let aged = self.spill_age.map(|age| self.batches.len().saturating_sub(age)).unwrap_or(0);
+        let mut index = 0;
+        while (resident > self.budget || index < aged) && index < self.batches.len() {
+            let spilled = match self.batches[index] {
+                Stored::Resident(ref batch, size) => {
+                    let mut bytes = Vec::with_capacity(size);
+                    let written = batch.spill(&mut bytes).and_then(|()| self.pager.borrow_mut().store.put(&bytes[..]));
+                    let key = match written {
+                        Ok(key) => StoreKey { key, pager: self.pager.clone() },
+                        Err(error) => {
+                            self.pager.borrow_mut().fail(error);
+                            break;
+                        },
+                    };
+                    resident -= size;
+
+                    // Record the range of keys, so that `warm` can skip batches outside a range.
+                    let mut cursor = batch.cursor();
+                    let mut keys = None;
+                    if cursor.key_valid(batch) {
+                        let least = cursor.key(batch).clone();
+                        let mut greatest = least.clone();
+                        while cursor.key_valid(batch) {
+                            greatest = cursor.key(batch).clone();
+                            cursor.step_key(batch);
+                        }
+                        keys = Some((least, greatest));
+                    }
+
+                    Some(Stored::Spilled(Spilled {
+                        key: Rc::new(key),
+                        len: batch.len(),
+                        keys,
+                        warm: None,
+                    }))
+                },
+                Stored::Spilled(..) => None,
+            };
+            if let Some(spilled) = spilled {
+                self.batches[index] = spilled;
+            }
+            index += 1;
+        }
+    }
+}
+
+impl<K, V, T: Lattice+Ord, R: Monoid, B: Batch<K, V, T, R>> SpillSpine<K, V, T, R, B> {
+    // Reports the release of all batches the spine holds, resident, spilled, and pending.
+    fn log_dropped(&self) {
+        if let Some(logger) = self.logger.as_ref() {
+            let operator = self.operator.global_id;
+            for stored in self.batches.iter() {
+                let length = match *stored {
+                    Stored::Resident(ref batch, _) => batch.len(),
+                    Stored::Spilled(ref spilled) => spilled.len,
+                };
+                logger.log(::logging::DropEvent { operator, length });
+            }
+            for batch in self.pending.iter() {
+                logger.log(::logging::DropEvent { operator, length: batch.len() });
+            }
+        }
+    }
+}
+
+impl<K, V, T: Lattice+Ord, R: Monoid, B: Batch<K, V, T, R>> Drop for SpillSpine<K, V, T, R, B> {
+    fn drop(&mut self) {
+        self.log_dropped();
+    }
+}
+
+/// Produces the stored batch, reading it from the store if spilled and neither warm nor paged in.
+fn load<K, B: Spill+Clone>(stored: &Stored<K, B>) -> Option<B> {
+    match *stored {
+        Stored::Resident(ref batch, _) => Some(batch.clone()),
+        Stored::Spilled(ref spilled) => {
+            match spilled.warm {
+                Some(ref batch) => Some(batch.clone()),
+                None => spilled.key.read(),
+            }
+        },
+    }
+}
@@ -3,6 +3,29 @@
 //! The `Spine` is a general-purpose trace implementation based on collection and merging
 //! immutable batches of updates. It is generic with respect to the batch type, and can be
 //! instantiated for any implementor of `trace::Batch`.
+//!
+//! How eagerly the spine merges its batches is described by a `MergePolicy`. Deferring merges
+//! reduces the latency of inserting batches, at the expense of memory and of reading more batches
+//! with each cursor; merging aggressively does the opposite. Spines created by `Trace::new`, as by
+//! the `arrange` operators, use `MergePolicy::default()`, and otherwise a policy may be set explicitly
+//! with `Spine::with_policy`. `MergePolicy::from_env` reads a policy from the environment variables
+//! `DIFFERENTIAL_MERGE_EFFORT`, `DIFFERENTIAL_MERGE_FANOUT`, `DIFFERENTIAL_MERGE_IDLE_EFFORT`, and
+//! `DIFFERENTIAL_MERGE_MAX_BATCHES`, for programs that choose to be configured by them.
+//!
+//! A spine may also be given a `MemoryBudget`, a bound on the estimated size of its updates. When an
+//! inserted batch takes the spine over its budget, the spine completes its merges and compacts its
+//! batches, and may be configured to report the frontier to which its readers would need to advance for
+//! it to discard its history. The spine never advances its times beyond the frontier its readers allow
+//! through `advance_by`. Spines created by `Trace::new` have no budget; one may be set with
+//! `Spine::set_memory_budget`, and `MemoryBudget::from_env` reads it from `DIFFERENTIAL_MEMORY_BUDGET`,
+//! in bytes, and the response from `DIFFERENTIAL_MEMORY_EVICTION`, either `compact` (the default) or `report`.
+//!
+//! A spine may be given a retention window with `Spine::set_retention`, which maps the upper frontier
+//! of the trace to a retention frontier. Updates at times not greater or equal to the retention frontier
+//! are removed from the batches of the trace, rather than only compacted, and the accumulations the trace
+//! reports no longer include them. Updates are removed progressively, with work proportional to the sizes
+//! of inserted batches, and the frontier before which all updates have been removed is reported by
+//! `Spine::retention_frontier`.
 
 use std::fmt::Debug;
 
@@ -11,7 +34,7 @@ use lattice::Lattice;
 use trace::{Batch, BatchReader, Trace, TraceReader};
 // use trace::cursor::cursor_list::CursorList;
 use trace::cursor::{Cursor, CursorList};
-use trace::Merger;
+use trace::{Builder, Merger};
 
 use ::timely::dataflow::operators::generic::OperatorInfo;
 
@@ -52,6 +75,12 @@ impl<K, V, T: Eq, R, B: Batch<K, V, T, R>> MergeState<K, V, T, R, B> {
             _ => false,
         }
     }
+    fn is_pending(&self) -> bool {
+        match *self {
+            MergeState::Merging(_, _, _, ref in_progress) => in_progress.pending(),
+            MergeState::Complete(_) => false,
+        }
+    }
     fn begin_merge(batch1: B, batch2: B, frontier: Option<Vec<T>>) -> Self {
         assert!(batch1.upper() == batch2.lower());
         let begin_merge = <B as Batch<K, V, T, R>>::begin_merge(&batch1, &batch2);
@@ -61,7 +90,7 @@ impl<K, V, T: Eq, R, B: Batch<K, V, T, R>> MergeState<K, V, T, R, B> {
         if let MergeState::Merging(ref source1, ref source2, ref frontier, ref mut in_progress) = self {
             in_progress.work(source1, source2, frontier, fuel);
         }
-        if *fuel > 0 {
+        if *fuel > 0 && !self.is_pending() {
             match self {
                 // ALLOC: Here is where we may de-allocate batches.
                 MergeState::Merging(b1, b2, _, finished) => {
@@ -88,6 +117,104 @@ impl<K, V, T: Eq, R, B: Batch<K, V, T, R>> MergeState<K, V, T, R, B> {
             MergeState::Complete(ref batch) => batch.len(),
         }
     }
+    fn lower(&self) -> &[T] {
+        match *self {
+            MergeState::Merging(ref batch1, _, _, _) => batch1.lower(),
+            MergeState::Complete(ref batch) => batch.lower(),
+        }
+    }
+    fn upper(&self) -> &[T] {
+        match *self {
+            MergeState::Merging(_, ref batch2, _, _) => batch2.upper(),
+            MergeState::Complete(ref batch) => batch.upper(),
+        }
+    }
+}
+
+/// A description of how eagerly a `Spine` merges its batches.
+#[derive(Clone, Debug)]
+pub struct MergePolicy {
+    /// The multiple of each inserted batch's length applied as effort to each merge in progress.
+    ///
+    /// Larger values complete merges sooner after they begin; the value is at least one.
+    pub effort: usize,
+    /// The ratio of the capacities of successive slots for batches, at least two.
+    ///
+    /// Batches are held in slots by their length, and two batches in the same slot are merged. Larger
+    /// fanouts merge less often, and leave more batches in the spine.
+    pub fanout: usize,
+    /// The effort applied to each merge in progress whenever the spine is prompted without new batches.
+    ///
+    /// This allows merges to advance while the input is quiet, rather than only as batches arrive.
+    pub idle_effort: usize,
+    /// The number of batches beyond which the spine completes merges in progress immediately.
+    pub max_batches: Option<usize>,
+}
+
+impl Default for MergePolicy {
+    /// An effort of four, a fanout of two, no idle effort, and no bound on the number of batches.
+    fn default() -> Self {
+        MergePolicy {
+            effort: 4,
+            fanout: 2,
+            idle_effort: 0,
+            max_batches: None,
+        }
+    }
+}
+
+impl MergePolicy {
+    /// Reads the policy from the environment, using the default for each variable that is not set.
+    pub fn from_env() -> Self {
+        fn read(name: &str) -> Option<usize> {
+            ::std::env::var(name).ok().and_then(|value| value.parse().ok())
+        }
+        let default = MergePolicy::default();
+        MergePolicy {
+            effort: read("DIFFERENTIAL_MERGE_EFFORT").unwrap_or(default.effort),
+            fanout: read("DIFFERENTIAL_MERGE_FANOUT").unwrap_or(default.fanout),
+            idle_effort: read("DIFFERENTIAL_MERGE_IDLE_EFFORT").unwrap_or(default.idle_effort),
+            max_batches: read("DIFFERENTIAL_MERGE_MAX_BATCHES").or(default.max_batches),
+        }
+    }
+}
+
+/// How a `Spine` responds to exceeding its `MemoryBudget`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// Completes merges in progress and merges all batches, consolidating updates at the advance frontier.
+    Compact,
+    /// Compacts, and reports the upper frontier of the trace as its eviction frontier.
+    ///
+    /// This is advisory: the trace forgets nothing itself. The eviction frontier, reported by
+    /// `Spine::eviction_frontier`, is the frontier to which readers would need to advance the trace for
+    /// it to discard its history. The trace does not advance its times past the frontier its readers
+    /// allow, and readers able to tolerate the loss of history may advance the trace to the eviction
+    /// frontier with `advance_by`, after which compaction discards the history.
+    Report,
+}
+
+/// A bound on the memory used by the updates of a `Spine`.
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    /// The number of bytes of updates the spine may hold before it acts.
+    ///
+    /// Updates are estimated to occupy the size of a `(K, V, T, R)` tuple each, as in `TraceReader::statistics`.
+    pub bytes: usize,
+    /// The response of the spine to exceeding the budget.
+    pub eviction: Eviction,
+}
+
+impl MemoryBudget {
+    /// Reads a budget from `DIFFERENTIAL_MEMORY_BUDGET` and `DIFFERENTIAL_MEMORY_EVICTION`, if the former is set.
+    pub fn from_env() -> Option<Self> {
+        let bytes = ::std::env::var("DIFFERENTIAL_MEMORY_BUDGET").ok().and_then(|value| value.parse().ok())?;
+        let eviction = match ::std::env::var("DIFFERENTIAL_MEMORY_EVICTION") {
+            Ok(ref value) if value == "report" => Eviction::Report,
+            _ => Eviction::Compact,
+        };
+        Some(MemoryBudget { bytes, eviction })
+    }
 }
 
 /// An append-only collection of update tuples.
@@ -104,7 +231,35 @@ pub struct Spine<K, V, T: Lattice+Ord, R: Monoid, B: Batch<K, V, T, R>> {
     merging: Vec<Option<MergeState<K,V,T,R,B>>>, // Several possibly shared collections of updates.
     pending: Vec<B>,                     // Batches at times in advance of `frontier`.
     upper: Vec<T>,
-    effort: usize,
+    policy: MergePolicy,
+    budget: Option<MemoryBudget>,
+    evicted: usize,                      // Updates remaining after the most recent eviction.
+    eviction_frontier: Option<Vec<T>>,   // Upper frontier at an eviction that readers have not yet allowed.
+    retention: Option<Box<Fn(&T)->T>>,   // Maps elements of `upper` to the least retained times.
+    retention_frontier: Vec<T>,          // Times before which updates have been removed.
+    retaining: Option<(Vec<T>, Vec<T>)>, // The frontier of a retention pass, and the upper frontier of batches it has rebuilt.
+}
+
+impl<K, V, T: Lattice+Ord, R: Monoid, B: Batch<K, V, T, R>> Spine<K, V, T, R, B> {
+    // Reports the release of all batches the spine holds, so that logs account for all of its updates.
+    fn log_dropped(&self) {
+        if let Some(logger) = self.logger.as_ref() {
+            let operator = self.operator.global_id;
+            for batch in self.merging.iter().filter_map(|batch| batch.as_ref()) {
+                logger.log(::logging::DropEvent { operator, length: batch.len() });
+            }
+            for batch in self.pending.iter() {
+                logger.log(::logging::DropEvent { operator, length: batch.len() });
+            }
+        }
+    }
+}
+
+// Reports the batches the spine releases, so that logs account for all of its updates.
+impl<K, V, T: Lattice+Ord, R: Monoid, B: Batch<K, V, T, R>> Drop for Spine<K, V, T, R, B> {
+    fn drop(&mut self) {
+        self.log_dropped();
+    }
 }
 
 impl<K, V, T, R, B> TraceReader<K, V, T, R> for Spine<K, V, T, R, B>
@@ -170,13 +325,24 @@ where
     fn advance_by(&mut self, frontier: &[T]) {
         self.advance_frontier = frontier.to_vec();
         if self.advance_frontier.len() == 0 {
+            self.log_dropped();
             self.pending.clear();
             self.merging.clear();
         }
+        // Readers have allowed the eviction once they advance to its frontier.
+        let allowed = self.eviction_frontier.as_ref().map(|evicted| {
+            evicted.iter().all(|t1| frontier.iter().any(|t2| t2.less_equal(t1)))
+        });
+        if allowed == Some(true) {
+            self.eviction_frontier = None;
+        }
     }
     fn advance_frontier(&mut self) -> &[T] { &self.advance_frontier[..] }
     fn distinguish_since(&mut self, frontier: &[T]) {
         self.through_frontier = frontier.to_vec();
+        if self.pending.is_empty() {
+            self.idle_merges();
+        }
         self.consider_merges();
     }
     fn distinguish_frontier(&mut self) -> &[T] { &self.through_frontier[..] }
@@ -193,6 +359,33 @@ where
             f(batch);
         }
     }
+
+    // Completes all merges in progress, and merges all batches that may be merged into one batch.
+    fn compact(&mut self) {
+
+        // Batches in `self.merging` are ordered from newest to oldest, and so we merge each
+        // batch with the accumulation of the newer batches that precede it.
+        let mut merged: Option<B> = None;
+        for position in 0 .. self.merging.len() {
+            if let Some(batch) = self.merging[position].take() {
+                let batch = batch.complete(&mut self.logger, self.operator.global_id, position);
+                merged = Some(match merged {
+                    Some(newer) => {
+                        MergeState::begin_merge(batch, newer, Some(self.advance_frontier.clone()))
+                            .complete(&mut self.logger, self.operator.global_id, position)
+                    },
+                    None => batch,
+                });
+            }
+        }
+
+        self.merging.clear();
+        if let Some(batch) = merged {
+            let batch_index = self.slot(batch.len());
+            while self.merging.len() <= batch_index { self.merging.push(None); }
+            self.merging[batch_index] = Some(MergeState::Complete(batch));
+        }
+    }
 }
 
 // A trace implementation for any key type that can be borrowed from or converted into `Key`.
@@ -207,7 +400,7 @@ where
 {
 
     fn new(info: ::timely::dataflow::operators::generic::OperatorInfo, logging: Option<::logging::Logger>) -> Self {
-        Self::with_effort(4, info, logging)
+        Self::with_policy(MergePolicy::default(), info, logging)
     }
 
     // Ideally, this method acts as insertion of `batch`, even if we are not yet able to begin
@@ -224,8 +417,11 @@ where
         if batch.lower() != batch.upper() {
             assert_eq!(batch.lower(), &self.upper[..]);
             self.upper = batch.upper().to_vec();
+            let fuel = (2 * self.capacity(self.slot(batch.len()))).saturating_mul(self.policy.effort);
             self.pending.push(batch);
             self.consider_merges();
+            self.enforce_retention(fuel);
+            self.enforce_budget();
         }
         else {
             // degenerate batches had best be empty.
@@ -256,10 +452,19 @@ where
     /// This trace will merge batches progressively, with each inserted batch applying a multiple
     /// of the batch's length in effort to each merge. The `effort` parameter is that multiplier.
     /// This value should be at least one for the merging to happen; a value of zero is not helpful.
-    pub fn with_effort(mut effort: usize, operator: OperatorInfo, logger: Option<::logging::Logger>) -> Self {
+    pub fn with_effort(effort: usize, operator: OperatorInfo, logger: Option<::logging::Logger>) -> Self {
+        let policy = MergePolicy { effort, fanout: 2, idle_effort: 0, max_batches: None };
+        Self::with_policy(policy, operator, logger)
+    }
+
+    /// Allocates a fueled `Spine` which merges batches as described by `policy`.
+    pub fn with_policy(mut policy: MergePolicy, operator: OperatorInfo, logger: Option<::logging::Logger>) -> Self {
 
         // Zero effort is .. not smart.
-        if effort == 0 { effort = 1; }
+        if policy.effort == 0 { policy.effort = 1; }
+        if policy.fanout < 2 { policy.fanout = 2; }
+
+        ::logging::log_layout::<K, V, T, R>(&logger, operator.global_id);
 
         Spine {
             operator,
@@ -270,7 +475,119 @@ where
             merging: Vec::new(),
             pending: Vec::new(),
             upper: vec![<T as Lattice>::minimum()],
-            effort,
+            policy,
+            budget: None,
+            evicted: 0,
+            eviction_frontier: None,
+            retention: None,
+            retention_frontier: vec![<T as Lattice>::minimum()],
+            retaining: None,
+        }
+    }
+
+    /// The policy by which the spine merges batches.
+    pub fn policy(&self) -> &MergePolicy {
+        &self.policy
+    }
+
+    /// Sets the memory budget of the spine, or removes it with `None`.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.budget = budget;
+        self.evicted = 0;
+    }
+
+    /// The memory budget of the spine, if any.
+    pub fn memory_budget(&self) -> Option<&MemoryBudget> {
+        self.budget.as_ref()
+    }
+
+    /// The frontier to which readers would need to advance the trace for it to discard its history, if the
+    /// trace has exceeded a budget with `Eviction::Report` and its readers have not yet advanced that far.
+    pub fn eviction_frontier(&self) -> Option<&[T]> {
+        self.eviction_frontier.as_ref().map(|frontier| &frontier[..])
+    }
+
+    /// Sets a retention window, mapping each element of the upper frontier of the trace to the least time retained.
+    ///
+    /// As the upper frontier advances, updates at times not greater or equal to the mapped times are removed
+    /// from the trace. For example, with `usize` timestamps `|t| t.saturating_sub(100)` retains updates from
+    /// the most recent one hundred times. Updates are removed progressively as batches are inserted, and
+    /// may remain in the trace until `retention_frontier` has advanced past them.
+    pub fn set_retention<F: Fn(&T)->T+'static>(&mut self, window: F) {
+        self.retention = Some(Box::new(window));
+    }
+
+    /// Removes the retention window, if any. Updates already removed are not restored.
+    pub fn clear_retention(&mut self) {
+        self.retention = None;
+        self.retaining = None;
+    }
+
+    /// The frontier before which updates have been removed by the retention window.
+    pub fn retention_frontier(&self) -> &[T] {
+        &self.retention_frontier[..]
+    }
+
+    // The number of updates held by the spine.
+    fn updates(&self) -> usize {
+        self.pending.iter().map(|batch| batch.len()).sum::<usize>() +
+        self.merging.iter().map(|x| x.as_ref().map(|x| x.len()).unwrap_or(0)).sum::<usize>()
+    }
+
+    // The least slot whose capacity is at least `len`.
+    fn slot(&self, len: usize) -> usize {
+        let mut index = 0;
+        let mut capacity = 1usize;
+        while capacity < len {
+            capacity = capacity.saturating_mul(self.policy.fanout);
+            index += 1;
+        }
+        index
+    }
+
+    // The capacity of the slot `index`.
+    fn capacity(&self, index: usize) -> usize {
+        let mut capacity = 1usize;
+        for _ in 0 .. index {
+            capacity = capacity.saturating_mul(self.policy.fanout);
+        }
+        capacity
+    }
+
+    // Applies the idle effort to each merge in progress.
+    fn idle_merges(&mut self) {
+        if self.policy.idle_effort > 0 {
+            for position in 0 .. self.merging.len() {
+                if let Some(batch) = self.merging[position].take() {
+                    let mut fuel = self.policy.idle_effort;
+                    self.merging[position] = Some(batch.work(&mut fuel, &mut self.logger, self.operator.global_id, position));
+                }
+            }
+        }
+    }
+
+    // Completes merges in progress, smallest first, while the spine holds more batches than permitted.
+    fn bound_batches(&mut self) {
+        if let Some(max_batches) = self.policy.max_batches {
+            let mut batches =
+            self.pending.len() +
+            self.merging.iter().map(|x| match *x {
+                Some(MergeState::Merging(..)) => 2,
+                Some(MergeState::Complete(..)) => 1,
+                None => 0,
+            }).sum::<usize>();
+
+            let mut position = 0;
+            while batches > max_batches && position < self.merging.len() {
+                if self.merging[position].as_ref().map(|x| !x.is_complete()).unwrap_or(false) {
+                    if let Some(batch) = self.merging[position].take() {
+                        let batch = batch.complete(&mut self.logger, self.operator.global_id, position);
+                        self.merging[position] = Some(MergeState::Complete(batch));
+                        batches -= 1;
+                    }
+                }
+                position += 1;
+            }
         }
     }
 
@@ -326,8 +643,8 @@ where
             let batch = self.pending.remove(0);
 
             // Step 0: Determine batch size and target slot.
-            let batch_size = batch.len().next_power_of_two();
-            let batch_index = batch_size.trailing_zeros() as usize;
+            let batch_index = self.slot(batch.len());
+            let batch_size = self.capacity(batch_index);
             while self.merging.len() <= batch_index { self.merging.push(None); }
 
             if self.merging.len() > 32 { eprintln!("large progressive merge; len: {:?}", self.merging.len()); }
@@ -381,7 +698,7 @@ where
             for position in (batch_index .. self.merging.len()).rev() {
 
                 // We add fuel for any merge that may lead to this location.
-                fuel += (2 * batch_size).saturating_mul(self.effort);
+                fuel += (2 * batch_size).saturating_mul(self.policy.effort);
 
                 // We now move to the right, merging until we stop merging or run out of fuel.
                 let mut new_position = position;
@@ -392,7 +709,7 @@ where
                         batch = batch.work(&mut fuel, &mut self.logger, self.operator.global_id, position);
 
                         // If we have a complete batch, and it wants to be in the next slot ...
-                        if batch.is_complete() && batch.len() >= (1 << new_position) {//.next_power_of_two().trailing_zeros() as usize > new_position {
+                        if batch.is_complete() && batch.len() >= self.capacity(new_position) {
 
                             new_position += 1;
                             if self.merging.len() <= new_position { self.merging.push(None); }
@@ -430,7 +747,10 @@ where
                             }
                         }
                         else {
+                            // A merge in progress elsewhere leaves the fuel to the merges that follow.
+                            let pending = batch.is_pending();
                             self.merging[new_position] = Some(batch);
+                            if pending { break; }
                         }
                     }
                     else {
@@ -441,7 +761,8 @@ where
 
             // Step 4: Consider migrating complete batches to lower bins, if appropriate.
             for index in (1 .. self.merging.len()).rev() {
-                if self.merging[index].as_ref().map(|x| x.is_complete() && x.len() < (1 << (index-1))).unwrap_or(false) {
+                let capacity = self.capacity(index-1);
+                if self.merging[index].as_ref().map(|x| x.is_complete() && x.len() < capacity).unwrap_or(false) {
                     if self.merging[index-1].is_none() {
                         self.merging[index-1] = self.merging[index].take();
                     }
@@ -449,5 +770,159 @@ where
             }
             while self.merging.last().map(|x| x.is_none()) == Some(true) { self.merging.pop(); }
         }
+
+        self.bound_batches();
+    }
+}
+
+impl<K, V, T, R, B> Spine<K, V, T, R, B>
+where
+    K: Ord+Clone,
+    V: Ord+Clone,
+    T: Lattice+Ord+Clone+Debug,
+    R: Monoid,
+    B: Batch<K, V, T, R>+Clone+'static,
+{
+    // Compacts the spine, and possibly reports an eviction frontier, if its updates exceed its budget.
+    //
+    // Compaction may leave the spine over budget, for example when its updates are at distinct times
+    // beyond the advance frontier, and so the spine only acts again once its updates have doubled.
+    fn enforce_budget(&mut self) {
+        let bytes = match self.budget {
+            Some(ref budget) => budget.bytes,
+            None => return,
+        };
+        let size = ::std::mem::size_of::<(K, V, T, R)>();
+        let updates = self.updates();
+        if updates.saturating_mul(size) > bytes && updates > 2 * self.evicted {
+            if self.budget.as_ref().map(|b| b.eviction == Eviction::Report).unwrap_or(false) && self.upper.len() > 0 {
+                self.eviction_frontier = Some(self.upper.clone());
+            }
+            self.compact();
+            self.evicted = self.updates();
+        }
     }
-}
\ No newline at end of file
+
+    // The frontier the retention window maps the upper frontier to, if it has advanced the retention frontier.
+    fn retention_target(&self) -> Option<Vec<T>> {
+        let frontier = match self.retention {
+            Some(ref window) if self.upper.len() > 0 => {
+                let mut frontier = ::timely::progress::frontier::Antichain::new();
+                for time in self.upper.iter() {
+                    frontier.insert(window(time));
+                }
+                frontier.elements().to_vec()
+            },
+            _ => return None,
+        };
+
+        let advanced = frontier != self.retention_frontier &&
+            frontier.iter().all(|t1| self.retention_frontier.iter().any(|t2| t2.less_equal(t1)));
+
+        if advanced { Some(frontier) } else { None }
+    }
+
+    // Removes updates before the retention frontier, if the window has advanced it, with about `fuel` work.
+    //
+    // A pass rebuilds the batches holding updates before its frontier, from oldest to newest, and records
+    // the upper frontier of the batches it has rebuilt. Batches merge only with adjacent batches, so any
+    // batch whose upper frontier does not exceed the recorded frontier holds only rebuilt updates, even if
+    // merged since. Each pass rebuilds each batch at most once, rather than on each insertion, and the
+    // retention frontier advances when the pass completes.
+    fn enforce_retention(&mut self, mut fuel: usize) {
+        loop {
+            let (target, mut rebuilt) = match self.retaining.take() {
+                Some(pass) => pass,
+                None => match self.retention_target() {
+                    Some(target) => (target, vec![<T as Lattice>::minimum()]),
+                    None => return,
+                },
+            };
+
+            // Batches in `self.merging` are ordered from newest to oldest, and precede those in `self.pending`.
+            let mut complete = true;
+            let positions = self.merging.len();
+            for index in 0 .. positions + self.pending.len() {
+                let (lower, upper) = if index < positions {
+                    match self.merging[positions - index - 1] {
+                        Some(ref batch) => (batch.lower().to_vec(), batch.upper().to_vec()),
+                        None => continue,
+                    }
+                }
+                else {
+                    let batch = &self.pending[index - positions];
+                    (batch.lower().to_vec(), batch.upper().to_vec())
+                };
+
+                let passed = rebuilt.iter().all(|t1| upper.iter().any(|t2| t2.less_equal(t1)));
+                let unaffected = lower.iter().all(|t1| target.iter().any(|t2| t2.less_equal(t1)));
+                if !passed && !unaffected {
+                    if fuel == 0 {
+                        complete = false;
+                        break;
+                    }
+                    if index < positions {
+                        let position = positions - index - 1;
+                        if let Some(batch) = self.merging[position].take() {
+                            let batch = batch.complete(&mut self.logger, self.operator.global_id, position);
+                            let retained = Self::retain_batch(batch.clone(), &target[..]);
+                            fuel = fuel.saturating_sub(batch.len());
+                            self.log_replaced(&batch, &retained);
+                            self.merging[position] = Some(MergeState::Complete(retained));
+                        }
+                    }
+                    else {
+                        let index = index - positions;
+                        let retained = Self::retain_batch(self.pending[index].clone(), &target[..]);
+                        fuel = fuel.saturating_sub(self.pending[index].len());
+                        self.log_replaced(&self.pending[index], &retained);
+                        self.pending[index] = retained;
+                    }
+                }
+                rebuilt = upper;
+            }
+
+            if complete {
+                self.retention_frontier = target;
+            }
+            else {
+                self.retaining = Some((target, rebuilt));
+                return;
+            }
+        }
+    }
+
+    // Reports the replacement of `batch` by `retained`, as the drop of one and the insertion of the other.
+    fn log_replaced(&self, batch: &B, retained: &B) {
+        if batch.len() != retained.len() {
+            if let Some(logger) = self.logger.as_ref() {
+                logger.log(::logging::DropEvent { operator: self.operator.global_id, length: batch.len() });
+                logger.log(::logging::BatchEvent { operator: self.operator.global_id, length: retained.len() });
+            }
+        }
+    }
+
+    // Rebuilds `batch` retaining only updates at times greater or equal to an element of `frontier`.
+    fn retain_batch(batch: B, frontier: &[T]) -> B {
+        let retained = |time: &T| frontier.iter().any(|t| t.less_equal(time));
+        if batch.lower().iter().all(|time| retained(time)) {
+            batch
+        }
+        else {
+            let mut builder = <B::Builder as Builder<K, V, T, R, B>>::new();
+            let mut cursor = batch.cursor();
+            while let Some(key) = cursor.get_key(&batch) {
+                while let Some(val) = cursor.get_val(&batch) {
+                    cursor.map_times(&batch, |time, diff| {
+                        if retained(time) {
+                            builder.push((key.clone(), val.clone(), time.clone(), diff.clone()));
+                        }
+                    });
+                    cursor.step_val(&batch);
+                }
+                cursor.step_key(&batch);
+            }
+            builder.done(batch.lower(), batch.upper(), batch.description().since())
+        }
+    }
+}
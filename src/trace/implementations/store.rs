@@ -0,0 +1,197 @@
+//! Storage for batches held outside of process memory.
+//!
+//! A `BatchStore` holds the serialized bytes of batches under keys it assigns, and is all a trace
+//! needs to move batches out of memory: the trace retains only the key and a description of each
+//! stored batch, and retrieves the bytes when the batch is next read. The `FileStore` writes each
+//! batch to its own file in a directory. Other stores, for example over an embedded key-value store
+//! like RocksDB, need only implement the trait, and can live outside this crate.
+//!
+//! Stores are used by the `SpillSpine`, which decides which batches to store and when to read them
+//! back in; see `SpillSpine::with_store`.
+//!
+//! Stored batches do not outlive the trace that stored them, and do not allow a trace to be recovered
+//! after a restart: the trace removes each batch from its store once it no longer needs it, and the
+//! keys of its batches are held only in its memory. To recover a trace without replaying its inputs,
+//! use `Trace::checkpoint` and `Trace::restore` instead.
+//!
+//! A `CompressedStore` compresses batches before passing them to another store, and decompresses them
+//! when they are read back. Over a `MemoryStore`, and with a spine that spills batches by age, this
+//! holds historical batches in memory compressed, and decompresses them only when cursors read them.
+//! The `Zstd` codec is available with the `zstd` feature.
+
+use std::fs::File;
+use std::io::{Read, Write, BufWriter, Result};
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes the files of different stores in the same process.
+static STORE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A store of serialized batches, by key.
+pub trait BatchStore {
+    /// Stores `bytes`, returning the key by which they may be retrieved.
+    fn put(&mut self, bytes: &[u8]) -> Result<u64>;
+    /// Retrieves the bytes stored under `key`.
+    fn get(&self, key: u64) -> Result<Vec<u8>>;
+    /// Discards the bytes stored under `key`.
+    fn remove(&mut self, key: u64) -> Result<()>;
+}
+
+/// A store writing each batch to its own file in a directory.
+///
+/// Files are named by the process, the store, and the key, as `differential-spill-{process}-{store}-{key}`,
+/// so that stores in the same process and sharing a directory do not collide. Files are removed as the
+/// trace releases their batches, but a process that crashes leaves its files behind; as no later process
+/// reads them, they may be deleted once the process that wrote them has exited.
+pub struct FileStore {
+    directory: PathBuf,
+    identifier: usize,
+    next: u64,
+}
+
+impl FileStore {
+    /// Creates a store writing files to `directory`.
+    pub fn new(directory: PathBuf) -> Self {
+        FileStore {
+            directory,
+            identifier: STORE_COUNTER.fetch_add(1, Ordering::SeqCst),
+            next: 0,
+        }
+    }
+
+    // The file holding the bytes stored under `key`.
+    fn path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("differential-spill-{}-{}-{}", ::std::process::id(), self.identifier, key))
+    }
+}
+
+impl BatchStore for FileStore {
+    fn put(&mut self, bytes: &[u8]) -> Result<u64> {
+        let key = self.next;
+        self.next += 1;
+        let mut writer = BufWriter::new(File::create(self.path(key))?);
+        writer.write_all(bytes)?;
+        writer.flush()?;
+        Ok(key)
+    }
+    fn get(&self, key: u64) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        File::open(self.path(key))?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+    fn remove(&mut self, key: u64) -> Result<()> {
+        ::std::fs::remove_file(self.path(key))
+    }
+}
+
+/// A store holding batches in memory, serialized.
+///
+/// Serialized batches are typically smaller than their in-memory forms, and this store is mostly
+/// useful for testing traces that store batches without touching the file system.
+pub struct MemoryStore {
+    batches: HashMap<u64, Vec<u8>>,
+    next: u64,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        MemoryStore { batches: HashMap::new(), next: 0 }
+    }
+
+    /// The number of batches in the store.
+    pub fn len(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// Indicates if the store holds no batches.
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+}
+
+impl Default for MemoryStore {
+    /// An empty store.
+    fn default() -> Self {
+        MemoryStore::new()
+    }
+}
+
+impl BatchStore for MemoryStore {
+    fn put(&mut self, bytes: &[u8]) -> Result<u64> {
+        let key = self.next;
+        self.next += 1;
+        self.batches.insert(key, bytes.to_vec());
+        Ok(key)
+    }
+    fn get(&self, key: u64) -> Result<Vec<u8>> {
+        self.batches
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| ::std::io::Error::new(::std::io::ErrorKind::NotFound, "no batch stored under key"))
+    }
+    fn remove(&mut self, key: u64) -> Result<()> {
+        self.batches.remove(&key);
+        Ok(())
+    }
+}
+
+/// A method of compressing serialized batches.
+pub trait Codec {
+    /// Compresses `bytes`.
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>>;
+    /// Recovers the bytes compressed by `compress`.
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// A store compressing batches before storing them in another store.
+pub struct CompressedStore<S, C> {
+    store: S,
+    codec: C,
+}
+
+impl<S: BatchStore, C: Codec> CompressedStore<S, C> {
+    /// Creates a store compressing batches with `codec` and storing them in `store`.
+    pub fn new(store: S, codec: C) -> Self {
+        CompressedStore { store, codec }
+    }
+}
+
+impl<S: BatchStore, C: Codec> BatchStore for CompressedStore<S, C> {
+    fn put(&mut self, bytes: &[u8]) -> Result<u64> {
+        let compressed = self.codec.compress(bytes)?;
+        self.store.put(&compressed[..])
+    }
+    fn get(&self, key: u64) -> Result<Vec<u8>> {
+        let compressed = self.store.get(key)?;
+        self.codec.decompress(&compressed[..])
+    }
+    fn remove(&mut self, key: u64) -> Result<()> {
+        self.store.remove(key)
+    }
+}
+
+/// Compression with zstd, at a configurable level.
+#[cfg(feature = "zstd")]
+pub struct Zstd {
+    level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl Zstd {
+    /// Creates a codec compressing at `level`; levels from one to about twenty trade speed for size.
+    pub fn new(level: i32) -> Self {
+        Zstd { level }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Codec for Zstd {
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        ::zstd::encode_all(bytes, self.level)
+    }
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        ::zstd::decode_all(bytes)
+    }
+}
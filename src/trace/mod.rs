@@ -7,13 +7,22 @@
 //! collection trace. This trait allows operator implementations to be generic with respect to the type of trace,
 //! and allows various data structures to be interpretable as multiple different types of trace.
 
+pub mod checkpoint;
 pub mod cursor;
 pub mod description;
+#[cfg(feature = "bincode")]
+pub mod encoding;
+#[cfg(feature = "arrow")]
+pub mod export;
 pub mod implementations;
 pub mod layers;
+pub mod validate;
 pub mod wrappers;
 
+use abomonation::Abomonation;
+
 use ::difference::Monoid;
+use lattice::Lattice;
 pub use self::cursor::Cursor;
 pub use self::description::Description;
 
@@ -104,6 +113,125 @@ pub trait TraceReader<Key, Val, Time, R> {
 	/// cursor methods, as they (by default) just move through batches accumulating cursors into a cursor list.
 	fn map_batches<F: FnMut(&Self::Batch)>(&mut self, f: F);
 
+	/// Eagerly performs outstanding work to compact the representation of the trace.
+	///
+	/// Traces may defer work, for example merging batches, to amortize its cost. This method asks the trace
+	/// to complete this work now, which may reclaim memory at the expense of a potentially large amount of
+	/// computation. The default implementation does nothing.
+	fn compact(&mut self) { }
+
+	/// Prepares the trace for low-latency reads of the keys in an inclusive range, or all keys.
+	///
+	/// Traces may hold some of their contents in a form that is expensive to read, for example on disk. This
+	/// method asks the trace to prepare the contents covering the keys between the bounds of `keys` for reading,
+	/// so that the first cursor to read them is not slowed by this work. The prepared contents may be held in
+	/// memory until `cool` is called. The default implementation does nothing.
+	fn warm(&mut self, _keys: Option<(&Key, &Key)>) { }
+
+	/// Releases any resources held by calls to `warm`.
+	///
+	/// The default implementation does nothing.
+	fn cool(&mut self) { }
+
+	/// Reports statistics describing the batches the trace holds.
+	///
+	/// The statistics are gathered by visiting each batch, and counting keys visits each key of each batch;
+	/// this is proportional to the size of the trace and should not be done frequently.
+	fn statistics(&mut self) -> TraceStatistics<Time> where Time: Clone {
+		let mut statistics = TraceStatistics {
+			batches: 0,
+			updates: 0,
+			keys: 0,
+			bytes: 0,
+			advance_frontier: self.advance_frontier().to_vec(),
+			distinguish_frontier: self.distinguish_frontier().to_vec(),
+		};
+		self.map_batches(|batch| {
+			statistics.batches += 1;
+			statistics.updates += batch.len();
+			let mut cursor = batch.cursor();
+			while cursor.key_valid(batch) {
+				statistics.keys += 1;
+				cursor.step_key(batch);
+			}
+		});
+		statistics.bytes = statistics.updates * ::std::mem::size_of::<(Key, Val, Time, R)>();
+		statistics
+	}
+
+	/// The accumulated contents of the trace at times not greater or equal to an element of `upper`.
+	///
+	/// Each `(key, val, count)` has a non-zero `count`, accumulated from updates at times before `upper`, and
+	/// they appear in order of key and value. An empty `upper` accumulates all updates. The result is `None` unless
+	/// each element of `upper` is strictly beyond the advance frontier, as otherwise times before `upper` may have
+	/// been advanced to times not before it. All batches are read, and updates from `upper` onward are excluded, so the
+	/// result does not depend on how the trace has merged its batches.
+	fn snapshot(&mut self, upper: &[Time]) -> Option<::std::vec::IntoIter<(Key, Val, R)>>
+	where
+		Key: Clone,
+		Val: Clone,
+		Time: Lattice,
+		R: Monoid,
+	{
+		let beyond = upper.iter().all(|time| self.advance_frontier().iter().any(|t| t.less_equal(time) && t != time));
+		if !beyond {
+			return None;
+		}
+
+		let mut result = Vec::new();
+		let (mut cursor, storage) = self.cursor();
+		while let Some(key) = cursor.get_key(&storage) {
+			while let Some(val) = cursor.get_val(&storage) {
+				let mut count = R::zero();
+				cursor.map_times(&storage, |time, diff| {
+					if !upper.iter().any(|t| t.less_equal(time)) {
+						count += diff;
+					}
+				});
+				if !count.is_zero() {
+					result.push((key.clone(), val.clone(), count));
+				}
+				cursor.step_val(&storage);
+			}
+			cursor.step_key(&storage);
+		}
+		Some(result.into_iter())
+	}
+
+	/// Writes the contents of the trace, advanced to its advance frontier and consolidated, to the file at `path`.
+	///
+	/// The file can be read by `Trace::restore` to rebuild the trace, as described in the `checkpoint` module.
+	fn checkpoint<P: AsRef<::std::path::Path>>(&mut self, path: P) -> ::std::io::Result<()>
+	where
+		Self: Sized,
+		Key: Abomonation+Ord+Clone,
+		Val: Abomonation+Ord+Clone,
+		Time: Abomonation+Lattice+Ord+Clone,
+		R: Abomonation+Monoid,
+	{
+		checkpoint::write(self, path)
+	}
+
+}
+
+/// Statistics describing the contents of a trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceStatistics<Time> {
+	/// The number of batches the trace holds, counting both inputs of merges in progress.
+	pub batches: usize,
+	/// The number of updates in the batches.
+	pub updates: usize,
+	/// The number of keys in the batches, counting keys once for each batch containing them.
+	pub keys: usize,
+	/// An estimate of the bytes the updates occupy, as if each were a `(key, val, time, diff)` tuple.
+	///
+	/// The estimate does not count memory the keys, values, times, or differences own, nor memory shared
+	/// among updates, as representations that store each key once for many updates do.
+	pub bytes: usize,
+	/// The frontier to which the trace may compact times.
+	pub advance_frontier: Vec<Time>,
+	/// The frontier from which the trace can be subset at batch boundaries.
+	pub distinguish_frontier: Vec<Time>,
 }
 
 /// An append-only collection of `(key, val, time, diff)` tuples.
@@ -134,6 +262,21 @@ where <Self as TraceReader<Key, Val, Time, R>>::Batch: Batch<Key, Val, Time, R>
 	/// This method should be logically equivalent to introducing an empty batch whose lower frontier equals
 	/// the upper frontier of the most recently introduced batch, and whose upper frontier is empty.
 	fn close(&mut self);
+
+	/// Allocates a trace containing the contents written to the file at `path` by `TraceReader::checkpoint`.
+	///
+	/// The trace holds the checkpointed contents as one batch, and accepts batches from the checkpoint's upper
+	/// frontier onward.
+	fn restore<P: AsRef<::std::path::Path>>(path: P, info: ::timely::dataflow::operators::generic::OperatorInfo, logging: Option<::logging::Logger>) -> ::std::io::Result<Self>
+	where
+		Self: Sized,
+		Key: Abomonation+Clone,
+		Val: Abomonation+Clone,
+		Time: Abomonation+Lattice+Ord+Clone,
+		R: Abomonation+Clone,
+	{
+		checkpoint::read(path, info, logging)
+	}
 }
 
 /// A batch of updates whose contents may be read.
@@ -184,6 +327,12 @@ pub trait Batcher<K, V, T, R, Output: Batch<K, V, T, R>> {
 	fn new() -> Self;
 	/// Adds an unordered batch of elements to the batcher.
 	fn push_batch(&mut self, batch: &mut Vec<((K, V), T, R)>);
+	/// Adds a batch of elements sorted by `(key, val, time)` to the batcher.
+	///
+	/// Batchers may use the order to avoid sorting the elements, and by default treat them as unordered.
+	fn push_sorted(&mut self, batch: &mut Vec<((K, V), T, R)>) {
+		self.push_batch(batch)
+	}
 	/// Returns all updates not greater or equal to an element of `upper`.
 	fn seal(&mut self, upper: &[T]) -> Output;
 	/// Returns the lower envelope of contained update times.
@@ -202,6 +351,28 @@ pub trait Builder<K, V, T, R, Output: Batch<K, V, T, R>> {
 	fn extend<I: Iterator<Item=(K,V,T,R)>>(&mut self, iter: I) {
 		for item in iter { self.push(item); }
 	}
+	/// Adds elements sorted by `(key, val, time)` to the batch, as produced by a batcher.
+	///
+	/// Elements with the same key, value, and time are accumulated, and those that accumulate to zero are
+	/// not added. The elements are drained from `elements`, whose allocation may then be reused.
+	fn push_sorted(&mut self, elements: &mut Vec<((K, V), T, R)>) where K: PartialEq, V: PartialEq, T: PartialEq, R: Monoid {
+		let mut pending: Option<((K, V), T, R)> = None;
+		for (data, time, diff) in elements.drain(..) {
+			if let Some(ref mut prev) = pending {
+				if prev.0 == data && prev.1 == time {
+					prev.2 += &diff;
+					continue;
+				}
+			}
+			if let Some(((key1, val1), time1, diff1)) = pending.take() {
+				if !diff1.is_zero() { self.push((key1, val1, time1, diff1)); }
+			}
+			pending = Some((data, time, diff));
+		}
+		if let Some(((key, val), time, diff)) = pending {
+			if !diff.is_zero() { self.push((key, val, time, diff)); }
+		}
+	}
 	/// Completes building and returns the batch.
 	fn done(self, lower: &[T], upper: &[T], since: &[T]) -> Output;
 }
@@ -212,15 +383,21 @@ pub trait Merger<K, V, T, R, Output: Batch<K, V, T, R>> {
 	fn new(source1: &Output, source2: &Output) -> Self;
 	/// Perform some amount of work, decrementing `fuel`.
 	///
-	/// If `fuel` is non-zero after the call, the merging is complete and
-	/// one should call `done` to extract the merged results.
+	/// If `fuel` is non-zero after the call and the merge is not `pending`,
+	/// the merging is complete and one should call `done` to extract the
+	/// merged results.
 	fn work(&mut self, source1: &Output, source2: &Output, frontier: &Option<Vec<T>>, fuel: &mut usize);
 	/// Extracts merged results.
 	///
 	/// This method should only be called after `work` has been called and
-	/// has not brought `fuel` to zero. Otherwise, the merge is still in
-	/// progress.
+	/// has not brought `fuel` to zero, and the merge is not `pending`.
+	/// Otherwise, the merge is still in progress.
 	fn done(self) -> Output;
+	/// Indicates that the merge is still in progress elsewhere, although
+	/// `work` left `fuel` non-zero.
+	///
+	/// Merges performed by `work` itself are never pending.
+	fn pending(&self) -> bool { false }
 }
 
 
@@ -284,6 +461,8 @@ pub mod rc_blanket_impls {
 
 	    #[inline(always)] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(storage) }
 	    #[inline(always)] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(storage) }
+
+	    #[inline(always)] fn may_contain(&self, storage: &Self::Storage, key: &K) -> bool { self.cursor.may_contain(storage, key) }
 	}
 
 	/// An immutable collection of updates.
@@ -300,6 +479,7 @@ pub mod rc_blanket_impls {
 	impl<K,V,T,R,B:Batch<K,V,T,R>> Batcher<K, V, T, R, Rc<B>> for RcBatcher<K,V,T,R,B> {
 		fn new() -> Self { RcBatcher { batcher: <B::Batcher as Batcher<K,V,T,R,B>>::new() } }
 		fn push_batch(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_batch(batch) }
+		fn push_sorted(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_sorted(batch) }
 		fn seal(&mut self, upper: &[T]) -> Rc<B> { Rc::new(self.batcher.seal(upper)) }
 		fn frontier(&mut self) -> &[T] { self.batcher.frontier() }
 	}
@@ -323,6 +503,7 @@ pub mod rc_blanket_impls {
 		fn new(source1: &Rc<B>, source2: &Rc<B>) -> Self { RcMerger { merger: B::begin_merge(source1, source2) } }
 		fn work(&mut self, source1: &Rc<B>, source2: &Rc<B>, frontier: &Option<Vec<T>>, fuel: &mut usize) { self.merger.work(source1, source2, frontier, fuel) }
 		fn done(self) -> Rc<B> { Rc::new(self.merger.done()) }
+		fn pending(&self) -> bool { self.merger.pending() }
 	}
 }
 
@@ -406,6 +587,7 @@ pub mod abomonated_blanket_impls {
 	impl<K,V,T,R,B:Batch<K,V,T,R>+Abomonation> Batcher<K, V, T, R, Abomonated<B,Vec<u8>>> for AbomonatedBatcher<K,V,T,R,B> {
 		fn new() -> Self { AbomonatedBatcher { batcher: <B::Batcher as Batcher<K,V,T,R,B>>::new() } }
 		fn push_batch(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_batch(batch) }
+		fn push_sorted(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_sorted(batch) }
 		fn seal(&mut self, upper: &[T]) -> Abomonated<B, Vec<u8>> {
 			let batch = self.batcher.seal(upper);
 			let mut bytes = Vec::with_capacity(measure(&batch));
@@ -442,6 +624,9 @@ pub mod abomonated_blanket_impls {
 		fn work(&mut self, source1: &Abomonated<B,Vec<u8>>, source2: &Abomonated<B,Vec<u8>>, frontier: &Option<Vec<T>>, fuel: &mut usize) {
 			self.merger.work(source1, source2, frontier, fuel)
 		}
+		fn pending(&self) -> bool {
+			self.merger.pending()
+		}
 		fn done(self) -> Abomonated<B, Vec<u8>> {
 			let batch = self.merger.done();
 			let mut bytes = Vec::with_capacity(measure(&batch));
@@ -0,0 +1,158 @@
+//! Checking the internal consistency of traces.
+//!
+//! A trace is a sequence of batches whose descriptions should abut, each batch holding updates in order
+//! of key and value, with non-zero differences at times not before the batch's lower frontier. Operators
+//! rely on these invariants without checking them, and a violation deep inside a computation usually
+//! surfaces only as incorrect output, far from its cause. The `validate` function visits each batch of a
+//! trace and reports the violations it finds, and `validate_batch` does the same for a single batch.
+//!
+//! Validation reads each update of each batch, and is intended for debugging rather than frequent use.
+
+use std::fmt;
+
+use ::difference::Monoid;
+use lattice::Lattice;
+use super::{BatchReader, Cursor, TraceReader};
+
+/// A violation of the invariants of a trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation<K, V, T> {
+	/// The lower frontier of a batch differs from the upper frontier of the batch before it.
+	Discontiguous {
+		/// The index of the batch, in the order of `TraceReader::map_batches`.
+		batch: usize,
+		/// The upper frontier of the preceding batch.
+		upper: Vec<T>,
+		/// The lower frontier of the batch.
+		lower: Vec<T>,
+	},
+	/// A key is not greater than the key before it.
+	KeyOrder {
+		/// The index of the batch.
+		batch: usize,
+		/// The key out of order.
+		key: K,
+	},
+	/// A value is not greater than the value before it, for the same key.
+	ValOrder {
+		/// The index of the batch.
+		batch: usize,
+		/// The key of the value.
+		key: K,
+		/// The value out of order.
+		val: V,
+	},
+	/// An update has a zero difference.
+	ZeroDiff {
+		/// The index of the batch.
+		batch: usize,
+		/// The key of the update.
+		key: K,
+		/// The value of the update.
+		val: V,
+		/// The time of the update.
+		time: T,
+	},
+	/// An update has a time not greater or equal to an element of the batch's lower frontier.
+	TimeBeforeLower {
+		/// The index of the batch.
+		batch: usize,
+		/// The key of the update.
+		key: K,
+		/// The value of the update.
+		val: V,
+		/// The time of the update.
+		time: T,
+	},
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, T: fmt::Debug> fmt::Display for Violation<K, V, T> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Violation::Discontiguous { batch, ref upper, ref lower } =>
+				write!(f, "batch {}: lower {:?} does not match preceding upper {:?}", batch, lower, upper),
+			Violation::KeyOrder { batch, ref key } =>
+				write!(f, "batch {}: key {:?} out of order", batch, key),
+			Violation::ValOrder { batch, ref key, ref val } =>
+				write!(f, "batch {}: value {:?} of key {:?} out of order", batch, val, key),
+			Violation::ZeroDiff { batch, ref key, ref val, ref time } =>
+				write!(f, "batch {}: zero difference for ({:?}, {:?}) at {:?}", batch, key, val, time),
+			Violation::TimeBeforeLower { batch, ref key, ref val, ref time } =>
+				write!(f, "batch {}: time {:?} of ({:?}, {:?}) before lower frontier", batch, time, key, val),
+		}
+	}
+}
+
+/// Reports the violations of the invariants of the batches of `trace`, and of their sequence.
+///
+/// Batches are numbered in the order `TraceReader::map_batches` visits them.
+pub fn validate<K, V, T, R, Tr>(trace: &mut Tr) -> Vec<Violation<K, V, T>>
+where
+	K: Ord+Clone,
+	V: Ord+Clone,
+	T: Lattice+Ord+Clone,
+	R: Monoid,
+	Tr: TraceReader<K, V, T, R>,
+{
+	let mut violations = Vec::new();
+	let mut index = 0;
+	let mut upper: Option<Vec<T>> = None;
+	trace.map_batches(|batch| {
+		if let Some(ref upper) = upper {
+			if upper[..] != batch.lower()[..] {
+				violations.push(Violation::Discontiguous {
+					batch: index,
+					upper: upper.clone(),
+					lower: batch.lower().to_vec(),
+				});
+			}
+		}
+		upper = Some(batch.upper().to_vec());
+		violations.extend(validate_batch(batch, index));
+		index += 1;
+	});
+	violations
+}
+
+/// Reports the violations of the invariants of `batch`, identified in each violation by `index`.
+pub fn validate_batch<K, V, T, R, B>(batch: &B, index: usize) -> Vec<Violation<K, V, T>>
+where
+	K: Ord+Clone,
+	V: Ord+Clone,
+	T: Lattice+Ord+Clone,
+	R: Monoid,
+	B: BatchReader<K, V, T, R>,
+{
+	let mut violations = Vec::new();
+	let lower = batch.lower();
+
+	let mut prev_key: Option<&K> = None;
+	let mut cursor = batch.cursor();
+	while let Some(key) = cursor.get_key(batch) {
+		if prev_key.map(|prev| prev >= key).unwrap_or(false) {
+			violations.push(Violation::KeyOrder { batch: index, key: key.clone() });
+		}
+		prev_key = Some(key);
+
+		let mut prev_val: Option<&V> = None;
+		while let Some(val) = cursor.get_val(batch) {
+			if prev_val.map(|prev| prev >= val).unwrap_or(false) {
+				violations.push(Violation::ValOrder { batch: index, key: key.clone(), val: val.clone() });
+			}
+			prev_val = Some(val);
+
+			cursor.map_times(batch, |time, diff| {
+				if diff.is_zero() {
+					violations.push(Violation::ZeroDiff { batch: index, key: key.clone(), val: val.clone(), time: time.clone() });
+				}
+				if !lower.iter().any(|t| t.less_equal(time)) {
+					violations.push(Violation::TimeBeforeLower { batch: index, key: key.clone(), val: val.clone(), time: time.clone() });
+				}
+			});
+			cursor.step_val(batch);
+		}
+		cursor.step_key(batch);
+	}
+
+	violations
+}
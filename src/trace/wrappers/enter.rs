@@ -202,6 +202,9 @@ where
 
     #[inline(always)] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(storage) }
     #[inline(always)] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(storage) }
+
+    #[inline(always)] fn may_contain(&self, storage: &Self::Storage, key: &K) -> bool { self.cursor.may_contain(storage, key) }
+    #[inline(always)] fn probe_key(&mut self, storage: &Self::Storage, key: &K) -> bool where K: PartialEq { self.cursor.probe_key(storage, key) }
 }
 
 
@@ -249,4 +252,6 @@ where
 
     #[inline(always)] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(&storage.batch) }
     #[inline(always)] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(&storage.batch) }
+
+    #[inline(always)] fn may_contain(&self, storage: &Self::Storage, key: &K) -> bool { self.cursor.may_contain(&storage.batch, key) }
 }
\ No newline at end of file
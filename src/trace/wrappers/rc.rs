@@ -122,6 +122,15 @@ impl<K,V,T,R,Tr> TraceReader<K, V, T, R> for TraceRc<K,V,T,R,Tr> where T: Lattic
     fn map_batches<F: FnMut(&Self::Batch)>(&mut self, f: F) {
         ::std::cell::RefCell::borrow_mut(&self.wrapper).trace.map_batches(f)
     }
+    fn compact(&mut self) {
+        ::std::cell::RefCell::borrow_mut(&self.wrapper).trace.compact()
+    }
+    fn warm(&mut self, keys: Option<(&K, &K)>) {
+        ::std::cell::RefCell::borrow_mut(&self.wrapper).trace.warm(keys)
+    }
+    fn cool(&mut self) {
+        ::std::cell::RefCell::borrow_mut(&self.wrapper).trace.cool()
+    }
 }
 
 impl<K,V,T,R,Tr> TraceRc<K,V,T,R,Tr> where T: Lattice+Ord+Clone+'static, Tr: TraceReader<K,V,T,R> {
@@ -243,3 +243,55 @@ fn import_skewed() {
         (4, vec![((0, 1), 1)]),
     ]);
 }
+
+#[test]
+fn test_registry() {
+    use differential_dataflow::operators::arrange::TraceRegistry;
+    use differential_dataflow::trace::implementations::ord::{OrdKeySpine, OrdValSpine};
+
+    let results = timely::execute(timely::Configuration::Thread, |worker| {
+
+        let mut registry = TraceRegistry::new();
+
+        let mut input = InputSession::new();
+        worker.dataflow::<usize,_,_>(|scope| {
+            let trace = input.to_collection(scope).arrange_by_self().trace;
+            assert!(!registry.publish("numbers", &trace));
+        });
+
+        input.insert(3u64);
+        input.insert(4u64);
+        input.advance_to(1);
+        input.flush();
+        for _ in 0 .. 10 { worker.step(); }
+
+        // traces are only available with the type they were published with.
+        assert!(registry.get::<u64, (), isize, OrdKeySpine<u64, usize, isize>>("numbers").is_some());
+        assert!(registry.get::<u64, u64, isize, OrdValSpine<u64, u64, usize, isize>>("numbers").is_none());
+        assert!(registry.get::<u64, (), isize, OrdKeySpine<u64, usize, isize>>("letters").is_none());
+        assert_eq!(registry.names(), vec!["numbers"]);
+
+        let captured = worker.dataflow(|scope| {
+            registry
+                .import::<_, u64, (), isize, OrdKeySpine<u64, usize, isize>>("numbers", scope)
+                .unwrap()
+                .as_collection(|key, _| *key)
+                .inner
+                .capture()
+        });
+
+        registry.advance_by(&[1]);
+        registry.distinguish_since(&[1]);
+        assert!(registry.unpublish("numbers"));
+        assert!(registry.is_empty());
+
+        input.close();
+        while worker.step() { }
+
+        captured
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).next().unwrap();
+
+    let mut numbers = results.extract().into_iter().flat_map(|(_, data)| data).map(|(x, _, d)| (x, d)).collect::<Vec<_>>();
+    numbers.sort();
+    assert_eq!(numbers, vec![(3, 1), (4, 1)]);
+}
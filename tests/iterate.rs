@@ -0,0 +1,147 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::order::Product;
+use timely::dataflow::Scope;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::{Iterate, IterateMonotonic, Join, Threshold};
+use differential_dataflow::operators::iterate::MutualRecursion;
+
+// Compares variants of iteration with `iterate`, computing the nodes reachable from a root as edges change.
+#[test]
+fn iterate_variants() {
+
+    timely::execute(timely::Configuration::Process(2), |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+
+        let (mut roots, mut edges, probe) = worker.dataflow::<u64,_,_>(|scope| {
+
+            let (roots_input, roots) = scope.new_collection::<u32, isize>();
+            let (edges_input, edges) = scope.new_collection::<(u32, u32), isize>();
+
+            let reach =
+            roots.iterate(|reach| {
+                let edges = edges.enter(&reach.scope());
+                let roots = roots.enter(&reach.scope());
+                reach.map(|x| (x, ())).join_map(&edges, |_, &(), &y| y).concat(&roots).distinct()
+            });
+
+            let monitored =
+            roots.iterate_monitored(1000, |reach| {
+                let edges = edges.enter(&reach.scope());
+                let roots = roots.enter(&reach.scope());
+                reach.map(|x| (x, ())).join_map(&edges, |_, &(), &y| y).concat(&roots).distinct()
+            });
+
+            // the gated loop, with a gate that never stops the iteration.
+            let gated =
+            roots.iterate_until(|_x, diff| (*diff as f64).abs(), |_time, _total| false, |reach| {
+                let edges = edges.enter(&reach.scope());
+                let roots = roots.enter(&reach.scope());
+                reach.map(|x| (x, ())).join_map(&edges, |_, &(), &y| y).concat(&roots).distinct()
+            });
+
+            // reached nodes and their successors, defined in terms of each other.
+            let mutual = scope.iterative::<u64,_,_>(|nested| {
+                let edges = edges.enter(nested);
+                let roots = roots.enter(nested);
+                let recursion = MutualRecursion::new(nested, Product::new(Default::default(), 1));
+                let reach = recursion.variable::<u32, isize>("reach");
+                let successors = recursion.variable::<u32, isize>("successors");
+                let next_successors = reach.map(|x| (x, ())).join_map(&edges, |_, &(), &y| y).distinct();
+                let next_reach = successors.concat(&roots).distinct();
+                successors.set(&next_successors);
+                let reach = reach.set(&next_reach);
+                recursion.complete();
+                reach.leave()
+            });
+
+            monitored.assert_eq(&reach);
+            gated.assert_eq(&reach);
+            mutual.assert_eq(&reach);
+
+            (roots_input, edges_input, reach.probe())
+        });
+
+        if index == 0 {
+            roots.insert(0);
+        }
+
+        for round in 0 .. 10u64 {
+            for node in 0 .. 10u32 {
+                if (node as u64 + round) as usize % peers == index {
+                    edges.insert((node, (node * 3 + round as u32) % 10));
+                    if round > 0 && round % 2 == 0 {
+                        edges.remove((node, (node * 3 + round as u32 - 1) % 10));
+                    }
+                }
+            }
+            roots.advance_to(round + 1);
+            edges.advance_to(round + 1);
+            roots.flush();
+            edges.flush();
+            while probe.less_than(edges.time()) {
+                worker.step();
+            }
+        }
+
+    }).unwrap().join().into_iter().for_each(|x| x.unwrap());
+}
+
+// Compares `iterate_monotonic` with `iterate`, computing the nodes reachable from a root as edges are added.
+#[test]
+fn iterate_monotonic() {
+
+    timely::execute(timely::Configuration::Process(2), |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+
+        let (mut roots, mut edges, probe) = worker.dataflow::<u64,_,_>(|scope| {
+
+            let (roots_input, roots) = scope.new_collection::<u32, isize>();
+            let (edges_input, edges) = scope.new_collection::<(u32, u32), isize>();
+
+            let reach =
+            roots.iterate(|reach| {
+                let edges = edges.enter(&reach.scope());
+                let roots = roots.enter(&reach.scope());
+                reach.map(|x| (x, ())).join_map(&edges, |_, &(), &y| y).concat(&roots).distinct()
+            });
+
+            let monotonic =
+            roots.iterate_monotonic(|reach| {
+                let edges = edges.enter(&reach.scope());
+                reach.map(|x| (x, ())).join_map(&edges, |_, &(), &y| y).distinct()
+            })
+            .distinct();
+
+            monotonic.assert_eq(&reach);
+
+            (roots_input, edges_input, reach.probe())
+        });
+
+        if index == 0 {
+            roots.insert(0);
+        }
+
+        for round in 0 .. 10u64 {
+            for node in 0 .. 10u32 {
+                if (node as u64 + round) as usize % peers == index {
+                    edges.insert((node, (node * 3 + round as u32) % 10));
+                }
+            }
+            roots.advance_to(round + 1);
+            edges.advance_to(round + 1);
+            roots.flush();
+            edges.flush();
+            while probe.less_than(edges.time()) {
+                worker.step();
+            }
+        }
+
+    }).unwrap().join().into_iter().for_each(|x| x.unwrap());
+}
@@ -101,4 +101,105 @@ fn join_scaling(scale: u64) {
 
     let extracted = data.extract();
     assert_eq!(extracted.len(), 0);
-}
\ No newline at end of file
+}
+#[test]
+fn half_join() {
+
+    use timely::PartialOrder;
+    use differential_dataflow::input::Input;
+    use differential_dataflow::operators::HalfJoin;
+    use differential_dataflow::operators::arrange::ArrangeByKey;
+
+    timely::execute(timely::Configuration::Process(2), |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+
+        let (mut input1, mut input2, probe) = worker.dataflow::<u64,_,_>(|scope| {
+
+            let (input1, data1) = scope.new_collection::<(u64, u64), isize>();
+            let (input2, data2) = scope.new_collection::<(u64, u64), isize>();
+
+            // the changes to the join, as produced by half joins; the second with a small amount of fuel.
+            let delta1 = data1.half_join(&data2.arrange_by_key(), |t2, t| t2.less_equal(t), |k, v1, v2| (*k, *v1, *v2));
+            let delta2 = data2.half_join_fueled(&data1.arrange_by_key(), |t2, t| t2.less_than(t), |k, v2, v1| (*k, *v1, *v2), 3);
+
+            let joined = data1.join(&data2).map(|(k, (v1, v2))| (k, v1, v2));
+            delta1.concat(&delta2).assert_eq(&joined);
+
+            (input1, input2, joined.probe())
+        });
+
+        for round in 0 .. 10u64 {
+            for key in 0 .. 20u64 {
+                if (key + round) as usize % peers == index {
+                    input1.insert((key % 5, key + round));
+                    input2.insert((key % 7, key * round));
+                    if round > 0 {
+                        input1.remove((key % 5, key + round - 1));
+                    }
+                }
+            }
+            input1.advance_to(round + 1);
+            input2.advance_to(round + 1);
+            input1.flush();
+            input2.flush();
+            while probe.less_than(input1.time()) {
+                worker.step();
+            }
+        }
+
+    }).unwrap().join().into_iter().for_each(|x| x.unwrap());
+}
+
+#[test]
+fn arrange_sorted() {
+
+    use differential_dataflow::input::Input;
+    use differential_dataflow::operators::JoinCore;
+    use differential_dataflow::operators::arrange::{Arrange, ArrangeByKey};
+    use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+    timely::execute(timely::Configuration::Process(2), |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+
+        let (mut input1, mut input2, probe) = worker.dataflow::<u64,_,_>(|scope| {
+
+            let (input1, data1) = scope.new_collection::<(u64, u64), isize>();
+            let (input2, data2) = scope.new_collection::<(u64, u64), isize>();
+
+            // the contents of an arrangement arrive sorted; the input itself generally does not.
+            let sorted = data1.arrange_by_key().as_collection(|k, v| (*k, *v)).arrange_sorted::<OrdValSpine<_,_,_,_>>();
+            let unsorted = data1.arrange_sorted::<OrdValSpine<_,_,_,_>>();
+            let arranged2 = data2.arrange_by_key();
+
+            let joined = data1.join(&data2);
+            sorted.join_core(&arranged2, |k, v1, v2| Some((*k, (*v1, *v2)))).assert_eq(&joined);
+            unsorted.join_core(&arranged2, |k, v1, v2| Some((*k, (*v1, *v2)))).assert_eq(&joined);
+
+            (input1, input2, joined.probe())
+        });
+
+        for round in 0 .. 10u64 {
+            for key in 0 .. 20u64 {
+                if (key + round) as usize % peers == index {
+                    input1.insert((20 - key, key + round));
+                    input2.insert((key % 7, key * round));
+                    if round > 0 {
+                        input1.remove((20 - key, key + round - 1));
+                    }
+                }
+            }
+            input1.advance_to(round + 1);
+            input2.advance_to(round + 1);
+            input1.flush();
+            input2.flush();
+            while probe.less_than(input1.time()) {
+                worker.step();
+            }
+        }
+
+    }).unwrap().join().into_iter().for_each(|x| x.unwrap());
+}
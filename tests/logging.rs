@@ -0,0 +1,50 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use timely::Configuration;
+use timely::dataflow::operators::{Capture, ToStream};
+use timely::dataflow::operators::capture::Extract;
+use timely::logging::{TimelyEvent, OperatesEvent};
+
+use differential_dataflow::logging::{self, DifferentialEvent, BatchEvent, MergeEvent, DropEvent, LayoutEvent};
+
+#[test]
+fn arrangement_sizes() {
+
+    let captured = timely::execute(Configuration::Thread, |worker| {
+        worker.dataflow::<Duration,_,_>(|scope| {
+
+            let operates = vec![
+                (Duration::from_secs(0), 0, TimelyEvent::Operates(OperatesEvent { id: 3, addr: vec![0, 3], name: "Arrange".to_string() })),
+                (Duration::from_secs(0), 0, TimelyEvent::Operates(OperatesEvent { id: 4, addr: vec![0, 4], name: "Map".to_string() })),
+            ];
+
+            let events = vec![
+                (Duration::from_secs(0), 0, DifferentialEvent::Layout(LayoutEvent { operator: 3, update_bytes: 8 })),
+                (Duration::from_secs(1), 0, DifferentialEvent::Batch(BatchEvent { operator: 3, length: 10 })),
+                (Duration::from_secs(2), 0, DifferentialEvent::Batch(BatchEvent { operator: 3, length: 5 })),
+                (Duration::from_secs(3), 0, DifferentialEvent::Merge(MergeEvent { operator: 3, scale: 4, length1: 10, length2: 5, complete: None })),
+                (Duration::from_secs(3), 0, DifferentialEvent::Merge(MergeEvent { operator: 3, scale: 4, length1: 10, length2: 5, complete: Some(12) })),
+                (Duration::from_secs(4), 0, DifferentialEvent::Drop(DropEvent { operator: 3, length: 2 })),
+            ];
+
+            logging::arrangement_sizes(&operates.to_stream(scope), &events.to_stream(scope))
+                .inner
+                .capture()
+        })
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).next().unwrap();
+
+    let mut accumulated = HashMap::new();
+    for (_time, updates) in captured.extract() {
+        for (data, _time, diff) in updates {
+            *accumulated.entry(data).or_insert(0) += diff;
+        }
+    }
+    accumulated.retain(|_data, diff| *diff != 0);
+
+    let expected = vec![((3, "Arrange".to_string(), 10, 80), 1)].into_iter().collect::<HashMap<_,_>>();
+    assert_eq!(accumulated, expected);
+}
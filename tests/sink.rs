@@ -0,0 +1,106 @@
+extern crate differential_dataflow;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use differential_dataflow::sink::{Coordinator, Participant};
+
+/// Records the calls made of it, and fails to commit once `fail` is set.
+struct Recorder {
+    calls: Vec<String>,
+    fail: bool,
+}
+
+impl Participant<usize> for Recorder {
+    fn commit(&mut self, upper: &[usize]) -> Result<(), String> {
+        if self.fail {
+            Err("commit failed".to_owned())
+        }
+        else {
+            self.calls.push(format!("commit {:?}", upper));
+            Ok(())
+        }
+    }
+    fn abort(&mut self, upper: &[usize]) {
+        self.calls.push(format!("abort {:?}", upper));
+    }
+    fn rollback(&mut self, upper: &[usize]) {
+        self.calls.push(format!("rollback {:?}", upper));
+    }
+}
+
+fn coordinate(participants: usize) -> (Coordinator<usize>, Vec<Rc<RefCell<Recorder>>>) {
+    let coordinator = Coordinator::new();
+    let mut recorders = Vec::new();
+    for _ in 0 .. participants {
+        let recorder = Rc::new(RefCell::new(Recorder { calls: Vec::new(), fail: false }));
+        coordinator.register(recorder.clone());
+        recorders.push(recorder);
+    }
+    (coordinator, recorders)
+}
+
+#[test]
+fn coordinator_commit() {
+
+    let (coordinator, recorders) = coordinate(2);
+
+    // Nothing is committed until all participants have prepared.
+    coordinator.prepared(0, &[2]);
+    assert_eq!(coordinator.committed().elements().to_vec(), vec![0]);
+    assert!(recorders[0].borrow().calls.is_empty());
+
+    // Participants commit through the least prepared frontier.
+    coordinator.prepared(1, &[1]);
+    assert_eq!(coordinator.committed().elements().to_vec(), vec![1]);
+    coordinator.prepared(1, &[3]);
+    assert_eq!(coordinator.committed().elements().to_vec(), vec![2]);
+
+    assert!(coordinator.failed().is_none());
+    for recorder in recorders.iter() {
+        assert_eq!(recorder.borrow().calls, vec!["commit [1]", "commit [2]"]);
+    }
+}
+
+#[test]
+fn coordinator_prepare_failure() {
+
+    let (coordinator, recorders) = coordinate(2);
+
+    // A failure to prepare aborts all participants.
+    coordinator.prepared(0, &[1]);
+    coordinator.failure(1, "disk full".to_owned());
+    assert_eq!(coordinator.failed(), Some("participant 1: disk full".to_owned()));
+    for recorder in recorders.iter() {
+        assert_eq!(recorder.borrow().calls, vec!["abort [0]"]);
+    }
+
+    // Nothing further is committed.
+    coordinator.prepared(1, &[1]);
+    assert_eq!(coordinator.committed().elements().to_vec(), vec![0]);
+    for recorder in recorders.iter() {
+        assert_eq!(recorder.borrow().calls, vec!["abort [0]"]);
+    }
+}
+
+#[test]
+fn coordinator_commit_failure() {
+
+    let (coordinator, recorders) = coordinate(3);
+
+    for index in 0 .. 3 {
+        coordinator.prepared(index, &[1]);
+    }
+    assert_eq!(coordinator.committed().elements().to_vec(), vec![1]);
+
+    // A failure to commit rolls back the participants that committed, and aborts the rest.
+    recorders[1].borrow_mut().fail = true;
+    for index in 0 .. 3 {
+        coordinator.prepared(index, &[2]);
+    }
+    assert_eq!(coordinator.failed(), Some("participant 1: commit failed".to_owned()));
+    assert_eq!(coordinator.committed().elements().to_vec(), vec![1]);
+    assert_eq!(recorders[0].borrow().calls, vec!["commit [1]", "commit [2]", "rollback [1]"]);
+    assert_eq!(recorders[1].borrow().calls, vec!["commit [1]", "abort [1]"]);
+    assert_eq!(recorders[2].borrow().calls, vec!["commit [1]", "abort [1]"]);
+}
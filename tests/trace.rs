@@ -88,3 +88,907 @@ fn test_advance() {
         cursor2.to_vec(&storage2),
         vec![((1.into(), 2), vec![(2, 1)]), ((2.into(), 3), vec![(2, 1), (2, -1)])]);
 }
+
+#[test]
+fn test_spill() {
+    use differential_dataflow::trace::implementations::spill::{OrdValSpill, SpillConfig};
+
+    type SpillTrace = OrdValSpill<u64, u64, usize, i64>;
+
+    // A budget of zero spills every batch to disk.
+    let config = SpillConfig { directory: ::std::env::temp_dir(), budget: 0 };
+    let mut trace = SpillTrace::with_config(config, OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            SpillTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 3), 2, -1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.distinguish_since(&[3]);
+
+    assert_eq!(trace.resident(), 0);
+    assert!(trace.spilled() > 0);
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![
+               ((1, 2), vec![(0, 1)]),
+               ((2, 3), vec![(1, 1), (2, -1)]),
+    ]);
+
+    // Warming spilled batches does not change the contents of the trace.
+    trace.warm(Some((&2, &2)));
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![
+               ((1, 2), vec![(0, 1)]),
+               ((2, 3), vec![(1, 1), (2, -1)]),
+    ]);
+    trace.cool();
+}
+
+#[test]
+fn test_spill_pages() {
+    use differential_dataflow::trace::implementations::spill::{OrdValSpill, SpillConfig};
+
+    type SpillTrace = OrdValSpill<u64, u64, usize, i64>;
+
+    let config = SpillConfig { directory: ::std::env::temp_dir(), budget: 0 };
+    let mut trace = SpillTrace::with_config(config, OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            SpillTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 3), 2, -1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.distinguish_since(&[3]);
+
+    // Without a page budget, cursors read spilled batches without retaining them.
+    trace.set_page_budget(0);
+    let (mut cursor, storage) = trace.cursor();
+    let contents = cursor.to_vec(&storage);
+    assert_eq!(trace.paged(), 0);
+
+    // With a page budget, spilled batches are retained once read, without changing the contents.
+    trace.set_page_budget(1 << 20);
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), contents);
+    assert!(trace.paged() > 0);
+    assert_eq!(trace.resident(), 0);
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), contents);
+
+    // Reducing the budget discards the pages.
+    trace.set_page_budget(0);
+    assert_eq!(trace.paged(), 0);
+}
+
+#[test]
+fn test_spill_pages_evict() {
+    use differential_dataflow::trace::Cursor;
+    use differential_dataflow::trace::implementations::spill::OrdValSpill;
+    use differential_dataflow::trace::implementations::store::MemoryStore;
+
+    type SpillTrace = OrdValSpill<u64, u64, usize, i64>;
+
+    let mut trace = SpillTrace::with_store(0, Box::new(MemoryStore::new()), OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            SpillTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        // A large batch of small keys, and a small batch of a large key, which are not merged.
+        batcher.push_batch(&mut vec![
+            ((1, 1), 0, 1),
+            ((2, 2), 0, 1),
+            ((3, 3), 0, 1),
+            ((4, 4), 0, 1),
+            ((5, 5), 0, 1),
+            ((10, 10), 1, 1),
+        ]);
+
+        for time in &[1, 2] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.distinguish_since(&[2]);
+    assert_eq!(trace.spilled(), 2);
+
+    // Pages are retained by default.
+    let (mut cursor, storage) = trace.cursor();
+    cursor.seek_key(&storage, &3);
+    let large = trace.paged();
+    assert!(large > 0);
+
+    // With room for only the large batch, reading the small batch discards the less recently read large batch.
+    trace.set_page_budget(large);
+    let (mut cursor, storage) = trace.cursor();
+    cursor.seek_key(&storage, &10);
+    assert_eq!(cursor.key(&storage), &10);
+    let small = trace.paged();
+    assert!(0 < small && small < large);
+
+    // Reading the large batch again discards the small batch.
+    let (mut cursor, storage) = trace.cursor();
+    cursor.seek_key(&storage, &3);
+    assert_eq!(trace.paged(), large);
+}
+
+#[test]
+fn test_spill_store() {
+    use differential_dataflow::trace::implementations::spill::OrdValSpill;
+    use differential_dataflow::trace::implementations::store::MemoryStore;
+
+    type SpillTrace = OrdValSpill<u64, u64, usize, i64>;
+
+    // Spill every batch to a store in memory, rather than to files.
+    let store = Box::new(MemoryStore::new());
+    let mut trace = SpillTrace::with_store(0, store, OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            SpillTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 3), 2, -1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.distinguish_since(&[3]);
+
+    assert_eq!(trace.resident(), 0);
+    assert!(trace.spilled() > 0);
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![
+               ((1, 2), vec![(0, 1)]),
+               ((2, 3), vec![(1, 1), (2, -1)]),
+    ]);
+}
+
+#[test]
+fn test_spill_lazy() {
+    use std::cell::Cell;
+    use differential_dataflow::trace::Cursor;
+    use differential_dataflow::trace::implementations::spill::OrdValSpill;
+    use differential_dataflow::trace::implementations::store::{BatchStore, MemoryStore};
+
+    /// A store counting its reads, which fail once `fail` is set.
+    struct Unreliable {
+        store: MemoryStore,
+        reads: Rc<Cell<usize>>,
+        fail: Rc<Cell<bool>>,
+    }
+    impl BatchStore for Unreliable {
+        fn put(&mut self, bytes: &[u8]) -> ::std::io::Result<u64> { self.store.put(bytes) }
+        fn get(&self, key: u64) -> ::std::io::Result<Vec<u8>> {
+            self.reads.set(self.reads.get() + 1);
+            if self.fail.get() { Err(::std::io::Error::new(::std::io::ErrorKind::Other, "store unavailable")) }
+            else { self.store.get(key) }
+        }
+        fn remove(&mut self, key: u64) -> ::std::io::Result<()> { self.store.remove(key) }
+    }
+
+    type SpillTrace = OrdValSpill<u64, u64, usize, i64>;
+
+    let reads = Rc::new(Cell::new(0));
+    let fail = Rc::new(Cell::new(false));
+    let store = Box::new(Unreliable { store: MemoryStore::new(), reads: reads.clone(), fail: fail.clone() });
+    let mut trace = SpillTrace::with_store(0, store, OperatorInfo::new(0, 0, &[]), None);
+    trace.set_page_budget(0);
+    {
+        let mut batcher = <<
+            SpillTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        // A large batch of small keys, and a small batch of a large key, which are not merged.
+        batcher.push_batch(&mut vec![
+            ((1, 1), 0, 1),
+            ((2, 2), 0, 1),
+            ((3, 3), 0, 1),
+            ((4, 4), 0, 1),
+            ((5, 5), 0, 1),
+            ((10, 10), 1, 1),
+        ]);
+
+        for time in &[1, 2] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.distinguish_since(&[2]);
+    assert_eq!(trace.spilled(), 2);
+
+    // Seeking a key reads only the batch whose range contains it.
+    reads.set(0);
+    let (mut cursor, storage) = trace.cursor();
+    cursor.seek_key(&storage, &3);
+    assert_eq!(cursor.key(&storage), &3);
+    assert_eq!(cursor.val(&storage), &3);
+    assert_eq!(reads.get(), 1);
+
+    // Batches that cannot be read are presented as empty, and the error is reported by the trace.
+    assert!(trace.error().is_none());
+    fail.set(true);
+    let (mut cursor, storage) = trace.cursor();
+    assert!(cursor.to_vec(&storage).is_empty());
+    assert!(trace.error().is_some());
+    assert!(trace.error().is_none());
+}
+
+#[test]
+fn test_columnar() {
+    use differential_dataflow::trace::implementations::columnar::ColValSpine;
+
+    type ColumnarTrace = ColValSpine<u64, u64, usize, i64>;
+
+    let mut trace = ColumnarTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            ColumnarTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 3), 2, -1),
+            ((2, 4), 2, 1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+
+    let (mut cursor, storage) = trace.cursor_through(&[2]).unwrap();
+    assert_eq!(cursor.to_vec(&storage), vec![
+               ((1, 2), vec![(0, 1)]),
+               ((2, 3), vec![(1, 1)]),
+    ]);
+
+    // Compaction preserves the accumulated contents of the trace.
+    trace.advance_by(&[2]);
+    trace.distinguish_since(&[3]);
+
+    let (mut cursor, storage) = trace.cursor_through(&[3]).unwrap();
+    let accumulated =
+    cursor
+        .to_vec(&storage)
+        .into_iter()
+        .map(|(record, updates)| (record, updates.into_iter().map(|(_, diff)| diff).sum::<i64>()))
+        .filter(|x| x.1 != 0)
+        .collect::<Vec<_>>();
+    assert_eq!(accumulated, vec![((1, 2), 1), ((2, 4), 1)]);
+}
+
+#[test]
+fn test_spill_compressed() {
+    use differential_dataflow::trace::implementations::spill::OrdValSpill;
+    use differential_dataflow::trace::implementations::store::{Codec, CompressedStore, MemoryStore};
+
+    /// A reversible transformation standing in for compression.
+    struct Invert;
+    impl Codec for Invert {
+        fn compress(&self, bytes: &[u8]) -> ::std::io::Result<Vec<u8>> { Ok(bytes.iter().map(|x| !x).collect()) }
+        fn decompress(&self, bytes: &[u8]) -> ::std::io::Result<Vec<u8>> { Ok(bytes.iter().map(|x| !x).collect()) }
+    }
+
+    type SpillTrace = OrdValSpill<u64, u64, usize, i64>;
+
+    // Store all but the newest batch compressed, with no memory budget.
+    let store = Box::new(CompressedStore::new(MemoryStore::new(), Invert));
+    let mut trace = SpillTrace::with_store(usize::max_value(), store, OperatorInfo::new(0, 0, &[]), None);
+    trace.set_spill_age(Some(1));
+    {
+        let mut batcher = <<
+            SpillTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 3), 2, -1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.distinguish_since(&[3]);
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![
+               ((1, 2), vec![(0, 1)]),
+               ((2, 3), vec![(1, 1), (2, -1)]),
+    ]);
+}
+
+#[test]
+fn test_checkpoint() {
+
+    type CheckpointTrace = OrdValSpine<u64, u64, usize, i64>;
+
+    let mut trace = CheckpointTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            CheckpointTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 3), 2, -1),
+            ((2, 4), 2, 1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.advance_by(&[2]);
+
+    let path = ::std::env::temp_dir().join(format!("differential-checkpoint-test-{}", ::std::process::id()));
+    trace.checkpoint(&path).unwrap();
+
+    // The restored trace holds the consolidated contents at the advance frontier.
+    let mut restored = CheckpointTrace::restore(&path, OperatorInfo::new(0, 0, &[]), None).unwrap();
+    ::std::fs::remove_file(&path).unwrap();
+
+    let (mut cursor, storage) = restored.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![
+               ((1, 2), vec![(2, 1)]),
+               ((2, 4), vec![(2, 1)]),
+    ]);
+    assert_eq!(restored.advance_frontier(), &[2]);
+}
+
+#[test]
+fn test_merge_policy() {
+    use differential_dataflow::trace::implementations::spine_fueled::MergePolicy;
+
+    type PolicyTrace = OrdValSpine<u64, u64, usize, i64>;
+
+    // Merge rarely, merge while idle, and complete merges once there are more than two batches.
+    let policy = MergePolicy { effort: 1, fanout: 8, idle_effort: 1, max_batches: Some(2) };
+    let mut trace = PolicyTrace::with_policy(policy, OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            PolicyTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        for time in 0 .. 10 {
+            batcher.push_batch(&mut vec![((time as u64, time as u64), time, 1)]);
+            trace.insert(batcher.seal(&[time + 1]));
+            trace.distinguish_since(&[time + 1]);
+        }
+    }
+
+    let (mut cursor, storage) = trace.cursor();
+    let expected = (0 .. 10).map(|x| ((x as u64, x as u64), vec![(x, 1)])).collect::<Vec<_>>();
+    assert_eq!(cursor.to_vec(&storage), expected);
+}
+
+#[test]
+fn test_memory_budget() {
+    use differential_dataflow::trace::implementations::spine_fueled::{MemoryBudget, Eviction};
+
+    type BudgetTrace = OrdValSpine<u64, u64, usize, i64>;
+
+    // A budget of four updates, at which point the spine reports the frontier it would need to forget its history.
+    let mut trace = BudgetTrace::with_effort(1, OperatorInfo::new(0, 0, &[]), None);
+    let bytes = 4 * ::std::mem::size_of::<(u64, u64, usize, i64)>();
+    trace.set_memory_budget(Some(MemoryBudget { bytes, eviction: Eviction::Report }));
+    {
+        let mut batcher = <<
+            BudgetTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        for time in 0 .. 5 {
+            batcher.push_batch(&mut vec![((0, 0), time, 1)]);
+            trace.distinguish_since(&[time + 1]);
+            trace.insert(batcher.seal(&[time + 1]));
+        }
+    }
+
+    // The spine does not advance its times past the frontier its readers allow.
+    assert_eq!(trace.advance_frontier(), &[0]);
+    let frontier = trace.eviction_frontier().expect("eviction frontier").to_vec();
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage).len(), 1);
+    assert_eq!(cursor.to_vec(&storage)[0].1.iter().map(|x| x.1).sum::<i64>(), 5);
+
+    // Once readers allow it, the spine discards its history.
+    trace.advance_by(&frontier[..]);
+    assert!(trace.eviction_frontier().is_none());
+    trace.compact();
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![((0, 0), vec![(5, 5)])]);
+}
+
+#[test]
+fn test_push_sorted() {
+    use differential_dataflow::trace::{BatchReader, Builder};
+
+    type SortedTrace = OrdValSpine<u64, u64, usize, i64>;
+    type SortedBatch = <SortedTrace as TraceReader<u64, u64, usize, i64>>::Batch;
+
+    let sorted = vec![((0, 0), 0, 1), ((0, 0), 0, 1), ((0, 1), 1, 1), ((1, 0), 0, 1), ((1, 0), 0, -1)];
+    let expected = vec![((0, 0), vec![(0, 2)]), ((0, 1), vec![(1, 1)])];
+
+    let mut builder = <SortedBatch as Batch<u64, u64, usize, i64>>::Builder::new();
+    builder.push_sorted(&mut sorted.clone());
+    let batch = builder.done(&[0], &[2], &[0]);
+    assert_eq!(batch.cursor().to_vec(&batch), expected);
+
+    let mut batcher = <SortedBatch as Batch<u64, u64, usize, i64>>::Batcher::new();
+    batcher.push_sorted(&mut sorted.clone());
+    let batch = batcher.seal(&[2]);
+    assert_eq!(batch.cursor().to_vec(&batch), expected);
+}
+
+#[test]
+fn test_retention() {
+    type RetainedTrace = OrdValSpine<u64, u64, usize, i64>;
+
+    // Retain updates from the two most recent times.
+    let mut trace = RetainedTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    trace.set_retention(|time: &usize| time.saturating_sub(2));
+    {
+        let mut batcher = <<
+            RetainedTrace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<
+            u64, u64, usize, i64>>::Batcher::new();
+
+        for time in 0 .. 5 {
+            batcher.push_batch(&mut vec![((time as u64, 0), time, 1)]);
+            trace.distinguish_since(&[time + 1]);
+            trace.insert(batcher.seal(&[time + 1]));
+        }
+    }
+
+    assert_eq!(trace.retention_frontier(), &[3]);
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![((3, 0), vec![(3, 1)]), ((4, 0), vec![(4, 1)])]);
+}
+
+#[test]
+fn test_validate() {
+    use differential_dataflow::trace::{BatchReader, Builder};
+    use differential_dataflow::trace::validate::{validate, validate_batch, Violation};
+
+    let mut trace = get_trace();
+    assert_eq!(validate(&mut trace), vec![]);
+
+    // A batch built from updates out of order, and with a zero difference.
+    type ValidBatch = <OrdValSpine<u64, u64, usize, i64> as TraceReader<u64, u64, usize, i64>>::Batch;
+    let mut builder = <ValidBatch as Batch<u64, u64, usize, i64>>::Builder::new();
+    builder.push((1, 0, 1, 1));
+    builder.push((0, 0, 1, 0));
+    let batch = builder.done(&[1], &[2], &[1]);
+    assert_eq!(batch.len(), 2);
+    assert_eq!(validate_batch(&batch, 0), vec![
+        Violation::KeyOrder { batch: 0, key: 0 },
+        Violation::ZeroDiff { batch: 0, key: 0, val: 0, time: 1 },
+    ]);
+}
+
+#[test]
+fn test_statistics() {
+    let mut trace = get_trace();
+    trace.advance_by(&[1]);
+
+    let statistics = trace.statistics();
+    assert_eq!(statistics.updates, 3);
+    assert!(statistics.batches > 0);
+    assert!(statistics.keys >= 2);
+    assert!(statistics.bytes > 0);
+    assert_eq!(statistics.advance_frontier, vec![1]);
+}
+
+#[test]
+fn test_cursor_range() {
+    use differential_dataflow::trace::Cursor;
+
+    type RangeTrace = OrdValSpine<(u64, u64), u64, usize, i64>;
+
+    let mut trace = RangeTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            RangeTrace as TraceReader<(u64, u64), u64, usize, i64>>::Batch as Batch<
+            (u64, u64), u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            (((0, 0), 0), 0, 1),
+            (((1, 0), 1), 0, 1),
+            (((1, 1), 2), 1, 1),
+            (((1, 1), 3), 2, 1),
+            (((2, 0), 4), 2, 1),
+        ]);
+
+        trace.insert(batcher.seal(&[3]));
+    }
+
+    let (mut cursor, storage) = trace.cursor();
+
+    let range = cursor.seek_key_range(&storage, &(1, 0), (2, 0)).map(|(k, v, u)| (*k, *v, u)).collect::<Vec<_>>();
+    assert_eq!(range, vec![
+        ((1, 0), 1, vec![(0, 1)]),
+        ((1, 1), 2, vec![(1, 1)]),
+        ((1, 1), 3, vec![(2, 1)]),
+    ]);
+
+    // Scans rewind the cursor, and so may revisit earlier keys.
+    let prefix = cursor.seek_key_prefix(&storage, &(0, 0), |k| k.0 == 0).map(|(k, v, _)| (*k, *v)).collect::<Vec<_>>();
+    assert_eq!(prefix, vec![((0, 0), 0)]);
+}
+
+#[test]
+fn test_append_only() {
+    use differential_dataflow::trace::implementations::append::AppendSpine;
+
+    type AppendTrace = AppendSpine<u64, u64, usize>;
+
+    let mut trace = AppendTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            AppendTrace as TraceReader<u64, u64, usize, isize>>::Batch as Batch<
+            u64, u64, usize, isize>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 2),
+            ((2, 4), 2, 1),
+        ]);
+
+        for time in &[1, 2, 3] {
+            trace.insert(batcher.seal(&[*time]));
+        }
+    }
+    trace.advance_by(&[3]);
+    trace.distinguish_since(&[3]);
+
+    // Updates with differences greater than one are reported with their counts.
+    let (mut cursor, storage) = trace.cursor();
+    let accumulated =
+    cursor
+        .to_vec(&storage)
+        .into_iter()
+        .map(|(record, updates)| (record, updates.into_iter().map(|(_, diff)| diff).sum::<isize>()))
+        .collect::<Vec<_>>();
+    assert_eq!(accumulated, vec![((1, 2), 1), ((2, 3), 2), ((2, 4), 1)]);
+}
+
+#[test]
+fn test_append_only_merge() {
+    use differential_dataflow::trace::{BatchReader, Builder, Merger};
+    use differential_dataflow::trace::implementations::append::{AppendBatch, AppendBuilder};
+
+    let mut builder1 = AppendBuilder::<u64, u64, usize>::new();
+    builder1.push((1, 2, 0, 1));
+    builder1.push((1, 2, 1, 2));
+    let batch1: AppendBatch<u64, u64, usize> = builder1.done(&[0], &[2], &[0]);
+
+    let mut builder2 = AppendBuilder::<u64, u64, usize>::new();
+    builder2.push((1, 2, 2, 1));
+    builder2.push((1, 3, 3, 1));
+    let batch2: AppendBatch<u64, u64, usize> = builder2.done(&[2], &[4], &[0]);
+
+    // Times of a value that are equal once advanced are consolidated as the batches are merged.
+    let mut merger = batch1.begin_merge(&batch2);
+    merger.work(&batch1, &batch2, &Some(vec![3]), &mut usize::max_value());
+    let merged = merger.done();
+
+    assert_eq!(merged.times, vec![(3, 4), (3, 1)]);
+    assert_eq!(merged.len(), 2);
+}
+
+#[test]
+fn test_dense_keys() {
+    use differential_dataflow::trace::Cursor;
+    use differential_dataflow::trace::implementations::dense::DenseValSpine;
+
+    type DenseTrace = DenseValSpine<u32, u32, usize, isize>;
+
+    let mut trace = DenseTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            DenseTrace as TraceReader<u32, u32, usize, isize>>::Batch as Batch<
+            u32, u32, usize, isize>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((4, 3), 0, 1),
+            ((4, 5), 1, 1),
+            ((9, 0), 1, 1),
+        ]);
+
+        trace.insert(batcher.seal(&[1]));
+        trace.insert(batcher.seal(&[2]));
+    }
+
+    // Seeking an absent key positions the cursor at the next present key.
+    let (mut cursor, storage) = trace.cursor();
+    cursor.seek_key(&storage, &2);
+    assert_eq!(cursor.get_key(&storage), Some(&4));
+    cursor.seek_key(&storage, &4);
+    assert_eq!(cursor.get_key(&storage), Some(&4));
+    cursor.seek_key(&storage, &10);
+    assert_eq!(cursor.get_key(&storage), None);
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage), vec![
+        ((1, 2), vec![(0, 1)]),
+        ((4, 3), vec![(0, 1)]),
+        ((4, 5), vec![(1, 1)]),
+        ((9, 0), vec![(1, 1)]),
+    ]);
+}
+
+#[test]
+fn test_dense_sparse_keys() {
+    use differential_dataflow::trace::Cursor;
+    use differential_dataflow::trace::implementations::dense::DenseValSpine;
+
+    type DenseTrace = DenseValSpine<u32, u32, usize, isize>;
+
+    let mut trace = DenseTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            DenseTrace as TraceReader<u32, u32, usize, isize>>::Batch as Batch<
+            u32, u32, usize, isize>>::Batcher::new();
+
+        // Large keys close together are indexed from the least of them.
+        batcher.push_batch(&mut vec![((10_000_000, 0), 0, 1), ((10_000_003, 0), 0, 1)]);
+        let batch = batcher.seal(&[1]);
+        assert_eq!(batch.offset, 10_000_000);
+        assert_eq!(batch.index.len(), 5);
+        trace.insert(batch);
+
+        // Keys far apart are not indexed.
+        batcher.push_batch(&mut vec![((0, 1), 1, 1), ((20_000_000, 1), 1, 1)]);
+        let batch = batcher.seal(&[2]);
+        assert!(batch.index.is_empty());
+        trace.insert(batch);
+    }
+
+    let (mut cursor, storage) = trace.cursor();
+    cursor.seek_key(&storage, &1);
+    assert_eq!(cursor.get_key(&storage), Some(&10_000_000));
+    cursor.seek_key(&storage, &10_000_001);
+    assert_eq!(cursor.get_key(&storage), Some(&10_000_003));
+    cursor.seek_key(&storage, &10_000_004);
+    assert_eq!(cursor.get_key(&storage), Some(&20_000_000));
+    cursor.seek_key(&storage, &20_000_001);
+    assert_eq!(cursor.get_key(&storage), None);
+}
+
+#[test]
+fn test_bloom_probe() {
+    use differential_dataflow::trace::Cursor;
+    use differential_dataflow::trace::implementations::bloom::{BloomFilter, BloomValSpine};
+
+    // Filters contain each of their keys.
+    let hashes = (0 .. 1000u64).map(|x| x.wrapping_mul(0x9e3779b97f4a7c15)).collect::<Vec<_>>();
+    let filter = BloomFilter::new(&hashes[..]);
+    assert!(hashes.iter().all(|&hash| filter.may_contain(hash)));
+
+    type BloomTrace = BloomValSpine<u64, u64, usize, isize>;
+
+    let mut trace = BloomTrace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<
+            BloomTrace as TraceReader<u64, u64, usize, isize>>::Batch as Batch<
+            u64, u64, usize, isize>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![((1, 2), 0, 1), ((5, 3), 0, 1)]);
+        trace.insert(batcher.seal(&[1]));
+        batcher.push_batch(&mut vec![((3, 4), 1, 1), ((5, 6), 1, 1)]);
+        trace.insert(batcher.seal(&[2]));
+    }
+
+    let (mut cursor, storage) = trace.cursor();
+    assert!(cursor.probe_key(&storage, &1));
+    assert_eq!(cursor.get_val(&storage), Some(&2));
+    assert!(!cursor.probe_key(&storage, &2));
+    assert!(cursor.probe_key(&storage, &5));
+    assert_eq!(cursor.get_val(&storage), Some(&3));
+    cursor.step_val(&storage);
+    assert_eq!(cursor.get_val(&storage), Some(&6));
+    assert!(!cursor.probe_key(&storage, &7));
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage).len(), 4);
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn test_batch_encoding() {
+    use differential_dataflow::trace::encoding::{encode, decode};
+    use differential_dataflow::trace::implementations::columnar::ColValBatch;
+
+    type Source = OrdValBatch<u64, u64, usize, i64>;
+    type Target = ColValBatch<u64, u64, usize, i64>;
+
+    let mut batcher = <Source as Batch<u64, u64, usize, i64>>::Batcher::new();
+    batcher.push_batch(&mut vec![
+        ((1, 2), 0, 1),
+        ((2, 3), 1, 1),
+        ((2, 3), 2, -1),
+    ]);
+    let batch = batcher.seal(&[3]);
+
+    let mut bytes = Vec::new();
+    encode(&batch, &mut bytes).unwrap();
+
+    // batches may be decoded as other batch implementations.
+    let decoded: Target = decode(&bytes[..]).unwrap();
+    assert_eq!(decoded.keys, vec![1, 2]);
+    assert_eq!(decoded.times, vec![0, 1, 2]);
+    assert_eq!(decoded.diffs, vec![1, 1, -1]);
+
+    // corrupted headers are rejected.
+    bytes[0] = 0;
+    assert!(decode::<_, _, _, _, Target, _>(&bytes[..]).is_err());
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_arrow_export() {
+    use differential_dataflow::trace::export::export;
+    use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+    type Trace = OrdValSpine<u64, u64, usize, i64>;
+
+    let mut trace = Trace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<Trace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<u64, u64, usize, i64>>::Batcher::new();
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((2, 4), 2, 1),
+            ((2, 4), 3, -1),
+        ]);
+        trace.insert(batcher.seal(&[4]));
+    }
+
+    // the cancelling updates to (2, 4) are consolidated at the frontier.
+    let batches = export(&mut trace, &[4], 1).unwrap();
+    assert_eq!(batches.len(), 2);
+    assert!(batches.iter().all(|batch| batch.num_columns() == 4 && batch.num_rows() == 1));
+
+    let batches = export(&mut trace, &[0], 10).unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].num_rows(), 4);
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_arrow_export_keys() {
+    use differential_dataflow::trace::export::export;
+    use differential_dataflow::trace::implementations::ord::OrdKeySpine;
+
+    type Trace = OrdKeySpine<u64, usize, i64>;
+
+    let mut trace = Trace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<Trace as TraceReader<u64, (), usize, i64>>::Batch as Batch<u64, (), usize, i64>>::Batcher::new();
+        batcher.push_batch(&mut vec![((1, ()), 0, 1), ((2, ()), 1, 1)]);
+        trace.insert(batcher.seal(&[2]));
+    }
+
+    // the unit values of a trace of keys are exported as a column.
+    let batches = export(&mut trace, &[2], 10).unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].num_columns(), 4);
+    assert_eq!(batches[0].num_rows(), 2);
+}
+
+#[test]
+fn test_snapshot() {
+    use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+    type Trace = OrdValSpine<u64, u64, usize, i64>;
+
+    let mut trace = Trace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<Trace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<u64, u64, usize, i64>>::Batcher::new();
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 2),
+            ((2, 3), 2, -2),
+            ((3, 4), 3, 1),
+        ]);
+        for time in 1 .. 5 {
+            trace.insert(batcher.seal(&[time]));
+        }
+    }
+
+    assert_eq!(trace.snapshot(&[2]).unwrap().collect::<Vec<_>>(), vec![(1, 2, 1), (2, 3, 2)]);
+    assert_eq!(trace.snapshot(&[]).unwrap().collect::<Vec<_>>(), vec![(1, 2, 1), (3, 4, 1)]);
+
+    // once compacted beyond a frontier, snapshots at it are unavailable.
+    trace.advance_by(&[3]);
+    trace.distinguish_since(&[3]);
+    assert!(trace.snapshot(&[2]).is_none());
+    assert_eq!(trace.snapshot(&[4]).unwrap().collect::<Vec<_>>(), vec![(1, 2, 1), (3, 4, 1)]);
+}
+
+#[test]
+fn test_snapshot_at_compacted_frontier() {
+    use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+    type Trace = OrdValSpine<u64, u64, usize, i64>;
+
+    let mut trace = Trace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<Trace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<u64, u64, usize, i64>>::Batcher::new();
+        batcher.push_batch(&mut vec![
+            ((1, 2), 0, 1),
+            ((2, 3), 1, 1),
+            ((3, 4), 3, 1),
+        ]);
+        for time in 1 .. 5 {
+            trace.insert(batcher.seal(&[time]));
+        }
+    }
+
+    // updates before the frontier are advanced to it, and so are no longer before it.
+    trace.advance_by(&[3]);
+    trace.distinguish_since(&[3]);
+    assert!(trace.snapshot(&[3]).is_none());
+    assert_eq!(trace.snapshot(&[4]).unwrap().collect::<Vec<_>>(), vec![(1, 2, 1), (2, 3, 1), (3, 4, 1)]);
+}
+
+#[test]
+fn test_background_merges() {
+    use differential_dataflow::trace::implementations::background::{BackgroundValSpine, set_background_threshold};
+
+    type Trace = BackgroundValSpine<u64, u64, usize, i64>;
+
+    // offload every merge, on this thread.
+    set_background_threshold(Some(0));
+
+    let mut trace = Trace::new(OperatorInfo::new(0, 0, &[]), None);
+    {
+        let mut batcher = <<Trace as TraceReader<u64, u64, usize, i64>>::Batch as Batch<u64, u64, usize, i64>>::Batcher::new();
+        for time in 0 .. 16 {
+            batcher.push_batch(&mut vec![((time as u64 % 4, time as u64), time, 1)]);
+            trace.insert(batcher.seal(&[time + 1]));
+            trace.advance_by(&[time + 1]);
+            trace.distinguish_since(&[time + 1]);
+        }
+    }
+
+    // merges in progress are awaited when completed.
+    trace.compact();
+    set_background_threshold(None);
+
+    let mut batches = 0;
+    trace.map_batches(|_| batches += 1);
+    assert_eq!(batches, 1);
+
+    let (mut cursor, storage) = trace.cursor();
+    let contents = cursor.to_vec(&storage);
+    assert_eq!(contents.len(), 16);
+    assert!(contents.iter().all(|&(_, ref updates)| updates == &vec![(16, 1)]));
+}
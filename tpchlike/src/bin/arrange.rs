@@ -3,13 +3,12 @@ extern crate differential_dataflow;
 extern crate core_affinity;
 extern crate tpchlike;
 
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::time::Instant;
 
 use timely::dataflow::operators::*;
 use differential_dataflow::AsCollection;
-use tpchlike::{Collections, Arrangements, types::*, queries};
+use tpchlike::generate::generate_or_load;
+use tpchlike::{Collections, Arrangements, types::*, queries, queries_delta};
 
 fn main() {
 
@@ -27,6 +26,7 @@ fn main() {
         let physical_batch = ::std::env::args().nth(3).unwrap().parse::<usize>().unwrap();
         let query: usize = ::std::env::args().nth(4).unwrap().parse().unwrap();
         let seal: bool = ::std::env::args().any(|x| x == "seal-inputs");
+        let delta: bool = ::std::env::args().any(|x| x == "delta");
 
         let (mut inputs, probe, used, mut traces) = worker.dataflow::<usize,_,_>(move |scope| {
 
@@ -57,28 +57,57 @@ fn main() {
 
             let mut arrangements = Arrangements::new(&mut collections, &mut probe);
 
-            queries::query01::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query02::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            queries::query03::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query04::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query05::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query06::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query07::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query08::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query09::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query10::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query11::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query12::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query13::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query14::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query15::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query16::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query17::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query18::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query19::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query20::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query21::query_arranged(&mut collections, &mut arrangements, &mut probe);
-            // queries::query22::query_arranged(&mut collections, &mut arrangements, &mut probe);
+            if delta {
+                match query {
+                    1  => queries_delta::query01::query(&mut collections, &mut arrangements, &mut probe),
+                    2  => queries_delta::query02::query(&mut collections, &mut arrangements, &mut probe),
+                    3  => queries_delta::query03::query(&mut collections, &mut arrangements, &mut probe),
+                    4  => queries_delta::query04::query(&mut collections, &mut arrangements, &mut probe),
+                    5  => queries_delta::query05::query(&mut collections, &mut arrangements, &mut probe),
+                    6  => queries_delta::query06::query(&mut collections, &mut arrangements, &mut probe),
+                    7  => queries_delta::query07::query(&mut collections, &mut arrangements, &mut probe),
+                    8  => queries_delta::query08::query(&mut collections, &mut arrangements, &mut probe),
+                    9  => queries_delta::query09::query(&mut collections, &mut arrangements, &mut probe),
+                    10 => queries_delta::query10::query(&mut collections, &mut arrangements, &mut probe),
+                    11 => queries_delta::query11::query(&mut collections, &mut arrangements, &mut probe),
+                    12 => queries_delta::query12::query(&mut collections, &mut arrangements, &mut probe),
+                    13 => queries_delta::query13::query(&mut collections, &mut arrangements, &mut probe),
+                    14 => queries_delta::query14::query(&mut collections, &mut arrangements, &mut probe),
+                    15 => queries_delta::query15::query(&mut collections, &mut arrangements, &mut probe),
+                    16 => queries_delta::query16::query(&mut collections, &mut arrangements, &mut probe),
+                    17 => queries_delta::query17::query(&mut collections, &mut arrangements, &mut probe),
+                    18 => queries_delta::query18::query(&mut collections, &mut arrangements, &mut probe),
+                    19 => queries_delta::query19::query(&mut collections, &mut arrangements, &mut probe),
+                    20 => queries_delta::query20::query(&mut collections, &mut arrangements, &mut probe),
+                    21 => queries_delta::query21::query(&mut collections, &mut arrangements, &mut probe),
+                    22 => queries_delta::query22::query(&mut collections, &mut arrangements, &mut probe),
+                    _ => panic!("query: {:?} unimplemented", query),
+                }
+            }
+            else {
+                queries::query01::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query02::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                queries::query03::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query04::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query05::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query06::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query07::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query08::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query09::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query10::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query11::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query12::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query13::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query14::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query15::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query16::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query17::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query18::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query19::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query20::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query21::query_arranged(&mut collections, &mut arrangements, &mut probe);
+                // queries::query22::query_arranged(&mut collections, &mut arrangements, &mut probe);
+            }
 
             // return the various input handles, and the list of probes.
             let inputs = (
@@ -96,14 +125,14 @@ fn main() {
         });
 
         // customer.tbl lineitem.tbl    nation.tbl  orders.tbl  part.tbl    partsupp.tbl    region.tbl  supplier.tbl
-        let mut customers = if used[0] { load::<Customer>(prefix.as_str(), "customer.tbl", index, peers, logical_batch, physical_batch, 0) } else { Vec::new() };
-        let mut lineitems = if used[1] { load::<LineItem>(prefix.as_str(), "lineitem.tbl", index, peers, logical_batch, physical_batch, 1) } else { Vec::new() };
-        let mut nations = if used[2] { load::<Nation>(prefix.as_str(), "nation.tbl", index, peers, logical_batch, physical_batch, 2) } else { Vec::new() };
-        let mut orders = if used[3] { load::<Order>(prefix.as_str(), "orders.tbl", index, peers, logical_batch, physical_batch, 3) } else { Vec::new() };
-        let mut parts = if used[4] { load::<Part>(prefix.as_str(), "part.tbl", index, peers, logical_batch, physical_batch, 4) } else { Vec::new() };
-        let mut partsupps = if used[5] { load::<PartSupp>(prefix.as_str(), "partsupp.tbl", index, peers, logical_batch, physical_batch, 5) } else { Vec::new() };
-        let mut regions = if used[6] { load::<Region>(prefix.as_str(), "region.tbl", index, peers, logical_batch, physical_batch, 6) } else { Vec::new() };
-        let mut suppliers = if used[7] { load::<Supplier>(prefix.as_str(), "supplier.tbl", index, peers, logical_batch, physical_batch, 7) } else { Vec::new() };
+        let mut customers = if used[0] { generate_or_load::<Customer>(prefix.as_str(), "customer.tbl", index, peers, logical_batch, physical_batch, 0) } else { Vec::new() };
+        let mut lineitems = if used[1] { generate_or_load::<LineItem>(prefix.as_str(), "lineitem.tbl", index, peers, logical_batch, physical_batch, 1) } else { Vec::new() };
+        let mut nations = if used[2] { generate_or_load::<Nation>(prefix.as_str(), "nation.tbl", index, peers, logical_batch, physical_batch, 2) } else { Vec::new() };
+        let mut orders = if used[3] { generate_or_load::<Order>(prefix.as_str(), "orders.tbl", index, peers, logical_batch, physical_batch, 3) } else { Vec::new() };
+        let mut parts = if used[4] { generate_or_load::<Part>(prefix.as_str(), "part.tbl", index, peers, logical_batch, physical_batch, 4) } else { Vec::new() };
+        let mut partsupps = if used[5] { generate_or_load::<PartSupp>(prefix.as_str(), "partsupp.tbl", index, peers, logical_batch, physical_batch, 5) } else { Vec::new() };
+        let mut regions = if used[6] { generate_or_load::<Region>(prefix.as_str(), "region.tbl", index, peers, logical_batch, physical_batch, 6) } else { Vec::new() };
+        let mut suppliers = if used[7] { generate_or_load::<Supplier>(prefix.as_str(), "supplier.tbl", index, peers, logical_batch, physical_batch, 7) } else { Vec::new() };
 
         println!("{:?}\tInput loaded", timer.elapsed());
 
@@ -192,49 +221,3 @@ fn main() {
 
     }).unwrap();
 }
-
-// Returns a sequence of physical batches of ready-to-go timestamped data. Not clear that `input` can exploit the pre-arrangement yet.
-fn load<T>(prefix: &str, name: &str, index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize)
-    -> Vec<Vec<(T, usize, isize)>>
-where T: for<'a> From<&'a str> {
-
-    let mut result = Vec::new();
-    let mut buffer = Vec::new();
-
-    let path = format!("{}{}", prefix, name);
-
-    let items_file = File::open(&path).expect("didn't find items file");
-    let mut items_reader =  BufReader::new(items_file);
-    let mut count = 0;
-
-    let mut line = String::new();
-
-    while items_reader.read_line(&mut line).unwrap() > 0 {
-
-        if count % peers == index {
-
-            let logical = (8 * count / logical_batch) + off;
-            let physical = logical / physical_batch;
-            let round = physical / 8;
-
-            while result.len() < round {
-                result.push(buffer);
-                buffer = Vec::with_capacity(2 + logical_batch * physical_batch / peers);
-            }
-
-            let item = T::from(line.as_str());
-            buffer.push((item, logical + 1, 1));
-        }
-
-        count += 1;
-
-        line.clear();
-    }
-
-    if buffer.len() > 0 {
-        result.push(buffer);
-    }
-
-    result.reverse();
-    result
-}
\ No newline at end of file
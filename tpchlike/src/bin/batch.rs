@@ -3,14 +3,13 @@ extern crate differential_dataflow;
 extern crate core_affinity;
 extern crate tpchlike;
 
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::time::Instant;
 
 use timely::dataflow::operators::*;
 
 use differential_dataflow::AsCollection;
 
+use tpchlike::generate::generate_or_load_batch;
 use tpchlike::{Collections, types::*, queries};
 
 fn main() {
@@ -82,14 +81,14 @@ fn main() {
         });
 
         // customer.tbl lineitem.tbl    nation.tbl  orders.tbl  part.tbl    partsupp.tbl    region.tbl  supplier.tbl
-        let mut customers = if used[0] { load::<Customer>(prefix.as_str(), "customer.tbl", index, peers) } else { Vec::new() };
-        let mut lineitems = if used[1] { load::<LineItem>(prefix.as_str(), "lineitem.tbl", index, peers) } else { Vec::new() };
-        let mut nations = if used[2] { load::<Nation>(prefix.as_str(), "nation.tbl", index, peers) } else { Vec::new() };
-        let mut orders = if used[3] { load::<Order>(prefix.as_str(), "orders.tbl", index, peers) } else { Vec::new() };
-        let mut parts = if used[4] { load::<Part>(prefix.as_str(), "part.tbl", index, peers) } else { Vec::new() };
-        let mut partsupps = if used[5] { load::<PartSupp>(prefix.as_str(), "partsupp.tbl", index, peers) } else { Vec::new() };
-        let mut regions = if used[6] { load::<Region>(prefix.as_str(), "region.tbl", index, peers) } else { Vec::new() };
-        let mut suppliers = if used[7] { load::<Supplier>(prefix.as_str(), "supplier.tbl", index, peers) } else { Vec::new() };
+        let mut customers = if used[0] { generate_or_load_batch::<Customer>(prefix.as_str(), "customer.tbl", index, peers) } else { Vec::new() };
+        let mut lineitems = if used[1] { generate_or_load_batch::<LineItem>(prefix.as_str(), "lineitem.tbl", index, peers) } else { Vec::new() };
+        let mut nations = if used[2] { generate_or_load_batch::<Nation>(prefix.as_str(), "nation.tbl", index, peers) } else { Vec::new() };
+        let mut orders = if used[3] { generate_or_load_batch::<Order>(prefix.as_str(), "orders.tbl", index, peers) } else { Vec::new() };
+        let mut parts = if used[4] { generate_or_load_batch::<Part>(prefix.as_str(), "part.tbl", index, peers) } else { Vec::new() };
+        let mut partsupps = if used[5] { generate_or_load_batch::<PartSupp>(prefix.as_str(), "partsupp.tbl", index, peers) } else { Vec::new() };
+        let mut regions = if used[6] { generate_or_load_batch::<Region>(prefix.as_str(), "region.tbl", index, peers) } else { Vec::new() };
+        let mut suppliers = if used[7] { generate_or_load_batch::<Supplier>(prefix.as_str(), "supplier.tbl", index, peers) } else { Vec::new() };
 
         let mut tuples = 0usize;
         tuples += customers.iter().map(|x| x.len()).sum::<usize>();
@@ -133,33 +132,3 @@ fn main() {
 
     }).unwrap();
 }
-
-// Returns a sequence of physical batches of ready-to-go timestamped data. Not clear that `input` can exploit the pre-arrangement yet.
-fn load<T>(prefix: &str, name: &str, index: usize, peers: usize)
-    -> Vec<Vec<(T, (), isize)>>
-where T: for<'a> From<&'a str> {
-
-    let mut buffer = Vec::new();
-
-    let path = format!("{}{}", prefix, name);
-
-    let items_file = File::open(&path).expect("didn't find items file");
-    let mut items_reader =  BufReader::new(items_file);
-    let mut count = 0;
-
-    let mut line = String::new();
-
-    while items_reader.read_line(&mut line).unwrap() > 0 {
-
-        if count % peers == index {
-            let item = T::from(line.as_str());
-            buffer.push((item, (), 1));
-        }
-
-        count += 1;
-
-        line.clear();
-    }
-
-    vec![buffer]
-}
@@ -3,14 +3,14 @@ extern crate differential_dataflow;
 extern crate core_affinity;
 extern crate tpchlike;
 
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use timely::dataflow::operators::*;
 
 use differential_dataflow::AsCollection;
 
+use tpchlike::generate::{generate_or_load, generate_or_load_refresh};
+use tpchlike::refresh::Pacing;
 use tpchlike::{Collections, types::*, queries};
 
 fn main() {
@@ -28,6 +28,10 @@ fn main() {
         let physical_batch = ::std::env::args().nth(3).unwrap().parse::<usize>().unwrap();
         let query: usize = ::std::env::args().nth(4).unwrap().parse().unwrap();
         let seal: bool = ::std::env::args().any(|x| x == "seal-inputs");
+        // Apply `refresh=<sets>` pairs of refresh functions after the load, `refresh-interval=<millis>` apart.
+        let argument = |name: &str| ::std::env::args().find(|x| x.starts_with(name)).map(|x| x[name.len()..].parse::<u64>().unwrap());
+        let sets = argument("refresh=").unwrap_or(0) as usize;
+        let interval = Duration::from_millis(argument("refresh-interval=").unwrap_or(0));
 
         let (mut inputs, probe, used) = worker.dataflow::<usize,_,_>(move |scope| {
 
@@ -85,14 +89,17 @@ fn main() {
         });
 
         // customer.tbl lineitem.tbl    nation.tbl  orders.tbl  part.tbl    partsupp.tbl    region.tbl  supplier.tbl
-        let mut customers = if used[0] { load::<Customer>(prefix.as_str(), "customer.tbl", index, peers, logical_batch, physical_batch, 0) } else { Vec::new() };
-        let mut lineitems = if used[1] { load::<LineItem>(prefix.as_str(), "lineitem.tbl", index, peers, logical_batch, physical_batch, 1) } else { Vec::new() };
-        let mut nations = if used[2] { load::<Nation>(prefix.as_str(), "nation.tbl", index, peers, logical_batch, physical_batch, 2) } else { Vec::new() };
-        let mut orders = if used[3] { load::<Order>(prefix.as_str(), "orders.tbl", index, peers, logical_batch, physical_batch, 3) } else { Vec::new() };
-        let mut parts = if used[4] { load::<Part>(prefix.as_str(), "part.tbl", index, peers, logical_batch, physical_batch, 4) } else { Vec::new() };
-        let mut partsupps = if used[5] { load::<PartSupp>(prefix.as_str(), "partsupp.tbl", index, peers, logical_batch, physical_batch, 5) } else { Vec::new() };
-        let mut regions = if used[6] { load::<Region>(prefix.as_str(), "region.tbl", index, peers, logical_batch, physical_batch, 6) } else { Vec::new() };
-        let mut suppliers = if used[7] { load::<Supplier>(prefix.as_str(), "supplier.tbl", index, peers, logical_batch, physical_batch, 7) } else { Vec::new() };
+        let mut customers = if used[0] { generate_or_load::<Customer>(prefix.as_str(), "customer.tbl", index, peers, logical_batch, physical_batch, 0) } else { Vec::new() };
+        let mut lineitems = if used[1] { generate_or_load::<LineItem>(prefix.as_str(), "lineitem.tbl", index, peers, logical_batch, physical_batch, 1) } else { Vec::new() };
+        let mut nations = if used[2] { generate_or_load::<Nation>(prefix.as_str(), "nation.tbl", index, peers, logical_batch, physical_batch, 2) } else { Vec::new() };
+        let mut orders = if used[3] { generate_or_load::<Order>(prefix.as_str(), "orders.tbl", index, peers, logical_batch, physical_batch, 3) } else { Vec::new() };
+        let mut parts = if used[4] { generate_or_load::<Part>(prefix.as_str(), "part.tbl", index, peers, logical_batch, physical_batch, 4) } else { Vec::new() };
+        let mut partsupps = if used[5] { generate_or_load::<PartSupp>(prefix.as_str(), "partsupp.tbl", index, peers, logical_batch, physical_batch, 5) } else { Vec::new() };
+        let mut regions = if used[6] { generate_or_load::<Region>(prefix.as_str(), "region.tbl", index, peers, logical_batch, physical_batch, 6) } else { Vec::new() };
+        let mut suppliers = if used[7] { generate_or_load::<Supplier>(prefix.as_str(), "supplier.tbl", index, peers, logical_batch, physical_batch, 7) } else { Vec::new() };
+        let mut refreshes = if used[1] || used[3] { generate_or_load_refresh(prefix.as_str(), sets, index, peers) } else { Vec::new() };
+        // Refreshed inputs must remain open after their initial load.
+        let seal_refreshed = seal && refreshes.is_empty();
 
         let mut tuples = 0usize;
         tuples += customers.iter().map(|x| x.len()).sum::<usize>();
@@ -124,9 +131,9 @@ fn main() {
 
             // introduce physical batch of data for each input with remaining data.
             if let Some(mut data) = customers.pop() { inputs.0.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.0 = None; } }
-            if let Some(mut data) = lineitems.pop() { inputs.1.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.1 = None; } }
+            if let Some(mut data) = lineitems.pop() { inputs.1.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal_refreshed { inputs.1 = None; } }
             if let Some(mut data) = nations.pop() { inputs.2.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.2 = None; } }
-            if let Some(mut data) = orders.pop() { inputs.3.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.3 = None; } }
+            if let Some(mut data) = orders.pop() { inputs.3.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal_refreshed { inputs.3 = None; } }
             if let Some(mut data) = parts.pop() { inputs.4.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.4 = None; } }
             if let Some(mut data) = partsupps.pop() { inputs.5.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.5 = None; } }
             if let Some(mut data) = regions.pop() { inputs.6.as_mut().map(|x| x.send_batch(&mut data)); } else { if seal { inputs.6 = None; } }
@@ -148,6 +155,39 @@ fn main() {
             round += 1;
         }
 
+        // Apply the refresh functions, each as a round of its own.
+        let mut pacing = Pacing::new(interval);
+        let mut refreshed = 0;
+        while let Some(mut refresh) = refreshes.pop() {
+
+            pacing.wait();
+            let started = Instant::now();
+            tuples += refresh.len();
+
+            let time = 1 + 8 * round * physical_batch;
+            inputs.1.as_mut().map(|x| x.send_batch(&mut refresh.lineitems_at(time)));
+            inputs.3.as_mut().map(|x| x.send_batch(&mut refresh.orders_at(time)));
+
+            let next_round = 1 + 8 * (round + 1) * physical_batch;
+            inputs.0.as_mut().map(|x| x.advance_to(next_round));
+            inputs.1.as_mut().map(|x| x.advance_to(next_round));
+            inputs.2.as_mut().map(|x| x.advance_to(next_round));
+            inputs.3.as_mut().map(|x| x.advance_to(next_round));
+            inputs.4.as_mut().map(|x| x.advance_to(next_round));
+            inputs.5.as_mut().map(|x| x.advance_to(next_round));
+            inputs.6.as_mut().map(|x| x.advance_to(next_round));
+            inputs.7.as_mut().map(|x| x.advance_to(next_round));
+
+            let time = next_round;
+            worker.step_while(|| probe.less_than(&time));
+            round += 1;
+            refreshed += 1;
+
+            if index == 0 {
+                println!("{:?}\tRefresh {} applied in {:?}", timer.elapsed(), refreshed, started.elapsed());
+            }
+        }
+
         // Finish outstanding work before stopping the timer.
         let next_round = usize::max_value();
         inputs.0.as_mut().map(|x| x.advance_to(next_round));
@@ -174,49 +214,3 @@ fn main() {
 
     }).unwrap();
 }
-
-// Returns a sequence of physical batches of ready-to-go timestamped data. Not clear that `input` can exploit the pre-arrangement yet.
-fn load<T>(prefix: &str, name: &str, index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize)
-    -> Vec<Vec<(T, usize, isize)>>
-where T: for<'a> From<&'a str> {
-
-    let mut result = Vec::new();
-    let mut buffer = Vec::new();
-
-    let path = format!("{}{}", prefix, name);
-
-    let items_file = File::open(&path).expect("didn't find items file");
-    let mut items_reader =  BufReader::new(items_file);
-    let mut count = 0;
-
-    let mut line = String::new();
-
-    while items_reader.read_line(&mut line).unwrap() > 0 {
-
-        if count % peers == index {
-
-            let logical = (8 * count / logical_batch) + off;
-            let physical = logical / physical_batch;
-            let round = physical / 8;
-
-            while result.len() < round {
-                result.push(buffer);
-                buffer = Vec::with_capacity(2 + logical_batch * physical_batch / peers);
-            }
-
-            let item = T::from(line.as_str());
-            buffer.push((item, logical + 1, 1));
-        }
-
-        count += 1;
-
-        line.clear();
-    }
-
-    if buffer.len() > 0 {
-        result.push(buffer);
-    }
-
-    result.reverse();
-    result
-}
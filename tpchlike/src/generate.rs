@@ -0,0 +1,477 @@
+//! Generation of TPC-H data, in place of `dbgen` and its files.
+//!
+//! Each worker synthesizes its own share of each relation, for a given scale factor, as records
+//! rather than text. The generator follows the specification `dbgen` implements: the cardinalities
+//! of the relations, the sparse keys of orders, the keys of suppliers of each part, and the domains
+//! and distributions of each column, including the dates and prices derived from others. It does
+//! not reproduce the random streams of `dbgen`, so its records differ from those of the files, but
+//! it is deterministic: each record is drawn from a stream seeded by its relation and row, so that
+//! the relations are the same for any number of workers.
+//!
+//! The refresh functions are generated as well, inserting orders under keys the initial orders do
+//! not use, and deleting initial orders in order of their keys.
+
+use arrayvec::ArrayString;
+
+use load::{batch, load, load_batch};
+use refresh::{Refresh, load_refresh};
+use types::*;
+
+/// Relations that can be generated.
+pub trait Table: Sized {
+    /// Calls `logic` on each record of the worker's share of the relation at `scale`.
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, logic: F);
+}
+
+/// Generates the worker's share of a relation, as physical batches of timestamped data as `load` produces.
+pub fn generate<T: Table>(scale: f64, index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize)
+    -> Vec<Vec<(T, usize, isize)>>
+{
+    batch(index, peers, logical_batch, physical_batch, off, |push| T::generate(scale, index, peers, |record| push(record)))
+}
+
+/// Generates the worker's share of a relation as a single untimed batch, as `load_batch` produces.
+pub fn generate_batch<T: Table>(scale: f64, index: usize, peers: usize) -> Vec<Vec<(T, (), isize)>> {
+    let mut buffer = Vec::new();
+    T::generate(scale, index, peers, |record| buffer.push((record, (), 1)));
+    vec![buffer]
+}
+
+/// The scale factor named by an input path of the form `generate:<scale>`, if it is one.
+pub fn scale(prefix: &str) -> Option<f64> {
+    if prefix.starts_with("generate:") {
+        Some(prefix["generate:".len() ..].parse().expect("malformed scale factor"))
+    }
+    else {
+        None
+    }
+}
+
+/// Generates the relation if `prefix` names a scale factor, as `generate` does, and otherwise loads it as `load` does.
+pub fn generate_or_load<T>(prefix: &str, name: &str, index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize)
+    -> Vec<Vec<(T, usize, isize)>>
+where T: Table+for<'a> From<&'a str> {
+    match scale(prefix) {
+        Some(scale) => generate(scale, index, peers, logical_batch, physical_batch, off),
+        None => load(prefix, name, index, peers, logical_batch, physical_batch, off),
+    }
+}
+
+/// Generates the relation if `prefix` names a scale factor, as `generate_batch` does, and otherwise loads it as `load_batch` does.
+pub fn generate_or_load_batch<T>(prefix: &str, name: &str, index: usize, peers: usize) -> Vec<Vec<(T, (), isize)>>
+where T: Table+for<'a> From<&'a str> {
+    match scale(prefix) {
+        Some(scale) => generate_batch(scale, index, peers),
+        None => load_batch(prefix, name, index, peers),
+    }
+}
+
+/// Generates the worker's share of the first `sets` refresh sets, as `load_refresh` loads them.
+pub fn generate_refresh(scale: f64, sets: usize, index: usize, peers: usize) -> Vec<Refresh> {
+
+    let count = rows(1_500, scale);
+    let mut result = Vec::with_capacity(sets);
+
+    for set in 0 .. sets {
+        let mut refresh = Refresh { orders: Vec::new(), lineitems: Vec::new() };
+        for row in (set * count .. (set + 1) * count).filter(|row| row % peers == index) {
+            let (inserted, items) = order(scale, INSERTED, row, inserted_key(row));
+            refresh.orders.push((inserted, 1));
+            refresh.lineitems.extend(items.into_iter().map(|item| (item, 1)));
+            let (deleted, items) = order(scale, ORDERS, row, order_key(row));
+            refresh.orders.push((deleted, -1));
+            refresh.lineitems.extend(items.into_iter().map(|item| (item, -1)));
+        }
+        result.push(refresh);
+    }
+
+    result.reverse();
+    result
+}
+
+/// Generates the refresh sets if `prefix` names a scale factor, as `generate_refresh` does, and otherwise loads them as `load_refresh` does.
+pub fn generate_or_load_refresh(prefix: &str, sets: usize, index: usize, peers: usize) -> Vec<Refresh> {
+    match scale(prefix) {
+        Some(scale) => generate_refresh(scale, sets, index, peers),
+        None => load_refresh(prefix, sets, index, peers),
+    }
+}
+
+// Seeds of the random streams of each relation.
+const CUSTOMERS: u64 = 1;
+const NATIONS: u64 = 2;
+const ORDERS: u64 = 3;
+const PARTS: u64 = 4;
+const PARTSUPPS: u64 = 5;
+const REGIONS: u64 = 6;
+const SUPPLIERS: u64 = 7;
+const INSERTED: u64 = 8;
+
+/// The number of rows of a relation of `base` rows at scale factor one.
+fn rows(base: usize, scale: f64) -> usize {
+    ::std::cmp::max(1, (base as f64 * scale) as usize)
+}
+
+/// Calls `logic` on each of the worker's rows of a relation of `count` rows.
+fn shard<F: FnMut(usize)>(count: usize, index: usize, peers: usize, logic: F) {
+    (0 .. count).filter(|row| row % peers == index).for_each(logic)
+}
+
+/// A stream of random numbers, by SplitMix64.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(table: u64, row: usize) -> Self {
+        Random { state: table.wrapping_mul(0x9E3779B97F4A7C15) ^ (row as u64).wrapping_mul(0xBF58476D1CE4E5B9) }
+    }
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+        value ^ (value >> 31)
+    }
+    /// A uniform value from `lower` through `upper`, inclusive.
+    fn range(&mut self, lower: i64, upper: i64) -> i64 {
+        lower + (self.next() % ((upper - lower + 1) as u64)) as i64
+    }
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.range(0, items.len() as i64 - 1) as usize]
+    }
+    /// Text of words, of a length from `lower` through `upper`.
+    fn text(&mut self, lower: i64, upper: i64) -> String {
+        let length = self.range(lower, upper) as usize;
+        let mut text = String::with_capacity(length + 16);
+        while text.len() < length {
+            if !text.is_empty() { text.push(' '); }
+            text.push_str(self.pick(WORDS));
+        }
+        text.truncate(length);
+        text
+    }
+    /// Alphanumeric characters, of a length from `lower` through `upper`.
+    fn chars(&mut self, lower: i64, upper: i64) -> String {
+        const ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ,";
+        let length = self.range(lower, upper);
+        (0 .. length).map(|_| ALPHABET[self.range(0, ALPHABET.len() as i64 - 1) as usize] as char).collect()
+    }
+    fn phone(&mut self, nation_key: usize) -> [u8; 15] {
+        let phone = format!("{:02}-{:03}-{:03}-{:04}", nation_key + 10, self.range(100, 999), self.range(100, 999), self.range(1000, 9999));
+        read_u15(&phone)
+    }
+}
+
+/// The date `days` after 1992-01-01.
+fn date(days: i64) -> Date {
+    let mut days = days;
+    let mut year = 1992;
+    while days >= year_length(year) {
+        days -= year_length(year);
+        year += 1;
+    }
+    let mut month = 1;
+    while days >= month_length(year, month) {
+        days -= month_length(year, month);
+        month += 1;
+    }
+    create_date(year as u16, month as u8, days as u8 + 1)
+}
+
+fn leap(year: i64) -> bool { year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) }
+fn year_length(year: i64) -> i64 { if leap(year) { 366 } else { 365 } }
+fn month_length(year: i64, month: i64) -> i64 {
+    match month {
+        2 => if leap(year) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days from 1992-01-01 to 1995-06-17, the date orders are current as of, and to 1998-08-02, the last order date.
+const CURRENT: i64 = 1263;
+const LAST_ORDER: i64 = 2405;
+
+/// The retail price of a part, in cents.
+fn retail_price(part_key: usize) -> i64 {
+    (90000 + ((part_key / 10) % 20001) + 100 * (part_key % 1000)) as i64
+}
+
+/// The key of the `copy`th supplier, of four, of a part.
+fn supplier_of(part_key: usize, copy: usize, suppliers: usize) -> usize {
+    (part_key + (copy * ((suppliers / 4) + (part_key - 1) / suppliers))) % suppliers + 1
+}
+
+/// The key of the initial order `row`, using the first eight of every thirty-two keys.
+fn order_key(row: usize) -> usize {
+    (row / 8) * 32 + (row % 8) + 1
+}
+
+/// The key of the inserted order `row`, using the next eight of every thirty-two keys.
+fn inserted_key(row: usize) -> usize {
+    (row / 8) * 32 + 8 + (row % 8) + 1
+}
+
+/// An order and its line items, drawn from the stream `table` at `row`.
+fn order(scale: f64, table: u64, row: usize, order_key: usize) -> (Order, Vec<LineItem>) {
+
+    let customers = rows(150_000, scale);
+    let parts = rows(200_000, scale);
+    let suppliers = rows(10_000, scale);
+
+    let mut random = Random::new(table, row);
+
+    // A third of customers place no orders.
+    let mut cust_key = random.range(1, customers as i64) as usize;
+    while cust_key % 3 == 0 && customers > 2 {
+        cust_key = random.range(1, customers as i64) as usize;
+    }
+    let order_day = random.range(0, LAST_ORDER);
+
+    let mut items = Vec::new();
+    let mut total_price = 0;
+    for line_number in 1 .. random.range(1, 7) + 1 {
+        let part_key = random.range(1, parts as i64) as usize;
+        let supp_key = supplier_of(part_key, random.range(0, 3) as usize, suppliers);
+        let quantity = random.range(1, 50);
+        let extended_price = quantity * retail_price(part_key);
+        let discount = random.range(0, 10);
+        let tax = random.range(0, 8);
+        let ship_day = order_day + random.range(1, 121);
+        let commit_day = order_day + random.range(30, 90);
+        let receipt_day = ship_day + random.range(1, 30);
+        let return_flag = if receipt_day <= CURRENT { random.pick(&["R", "A"]) } else { "N" };
+        let line_status = if ship_day > CURRENT { "O" } else { "F" };
+        total_price += extended_price * (100 + tax) * (100 - discount) / 10000;
+        items.push(LineItem {
+            order_key,
+            part_key,
+            supp_key,
+            line_number: line_number as i32,
+            quantity,
+            extended_price,
+            discount,
+            tax,
+            return_flag: read_u01(return_flag),
+            line_status: read_u01(line_status),
+            ship_date: date(ship_day),
+            commit_date: date(commit_day),
+            receipt_date: date(receipt_day),
+            ship_instruct: read_u25(random.pick(INSTRUCTIONS)),
+            ship_mode: read_u10(random.pick(MODES)),
+            comment: ArrayString::from(&random.text(10, 43)).unwrap(),
+        });
+    }
+
+    let order_status =
+    if items.iter().all(|item| &item.line_status == b"F") { "F" }
+    else if items.iter().all(|item| &item.line_status == b"O") { "O" }
+    else { "P" };
+
+    let order = Order {
+        order_key,
+        cust_key,
+        order_status: read_u01(order_status),
+        total_price,
+        order_date: date(order_day),
+        order_priority: read_u15(random.pick(PRIORITIES)),
+        clerk: read_u15(&format!("Clerk#{:09}", random.range(1, rows(1_000, scale) as i64))),
+        ship_priority: 0,
+        comment: ArrayString::from(&random.text(19, 78)).unwrap(),
+    };
+
+    (order, items)
+}
+
+impl Table for Customer {
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(rows(150_000, scale), index, peers, |row| {
+            let mut random = Random::new(CUSTOMERS, row);
+            let cust_key = row + 1;
+            let nation_key = random.range(0, 24) as usize;
+            logic(Customer {
+                cust_key,
+                name: AbomonationWrapper { element: ArrayString::from(&format!("Customer#{:09}", cust_key)).unwrap() },
+                address: AbomonationWrapper { element: ArrayString::from(&random.chars(10, 40)).unwrap() },
+                nation_key,
+                phone: random.phone(nation_key),
+                acctbal: random.range(-99999, 999999),
+                mktsegment: read_u10(random.pick(SEGMENTS)),
+                comment: AbomonationWrapper { element: ArrayString::from(&random.text(29, 116)).unwrap() },
+            })
+        })
+    }
+}
+
+impl Table for LineItem {
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(rows(1_500_000, scale), index, peers, |row| {
+            for item in order(scale, ORDERS, row, order_key(row)).1 {
+                logic(item);
+            }
+        })
+    }
+}
+
+impl Table for Nation {
+    fn generate<F: FnMut(Self)>(_scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(NATION_NAMES.len(), index, peers, |row| {
+            let mut random = Random::new(NATIONS, row);
+            let (name, region_key) = NATION_NAMES[row];
+            logic(Nation {
+                nation_key: row,
+                name: read_u25(name),
+                region_key,
+                comment: ArrayString::from(&random.text(31, 114)).unwrap(),
+            })
+        })
+    }
+}
+
+impl Table for Order {
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(rows(1_500_000, scale), index, peers, |row| logic(order(scale, ORDERS, row, order_key(row)).0))
+    }
+}
+
+impl Table for Part {
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(rows(200_000, scale), index, peers, |row| {
+            let mut random = Random::new(PARTS, row);
+            let part_key = row + 1;
+            let mut colors: Vec<&str> = Vec::with_capacity(5);
+            while colors.len() < 5 {
+                let color = random.pick(COLORS);
+                if !colors.contains(&color) { colors.push(color); }
+            }
+            let manufacturer = random.range(1, 5);
+            let typ = format!("{} {} {}", random.pick(TYPE_SIZES), random.pick(TYPE_FINISHES), random.pick(TYPE_MATERIALS));
+            let container = format!("{} {}", random.pick(CONTAINER_SIZES), random.pick(CONTAINER_KINDS));
+            logic(Part {
+                part_key,
+                name: ArrayString::from(&colors.join(" ")).unwrap(),
+                mfgr: read_u25(&format!("Manufacturer#{}", manufacturer)),
+                brand: read_u10(&format!("Brand#{}{}", manufacturer, random.range(1, 5))),
+                typ: AbomonationWrapper { element: ArrayString::from(&typ).unwrap() },
+                size: random.range(1, 50) as i32,
+                container: read_u10(&container),
+                retail_price: retail_price(part_key),
+                comment: ArrayString::from(&random.text(5, 22)).unwrap(),
+            })
+        })
+    }
+}
+
+impl Table for PartSupp {
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, mut logic: F) {
+        let suppliers = rows(10_000, scale);
+        shard(4 * rows(200_000, scale), index, peers, |row| {
+            let mut random = Random::new(PARTSUPPS, row);
+            let part_key = row / 4 + 1;
+            logic(PartSupp {
+                part_key,
+                supp_key: supplier_of(part_key, row % 4, suppliers),
+                availqty: random.range(1, 9999) as i32,
+                supplycost: random.range(100, 100000),
+                comment: ArrayString::from(&random.text(49, 198)).unwrap(),
+            })
+        })
+    }
+}
+
+impl Table for Region {
+    fn generate<F: FnMut(Self)>(_scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(REGION_NAMES.len(), index, peers, |row| {
+            let mut random = Random::new(REGIONS, row);
+            logic(Region {
+                region_key: row,
+                name: read_u25(REGION_NAMES[row]),
+                comment: ArrayString::from(&random.text(31, 115)).unwrap(),
+            })
+        })
+    }
+}
+
+impl Table for Supplier {
+    fn generate<F: FnMut(Self)>(scale: f64, index: usize, peers: usize, mut logic: F) {
+        shard(rows(10_000, scale), index, peers, |row| {
+            let mut random = Random::new(SUPPLIERS, row);
+            let supp_key = row + 1;
+            let nation_key = random.range(0, 24) as usize;
+            // Five in ten thousand suppliers have complaints, and as many have recommendations.
+            let comment = match random.range(1, 10000) {
+                1 ..= 5 => format!("{} Customer {} Complaints", random.text(10, 40), random.pick(WORDS)),
+                6 ..= 10 => format!("{} Customer {} Recommends", random.text(10, 40), random.pick(WORDS)),
+                _ => random.text(25, 100),
+            };
+            logic(Supplier {
+                supp_key,
+                name: read_u25(&format!("Supplier#{:09}", supp_key)),
+                address: AbomonationWrapper { element: ArrayString::from(&random.chars(10, 40)).unwrap() },
+                nation_key,
+                phone: random.phone(nation_key),
+                acctbal: random.range(-99999, 999999),
+                comment: AbomonationWrapper { element: ArrayString::from(&comment).unwrap() },
+            })
+        })
+    }
+}
+
+const REGION_NAMES: &[&str] = &["AFRICA", "AMERICA", "ASIA", "EUROPE", "MIDDLE EAST"];
+
+const NATION_NAMES: &[(&str, usize)] = &[
+    ("ALGERIA", 0), ("ARGENTINA", 1), ("BRAZIL", 1), ("CANADA", 1), ("EGYPT", 4),
+    ("ETHIOPIA", 0), ("FRANCE", 3), ("GERMANY", 3), ("INDIA", 2), ("INDONESIA", 2),
+    ("IRAN", 4), ("IRAQ", 4), ("JAPAN", 2), ("JORDAN", 4), ("KENYA", 0),
+    ("MOROCCO", 0), ("MOZAMBIQUE", 0), ("PERU", 1), ("CHINA", 2), ("ROMANIA", 3),
+    ("SAUDI ARABIA", 4), ("VIETNAM", 2), ("RUSSIA", 3), ("UNITED KINGDOM", 3), ("UNITED STATES", 1),
+];
+
+const SEGMENTS: &[&str] = &["AUTOMOBILE", "BUILDING", "FURNITURE", "MACHINERY", "HOUSEHOLD"];
+const PRIORITIES: &[&str] = &["1-URGENT", "2-HIGH", "3-MEDIUM", "4-NOT SPECIFIED", "5-LOW"];
+const INSTRUCTIONS: &[&str] = &["DELIVER IN PERSON", "COLLECT COD", "NONE", "TAKE BACK RETURN"];
+const MODES: &[&str] = &["REG AIR", "AIR", "RAIL", "SHIP", "TRUCK", "MAIL", "FOB"];
+
+const TYPE_SIZES: &[&str] = &["STANDARD", "SMALL", "MEDIUM", "LARGE", "ECONOMY", "PROMO"];
+const TYPE_FINISHES: &[&str] = &["ANODIZED", "BURNISHED", "PLATED", "POLISHED", "BRUSHED"];
+const TYPE_MATERIALS: &[&str] = &["TIN", "NICKEL", "BRASS", "STEEL", "COPPER"];
+const CONTAINER_SIZES: &[&str] = &["SM", "LG", "MED", "JUMBO", "WRAP"];
+const CONTAINER_KINDS: &[&str] = &["CASE", "BOX", "BAG", "JAR", "PKG", "PACK", "CAN", "DRUM"];
+
+const COLORS: &[&str] = &[
+    "almond", "antique", "aquamarine", "azure", "beige", "bisque", "black", "blanched", "blue",
+    "blush", "brown", "burlywood", "burnished", "chartreuse", "chiffon", "chocolate", "coral",
+    "cornflower", "cornsilk", "cream", "cyan", "dark", "deep", "dim", "dodger", "drab", "firebrick",
+    "floral", "forest", "frosted", "gainsboro", "ghost", "goldenrod", "green", "grey", "honeydew",
+    "hot", "indian", "ivory", "khaki", "lace", "lavender", "lawn", "lemon", "light", "lime", "linen",
+    "magenta", "maroon", "medium", "metallic", "midnight", "mint", "misty", "moccasin", "navajo",
+    "navy", "olive", "orange", "orchid", "pale", "papaya", "peach", "peru", "pink", "plum", "powder",
+    "puff", "purple", "red", "rose", "rosy", "royal", "saddle", "salmon", "sandy", "seashell",
+    "sienna", "sky", "slate", "smoke", "snow", "spring", "steel", "tan", "thistle", "tomato",
+    "turquoise", "violet", "wheat", "white", "yellow",
+];
+
+// Words of the text of comments, from the grammar of the specification.
+const WORDS: &[&str] = &[
+    "foxes", "ideas", "theodolites", "pinto", "beans", "instructions", "dependencies", "excuses",
+    "platelets", "asymptotes", "courts", "dolphins", "multipliers", "sauternes", "warthogs", "frets",
+    "dinos", "attainments", "somas", "patterns", "forges", "braids", "frays", "warhorses", "dugouts",
+    "epitaphs", "pearls", "tithes", "waters", "orbits", "gifts", "sheaves", "depths", "sentiments",
+    "decoys", "realms", "pains", "grouches", "escapades", "packages", "requests", "accounts", "deposits",
+    "sleep", "wake", "are", "cajole", "haggle", "nag", "use", "boost", "affix", "detect", "integrate",
+    "maintain", "nod", "was", "lose", "sublate", "solve", "thrash", "promise", "engage", "hinder",
+    "print", "breach", "eat", "grow", "impress", "mold", "poach", "serve", "run", "dazzle", "snooze",
+    "doze", "unwind", "kindle", "play", "hang", "believe", "doubt", "furious", "sly", "careful",
+    "blithe", "quick", "fluffy", "slow", "quiet", "ruthless", "thin", "close", "dogged", "daring",
+    "brave", "stealthy", "permanent", "enticing", "idle", "busy", "regular", "final", "ironic", "even",
+    "bold", "silent", "special", "pending", "express", "unusual", "sometimes", "always", "never",
+    "furiously", "slyly", "carefully", "blithely", "quickly", "fluffily", "slowly", "quietly",
+    "ruthlessly", "thinly", "closely", "doggedly", "daringly", "bravely", "stealthily", "permanently",
+    "enticingly", "idly", "busily", "regularly", "finally", "ironically", "evenly", "boldly", "silently",
+    "about", "above", "according", "to", "across", "after", "against", "along", "among", "around",
+    "at", "atop", "before", "behind", "beneath", "beside", "besides", "between", "beyond", "by",
+    "despite", "during", "except", "for", "from", "inside", "instead", "of", "into", "near", "on",
+    "outside", "over", "past", "since", "through", "throughout", "toward", "under", "until", "upon",
+    "without", "with", "within", "the",
+];
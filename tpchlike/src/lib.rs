@@ -11,6 +11,10 @@ use differential_dataflow::Collection;
 
 pub mod types;
 pub mod queries;
+pub mod queries_delta;
+pub mod load;
+pub mod refresh;
+pub mod generate;
 
 pub use types::*;
 
@@ -68,6 +72,7 @@ use differential_dataflow::trace::implementations::ord::OrdValSpine as DefaultVa
 use differential_dataflow::operators::arrange::TraceAgent;
 
 type ArrangedIndex<T> = TraceAgent<usize, T, usize, isize, DefaultValTrace<usize, T, usize, isize>>;
+type ArrangedPairIndex<T> = TraceAgent<(usize, usize), T, usize, isize, DefaultValTrace<(usize, usize), T, usize, isize>>;
 
 pub struct Arrangements {
     customers:  ArrangedIndex<Customer>,
@@ -76,6 +81,11 @@ pub struct Arrangements {
     parts:      ArrangedIndex<Part>,
     regions:    ArrangedIndex<Region>,
     suppliers:  ArrangedIndex<Supplier>,
+    // line items by `order_key`, and by `(part_key, supp_key)`.
+    lineitems:  ArrangedIndex<LineItem>,
+    lineitems_by_partsupp: ArrangedPairIndex<LineItem>,
+    // partsupps by `(part_key, supp_key)`.
+    partsupps:  ArrangedPairIndex<PartSupp>,
 }
 
 use timely::dataflow::Scope;
@@ -118,6 +128,21 @@ impl Arrangements {
         arranged.trace.distinguish_since(&[]);
         let suppliers = arranged.trace;
 
+        let mut arranged = collections.lineitems().map(|x| (x.order_key, x)).arrange_by_key();
+        arranged.stream.probe_with(probe);
+        arranged.trace.distinguish_since(&[]);
+        let lineitems = arranged.trace;
+
+        let mut arranged = collections.lineitems().map(|x| ((x.part_key, x.supp_key), x)).arrange_by_key();
+        arranged.stream.probe_with(probe);
+        arranged.trace.distinguish_since(&[]);
+        let lineitems_by_partsupp = arranged.trace;
+
+        let mut arranged = collections.partsupps().map(|x| ((x.part_key, x.supp_key), x)).arrange_by_key();
+        arranged.stream.probe_with(probe);
+        arranged.trace.distinguish_since(&[]);
+        let partsupps = arranged.trace;
+
         Arrangements {
             customers,
             nations,
@@ -125,6 +150,9 @@ impl Arrangements {
             parts,
             regions,
             suppliers,
+            lineitems,
+            lineitems_by_partsupp,
+            partsupps,
         }
     }
 
@@ -138,5 +166,8 @@ impl Arrangements {
         self.parts.advance_by(frontier);
         self.regions.advance_by(frontier);
         self.suppliers.advance_by(frontier);
+        self.lineitems.advance_by(frontier);
+        self.lineitems_by_partsupp.advance_by(frontier);
+        self.partsupps.advance_by(frontier);
     }
 }
\ No newline at end of file
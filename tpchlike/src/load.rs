@@ -0,0 +1,120 @@
+//! Worker-parallel loading of TPC-H data files.
+//!
+//! Each worker reads and parses only its own share of the input, rather than each worker reading
+//! every line and discarding those belonging to other workers. Inputs may either be single `.tbl`
+//! files, in which case each worker reads a contiguous byte range of the file, or partitioned files
+//! as produced by `dbgen -C <n> -S <k>`, named `<name>.1`, `<name>.2`, and so on, in which case the
+//! partitions are distributed round-robin among the workers.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// Calls `logic` on each line of the worker's share of the named input.
+///
+/// The first argument to `logic` counts the lines presented to this worker.
+pub fn read_lines<F: FnMut(usize, &str)>(prefix: &str, name: &str, index: usize, peers: usize, mut logic: F) {
+
+    let mut count = 0;
+    let mut line = String::new();
+
+    if Path::new(&format!("{}{}.1", prefix, name)).exists() {
+        // Partitioned input; read each partition assigned to this worker.
+        let mut part = 1;
+        while Path::new(&format!("{}{}.{}", prefix, name, part)).exists() {
+            if (part - 1) % peers == index {
+                let path = format!("{}{}.{}", prefix, name, part);
+                let file = File::open(&path).expect("didn't find items file");
+                let mut reader = BufReader::new(file);
+                while reader.read_line(&mut line).unwrap() > 0 {
+                    logic(count, line.as_str());
+                    count += 1;
+                    line.clear();
+                }
+            }
+            part += 1;
+        }
+    }
+    else {
+        // Single input; read the lines starting in this worker's byte range.
+        let path = format!("{}{}", prefix, name);
+        let mut file = File::open(&path).expect("didn't find items file");
+        let length = file.metadata().expect("couldn't read file metadata").len();
+        let lower = length * (index as u64) / (peers as u64);
+        let upper = length * (index as u64 + 1) / (peers as u64);
+
+        // Start one byte early, and discard through the next newline, so that a line starting
+        // exactly at `lower` is read by this worker rather than skipped.
+        let mut position = if lower > 0 { lower - 1 } else { 0 };
+        file.seek(SeekFrom::Start(position)).expect("couldn't seek in file");
+        let mut reader = BufReader::new(file);
+        if lower > 0 {
+            position += reader.read_line(&mut line).unwrap() as u64;
+            line.clear();
+        }
+
+        while position < upper {
+            let read = reader.read_line(&mut line).unwrap();
+            if read == 0 { break; }
+            position += read as u64;
+            logic(count, line.as_str());
+            count += 1;
+            line.clear();
+        }
+    }
+}
+
+/// Loads the worker's share of the named input, as physical batches of timestamped data.
+///
+/// Records are assigned logical times as if the workers' records were interleaved, consistent
+/// with each worker holding an equal share of the input. The batches are returned in reverse
+/// order, so that they can be popped off of the end of the vector.
+pub fn load<T>(prefix: &str, name: &str, index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize)
+    -> Vec<Vec<(T, usize, isize)>>
+where T: for<'a> From<&'a str> {
+    batch(index, peers, logical_batch, physical_batch, off, |push| {
+        read_lines(prefix, name, index, peers, |_local, line| push(T::from(line)))
+    })
+}
+
+/// Assigns the worker's share of records, as `rows` presents them, to physical batches as `load` does.
+pub(crate) fn batch<T, F>(index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize, rows: F)
+    -> Vec<Vec<(T, usize, isize)>>
+where F: FnOnce(&mut FnMut(T)) {
+
+    let mut result = Vec::new();
+    let mut buffer = Vec::new();
+    let mut local = 0;
+
+    rows(&mut |record| {
+
+        let count = local * peers + index;
+        let logical = (8 * count / logical_batch) + off;
+        let physical = logical / physical_batch;
+        let round = physical / 8;
+
+        while result.len() < round {
+            result.push(::std::mem::replace(&mut buffer, Vec::with_capacity(2 + logical_batch * physical_batch / peers)));
+        }
+
+        buffer.push((record, logical + 1, 1));
+        local += 1;
+    });
+
+    if buffer.len() > 0 {
+        result.push(buffer);
+    }
+
+    result.reverse();
+    result
+}
+
+/// Loads the worker's share of the named input as a single untimed batch.
+pub fn load_batch<T>(prefix: &str, name: &str, index: usize, peers: usize)
+    -> Vec<Vec<(T, (), isize)>>
+where T: for<'a> From<&'a str> {
+
+    let mut buffer = Vec::new();
+    read_lines(prefix, name, index, peers, |_local, line| buffer.push((T::from(line), (), 1)));
+    vec![buffer]
+}
@@ -6,8 +6,6 @@ extern crate arrayvec;
 extern crate regex;
 extern crate core_affinity;
 
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::time::Instant;
 
 use timely::dataflow::*;
@@ -17,8 +15,10 @@ use differential_dataflow::{Collection, AsCollection};
 
 mod types;
 mod queries;
+mod load;
 
 use types::*;
+use load::load;
 
 fn main() {
 
@@ -228,49 +228,3 @@ impl<G: Scope> Collections<G> {
     fn regions(&mut self) -> &Collection<G, Region, isize> { self.used[6] = true; &self.regions }
     fn suppliers(&mut self) -> &Collection<G, Supplier, isize> { self.used[7] = true; &self.suppliers }
 }
-
-// Returns a sequence of physical batches of ready-to-go timestamped data. Not clear that `input` can exploit the pre-arrangement yet.
-fn load<T>(prefix: &str, name: &str, index: usize, peers: usize, logical_batch: usize, physical_batch: usize, off: usize)
-    -> Vec<Vec<(T, usize, isize)>>
-where T: for<'a> From<&'a str> {
-
-    let mut result = Vec::new();
-    let mut buffer = Vec::new();
-
-    let path = format!("{}{}", prefix, name);
-
-    let items_file = File::open(&path).expect("didn't find items file");
-    let mut items_reader =  BufReader::new(items_file);
-    let mut count = 0;
-
-    let mut line = String::new();
-
-    while items_reader.read_line(&mut line).unwrap() > 0 {
-
-        if count % peers == index {
-
-            let logical = (8 * count / logical_batch) + off;
-            let physical = logical / physical_batch;
-            let round = physical / 8;
-
-            while result.len() < round {
-                result.push(buffer);
-                buffer = Vec::with_capacity(2 + logical_batch * physical_batch / peers);
-            }
-
-            let item = T::from(line.as_str());
-            buffer.push((item, logical + 1, 1));
-        }
-
-        count += 1;
-
-        line.clear();
-    }
-
-    if buffer.len() > 0 {
-        result.push(buffer);
-    }
-
-    result.reverse();
-    result
-}
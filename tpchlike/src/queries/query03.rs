@@ -81,25 +81,12 @@ pub fn query_arranged<G: Scope<Timestamp=usize>>(
     probe: &mut ProbeHandle<G::Timestamp>
 )
 {
-    use differential_dataflow::operators::arrange::ArrangeBySelf;
-
-    let lineitems =
-    collections
-        .lineitems()
-        .explode(|l|
-            if l.ship_date > create_date(1995, 3, 15) {
-                Some((l.order_key, (l.extended_price * (100 - l.discount) / 100) as isize))
-            }
-            else { None }
-        )
-        .arrange_by_self();
-
     arrangements
         .orders
         .import(&collections.orders().scope())
-        .join_core(&lineitems, |_k, o, &()| {
-            if o.order_date < create_date(1995, 3, 15) {
-                Some((o.cust_key, (o.order_key, o.order_date, o.ship_priority)))
+        .join_core(&arrangements.lineitems.import(&collections.lineitems().scope()), |_k, o, l| {
+            if o.order_date < create_date(1995, 3, 15) && l.ship_date > create_date(1995, 3, 15) {
+                Some((o.cust_key, (o.order_key, o.order_date, o.ship_priority, (l.extended_price * (100 - l.discount) / 100) as isize)))
             }
             else {
                 None
@@ -113,6 +100,7 @@ pub fn query_arranged<G: Scope<Timestamp=usize>>(
                 None
             }
         })
+        .explode(|(order_key, order_date, ship_priority, revenue)| Some(((order_key, order_date, ship_priority), revenue)))
         .count_total()
         .probe_with(probe);
 }
\ No newline at end of file
@@ -0,0 +1,41 @@
+//! Delta-query variants of the TPCH-like queries, over shared `Arrangements`.
+//!
+//! Each query joins its base relations with a delta query: for each relation in the join, a pipeline
+//! starts from the changes to that relation and looks up matching records in arrangements of the other
+//! relations with `half_join`. A pipeline looks up the relations after its own, in the order the query
+//! lists them, at times through the time of its changes, and those before its own at times strictly
+//! before it, so that changes at the same time are joined exactly once. No intermediate results of the
+//! joins are arranged; the only arrangements are those of the base relations, found in `Arrangements`
+//! where they are keyed as a lookup needs, and otherwise arranged by the query for itself. The results
+//! of the joins are aggregated as in `queries`.
+//!
+//! Queries that read one relation, or that do not join base relations, are those of `queries`.
+
+pub mod query01;
+pub mod query02;
+pub mod query03;
+pub mod query04;
+pub mod query05;
+pub mod query06;
+pub mod query07;
+pub mod query08;
+pub mod query09;
+pub mod query10;
+pub mod query11;
+pub mod query12;
+pub mod query13;
+pub mod query14;
+pub mod query15;
+pub mod query16;
+pub mod query17;
+pub mod query18;
+pub mod query19;
+pub mod query20;
+pub mod query21;
+pub mod query22;
+
+/// Accepts changes to relations after the pipeline's own: those through the time of its changes.
+fn through(time2: &usize, time: &usize) -> bool { time2 <= time }
+
+/// Accepts changes to relations before the pipeline's own: those strictly before the time of its changes.
+fn before(time2: &usize, time: &usize) -> bool { time2 < time }
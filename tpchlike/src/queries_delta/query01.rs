@@ -0,0 +1,9 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use {Collections, Arrangements};
+
+// Q1 reads only line items, and is the query of `queries`.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, _arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+    ::queries::query01::query(collections, probe)
+}
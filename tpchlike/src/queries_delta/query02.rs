@@ -0,0 +1,110 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn substring(source: &[u8], query: &[u8]) -> bool {
+    (0 .. (source.len() - query.len())).any(|offset|
+        (0 .. query.len()).all(|i| source[i + offset] == query[i])
+    )
+}
+
+fn brass(part: &Part) -> bool { substring(part.typ.as_str().as_bytes(), b"BRASS") && part.size == 15 }
+fn europe(region: &Region) -> bool { starts_with(&region.name[..], b"EUROPE") }
+
+/// A joined record, as the part key and the cost, supplier key, manufacturer, supplier, and nation name.
+fn record(part: &Part, partsupp: &PartSupp, supplier: &Supplier, nation: &Nation) -> (usize, (i64, usize, [u8; 25], Supplier, [u8; 25])) {
+    (part.part_key, (partsupp.supplycost, partsupp.supp_key, part.mfgr, supplier.clone(), nation.name))
+}
+
+// Relations, in the order of the delta query: partsupp, part, supplier, nation, region.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.partsupps().scope();
+
+    let parts = arrangements.parts.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let regions = arrangements.regions.import(&scope);
+
+    let partsupps_by_part = collections.partsupps().map(|ps| (ps.part_key, ps)).arrange_by_key();
+    let partsupps_by_supp = collections.partsupps().map(|ps| (ps.supp_key, ps)).arrange_by_key();
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, s)).arrange_by_key();
+    let nations_by_region = collections.nations().map(|n| (n.region_key, n)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .partsupps()
+        .map(|ps| (ps.part_key, ps))
+        .half_join(&parts, through, |_, ps, p| if brass(p) { Some((ps.supp_key, (ps.clone(), p.clone()))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &(ref ps, ref p), s| (s.nation_key, (ps.clone(), p.clone(), s.clone())))
+        .half_join(&nations, through, |_, &(ref ps, ref p, ref s), n| (n.region_key, record(p, ps, s, n)))
+        .half_join(&regions, through, |_, joined, r| if europe(r) { Some(joined.clone()) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .parts()
+        .filter(|p| brass(p))
+        .map(|p| (p.part_key, p))
+        .half_join(&partsupps_by_part, before, |_, p, ps| (ps.supp_key, (ps.clone(), p.clone())))
+        .half_join(&suppliers, through, |_, &(ref ps, ref p), s| (s.nation_key, (ps.clone(), p.clone(), s.clone())))
+        .half_join(&nations, through, |_, &(ref ps, ref p, ref s), n| (n.region_key, record(p, ps, s, n)))
+        .half_join(&regions, through, |_, joined, r| if europe(r) { Some(joined.clone()) } else { None })
+        .flat_map(|x| x);
+
+    let changes3 =
+    collections
+        .suppliers()
+        .map(|s| (s.nation_key, s))
+        .half_join(&nations, through, |_, s, n| (n.region_key, (s.clone(), n.clone())))
+        .half_join(&regions, through, |_, &(ref s, ref n), r| if europe(r) { Some((s.supp_key, (s.clone(), n.clone()))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&partsupps_by_supp, before, |_, &(ref s, ref n), ps| (ps.part_key, (ps.clone(), s.clone(), n.clone())))
+        .half_join(&parts, before, |_, &(ref ps, ref s, ref n), p| if brass(p) { Some(record(p, ps, s, n)) } else { None })
+        .flat_map(|x| x);
+
+    let changes4 =
+    collections
+        .nations()
+        .map(|n| (n.region_key, n))
+        .half_join(&regions, through, |_, n, r| if europe(r) { Some((n.nation_key, n.clone())) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers_by_nation, before, |_, n, s| (s.supp_key, (s.clone(), n.clone())))
+        .half_join(&partsupps_by_supp, before, |_, &(ref s, ref n), ps| (ps.part_key, (ps.clone(), s.clone(), n.clone())))
+        .half_join(&parts, before, |_, &(ref ps, ref s, ref n), p| if brass(p) { Some(record(p, ps, s, n)) } else { None })
+        .flat_map(|x| x);
+
+    let changes5 =
+    collections
+        .regions()
+        .filter(|r| europe(r))
+        .map(|r| (r.region_key, ()))
+        .half_join(&nations_by_region, before, |_, &(), n| (n.nation_key, n.clone()))
+        .half_join(&suppliers_by_nation, before, |_, n, s| (s.supp_key, (s.clone(), n.clone())))
+        .half_join(&partsupps_by_supp, before, |_, &(ref s, ref n), ps| (ps.part_key, (ps.clone(), s.clone(), n.clone())))
+        .half_join(&parts, before, |_, &(ref ps, ref s, ref n), p| if brass(p) { Some(record(p, ps, s, n)) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .concat(&changes4)
+        .concat(&changes5)
+        .reduce(|_part_key, s, t| {
+            let minimum = (s[0].0).0;
+            t.extend(s.iter().take_while(|x| (x.0).0 == minimum).map(|&(x, w)| (x.clone(), w)));
+        })
+        .map(|(part_key, (cost, _supp_key, mfgr, s, name))| (s.nation_key, ((cost, part_key, mfgr, s.acctbal, s.name, s.address, s.phone, s.comment), name)))
+        .probe_with(probe);
+}
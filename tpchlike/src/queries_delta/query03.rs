@@ -0,0 +1,76 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn building(customer: &Customer) -> bool { starts_with(&customer.mktsegment[..], b"BUILDING") }
+fn pending(order: &Order) -> bool { order.order_date < create_date(1995, 3, 15) }
+fn shipped(item: &LineItem) -> bool { item.ship_date > create_date(1995, 3, 15) }
+fn revenue(item: &LineItem) -> isize { (item.extended_price * (100 - item.discount) / 100) as isize }
+
+// Relations, in the order of the delta query: lineitem, orders, customer.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let customers = arrangements.customers.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+
+    let orders_by_customer =
+    collections
+        .orders()
+        .flat_map(|o| if pending(&o) { Some((o.cust_key, (o.order_key, o.order_date, o.ship_priority))) } else { None })
+        .arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .flat_map(|l| if shipped(&l) { Some((l.order_key, revenue(&l))) } else { None })
+        .half_join(&orders, through, |_, &revenue, o| {
+            if pending(o) { Some((o.cust_key, (o.order_key, o.order_date, o.ship_priority, revenue))) } else { None }
+        })
+        .flat_map(|x| x)
+        .half_join(&customers, through, |_, &joined, c| if building(c) { Some(joined) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .orders()
+        .filter(|o| pending(o))
+        .map(|o| (o.cust_key, (o.order_key, o.order_date, o.ship_priority)))
+        .half_join(&customers, through, |_, &(order_key, order_date, ship_priority), c| {
+            if building(c) { Some((order_key, (order_date, ship_priority))) } else { None }
+        })
+        .flat_map(|x| x)
+        .half_join(&lineitems, before, |&order_key, &(order_date, ship_priority), l| {
+            if shipped(l) { Some((order_key, order_date, ship_priority, revenue(l))) } else { None }
+        })
+        .flat_map(|x| x);
+
+    let changes3 =
+    collections
+        .customers()
+        .flat_map(|c| if building(&c) { Some((c.cust_key, ())) } else { None })
+        .half_join(&orders_by_customer, before, |_, &(), &(order_key, order_date, ship_priority)| (order_key, (order_date, ship_priority)))
+        .half_join(&lineitems, before, |&order_key, &(order_date, ship_priority), l| {
+            if shipped(l) { Some((order_key, order_date, ship_priority, revenue(l))) } else { None }
+        })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .explode(|(order_key, order_date, ship_priority, revenue)| Some(((order_key, (order_date, ship_priority)), revenue)))
+        .count_total()
+        .probe_with(probe);
+}
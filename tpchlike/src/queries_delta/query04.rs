@@ -0,0 +1,45 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn quarter(order: &Order) -> bool {
+    order.order_date >= create_date(1993, 7, 1) && order.order_date < create_date(1993, 10, 1)
+}
+
+// Relations, in the order of the delta query: the distinct keys of late orders, orders.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let orders = arrangements.orders.import(&collections.orders().scope());
+
+    let late =
+    collections
+        .lineitems()
+        .flat_map(|l| if l.commit_date < l.receipt_date { Some(l.order_key) } else { None })
+        .distinct_total()
+        .map(|order_key| (order_key, ()));
+
+    let late_arranged = late.arrange_by_key();
+
+    let changes1 =
+    late
+        .half_join(&orders, through, |_, &(), o| if quarter(o) { Some(o.order_priority) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .orders()
+        .filter(|o| quarter(o))
+        .map(|o| (o.order_key, o.order_priority))
+        .half_join(&late_arranged, before, |_, &priority, &()| priority);
+
+    changes1
+        .concat(&changes2)
+        .count_total()
+        .probe_with(probe);
+}
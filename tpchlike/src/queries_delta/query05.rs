@@ -0,0 +1,120 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn asia(region: &Region) -> bool { starts_with(&region.name[..], b"ASIA") }
+fn year(order: &Order) -> bool { order.order_date >= create_date(1994, 1, 1) && order.order_date < create_date(1995, 1, 1) }
+fn revenue(item: &LineItem) -> isize { (item.extended_price * (100 - item.discount) / 100) as isize }
+
+// Relations, in the order of the delta query: lineitem, orders, customer, supplier, nation, region.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let customers = arrangements.customers.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let regions = arrangements.regions.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+
+    let lineitems_by_supp = collections.lineitems().map(|l| (l.supp_key, (l.order_key, revenue(&l)))).arrange_by_key();
+    let orders_by_customer = collections.orders().flat_map(|o| if year(&o) { Some((o.cust_key, o.order_key)) } else { None }).arrange_by_key();
+    let customers_by_nation = collections.customers().map(|c| (c.nation_key, c.cust_key)).arrange_by_key();
+    let nations_by_region = collections.nations().map(|n| (n.region_key, n.nation_key)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .map(|l| (l.order_key, (l.supp_key, revenue(&l))))
+        .half_join(&orders, through, |_, &(supp_key, revenue), o| if year(o) { Some((o.cust_key, (supp_key, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, through, |_, &(supp_key, revenue), c| (supp_key, (c.nation_key, revenue)))
+        .half_join(&suppliers, through, |_, &(nation_key, revenue), s| if s.nation_key == nation_key { Some((nation_key, revenue)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&nations, through, |_, &(nation_key, revenue), n| (n.region_key, (nation_key, revenue)))
+        .half_join(&regions, through, |_, &joined, r| if asia(r) { Some(joined) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .orders()
+        .filter(|o| year(o))
+        .map(|o| (o.cust_key, o.order_key))
+        .half_join(&customers, through, |_, &order_key, c| (c.nation_key, order_key))
+        .half_join(&nations, through, |&nation_key, &order_key, n| (n.region_key, (order_key, nation_key)))
+        .half_join(&regions, through, |_, &joined, r| if asia(r) { Some(joined) } else { None })
+        .flat_map(|x| x)
+        .half_join(&lineitems, before, |_, &nation_key, l| (l.supp_key, (nation_key, revenue(l))))
+        .half_join(&suppliers, through, |_, &(nation_key, revenue), s| if s.nation_key == nation_key { Some((nation_key, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes3 =
+    collections
+        .customers()
+        .map(|c| (c.nation_key, c.cust_key))
+        .half_join(&nations, through, |&nation_key, &cust_key, n| (n.region_key, (cust_key, nation_key)))
+        .half_join(&regions, through, |_, &joined, r| if asia(r) { Some(joined) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders_by_customer, before, |_, &nation_key, &order_key| (order_key, nation_key))
+        .half_join(&lineitems, before, |_, &nation_key, l| (l.supp_key, (nation_key, revenue(l))))
+        .half_join(&suppliers, through, |_, &(nation_key, revenue), s| if s.nation_key == nation_key { Some((nation_key, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes4 =
+    collections
+        .suppliers()
+        .map(|s| (s.nation_key, s.supp_key))
+        .half_join(&nations, through, |&nation_key, &supp_key, n| (n.region_key, (supp_key, nation_key)))
+        .half_join(&regions, through, |_, &joined, r| if asia(r) { Some(joined) } else { None })
+        .flat_map(|x| x)
+        .half_join(&lineitems_by_supp, before, |_, &nation_key, &(order_key, revenue)| (order_key, (nation_key, revenue)))
+        .half_join(&orders, before, |_, &(nation_key, revenue), o| if year(o) { Some((o.cust_key, (nation_key, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, before, |_, &(nation_key, revenue), c| if c.nation_key == nation_key { Some((nation_key, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes5 =
+    collections
+        .nations()
+        .map(|n| (n.region_key, n.nation_key))
+        .half_join(&regions, through, |_, &nation_key, r| if asia(r) { Some((nation_key, ())) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers_by_nation, before, |&nation_key, &(), &cust_key| (cust_key, nation_key))
+        .half_join(&orders_by_customer, before, |_, &nation_key, &order_key| (order_key, nation_key))
+        .half_join(&lineitems, before, |_, &nation_key, l| (l.supp_key, (nation_key, revenue(l))))
+        .half_join(&suppliers, before, |_, &(nation_key, revenue), s| if s.nation_key == nation_key { Some((nation_key, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes6 =
+    collections
+        .regions()
+        .filter(|r| asia(r))
+        .map(|r| (r.region_key, ()))
+        .half_join(&nations_by_region, before, |_, &(), &nation_key| (nation_key, ()))
+        .half_join(&customers_by_nation, before, |&nation_key, &(), &cust_key| (cust_key, nation_key))
+        .half_join(&orders_by_customer, before, |_, &nation_key, &order_key| (order_key, nation_key))
+        .half_join(&lineitems, before, |_, &nation_key, l| (l.supp_key, (nation_key, revenue(l))))
+        .half_join(&suppliers, before, |_, &(nation_key, revenue), s| if s.nation_key == nation_key { Some((nation_key, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .concat(&changes4)
+        .concat(&changes5)
+        .concat(&changes6)
+        .explode(|(nation_key, revenue)| Some((nation_key, revenue)))
+        .count_total()
+        .probe_with(probe);
+}
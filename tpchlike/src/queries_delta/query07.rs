@@ -0,0 +1,127 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn nation(nation: &Nation) -> bool { starts_with(&nation.name, b"FRANCE") || starts_with(&nation.name, b"GERMANY") }
+
+/// The supplier, order key, year, and revenue of a line item shipped in the two years.
+fn shipment(item: &LineItem) -> Option<(usize, usize, u32, isize)> {
+    if create_date(1995, 1, 1) <= item.ship_date && item.ship_date <= create_date(1996, 12, 31) {
+        Some((item.supp_key, item.order_key, item.ship_date >> 16, (item.extended_price * (100 - item.discount)) as isize / 100))
+    }
+    else { None }
+}
+
+// Relations, in the order of the delta query: lineitem, orders, customer, supplier, the nation of the
+// supplier, and the nation of the customer; changes to nations drive a pipeline as each of the two.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let customers = arrangements.customers.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+
+    let lineitems_by_supp = collections.lineitems().flat_map(|l| shipment(&l).map(|(supp, order, year, revenue)| (supp, (order, year, revenue)))).arrange_by_key();
+    let orders_by_customer = collections.orders().map(|o| (o.cust_key, o.order_key)).arrange_by_key();
+    let customers_by_nation = collections.customers().map(|c| (c.nation_key, c.cust_key)).arrange_by_key();
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, s.supp_key)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .flat_map(|l| shipment(&l).map(|(supp, order, year, revenue)| (supp, (order, year, revenue))))
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(order, year, revenue), n| if nation(n) { Some((order, (n.name, year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders, through, |_, &joined, o| (o.cust_key, joined))
+        .half_join(&customers, through, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, through, |_, &(name_s, year, revenue), n| if nation(n) { Some((name_s, n.name, year, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .orders()
+        .map(|o| (o.cust_key, o.order_key))
+        .half_join(&customers, through, |_, &order, c| (c.nation_key, order))
+        .half_join(&nations, through, |_, &order, n| if nation(n) { Some((order, n.name)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&lineitems, before, |_, &name_c, l| shipment(l).map(|(supp, _, year, revenue)| (supp, (name_c, year, revenue))))
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(name_c, year, revenue), n| if nation(n) { Some((n.name, name_c, year, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes3 =
+    collections
+        .customers()
+        .map(|c| (c.nation_key, c.cust_key))
+        .half_join(&nations, through, |_, &cust, n| if nation(n) { Some((cust, n.name)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders_by_customer, before, |_, &name_c, &order| (order, name_c))
+        .half_join(&lineitems, before, |_, &name_c, l| shipment(l).map(|(supp, _, year, revenue)| (supp, (name_c, year, revenue))))
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(name_c, year, revenue), n| if nation(n) { Some((n.name, name_c, year, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes4 =
+    collections
+        .suppliers()
+        .map(|s| (s.nation_key, s.supp_key))
+        .half_join(&nations, through, |_, &supp, n| if nation(n) { Some((supp, n.name)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&lineitems_by_supp, before, |_, &name_s, &(order, year, revenue)| (order, (name_s, year, revenue)))
+        .half_join(&orders, before, |_, &joined, o| (o.cust_key, joined))
+        .half_join(&customers, before, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, through, |_, &(name_s, year, revenue), n| if nation(n) { Some((name_s, n.name, year, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes5 =
+    collections
+        .nations()
+        .filter(|n| nation(n))
+        .map(|n| (n.nation_key, n.name))
+        .half_join(&suppliers_by_nation, before, |_, &name_s, &supp| (supp, name_s))
+        .half_join(&lineitems_by_supp, before, |_, &name_s, &(order, year, revenue)| (order, (name_s, year, revenue)))
+        .half_join(&orders, before, |_, &joined, o| (o.cust_key, joined))
+        .half_join(&customers, before, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, through, |_, &(name_s, year, revenue), n| if nation(n) { Some((name_s, n.name, year, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    let changes6 =
+    collections
+        .nations()
+        .filter(|n| nation(n))
+        .map(|n| (n.nation_key, n.name))
+        .half_join(&customers_by_nation, before, |_, &name_c, &cust| (cust, name_c))
+        .half_join(&orders_by_customer, before, |_, &name_c, &order| (order, name_c))
+        .half_join(&lineitems, before, |_, &name_c, l| shipment(l).map(|(supp, _, year, revenue)| (supp, (name_c, year, revenue))))
+        .flat_map(|x| x)
+        .half_join(&suppliers, before, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, before, |_, &(name_c, year, revenue), n| if nation(n) { Some((n.name, name_c, year, revenue)) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .concat(&changes4)
+        .concat(&changes5)
+        .concat(&changes6)
+        .filter(|x| x.0 != x.1)
+        .explode(|(name_s, name_c, year, revenue)| Some(((name_s, name_c, year), revenue)))
+        .count_total()
+        .probe_with(probe);
+}
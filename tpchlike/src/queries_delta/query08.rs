@@ -0,0 +1,171 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::difference::DiffPair;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn america(region: &Region) -> bool { starts_with(&region.name, b"AMERICA") }
+fn brazil(nation: &Nation) -> bool { starts_with(&nation.name, b"BRAZIL") }
+fn steel(part: &Part) -> bool { part.typ.as_str() == "ECONOMY ANODIZED STEEL" }
+fn dated(order: &Order) -> bool { create_date(1995, 1, 1) <= order.order_date && order.order_date <= create_date(1996, 12, 31) }
+fn revenue(item: &LineItem) -> isize { (item.extended_price * (100 - item.discount)) as isize / 100 }
+
+// Relations, in the order of the delta query: lineitem, part, orders, customer, the nation of the customer,
+// region, supplier, and the nation of the supplier; changes to nations drive a pipeline as each of the two.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let parts = arrangements.parts.import(&scope);
+    let orders = arrangements.orders.import(&scope);
+    let customers = arrangements.customers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let regions = arrangements.regions.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+
+    let lineitems_by_part = collections.lineitems().map(|l| (l.part_key, (l.supp_key, l.order_key, revenue(&l)))).arrange_by_key();
+    let lineitems_by_supp = collections.lineitems().map(|l| (l.supp_key, (l.part_key, l.order_key, revenue(&l)))).arrange_by_key();
+    let orders_by_customer = collections.orders().flat_map(|o| if dated(&o) { Some((o.cust_key, (o.order_key, o.order_date >> 16))) } else { None }).arrange_by_key();
+    let customers_by_nation = collections.customers().map(|c| (c.nation_key, c.cust_key)).arrange_by_key();
+    let nations_by_region = collections.nations().map(|n| (n.region_key, n.nation_key)).arrange_by_key();
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, s.supp_key)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .map(|l| (l.part_key, (l.supp_key, l.order_key, revenue(&l))))
+        .half_join(&parts, through, |_, &(supp, order, revenue), p| if steel(p) { Some((order, (supp, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders, through, |_, &(supp, revenue), o| if dated(o) { Some((o.cust_key, (supp, o.order_date >> 16, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, through, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, through, |_, &joined, n| (n.region_key, joined))
+        .half_join(&regions, through, |_, &(supp, year, revenue), r| if america(r) { Some((supp, (year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(year, revenue), n| (year, brazil(n), revenue));
+
+    let changes2 =
+    collections
+        .parts()
+        .filter(|p| steel(p))
+        .map(|p| (p.part_key, ()))
+        .half_join(&lineitems_by_part, before, |_, &(), &(supp, order, revenue)| (order, (supp, revenue)))
+        .half_join(&orders, through, |_, &(supp, revenue), o| if dated(o) { Some((o.cust_key, (supp, o.order_date >> 16, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, through, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, through, |_, &joined, n| (n.region_key, joined))
+        .half_join(&regions, through, |_, &(supp, year, revenue), r| if america(r) { Some((supp, (year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(year, revenue), n| (year, brazil(n), revenue));
+
+    let changes3 =
+    collections
+        .orders()
+        .filter(|o| dated(o))
+        .map(|o| (o.cust_key, (o.order_key, o.order_date >> 16)))
+        .half_join(&customers, through, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, through, |_, &joined, n| (n.region_key, joined))
+        .half_join(&regions, through, |_, &joined, r| if america(r) { Some(joined) } else { None })
+        .flat_map(|x| x)
+        .half_join(&lineitems, before, |_, &year, l| (l.part_key, (l.supp_key, year, revenue(l))))
+        .half_join(&parts, before, |_, &(supp, year, revenue), p| if steel(p) { Some((supp, (year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(year, revenue), n| (year, brazil(n), revenue));
+
+    let changes4 =
+    collections
+        .customers()
+        .map(|c| (c.nation_key, c.cust_key))
+        .half_join(&nations, through, |_, &cust, n| (n.region_key, cust))
+        .half_join(&regions, through, |_, &cust, r| if america(r) { Some((cust, ())) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders_by_customer, before, |_, &(), &joined| joined)
+        .half_join(&lineitems, before, |_, &year, l| (l.part_key, (l.supp_key, year, revenue(l))))
+        .half_join(&parts, before, |_, &(supp, year, revenue), p| if steel(p) { Some((supp, (year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(year, revenue), n| (year, brazil(n), revenue));
+
+    let changes5 =
+    collections
+        .nations()
+        .map(|n| (n.region_key, n.nation_key))
+        .half_join(&regions, through, |_, &nation, r| if america(r) { Some((nation, ())) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers_by_nation, before, |_, &(), &cust| (cust, ()))
+        .half_join(&orders_by_customer, before, |_, &(), &joined| joined)
+        .half_join(&lineitems, before, |_, &year, l| (l.part_key, (l.supp_key, year, revenue(l))))
+        .half_join(&parts, before, |_, &(supp, year, revenue), p| if steel(p) { Some((supp, (year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(year, revenue), n| (year, brazil(n), revenue));
+
+    let changes6 =
+    collections
+        .regions()
+        .filter(|r| america(r))
+        .map(|r| (r.region_key, ()))
+        .half_join(&nations_by_region, before, |_, &(), &nation| (nation, ()))
+        .half_join(&customers_by_nation, before, |_, &(), &cust| (cust, ()))
+        .half_join(&orders_by_customer, before, |_, &(), &joined| joined)
+        .half_join(&lineitems, before, |_, &year, l| (l.part_key, (l.supp_key, year, revenue(l))))
+        .half_join(&parts, before, |_, &(supp, year, revenue), p| if steel(p) { Some((supp, (year, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &(year, revenue), n| (year, brazil(n), revenue));
+
+    let changes7 =
+    collections
+        .suppliers()
+        .map(|s| (s.nation_key, s.supp_key))
+        .half_join(&nations, through, |_, &supp, n| (supp, brazil(n)))
+        .half_join(&lineitems_by_supp, before, |_, &is_brazil, &(part, order, revenue)| (part, (order, is_brazil, revenue)))
+        .half_join(&parts, before, |_, &(order, is_brazil, revenue), p| if steel(p) { Some((order, (is_brazil, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders, before, |_, &(is_brazil, revenue), o| if dated(o) { Some((o.cust_key, (o.order_date >> 16, is_brazil, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, before, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, before, |_, &joined, n| (n.region_key, joined))
+        .half_join(&regions, before, |_, &joined, r| if america(r) { Some(joined) } else { None })
+        .flat_map(|x| x);
+
+    let changes8 =
+    collections
+        .nations()
+        .map(|n| (n.nation_key, brazil(&n)))
+        .half_join(&suppliers_by_nation, before, |_, &is_brazil, &supp| (supp, is_brazil))
+        .half_join(&lineitems_by_supp, before, |_, &is_brazil, &(part, order, revenue)| (part, (order, is_brazil, revenue)))
+        .half_join(&parts, before, |_, &(order, is_brazil, revenue), p| if steel(p) { Some((order, (is_brazil, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&orders, before, |_, &(is_brazil, revenue), o| if dated(o) { Some((o.cust_key, (o.order_date >> 16, is_brazil, revenue))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, before, |_, &joined, c| (c.nation_key, joined))
+        .half_join(&nations, before, |_, &joined, n| (n.region_key, joined))
+        .half_join(&regions, before, |_, &joined, r| if america(r) { Some(joined) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .concat(&changes4)
+        .concat(&changes5)
+        .concat(&changes6)
+        .concat(&changes7)
+        .concat(&changes8)
+        .explode(|(year, is_brazil, revenue)| Some((year, DiffPair::new(if is_brazil { revenue } else { 0 }, revenue))))
+        .count_total()
+        .probe_with(probe);
+}
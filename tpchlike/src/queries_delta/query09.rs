@@ -0,0 +1,116 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn substring(source: &[u8], query: &[u8]) -> bool {
+    (0 .. (source.len() - query.len())).any(|offset|
+        (0 .. query.len()).all(|i| source[i + offset] == query[i])
+    )
+}
+
+fn green(part: &Part) -> bool { substring(part.name.as_bytes(), b"green") }
+fn revenue(item: &LineItem) -> i64 { item.extended_price * (100 - item.discount) / 100 }
+
+// Relations, in the order of the delta query: lineitem, part, partsupp, orders, supplier, nation.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let parts = arrangements.parts.import(&scope);
+    let orders = arrangements.orders.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+    let lineitems_by_partsupp = arrangements.lineitems_by_partsupp.import(&scope);
+    let partsupps = arrangements.partsupps.import(&scope);
+
+    let lineitems_by_part = collections.lineitems().map(|l| (l.part_key, (l.supp_key, l.order_key, revenue(&l), l.quantity))).arrange_by_key();
+    let lineitems_by_supp = collections.lineitems().map(|l| (l.supp_key, (l.part_key, l.order_key, revenue(&l), l.quantity))).arrange_by_key();
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, s.supp_key)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .map(|l| (l.part_key, (l.supp_key, l.order_key, revenue(&l), l.quantity)))
+        .half_join(&parts, through, |&part, &(supp, order, revenue, quantity), p| if green(p) { Some(((part, supp), (order, revenue, quantity))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&partsupps, through, |&(_, supp), &(order, revenue, quantity), ps| (order, (supp, (revenue - ps.supplycost * quantity) as isize)))
+        .half_join(&orders, through, |_, &(supp, amount), o| (supp, (o.order_date >> 16, amount)))
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |&nation, &(year, amount), n| (nation, year, n.name, amount));
+
+    let changes2 =
+    collections
+        .parts()
+        .filter(|p| green(p))
+        .map(|p| (p.part_key, ()))
+        .half_join(&lineitems_by_part, before, |&part, &(), &(supp, order, revenue, quantity)| ((part, supp), (order, revenue, quantity)))
+        .half_join(&partsupps, through, |&(_, supp), &(order, revenue, quantity), ps| (order, (supp, (revenue - ps.supplycost * quantity) as isize)))
+        .half_join(&orders, through, |_, &(supp, amount), o| (supp, (o.order_date >> 16, amount)))
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |&nation, &(year, amount), n| (nation, year, n.name, amount));
+
+    let changes3 =
+    collections
+        .partsupps()
+        .map(|ps| (ps.part_key, (ps.supp_key, ps.supplycost)))
+        .half_join(&parts, before, |&part, &(supp, cost), p| if green(p) { Some(((part, supp), cost)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&lineitems_by_partsupp, before, |&(_, supp), &cost, l| (l.order_key, (supp, (revenue(l) - cost * l.quantity) as isize)))
+        .half_join(&orders, through, |_, &(supp, amount), o| (supp, (o.order_date >> 16, amount)))
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |&nation, &(year, amount), n| (nation, year, n.name, amount));
+
+    let changes4 =
+    collections
+        .orders()
+        .map(|o| (o.order_key, o.order_date >> 16))
+        .half_join(&lineitems, before, |_, &year, l| (l.part_key, (l.supp_key, year, revenue(l), l.quantity)))
+        .half_join(&parts, before, |&part, &(supp, year, revenue, quantity), p| if green(p) { Some(((part, supp), (year, revenue, quantity))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&partsupps, before, |&(_, supp), &(year, revenue, quantity), ps| (supp, (year, (revenue - ps.supplycost * quantity) as isize)))
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |&nation, &(year, amount), n| (nation, year, n.name, amount));
+
+    let changes5 =
+    collections
+        .suppliers()
+        .map(|s| (s.nation_key, s.supp_key))
+        .half_join(&nations, through, |&nation, &supp, n| (supp, (nation, n.name)))
+        .half_join(&lineitems_by_supp, before, |&supp, &(nation, name), &(part, order, revenue, quantity)| (part, (supp, order, nation, name, revenue, quantity)))
+        .half_join(&parts, before, |&part, &(supp, order, nation, name, revenue, quantity), p| {
+            if green(p) { Some(((part, supp), (order, nation, name, revenue, quantity))) } else { None }
+        })
+        .flat_map(|x| x)
+        .half_join(&partsupps, before, |_, &(order, nation, name, revenue, quantity), ps| (order, (nation, name, (revenue - ps.supplycost * quantity) as isize)))
+        .half_join(&orders, before, |_, &(nation, name, amount), o| (nation, o.order_date >> 16, name, amount));
+
+    let changes6 =
+    collections
+        .nations()
+        .map(|n| (n.nation_key, n.name))
+        .half_join(&suppliers_by_nation, before, |&nation, &name, &supp| (supp, (nation, name)))
+        .half_join(&lineitems_by_supp, before, |&supp, &(nation, name), &(part, order, revenue, quantity)| (part, (supp, order, nation, name, revenue, quantity)))
+        .half_join(&parts, before, |&part, &(supp, order, nation, name, revenue, quantity), p| {
+            if green(p) { Some(((part, supp), (order, nation, name, revenue, quantity))) } else { None }
+        })
+        .flat_map(|x| x)
+        .half_join(&partsupps, before, |_, &(order, nation, name, revenue, quantity), ps| (order, (nation, name, (revenue - ps.supplycost * quantity) as isize)))
+        .half_join(&orders, before, |_, &(nation, name, amount), o| (nation, o.order_date >> 16, name, amount));
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .concat(&changes4)
+        .concat(&changes5)
+        .concat(&changes6)
+        .explode(|(nation, year, name, amount)| Some(((nation, (year, name)), amount)))
+        .count_total()
+        .probe_with(probe);
+}
@@ -0,0 +1,76 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn returned(item: &LineItem) -> bool { starts_with(&item.return_flag, b"R") }
+fn quarter(order: &Order) -> bool { create_date(1993,10,1) < order.order_date && order.order_date <= create_date(1994,1,1) }
+fn revenue(item: &LineItem) -> isize { (item.extended_price * (100 - item.discount)) as isize }
+
+// Relations, in the order of the delta query: lineitem, orders, customer, nation.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let customers = arrangements.customers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+
+    let orders_by_customer = collections.orders().flat_map(|o| if quarter(&o) { Some((o.cust_key, o.order_key)) } else { None }).arrange_by_key();
+    let customers_by_nation = collections.customers().map(|c| (c.nation_key, c)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .flat_map(|l| if returned(&l) { Some((l.order_key, revenue(&l))) } else { None })
+        .half_join(&orders, through, |_, &revenue, o| if quarter(o) { Some((o.cust_key, revenue)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, through, |_, &revenue, c| (c.nation_key, (c.clone(), revenue)))
+        .half_join(&nations, through, |_, &(ref c, revenue), n| (c.clone(), n.name, revenue));
+
+    let changes2 =
+    collections
+        .orders()
+        .filter(|o| quarter(o))
+        .map(|o| (o.order_key, o.cust_key))
+        .half_join(&lineitems, before, |_, &cust, l| if returned(l) { Some((cust, revenue(l))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&customers, through, |_, &revenue, c| (c.nation_key, (c.clone(), revenue)))
+        .half_join(&nations, through, |_, &(ref c, revenue), n| (c.clone(), n.name, revenue));
+
+    let changes3 =
+    collections
+        .customers()
+        .map(|c| (c.cust_key, c))
+        .half_join(&orders_by_customer, before, |_, c, &order| (order, c.clone()))
+        .half_join(&lineitems, before, |_, c, l| if returned(l) { Some((c.nation_key, (c.clone(), revenue(l)))) } else { None })
+        .flat_map(|x| x)
+        .half_join(&nations, through, |_, &(ref c, revenue), n| (c.clone(), n.name, revenue));
+
+    let changes4 =
+    collections
+        .nations()
+        .map(|n| (n.nation_key, n.name))
+        .half_join(&customers_by_nation, before, |_, &name, c| (c.cust_key, (c.clone(), name)))
+        .half_join(&orders_by_customer, before, |_, joined, &order| (order, joined.clone()))
+        .half_join(&lineitems, before, |_, &(ref c, name), l| if returned(l) { Some((c.clone(), name, revenue(l))) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .concat(&changes4)
+        .explode(|(c, name, revenue)| Some(((c.nation_key, ((c.cust_key, c.name, c.phone, c.address, c.comment), name)), revenue)))
+        .count_total()
+        .probe_with(probe);
+}
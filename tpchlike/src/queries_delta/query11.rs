@@ -0,0 +1,64 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn germany(nation: &Nation) -> bool { starts_with(&nation.name, b"GERMANY") }
+fn value(partsupp: &PartSupp) -> isize { (partsupp.supplycost as isize) * (partsupp.availqty as isize) }
+
+// Relations, in the order of the delta query: partsupp, supplier, nation.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.partsupps().scope();
+
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+
+    let partsupps_by_supp = collections.partsupps().map(|ps| (ps.supp_key, (ps.part_key, value(&ps)))).arrange_by_key();
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, s.supp_key)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .partsupps()
+        .map(|ps| (ps.supp_key, (ps.part_key, value(&ps))))
+        .half_join(&suppliers, through, |_, &joined, s| (s.nation_key, joined))
+        .half_join(&nations, through, |_, &joined, n| if germany(n) { Some(joined) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .suppliers()
+        .map(|s| (s.nation_key, s.supp_key))
+        .half_join(&nations, through, |_, &supp, n| if germany(n) { Some((supp, ())) } else { None })
+        .flat_map(|x| x)
+        .half_join(&partsupps_by_supp, before, |_, &(), &joined| joined);
+
+    let changes3 =
+    collections
+        .nations()
+        .filter(|n| germany(n))
+        .map(|n| (n.nation_key, ()))
+        .half_join(&suppliers_by_nation, before, |_, &(), &supp| (supp, ()))
+        .half_join(&partsupps_by_supp, before, |_, &(), &joined| joined);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .explode(|(part_key, value)| Some((((), part_key), value)))
+        .reduce(|_part_key, s, t| {
+            let threshold: isize = s.iter().map(|x| x.1 as isize).sum::<isize>() / 10000;
+            t.extend(s.iter().filter(|x| x.1 > threshold).map(|&(&a,b)| (a, b)));
+        })
+        .map(|(_, part_key)| part_key)
+        .count_total()
+        .probe_with(probe);
+}
@@ -0,0 +1,53 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::difference::DiffPair;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn high(order: &Order) -> bool { starts_with(&order.order_priority, b"1-URGENT") || starts_with(&order.order_priority, b"2-HIGH") }
+
+/// The order key and ship mode of a line item matching the query's shipping predicates.
+fn shipment(item: &LineItem) -> Option<(usize, [u8; 10])> {
+    if (starts_with(&item.ship_mode, b"MAIL") || starts_with(&item.ship_mode, b"SHIP")) &&
+        item.commit_date < item.receipt_date && item.ship_date < item.commit_date &&
+        create_date(1994,1,1) <= item.receipt_date && item.receipt_date < create_date(1995,1,1) {
+        Some((item.order_key, item.ship_mode))
+    }
+    else { None }
+}
+
+// Relations, in the order of the delta query: lineitem, orders.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let lineitems = arrangements.lineitems.import(&scope);
+
+    let changes1 =
+    collections
+        .lineitems()
+        .flat_map(|l| shipment(&l))
+        .half_join(&orders, through, |_, &ship_mode, o| (ship_mode, high(o)));
+
+    let changes2 =
+    collections
+        .orders()
+        .map(|o| (o.order_key, high(&o)))
+        .half_join(&lineitems, before, |_, &is_high, l| shipment(l).map(|(_, ship_mode)| (ship_mode, is_high)))
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .explode(|(ship_mode, is_high)| Some((ship_mode, if is_high { DiffPair::new(1, 0) } else { DiffPair::new(0, 1) })))
+        .count_total()
+        .probe_with(probe);
+}
@@ -0,0 +1,52 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::difference::DiffPair;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn promo(part: &Part) -> bool { starts_with(&part.typ.as_bytes(), b"PROMO") }
+
+/// The part key and revenue of a line item shipped in the month.
+fn shipment(item: &LineItem) -> Option<(usize, isize)> {
+    if create_date(1995,9,1) <= item.ship_date && item.ship_date < create_date(1995,10,1) {
+        Some((item.part_key, (item.extended_price * (100 - item.discount) / 100) as isize))
+    }
+    else { None }
+}
+
+// Relations, in the order of the delta query: lineitem, part.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let parts = arrangements.parts.import(&scope);
+
+    let lineitems_by_part = collections.lineitems().flat_map(|l| shipment(&l)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .flat_map(|l| shipment(&l))
+        .half_join(&parts, through, |_, &revenue, p| (promo(p), revenue));
+
+    let changes2 =
+    collections
+        .parts()
+        .map(|p| (p.part_key, promo(&p)))
+        .half_join(&lineitems_by_part, before, |_, &is_promo, &revenue| (is_promo, revenue));
+
+    changes1
+        .concat(&changes2)
+        .explode(|(is_promo, revenue)| Some(((), DiffPair::new(revenue, if is_promo { revenue } else { 0 }))))
+        .count_total()
+        .probe_with(probe);
+}
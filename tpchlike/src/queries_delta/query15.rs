@@ -0,0 +1,71 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+// Relations, in the order of the delta query: the derived top suppliers, supplier.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.suppliers().scope();
+
+    let suppliers = arrangements.suppliers.import(&scope);
+
+    // revenue by supplier
+    let revenue =
+        collections
+            .lineitems()
+            .explode(|item|
+                if create_date(1996, 1, 1) <= item.ship_date && item.ship_date < create_date(1996,4,1) {
+                    Some((item.supp_key, (item.extended_price * (100 - item.discount) / 100) as isize))
+                }
+                else { None }
+            );
+
+    // suppliers with maximum revenue
+    let top_suppliers =
+        revenue
+            // do a hierarchical min, to improve update perf.
+            .map(|key| ((key % 1000) as u16, key))
+            .reduce(|_k, s, t| {
+                let max = s.iter().map(|x| x.1).max().unwrap();
+                t.extend(s.iter().filter(|x| x.1 == max).map(|&(&a,b)| (a,b)));
+            })
+            .map(|(_,key)| ((key % 100) as u8, key))
+            .reduce(|_k, s, t| {
+                let max = s.iter().map(|x| x.1).max().unwrap();
+                t.extend(s.iter().filter(|x| x.1 == max).map(|&(&a,b)| (a,b)));
+            })
+            .map(|(_,key)| ((key % 10) as u8, key))
+            .reduce(|_k, s, t| {
+                let max = s.iter().map(|x| x.1).max().unwrap();
+                t.extend(s.iter().filter(|x| x.1 == max).map(|&(&a,b)| (a,b)));
+            })
+            .map(|(_,key)| ((), key))
+            .reduce(|_k, s, t| {
+                let max = s.iter().map(|x| x.1).max().unwrap();
+                t.extend(s.iter().filter(|x| x.1 == max).map(|&(&a,b)| (a,b)));
+            })
+            .map(|(_, key)| key)
+            .count_total();
+
+    let top_suppliers_by_key = top_suppliers.arrange_by_key();
+
+    let changes1 =
+    top_suppliers
+        .half_join(&suppliers, through, |&supp_key, &count, s| (supp_key, ((s.name, s.address.clone(), s.phone), count)));
+
+    let changes2 =
+    collections
+        .suppliers()
+        .map(|s| (s.supp_key, (s.name, s.address, s.phone)))
+        .half_join(&top_suppliers_by_key, before, |&supp_key, record, &count| (supp_key, (record.clone(), count)));
+
+    changes1
+        .concat(&changes2)
+        .probe_with(probe);
+}
@@ -0,0 +1,70 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use regex::Regex;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn part(part: &Part) -> bool {
+    !starts_with(&part.brand, b"Brand#45") && !starts_with(&part.typ.as_bytes(), b"MEDIUM POLISHED") && [49, 14, 23, 45, 19, 3, 36, 9].contains(&part.size)
+}
+
+// Relations, in the order of the delta query: partsupp, part, supplier. The antijoin with complaining
+// suppliers is a join with all other suppliers, which agrees because each partsupp has its supplier.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let regex = Regex::new("Customer.*Complaints").expect("Regex construction failed");
+
+    let scope = collections.partsupps().scope();
+
+    let parts = arrangements.parts.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+
+    let partsupps_by_part = collections.partsupps().map(|ps| (ps.part_key, ps.supp_key)).arrange_by_key();
+    let partsupps_by_supp = collections.partsupps().map(|ps| (ps.supp_key, ps.part_key)).arrange_by_key();
+
+    let changes1 = {
+        let regex = regex.clone();
+        collections
+            .partsupps()
+            .map(|ps| (ps.part_key, ps.supp_key))
+            .half_join(&parts, through, |_, &supp, p| if part(p) { Some((supp, (p.brand, p.typ.clone(), p.size))) } else { None })
+            .flat_map(|x| x)
+            .half_join(&suppliers, through, move |_, record, s| if !regex.is_match(&s.comment) { Some(record.clone()) } else { None })
+            .flat_map(|x| x)
+    };
+
+    let changes2 = {
+        let regex = regex.clone();
+        collections
+            .parts()
+            .filter(|p| part(p))
+            .map(|p| (p.part_key, (p.brand, p.typ, p.size)))
+            .half_join(&partsupps_by_part, before, |_, record, &supp| (supp, record.clone()))
+            .half_join(&suppliers, through, move |_, record, s| if !regex.is_match(&s.comment) { Some(record.clone()) } else { None })
+            .flat_map(|x| x)
+    };
+
+    let changes3 =
+    collections
+        .suppliers()
+        .flat_map(move |s| if !regex.is_match(&s.comment) { Some((s.supp_key, ())) } else { None })
+        .half_join(&partsupps_by_supp, before, |_, &(), &part_key| (part_key, ()))
+        .half_join(&parts, before, |_, &(), p| if part(p) { Some((p.brand, p.typ.clone(), p.size)) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .count_total()
+        .probe_with(probe);
+}
@@ -0,0 +1,51 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn part(part: &Part) -> bool { &part.brand[..8] == b"Brand#23" && &part.container[..7] == b"MED BOX" }
+
+// Relations, in the order of the delta query: lineitem, part.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let parts = arrangements.parts.import(&scope);
+
+    let lineitems_by_part = collections.lineitems().map(|l| (l.part_key, (l.quantity, l.extended_price))).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .map(|l| (l.part_key, (l.quantity, l.extended_price)))
+        .half_join(&parts, through, |&part_key, &joined, p| if part(p) { Some((part_key, joined)) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .parts()
+        .filter(|p| part(p))
+        .map(|p| (p.part_key, ()))
+        .half_join(&lineitems_by_part, before, |&part_key, &(), &joined| (part_key, joined));
+
+    changes1
+        .concat(&changes2)
+        .reduce(|_k, s, t| {
+
+            // determine the total and count of quantity.
+            let total: i64 = s.iter().map(|x| (x.0).0 * (x.1 as i64)).sum();
+            let count: i64 = s.iter().map(|x| x.1 as i64).sum();
+
+            // produce as output those tuples with below-threshold quantity.
+            t.extend(s.iter().filter(|&&(&(quantity,_),_)| 5 * quantity * count < total)
+                             .map(|&(&(_,price),count)| (price, count)));
+        })
+        .explode(|(_part, price)| Some(((), price as isize)))
+        .count_total()
+        .probe_with(probe);
+}
@@ -0,0 +1,51 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use super::{through, before};
+
+// Relations, in the order of the delta query: the derived large orders, orders, customer.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let customers = arrangements.customers.import(&scope);
+
+    let large =
+    collections
+        .lineitems()
+        .explode(|l| Some((l.order_key, l.quantity as isize)))
+        .count_total()
+        .filter(|&(_key, cnt)| cnt > 300);
+
+    let large_by_order = large.arrange_by_key();
+    let orders_by_customer = collections.orders().map(|o| (o.cust_key, (o.order_key, o.order_date, o.total_price))).arrange_by_key();
+
+    let changes1 =
+    large
+        .half_join(&orders, through, |&o_key, &quant, o| (o.cust_key, (o_key, o.order_date, o.total_price, quant)))
+        .half_join(&customers, through, |&cust_key, &joined, c| (cust_key, (joined, c.name.clone())));
+
+    let changes2 =
+    collections
+        .orders()
+        .map(|o| (o.order_key, (o.cust_key, o.order_date, o.total_price)))
+        .half_join(&large_by_order, before, |&o_key, &(cust_key, date, price), &quant| (cust_key, (o_key, date, price, quant)))
+        .half_join(&customers, through, |&cust_key, &joined, c| (cust_key, (joined, c.name.clone())));
+
+    let changes3 =
+    collections
+        .customers()
+        .map(|c| (c.cust_key, c.name))
+        .half_join(&orders_by_customer, before, |&cust_key, name, &(o_key, date, price)| (o_key, (cust_key, name.clone(), date, price)))
+        .half_join(&large_by_order, before, |&o_key, &(cust_key, ref name, date, price), &quant| (cust_key, ((o_key, date, price, quant), name.clone())));
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .probe_with(probe);
+}
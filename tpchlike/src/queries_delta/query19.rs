@@ -0,0 +1,59 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+/// The part key, quantity, and revenue of a line item delivered in person by air.
+fn shipment(item: &LineItem) -> Option<(usize, (i64, isize))> {
+    if (starts_with(&item.ship_mode, b"AIR") || starts_with(&item.ship_mode, b"AIR REG")) && starts_with(&item.ship_instruct, b"DELIVER IN PERSON") {
+        Some((item.part_key, (item.quantity, (item.extended_price * (100 - item.discount) / 100) as isize)))
+    }
+    else { None }
+}
+
+/// True when the quantity and part satisfy any of the query's three conditions.
+fn matches(quantity: i64, part: &Part) -> bool {
+    let (brand, container, size) = (&part.brand, &part.container, part.size);
+    (quantity >= 1 && quantity <= 11 && starts_with(brand, b"Brand#12") && 1 <= size && size <= 5 && (starts_with(container, b"SM CASE") || starts_with(container, b"SM BOX") || starts_with(container, b"SM PACK") || starts_with(container, b"MED PKG"))) ||
+    (quantity >= 10 && quantity <= 20 && starts_with(brand, b"Brand#23") && 1 <= size && size <= 10 && (starts_with(container, b"MED BAG") || starts_with(container, b"MED BOX") || starts_with(container, b"MED PKG") || starts_with(container, b"MED PACK"))) ||
+    (quantity >= 20 && quantity <= 30 && starts_with(brand, b"Brand#34") && 1 <= size && size <= 15 && (starts_with(container, b"LG CASE") || starts_with(container, b"LG BOX") || starts_with(container, b"LG PACK") || starts_with(container, b"LG PKG")))
+}
+
+// Relations, in the order of the delta query: lineitem, part.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let parts = arrangements.parts.import(&scope);
+
+    let lineitems_by_part = collections.lineitems().flat_map(|l| shipment(&l)).arrange_by_key();
+
+    let changes1 =
+    collections
+        .lineitems()
+        .flat_map(|l| shipment(&l))
+        .half_join(&parts, through, |_, &(quantity, revenue), p| if matches(quantity, p) { Some(revenue) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .parts()
+        .map(|p| (p.part_key, p))
+        .half_join(&lineitems_by_part, before, |_, p, &(quantity, revenue)| if matches(quantity, p) { Some(revenue) } else { None })
+        .flat_map(|x| x);
+
+    changes1
+        .concat(&changes2)
+        .explode(|revenue| Some(((), revenue)))
+        .count_total()
+        .probe_with(probe);
+}
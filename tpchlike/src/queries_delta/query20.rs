@@ -0,0 +1,123 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::operators::reduce::ReduceCore;
+use differential_dataflow::trace::implementations::ord::OrdValSpine as DefaultValTrace;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn forest(part: &Part) -> bool { part.name.as_bytes() == b"forest" }
+fn canada(nation: &Nation) -> bool { starts_with(&nation.name, b"CANADA") }
+
+/// The part key, supplier key, and quantity of a line item shipped in the year.
+fn shipment(item: &LineItem) -> Option<(usize, (usize, i64))> {
+    if item.ship_date >= create_date(1994, 1, 1) && item.ship_date < create_date(1995, 1, 1) {
+        Some((item.part_key, (item.supp_key, item.quantity)))
+    }
+    else { None }
+}
+
+// Relations, in the order of the delta queries: lineitem and part for the shipped quantities; then partsupp,
+// part, and the shipped quantities for the qualifying suppliers; then those suppliers, supplier, and nation.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let parts = arrangements.parts.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let partsupps = arrangements.partsupps.import(&scope);
+
+    let lineitems_by_part = collections.lineitems().flat_map(|l| shipment(&l)).arrange_by_key();
+    let partsupps_by_part = collections.partsupps().map(|ps| (ps.part_key, (ps.supp_key, ps.availqty))).arrange_by_key();
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, (s.supp_key, s.name, s.address))).arrange_by_key();
+
+    let shipped1 =
+    collections
+        .lineitems()
+        .flat_map(|l| shipment(&l))
+        .half_join(&parts, through, |&part_key, &(supp_key, quantity), p| if forest(p) { Some(((part_key, supp_key), quantity)) } else { None })
+        .flat_map(|x| x);
+
+    let shipped2 =
+    collections
+        .parts()
+        .filter(|p| forest(p))
+        .map(|p| (p.part_key, ()))
+        .half_join(&lineitems_by_part, before, |&part_key, &(), &(supp_key, quantity)| ((part_key, supp_key), quantity));
+
+    let available =
+    shipped1
+        .concat(&shipped2)
+        .explode(|(key, quantity)| Some(((key, ()), quantity as isize)))
+        .reduce_abelian::<_,_,DefaultValTrace<_,_,_,_>,_>(|_k,s,t| t.push((s[0].1, 1)));
+
+    let qualified1 =
+    collections
+        .partsupps()
+        .map(|ps| (ps.part_key, (ps.supp_key, ps.availqty)))
+        .half_join(&parts, through, |&part_key, &(supp_key, avail), p| if forest(p) { Some(((part_key, supp_key), avail)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&available, through, |&(_, supp_key), &avail1, &avail2| if avail1 > avail2 as i32 / 2 { Some(supp_key) } else { None })
+        .flat_map(|x| x);
+
+    let qualified2 =
+    collections
+        .parts()
+        .filter(|p| forest(p))
+        .map(|p| (p.part_key, ()))
+        .half_join(&partsupps_by_part, before, |&part_key, &(), &(supp_key, avail)| ((part_key, supp_key), avail))
+        .half_join(&available, through, |&(_, supp_key), &avail1, &avail2| if avail1 > avail2 as i32 / 2 { Some(supp_key) } else { None })
+        .flat_map(|x| x);
+
+    let qualified3 =
+    available
+        .as_collection(|&key, &avail2| (key, avail2))
+        .half_join(&partsupps, before, |&(part_key, supp_key), &avail2, ps| if ps.availqty > avail2 as i32 / 2 { Some((part_key, supp_key)) } else { None })
+        .flat_map(|x| x)
+        .half_join(&parts, before, |_, &supp_key, p| if forest(p) { Some(supp_key) } else { None })
+        .flat_map(|x| x);
+
+    let qualified =
+    qualified1
+        .concat(&qualified2)
+        .concat(&qualified3);
+
+    let qualified_by_supp = qualified.map(|supp_key| (supp_key, ())).arrange_by_key();
+
+    let changes1 =
+    qualified
+        .map(|supp_key| (supp_key, ()))
+        .half_join(&suppliers, through, |_, &(), s| (s.nation_key, (s.name, s.address.clone())))
+        .half_join(&nations, through, |&nation_key, joined, n| if canada(n) { Some((nation_key, (joined.clone(), n.name))) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .suppliers()
+        .map(|s| (s.supp_key, (s.name, s.address, s.nation_key)))
+        .half_join(&qualified_by_supp, before, |_, &(name, ref addr, nation_key), &()| (nation_key, (name, addr.clone())))
+        .half_join(&nations, through, |&nation_key, joined, n| if canada(n) { Some((nation_key, (joined.clone(), n.name))) } else { None })
+        .flat_map(|x| x);
+
+    let changes3 =
+    collections
+        .nations()
+        .filter(|n| canada(n))
+        .map(|n| (n.nation_key, n.name))
+        .half_join(&suppliers_by_nation, before, |&nation_key, &n_name, &(supp_key, name, ref addr)| (supp_key, (nation_key, (name, addr.clone()), n_name)))
+        .half_join(&qualified_by_supp, before, |_, &(nation_key, ref joined, n_name), &()| (nation_key, (joined.clone(), n_name)));
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .probe_with(probe);
+}
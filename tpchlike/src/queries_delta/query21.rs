@@ -0,0 +1,93 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::operators::ThresholdTotal;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+
+use {Collections, Arrangements};
+use types::*;
+use super::{through, before};
+
+fn starts_with(source: &[u8], query: &[u8]) -> bool {
+    source.len() >= query.len() && &source[..query.len()] == query
+}
+
+fn finished(order: &Order) -> bool { starts_with(&order.order_status, b"F") }
+fn saudi_arabia(nation: &Nation) -> bool { starts_with(&nation.name, b"SAUDI ARABIA") }
+
+// Relations, in the order of the delta queries: lineitem and orders for the lineitems of "F" orders; then
+// the derived late suppliers, supplier, and nation.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let scope = collections.lineitems().scope();
+
+    let orders = arrangements.orders.import(&scope);
+    let suppliers = arrangements.suppliers.import(&scope);
+    let nations = arrangements.nations.import(&scope);
+    let lineitems_by_order = arrangements.lineitems.import(&scope);
+
+    let suppliers_by_nation = collections.suppliers().map(|s| (s.nation_key, (s.supp_key, s.name))).arrange_by_key();
+
+    let lineitems1 =
+    collections
+        .lineitems()
+        .map(|l| (l.order_key, (l.supp_key, l.receipt_date > l.commit_date)))
+        .half_join(&orders, through, |&order_key, &joined, o| if finished(o) { Some((order_key, joined)) } else { None })
+        .flat_map(|x| x);
+
+    let lineitems2 =
+    collections
+        .orders()
+        .filter(|o| finished(o))
+        .map(|o| (o.order_key, ()))
+        .half_join(&lineitems_by_order, before, |&order_key, &(), l| (order_key, (l.supp_key, l.receipt_date > l.commit_date)));
+
+    // lineitems relevant to "F" orders.
+    let lineitems = lineitems1.concat(&lineitems2);
+
+    let lateitems = lineitems.filter(|l| (l.1).1);
+    let lateorders = lateitems.map(|l| l.0).distinct_total();
+
+    let problems =
+    lineitems
+        .map(|(order_key, (_supp_key, is_late))| (order_key, is_late))
+        .semijoin(&lateorders)    //- on_time and late, but just one late -\\
+        .reduce(|_order_key, s, t| if s.len() == 2 && s[1].1 == 1 { t.push(((), 1)); })
+        .map(|(order_key, _)| order_key);
+
+    let latesupps =
+    lateitems
+        .semijoin(&problems)
+        .map(|(_order_key, (supp_key, _))| (supp_key, ()));
+
+    let latesupps_by_supp = latesupps.arrange_by_key();
+
+    let changes1 =
+    latesupps
+        .half_join(&suppliers, through, |_, &(), s| (s.nation_key, s.name))
+        .half_join(&nations, through, |&nation_key, &name, n| if saudi_arabia(n) { Some((nation_key, name)) } else { None })
+        .flat_map(|x| x);
+
+    let changes2 =
+    collections
+        .suppliers()
+        .map(|s| (s.supp_key, (s.nation_key, s.name)))
+        .half_join(&latesupps_by_supp, before, |_, &joined, &()| joined)
+        .half_join(&nations, through, |&nation_key, &name, n| if saudi_arabia(n) { Some((nation_key, name)) } else { None })
+        .flat_map(|x| x);
+
+    let changes3 =
+    collections
+        .nations()
+        .filter(|n| saudi_arabia(n))
+        .map(|n| (n.nation_key, ()))
+        .half_join(&suppliers_by_nation, before, |&nation_key, &(), &(supp_key, name)| (supp_key, (nation_key, name)))
+        .half_join(&latesupps_by_supp, before, |_, &joined, &()| joined);
+
+    changes1
+        .concat(&changes2)
+        .concat(&changes3)
+        .count_total()
+        .probe_with(probe);
+}
@@ -0,0 +1,66 @@
+use timely::dataflow::*;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+
+use differential_dataflow::operators::*;
+use differential_dataflow::difference::DiffPair;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::operators::reduce::ReduceCore;
+use differential_dataflow::operators::ThresholdTotal;
+
+use differential_dataflow::trace::implementations::ord::OrdValSpine as DefaultValTrace;
+
+use {Collections, Arrangements};
+use super::{through, before};
+
+// Relations, in the order of the delta query: customer, the derived customers with orders. The antijoin
+// is the qualifying customers less their join with customers with orders.
+pub fn query<G: Scope<Timestamp=usize>>(collections: &mut Collections<G>, _arrangements: &mut Arrangements, probe: &mut ProbeHandle<usize>) {
+
+    let customers =
+    collections
+        .customers()
+        .flat_map(|c| {
+            if c.acctbal > 0 {
+                match &[c.phone[0], c.phone[1]] {
+                    b"13" | b"31" | b"23" | b"29" | b"30" | b"18" | b"17" => {
+                        Some((((c.phone[1] as u16) << 8) + c.phone[0] as u16, c.acctbal, c.cust_key))
+                    },
+                    _ => None,
+                }
+            }
+            else { None }
+        });
+
+    let averages =
+    customers
+        .explode(|(cc, acctbal, _)| Some(((cc, ()), DiffPair::new(acctbal as isize, 1))))
+        .reduce_abelian::<_,_,DefaultValTrace<_,_,_,_>,_>(|_k,s,t| t.push((s[0].1, 1)));
+
+    let customers_by_key = customers.map(|(cc, acct, key)| (key, (cc, acct))).arrange_by_key();
+
+    let ordered = collections.orders().map(|o| o.cust_key).distinct_total();
+    let ordered_by_key = ordered.map(|key| (key, ())).arrange_by_key();
+
+    let changes1 =
+    customers
+        .map(|(cc, acct, key)| (key, (cc, acct)))
+        .half_join(&ordered_by_key, through, |&key, &joined, &()| (key, joined));
+
+    let changes2 =
+    ordered
+        .map(|key| (key, ()))
+        .half_join(&customers_by_key, before, |&key, &(), &joined| (key, joined));
+
+    customers
+        .map(|(cc, acct, key)| (key, (cc, acct)))
+        .concat(&changes1.concat(&changes2).negate())
+        .map(|(_, (cc, acct))| (cc, acct as isize))
+        .join_core(&averages, |&cc, &acct, &pair| {
+            let acct : isize = acct;
+            let pair : DiffPair<isize, isize> = pair;
+            if acct > (pair.element1 / pair.element2) { Some((cc, acct)) } else { None }
+        })
+        .explode(|(cc, acct)| Some((cc, DiffPair::new(acct as isize, 1))))
+        .count_total()
+        .probe_with(probe);
+}
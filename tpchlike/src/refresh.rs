@@ -0,0 +1,101 @@
+//! The TPC-H refresh functions, as updates to the `orders` and `lineitem` relations.
+//!
+//! The refresh function RF1 inserts new orders and their line items, and RF2 deletes old orders
+//! and their line items. `dbgen -U <n>` produces the data for `n` pairs of refresh functions: the
+//! files `orders.tbl.u<k>` and `lineitem.tbl.u<k>` of rows RF1 inserts, and `delete.<k>` of the
+//! keys of the orders RF2 deletes, each about a thousandth of the orders of the initial load.
+//!
+//! The inserted rows are shared among workers as `load` shares the initial load. The deleted rows
+//! are those of the worker's share of `orders.tbl` and `lineitem.tbl`, so that each worker retracts
+//! exactly the rows it loaded.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use load::read_lines;
+use types::{Order, LineItem};
+
+/// The updates of one pair of refresh functions.
+pub struct Refresh {
+    /// Inserted and deleted orders.
+    pub orders: Vec<(Order, isize)>,
+    /// Inserted and deleted line items.
+    pub lineitems: Vec<(LineItem, isize)>,
+}
+
+impl Refresh {
+    /// The orders, as updates at `time`.
+    pub fn orders_at(&mut self, time: usize) -> Vec<(Order, usize, isize)> {
+        self.orders.drain(..).map(|(order, diff)| (order, time, diff)).collect()
+    }
+    /// The line items, as updates at `time`.
+    pub fn lineitems_at(&mut self, time: usize) -> Vec<(LineItem, usize, isize)> {
+        self.lineitems.drain(..).map(|(item, diff)| (item, time, diff)).collect()
+    }
+    /// The number of updates.
+    pub fn len(&self) -> usize {
+        self.orders.len() + self.lineitems.len()
+    }
+}
+
+/// Loads the worker's share of the first `sets` refresh sets.
+///
+/// The sets are returned in reverse order, so that they can be popped off of the end of the vector.
+pub fn load_refresh(prefix: &str, sets: usize, index: usize, peers: usize) -> Vec<Refresh> {
+
+    let mut result = Vec::with_capacity(sets);
+    let mut deleted = HashMap::new();
+
+    for set in 0 .. sets {
+
+        let mut refresh = Refresh { orders: Vec::new(), lineitems: Vec::new() };
+        read_lines(prefix, &format!("orders.tbl.u{}", set + 1), index, peers, |_, line| refresh.orders.push((Order::from(line), 1)));
+        read_lines(prefix, &format!("lineitem.tbl.u{}", set + 1), index, peers, |_, line| refresh.lineitems.push((LineItem::from(line), 1)));
+        result.push(refresh);
+
+        // Every worker reads all of the deleted keys, as any of them may have loaded the orders.
+        read_lines(prefix, &format!("delete.{}", set + 1), 0, 1, |_, line| {
+            let key = line.split('|').next().unwrap().trim().parse::<usize>().expect("malformed order key");
+            deleted.insert(key, set);
+        });
+    }
+
+    if !deleted.is_empty() {
+        read_lines(prefix, "orders.tbl", index, peers, |_, line| {
+            let order = Order::from(line);
+            if let Some(&set) = deleted.get(&order.order_key) {
+                result[set].orders.push((order, -1));
+            }
+        });
+        read_lines(prefix, "lineitem.tbl", index, peers, |_, line| {
+            let item = LineItem::from(line);
+            if let Some(&set) = deleted.get(&item.order_key) {
+                result[set].lineitems.push((item, -1));
+            }
+        });
+    }
+
+    result.reverse();
+    result
+}
+
+/// Paces refresh sets, so that each starts no sooner than an interval after the previous one.
+pub struct Pacing {
+    interval: Duration,
+    next: Instant,
+}
+
+impl Pacing {
+    /// Paces sets `interval` apart; a zero interval applies them as fast as they complete.
+    pub fn new(interval: Duration) -> Self {
+        Pacing { interval, next: Instant::now() }
+    }
+    /// Blocks until the next set should start.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if now < self.next {
+            ::std::thread::sleep(self.next - now);
+        }
+        self.next = ::std::cmp::max(now, self.next) + self.interval;
+    }
+}